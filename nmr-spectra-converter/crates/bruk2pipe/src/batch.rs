@@ -0,0 +1,68 @@
+//! Parallel conversion of many Bruker datasets.
+//!
+//! Thin wrapper around [`nmrpipe_io::batch::run_batch`]: each job reads a
+//! Bruker `fid`/`ser` file, runs [`bruker_to_pipe`] and writes the NMRPipe
+//! output file.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use nmrpipe_core::fdata::Fdata;
+use nmrpipe_io::batch::{run_batch, BatchJob, BatchProgress, BatchReport};
+use nmrpipe_io::writer::{write_nmrpipe_planes, WriteError};
+
+use crate::convert::{bruker_to_pipe, BrukerError, BrukerOptions};
+
+/// Convert a single Bruker fid/ser file to an NMRPipe file, returning the output header.
+pub fn convert_file(input: &Path, output: &Path, opts: &BrukerOptions) -> Result<Fdata, BrukerError> {
+    let mut reader = BufReader::new(File::open(input)?);
+    let result = bruker_to_pipe(&mut reader, opts)?;
+    write_nmrpipe_planes(output, &result.fdata, &result.planes).map_err(|e| match e {
+        WriteError::Io(e) => BrukerError::Io(e),
+        e => BrukerError::Io(std::io::Error::other(e)),
+    })?;
+    Ok(result.fdata)
+}
+
+/// Convert many Bruker files in parallel with the same options.
+///
+/// Useful when every input shares one acquisition setup (e.g. the CLI).
+/// `workers` = 0 uses one thread per CPU.
+pub fn convert_many<P>(
+    jobs: &[BatchJob],
+    opts: &BrukerOptions,
+    workers: usize,
+    progress: P,
+) -> BatchReport<Fdata, BrukerError>
+where
+    P: Fn(&BatchProgress) + Sync,
+{
+    convert_many_with(jobs, |_| Ok(opts.clone()), workers, progress)
+}
+
+/// Convert many Bruker datasets in parallel, building the options per job.
+///
+/// Bruker datasets normally each carry their own `acqus`, so `options_for`
+/// is called on the worker thread to derive the FDATA header and DMX
+/// parameters for that job.  An error from `options_for` fails only that job.
+pub fn convert_many_with<F, P>(
+    jobs: &[BatchJob],
+    options_for: F,
+    workers: usize,
+    progress: P,
+) -> BatchReport<Fdata, BrukerError>
+where
+    F: Fn(&BatchJob) -> Result<BrukerOptions, BrukerError> + Sync,
+    P: Fn(&BatchProgress) + Sync,
+{
+    run_batch(
+        jobs,
+        workers,
+        |job| {
+            let opts = options_for(job)?;
+            convert_file(&job.input, &job.output, &opts)
+        },
+        progress,
+    )
+}
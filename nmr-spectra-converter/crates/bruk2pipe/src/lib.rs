@@ -6,5 +6,7 @@
 pub mod ser2fid;
 pub mod dmx;
 pub mod convert;
+pub mod batch;
 
 pub use convert::{bruker_to_pipe, BrukerOptions, BrukerResult, BrukerError, BrukerType};
+pub use batch::{convert_file, convert_many, convert_many_with};
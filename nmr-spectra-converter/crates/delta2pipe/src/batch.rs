@@ -0,0 +1,41 @@
+//! Parallel conversion of many Delta files.
+//!
+//! Thin wrapper around [`nmrpipe_io::batch::run_batch`]: each job reads a
+//! `.jdf` file, runs [`delta_to_pipe`] and writes the NMRPipe output file.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use nmrpipe_core::fdata::Fdata;
+use nmrpipe_io::batch::{run_batch, BatchJob, BatchProgress, BatchReport};
+use nmrpipe_io::writer::{write_nmrpipe_planes, WriteError};
+
+use crate::convert::{delta_to_pipe, DeltaError, DeltaOptions};
+
+/// Convert a single Delta file to an NMRPipe file, returning the output header.
+pub fn convert_file(input: &Path, output: &Path, opts: &DeltaOptions) -> Result<Fdata, DeltaError> {
+    let mut reader = BufReader::new(File::open(input)?);
+    let result = delta_to_pipe(&mut reader, opts)?;
+    write_nmrpipe_planes(output, &result.fdata, &result.planes).map_err(|e| match e {
+        WriteError::Io(e) => DeltaError::Io(e),
+        e => DeltaError::Io(std::io::Error::other(e)),
+    })?;
+    Ok(result.fdata)
+}
+
+/// Convert many Delta files in parallel with the same options.
+///
+/// `workers` = 0 uses one thread per CPU.  `progress` is called after each
+/// file; failures are collected in the report rather than aborting the batch.
+pub fn convert_many<P>(
+    jobs: &[BatchJob],
+    opts: &DeltaOptions,
+    workers: usize,
+    progress: P,
+) -> BatchReport<Fdata, DeltaError>
+where
+    P: Fn(&BatchProgress) + Sync,
+{
+    run_batch(jobs, workers, |job| convert_file(&job.input, &job.output, opts), progress)
+}
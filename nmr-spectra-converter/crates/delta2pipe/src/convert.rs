@@ -9,7 +9,7 @@ use crate::submatrix;
 use nmrpipe_core::enums::*;
 use nmrpipe_core::fdata::*;
 use nmrpipe_io::dfcorrect::DFCorrector;
-use std::io::{self, Read};
+use std::io::{self, Read, Seek, SeekFrom};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -66,6 +66,8 @@ pub struct DeltaResult {
     pub applied_df_val: f32,
     /// Transition ratio.
     pub tr_val: f32,
+    /// Sample solvent from the `SOLVENT` parameter, if recorded.
+    pub solvent: Option<String>,
 }
 
 /// Stored parameters extracted from the Delta parameter section.
@@ -79,6 +81,7 @@ struct ExtractedParams {
     df_factors: [i32; JMAXDIM],
     tr_val: f32,
     nmrpipe_info: Option<String>,
+    solvent: Option<String>,
 }
 
 impl Default for ExtractedParams {
@@ -93,10 +96,53 @@ impl Default for ExtractedParams {
             df_factors: [0; JMAXDIM],
             tr_val: 0.0,
             nmrpipe_info: None,
+            solvent: None,
         }
     }
 }
 
+/// Parameter records of a parameter section, which starts with its own
+/// 16-byte header.
+fn parse_param_section(parm_buf: &[u8], swap: bool) -> Vec<DeltaParam> {
+    if parm_buf.len() < 16 {
+        return Vec::new();
+    }
+    let parm_hdr = DeltaParamHeader::parse(parm_buf, swap);
+    let size = parm_hdr.parm_size.max(0) as usize;
+    if size == 0 {
+        return Vec::new();
+    }
+    let record_start = 16usize;
+    let mut records = Vec::new();
+    for i in parm_hdr.lo_id..parm_hdr.hi_id {
+        let off = record_start + (i - parm_hdr.lo_id) as usize * size;
+        if off + size > parm_buf.len() {
+            break;
+        }
+        records.push(parse_param_record(&parm_buf[off..off + size], swap));
+    }
+    records
+}
+
+/// Read the header and parameter list of a Delta file without its data,
+/// e.g. to show how it was acquired.
+pub fn read_delta_params<R: Read + Seek>(input: &mut R) -> Result<(DeltaHeader, Vec<DeltaParam>), DeltaError> {
+    let mut hdr_buf = vec![0u8; DELTA_HDR_SIZE];
+    input
+        .read_exact(&mut hdr_buf)
+        .map_err(|_| DeltaError::InvalidHeader("file too small".into()))?;
+    let hdr = DeltaHeader::parse(&hdr_buf, cfg!(target_endian = "little"))
+        .map_err(|e| DeltaError::InvalidHeader(e.into()))?;
+    if hdr.param_length <= 0 {
+        return Ok((hdr, Vec::new()));
+    }
+    input.seek(SeekFrom::Start(hdr.param_start as u64))?;
+    let mut parm_buf = Vec::new();
+    input.take(hdr.param_length as u64).read_to_end(&mut parm_buf)?;
+    let params = parse_param_section(&parm_buf, hdr.needs_data_swap());
+    Ok((hdr, params))
+}
+
 /// Convert JEOL Delta data to NMRPipe format.
 ///
 /// Reads the entire input into memory, parses the header and parameters,
@@ -154,25 +200,15 @@ pub fn delta_to_pipe<R: Read>(input: &mut R, opts: &DeltaOptions) -> Result<Delt
     let word_size_out = 4i32; // always f32 output
     let smx_size = hdr.get_smx_sizes();
 
+    check_layout(&hdr, &in_size, &out_size, &smx_size, word_size_in)?;
+
     // ── Parse parameters ────────────────────────────────────────────────
 
     let mut params = ExtractedParams::default();
 
     if hdr.param_length > 0 && (hdr.param_start as usize) < all_data.len() {
-        let parm_buf = &all_data[hdr.param_start as usize..];
-        if parm_buf.len() >= 16 {
-            let parm_hdr = DeltaParamHeader::parse(parm_buf, swap_data);
-
-            let record_start = 16usize;
-            for i in parm_hdr.lo_id..parm_hdr.hi_id {
-                let off = record_start + (i - parm_hdr.lo_id) as usize * parm_hdr.parm_size as usize;
-                if off + parm_hdr.parm_size as usize > parm_buf.len() {
-                    break;
-                }
-                let rec = &parm_buf[off..off + parm_hdr.parm_size as usize];
-                let param = parse_param_record(rec, swap_data);
-                store_param(&mut params, &param);
-            }
+        for param in parse_param_section(&all_data[hdr.param_start as usize..], swap_data) {
+            store_param(&mut params, &param);
         }
     }
 
@@ -224,6 +260,14 @@ pub fn delta_to_pipe<R: Read>(input: &mut R, opts: &DeltaOptions) -> Result<Delt
         )));
     }
 
+    let needed = total_in_size * channel_count as i64 * word_size_in as i64;
+    if (data_len as i64) < needed {
+        return Err(DeltaError::InvalidHeader(format!(
+            "data section holds {} bytes but {} channel(s) of {} {}-byte points need {}",
+            data_len, channel_count, total_in_size, word_size_in, needed
+        )));
+    }
+
     let mut data_buf = all_data[data_start..data_start + data_len].to_vec();
 
     // Byte-swap if needed
@@ -287,21 +331,20 @@ pub fn delta_to_pipe<R: Read>(input: &mut R, opts: &DeltaOptions) -> Result<Delt
         let src_end = src_start + bytes_per_channel_in;
         let dest_start = ch * bytes_per_channel_out;
 
-        if src_end <= float_bytes.len() {
-            let _ = submatrix::smx2matrix(
-                &float_bytes[src_start..src_end],
-                &mut out_bytes[dest_start..dest_start + bytes_per_channel_out],
-                &out_size[..dim_count],
-                Some(&mat_x1[..dim_count]),
-                Some(&mat_xn[..dim_count]),
-                &in_size[..dim_count],
-                Some(&smx_x1[..dim_count]),
-                Some(&smx_xn[..dim_count]),
-                &smx_size[..dim_count],
-                word_size_out,
-                dim_count,
-            );
-        }
+        submatrix::smx2matrix(
+            &float_bytes[src_start..src_end],
+            &mut out_bytes[dest_start..dest_start + bytes_per_channel_out],
+            &out_size[..dim_count],
+            Some(&mat_x1[..dim_count]),
+            Some(&mat_xn[..dim_count]),
+            &in_size[..dim_count],
+            Some(&smx_x1[..dim_count]),
+            Some(&smx_xn[..dim_count]),
+            &smx_size[..dim_count],
+            word_size_out,
+            dim_count,
+        )
+        .map_err(DeltaError::Smx)?;
     }
 
     // Convert back to f32
@@ -369,6 +412,19 @@ pub fn delta_to_pipe<R: Read>(input: &mut R, opts: &DeltaOptions) -> Result<Delt
         }
     }
 
+    // ── Ascending frequency axis ────────────────────────────────────────
+    //
+    // Processed spectra may run from low to high frequency; NMRPipe data
+    // runs high → low, so every X vector is reversed (whatever the channel
+    // layout, the data is a sequence of X vectors of out_size[0] points).
+    // The origin is taken from the low end either way (see get_delta_orig).
+
+    if axis_ascending(&hdr, 0) {
+        for vector in result_data.chunks_mut(out_size[0] as usize) {
+            vector.reverse();
+        }
+    }
+
     // ── Build FDATA header ──────────────────────────────────────────────
 
     let mut fdata = Fdata::new();
@@ -541,9 +597,75 @@ pub fn delta_to_pipe<R: Read>(input: &mut R, opts: &DeltaOptions) -> Result<Delt
         stored_df_val,
         applied_df_val: df_val,
         tr_val,
+        solvent: params.solvent,
     })
 }
 
+// ─── Layout checks ──────────────────────────────────────────────────────────
+
+/// Reject layouts the conversion cannot reproduce faithfully, so that an
+/// unusual file gives an error instead of a garbled spectrum.
+fn check_layout(
+    hdr: &DeltaHeader,
+    in_size: &[i32; JMAXDIM],
+    out_size: &[i32; JMAXDIM],
+    smx_size: &[i32; JMAXDIM],
+    word_size: i32,
+) -> Result<(), DeltaError> {
+    if word_size != 4 && word_size != 8 {
+        return Err(DeltaError::Unsupported(format!(
+            "{}-byte samples (data type {})",
+            word_size, hdr.data_type
+        )));
+    }
+    for i in 0..hdr.dim_count as usize {
+        let dim = i + 1;
+        if smx_size[i] <= 1 {
+            return Err(DeltaError::Unsupported(format!("data format code {}", hdr.data_format)));
+        }
+        if !(JEOL_AXISTYPE_NONE..=JEOL_AXISTYPE_ENVELOPE).contains(&hdr.axis_type[i]) {
+            return Err(DeltaError::Unsupported(format!(
+                "axis type {} in dimension {}",
+                hdr.axis_type[i], dim
+            )));
+        }
+        let unit = &hdr.unit_list[i];
+        if unit.unit_type != JEOL_SIUNIT_NONE && !hdr.is_time_domain(i) && !hdr.is_ppm(i) && !hdr.is_hz(i) {
+            return Err(DeltaError::Unsupported(format!(
+                "axis units (SI type {}, exponent {}) in dimension {}",
+                unit.unit_type, unit.unit_exp, dim
+            )));
+        }
+        if in_size[i] < 1 || in_size[i] % smx_size[i] != 0 {
+            return Err(DeltaError::InvalidHeader(format!(
+                "dimension {} has {} points, not a whole number of {}-point submatrices",
+                dim, in_size[i], smx_size[i]
+            )));
+        }
+        if hdr.offset_start[i] < 0 || out_size[i] < 1 || hdr.offset_stop[i] >= in_size[i] {
+            return Err(DeltaError::InvalidHeader(format!(
+                "data range {}..{} in dimension {} lies outside its {} points",
+                hdr.offset_start[i], hdr.offset_stop[i], dim, in_size[i]
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// True for a frequency-domain dimension stored from low to high frequency
+fn axis_ascending(hdr: &DeltaHeader, dim: usize) -> bool {
+    (hdr.is_ppm(dim) || hdr.is_hz(dim))
+        && apply_unit_scale(hdr.axis_start[dim], &hdr.unit_list[dim])
+            < apply_unit_scale(hdr.axis_stop[dim], &hdr.unit_list[dim])
+}
+
+/// Low-frequency end of a frequency-domain axis, in its own units
+fn axis_low(hdr: &DeltaHeader, dim: usize) -> f64 {
+    let start = apply_unit_scale(hdr.axis_start[dim], &hdr.unit_list[dim]);
+    let stop = apply_unit_scale(hdr.axis_stop[dim], &hdr.unit_list[dim]);
+    start.min(stop)
+}
+
 // ─── Parameter extraction ───────────────────────────────────────────────────
 
 fn store_param(params: &mut ExtractedParams, param: &DeltaParam) {
@@ -596,6 +718,16 @@ fn store_param(params: &mut ExtractedParams, param: &DeltaParam) {
         return;
     }
 
+    if name == "SOLVENT" {
+        if let JVal::Str(s) = &param.val {
+            let s = s.trim();
+            if !s.is_empty() {
+                params.solvent = Some(s.to_string());
+            }
+        }
+        return;
+    }
+
     if name == "NMRPIPE_INFO" {
         if let JVal::Str(s) = &param.val {
             params.nmrpipe_info = Some(s.clone());
@@ -679,6 +811,11 @@ fn get_delta_car(
 ) -> f32 {
     if hdr.is_time_domain(dim) {
         let z = hdr.zero_point[dim] as f32;
+        // Indirect dimensions often record no zero point: their carrier is
+        // the Y_OFFSET (Z_OFFSET, ...) parameter in ppm
+        if z == 0.0 && dim > 0 && params.car[dim] != 0.0 {
+            return params.car[dim];
+        }
         sw * z / obs
     } else {
         params.car[dim]
@@ -700,11 +837,11 @@ fn get_delta_orig(
         // PPM calibration from time-domain info
         obs * car - sw * (size_f - mid) as f32 / size_f as f32
     } else if hdr.is_ppm(dim) {
-        let s = apply_unit_scale(hdr.axis_stop[dim], &hdr.unit_list[dim]);
+        let s = axis_low(hdr, dim);
         let n = if out_size == 0 { 1 } else { out_size };
         s as f32 * obs + 0.5 * sw / n as f32
     } else if hdr.is_hz(dim) {
-        let s = apply_unit_scale(hdr.axis_stop[dim], &hdr.unit_list[dim]);
+        let s = axis_low(hdr, dim);
         let n = if out_size == 0 { 1 } else { out_size };
         s as f32 + 0.5 * sw / n as f32
     } else {
@@ -928,4 +1065,132 @@ mod tests {
         let lab = format_label(&titles, 0, 2);
         assert_eq!(lab, "HN"); // 1H with 15N present → HN
     }
+
+    #[test]
+    fn test_indirect_carrier_falls_back_to_offset_parameter() {
+        let mut hdr = DeltaHeader::default();
+        for u in hdr.unit_list.iter_mut().take(2) {
+            u.unit_type = JEOL_SIUNIT_SECONDS;
+            u.unit_exp = 1;
+        }
+        let mut params = ExtractedParams::default();
+        params.car[0] = 5.0;
+        params.car[1] = 70.0;
+
+        // No zero point: Y_OFFSET is the F1 carrier, the direct dimension keeps delta2pipe's rule
+        assert_eq!(get_delta_car(&hdr, &params, 1, 20000.0, 100.0), 70.0);
+        assert_eq!(get_delta_car(&hdr, &params, 0, 6000.0, 400.0), 0.0);
+        hdr.zero_point[1] = 0.5;
+        assert_eq!(get_delta_car(&hdr, &params, 1, 20000.0, 100.0), 100.0);
+    }
+
+    /// One synthesized dimension: points, axis type, (SI unit type, exponent), start, stop
+    type Dim = (i32, i32, (i32, i32), f64, f64);
+
+    const SECONDS: (i32, i32) = (JEOL_SIUNIT_SECONDS, 1);
+    const PPM: (i32, i32) = (JEOL_SIUNIT_PPM, 1);
+    const HZ: (i32, i32) = (JEOL_SIUNIT_HZ, 1);
+
+    /// A minimal .jdf: big-endian header followed by `values` stored as
+    /// `data_type` in the given byte order (already in submatrix order)
+    fn synth_jdf(dims: &[Dim], data_type: i32, data_format: i32, little_endian: bool, values: &[f64]) -> Vec<u8> {
+        let mut buf = vec![0u8; DELTA_HDR_SIZE];
+        buf[..8].copy_from_slice(b"JEOL.NMR");
+        buf[8] = if little_endian { JEOL_LITTLE_ENDIAN } else { JEOL_BIG_ENDIAN } as u8;
+        buf[12] = dims.len() as u8;
+        buf[14] = ((data_type << 6) | data_format) as u8;
+        for (i, &(size, axis_type, (unit, exp), start, stop)) in dims.iter().enumerate() {
+            buf[24 + i] = axis_type as u8;
+            buf[32 + 2 * i] = (exp & 0x0F) as u8;
+            buf[33 + 2 * i] = unit as u8;
+            buf[176 + 4 * i..180 + 4 * i].copy_from_slice(&(size as u32).to_be_bytes());
+            buf[240 + 4 * i..244 + 4 * i].copy_from_slice(&(size as u32 - 1).to_be_bytes());
+            buf[272 + 8 * i..280 + 8 * i].copy_from_slice(&start.to_be_bytes());
+            buf[336 + 8 * i..344 + 8 * i].copy_from_slice(&stop.to_be_bytes());
+            buf[1064 + 8 * i..1072 + 8 * i].copy_from_slice(&400.0f64.to_be_bytes());
+        }
+        let data: Vec<u8> = values
+            .iter()
+            .flat_map(|&v| match (data_type == JEOL_DATATYPE_FLOAT, little_endian) {
+                (true, true) => (v as f32).to_le_bytes().to_vec(),
+                (true, false) => (v as f32).to_be_bytes().to_vec(),
+                (false, true) => v.to_le_bytes().to_vec(),
+                (false, false) => v.to_be_bytes().to_vec(),
+            })
+            .collect();
+        buf[1284..1288].copy_from_slice(&(DELTA_HDR_SIZE as u32).to_be_bytes());
+        buf[1288..1296].copy_from_slice(&(data.len() as u64).to_be_bytes());
+        buf.extend(data);
+        buf
+    }
+
+    fn convert(file: &[u8]) -> Result<DeltaResult, DeltaError> {
+        delta_to_pipe(&mut &file[..], &DeltaOptions::default())
+    }
+
+    #[test]
+    fn test_processed_spectrum_in_either_precision_and_order() {
+        let values: Vec<f64> = (0..16).map(f64::from).collect();
+        // Single precision, little-endian, stored from -1 to 15 ppm: reversed to run high → low
+        let ascending = [(16, JEOL_AXISTYPE_REAL, PPM, -1.0, 15.0)];
+        let out = convert(&synth_jdf(&ascending, JEOL_DATATYPE_FLOAT, JEOL_FORMAT_1D, true, &values)).unwrap();
+        assert_eq!(out.planes[0], (0..16).rev().map(|v| v as f32).collect::<Vec<_>>());
+        assert_eq!(out.fdata.data[FDQUADFLAG], QuadFlag::Real as i32 as f32);
+        // Double precision, big-endian, already high → low: same axis, data untouched
+        let descending = [(16, JEOL_AXISTYPE_REAL, PPM, 15.0, -1.0)];
+        let back = convert(&synth_jdf(&descending, JEOL_DATATYPE_DOUBLE, JEOL_FORMAT_1D, false, &values)).unwrap();
+        assert_eq!(back.planes[0], values.iter().map(|&v| v as f32).collect::<Vec<_>>());
+        for result in [&out, &back] {
+            // Origin at the low end (-1 ppm) plus half a point, in Hz
+            let orig = result.fdata.data[FDF2ORIG];
+            assert!((orig - (-400.0 + 0.5 * 6400.0 / 16.0)).abs() < 1e-3, "orig {}", orig);
+            assert_eq!(result.fdata.data[FDF2FTFLAG], 1.0);
+        }
+    }
+
+    #[test]
+    fn test_two_channel_fid_interleaves_rows() {
+        // R block then I block; the imaginary channel is negated unless the axis is flagged reversed
+        let values: Vec<f64> = (0..16).map(|v| v as f64).chain((0..16).map(|v| 100.0 + v as f64)).collect();
+        let fid = [(16, JEOL_AXISTYPE_COMPLEX, SECONDS, 0.0, 1.0)];
+        let out = convert(&synth_jdf(&fid, JEOL_DATATYPE_DOUBLE, JEOL_FORMAT_1D, false, &values)).unwrap();
+        assert_eq!(out.planes[0].len(), 32);
+        assert_eq!(&out.planes[0][..3], &[0.0, 1.0, 2.0]);
+        assert_eq!(&out.planes[0][16..19], &[-100.0, -101.0, -102.0]);
+        assert_eq!(out.fdata.data[FDQUADFLAG], QuadFlag::Complex as i32 as f32);
+    }
+
+    #[test]
+    fn test_2d_submatrices_are_untiled() {
+        // 8 × 8 real matrix in 4 × 4 submatrices, tiles and points X-fastest
+        let value = |x: usize, y: usize| (10 * y + x) as f64;
+        let mut tiled = Vec::new();
+        for (bx, by) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            for y in 0..4 {
+                for x in 0..4 {
+                    tiled.push(value(bx * 4 + x, by * 4 + y));
+                }
+            }
+        }
+        let dims = [(8, JEOL_AXISTYPE_REAL, HZ, 1000.0, -1000.0), (8, JEOL_AXISTYPE_REAL, HZ, 500.0, -500.0)];
+        let out = convert(&synth_jdf(&dims, JEOL_DATATYPE_FLOAT, JEOL_FORMAT_SMALL2D, true, &tiled)).unwrap();
+        let plane = &out.planes[0];
+        assert!((0..8).all(|y| (0..8).all(|x| plane[y * 8 + x] == value(x, y) as f32)));
+    }
+
+    #[test]
+    fn test_unsupported_layouts_are_rejected() {
+        let dims = [(16, JEOL_AXISTYPE_REAL, PPM, 15.0, -1.0)];
+        let values = vec![1.0; 16];
+        let err = |file: Vec<u8>| convert(&file).unwrap_err().to_string();
+
+        assert!(err(synth_jdf(&dims, JEOL_DATATYPE_FLOAT, 9, true, &values)).contains("data format code 9"));
+        assert!(err(synth_jdf(&dims, JEOL_DATATYPE_FLOAT, JEOL_FORMAT_1D, true, &values[..8])).contains("data section holds 32 bytes"));
+        let ragged = [(12, JEOL_AXISTYPE_REAL, PPM, 15.0, -1.0)];
+        assert!(err(synth_jdf(&ragged, JEOL_DATATYPE_FLOAT, JEOL_FORMAT_1D, true, &values[..12])).contains("8-point submatrices"));
+        let celsius = [(16, JEOL_AXISTYPE_REAL, (JEOL_SIUNIT_CELSIUS, 1), 0.0, 1.0)];
+        assert!(err(synth_jdf(&celsius, JEOL_DATATYPE_FLOAT, JEOL_FORMAT_1D, true, &values)).contains("axis units"));
+        let odd_axis = [(16, 9, PPM, 15.0, -1.0)];
+        assert!(err(synth_jdf(&odd_axis, JEOL_DATATYPE_FLOAT, JEOL_FORMAT_1D, true, &values)).contains("axis type 9"));
+    }
 }
@@ -5,5 +5,7 @@
 pub mod header;
 pub mod submatrix;
 pub mod convert;
+pub mod batch;
 
 pub use convert::*;
+pub use batch::{convert_file, convert_many};
@@ -0,0 +1,126 @@
+//! Human-readable comparison of two FDATA headers.
+//!
+//! Lists the parameters that decide how a file is read and displayed —
+//! dimension count and order, transpose and quadrature flags, and per axis
+//! the sizes, SW, OBS, ORIG, carrier, phases and apodization — that differ
+//! between two headers.  Used to find out why a converted file disagrees
+//! with a reference conversion.
+
+use crate::fdata::*;
+use crate::params::*;
+
+/// Global parameters compared by [`diff_headers`]
+const GLOBAL_PARAMS: &[&str] = &[
+    "FDDIMCOUNT", "FDDIMORDER1", "FDDIMORDER2", "FDDIMORDER3", "FDDIMORDER4", "FDTRANSPOSED",
+    "FD2DPHASE", "FDQUADFLAG", "FDPIPEFLAG", "FDCUBEFLAG", "FDREALSIZE", "FDFILECOUNT",
+    "FDDMXVAL", "FDDMXFLAG", "FDDELTATR", "FDNUSDIM",
+];
+
+/// Per-axis ND parameters compared by [`diff_headers`]
+const AXIS_PARAMS: &[&str] = &[
+    "SIZE", "TDSIZE", "FTSIZE", "SW", "OBS", "ORIG", "CAR", "CENTER", "OFFPPM", "FTFLAG",
+    "QUADFLAG", "AQSIGN", "UNITS", "P0", "P1", "APODCODE", "APODQ1", "APODQ2", "APODQ3", "LB",
+    "GB", "C1", "ZF", "X1", "XN",
+];
+
+const AXIS_NAMES: [&str; 4] = ["X", "Y", "Z", "A"];
+
+/// One parameter that differs between two headers
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderDifference {
+    /// `FDTRANSPOSED`, or axis and ND name such as `X SW`
+    pub name: String,
+    pub a: String,
+    pub b: String,
+}
+
+/// Whether two header values differ by more than float rounding
+fn values_differ(a: f32, b: f32) -> bool {
+    (a - b).abs() > 1e-6 * a.abs().max(b.abs())
+}
+
+/// Parameters that differ between `a` and `b`: the global ones, then each
+/// axis up to the larger dimension count, compared axis by axis (X, Y, …).
+pub fn diff_headers(a: &Fdata, b: &Fdata) -> Vec<HeaderDifference> {
+    let mut out = Vec::new();
+    for &name in GLOBAL_PARAMS {
+        let Some(loc) = fd_param_loc(name) else {
+            continue;
+        };
+        let (va, vb) = (a.data[loc], b.data[loc]);
+        if values_differ(va, vb) {
+            out.push(HeaderDifference { name: name.to_string(), a: va.to_string(), b: vb.to_string() });
+        }
+    }
+    let dims = a.dim_count().max(b.dim_count()).clamp(1, 4);
+    for (i, axis) in AXIS_NAMES.iter().enumerate().take(dims as usize) {
+        let dim = i as i32 + 1;
+        let (la, lb) = (a.get_parm_str(NDLABEL, dim), b.get_parm_str(NDLABEL, dim));
+        if la != lb {
+            out.push(HeaderDifference { name: format!("{} LABEL", axis), a: la, b: lb });
+        }
+        for &name in AXIS_PARAMS {
+            let Some(code) = nd_param_code(name) else {
+                continue;
+            };
+            let (va, vb) = (a.get_parm(code, dim), b.get_parm(code, dim));
+            if values_differ(va, vb) {
+                out.push(HeaderDifference { name: format!("{} {}", axis, name), a: va.to_string(), b: vb.to_string() });
+            }
+        }
+    }
+    out
+}
+
+/// [`diff_headers`] as an aligned text table headed by the two file names
+pub fn format_header_diff(differences: &[HeaderDifference], name_a: &str, name_b: &str) -> String {
+    if differences.is_empty() {
+        return format!("Headers of {} and {} agree\n", name_a, name_b);
+    }
+    let width = |f: &dyn Fn(&HeaderDifference) -> usize, title: usize| differences.iter().map(f).fold(title, usize::max);
+    let w_name = width(&|d| d.name.len(), "Parameter".len());
+    let w_a = width(&|d| d.a.chars().count(), name_a.chars().count());
+    let mut out = format!("{:<w_name$}  {:<w_a$}  {}\n", "Parameter", name_a, name_b);
+    for d in differences {
+        out.push_str(&format!("{:<w_name$}  {:<w_a$}  {}\n", d.name, d.a, d.b));
+    }
+    out.push_str(&format!("{} parameters differ\n", differences.len()));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> Fdata {
+        let mut fd = Fdata::new();
+        fd.init_default();
+        fd.set_dim_count(2);
+        fd.set_dim_spectral(CUR_XDIM, 2048, 12000.0, 600.13, 4800.0, 4.7, "1H", true);
+        fd.set_dim_spectral(CUR_YDIM, 256, 3000.0, 60.81, 1500.0, 120.0, "15N", true);
+        fd
+    }
+
+    #[test]
+    fn test_header_diff() {
+        let a = header();
+        assert!(diff_headers(&a, &a.clone()).is_empty());
+        assert_eq!(format_header_diff(&[], "a.fid", "b.fid"), "Headers of a.fid and b.fid agree\n");
+
+        let mut b = a.clone();
+        b.set_parm(NDSW, 11990.5, CUR_XDIM);
+        b.set_parm(NDP0, -12.5, CUR_YDIM);
+        b.set_parm_str(NDLABEL, "N15", CUR_YDIM);
+        b.set_transposed(true);
+        let diffs = diff_headers(&a, &b);
+        let names: Vec<&str> = diffs.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["FDTRANSPOSED", "X SW", "Y LABEL", "Y P0"]);
+        assert_eq!((diffs[1].a.as_str(), diffs[1].b.as_str()), ("12000", "11990.5"));
+
+        let text = format_header_diff(&diffs, "native.fid", "nmrpipe.fid");
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "Parameter     native.fid  nmrpipe.fid");
+        assert_eq!(lines[2], "X SW          12000       11990.5");
+        assert_eq!(lines[5], "4 parameters differ");
+    }
+}
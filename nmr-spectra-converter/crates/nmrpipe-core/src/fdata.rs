@@ -433,7 +433,7 @@ impl Fdata {
     /// The dimension order array maps current axes (X,Y,Z,A) to physical
     /// dimensions (F2, F1, F3, F4). For example, dimension order [2,1,3,4]
     /// means X-axis stores dimension 2, Y-axis stores dimension 1, etc.
-    fn get_loc(&self, parm: i32, dim_code: i32) -> Option<usize> {
+    pub(crate) fn get_loc(&self, parm: i32, dim_code: i32) -> Option<usize> {
         if parm > NDPARM {
             // Generalized ND parameter
             let nd_idx = (parm - NDPARM - 1) as usize;
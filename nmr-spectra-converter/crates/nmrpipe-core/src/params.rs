@@ -14,6 +14,101 @@ pub const CUR_ZDIM: i32 = 3;
 pub const CUR_ADIM: i32 = 4;
 pub const NULL_DIM: i32 = 0;
 
+// ─── Parameter names ────────────────────────────────────────────────────────
+
+/// Every fixed FDATA location by its `namelist.h` name.
+pub const FD_PARAM_NAMES: &[(&str, usize)] = &[
+    ("FDMAGIC", FDMAGIC), ("FDFLTFORMAT", FDFLTFORMAT), ("FDFLTORDER", FDFLTORDER), ("FDID", FDID),
+    ("FDSIZE", FDSIZE), ("FDREALSIZE", FDREALSIZE), ("FDSPECNUM", FDSPECNUM),
+    ("FDQUADFLAG", FDQUADFLAG), ("FD2DPHASE", FD2DPHASE), ("FDTRANSPOSED", FDTRANSPOSED),
+    ("FDDIMCOUNT", FDDIMCOUNT), ("FDDIMORDER", FDDIMORDER), ("FDDIMORDER1", FDDIMORDER1),
+    ("FDDIMORDER2", FDDIMORDER2), ("FDDIMORDER3", FDDIMORDER3), ("FDDIMORDER4", FDDIMORDER4),
+    ("FDNUSDIM", FDNUSDIM), ("FDPIPEFLAG", FDPIPEFLAG), ("FDCUBEFLAG", FDCUBEFLAG),
+    ("FDPIPECOUNT", FDPIPECOUNT), ("FDSLICECOUNT0", FDSLICECOUNT0),
+    ("FDSLICECOUNT1", FDSLICECOUNT1), ("FDFILECOUNT", FDFILECOUNT),
+    ("FDTHREADCOUNT", FDTHREADCOUNT), ("FDTHREADID", FDTHREADID), ("FDFIRSTPLANE", FDFIRSTPLANE),
+    ("FDLASTPLANE", FDLASTPLANE), ("FDPARTITION", FDPARTITION), ("FDPLANELOC", FDPLANELOC),
+    ("FDMAX", FDMAX), ("FDMIN", FDMIN), ("FDSCALEFLAG", FDSCALEFLAG), ("FDDISPMAX", FDDISPMAX),
+    ("FDDISPMIN", FDDISPMIN), ("FDPTHRESH", FDPTHRESH), ("FDNTHRESH", FDNTHRESH),
+    ("FDUSER1", FDUSER1), ("FDUSER2", FDUSER2), ("FDUSER3", FDUSER3), ("FDUSER4", FDUSER4),
+    ("FDUSER5", FDUSER5), ("FDUSER6", FDUSER6), ("FDLASTBLOCK", FDLASTBLOCK),
+    ("FDCONTBLOCK", FDCONTBLOCK), ("FDBASEBLOCK", FDBASEBLOCK), ("FDPEAKBLOCK", FDPEAKBLOCK),
+    ("FDBMAPBLOCK", FDBMAPBLOCK), ("FDHISTBLOCK", FDHISTBLOCK), ("FD1DBLOCK", FD1DBLOCK),
+    ("FDMONTH", FDMONTH), ("FDDAY", FDDAY), ("FDYEAR", FDYEAR), ("FDHOURS", FDHOURS),
+    ("FDMINS", FDMINS), ("FDSECS", FDSECS), ("FDMCFLAG", FDMCFLAG), ("FDNOISE", FDNOISE),
+    ("FDRANK", FDRANK), ("FDTEMPERATURE", FDTEMPERATURE), ("FDPRESSURE", FDPRESSURE),
+    ("FD2DVIRGIN", FD2DVIRGIN), ("FDTAU", FDTAU), ("FDDOMINFO", FDDOMINFO),
+    ("FDMETHINFO", FDMETHINFO), ("FDSCALE", FDSCALE), ("FDSCORE", FDSCORE), ("FDSCANS", FDSCANS),
+    ("FDSRCNAME", FDSRCNAME), ("FDUSERNAME", FDUSERNAME), ("FDOPERNAME", FDOPERNAME),
+    ("FDTITLE", FDTITLE), ("FDCOMMENT", FDCOMMENT), ("FDDMXVAL", FDDMXVAL),
+    ("FDDMXFLAG", FDDMXFLAG), ("FDDELTATR", FDDELTATR), ("FDF2LABEL", FDF2LABEL),
+    ("FDF2APOD", FDF2APOD), ("FDF2SW", FDF2SW), ("FDF2OBS", FDF2OBS), ("FDF2OBSMID", FDF2OBSMID),
+    ("FDF2ORIG", FDF2ORIG), ("FDF2UNITS", FDF2UNITS), ("FDF2QUADFLAG", FDF2QUADFLAG),
+    ("FDF2FTFLAG", FDF2FTFLAG), ("FDF2AQSIGN", FDF2AQSIGN), ("FDF2CAR", FDF2CAR),
+    ("FDF2CENTER", FDF2CENTER), ("FDF2OFFPPM", FDF2OFFPPM), ("FDF2P0", FDF2P0), ("FDF2P1", FDF2P1),
+    ("FDF2APODCODE", FDF2APODCODE), ("FDF2APODQ1", FDF2APODQ1), ("FDF2APODQ2", FDF2APODQ2),
+    ("FDF2APODQ3", FDF2APODQ3), ("FDF2LB", FDF2LB), ("FDF2GB", FDF2GB), ("FDF2GOFF", FDF2GOFF),
+    ("FDF2C1", FDF2C1), ("FDF2APODDF", FDF2APODDF), ("FDF2ZF", FDF2ZF), ("FDF2X1", FDF2X1),
+    ("FDF2XN", FDF2XN), ("FDF2FTSIZE", FDF2FTSIZE), ("FDF2TDSIZE", FDF2TDSIZE),
+    ("FDF1LABEL", FDF1LABEL), ("FDF1APOD", FDF1APOD), ("FDF1SW", FDF1SW), ("FDF1OBS", FDF1OBS),
+    ("FDF1OBSMID", FDF1OBSMID), ("FDF1ORIG", FDF1ORIG), ("FDF1UNITS", FDF1UNITS),
+    ("FDF1FTFLAG", FDF1FTFLAG), ("FDF1AQSIGN", FDF1AQSIGN), ("FDF1QUADFLAG", FDF1QUADFLAG),
+    ("FDF1CAR", FDF1CAR), ("FDF1CENTER", FDF1CENTER), ("FDF1OFFPPM", FDF1OFFPPM),
+    ("FDF1P0", FDF1P0), ("FDF1P1", FDF1P1), ("FDF1APODCODE", FDF1APODCODE),
+    ("FDF1APODQ1", FDF1APODQ1), ("FDF1APODQ2", FDF1APODQ2), ("FDF1APODQ3", FDF1APODQ3),
+    ("FDF1LB", FDF1LB), ("FDF1GB", FDF1GB), ("FDF1GOFF", FDF1GOFF), ("FDF1C1", FDF1C1),
+    ("FDF1ZF", FDF1ZF), ("FDF1X1", FDF1X1), ("FDF1XN", FDF1XN), ("FDF1FTSIZE", FDF1FTSIZE),
+    ("FDF1TDSIZE", FDF1TDSIZE), ("FDF3LABEL", FDF3LABEL), ("FDF3APOD", FDF3APOD),
+    ("FDF3OBS", FDF3OBS), ("FDF3OBSMID", FDF3OBSMID), ("FDF3SW", FDF3SW), ("FDF3ORIG", FDF3ORIG),
+    ("FDF3FTFLAG", FDF3FTFLAG), ("FDF3AQSIGN", FDF3AQSIGN), ("FDF3SIZE", FDF3SIZE),
+    ("FDF3QUADFLAG", FDF3QUADFLAG), ("FDF3UNITS", FDF3UNITS), ("FDF3P0", FDF3P0),
+    ("FDF3P1", FDF3P1), ("FDF3CAR", FDF3CAR), ("FDF3CENTER", FDF3CENTER),
+    ("FDF3OFFPPM", FDF3OFFPPM), ("FDF3APODCODE", FDF3APODCODE), ("FDF3APODQ1", FDF3APODQ1),
+    ("FDF3APODQ2", FDF3APODQ2), ("FDF3APODQ3", FDF3APODQ3), ("FDF3LB", FDF3LB), ("FDF3GB", FDF3GB),
+    ("FDF3GOFF", FDF3GOFF), ("FDF3C1", FDF3C1), ("FDF3ZF", FDF3ZF), ("FDF3X1", FDF3X1),
+    ("FDF3XN", FDF3XN), ("FDF3FTSIZE", FDF3FTSIZE), ("FDF3TDSIZE", FDF3TDSIZE),
+    ("FDF4LABEL", FDF4LABEL), ("FDF4APOD", FDF4APOD), ("FDF4OBS", FDF4OBS),
+    ("FDF4OBSMID", FDF4OBSMID), ("FDF4SW", FDF4SW), ("FDF4ORIG", FDF4ORIG),
+    ("FDF4FTFLAG", FDF4FTFLAG), ("FDF4AQSIGN", FDF4AQSIGN), ("FDF4SIZE", FDF4SIZE),
+    ("FDF4QUADFLAG", FDF4QUADFLAG), ("FDF4UNITS", FDF4UNITS), ("FDF4P0", FDF4P0),
+    ("FDF4P1", FDF4P1), ("FDF4CAR", FDF4CAR), ("FDF4CENTER", FDF4CENTER),
+    ("FDF4OFFPPM", FDF4OFFPPM), ("FDF4APODCODE", FDF4APODCODE), ("FDF4APODQ1", FDF4APODQ1),
+    ("FDF4APODQ2", FDF4APODQ2), ("FDF4APODQ3", FDF4APODQ3), ("FDF4LB", FDF4LB), ("FDF4GB", FDF4GB),
+    ("FDF4GOFF", FDF4GOFF), ("FDF4C1", FDF4C1), ("FDF4ZF", FDF4ZF), ("FDF4X1", FDF4X1),
+    ("FDF4XN", FDF4XN), ("FDF4FTSIZE", FDF4FTSIZE), ("FDF4TDSIZE", FDF4TDSIZE),
+];
+
+/// Generalized ND parameters by name, without the `ND` prefix, plus the
+/// short forms of the conversion command lines (`-xN`, `-xT`, `-xLAB`).
+pub const ND_PARAM_NAMES: &[(&str, i32)] = &[
+    ("SIZE", NDSIZE), ("APOD", NDAPOD), ("SW", NDSW), ("ORIG", NDORIG), ("OBS", NDOBS),
+    ("FTFLAG", NDFTFLAG), ("QUADFLAG", NDQUADFLAG), ("UNITS", NDUNITS), ("LABEL", NDLABEL),
+    ("LABEL1", NDLABEL1), ("LABEL2", NDLABEL2), ("P0", NDP0), ("P1", NDP1), ("CAR", NDCAR),
+    ("CENTER", NDCENTER), ("AQSIGN", NDAQSIGN), ("APODCODE", NDAPODCODE), ("APODQ1", NDAPODQ1),
+    ("APODQ2", NDAPODQ2), ("APODQ3", NDAPODQ3), ("C1", NDC1), ("ZF", NDZF), ("X1", NDX1),
+    ("XN", NDXN), ("OFFPPM", NDOFFPPM), ("FTSIZE", NDFTSIZE), ("TDSIZE", NDTDSIZE),
+    ("ACQMETHOD", NDACQMETHOD), ("FTDOMAIN", NDFTDOMAIN), ("LB", NDLB), ("GB", NDGB),
+    ("GOFF", NDGOFF), ("OBSMID", NDOBSMID),
+    // Command-line aliases
+    ("N", NDSIZE), ("T", NDTDSIZE), ("LAB", NDLABEL),
+];
+
+/// FDATA location of a fixed parameter name such as `FDF2SW` (case-insensitive).
+pub fn fd_param_loc(name: &str) -> Option<usize> {
+    FD_PARAM_NAMES
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|&(_, loc)| loc)
+}
+
+/// Generalized parameter code of a name such as `SW`, `NDSW` or `N`
+/// (case-insensitive).
+pub fn nd_param_code(name: &str) -> Option<i32> {
+    let upper = name.to_ascii_uppercase();
+    let find = |key: &str| ND_PARAM_NAMES.iter().find(|(n, _)| *n == key).map(|&(_, code)| code);
+    find(&upper).or_else(|| upper.strip_prefix("ND").and_then(find))
+}
+
 impl Fdata {
     // ─── Convenience getters ────────────────────────────────────────────
 
@@ -237,6 +332,73 @@ impl Fdata {
     pub fn get_max(&self) -> f32 {
         self.data[FDMAX]
     }
+
+    // ─── Lookup by name ─────────────────────────────────────────────────
+
+    /// Current dimension code (1-4) that holds physical dimension `phys`
+    /// (1 = F1, 2 = F2, 3 = F3, 4 = F4) under the dimension order.
+    pub fn dim_code_of(&self, phys: i32) -> Option<i32> {
+        (0..4)
+            .find(|&i| self.data[FDDIMORDER + i] as i32 == phys)
+            .map(|i| i as i32 + 1)
+    }
+
+    /// Dimension code of a prefix: `X`/`Y`/`Z`/`A` are current axes,
+    /// `F1`-`F4` physical dimensions.
+    fn dim_code_of_prefix(&self, prefix: &str) -> Option<i32> {
+        match prefix {
+            "X" => Some(CUR_XDIM),
+            "Y" => Some(CUR_YDIM),
+            "Z" => Some(CUR_ZDIM),
+            "A" => Some(CUR_ADIM),
+            "F1" | "F2" | "F3" | "F4" => self.dim_code_of(prefix[1..].parse().ok()?),
+            _ => None,
+        }
+    }
+
+    /// FDATA location of a parameter by a familiar name, case-insensitive:
+    ///
+    /// - fixed names, with or without `FD`: `FDF2SW`, `f2sw`, `FDSIZE`, `title`
+    /// - ND names on the X axis: `NDSW`, `SW`, `N`
+    /// - ND names with a dimension prefix, separated by `_`/`.` or glued on:
+    ///   `Y_SW`, `-ySW`, `F1.OBS`, `F1SIZE` (physical dimensions follow the
+    ///   dimension order, so they work on transposed data)
+    ///
+    /// A bare ND name wins over a glued prefix, so `XN` is `NDXN`; write
+    /// `X_N` for the X size.
+    pub fn param_loc_by_name(&self, name: &str) -> Option<usize> {
+        let upper = name.trim().trim_start_matches('-').to_ascii_uppercase();
+        if let Some((prefix, key)) = upper.split_once(['_', '.']) {
+            let dim = self.dim_code_of_prefix(prefix)?;
+            return self.get_loc(nd_param_code(key)?, dim);
+        }
+        if let Some(loc) = fd_param_loc(&upper).or_else(|| fd_param_loc(&format!("FD{}", upper))) {
+            return Some(loc);
+        }
+        if let Some(code) = nd_param_code(&upper) {
+            return self.get_loc(code, CUR_XDIM);
+        }
+        let split = if upper.starts_with('F') { 2 } else { 1 };
+        let (prefix, key) = (upper.get(..split)?, upper.get(split..)?);
+        self.get_loc(nd_param_code(key)?, self.dim_code_of_prefix(prefix)?)
+    }
+
+    /// Get a numeric parameter by name (see [`Fdata::param_loc_by_name`]);
+    /// `None` if the name is unknown.
+    pub fn get_param_by_name(&self, name: &str) -> Option<f32> {
+        self.param_loc_by_name(name).map(|loc| self.data[loc])
+    }
+
+    /// Set a numeric parameter by name; returns `false` if the name is unknown.
+    pub fn set_param_by_name(&mut self, name: &str, val: f32) -> bool {
+        match self.param_loc_by_name(name) {
+            Some(loc) => {
+                self.data[loc] = val;
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -266,4 +428,41 @@ mod tests {
         fd.set_comment("This is a longer comment string");
         assert_eq!(fd.get_comment(), "This is a longer comment string");
     }
+
+    #[test]
+    fn test_param_by_name() {
+        let mut fd = Fdata::new();
+        fd.init_default();
+        fd.set_dim_count(2);
+        fd.set_dim_spectral(CUR_XDIM, 2048, 12000.0, 600.13, 4800.0, 4.7, "1H", true);
+        fd.set_dim_spectral(CUR_YDIM, 256, 3000.0, 60.81, 1500.0, 120.0, "15N", true);
+
+        // Fixed names, with or without FD, any case
+        assert_eq!(fd.get_param_by_name("FDF2SW"), Some(12000.0));
+        assert_eq!(fd.get_param_by_name("f2sw"), Some(12000.0));
+        assert_eq!(fd.get_param_by_name("FDSPECNUM"), Some(256.0));
+        assert_eq!(fd.get_param_by_name("dimcount"), Some(2.0));
+
+        // ND names default to X; prefixes pick the axis
+        assert_eq!(fd.get_param_by_name("NDSW"), Some(12000.0));
+        assert_eq!(fd.get_param_by_name("obs"), Some(600.13));
+        assert_eq!(fd.get_param_by_name("Y_SW"), Some(3000.0));
+        assert_eq!(fd.get_param_by_name("-yN"), Some(256.0));
+        assert_eq!(fd.get_param_by_name("F1.OBS"), Some(60.81));
+        assert_eq!(fd.get_param_by_name("F1SIZE"), Some(256.0));
+        assert_eq!(fd.param_loc_by_name("XN"), Some(FDF2XN));
+        assert_eq!(fd.param_loc_by_name("X_N"), Some(FDSIZE));
+
+        // Physical dimensions follow a transpose, current axes do not
+        fd.data[FDDIMORDER1] = 1.0;
+        fd.data[FDDIMORDER2] = 2.0;
+        assert_eq!(fd.get_param_by_name("F2_SW"), Some(12000.0));
+        assert_eq!(fd.get_param_by_name("xSW"), Some(3000.0));
+
+        assert!(fd.set_param_by_name("ySW", 9000.0));
+        assert_eq!(fd.get_sw(CUR_YDIM), 9000.0);
+        assert_eq!(fd.get_param_by_name("NOSUCH"), None);
+        assert_eq!(fd.get_param_by_name("Q_SW"), None);
+        assert!(!fd.set_param_by_name("", 1.0));
+    }
 }
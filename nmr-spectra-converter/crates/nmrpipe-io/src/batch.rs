@@ -0,0 +1,254 @@
+//! Parallel batch runner shared by the vendor converters.
+//!
+//! `delta2pipe::convert_many` and `bruk2pipe::convert_many` both hand their
+//! per-dataset conversion closure to [`run_batch`], which spreads the jobs
+//! over a pool of scoped worker threads, reports progress after each job,
+//! and collects every outcome (in input order) into a [`BatchReport`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// One dataset to convert: vendor input → NMRPipe output file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchJob {
+    /// Input dataset (JEOL .jdf file, Bruker fid/ser file, …).
+    pub input: PathBuf,
+    /// NMRPipe file to write.
+    pub output: PathBuf,
+}
+
+impl BatchJob {
+    pub fn new(input: impl Into<PathBuf>, output: impl Into<PathBuf>) -> Self {
+        Self {
+            input: input.into(),
+            output: output.into(),
+        }
+    }
+}
+
+/// Progress notification sent after each job finishes.
+#[derive(Debug)]
+pub struct BatchProgress<'a> {
+    /// Number of jobs finished so far (including this one).
+    pub completed: usize,
+    /// Total number of jobs in the batch.
+    pub total: usize,
+    /// The job that just finished.
+    pub job: &'a BatchJob,
+    /// Error message if the job failed.
+    pub error: Option<String>,
+}
+
+/// Outcome of a single job.
+#[derive(Debug)]
+pub struct BatchOutcome<T, E> {
+    pub job: BatchJob,
+    pub result: Result<T, E>,
+}
+
+/// Aggregated result of a batch run, in the same order as the input jobs.
+#[derive(Debug)]
+pub struct BatchReport<T, E> {
+    pub outcomes: Vec<BatchOutcome<T, E>>,
+}
+
+impl<T, E: fmt::Display> BatchReport<T, E> {
+    /// Number of jobs that converted successfully.
+    pub fn succeeded(&self) -> usize {
+        self.outcomes.iter().filter(|o| o.result.is_ok()).count()
+    }
+
+    /// Jobs that failed, with their errors.
+    pub fn failures(&self) -> impl Iterator<Item = (&BatchJob, &E)> {
+        self.outcomes
+            .iter()
+            .filter_map(|o| o.result.as_ref().err().map(|e| (&o.job, e)))
+    }
+
+    /// True if every job succeeded.
+    pub fn is_ok(&self) -> bool {
+        self.outcomes.iter().all(|o| o.result.is_ok())
+    }
+
+    /// One line per failed job (`input: error`), empty if all succeeded.
+    pub fn error_summary(&self) -> String {
+        self.failures()
+            .map(|(job, e)| format!("{}: {}", job.input.display(), e))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Output file stem for a Bruker experiment directory: the directory name,
+/// prefixed with the sample directory when it is a bare experiment number
+/// (`sampleA/10` → `sampleA_10`), so experiments of different samples with
+/// the same number do not overwrite each other.
+pub fn bruker_output_name(dir: &Path) -> String {
+    let name = dir
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "data".to_string());
+    if name.chars().all(|c| c.is_ascii_digit()) {
+        if let Some(sample) = dir.parent().and_then(|p| p.file_name()) {
+            return format!("{}_{}", sample.to_string_lossy(), name);
+        }
+    }
+    name
+}
+
+/// Error listing every output path claimed by more than one job.  Checked
+/// before a batch starts, since parallel jobs writing the same file would
+/// silently clobber each other.
+pub fn check_unique_outputs(jobs: &[BatchJob]) -> Result<(), String> {
+    let mut by_output: HashMap<&Path, Vec<&Path>> = HashMap::new();
+    for job in jobs {
+        by_output.entry(&job.output).or_default().push(&job.input);
+    }
+    let mut clashes: Vec<String> = by_output
+        .into_iter()
+        .filter(|(_, inputs)| inputs.len() > 1)
+        .map(|(output, inputs)| {
+            let inputs: Vec<String> = inputs.iter().map(|p| p.display().to_string()).collect();
+            format!("{} <- {}", output.display(), inputs.join(", "))
+        })
+        .collect();
+    if clashes.is_empty() {
+        return Ok(());
+    }
+    clashes.sort();
+    Err(format!("several inputs map to the same output file:\n{}", clashes.join("\n")))
+}
+
+/// Default worker count: the number of available CPUs.
+pub fn default_workers() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// Run `convert` over all `jobs` on up to `workers` threads (0 = one per CPU).
+///
+/// `progress` is called once per finished job, serialised so that
+/// `completed` increases monotonically.  A failing job never aborts the
+/// rest of the batch; its error is kept in the report.
+pub fn run_batch<T, E, F, P>(
+    jobs: &[BatchJob],
+    workers: usize,
+    convert: F,
+    progress: P,
+) -> BatchReport<T, E>
+where
+    T: Send,
+    E: fmt::Display + Send,
+    F: Fn(&BatchJob) -> Result<T, E> + Sync,
+    P: Fn(&BatchProgress) + Sync,
+{
+    let total = jobs.len();
+    let workers = if workers == 0 { default_workers() } else { workers };
+    let workers = workers.min(total).max(1);
+
+    let next = AtomicUsize::new(0);
+    let completed = Mutex::new(0usize);
+    let results: Mutex<Vec<Option<Result<T, E>>>> =
+        Mutex::new((0..total).map(|_| None).collect());
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let idx = next.fetch_add(1, Ordering::SeqCst);
+                if idx >= total {
+                    break;
+                }
+                let job = &jobs[idx];
+                let result = convert(job);
+                let error = result.as_ref().err().map(|e| e.to_string());
+                results.lock().unwrap()[idx] = Some(result);
+
+                let mut done = completed.lock().unwrap();
+                *done += 1;
+                progress(&BatchProgress {
+                    completed: *done,
+                    total,
+                    job,
+                    error,
+                });
+            });
+        }
+    });
+
+    let outcomes = results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .zip(jobs)
+        .map(|(result, job)| BatchOutcome {
+            job: job.clone(),
+            result: result.expect("every batch job runs exactly once"),
+        })
+        .collect();
+
+    BatchReport { outcomes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_batch_keeps_order_and_errors() {
+        let jobs: Vec<BatchJob> = (0..8)
+            .map(|i| BatchJob::new(format!("in{}", i), format!("out{}", i)))
+            .collect();
+        let calls = AtomicUsize::new(0);
+
+        let report = run_batch(
+            &jobs,
+            3,
+            |job| {
+                let n: usize = job.input.to_string_lossy()[2..].parse().unwrap();
+                if n % 3 == 0 {
+                    Err(format!("bad {}", n))
+                } else {
+                    Ok(n * 10)
+                }
+            },
+            |p| {
+                assert_eq!(p.total, 8);
+                calls.fetch_add(1, Ordering::SeqCst);
+            },
+        );
+
+        assert_eq!(calls.load(Ordering::SeqCst), 8);
+        assert_eq!(report.outcomes.len(), 8);
+        assert_eq!(report.succeeded(), 5);
+        assert!(!report.is_ok());
+        assert_eq!(*report.outcomes[4].result.as_ref().unwrap(), 40);
+        assert_eq!(report.failures().count(), 3);
+        assert!(report.error_summary().contains("in6: bad 6"));
+    }
+
+    #[test]
+    fn test_output_names_and_clashes() {
+        assert_eq!(bruker_output_name(Path::new("data/sampleA/10")), "sampleA_10");
+        assert_eq!(bruker_output_name(Path::new("data/cosy_exp")), "cosy_exp");
+
+        let jobs = [
+            BatchJob::new("a/x.jdf", "out/x.fid"),
+            BatchJob::new("b/y.jdf", "out/y.fid"),
+            BatchJob::new("c/x.jdf", "out/x.fid"),
+        ];
+        let err = check_unique_outputs(&jobs).unwrap_err();
+        assert!(err.contains("out/x.fid <- a/x.jdf, c/x.jdf"));
+        assert!(check_unique_outputs(&jobs[..2]).is_ok());
+    }
+
+    #[test]
+    fn test_run_batch_empty() {
+        let report = run_batch::<(), String, _, _>(&[], 0, |_| Ok(()), |_| {});
+        assert!(report.is_ok());
+        assert_eq!(report.succeeded(), 0);
+    }
+}
@@ -69,6 +69,48 @@ pub fn int3_to_float(buf: &[u8], swap: bool) -> Vec<f32> {
         .collect()
 }
 
+/// Byte order of the 4-byte words of an NMRPipe file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    /// Byte order of this platform
+    pub fn native() -> Self {
+        if is_big_endian() {
+            Self::Big
+        } else {
+            Self::Little
+        }
+    }
+
+    /// The other byte order
+    pub fn swapped(self) -> Self {
+        match self {
+            Self::Little => Self::Big,
+            Self::Big => Self::Little,
+        }
+    }
+
+    /// Decode a float stored in this byte order.
+    pub fn read_f32(self, bytes: [u8; 4]) -> f32 {
+        match self {
+            Self::Little => f32::from_le_bytes(bytes),
+            Self::Big => f32::from_be_bytes(bytes),
+        }
+    }
+
+    /// Encode a float in this byte order.
+    pub fn write_f32(self, val: f32) -> [u8; 4] {
+        match self {
+            Self::Little => val.to_le_bytes(),
+            Self::Big => val.to_be_bytes(),
+        }
+    }
+}
+
 /// Detect platform byte order: returns `true` if big-endian.
 pub fn is_big_endian() -> bool {
     cfg!(target_endian = "big")
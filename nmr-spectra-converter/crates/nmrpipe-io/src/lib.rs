@@ -1,11 +1,13 @@
 //! NMRPipe I/O utilities: binary reading/writing, byte‐swapping, type conversion,
-//! and digital‐filter correction.
+//! digital‐filter correction, and parallel batch conversion.
 
+pub mod batch;
 pub mod byteswap;
 pub mod dfcorrect;
 pub mod reader;
 pub mod writer;
 
+pub use batch::*;
 pub use byteswap::*;
 pub use dfcorrect::*;
 pub use reader::*;
@@ -5,6 +5,8 @@ use nmrpipe_core::fdata::*;
 use std::io::{self, Read, Seek, SeekFrom};
 use thiserror::Error;
 
+use crate::byteswap::ByteOrder;
+
 #[derive(Error, Debug)]
 pub enum ReadError {
     #[error("I/O error: {0}")]
@@ -15,11 +17,71 @@ pub enum ReadError {
     Truncated { expected: usize, got: usize },
 }
 
-/// Read an NMRPipe FDATA header from a reader.
+/// Header word `loc` of `buf` decoded in `order`
+fn header_word(buf: &[u8], loc: usize, order: ByteOrder) -> f32 {
+    order.read_f32([buf[loc * 4], buf[loc * 4 + 1], buf[loc * 4 + 2], buf[loc * 4 + 3]])
+}
+
+/// Whether `buf` decoded in `order` looks like an FDATA header: zero
+/// FDMAGIC, 1-4 dimensions and a whole, positive X size.
+fn plausible_header(buf: &[u8], order: ByteOrder) -> bool {
+    let dims = header_word(buf, FDDIMCOUNT, order);
+    let size = header_word(buf, FDSIZE, order);
+    header_word(buf, FDMAGIC, order) == 0.0
+        && (1.0..=4.0).contains(&dims)
+        && dims.fract() == 0.0
+        && size >= 1.0
+        && size <= (1 << 28) as f32
+        && size.fract() == 0.0
+}
+
+/// Byte order of an FDATA header.
+///
+/// Decided by the FDFLTORDER constant (2.345); when that word is damaged
+/// or zero, by the FDFLTFORMAT IEEE constant, and failing that by which
+/// order gives a plausible header (FDMAGIC 0, dimension count, X size).
+pub fn detect_byte_order(buf: &[u8]) -> Result<ByteOrder, ReadError> {
+    if buf.len() < FDATA_BYTES {
+        return Err(ReadError::InvalidHeader("buffer too small for FDATA header".to_string()));
+    }
+    let native = ByteOrder::native();
+    let orders = [native, native.swapped()];
+    let unique = |test: &dyn Fn(ByteOrder) -> bool| {
+        let matching: Vec<ByteOrder> = orders.iter().copied().filter(|&o| test(o)).collect();
+        match matching[..] {
+            [order] => Some(order),
+            _ => None,
+        }
+    };
+    unique(&|o| (header_word(buf, FDFLTORDER, o) - FD_ORDER_CONS).abs() < 0.001)
+        .or_else(|| unique(&|o| header_word(buf, FDFLTFORMAT, o) == FD_IEEE_CONS as f32))
+        .or_else(|| unique(&|o| plausible_header(buf, o)))
+        .ok_or_else(|| ReadError::InvalidHeader("byte order could not be determined".to_string()))
+}
+
+/// Decode an FDATA header in whichever byte order it was written.
+///
+/// The header comes back in native order; a damaged FDFLTORDER word is
+/// repaired so the header is valid when written again.
+pub fn decode_fdata_header(buf: &[u8]) -> Result<(Fdata, ByteOrder), ReadError> {
+    let order = detect_byte_order(buf)?;
+    let mut fdata = Fdata::new();
+    for (i, val) in fdata.data.iter_mut().enumerate() {
+        *val = header_word(buf, i, order);
+    }
+    fdata.data[FDFLTORDER] = FD_ORDER_CONS;
+    Ok((fdata, order))
+}
+
+/// Read an NMRPipe FDATA header from a reader.  The status is
+/// [`HdrStatus::Swapped`] when the file's byte order is not this platform's,
+/// so its data must be swapped too.
 pub fn read_fdata_header<R: Read>(reader: &mut R) -> Result<(Fdata, HdrStatus), ReadError> {
     let mut buf = vec![0u8; FDATA_BYTES];
     reader.read_exact(&mut buf)?;
-    Fdata::from_bytes(&buf).map_err(|e| ReadError::InvalidHeader(e.to_string()))
+    let (fdata, order) = decode_fdata_header(&buf)?;
+    let status = if order == ByteOrder::native() { HdrStatus::Ok } else { HdrStatus::Swapped };
+    Ok((fdata, status))
 }
 
 /// Read spectral data as f32 values from a reader.
@@ -74,3 +136,72 @@ pub fn read_nmrpipe_file<R: Read>(reader: &mut R) -> Result<(Fdata, Vec<f32>), R
     let data = read_float_data(reader, total, needs_swap)?;
     Ok((fdata, data))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer::{write_nmrpipe_file_ordered, PipeWriter};
+    use std::io::Cursor;
+
+    fn header() -> Fdata {
+        let mut fd = Fdata::new();
+        fd.init_default();
+        fd.data[FDSIZE] = 4.0;
+        fd.data[FDSPECNUM] = 1.0;
+        fd.data[FDF2SW] = 12000.0;
+        fd
+    }
+
+    fn file(order: ByteOrder) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_nmrpipe_file_ordered(&mut buf, &header(), &[1.0, -2.5, 3.25, 1e6], order).unwrap();
+        buf
+    }
+
+    /// Zero header word `loc` of an encoded file
+    fn clear_word(buf: &mut [u8], loc: usize) {
+        buf[loc * 4..loc * 4 + 4].fill(0);
+    }
+
+    #[test]
+    fn test_read_either_byte_order() {
+        for order in [ByteOrder::Little, ByteOrder::Big] {
+            let buf = file(order);
+            assert_eq!(detect_byte_order(&buf).unwrap(), order);
+            let (fd, data) = read_nmrpipe_file(&mut Cursor::new(&buf)).unwrap();
+            assert_eq!(fd.data[FDF2SW], 12000.0);
+            assert_eq!(data, [1.0, -2.5, 3.25, 1e6]);
+            let (_, status) = read_fdata_header(&mut Cursor::new(&buf)).unwrap();
+            let expected = if order == ByteOrder::native() { HdrStatus::Ok } else { HdrStatus::Swapped };
+            assert_eq!(status, expected);
+        }
+
+        // A pipe stream from a big-endian system reads the same
+        let mut pipe = PipeWriter::with_byte_order(Vec::new(), ByteOrder::Big);
+        pipe.write_header(&header()).unwrap();
+        pipe.write_vector(&[1.0, -2.5, 3.25, 1e6]).unwrap();
+        assert_eq!(pipe.into_inner(), file(ByteOrder::Big));
+    }
+
+    #[test]
+    fn test_damaged_byte_order_word() {
+        // FDFLTORDER lost: FDFLTFORMAT decides, and the header is repaired
+        let mut buf = file(ByteOrder::Big);
+        clear_word(&mut buf, FDFLTORDER);
+        assert_eq!(detect_byte_order(&buf).unwrap(), ByteOrder::Big);
+        let (fd, _) = decode_fdata_header(&buf).unwrap();
+        assert_eq!(fd.data[FDFLTORDER], FD_ORDER_CONS);
+        assert_eq!(fd.data[FDSIZE], 4.0);
+
+        // Both constants lost: only one order gives a plausible header
+        clear_word(&mut buf, FDFLTFORMAT);
+        assert_eq!(detect_byte_order(&buf).unwrap(), ByteOrder::Big);
+        let (_, data) = read_nmrpipe_file(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(data, [1.0, -2.5, 3.25, 1e6]);
+
+        // Not a header at all, or too short
+        assert!(matches!(detect_byte_order(&[0x5Au8; FDATA_BYTES]), Err(ReadError::InvalidHeader(_))));
+        assert!(detect_byte_order(&buf[..100]).is_err());
+        assert!(matches!(read_fdata_header(&mut Cursor::new(&buf[..100])), Err(ReadError::Io(_))));
+    }
+}
@@ -1,13 +1,29 @@
 //! NMRPipe data writer: write header + spectral data to files or streams.
+//!
+//! Besides the raw header/vector functions used by the converters,
+//! [`PipeSpectrum`] describes a complete 1D or 2D spectrum (real and
+//! imaginary parts, axes, title) and builds its FDATA header, so a
+//! spectrum processed elsewhere can be written as a file NMRPipe and
+//! NMRDraw open directly, or as a `%03d` plane series.
 
+use nmrpipe_core::enums::{AxisUnit, Phase2D, QuadFlag};
 use nmrpipe_core::fdata::*;
-use std::io::{self, Write};
+use nmrpipe_core::params::{CUR_XDIM, CUR_YDIM};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
+use crate::byteswap::ByteOrder;
+
 #[derive(Error, Debug)]
 pub enum WriteError {
     #[error("I/O error: {0}")]
     Io(#[from] io::Error),
+    #[error("Invalid spectrum: {0}")]
+    InvalidSpectrum(String),
+    #[error("Not a plane series template (no %d or %0Nd): {0}")]
+    Template(String),
 }
 
 /// Write an NMRPipe FDATA header to a writer.
@@ -19,11 +35,28 @@ pub fn write_fdata_header<W: Write>(writer: &mut W, fdata: &Fdata) -> Result<(),
 
 /// Write spectral data as f32 values to a writer (native endian).
 pub fn write_float_data<W: Write>(writer: &mut W, data: &[f32]) -> Result<(), WriteError> {
-    // Write as native-endian bytes
+    write_float_data_ordered(writer, data, ByteOrder::native())
+}
+
+/// Write an FDATA header in `order`, e.g. for a big-endian workstation.
+/// NMRPipe detects the order from FDFLTORDER, so either order reads back.
+pub fn write_fdata_header_ordered<W: Write>(
+    writer: &mut W,
+    fdata: &Fdata,
+    order: ByteOrder,
+) -> Result<(), WriteError> {
+    write_float_data_ordered(writer, &fdata.data, order)
+}
+
+/// Write spectral data as f32 values in `order`.
+pub fn write_float_data_ordered<W: Write>(
+    writer: &mut W,
+    data: &[f32],
+    order: ByteOrder,
+) -> Result<(), WriteError> {
     let mut buf = vec![0u8; data.len() * 4];
-    for (i, &val) in data.iter().enumerate() {
-        let bytes = val.to_ne_bytes();
-        buf[i * 4..i * 4 + 4].copy_from_slice(&bytes);
+    for (chunk, &val) in buf.chunks_exact_mut(4).zip(data) {
+        chunk.copy_from_slice(&order.write_f32(val));
     }
     writer.write_all(&buf)?;
     Ok(())
@@ -35,35 +68,269 @@ pub fn write_nmrpipe_file<W: Write>(
     fdata: &Fdata,
     data: &[f32],
 ) -> Result<(), WriteError> {
-    write_fdata_header(writer, fdata)?;
-    write_float_data(writer, data)?;
+    write_nmrpipe_file_ordered(writer, fdata, data, ByteOrder::native())
+}
+
+/// Write a complete NMRPipe file in `order`.
+pub fn write_nmrpipe_file_ordered<W: Write>(
+    writer: &mut W,
+    fdata: &Fdata,
+    data: &[f32],
+    order: ByteOrder,
+) -> Result<(), WriteError> {
+    write_fdata_header_ordered(writer, fdata, order)?;
+    write_float_data_ordered(writer, data, order)?;
+    Ok(())
+}
+
+/// Write a converter result (header + one or more data planes) to a file,
+/// creating the parent directory if needed.
+pub fn write_nmrpipe_planes(
+    path: &Path,
+    fdata: &Fdata,
+    planes: &[Vec<f32>],
+) -> Result<(), WriteError> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    let mut out = BufWriter::new(File::create(path)?);
+    write_fdata_header(&mut out, fdata)?;
+    for plane in planes {
+        write_float_data(&mut out, plane)?;
+    }
+    out.flush()?;
     Ok(())
 }
 
+// ─── Complete spectra ───────────────────────────────────────────────────────
+
+/// Vectors of one part of a [`PipeSpectrum`], one per F1 point.
+pub type PipeVectors = Vec<Vec<f32>>;
+
+/// One dimension of a [`PipeSpectrum`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipeAxis {
+    /// Spectral width in Hz.
+    pub sw_hz: f64,
+    /// Observe frequency in MHz.
+    pub obs_mhz: f64,
+    /// Frequency of the last (lowest) point in Hz, as FDxxORIG.
+    pub orig_hz: f64,
+    /// Axis label, usually the nucleus ("1H", "13C").
+    pub label: String,
+    /// Frequency domain (`true`) or time domain.
+    pub freq: bool,
+}
+
+/// A complete 1D or 2D spectrum to write as NMRPipe data.
+///
+/// Vectors run along X (F2, the direct dimension); a 2D spectrum holds one
+/// vector per F1 point.  A complex X vector is written as its real block
+/// followed by its imaginary block, and a complex F1 dimension as
+/// alternating real and imaginary vectors (States), as NMRPipe stores them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipeSpectrum {
+    pub x: PipeAxis,
+    /// F1 axis of a 2D spectrum.
+    pub y: Option<PipeAxis>,
+    /// Real part: one vector per F1 point (a single one for 1D).
+    pub real: PipeVectors,
+    /// X-imaginary part, shaped like `real`; empty for real X data.
+    pub imag: PipeVectors,
+    /// F1-imaginary vectors (X real part, X imaginary part), shaped like
+    /// `real` and `imag`, for a complex F1 dimension.
+    pub y_imag: Option<(PipeVectors, PipeVectors)>,
+    /// Title stored in the header (up to 60 characters).
+    pub title: String,
+}
+
+impl PipeSpectrum {
+    /// Points per X vector (complex points for complex data).
+    pub fn x_size(&self) -> usize {
+        self.real.first().map_or(0, Vec::len)
+    }
+
+    fn x_complex(&self) -> bool {
+        !self.imag.is_empty()
+    }
+
+    /// Check that every part has the same shape.
+    pub fn validate(&self) -> Result<(), WriteError> {
+        let bad = |what: &str| Err(WriteError::InvalidSpectrum(what.to_string()));
+        let n = self.x_size();
+        if n == 0 {
+            return bad("no data");
+        }
+        if self.y.is_none() && self.real.len() != 1 {
+            return bad("a 1D spectrum holds exactly one vector");
+        }
+        let shaped = |part: &[Vec<f32>]| part.len() == self.real.len() && part.iter().all(|v| v.len() == n);
+        if !shaped(&self.real) {
+            return bad("vectors differ in length");
+        }
+        if self.x_complex() && !shaped(&self.imag) {
+            return bad("imaginary part does not match the real part");
+        }
+        if let Some((re, im)) = &self.y_imag {
+            if self.y.is_none() || !shaped(re) || (self.x_complex() && !shaped(im)) {
+                return bad("F1-imaginary part does not match the real part");
+            }
+        }
+        Ok(())
+    }
+
+    /// The vectors in file order, each with its imaginary block appended.
+    pub fn vectors(&self) -> Vec<Vec<f32>> {
+        let vector = |re: &[f32], im: Option<&Vec<f32>>| {
+            let mut v = re.to_vec();
+            if let Some(im) = im.filter(|_| self.x_complex()) {
+                v.extend_from_slice(im);
+            }
+            v
+        };
+        let mut out = Vec::new();
+        for (i, re) in self.real.iter().enumerate() {
+            out.push(vector(re, self.imag.get(i)));
+            if let Some((y_re, y_im)) = &self.y_imag {
+                out.push(vector(&y_re[i], y_im.get(i)));
+            }
+        }
+        out
+    }
+
+    /// The FDATA header describing this spectrum.
+    pub fn fdata(&self) -> Fdata {
+        let mut fd = Fdata::new();
+        fd.init_default();
+        let x_complex = self.x_complex();
+        let y_complex = self.y_imag.is_some();
+        fd.set_dim_count(if self.y.is_some() { 2 } else { 1 });
+        set_pipe_axis(&mut fd, CUR_XDIM, &self.x, self.x_size(), self.x_size(), x_complex);
+        match &self.y {
+            // The Y size (FDSPECNUM) counts the stored vectors
+            Some(y) => {
+                let points = self.real.len();
+                let stored = if y_complex { 2 * points } else { points };
+                set_pipe_axis(&mut fd, CUR_YDIM, y, stored, points, y_complex);
+                fd.set_phase2d(if y_complex { Phase2D::States } else { Phase2D::Magnitude });
+            }
+            None => fd.data[FDSPECNUM] = 1.0,
+        }
+        fd.data[FDREALSIZE] = self.x_size() as f32;
+        fd.data[FDQUADFLAG] = if x_complex || y_complex { QuadFlag::Complex } else { QuadFlag::Real } as i32 as f32;
+        let (min, max) = self
+            .real
+            .iter()
+            .flatten()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+        if min <= max {
+            fd.set_min_max(min, max);
+            fd.data[FDDISPMIN] = min;
+            fd.data[FDDISPMAX] = max;
+        }
+        fd.set_title(&self.title);
+        fd
+    }
+}
+
+/// Spectral parameters of dimension `dim` holding `size` stored values for
+/// `points` (complex) points, with the carrier at the centre point and the
+/// units NMRDraw labels the axis with.
+fn set_pipe_axis(fd: &mut Fdata, dim: i32, axis: &PipeAxis, size: usize, points: usize, complex: bool) {
+    let car_ppm = if axis.obs_mhz > 0.0 { (axis.orig_hz + axis.sw_hz / 2.0) / axis.obs_mhz } else { 0.0 };
+    fd.set_dim_spectral(dim, size as i32, axis.sw_hz, axis.obs_mhz, axis.orig_hz, car_ppm, &axis.label, complex);
+    fd.set_parm(NDCENTER, (points / 2 + 1) as f32, dim);
+    fd.set_parm(NDFTFLAG, if axis.freq { 1.0 } else { 0.0 }, dim);
+    let (size_parm, units) = if axis.freq { (NDFTSIZE, AxisUnit::Ppm) } else { (NDTDSIZE, AxisUnit::Sec) };
+    fd.set_parm(size_parm, points as f32, dim);
+    fd.set_parm(NDUNITS, units as i32 as f32, dim);
+}
+
+/// Write `spectrum` as a single NMRPipe file (`.fid`, `.ft1`, `.ft2`),
+/// creating the parent directory if needed.
+pub fn write_spectrum_file(path: &Path, spectrum: &PipeSpectrum) -> Result<(), WriteError> {
+    spectrum.validate()?;
+    write_nmrpipe_planes(path, &spectrum.fdata(), &spectrum.vectors())
+}
+
+/// File `index` (1-based) of a plane series named by `template`, whose
+/// file name holds `%d` or a zero-padded `%0Nd` (`test%03d.ft2`).
+pub fn series_path(template: &Path, index: usize) -> Result<PathBuf, WriteError> {
+    let invalid = || WriteError::Template(template.display().to_string());
+    let name = template.file_name().and_then(|n| n.to_str()).ok_or_else(invalid)?;
+    let start = name.find('%').ok_or_else(invalid)?;
+    let len = name[start + 1..].find('d').ok_or_else(invalid)?;
+    let spec = &name[start + 1..start + 1 + len];
+    if !spec.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let width: usize = if spec.is_empty() { 0 } else { spec.parse().map_err(|_| invalid())? };
+    let file = format!("{}{:0width$}{}", &name[..start], index, &name[start + 2 + len..], width = width);
+    Ok(template.with_file_name(file))
+}
+
+/// Write `spectrum` as a plane series, one file per vector, each with the
+/// full header, named from `template` (`test%03d.ft2`).  Returns the files
+/// in order.
+pub fn write_spectrum_series(template: &Path, spectrum: &PipeSpectrum) -> Result<Vec<PathBuf>, WriteError> {
+    spectrum.validate()?;
+    let vectors = spectrum.vectors();
+    let mut fdata = spectrum.fdata();
+    fdata.data[FDFILECOUNT] = vectors.len() as f32;
+    let mut paths = Vec::with_capacity(vectors.len());
+    for (i, vector) in vectors.iter().enumerate() {
+        let path = series_path(template, i + 1)?;
+        fdata.data[FDFIRSTPLANE] = (i + 1) as f32;
+        fdata.data[FDLASTPLANE] = (i + 1) as f32;
+        write_nmrpipe_planes(&path, &fdata, std::slice::from_ref(vector))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
 /// Write NMRPipe data as a pipe stream (header + vectors one at a time).
 pub struct PipeWriter<W: Write> {
     writer: W,
     header_written: bool,
+    order: ByteOrder,
 }
 
 impl<W: Write> PipeWriter<W> {
     pub fn new(writer: W) -> Self {
+        Self::with_byte_order(writer, ByteOrder::native())
+    }
+
+    /// A pipe writer producing the stream in `order`.
+    pub fn with_byte_order(writer: W, order: ByteOrder) -> Self {
         Self {
             writer,
             header_written: false,
+            order,
         }
     }
 
     /// Write the header (must be called first).
     pub fn write_header(&mut self, fdata: &Fdata) -> Result<(), WriteError> {
-        write_fdata_header(&mut self.writer, fdata)?;
+        write_fdata_header_ordered(&mut self.writer, fdata, self.order)?;
         self.header_written = true;
         Ok(())
     }
 
     /// Write a single vector of spectral data (one row/column).
     pub fn write_vector(&mut self, data: &[f32]) -> Result<(), WriteError> {
-        write_float_data(&mut self.writer, data)
+        write_float_data_ordered(&mut self.writer, data, self.order)
+    }
+
+    /// Write a complete spectrum: its header, then every vector.
+    pub fn write_spectrum(&mut self, spectrum: &PipeSpectrum) -> Result<(), WriteError> {
+        spectrum.validate()?;
+        self.write_header(&spectrum.fdata())?;
+        for vector in spectrum.vectors() {
+            self.write_vector(&vector)?;
+        }
+        Ok(())
     }
 
     /// Flush the writer.
@@ -77,3 +344,108 @@ impl<W: Write> PipeWriter<W> {
         self.writer
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::{read_fdata_header, read_float_data};
+    use nmrpipe_core::enums::HdrStatus;
+    use std::io::Cursor;
+
+    fn axis(label: &str, freq: bool) -> PipeAxis {
+        PipeAxis { sw_hz: 6000.0, obs_mhz: 600.0, orig_hz: -1200.0, label: label.to_string(), freq }
+    }
+
+    /// 2 × 3 hypercomplex spectrum with distinct values in every quadrant
+    fn hypercomplex() -> PipeSpectrum {
+        let block = |base: f32| vec![vec![base, base + 1.0, base + 2.0], vec![base + 3.0, base + 4.0, base + 5.0]];
+        PipeSpectrum {
+            x: axis("1H", true),
+            y: Some(axis("13C", true)),
+            real: block(0.0),
+            imag: block(10.0),
+            y_imag: Some((block(20.0), block(30.0))),
+            title: "HSQC".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_spectrum_header_and_vector_layout() {
+        // 1D complex: real block then imaginary block
+        let fid = PipeSpectrum {
+            x: axis("1H", false),
+            y: None,
+            real: vec![vec![1.0, 2.0, 3.0, 4.0]],
+            imag: vec![vec![-1.0, -2.0, -3.0, -4.0]],
+            y_imag: None,
+            title: String::new(),
+        };
+        let mut pipe = PipeWriter::new(Vec::new());
+        pipe.write_spectrum(&fid).unwrap();
+        let buf = pipe.into_inner();
+        let mut cursor = Cursor::new(&buf);
+        let (fd, status) = read_fdata_header(&mut cursor).unwrap();
+        assert_eq!(status, HdrStatus::Ok);
+        assert_eq!(fd.dim_count(), 1);
+        assert_eq!(fd.get_size(CUR_XDIM), 4);
+        assert!(fd.is_complex(CUR_XDIM) && !fd.is_freq(CUR_XDIM));
+        assert_eq!(fd.get_sw(CUR_XDIM), 6000.0);
+        assert_eq!(fd.get_orig(CUR_XDIM), -1200.0);
+        assert!((fd.get_car(CUR_XDIM) - 3.0).abs() < 1e-6);
+        assert_eq!(fd.get_parm_str(NDLABEL, CUR_XDIM), "1H");
+        assert_eq!(read_float_data(&mut cursor, 8, false).unwrap(), [1.0, 2.0, 3.0, 4.0, -1.0, -2.0, -3.0, -4.0]);
+
+        // 2D hypercomplex: F1 real and imaginary vectors alternate
+        let spectrum = hypercomplex();
+        let fd = spectrum.fdata();
+        assert_eq!(fd.dim_count(), 2);
+        assert_eq!(fd.data[FDSPECNUM], 4.0);
+        assert_eq!(fd.get_size(CUR_YDIM), 4);
+        assert_eq!(fd.get_parm(NDCENTER, CUR_YDIM), 2.0);
+        assert!(fd.is_complex(CUR_YDIM) && fd.is_freq(CUR_YDIM));
+        assert_eq!(fd.get_phase2d(), Phase2D::States);
+        assert_eq!(fd.get_parm_str(NDLABEL, CUR_YDIM), "13C");
+        assert_eq!(fd.get_title(), "HSQC");
+        assert_eq!((fd.get_min(), fd.get_max()), (0.0, 5.0));
+        let vectors = spectrum.vectors();
+        assert_eq!(vectors[0], [0.0, 1.0, 2.0, 10.0, 11.0, 12.0]);
+        assert_eq!(vectors[1], [20.0, 21.0, 22.0, 30.0, 31.0, 32.0]);
+        assert_eq!(vectors[2], [3.0, 4.0, 5.0, 13.0, 14.0, 15.0]);
+
+        // Mismatched parts are refused
+        let mut ragged = hypercomplex();
+        ragged.imag[1].pop();
+        assert!(matches!(ragged.validate(), Err(WriteError::InvalidSpectrum(_))));
+    }
+
+    #[test]
+    fn test_spectrum_file_and_plane_series() {
+        let dir = std::env::temp_dir().join(format!("nmrpipe_writer_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let spectrum = hypercomplex();
+
+        let file = dir.join("hsqc.ft2");
+        write_spectrum_file(&file, &spectrum).unwrap();
+        let bytes = fs::read(&file).unwrap();
+        assert_eq!(bytes.len(), FDATA_BYTES + 4 * 6 * 4);
+        let mut cursor = Cursor::new(&bytes);
+        let (fd, _) = read_fdata_header(&mut cursor).unwrap();
+        assert_eq!(fd.data[FDSPECNUM], 4.0);
+        let data = read_float_data(&mut cursor, 24, false).unwrap();
+        assert_eq!(data, spectrum.vectors().concat());
+
+        assert_eq!(series_path(Path::new("a/test%03d.ft2"), 7).unwrap(), Path::new("a/test007.ft2"));
+        assert_eq!(series_path(Path::new("p%d.fid"), 12).unwrap(), Path::new("p12.fid"));
+        assert!(matches!(series_path(Path::new("plain.ft2"), 1), Err(WriteError::Template(_))));
+
+        let planes = write_spectrum_series(&dir.join("hsqc%03d.ft2"), &spectrum).unwrap();
+        assert_eq!(planes.len(), 4);
+        assert_eq!(planes[3], dir.join("hsqc004.ft2"));
+        let mut cursor = Cursor::new(fs::read(&planes[1]).unwrap());
+        let (fd, _) = read_fdata_header(&mut cursor).unwrap();
+        assert_eq!(fd.data[FDFILECOUNT], 4.0);
+        assert_eq!(read_float_data(&mut cursor, 6, false).unwrap(), spectrum.vectors()[1]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
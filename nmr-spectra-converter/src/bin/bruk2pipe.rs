@@ -3,8 +3,10 @@
 use clap::Parser;
 use std::fs::File;
 use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
 
 use nmrpipe_core::fdata::Fdata;
+use nmrpipe_io::batch::{self, BatchJob};
 
 #[derive(Parser)]
 #[command(
@@ -13,14 +15,22 @@ use nmrpipe_core::fdata::Fdata;
     about = "Convert Bruker SER/FID files to NMRPipe format"
 )]
 struct Cli {
-    /// Input Bruker SER/FID file
-    #[arg(short, long)]
-    r#in: String,
+    /// Input Bruker SER/FID file(s); several files are converted in parallel into --out-dir
+    #[arg(short, long, num_args = 1.., required = true)]
+    r#in: Vec<String>,
 
     /// Output NMRPipe file (or - for stdout)
     #[arg(short, long, default_value = "-")]
     out: String,
 
+    /// Output directory for batch mode (one <sample>_<expno>.fid per input)
+    #[arg(long)]
+    out_dir: Option<String>,
+
+    /// Number of parallel workers in batch mode (0 = one per CPU)
+    #[arg(short, long, default_value_t = 0)]
+    jobs: usize,
+
     /// Bruker type: amx, dmx, or am
     #[arg(short = 't', long, default_value = "amx")]
     bruk_type: String,
@@ -119,7 +129,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         verbose: cli.verb,
     };
 
-    let mut input = BufReader::new(File::open(&cli.r#in)?);
+    if cli.r#in.len() > 1 || cli.out_dir.is_some() {
+        return run_batch(&cli, &opts);
+    }
+
+    let mut input = BufReader::new(File::open(&cli.r#in[0])?);
     let result = bruk2pipe::bruker_to_pipe(&mut input, &opts)?;
 
     if cli.verb {
@@ -131,6 +145,46 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Convert every input into `--out-dir` in parallel, reporting progress on stderr.
+///
+/// Bruker inputs are all called `fid` or `ser`, so outputs are named after
+/// the experiment directory that contains them (`<sample>_<expno>.fid`).
+fn run_batch(cli: &Cli, opts: &bruk2pipe::BrukerOptions) -> Result<(), Box<dyn std::error::Error>> {
+    if cli.out != "-" {
+        return Err("--out names a single output file; use --out-dir with several inputs".into());
+    }
+    let out_dir = PathBuf::from(cli.out_dir.as_deref().unwrap_or("."));
+    let jobs: Vec<BatchJob> = cli
+        .r#in
+        .iter()
+        .map(|input| {
+            let path = Path::new(input);
+            let name = match path.parent() {
+                Some(dir) if dir.file_name().is_some() => batch::bruker_output_name(dir),
+                _ => path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "data".to_string()),
+            };
+            BatchJob::new(input, out_dir.join(format!("{}.fid", name)))
+        })
+        .collect();
+    batch::check_unique_outputs(&jobs)?;
+
+    let report = bruk2pipe::convert_many(&jobs, opts, cli.jobs, |p| {
+        match &p.error {
+            None => eprintln!("[{}/{}] {} -> {}", p.completed, p.total, p.job.input.display(), p.job.output.display()),
+            Some(e) => eprintln!("[{}/{}] {} FAILED: {}", p.completed, p.total, p.job.input.display(), e),
+        }
+    });
+
+    eprintln!("Converted {}/{} files.", report.succeeded(), jobs.len());
+    if !report.is_ok() {
+        return Err(format!("some conversions failed:\n{}", report.error_summary()).into());
+    }
+    Ok(())
+}
+
 fn write_output(
     out_path: &str,
     fdata: &Fdata,
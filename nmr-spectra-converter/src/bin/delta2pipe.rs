@@ -3,8 +3,10 @@
 use clap::Parser;
 use std::fs::File;
 use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
 
 use nmrpipe_core::fdata::Fdata;
+use nmrpipe_io::batch::{self, BatchJob};
 
 #[derive(Parser)]
 #[command(
@@ -13,14 +15,22 @@ use nmrpipe_core::fdata::Fdata;
     about = "Convert JEOL Delta (.jdf) files to NMRPipe format"
 )]
 struct Cli {
-    /// Input Delta file
-    #[arg(short, long)]
-    r#in: String,
+    /// Input Delta file(s); several files are converted in parallel into --out-dir
+    #[arg(short, long, num_args = 1.., required = true)]
+    r#in: Vec<String>,
 
     /// Output NMRPipe file (or - for stdout)
     #[arg(short, long, default_value = "-")]
     out: String,
 
+    /// Output directory for batch mode (one <name>.fid per input)
+    #[arg(long)]
+    out_dir: Option<String>,
+
+    /// Number of parallel workers in batch mode (0 = one per CPU)
+    #[arg(short, long, default_value_t = 0)]
+    jobs: usize,
+
     /// Convert only real data (no imaginary)
     #[arg(long, default_value_t = false)]
     real_only: bool,
@@ -53,7 +63,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         verbose: cli.verb,
     };
 
-    let mut input = BufReader::new(File::open(&cli.r#in)?);
+    if cli.r#in.len() > 1 || cli.out_dir.is_some() {
+        return run_batch(&cli, &opts);
+    }
+
+    let mut input = BufReader::new(File::open(&cli.r#in[0])?);
     let result = delta2pipe::delta_to_pipe(&mut input, &opts)?;
 
     if cli.verb {
@@ -72,6 +86,39 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Convert every input into `--out-dir` in parallel, reporting progress on stderr.
+fn run_batch(cli: &Cli, opts: &delta2pipe::DeltaOptions) -> Result<(), Box<dyn std::error::Error>> {
+    if cli.out != "-" {
+        return Err("--out names a single output file; use --out-dir with several inputs".into());
+    }
+    let out_dir = PathBuf::from(cli.out_dir.as_deref().unwrap_or("."));
+    let jobs: Vec<BatchJob> = cli
+        .r#in
+        .iter()
+        .map(|input| {
+            let stem = Path::new(input)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "data".to_string());
+            BatchJob::new(input, out_dir.join(format!("{}.fid", stem)))
+        })
+        .collect();
+    batch::check_unique_outputs(&jobs)?;
+
+    let report = delta2pipe::convert_many(&jobs, opts, cli.jobs, |p| {
+        match &p.error {
+            None => eprintln!("[{}/{}] {} -> {}", p.completed, p.total, p.job.input.display(), p.job.output.display()),
+            Some(e) => eprintln!("[{}/{}] {} FAILED: {}", p.completed, p.total, p.job.input.display(), e),
+        }
+    });
+
+    eprintln!("Converted {}/{} files.", report.succeeded(), jobs.len());
+    if !report.is_ok() {
+        return Err(format!("some conversions failed:\n{}", report.error_summary()).into());
+    }
+    Ok(())
+}
+
 fn write_output(
     out_path: &str,
     fdata: &Fdata,
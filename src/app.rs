@@ -2,25 +2,60 @@
 ///
 /// Ties together all subsystems: data, pipeline, GUI, and logging.
 
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
 use eframe::egui;
+use serde_json::json;
 
-use crate::data::spectrum::SpectrumData;
+use crate::data::{bruker, nmrpipe_format};
+use crate::data::session::Session;
+use crate::data::spectrum::{Nucleus, Provenance, SpectrumData, VendorFormat};
+use crate::data::storage::{StoragePrecision, StoredSpectrum};
+use crate::error::{NmrError, NmrResult};
 use crate::gui::contour_view::{self, ContourViewState};
 use crate::gui::conversion_dialog::{
     self, ConversionAction, ConversionDialogState,
 };
 use crate::gui::export_dialog::{self, ExportAction, ExportDialogState, ExportSettings};
 use crate::gui::export_tab::{self, ExportTabAction, ExportTabState};
-use crate::gui::phase_dialog::{self, PhaseAction, PhaseDialogState};
-use crate::gui::pipeline_panel::{self, PipelineAction, PipelinePanelState};
-use crate::gui::spectrum_view::{self, SpectrumViewState};
+use crate::gui::phase_dialog::{self, Phase2dState, PhaseAction, PhaseDialogState};
+use crate::gui::plot_scene;
+use crate::gui::pulse_program_view;
+use crate::gui::preferences::{self, AutoReportSettings, Preferences, PreferencesAction, PreferencesDialogState};
+use crate::gui::pipeline_panel::{self, ComparisonMode, PipelineAction, PipelinePanelState};
+use crate::gui::spectrum_view::{self, AxisDirection, IntegralLabels, OverlayTrace, ResolutionPreview, SpectrumViewState};
 use crate::gui::theme::{self, AppTheme, ThemeColors};
 use crate::gui::toolbar::{self, ToolbarAction};
-use crate::log::reproducibility::ReproLog;
+use crate::log::mirror::{self, LogMirror};
+use crate::log::reproducibility::{LogEntry, OpMetrics, ReproLog};
+use crate::log::teaching::{self, Explanation};
+use crate::pipeline::batch::{self, BatchConversion};
+use crate::pipeline::bundle;
+use crate::pipeline::cache::ConversionCache;
+use crate::pipeline::command;
 use crate::pipeline::conversion;
-use crate::pipeline::processing::{self, ProcessingOp};
+use crate::pipeline::external;
+use crate::pipeline::ipc::{self, IpcCommand, IpcRequest, IpcServer};
+use crate::pipeline::library::{ReferenceLibrary, ReferenceSpectrum};
+use crate::pipeline::loader::{self, LoadResult, PendingLoad};
+use crate::pipeline::open::{self, Candidate, OpenTarget};
+use crate::pipeline::processing::{
+    self, Ft2dMode, IntegralBaseline, IntensityMode, NonFiniteFix, NonFiniteReport, PeakSign, ProcessingOp,
+};
+use crate::pipeline::profiles;
+use crate::pipeline::relink;
+use crate::pipeline::script::{self, ScriptProcessing};
+use crate::pipeline::template::{self, PipelineTemplate, TemplateRun};
+use crate::pipeline::validation::{self, ValidationReport};
+use crate::pipeline::workdir::{self, WorkDir, WorkDirSettings};
+
+/// Write a project file as pretty-printed JSON
+fn write_project(path: &std::path::Path, save: &ProjectSave) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(save).map_err(|e| format!("Serialize error: {}", e))?;
+    std::fs::write(path, json).map_err(|e| format!("Write error: {}", e))
+}
 
 /// Which domain tab the user is viewing
 #[derive(Clone, Copy, PartialEq)]
@@ -28,6 +63,8 @@ enum DomainTab {
     TimeDomain,
     FrequencyDomain,
     Export,
+    /// Read-only pulse program listing
+    PulseProgram,
 }
 
 /// Serializable project state for save/load
@@ -38,20 +75,335 @@ struct ProjectSave {
     is_frequency_domain: bool,
     // Annotations
     peaks: Vec<[f64; 2]>,
+    /// ppm of the peaks flagged as exchangeable by a D₂O shake
+    #[serde(default)]
+    exchangeable: Vec<f64>,
+    /// Peaks labelled as common impurities: (ppm, names)
+    #[serde(default)]
+    common_impurities: Vec<(f64, String)>,
+    /// Carbon types of the peaks of a DEPT-135 / APT spectrum
+    #[serde(default)]
+    carbon_types: Vec<processing::CarbonPeak>,
+    #[serde(default)]
+    intensity_mode: IntensityMode,
     multiplets: Vec<crate::pipeline::processing::Multiplet>,
     integrations: Vec<(f64, f64, f64)>,
     integration_reference_h: f64,
+    /// Absent in projects saved before local baselines: raw sums
+    #[serde(default)]
+    integral_baseline: Option<IntegralBaseline>,
+    #[serde(default)]
+    integral_labels: IntegralLabels,
+    #[serde(default)]
+    integral_trace: bool,
     j_couplings: Vec<(f64, f64, f64, f64)>,
     baseline_points: Vec<[f64; 2]>,
+    #[serde(default)]
+    cross_peaks: Vec<processing::CrossPeak>,
+    /// Region the cross-peak volumes were integrated over
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    volume_region: Option<processing::VolumeRegion>,
+    /// Overlaid library references with their legend settings
+    #[serde(default)]
+    overlays: Vec<OverlayTrace>,
+    /// Absent in projects saved before the axis option: the preference
+    #[serde(default)]
+    axis_direction: Option<AxisDirection>,
     // Metadata
     theme: String,
     sample_name: String,
+    /// Bundled raw data, relative to the project file (`.nmrpkg` bundles)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    raw_data: Option<std::path::PathBuf>,
+    /// Source path relative to the project file, tried before the absolute one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source_relative: Option<std::path::PathBuf>,
+}
+
+/// Per-document state of a session spectrum while another one is active
+struct ParkedDocument {
+    fid_snapshot: Option<SpectrumData>,
+    domain_tab: DomainTab,
+    undo_stack: Vec<(ProcessingOp, StoredSpectrum)>,
+    redo_stack: Vec<(ProcessingOp, StoredSpectrum)>,
+    before_snapshot: Option<SpectrumData>,
+    repro_log: ReproLog,
+    annotations: spectrum_view::Annotations,
+    cross_peaks: Vec<processing::CrossPeak>,
+    volume_region: Option<processing::VolumeRegion>,
+}
+
+/// 1H/2H shift comparison against a second spectrum of the same sample
+struct IsotopeComparison {
+    partner: PathBuf,
+    /// Processed partner spectrum, kept so its peaks can be re-picked
+    partner_spectrum: SpectrumData,
+    /// Whether the partner is the 2H spectrum (the current one being 1H)
+    partner_is_d: bool,
+    own_peaks: Vec<[f64; 2]>,
+    partner_peaks: Vec<[f64; 2]>,
+    /// Partner pick settings: 2H lines are much broader than 1H ones
+    partner_threshold: f64,
+    partner_spacing_hz: f64,
+    tolerance_ppm: f64,
+}
+
+impl IsotopeComparison {
+    fn pairs(&self) -> Vec<processing::IsotopeShiftPair> {
+        let (h, d) = if self.partner_is_d {
+            (&self.own_peaks, &self.partner_peaks)
+        } else {
+            (&self.partner_peaks, &self.own_peaks)
+        };
+        processing::pair_isotope_shifts(h, d, self.tolerance_ppm)
+    }
+
+    fn pick_partner_peaks(&mut self) {
+        self.partner_peaks = auto_peaks(
+            &self.partner_spectrum,
+            self.partner_threshold,
+            self.partner_spacing_hz,
+            PeakSign::default_for(&self.partner_spectrum.experiment_type),
+        );
+    }
+}
+
+/// Partner spectrum for a 1H/2H comparison being loaded in the background
+struct IsotopeLoad {
+    load: PendingLoad,
+    wanted: Nucleus,
+}
+
+/// Peak list comparison against a second spectrum of the same nucleus,
+/// e.g. a later time point of a reaction or a stability sample
+struct SpectrumComparison {
+    compared: PathBuf,
+    /// Processed compared spectrum, kept so its peaks can be re-picked
+    compared_spectrum: SpectrumData,
+    own_peaks: Vec<[f64; 2]>,
+    compared_peaks: Vec<[f64; 2]>,
+    threshold: f64,
+    spacing_hz: f64,
+    tolerance_ppm: f64,
+    /// Line the compared peaks up with the current ones before matching
+    align: bool,
+    offset_ppm: f64,
+    /// The compared spectrum was recorded after a D₂O shake: peaks that
+    /// fade are flagged as exchangeable
+    d2o_shake: bool,
+    /// Normalised intensity below which a faded peak counts as exchanged
+    exchange_ratio: f64,
+}
+
+/// Largest referencing difference [`SpectrumComparison::realign`] corrects
+const MAX_ALIGNMENT_PPM: f64 = 0.2;
+
+impl SpectrumComparison {
+    fn rows(&self) -> Vec<processing::PeakDiff> {
+        processing::compare_peak_lists(&self.own_peaks, &self.compared_peaks, self.offset_ppm, self.tolerance_ppm)
+    }
+
+    fn pick_compared_peaks(&mut self) {
+        self.compared_peaks = auto_peaks(
+            &self.compared_spectrum,
+            self.threshold,
+            self.spacing_hz,
+            PeakSign::default_for(&self.compared_spectrum.experiment_type),
+        );
+        self.realign();
+    }
+
+    /// Exchangeable peaks and before − after difference of a D₂O shake
+    fn d2o_exchange(&self, before: &SpectrumData) -> processing::D2oExchange {
+        processing::d2o_exchange(before, &self.compared_spectrum, &self.rows(), self.offset_ppm, self.exchange_ratio)
+    }
+
+    fn realign(&mut self) {
+        self.offset_ppm = if self.align {
+            processing::align_peak_lists(&self.own_peaks, &self.compared_peaks, self.tolerance_ppm, MAX_ALIGNMENT_PPM)
+        } else {
+            0.0
+        };
+    }
+
+    /// Comparison table with one row per peak, highest shift first
+    fn to_csv(&self) -> String {
+        let fmt = |v: Option<f64>, decimals: usize| v.map(|v| format!("{:.*}", decimals, v)).unwrap_or_default();
+        let mut csv = String::from("status,ppm,reference_ppm,reference_intensity,compared_ppm,compared_intensity,intensity_ratio\n");
+        for row in self.rows() {
+            csv.push_str(&format!(
+                "{},{:.4},{},{},{},{},{}\n",
+                row.change,
+                row.ppm,
+                fmt(row.reference.map(|p| p[0]), 4),
+                row.reference.map(|p| format!("{:.6e}", p[1])).unwrap_or_default(),
+                fmt(row.compared.map(|p| p[0]), 4),
+                row.compared.map(|p| format!("{:.6e}", p[1])).unwrap_or_default(),
+                fmt(row.intensity_ratio, 3),
+            ));
+        }
+        csv
+    }
+}
+
+/// Settings of the carbon-type (DEPT / APT editing) window
+struct CarbonTyping {
+    editing: processing::SpectralEditing,
+    /// The spectrum is phased with the opposite sign convention
+    inverted: bool,
+    /// Source file of the session's DEPT-90 telling CH from CH3, which
+    /// stays valid when other entries are closed
+    dept90: Option<PathBuf>,
+    tolerance_ppm: f64,
+}
+
+/// Peaks followed through the 1D spectra of the session, e.g. a ligand
+/// titration, for a chemical-shift perturbation (CSP) plot
+struct TitrationSeries {
+    /// Name of each spectrum, in titration (session) order
+    names: Vec<String>,
+    /// Picked peaks of each spectrum
+    peaks: Vec<Vec<[f64; 2]>>,
+    /// Titration coordinate of each spectrum (equivalents, concentration…)
+    points: Vec<f64>,
+    x_label: String,
+    tolerance_ppm: f64,
+    /// Tracks as matched, then corrected by hand
+    tracks: Vec<processing::PeakTrack>,
+    nucleus: Nucleus,
+}
+
+impl TitrationSeries {
+    /// Match the peaks again, dropping any corrections
+    fn retrack(&mut self) {
+        self.tracks = processing::track_peaks(&self.peaks, self.tolerance_ppm);
+    }
+
+    /// Picked peak of spectrum `k` nearest to where `track` was last seen
+    /// before it (or first seen after it)
+    fn nearest_peak(&self, track: usize, k: usize) -> Option<f64> {
+        let shifts = &self.tracks.get(track)?.shifts;
+        let near = shifts[..k].iter().rev().flatten().chain(shifts[k..].iter().flatten()).next().copied()?;
+        self.peaks
+            .get(k)?
+            .iter()
+            .map(|p| p[0])
+            .min_by(|a, b| (a - near).abs().total_cmp(&(b - near).abs()))
+    }
+
+    /// One row per peak and spectrum; empty shift and Δδ where the peak was
+    /// not found
+    fn to_csv(&self) -> String {
+        let fmt = |v: Option<f64>| v.map(|v| format!("{:.4}", v)).unwrap_or_default();
+        let mut csv = format!("peak,start_ppm,spectrum,{},shift_ppm,delta_ppm\n", csv_field(&self.x_label));
+        for (i, track) in self.tracks.iter().enumerate() {
+            let deltas = track.perturbations();
+            for (k, name) in self.names.iter().enumerate() {
+                csv.push_str(&format!(
+                    "{},{},{},{},{},{}\n",
+                    i + 1,
+                    fmt(track.start_ppm()),
+                    csv_field(name),
+                    self.points[k],
+                    fmt(track.shifts.get(k).copied().flatten()),
+                    fmt(deltas.get(k).copied().flatten()),
+                ));
+            }
+        }
+        csv
+    }
+}
+
+/// Quote a CSV field holding a comma or quote
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Built-in vs NMRPipe conversion check running in the background
+struct ConversionValidation {
+    source: PathBuf,
+    result: Arc<Mutex<Option<Result<ValidationReport, String>>>>,
+    logged: bool,
+}
+
+/// Outcome of each dataset, or why the folder could not be processed
+type TemplateRunResult = NmrResult<Vec<TemplateRun>>;
+
+/// A processing template applied to a folder in the background
+struct TemplateFolderRun {
+    template: String,
+    folder: PathBuf,
+    result: Arc<Mutex<Option<TemplateRunResult>>>,
+    logged: bool,
+}
+
+/// A step that would take memory use past the budget, waiting for the
+/// user to choose how to go on
+#[derive(Debug, Clone)]
+struct MemoryWarning {
+    action: PipelineAction,
+    step: String,
+    /// Spectra plus history once the step has run (bytes)
+    projected: usize,
+    /// Of which the undo snapshot of the step
+    snapshot: usize,
+}
+
+/// How the user chose to go on past the memory budget
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MemoryChoice {
+    Continue,
+    SinglePrecision,
+    WithoutUndo,
+    Cancel,
+}
+
+/// Automatic peak picking of `sign` beyond `threshold` (fraction of max)
+/// with peaks at least `min_spacing_hz` apart
+fn auto_peaks(spectrum: &SpectrumData, threshold: f64, min_spacing_hz: f64, sign: PeakSign) -> Vec<[f64; 2]> {
+    // Convert Hz to index distance using the point spacing of the axis
+    let hz_per_point = spectrum.axes.first().map(|a| a.hz_per_point()).unwrap_or(1.0);
+    let pts_per_hz = if hz_per_point > 0.0 { 1.0 / hz_per_point } else { 1.0 };
+    let min_dist = ((min_spacing_hz * pts_per_hz) as usize).max(2);
+    processing::detect_peaks(spectrum, threshold, min_dist, sign)
+}
+
+/// Assignments of a saved processed 1D project whose nucleus is `nucleus`
+fn load_1d_assignments(path: &std::path::Path, nucleus: &Nucleus) -> Result<Vec<(f64, String)>, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Read error: {}", e))?;
+    let save: ProjectSave = serde_json::from_str(&json).map_err(|e| format!("Parse error: {}", e))?;
+    let spectrum = save.spectrum.ok_or("the project has no spectrum")?;
+    if spectrum.is_2d() || !spectrum.is_frequency_domain {
+        return Err("the project is not a processed 1D spectrum".to_string());
+    }
+    match spectrum.axes.first() {
+        Some(axis) if axis.nucleus == *nucleus => {}
+        Some(axis) => return Err(format!("{} project does not match the {} F2 axis", axis.nucleus, nucleus)),
+        None => return Err("the project spectrum has no axis".to_string()),
+    }
+    let assignments = processing::assignments_from_1d(&save.peaks, &save.multiplets);
+    if assignments.is_empty() {
+        return Err("the project has no picked peaks or multiplets".to_string());
+    }
+    Ok(assignments)
 }
 
 /// The main application
 pub struct NmrApp {
-    /// Currently loaded / working spectrum
-    spectrum: Option<SpectrumData>,
+    /// Loaded spectra; the active one is the working spectrum
+    session: Session<ParkedDocument>,
+    /// The load being set up (file dialog, experiment or conversion
+    /// dialog) is added to the session instead of replacing the active
+    /// spectrum; handed to the pending load when it starts
+    session_add: bool,
+    show_session: bool,
+    /// Offset between spectra when stacking, as a fraction of the tallest
+    /// point of the active spectrum
+    session_stack_step: f64,
 
     /// Snapshot of the FID right before FT was applied, so the user
     /// can flip back and inspect the time-domain data.
@@ -59,10 +411,11 @@ pub struct NmrApp {
     /// Which domain tab is selected
     domain_tab: DomainTab,
 
-    /// Undo history: stack of (operation, snapshot-before)
-    undo_stack: Vec<(ProcessingOp, SpectrumData)>,
+    /// Undo history: stack of (operation, snapshot-before), stored at the
+    /// precision chosen in the preferences
+    undo_stack: Vec<(ProcessingOp, StoredSpectrum)>,
     /// Redo stack
-    redo_stack: Vec<(ProcessingOp, SpectrumData)>,
+    redo_stack: Vec<(ProcessingOp, StoredSpectrum)>,
 
     /// "Before" spectrum for comparison
     before_snapshot: Option<SpectrumData>,
@@ -75,6 +428,8 @@ pub struct NmrApp {
     spectrum_view_state: SpectrumViewState,
     contour_view_state: ContourViewState,
     phase_dialog_state: PhaseDialogState,
+    /// 2D phase correction along F2 or F1
+    phase_2d_state: Phase2dState,
     conversion_dialog_state: ConversionDialogState,
     export_dialog_state: ExportDialogState,
     export_tab_state: ExportTabState,
@@ -82,6 +437,21 @@ pub struct NmrApp {
     /// Status messages
     status_message: String,
     show_log_window: bool,
+    show_correlation_table: bool,
+    show_peak_table: bool,
+
+    /// Reference spectra library, read when its window is first opened
+    reference_library: Option<ReferenceLibrary>,
+    show_reference_library: bool,
+    /// Name typed for the next saved reference
+    reference_name: String,
+    /// References overlaid on the spectrum (by name)
+    overlaid_references: Vec<String>,
+    /// Impurity quantification: reference name, ppm region, impurity H count
+    impurity_reference: String,
+    impurity_region: (f64, f64),
+    impurity_h: f64,
+    impurity_result: Option<processing::ImpurityFit>,
     show_about: bool,
 
     /// NMRPipe availability
@@ -94,12 +464,97 @@ pub struct NmrApp {
     /// Current conversion method (NMRPipe vs Built-in)
     conversion_method: crate::gui::conversion_dialog::ConversionMethod,
 
-    /// Dropped files buffer
+    /// Paths waiting to be opened: dropped files, or the path given on the
+    /// command line
     dropped_files: Vec<PathBuf>,
+
+    /// Background batch conversion (File → Batch Convert Folder)
+    batch_conversion: Option<BatchConversion>,
+
+    /// egui context, for repaint requests from background threads
+    egui_ctx: egui::Context,
+
+    /// Persisted user preferences
+    preferences: Preferences,
+    preferences_dialog_state: PreferencesDialogState,
+    /// Where conversion outputs go this session
+    work_dir: WorkDir,
+
+    /// Dataset being loaded in the background
+    pending_load: Option<PendingLoad>,
+    /// Source path of a loaded project that could not be found
+    missing_source: Option<std::path::PathBuf>,
+    /// Magnitude step waiting for the user to accept losing the phase
+    confirm_magnitude: Option<PipelineAction>,
+    /// Set when the user accepted; lets the action through once
+    magnitude_confirmed: bool,
+    /// Step over the memory budget waiting for confirmation
+    confirm_memory: Option<MemoryWarning>,
+    /// Step the user let through despite the budget; cleared once it runs
+    memory_confirmed: Option<PipelineAction>,
+    /// Run the next step without an undo snapshot, dropping the history
+    skip_undo_snapshot: bool,
+    /// NaN/Inf values found in the data, offered for replacement
+    non_finite: Option<NonFiniteReport>,
+    /// Count of non-finite values the user chose to keep; not asked again
+    /// until it changes
+    non_finite_kept: usize,
+    /// Bruker sample folder whose experiments are offered for loading
+    bruker_experiments: Option<(PathBuf, Vec<crate::data::bruker::BrukerExperiment>)>,
+    /// Folder (or dropped files) holding several datasets, offered for loading
+    open_candidates: Option<(PathBuf, Vec<Candidate>)>,
+    /// Open 1H/2H isotope-shift comparison window
+    isotope_comparison: Option<IsotopeComparison>,
+    isotope_load: Option<IsotopeLoad>,
+    /// Open peak list comparison window, and its spectrum being loaded
+    spectrum_comparison: Option<SpectrumComparison>,
+    comparison_load: Option<PendingLoad>,
+    /// The spectrum being loaded for comparison is the D₂O-shaken sample
+    comparison_d2o: bool,
+    /// Open carbon-type window of a DEPT-135 / APT spectrum
+    carbon_typing: Option<CarbonTyping>,
+    /// Open chemical-shift perturbation window over the session spectra
+    titration: Option<TitrationSeries>,
+    /// Raw data copy of a "Save Project as Bundle" in progress
+    bundle_copy: Option<bundle::BundleCopy>,
+    /// Figure being written in the background
+    pending_export: Option<plot_scene::PendingExport>,
+    /// Help → Developer → Validate Conversion
+    conversion_validation: Option<ConversionValidation>,
+    /// Template run on a folder, shown until its window is closed
+    template_run: Option<TemplateFolderRun>,
+    /// Help → Developer → Compare NMRPipe Headers: (title, diff text)
+    header_diff: Option<(String, String)>,
+    /// External prediction tool running in the background
+    pending_tool: Option<external::PendingTool>,
+    /// Log entries already considered for teaching-mode explanations
+    explained_upto: usize,
+    /// Teaching-mode explanations waiting to be read
+    explanations: Vec<(LogEntry, Explanation)>,
+    /// Copy of the log kept next to the data (Preferences → Processing log)
+    log_mirror: LogMirror,
+    /// Command server started with `--listen`
+    ipc_server: Option<IpcServer>,
+    /// `open` request answered once its load finishes
+    ipc_waiting: Option<IpcRequest>,
+    /// Started with `--viewer`: data is processed with the default profile
+    /// on opening, and only viewing and image export are offered
+    viewer: bool,
 }
 
 impl NmrApp {
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+    /// `open` (from the command line) is opened on the first frame.
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        open: Option<PathBuf>,
+        listen: Option<SocketAddr>,
+        viewer: bool,
+    ) -> Self {
+        #[cfg(feature = "gpu-contours")]
+        if let Some(render_state) = &cc.wgpu_render_state {
+            crate::gui::contour_gpu::init(render_state);
+        }
+
         // ── Apply default theme ──
         let default_theme = AppTheme::Light;
         theme::apply_theme(&cc.egui_ctx, default_theme);
@@ -142,23 +597,78 @@ impl NmrApp {
             log::info!("NMRPipe not found — using built-in processing");
         }
 
+        // The viewer keeps its read-only settings out of the preferences,
+        // which it may still save (e.g. the undo history precision)
+        let preferences = Preferences::load();
+        let work_dir = WorkDir::new(if viewer { WorkDirSettings::transient() } else { preferences.work_dir.clone() });
+        let pipeline_state = PipelinePanelState {
+            external_tools: preferences.external_tools.iter().map(|t| t.name.clone()).collect(),
+            ..Default::default()
+        };
+        let mut status_message = if viewer {
+            "Viewer — open an NMR data file or folder to look at it".to_string()
+        } else {
+            "Ready — open an NMR data file or folder to begin".to_string()
+        };
+        let ipc_server = listen.and_then(|address| {
+            let ctx = cc.egui_ctx.clone();
+            let token = ipc::new_token();
+            match ipc::start(address, token.clone(), move || ctx.request_repaint()) {
+                Ok(server) => {
+                    // Clients authenticate with the token; scripts on the
+                    // same account read it from the file
+                    println!("Command server on {}, token {}", server.address, token);
+                    match Preferences::config_dir().map(|dir| ipc::write_token_file(&dir, &token)) {
+                        Some(Ok(path)) => log::info!("Command server token written to {}", path.display()),
+                        Some(Err(e)) => log::warn!("Cannot write the command server token: {}", e),
+                        None => log::warn!("No config directory for the command server token"),
+                    }
+                    status_message = format!("Ready — listening for commands on {}", server.address);
+                    Some(server)
+                }
+                Err(e) => {
+                    log::error!("Cannot listen on {}: {}", address, e);
+                    status_message = format!("Command server not started: cannot listen on {} ({})", address, e);
+                    None
+                }
+            }
+        });
+
         Self {
-            spectrum: None,
+            session: Session::default(),
+            session_add: false,
+            show_session: false,
+            session_stack_step: 0.3,
             fid_snapshot: None,
             domain_tab: DomainTab::TimeDomain,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
             before_snapshot: None,
             repro_log: ReproLog::new(),
-            pipeline_state: PipelinePanelState::default(),
-            spectrum_view_state: SpectrumViewState::default(),
+            pipeline_state,
+            spectrum_view_state: SpectrumViewState {
+                shift_regions: preferences.shift_regions.regions.clone(),
+                axis_direction: preferences.axis_direction,
+                ..Default::default()
+            },
             contour_view_state: ContourViewState::default(),
             phase_dialog_state: PhaseDialogState::default(),
+            phase_2d_state: Phase2dState::default(),
             conversion_dialog_state: ConversionDialogState::default(),
             export_dialog_state: ExportDialogState::default(),
             export_tab_state: ExportTabState::default(),
-            status_message: "Ready — open an NMR data file or folder to begin".to_string(),
+            status_message,
             show_log_window: false,
+            show_correlation_table: false,
+            show_peak_table: false,
+            reference_library: None,
+            show_reference_library: false,
+            reference_name: String::new(),
+            overlaid_references: Vec::new(),
+            impurity_reference: String::new(),
+            impurity_region: (1.0, 1.5),
+            impurity_h: 1.0,
+            impurity_result: None,
             show_about: false,
             nmrpipe_available,
             current_theme: default_theme,
@@ -168,32 +678,109 @@ impl NmrApp {
             } else {
                 crate::gui::conversion_dialog::ConversionMethod::BuiltIn
             },
-            dropped_files: Vec::new(),
+            dropped_files: open.into_iter().collect(),
+            batch_conversion: None,
+            egui_ctx: cc.egui_ctx.clone(),
+            preferences,
+            preferences_dialog_state: PreferencesDialogState::default(),
+            work_dir,
+            pending_load: None,
+            missing_source: None,
+            confirm_magnitude: None,
+            magnitude_confirmed: false,
+            confirm_memory: None,
+            memory_confirmed: None,
+            skip_undo_snapshot: false,
+            non_finite: None,
+            non_finite_kept: 0,
+            bruker_experiments: None,
+            open_candidates: None,
+            isotope_comparison: None,
+            isotope_load: None,
+            spectrum_comparison: None,
+            comparison_load: None,
+            comparison_d2o: false,
+            carbon_typing: None,
+            titration: None,
+            bundle_copy: None,
+            pending_export: None,
+            conversion_validation: None,
+            template_run: None,
+            header_diff: None,
+            explained_upto: 0,
+            explanations: Vec::new(),
+            log_mirror: LogMirror::default(),
+            pending_tool: None,
+            ipc_server,
+            ipc_waiting: None,
+            viewer,
         }
     }
 
-    /// Load a file or folder.
-    /// For JDF files, opens the conversion dialog first so the user can set parameters.
+    /// Open a data file or folder, a project, a zip archive or an NMRPipe
+    /// series pattern.  Bruker samples and folders with several datasets
+    /// ask which one to load; for JDF files the conversion dialog opens
+    /// first so the user can set parameters.
     fn load_path(&mut self, path: PathBuf) {
-        // If it's a directory, find NMR files in it
-        let files_to_try = if path.is_dir() {
-            let files = conversion::list_nmr_files(&path);
-            if files.is_empty() {
-                self.status_message = format!("No NMR data files found in: {}", path.display());
+        let target = match open::resolve(&path) {
+            Ok(OpenTarget::Dataset(target)) => target,
+            Ok(OpenTarget::Project(project)) => {
+                self.session_add = false;
+                self.open_project(&project);
+                return;
+            }
+            Ok(OpenTarget::BrukerSample(sample, experiments)) => {
+                self.status_message = format!("{} experiments in {} — pick one to load", experiments.len(), sample.display());
+                self.bruker_experiments = Some((sample, experiments));
+                return;
+            }
+            Ok(OpenTarget::Archive(archive)) => {
+                let dest = self.work_dir.extract_dir_for(&archive);
+                match open::extract_archive(&archive, &dest) {
+                    Ok(root) => self.load_path(root),
+                    Err(e) => {
+                        self.session_add = false;
+                        self.status_message = format!("Could not extract {}: {}", archive.display(), e);
+                    }
+                }
+                return;
+            }
+            Ok(OpenTarget::Choose(dir, candidates)) => {
+                self.status_message = format!("{} datasets in {} — pick one to load", candidates.len(), dir.display());
+                self.open_candidates = Some((dir, candidates));
+                return;
+            }
+            Err(e) => {
+                self.session_add = false;
+                self.status_message = e.user_message();
                 return;
             }
-            files
-        } else {
-            vec![path.clone()]
         };
-
-        let target = files_to_try[0].clone();
         let format = conversion::detect_format(&target);
 
+        // Bruker FIDs that look already DMX-corrected: confirm before converting,
+        // unless Preferences already fix the digital-filter handling
+        if format == crate::data::spectrum::VendorFormat::Bruker
+            && self.preferences.bruker_dmx == crate::gui::conversion_dialog::DmxCorrection::Auto
+        {
+            let hint = crate::data::bruker::read_bruker_params(&target)
+                .ok()
+                .and_then(|(params, _)| crate::data::bruker::detect_precorrected(&target, &params));
+            if let Some(hint) = hint {
+                self.conversion_dialog_state.open = true;
+                self.conversion_dialog_state.pending_path = Some(target);
+                self.conversion_dialog_state.dmx_hint = Some(hint);
+                self.conversion_dialog_state.settings.dmx_correction = Default::default();
+                self.status_message = "Bruker data may already be DMX-corrected — confirm, then click Convert…".to_string();
+                return;
+            }
+        }
+
         // For JEOL files, show the conversion settings dialog
         if format == crate::data::spectrum::VendorFormat::Jeol {
             self.conversion_dialog_state.open = true;
             self.conversion_dialog_state.pending_path = Some(target);
+            self.conversion_dialog_state.dmx_hint = None;
             self.conversion_dialog_state.info_loaded = false;
             self.conversion_dialog_state.info_text.clear();
             // Keep existing settings so user adjustments persist between loads
@@ -209,24 +796,71 @@ impl NmrApp {
     fn make_settings(&self, base: Option<&crate::gui::conversion_dialog::ConversionSettings>) -> crate::gui::conversion_dialog::ConversionSettings {
         let mut s = base.cloned().unwrap_or_default();
         s.conversion_method = self.conversion_method;
+        // A choice made in the conversion dialog wins over the preference
+        if s.dmx_correction == crate::gui::conversion_dialog::DmxCorrection::Auto {
+            s.dmx_correction = self.preferences.bruker_dmx;
+        }
         s
     }
 
     /// Actually perform the loading (after any dialog).
+    ///
+    /// Conversion runs in the background; the current data stays in place
+    /// until [`Self::finish_load`] receives the result.
     fn do_load(
         &mut self,
         path: &std::path::Path,
         settings: Option<&crate::gui::conversion_dialog::ConversionSettings>,
     ) {
+        if let Some(previous) = self.pending_load.take() {
+            previous.cancel();
+        }
         self.status_message = format!("Loading: {}…", path.display());
-        self.repro_log = ReproLog::new();
-        self.repro_log.set_source(&path.to_string_lossy());
+
+        let mut log = ReproLog::new();
+        log.set_source(&path.to_string_lossy());
+
+        // Merge user-provided settings with current conversion method
+        let mut merged = self.make_settings(settings);
+        merged.output_dir = Some(self.work_dir.output_dir_for(path));
+
+        let cache = self.preferences.cache.enabled.then(|| self.conversion_cache());
+        let mut pending = loader::start_load(path, log, merged, cache, self.egui_ctx.clone());
+        pending.add_to_session = std::mem::take(&mut self.session_add);
+        self.pending_load = Some(pending);
+    }
+
+    /// Install the result of a background load, resetting per-file state.
+    fn finish_load(&mut self, path: &std::path::Path, result: LoadResult, add_to_session: bool) {
+        let spectrum = match result.spectrum {
+            Ok(spectrum) => spectrum,
+            Err(NmrError::Cancelled) => {
+                self.status_message = format!("Loading cancelled: {}", path.display());
+                log::info!("Load cancelled: {}", path.display());
+                return;
+            }
+            Err(e) => {
+                self.status_message = format!("Error loading {}: {}", path.display(), e.user_message());
+                log::error!("Load error: {}", e);
+                return;
+            }
+        };
+
+        // Keep the current spectrum, with its history and annotations, in
+        // the session
+        let parked = (add_to_session && self.session.active().is_some()).then(|| self.park_document());
+
+        self.repro_log = result.log;
+        self.log_mirror.project = None;
         self.undo_stack.clear();
         self.redo_stack.clear();
         self.before_snapshot = None;
         self.fid_snapshot = None;
         // Reset phase dialog from previous file
         self.phase_dialog_state = PhaseDialogState::default();
+        self.phase_2d_state = Phase2dState::default();
+        self.non_finite = None;
+        self.non_finite_kept = 0;
 
         // Reset all annotations from previous file
         self.spectrum_view_state.peaks.clear();
@@ -235,811 +869,1258 @@ impl NmrApp {
         self.spectrum_view_state.integration_start = None;
         self.spectrum_view_state.j_couplings.clear();
         self.spectrum_view_state.j_coupling_first = None;
+        self.spectrum_view_state.linewidths.clear();
+        self.spectrum_view_state.peak_fits.clear();
+        self.spectrum_view_state.fit_start = None;
         self.spectrum_view_state.baseline_points.clear();
+        self.spectrum_view_state.solvent_regions.clear();
+        self.spectrum_view_state.solvent_drag = None;
         self.spectrum_view_state.peak_picking = false;
         self.spectrum_view_state.baseline_picking = false;
         self.spectrum_view_state.integration_picking = false;
         self.spectrum_view_state.j_coupling_picking = false;
+        self.spectrum_view_state.auto_j_picking = false;
+        self.spectrum_view_state.linewidth_picking = false;
+        self.spectrum_view_state.fit_picking = false;
+        self.spectrum_view_state.solvent_picking = false;
+        self.spectrum_view_state.enhance = ResolutionPreview::default();
         self.spectrum_view_state.auto_scale = true;
-
-        // Merge user-provided settings with current conversion method
-        let merged = self.make_settings(settings);
-
-        // Set domain tab based on what we actually loaded
-        // (will be updated below after successful load to match the data)
-
-        match conversion::load_spectrum(path, &mut self.repro_log, Some(&merged)) {
-            Ok(spectrum) => {
-                // Auto-select the correct domain tab based on loaded data
-                if spectrum.is_frequency_domain {
-                    self.domain_tab = DomainTab::FrequencyDomain;
-                } else {
-                    self.domain_tab = DomainTab::TimeDomain;
-                }
-                let pts_info = if spectrum.is_2d() {
-                    format!("{}×{}",
-                        spectrum.data_2d.len(),
-                        spectrum.data_2d.first().map(|r| r.len()).unwrap_or(0))
-                } else {
-                    format!("{} pts", spectrum.real.len())
-                };
-                self.status_message = format!(
-                    "Loaded: {} ({}, {}, {}) [{}]",
-                    spectrum.sample_name,
-                    spectrum.experiment_type,
-                    pts_info,
-                    spectrum.vendor_format,
-                    if spectrum.conversion_method_used.is_empty() {
-                        "unknown method"
-                    } else {
-                        &spectrum.conversion_method_used
-                    },
-                );
-                // Set nucleus and experiment info in the log
-                let nucleus = spectrum.axes.first()
-                    .map(|a| a.nucleus.to_string())
-                    .unwrap_or_default();
-                self.repro_log.set_spectrum_info(&nucleus, &spectrum.experiment_type.to_string());
-                self.spectrum = Some(spectrum);
-            }
-            Err(e) => {
-                self.status_message = format!("Error loading {}: {}", path.display(), e);
-                log::error!("Load error: {}", e);
-            }
+        self.spectrum_view_state.range_preset = None;
+        self.spectrum_view_state.restore_linked_view = self.spectrum_view_state.link_zoom;
+        self.contour_view_state.cross_peaks.clear();
+        self.contour_view_state.volume_region = None;
+        self.impurity_result = None;
+        self.spectrum_view_state.impurity_fit = None;
+        self.spectrum_view_state.exchangeable.clear();
+        self.spectrum_view_state.common_impurities.clear();
+        self.spectrum_view_state.carbon_types.clear();
+        self.spectrum_view_state.d2o_difference = None;
+        self.spectrum_view_state.predicted_sticks.clear();
+        self.spectrum_view_state.suggested_assignments.clear();
+        self.sync_reference_overlays();
+        if let Some(axis) = spectrum.axes.first() {
+            self.pipeline_state.apply_nucleus_defaults(&axis.nucleus);
         }
-    }
-
-    /// Save a snapshot before an operation (for undo)
-    fn push_undo(&mut self, op: ProcessingOp) {
-        if let Some(spectrum) = &self.spectrum {
-            self.before_snapshot = Some(spectrum.clone());
-            self.undo_stack.push((op, spectrum.clone()));
-            self.redo_stack.clear(); // Clear redo on new action
+        self.pipeline_state.dead_time_points = self.preferences.dead_time.points;
+        self.pipeline_state.dead_time_mode = self.preferences.dead_time.mode;
+        self.pipeline_state.ft2d_mode = Ft2dMode::default_for(&spectrum.experiment_type);
+        self.pipeline_state.f1_acquisition = spectrum.f1_acquisition;
+        self.pipeline_state.peak_sign = PeakSign::default_for(&spectrum.experiment_type);
+
+        // Auto-select the correct domain tab based on loaded data
+        if spectrum.is_frequency_domain {
+            self.domain_tab = DomainTab::FrequencyDomain;
+        } else {
+            self.domain_tab = DomainTab::TimeDomain;
         }
-    }
-
-    /// Undo the last operation
-    fn undo(&mut self) {
-        if let Some((op, snapshot)) = self.undo_stack.pop() {
-            if let Some(current) = self.spectrum.take() {
-                self.redo_stack.push((op.clone(), current));
+        let pts_info = if let Some(array) = &spectrum.array {
+            format!("{} FIDs arrayed in {}", array.slices, array.array)
+        } else if spectrum.is_2d() {
+            format!("{}×{}",
+                spectrum.data_2d.len(),
+                spectrum.data_2d.first().map(|r| r.len()).unwrap_or(0))
+        } else {
+            format!("{} pts", spectrum.real.len())
+        };
+        self.status_message = format!(
+            "Loaded: {} ({}, {}, {}) [{}]",
+            spectrum.sample_name,
+            spectrum.experiment_type,
+            pts_info,
+            spectrum.vendor_format,
+            if spectrum.conversion_method_used.is_empty() {
+                "unknown method"
+            } else {
+                &spectrum.conversion_method_used
+            },
+        );
+        // Set nucleus and experiment info in the log
+        let nucleus = spectrum.axes.first()
+            .map(|a| a.nucleus.to_string())
+            .unwrap_or_default();
+        self.repro_log.set_spectrum_info(&nucleus, &spectrum.experiment_type.to_string());
+        let color = plot_scene::session_color(self.session.len());
+        match parked {
+            Some(parked) => {
+                self.session.push(spectrum, color, parked);
+                self.show_session = true;
             }
-            self.spectrum = Some(snapshot);
-            self.before_snapshot = None; // Clear stale comparison
-            self.repro_log.pop_entry();
-            self.status_message = format!("Undone: {}", op);
+            None => self.session.replace_active(spectrum, color),
+        }
+        self.check_non_finite();
+        self.auto_reference_solvent();
+        if self.viewer && self.session.active().is_some_and(|s| !s.is_frequency_domain) {
+            self.handle_pipeline_action(PipelineAction::QuickProcess);
         }
     }
 
-    /// Redo the last undone operation
-    fn redo(&mut self) {
-        if let Some((op, snapshot)) = self.redo_stack.pop() {
-            if let Some(current) = self.spectrum.take() {
-                self.undo_stack.push((op.clone(), current));
-            }
-            self.spectrum = Some(snapshot);
-            self.status_message = format!("Redone: {}", op);
+    /// Move the working spectrum's history, log and annotations out, for
+    /// another spectrum of the session to become active
+    fn park_document(&mut self) -> ParkedDocument {
+        let view = &mut self.spectrum_view_state;
+        view.peak_picking = false;
+        view.baseline_picking = false;
+        view.integration_picking = false;
+        view.j_coupling_picking = false;
+        view.auto_j_picking = false;
+        view.linewidth_picking = false;
+        view.fit_picking = false;
+        view.solvent_picking = false;
+        view.enhance = ResolutionPreview::default();
+        self.phase_dialog_state = PhaseDialogState::default();
+        self.phase_2d_state = Phase2dState::default();
+        ParkedDocument {
+            fid_snapshot: self.fid_snapshot.take(),
+            domain_tab: self.domain_tab,
+            undo_stack: std::mem::take(&mut self.undo_stack),
+            redo_stack: std::mem::take(&mut self.redo_stack),
+            before_snapshot: self.before_snapshot.take(),
+            repro_log: std::mem::take(&mut self.repro_log),
+            annotations: self.spectrum_view_state.take_annotations(),
+            cross_peaks: std::mem::take(&mut self.contour_view_state.cross_peaks),
+            volume_region: self.contour_view_state.volume_region.take(),
         }
     }
 
-    /// Export the current spectrum to a PNG or SVG image file with configurable settings.
-    fn export_spectrum_image_with_settings(
-        &self,
-        path: &std::path::Path,
-        settings: &ExportSettings,
-    ) -> Result<(), String> {
-        let spectrum = self.spectrum.as_ref().ok_or("No spectrum loaded")?;
-        if spectrum.real.is_empty() {
-            return Err("Spectrum has no data".to_string());
-        }
-
-        let width = settings.width;
-        let height = settings.height;
-        let margin_left: u32 = (width as f64 * 0.04).max(80.0) as u32;
-        let margin_right: u32 = (width as f64 * 0.025).max(40.0) as u32;
-        let margin_top: u32 = (height as f64 * 0.08).max(50.0) as u32;
-        let margin_bottom: u32 = (height as f64 * 0.10).max(70.0) as u32;
-        let plot_w = width - margin_left - margin_right;
-        let plot_h = height - margin_top - margin_bottom;
-
-        // Build ppm scale
-        let ppm_scale = if spectrum.is_frequency_domain && !spectrum.axes.is_empty() {
-            spectrum.axes[0].ppm_scale()
-        } else {
-            (0..spectrum.real.len()).map(|i| i as f64).collect::<Vec<_>>()
-        };
-
-        // Determine x range (ppm) — user-configurable
-        let (ppm_hi, ppm_lo) = if settings.use_custom_range {
-            (
-                settings.ppm_start.max(settings.ppm_end),
-                settings.ppm_start.min(settings.ppm_end),
-            )
-        } else {
-            let ppm_min = ppm_scale.iter().cloned().fold(f64::INFINITY, f64::min);
-            let ppm_max = ppm_scale.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
-            (ppm_max, ppm_min)
-        };
-        let x_range = ppm_hi - ppm_lo;
-        if x_range <= 0.0 {
-            return Err("Invalid ppm range".to_string());
+    /// Make another spectrum of the session the working one, parking the
+    /// current one with its history and annotations
+    fn activate_spectrum(&mut self, index: usize) {
+        if index >= self.session.len() || index == self.session.active_index() {
+            return;
         }
-
-        // Filter data to ppm range
-        let clip_neg = settings.clip_negatives;
-        let y_data: Vec<(f64, f64)> = ppm_scale
-            .iter()
-            .zip(spectrum.real.iter())
-            .filter(|(&ppm, _)| ppm >= ppm_lo && ppm <= ppm_hi)
-            .map(|(&ppm, &y)| (ppm, if clip_neg { y.max(0.0) } else { y }))
-            .collect();
-
-        if y_data.is_empty() {
-            return Err("No data points in the selected PPM range".to_string());
+        let current = self.park_document();
+        match self.session.activate(index, current) {
+            Some(parked) => {
+                self.fid_snapshot = parked.fid_snapshot;
+                self.domain_tab = parked.domain_tab;
+                self.undo_stack = parked.undo_stack;
+                self.redo_stack = parked.redo_stack;
+                self.before_snapshot = parked.before_snapshot;
+                self.repro_log = parked.repro_log;
+                self.spectrum_view_state.restore_annotations(parked.annotations);
+                self.contour_view_state.cross_peaks = parked.cross_peaks;
+                self.contour_view_state.volume_region = parked.volume_region;
+            }
+            None => {
+                self.repro_log = ReproLog::new();
+                self.domain_tab = DomainTab::FrequencyDomain;
+            }
         }
-
-        let y_min = if clip_neg {
-            0.0
-        } else {
-            y_data.iter().map(|d| d.1).fold(f64::INFINITY, f64::min)
-        };
-        let y_max = y_data.iter().map(|d| d.1).fold(f64::NEG_INFINITY, f64::max);
-        let y_range = (y_max - y_min).max(1e-12);
-        // Add 5% padding at top
-        let y_max_padded = y_max + y_range * 0.05;
-        let y_range_padded = (y_max_padded - y_min).max(1e-12);
-
-        // Title
-        let title = if settings.use_custom_title && !settings.custom_title.is_empty() {
-            settings.custom_title.clone()
-        } else {
-            format!(
-                "{} — {} — {} pts",
-                spectrum.sample_name,
-                spectrum.experiment_type,
-                spectrum.real.len()
-            )
+        self.impurity_result = None;
+        self.carbon_typing = None;
+        self.non_finite = None;
+        self.non_finite_kept = 0;
+        self.explained_upto = self.repro_log.len();
+        let Some(spectrum) = self.session.active() else {
+            return;
         };
-
-        let ext = path
-            .extension()
-            .map(|e| e.to_string_lossy().to_lowercase())
-            .unwrap_or_default();
-
-        match ext.as_str() {
-            "svg" => self.export_svg(
-                path, spectrum, &ppm_scale, &title,
-                ppm_hi, ppm_lo, x_range, y_min, y_max_padded, y_range_padded,
-                clip_neg, settings, width, height,
-                margin_left, margin_right, margin_top, margin_bottom, plot_w, plot_h,
-            ),
-            _ => self.export_png(
-                path, spectrum, &ppm_scale, &title,
-                ppm_hi, ppm_lo, x_range, y_min, y_max_padded, y_range_padded,
-                clip_neg, settings, width, height,
-                margin_left, margin_right, margin_top, margin_bottom, plot_w, plot_h,
-            ),
+        if !spectrum.is_frequency_domain && self.fid_snapshot.is_none() {
+            self.domain_tab = DomainTab::TimeDomain;
+        }
+        if let Some(axis) = spectrum.axes.first() {
+            self.pipeline_state.apply_nucleus_defaults(&axis.nucleus);
         }
+        self.status_message = format!("Active spectrum: {}", self.session.entries[index].name());
+        self.sync_reference_overlays();
+        self.check_non_finite();
     }
 
-    fn export_png(
-        &self,
-        path: &std::path::Path,
-        spectrum: &SpectrumData,
-        ppm_scale: &[f64],
-        title: &str,
-        ppm_hi: f64, ppm_lo: f64, x_range: f64,
-        y_min: f64, _y_max: f64, y_range: f64,
-        clip_neg: bool,
-        settings: &ExportSettings,
-        width: u32, height: u32,
-        margin_left: u32, _margin_right: u32, margin_top: u32, _margin_bottom: u32,
-        plot_w: u32, plot_h: u32,
-    ) -> Result<(), String> {
-        let mut imgbuf = image::RgbImage::from_pixel(width, height, image::Rgb([255, 255, 255]));
-
-        // Scale factors
-        let ms_f = settings.marker_scale;
-        let fs_f = settings.font_scale;
-        let ts = (2.0 * fs_f).round().max(1.0) as u32;           // text scale for labels
-        let title_ts = (3.0 * fs_f).round().max(1.0) as u32;     // text scale for title
-        let char_w = (4 * ts) as i32;                             // char width in px
-        let char_h = (5 * ts) as i32;                             // char height in px
-        let marker_size = (4.0 * ms_f).round().max(1.0) as i32;  // triangle half-width
-        let marker_gap = (6.0 * ms_f).round().max(2.0) as i32;   // gap between peak and marker
-
-        // Draw grid lines
-        if settings.show_grid {
-            let grid_color = image::Rgb([230, 230, 235]);
-            let num_grid_x = ((x_range / 1.0).ceil() as u32).min(20).max(5);
-            for gi in 1..num_grid_x {
-                let gx = margin_left + (plot_w as f64 * gi as f64 / num_grid_x as f64) as u32;
-                for y in margin_top + 1..margin_top + plot_h {
-                    if gx < width { imgbuf.put_pixel(gx, y, grid_color); }
-                }
-            }
-            for gi in 1..5 {
-                let gy = margin_top + (plot_h as f64 * gi as f64 / 5.0) as u32;
-                for x in margin_left + 1..margin_left + plot_w {
-                    if gy < height { imgbuf.put_pixel(x, gy, grid_color); }
-                }
-            }
-        }
-
-        // Draw plot border
-        let border_color = image::Rgb([100, 100, 110]);
-        for x in margin_left..=margin_left + plot_w {
-            if x < width {
-                imgbuf.put_pixel(x, margin_top, border_color);
-                imgbuf.put_pixel(x, margin_top + plot_h, border_color);
-            }
-        }
-        for y in margin_top..=margin_top + plot_h {
-            if y < height {
-                imgbuf.put_pixel(margin_left, y, border_color);
-                imgbuf.put_pixel(margin_left + plot_w, y, border_color);
-            }
-        }
-
-        // Draw spectrum — NMR convention: high ppm on left
-        let spec_color = image::Rgb([26, 58, 107]); // dark navy
-        let n = spectrum.real.len().min(ppm_scale.len());
-        let mut prev_px: Option<(i32, i32)> = None;
-        for i in 0..n {
-            let ppm = ppm_scale[i];
-            if ppm < ppm_lo || ppm > ppm_hi {
-                prev_px = None;
-                continue;
-            }
-            let x_frac = (ppm_hi - ppm) / x_range;
-            let px_x = margin_left as i32 + (x_frac * plot_w as f64) as i32;
-            let y_val = if clip_neg { spectrum.real[i].max(0.0) } else { spectrum.real[i] };
-            let y_frac = 1.0 - (y_val - y_min) / y_range;
-            let px_y = margin_top as i32 + (y_frac * plot_h as f64).clamp(0.0, plot_h as f64) as i32;
-
-            if let Some((px, py)) = prev_px {
-                draw_line(&mut imgbuf, px, py, px_x, px_y, spec_color, width, height);
-            }
-            prev_px = Some((px_x, px_y));
-        }
-
-        // Draw peak markers with collision-avoidant labels
-        if settings.show_peaks {
-            let peak_color = image::Rgb([224, 48, 48]);
-            let leader_color = image::Rgb([200, 120, 120]);
-
-            // Phase 1: Collect all visible peak positions and label info
-            struct PeakLabel {
-                px_x: i32,
-                px_y: i32,
-                label: String,
-                label_x: i32,
-                label_y: i32,
-                label_w: i32,
-                label_h: i32,
-            }
-            let mut labels: Vec<PeakLabel> = Vec::new();
-            let label_pad = (char_h / 2).max(4);   // padding between labels
-
-            for peak in &self.spectrum_view_state.peaks {
-                if peak[0] < ppm_lo || peak[0] > ppm_hi { continue; }
-                let x_frac = (ppm_hi - peak[0]) / x_range;
-                let px_x = margin_left as i32 + (x_frac * plot_w as f64) as i32;
-                let y_val = if clip_neg { peak[1].max(0.0) } else { peak[1] };
-                let y_frac = 1.0 - (y_val - y_min) / y_range;
-                let px_y = margin_top as i32 + (y_frac * plot_h as f64).clamp(0.0, plot_h as f64) as i32;
-
-                let label = format!("{:.2}", peak[0]);
-                let label_w = label.len() as i32 * char_w;
-                let label_h = char_h;
-                let label_x = px_x - label_w / 2;
-                // Natural position: above the marker triangle
-                let label_y = px_y - marker_gap - marker_size - label_pad - label_h;
-
-                labels.push(PeakLabel {
-                    px_x, px_y, label, label_x, label_y, label_w, label_h,
-                });
-            }
-
-            // Phase 2: Collision avoidance — multi-pass, check all pairs
-            labels.sort_by_key(|l| l.label_x);
-            for _pass in 0..5 {
-                let mut any_moved = false;
-                for i in 0..labels.len() {
-                    for _iter in 0..20 {
-                        let mut needs_shift = false;
-                        let mut shift_to = 0i32;
-                        for j in 0..labels.len() {
-                            if j == i { continue; }
-                            let (ax, ay, aw, ah) = (labels[i].label_x, labels[i].label_y, labels[i].label_w, labels[i].label_h);
-                            let (bx, by, bw, bh) = (labels[j].label_x, labels[j].label_y, labels[j].label_w, labels[j].label_h);
-                            // AABB overlap check with padding
-                            if ax < bx + bw + label_pad && bx < ax + aw + label_pad
-                                && ay < by + bh + label_pad && by < ay + ah + label_pad
-                            {
-                                let target = labels[j].label_y - labels[i].label_h - label_pad;
-                                if !needs_shift || target < shift_to {
-                                    shift_to = target;
+    /// Spectra of the session: active one, visibility, colors and offsets
+    fn show_session_window(&mut self, ctx: &egui::Context) {
+        if !self.show_session {
+            return;
+        }
+        let mut activate = None;
+        let mut remove = None;
+        let mut add = false;
+        let mut stack = false;
+        let mut track = false;
+        let active = self.session.active_index();
+        egui::Window::new("🗂 Session Spectra")
+            .open(&mut self.show_session)
+            .default_size([460.0, 260.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                if self.session.is_empty() {
+                    ui.label("No spectra loaded.");
+                } else {
+                    ui.label("The active spectrum is processed; the others are overlaid on it (1D only).");
+                    egui::ScrollArea::vertical().max_height(220.0).show(ui, |ui| {
+                        egui::Grid::new("session_grid").striped(true).show(ui, |ui| {
+                            ui.strong("Active");
+                            ui.strong("Show");
+                            ui.strong("Color");
+                            ui.strong("Name");
+                            ui.strong("Scale");
+                            ui.strong("Offset");
+                            ui.end_row();
+                            for (i, entry) in self.session.entries.iter_mut().enumerate() {
+                                if ui.radio(i == active, "").clicked() {
+                                    activate = Some(i);
+                                }
+                                ui.add_enabled(i != active, egui::Checkbox::without_text(&mut entry.visible))
+                                    .on_disabled_hover_text("The active spectrum is always shown");
+                                ui.color_edit_button_srgb(&mut entry.color);
+                                let name = entry.name();
+                                if entry.is_overlayable() {
+                                    ui.label(name);
+                                } else {
+                                    ui.label(name).on_hover_text("Not overlaid: only processed 1D spectra are");
+                                }
+                                ui.add(egui::DragValue::new(&mut entry.scale).speed(0.01).range(0.0..=1000.0).prefix("×"));
+                                ui.add(egui::DragValue::new(&mut entry.offset).speed(0.01).range(-50.0..=50.0))
+                                    .on_hover_text("Vertical offset, as a fraction of the active spectrum's tallest point");
+                                if ui
+                                    .add_enabled(i != active, egui::Button::new("✕").small())
+                                    .on_hover_text("Close this spectrum")
+                                    .clicked()
+                                {
+                                    remove = Some(i);
                                 }
-                                needs_shift = true;
+                                ui.end_row();
                             }
-                        }
-                        if needs_shift {
-                            // Don't shift above the title area
-                            let min_y = (15 + char_h * 2) as i32;
-                            labels[i].label_y = shift_to.max(min_y);
-                            any_moved = true;
-                        } else {
-                            break;
-                        }
-                    }
+                        });
+                    });
                 }
-                if !any_moved { break; }
-            }
-
-            // Phase 3: Draw markers, leader lines, and labels
-            for pl in &labels {
-                // Triangle marker (pointing down at peak)
-                for dx in -marker_size..=marker_size {
-                    for dy in 0..=marker_size {
-                        if dx.abs() <= dy {
-                            let mx = pl.px_x + dx;
-                            let my = pl.px_y - marker_gap - dy;
-                            if mx >= 0 && mx < width as i32 && my >= 0 && my < height as i32 {
-                                imgbuf.put_pixel(mx as u32, my as u32, peak_color);
-                            }
-                        }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("➕ Add Spectrum…").clicked() {
+                        add = true;
                     }
-                }
-
-                // Leader line if label was displaced from natural position
-                let natural_y = pl.px_y - marker_gap - marker_size - label_pad - pl.label_h;
-                if pl.label_y < natural_y - label_pad {
-                    let line_x = pl.px_x;
-                    let line_top = pl.label_y + pl.label_h + 1;
-                    let line_bot = pl.px_y - marker_gap - marker_size;
-                    if line_top < line_bot {
-                        for ly in line_top..line_bot {
-                            if line_x >= 0 && line_x < width as i32 && ly >= 0 && ly < height as i32 {
-                                imgbuf.put_pixel(line_x as u32, ly as u32, leader_color);
-                            }
+                    ui.separator();
+                    ui.add(egui::DragValue::new(&mut self.session_stack_step).speed(0.01).range(0.0..=10.0).prefix("step "));
+                    if ui.add_enabled(self.session.len() > 1, egui::Button::new("☰ Stack")).clicked() {
+                        stack = true;
+                    }
+                    if ui.add_enabled(self.session.len() > 1, egui::Button::new("Overlay")).clicked() {
+                        for entry in &mut self.session.entries {
+                            entry.offset = 0.0;
                         }
                     }
-                }
-
-                // Draw label text
-                draw_simple_text(
-                    &mut imgbuf,
-                    &pl.label,
-                    pl.label_x.max(0) as u32,
-                    pl.label_y.max(0) as u32,
-                    peak_color,
-                    ts,
-                );
-            }
+                    ui.separator();
+                    if ui
+                        .add_enabled(self.session.len() > 1, egui::Button::new("📈 Shift Perturbation…"))
+                        .on_hover_text("Follow the peaks through the spectra (e.g. a titration) and plot Δδ")
+                        .clicked()
+                    {
+                        track = true;
+                    }
+                });
+            });
+        if stack {
+            self.session.stack(self.session_stack_step);
         }
+        if let Some(index) = activate {
+            self.activate_spectrum(index);
+        } else if let Some(entry) = remove.and_then(|index| self.session.remove(index)) {
+            self.carbon_typing = None;
+            self.status_message = format!("Closed {}", entry.name());
+        }
+        if add {
+            self.add_to_session();
+        }
+        if track {
+            self.track_session_peaks();
+        }
+    }
 
-        // ── Below-plot stacked labels ──
-        let tick_step = smart_tick_step(x_range);
-        let first_tick = (ppm_lo / tick_step).ceil() * tick_step;
-        let tick_len = (4.0 * ms_f).round().max(2.0) as u32;
-        let row_gap = (4.0 * fs_f).round().max(3.0) as u32;
-        let char_h_u = char_h as u32;
+    /// Load another spectrum into the session, keeping the current one
+    fn add_to_session(&mut self) {
+        if let Some(path) = toolbar::open_file_dialog() {
+            self.session_add = true;
+            self.load_path(path);
+        }
+    }
 
-        // Row 1: tick marks + axis labels
-        let tick_label_y = margin_top + plot_h + tick_len + row_gap;
-        {
-            let mut tick = first_tick;
-            while tick <= ppm_hi {
-                let x_frac = (ppm_hi - tick) / x_range;
-                let gx = margin_left + (x_frac * plot_w as f64) as u32;
-                let label = format!("{:.1}", tick);
-                let label_w = label.len() as u32 * (4 * ts);
-                draw_simple_text(
-                    &mut imgbuf,
-                    &label,
-                    gx.saturating_sub(label_w / 2),
-                    tick_label_y,
-                    image::Rgb([60, 60, 70]),
-                    ts,
-                );
-                // Tick mark
-                for dy in 0..tick_len {
-                    if gx < width && margin_top + plot_h + dy < height {
-                        imgbuf.put_pixel(gx, margin_top + plot_h + dy, image::Rgb([100, 100, 110]));
-                    }
-                }
-                tick += tick_step;
+    /// Follow the peaks of the session's 1D spectra of the active nucleus
+    /// through the series, in session order.  Spectra without picked peaks
+    /// are picked with the panel's settings.
+    fn track_session_peaks(&mut self) {
+        let Some(nucleus) = self.session.active().and_then(|s| s.axes.first()).map(|a| a.nucleus.clone()) else {
+            return;
+        };
+        let active = self.session.active_index();
+        let state = &self.pipeline_state;
+        let mut names = Vec::new();
+        let mut peaks = Vec::new();
+        for (i, entry) in self.session.entries.iter().enumerate() {
+            if !entry.is_overlayable() || entry.spectrum.axes.first().map(|a| &a.nucleus) != Some(&nucleus) {
+                continue;
             }
+            let picked = if i == active {
+                &self.spectrum_view_state.peaks[..]
+            } else {
+                entry.parked.as_ref().map(|p| p.annotations.peaks()).unwrap_or_default()
+            };
+            let picked = if picked.is_empty() {
+                let sign = PeakSign::default_for(&entry.spectrum.experiment_type);
+                auto_peaks(&entry.spectrum, state.peak_threshold, state.min_peak_spacing_hz, sign)
+            } else {
+                picked.to_vec()
+            };
+            names.push(entry.name());
+            peaks.push(picked);
+        }
+        if names.len() < 2 {
+            self.status_message = format!("Peak tracking needs at least two processed 1D {} spectra in the session", nucleus);
+            return;
         }
-        let mut next_row_y = tick_label_y + char_h_u + row_gap;
+        let mut series = TitrationSeries {
+            points: (0..names.len()).map(|k| k as f64).collect(),
+            names,
+            peaks,
+            x_label: "Titration point".to_string(),
+            tolerance_ppm: if nucleus == Nucleus::C13 { 0.5 } else { 0.05 },
+            tracks: Vec::new(),
+            nucleus,
+        };
+        series.retrack();
+        self.repro_log.add_entry(
+            "Peak Tracking",
+            &format!(
+                "{} peaks tracked through {} {} spectra ({}) within {:.3} ppm per step\n# largest Δδ {:.4} ppm",
+                series.tracks.len(),
+                series.names.len(),
+                series.nucleus,
+                series.names.join(", "),
+                series.tolerance_ppm,
+                series.tracks.iter().map(|t| t.max_perturbation()).fold(0.0, f64::max),
+            ),
+            "# peak tracking across a series (no NMRPipe equivalent)",
+        );
+        self.status_message = format!("{} peaks tracked through {} spectra", series.tracks.len(), series.names.len());
+        self.titration = Some(series);
+    }
 
-        // Row 2: Integration labels
-        if settings.show_integrations && !self.spectrum_view_state.integrations.is_empty() {
-            let int_color = image::Rgb([76, 175, 80]);
-            let first_raw = self.spectrum_view_state.integrations
-                .first()
-                .map(|r| r.2)
-                .unwrap_or(1.0)
-                .abs()
-                .max(1e-12);
-
-            for &(start_ppm, end_ppm, raw_val) in &self.spectrum_view_state.integrations {
-                let lo = start_ppm.min(end_ppm).max(ppm_lo);
-                let hi = start_ppm.max(end_ppm).min(ppm_hi);
-                if lo >= hi { continue; }
-
-                // Draw dashed boundary lines
-                let x_lo = margin_left as i32 + ((ppm_hi - hi) / x_range * plot_w as f64) as i32;
-                let x_hi = margin_left as i32 + ((ppm_hi - lo) / x_range * plot_w as f64) as i32;
-                let dash_len = (4.0 * ms_f).round().max(2.0) as u32;
-                let gap_len = (2.0 * ms_f).round().max(1.0) as u32;
-                for y in margin_top..margin_top + plot_h {
-                    let cycle = (y - margin_top) % (dash_len + gap_len);
-                    if cycle < dash_len {
-                        if x_lo >= 0 && x_lo < width as i32 {
-                            imgbuf.put_pixel(x_lo as u32, y, int_color);
+    /// Tracked peaks of the session with hand correction, and the Δδ plot
+    fn show_titration_window(&mut self, ctx: &egui::Context) {
+        let Some(series) = self.titration.as_mut() else {
+            return;
+        };
+        let mut open = true;
+        let mut retrack = false;
+        let mut export_csv = false;
+        let mut export_figure = false;
+        egui::Window::new("📈 Chemical-Shift Perturbation")
+            .open(&mut open)
+            .default_size([640.0, 560.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Match tolerance:");
+                    ui.add(
+                        egui::DragValue::new(&mut series.tolerance_ppm)
+                            .speed(0.005)
+                            .range(0.001..=2.0)
+                            .suffix(" ppm"),
+                    )
+                    .on_hover_text("Largest shift of a peak from one spectrum to the next");
+                    if ui.button("⟳ Re-track").on_hover_text("Match the peaks again, discarding corrections").clicked() {
+                        retrack = true;
+                    }
+                    ui.separator();
+                    ui.label("x axis:");
+                    ui.add(egui::TextEdit::singleline(&mut series.x_label).desired_width(140.0));
+                });
+                ui.label("Edit a shift to correct a match; ✕ drops it, ＋ takes the nearest picked peak.");
+                ui.separator();
+
+                let mut remove = None;
+                egui::ScrollArea::both().max_height(220.0).show(ui, |ui| {
+                    egui::Grid::new("titration_grid").striped(true).show(ui, |ui| {
+                        ui.strong("Peak");
+                        for name in &series.names {
+                            ui.strong(name);
                         }
-                        if x_hi >= 0 && x_hi < width as i32 {
-                            imgbuf.put_pixel(x_hi as u32, y, int_color);
+                        ui.strong("max |Δδ|");
+                        ui.end_row();
+                        ui.label("x");
+                        for x in &mut series.points {
+                            ui.add(egui::DragValue::new(x).speed(0.05));
                         }
-                    }
+                        ui.end_row();
+                        for i in 0..series.tracks.len() {
+                            let [r, g, b] = plot_scene::session_color(i);
+                            let start = series.tracks[i].start_ppm().map(|p| format!("δ {:.3}", p)).unwrap_or_default();
+                            ui.colored_label(egui::Color32::from_rgb(r, g, b), start);
+                            for k in 0..series.names.len() {
+                                let nearest = series.nearest_peak(i, k);
+                                ui.horizontal(|ui| match &mut series.tracks[i].shifts[k] {
+                                    Some(shift) => {
+                                        ui.add(egui::DragValue::new(shift).speed(0.0005).fixed_decimals(4));
+                                        if ui.small_button("✕").on_hover_text("Not this peak").clicked() {
+                                            series.tracks[i].shifts[k] = None;
+                                        }
+                                    }
+                                    None => {
+                                        let add = ui
+                                            .add_enabled(nearest.is_some(), egui::Button::new("＋").small())
+                                            .on_hover_text("Take the picked peak nearest to the track");
+                                        if add.clicked() {
+                                            series.tracks[i].shifts[k] = nearest;
+                                        }
+                                    }
+                                });
+                            }
+                            ui.label(format!("{:.4}", series.tracks[i].max_perturbation()));
+                            if ui.small_button("🗑").on_hover_text("Remove this peak").clicked() {
+                                remove = Some(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+                if let Some(i) = remove {
+                    series.tracks.remove(i);
                 }
-                // Integral value label
-                let mid_x = (x_lo + x_hi) / 2;
-                let rel_val = raw_val / first_raw;
-                let h_val = rel_val * self.spectrum_view_state.integration_reference_h;
-                let label = format!("{:.2}H", h_val);
-                let label_w = label.len() as i32 * char_w;
-                let label_x = (mid_x - label_w / 2).max(0) as u32;
-                draw_simple_text(&mut imgbuf, &label, label_x, next_row_y, int_color, ts);
-            }
-            next_row_y += char_h_u + row_gap;
-        }
 
-        // Row 3: Multiplet labels
-        if settings.show_multiplets && !self.spectrum_view_state.multiplets.is_empty() {
-            let mult_color = image::Rgb([0, 96, 170]);
-            for mult in &self.spectrum_view_state.multiplets {
-                if mult.center_ppm < ppm_lo || mult.center_ppm > ppm_hi { continue; }
-                let x_frac = (ppm_hi - mult.center_ppm) / x_range;
-                let px_x = margin_left as i32 + (x_frac * plot_w as f64) as i32;
-                let label = if mult.j_hz > 0.5 {
-                    format!("{} J={:.1}", mult.label, mult.j_hz)
-                } else {
-                    mult.label.clone()
-                };
-                let label_w = label.len() as i32 * char_w;
-                let label_x = (px_x - label_w / 2).max(0) as u32;
-                draw_simple_text(&mut imgbuf, &label, label_x, next_row_y, mult_color, ts);
-            }
-            next_row_y += char_h_u + row_gap;
-        }
-
-        // X-axis title
-        let axis_title = "Chemical Shift (ppm)";
-        let axis_title_w = axis_title.len() as u32 * (4 * ts);
-        draw_simple_text(
-            &mut imgbuf,
-            axis_title,
-            margin_left + plot_w / 2 - axis_title_w / 2,
-            next_row_y + row_gap,
-            image::Rgb([60, 60, 70]),
-            ts,
-        );
+                ui.separator();
+                egui_plot::Plot::new("csp_plot")
+                    .height(220.0)
+                    .x_axis_label(series.x_label.clone())
+                    .y_axis_label("Δδ (ppm)")
+                    .legend(egui_plot::Legend::default())
+                    .show(ui, |plot_ui| {
+                        for (i, track) in series.tracks.iter().enumerate() {
+                            let [r, g, b] = plot_scene::session_color(i);
+                            let color = egui::Color32::from_rgb(r, g, b);
+                            let name = track.start_ppm().map(|p| format!("δ {:.3}", p)).unwrap_or_default();
+                            let points: Vec<[f64; 2]> = track
+                                .perturbations()
+                                .iter()
+                                .zip(&series.points)
+                                .filter_map(|(d, &x)| Some([x, (*d)?]))
+                                .collect();
+                            plot_ui.line(egui_plot::Line::new(egui_plot::PlotPoints::from(points.clone())).color(color).name(&name));
+                            plot_ui.points(egui_plot::Points::new(points).color(color).radius(3.0).name(&name));
+                        }
+                    });
 
-        // Draw title
-        draw_simple_text(&mut imgbuf, title, margin_left, 15, image::Rgb([40, 40, 50]), title_ts);
-
-        // PPM range info
-        let range_info = format!("{:.1} - {:.1} ppm", ppm_hi, ppm_lo);
-        let range_w = range_info.len() as u32 * (4 * ts);
-        draw_simple_text(
-            &mut imgbuf,
-            &range_info,
-            (margin_left + plot_w).saturating_sub(range_w),
-            15,
-            image::Rgb([120, 120, 130]),
-            ts,
-        );
+                ui.horizontal(|ui| {
+                    if ui.button("📋 Copy as CSV").clicked() {
+                        ui.ctx().copy_text(series.to_csv());
+                    }
+                    if ui.button("💾 Export CSV…").clicked() {
+                        export_csv = true;
+                    }
+                    if ui.button("🖼 Export Plot…").on_hover_text("SVG, PNG or PDF, with the export settings' font and size").clicked() {
+                        export_figure = true;
+                    }
+                });
+            });
+        if retrack {
+            series.retrack();
+        }
+        if !open {
+            self.titration = None;
+            return;
+        }
+        if export_csv {
+            self.export_titration_csv();
+        }
+        if export_figure {
+            self.export_titration_plot();
+        }
+    }
 
-        imgbuf.save(path).map_err(|e| e.to_string())
+    fn export_titration_csv(&mut self) {
+        let Some(series) = self.titration.as_ref() else {
+            return;
+        };
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("Export Chemical-Shift Perturbation")
+            .set_file_name("csp.csv")
+            .add_filter("CSV (comma-separated)", &["csv"])
+            .save_file()
+        else {
+            return;
+        };
+        match std::fs::write(&path, series.to_csv()) {
+            Ok(()) => {
+                self.repro_log.add_entry(
+                    "Export Data",
+                    &format!("Chemical-shift perturbation of {} peaks written to {}", series.tracks.len(), path.display()),
+                    "",
+                );
+                self.status_message = format!("CSP table exported to {}", path.display());
+            }
+            Err(e) => self.status_message = format!("Export failed: {}", e),
+        }
     }
 
-    fn export_svg(
-        &self,
-        path: &std::path::Path,
-        spectrum: &SpectrumData,
-        ppm_scale: &[f64],
-        title: &str,
-        ppm_hi: f64, ppm_lo: f64, x_range: f64,
-        y_min: f64, _y_max: f64, y_range: f64,
-        clip_neg: bool,
-        settings: &ExportSettings,
-        width: u32, height: u32,
-        margin_left: u32, _margin_right: u32, margin_top: u32, _margin_bottom: u32,
-        plot_w: u32, plot_h: u32,
-    ) -> Result<(), String> {
-        let mut svg = String::new();
-        svg.push_str(&format!(
-            "<svg xmlns='http://www.w3.org/2000/svg' width='{}' height='{}'>\n",
-            width, height
-        ));
-        svg.push_str("<rect width='100%' height='100%' fill='white'/>\n");
-
-        // Scale factors
-        let ms = settings.marker_scale as f64;
-        let fs = settings.font_scale as f64;
-        let font_sm = (10.0 * fs).round().max(6.0);   // small label font
-        let font_md = (12.0 * fs).round().max(7.0);   // axis label font
-        let font_lg = (16.0 * fs).round().max(8.0);   // title font
-        let font_ax = (13.0 * fs).round().max(7.0);   // axis title font
-        let font_rng = (11.0 * fs).round().max(6.0);  // range info font
-        let marker_h = 8.0 * ms;                       // marker bottom-to-tip
-        let marker_w = 4.0 * ms;                       // marker half-width
-
-        // Grid lines
-        if settings.show_grid {
-            let tick_step = smart_tick_step(x_range);
-            let first_tick = (ppm_lo / tick_step).ceil() * tick_step;
-            let mut tick = first_tick;
-            while tick <= ppm_hi {
-                let x_frac = (ppm_hi - tick) / x_range;
-                let sx = margin_left as f64 + x_frac * plot_w as f64;
-                svg.push_str(&format!(
-                    "<line x1='{:.1}' y1='{}' x2='{:.1}' y2='{}' stroke='#E6E6EB' stroke-width='0.5'/>\n",
-                    sx, margin_top, sx, margin_top + plot_h
-                ));
-                tick += tick_step;
+    fn export_titration_plot(&mut self) {
+        let Some(series) = self.titration.as_ref() else {
+            return;
+        };
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("Export CSP Plot")
+            .set_file_name("csp.svg")
+            .add_filter("SVG", &["svg"])
+            .add_filter("PNG", &["png"])
+            .add_filter("PDF", &["pdf"])
+            .save_file()
+        else {
+            return;
+        };
+        let title = format!("Chemical-shift perturbation — {}", series.nucleus);
+        let settings = &self.export_dialog_state.settings;
+        let result = plot_scene::csp_scene(&series.points, &series.x_label, &series.tracks, &title, settings).and_then(|scene| {
+            let threads = plot_scene::render_threads(self.preferences.export_threads);
+            plot_scene::write_figure(&scene, &path, threads, &plot_scene::RenderProgress::default())
+        });
+        match result {
+            Ok(()) => {
+                self.repro_log.add_entry(
+                    "Export Image",
+                    &format!("Chemical-shift perturbation plot written to {}", path.display()),
+                    "",
+                );
+                self.status_message = format!("CSP plot exported to {}", path.display());
             }
+            Err(e) => self.status_message = format!("Export failed: {}", e.user_message()),
         }
+    }
 
-        // Spectrum polyline
-        let n = spectrum.real.len().min(ppm_scale.len());
-        svg.push_str(&format!(
-            "<polyline fill='none' stroke='#1A3A6B' stroke-width='{:.1}' points='",
-            settings.line_width
-        ));
-        for i in 0..n {
-            let ppm = ppm_scale[i];
-            if ppm < ppm_lo || ppm > ppm_hi { continue; }
-            let x_frac = (ppm_hi - ppm) / x_range;
-            let sx = margin_left as f64 + x_frac * plot_w as f64;
-            let y_val = if clip_neg { spectrum.real[i].max(0.0) } else { spectrum.real[i] };
-            let y_frac = 1.0 - (y_val - y_min) / y_range;
-            let sy = margin_top as f64 + (y_frac * plot_h as f64).clamp(0.0, plot_h as f64);
-            svg.push_str(&format!("{:.1},{:.1} ", sx, sy));
-        }
-        svg.push_str("'/>\n");
-
-        // Plot border
-        svg.push_str(&format!(
-            "<rect x='{}' y='{}' width='{}' height='{}' fill='none' stroke='#64646E' stroke-width='1'/>\n",
-            margin_left, margin_top, plot_w, plot_h
-        ));
+    /// Look for NaN/Inf values in the data, which would otherwise leave a
+    /// blank plot; they are reported and offered for replacement
+    fn check_non_finite(&mut self) {
+        let report = self.session.active().and_then(processing::find_non_finite);
+        let Some(report) = report else {
+            self.non_finite = None;
+            self.non_finite_kept = 0;
+            return;
+        };
+        if report.total() == self.non_finite_kept {
+            return;
+        }
+        log::warn!("Non-finite values in the data: {}", report);
+        self.status_message = format!("{} — ⚠ {} NaN/Inf value(s) in the data", self.status_message, report.total());
+        self.non_finite = Some(report);
+    }
 
-        // Peak markers with collision-avoidant labels
-        if settings.show_peaks {
-            // Collect peak positions and labels
-            struct SvgPeakLabel {
-                sx: f64,
-                sy: f64,
-                label: String,
-                label_x: f64,
-                label_y: f64,
-                label_w: f64,
-                label_h: f64,
-            }
-            let mut labels: Vec<SvgPeakLabel> = Vec::new();
-            let char_w_est = font_sm * 0.6; // approximate char width for font
-            let label_pad = font_sm * 0.4;  // padding between labels
-
-            for peak in &self.spectrum_view_state.peaks {
-                if peak[0] < ppm_lo || peak[0] > ppm_hi { continue; }
-                let x_frac = (ppm_hi - peak[0]) / x_range;
-                let sx = margin_left as f64 + x_frac * plot_w as f64;
-                let y_val = if clip_neg { peak[1].max(0.0) } else { peak[1] };
-                let y_frac = 1.0 - (y_val - y_min) / y_range;
-                let sy = margin_top as f64 + (y_frac * plot_h as f64).clamp(0.0, plot_h as f64);
-
-                let label = format!("{:.2}", peak[0]);
-                let label_w = label.len() as f64 * char_w_est;
-                let label_h = font_sm * 1.2;
-                let label_x = sx - label_w / 2.0;
-                let label_y = sy - marker_h * 2.5 - label_h - label_pad;
-
-                labels.push(SvgPeakLabel {
-                    sx, sy, label, label_x, label_y, label_w, label_h,
-                });
+    /// Reference a loaded or freshly transformed 1D spectrum to its residual
+    /// solvent signal, if enabled for its nucleus in the preferences
+    fn auto_reference_solvent(&mut self) {
+        let Some(spectrum) = self.session.active_mut() else {
+            return;
+        };
+        let enabled = spectrum
+            .axes
+            .first()
+            .is_some_and(|a| self.preferences.solvent_reference.applies_to(&a.nucleus));
+        if !enabled || spectrum.solvent.is_empty() || spectrum.is_2d() || !spectrum.is_frequency_domain {
+            return;
+        }
+        match processing::reference_to_solvent(spectrum, &mut self.repro_log) {
+            Some(r) => {
+                self.status_message = format!(
+                    "{} — referenced to {} ({:+.4} ppm)",
+                    self.status_message, r.solvent, r.shift_ppm
+                );
             }
+            None => log::info!("No residual solvent signal found to reference '{}'", spectrum.solvent),
+        }
+    }
 
-            // Collision avoidance — multi-pass, check all pairs
-            labels.sort_by(|a, b| a.label_x.partial_cmp(&b.label_x).unwrap_or(std::cmp::Ordering::Equal));
-            for _pass in 0..5 {
-                let mut any_moved = false;
-                for i in 0..labels.len() {
-                    for _iter in 0..20 {
-                        let mut needs_shift = false;
-                        let mut shift_to = 0.0f64;
-                        for j in 0..labels.len() {
-                            if j == i { continue; }
-                            let (ax, ay, aw, ah) = (labels[i].label_x, labels[i].label_y, labels[i].label_w, labels[i].label_h);
-                            let (bx, by, bw, bh) = (labels[j].label_x, labels[j].label_y, labels[j].label_w, labels[j].label_h);
-                            if ax < bx + bw + label_pad && bx < ax + aw + label_pad
-                                && ay < by + bh + label_pad && by < ay + ah + label_pad
-                            {
-                                let target = labels[j].label_y - labels[i].label_h - label_pad;
-                                if !needs_shift || target < shift_to {
-                                    shift_to = target;
-                                }
-                                needs_shift = true;
-                            }
-                        }
-                        if needs_shift {
-                            let min_y = 30.0 + font_lg + 4.0;
-                            labels[i].label_y = shift_to.max(min_y);
-                            any_moved = true;
-                        } else {
-                            break;
-                        }
-                    }
+    /// Look for a zero-frequency spike in a freshly transformed 1D spectrum;
+    /// it is removed as its own undo step when the preferences ask for it,
+    /// otherwise only reported
+    fn check_center_glitch(&mut self) {
+        let settings = self.preferences.center_glitch.clone();
+        let Some(spectrum) = self.session.active() else {
+            return;
+        };
+        let Some(glitch) = processing::detect_center_glitch(spectrum, settings.threshold_sigma) else {
+            return;
+        };
+        if settings.auto_remove {
+            self.push_undo(ProcessingOp::CenterGlitchRemoval { ppm: glitch.ppm });
+            let spectrum = self.session.active_mut().unwrap();
+            processing::remove_center_glitch(spectrum, &glitch, &mut self.repro_log);
+            self.status_message = format!(
+                "{} — center glitch at {:.3} ppm removed (Undo restores it)",
+                self.status_message, glitch.ppm
+            );
+        } else {
+            self.status_message = format!(
+                "{} — center glitch at {:.3} ppm ({:.0}× noise); remove it under ✨ Center Glitch",
+                self.status_message, glitch.ppm, glitch.sigma
+            );
+        }
+    }
+
+    /// Progress overlay for a running load, with a Cancel button.
+    fn show_load_overlay(&mut self, ctx: &egui::Context) {
+        let finished = self.pending_load.as_ref().and_then(|p| p.take_result());
+        if let Some(result) = finished {
+            if let Some(pending) = self.pending_load.take() {
+                let loaded = result.spectrum.is_ok();
+                self.finish_load(&pending.path, result, pending.add_to_session);
+                if let Some(request) = self.ipc_waiting.take() {
+                    request.reply(if loaded { Ok(self.ipc_status()) } else { Err(self.status_message.clone()) });
                 }
-                if !any_moved { break; }
             }
+            return;
+        }
+        let Some(pending) = &self.pending_load else {
+            return;
+        };
 
-            // Draw markers, leader lines, labels
-            for pl in &labels {
-                // Triangle marker
-                svg.push_str(&format!(
-                    "<polygon points='{:.1},{:.1} {:.1},{:.1} {:.1},{:.1}' fill='#E03030'/>\n",
-                    pl.sx, pl.sy - marker_h,
-                    pl.sx - marker_w, pl.sy - marker_h * 2.5,
-                    pl.sx + marker_w, pl.sy - marker_h * 2.5
-                ));
+        let name = pending
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| pending.path.display().to_string());
+        egui::Modal::new(egui::Id::new("load_progress_overlay")).show(ctx, |ui| {
+            ui.set_min_width(320.0);
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.strong(format!("Loading {}…", name));
+            });
+            ui.label(format!("Elapsed: {:.1} s", pending.started.elapsed().as_secs_f64()));
+            ui.add_space(6.0);
+            if pending.is_cancelled() {
+                ui.label("Cancelling…");
+            } else if ui.button("✖ Cancel").clicked() {
+                pending.cancel();
+                self.status_message = format!("Cancelling load of {}…", name);
+            }
+        });
+        ctx.request_repaint_after(std::time::Duration::from_millis(100));
+    }
 
-                // Leader line if displaced
-                let natural_y = pl.sy - marker_h * 2.5 - pl.label_h - label_pad;
-                if pl.label_y < natural_y - label_pad {
-                    svg.push_str(&format!(
-                        "<line x1='{:.1}' y1='{:.1}' x2='{:.1}' y2='{:.1}' stroke='#C87878' stroke-width='0.5'/>\n",
-                        pl.sx, pl.label_y + pl.label_h,
-                        pl.sx, pl.sy - marker_h * 2.5
-                    ));
+    /// Carry out the next command-server request, once any load (maybe
+    /// started by the previous request) has finished
+    fn poll_ipc(&mut self) {
+        if self.ipc_waiting.is_some() || self.pending_load.is_some() {
+            return;
+        }
+        let Some(request) = self.ipc_server.as_ref().and_then(IpcServer::try_next) else {
+            return;
+        };
+        let result = match request.command.clone() {
+            IpcCommand::Open { path } => match open::resolve(&path) {
+                Ok(OpenTarget::Dataset(target)) => {
+                    // Default conversion settings: nobody is there to
+                    // confirm a dialog
+                    self.do_load(&target, None);
+                    self.ipc_waiting = Some(request);
+                    return;
                 }
+                Ok(OpenTarget::Project(project)) => self.load_project(&project).map(|_| {
+                    self.status_message = format!("Project loaded: {}", project.display());
+                    self.ipc_status()
+                }),
+                Ok(_) => Err(format!("{} holds several datasets; open one of them", path.display())),
+                Err(e) => Err(e.to_string()),
+            },
+            IpcCommand::Process { steps } => self.ipc_process(steps),
+            IpcCommand::Export { stem, targets } => match self.session.active() {
+                Some(spectrum) => script::export(spectrum, &self.repro_log, &targets, &stem)
+                    .map(|files| {
+                        self.status_message = format!("Exported {} file(s) for a remote request", files.len());
+                        json!({ "files": files })
+                    })
+                    .map_err(|e| e.to_string()),
+                None => Err("no data loaded".to_string()),
+            },
+            IpcCommand::Status => Ok(self.ipc_status()),
+        };
+        request.reply(result);
+    }
 
-                // Label
-                svg.push_str(&format!(
-                    "<text x='{:.0}' y='{:.0}' font-family='sans-serif' font-size='{:.0}' fill='#C02020' text-anchor='middle'>{}</text>\n",
-                    pl.sx, pl.label_y + pl.label_h * 0.8, font_sm, pl.label
-                ));
+    /// Process the loaded data for a remote request: the experiment's
+    /// profile, as Quick Process, or the steps given
+    fn ipc_process(&mut self, steps: Option<ScriptProcessing>) -> Result<serde_json::Value, String> {
+        let Some(spectrum) = self.session.active_mut() else {
+            return Err("no data loaded".to_string());
+        };
+        match steps {
+            None => {
+                let before = self.undo_stack.len();
+                self.handle_pipeline_action(PipelineAction::QuickProcess);
+                if self.undo_stack.len() == before {
+                    return Err(self.status_message.clone());
+                }
+            }
+            Some(steps) => {
+                if !spectrum.is_frequency_domain {
+                    self.fid_snapshot = Some(spectrum.clone());
+                }
+                self.push_undo(ProcessingOp::ProcessingSteps);
+                let spectrum = self.session.active_mut().unwrap();
+                script::process(spectrum, &steps, &mut self.repro_log);
+                if spectrum.is_frequency_domain {
+                    self.domain_tab = DomainTab::FrequencyDomain;
+                }
+                self.status_message = "Processed with the steps of a remote request".to_string();
             }
         }
+        self.spectrum_view_state.auto_scale = true;
+        self.check_non_finite();
+        Ok(self.ipc_status())
+    }
 
-        // ── Below-plot stacked labels ──
-        let tick_step = smart_tick_step(x_range);
-        let first_tick = (ppm_lo / tick_step).ceil() * tick_step;
-        let row_gap = (4.0 * fs).max(3.0);
-
-        // Row 1: tick marks + axis labels
-        let tick_label_y = margin_top as f64 + plot_h as f64 + 6.0 + font_md;
-        {
-            let mut tick = first_tick;
-            while tick <= ppm_hi {
-                let x_frac = (ppm_hi - tick) / x_range;
-                let gx = margin_left as f64 + plot_w as f64 * x_frac;
-                svg.push_str(&format!(
-                    "<text x='{:.0}' y='{:.0}' font-family='sans-serif' font-size='{:.0}' fill='#3C3C46' text-anchor='middle'>{:.1}</text>\n",
-                    gx, tick_label_y, font_md, tick
-                ));
-                tick += tick_step;
+    /// Process the current spectrum with the `-fn` steps of an NMRPipe
+    /// script (`fid.com`, `ft2.com`), as one undo step
+    fn run_nmrpipe_script(&mut self) {
+        let Some(spectrum) = self.session.active() else {
+            self.status_message = "Load a spectrum before running a script".to_string();
+            return;
+        };
+        let Some(path) = toolbar::open_nmrpipe_script_dialog() else {
+            return;
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                self.status_message = format!("Could not read {}: {}", path.display(), e);
+                return;
+            }
+        };
+        let script = command::parse_nmrpipe_script(&text, spectrum.real.len(), spectrum.is_2d());
+        let skipped: Vec<String> =
+            script.skipped.iter().map(|s| format!("{} ({})", s.function, s.reason)).collect();
+        if script.steps.is_empty() {
+            self.status_message = format!("No steps to run in {}", path.display());
+            if !skipped.is_empty() {
+                self.status_message.push_str(&format!(" — skipped: {}", skipped.join("; ")));
+            }
+            return;
+        }
+        if !spectrum.is_frequency_domain {
+            self.fid_snapshot = Some(spectrum.clone());
+        }
+        self.push_undo(ProcessingOp::NmrPipeScript { steps: script.steps.len() });
+        let spectrum = self.session.active_mut().unwrap();
+        // Each step logs itself with its NMRPipe command
+        let result = command::run_imported_script(spectrum, &script, &mut self.repro_log);
+        let transformed = spectrum.is_frequency_domain;
+        self.repro_log.add_entry(
+            "NMRPipe Script",
+            &format!(
+                "Processed with {} ({} steps){}",
+                path.display(),
+                script.steps.len(),
+                if skipped.is_empty() { String::new() } else { format!("\n# skipped: {}", skipped.join("; ")) },
+            ),
+            &format!("# steps imported from {}, logged above", path.display()),
+        );
+        match result {
+            Ok(()) => {
+                self.status_message = format!("Ran {} steps of {}", script.steps.len(), path.display());
+                if !skipped.is_empty() {
+                    self.status_message.push_str(&format!(", {} skipped (see the log)", skipped.len()));
+                }
             }
+            Err(e) => self.status_message = format!("Script stopped: {}", e.user_message()),
+        }
+        if transformed {
+            self.domain_tab = DomainTab::FrequencyDomain;
         }
-        let mut next_row_y = tick_label_y + row_gap;
+        self.spectrum_view_state.auto_scale = true;
+        self.check_non_finite();
+    }
 
-        // Row 2: Integration labels
-        if settings.show_integrations && !self.spectrum_view_state.integrations.is_empty() {
-            let first_raw = self.spectrum_view_state.integrations
-                .first()
-                .map(|r| r.2)
-                .unwrap_or(1.0)
-                .abs()
-                .max(1e-12);
-            for &(start_ppm, end_ppm, raw_val) in &self.spectrum_view_state.integrations {
-                let lo = start_ppm.min(end_ppm).max(ppm_lo);
-                let hi = start_ppm.max(end_ppm).min(ppm_hi);
-                if lo >= hi { continue; }
-                let x_lo = margin_left as f64 + (ppm_hi - hi) / x_range * plot_w as f64;
-                let x_hi = margin_left as f64 + (ppm_hi - lo) / x_range * plot_w as f64;
-                svg.push_str(&format!(
-                    "<line x1='{:.1}' y1='{}' x2='{:.1}' y2='{}' stroke='#4CAF50' stroke-width='1' stroke-dasharray='4,2'/>\n",
-                    x_lo, margin_top, x_lo, margin_top + plot_h
-                ));
-                svg.push_str(&format!(
-                    "<line x1='{:.1}' y1='{}' x2='{:.1}' y2='{}' stroke='#4CAF50' stroke-width='1' stroke-dasharray='4,2'/>\n",
-                    x_hi, margin_top, x_hi, margin_top + plot_h
-                ));
-                let mid_x = (x_lo + x_hi) / 2.0;
-                let rel_val = raw_val / first_raw;
-                let h_val = rel_val * self.spectrum_view_state.integration_reference_h;
-                svg.push_str(&format!(
-                    "<text x='{:.0}' y='{:.0}' font-family='sans-serif' font-size='{:.0}' fill='#4CAF50' text-anchor='middle'>{:.2}H</text>\n",
-                    mid_x, next_row_y + font_sm, font_sm + 1.0, h_val
-                ));
+    /// Save the steps of the undo history as a processing template
+    fn save_template(&mut self) {
+        let name = match self.pipeline_state.template_name.trim() {
+            "" => self.session.active().map(|s| s.experiment_type.to_string()).unwrap_or_default(),
+            name => name.to_string(),
+        };
+        let (template, skipped) = PipelineTemplate::from_history(&name, self.undo_stack.iter().map(|(op, _)| op));
+        if template.steps.is_empty() {
+            self.status_message = "No processing steps that can be saved as a template".to_string();
+            return;
+        }
+        let Some(path) = toolbar::save_template_dialog(&template.name) else {
+            return;
+        };
+        match template.save(&path) {
+            Ok(()) => {
+                self.status_message = format!("Template saved: {} ({} steps)", path.display(), template.steps.len());
+                if !skipped.is_empty() {
+                    self.status_message.push_str(&format!(" — left out: {}", skipped.join(", ")));
+                }
             }
-            next_row_y += font_sm + row_gap;
+            Err(e) => self.status_message = format!("Template not saved: {}", e.user_message()),
         }
+    }
 
-        // Row 3: Multiplet labels
-        if settings.show_multiplets && !self.spectrum_view_state.multiplets.is_empty() {
-            for mult in &self.spectrum_view_state.multiplets {
-                if mult.center_ppm < ppm_lo || mult.center_ppm > ppm_hi { continue; }
-                let x_frac = (ppm_hi - mult.center_ppm) / x_range;
-                let sx = margin_left as f64 + x_frac * plot_w as f64;
-                let label = if mult.j_hz > 0.5 {
-                    format!("{}, J={:.1}", mult.label, mult.j_hz)
-                } else {
-                    mult.label.clone()
-                };
-                svg.push_str(&format!(
-                    "<text x='{:.0}' y='{:.0}' font-family='sans-serif' font-size='{:.0}' fill='#0060AA' text-anchor='middle'>{}</text>\n",
-                    sx, next_row_y + font_sm, font_sm, label
-                ));
+    /// Run a saved template on the current spectrum, as one undo step
+    fn apply_template(&mut self) {
+        let Some(path) = toolbar::open_template_dialog() else {
+            return;
+        };
+        let template = match PipelineTemplate::load(&path) {
+            Ok(template) => template,
+            Err(e) => {
+                self.status_message = format!("Template not loaded: {}", e.user_message());
+                return;
+            }
+        };
+        let Some(spectrum) = self.session.active() else {
+            return;
+        };
+        if !spectrum.is_frequency_domain {
+            self.fid_snapshot = Some(spectrum.clone());
+        }
+        self.push_undo(ProcessingOp::Template { name: template.name.clone(), steps: template.steps.clone() });
+        let spectrum = self.session.active_mut().unwrap();
+        let result = template.apply(spectrum, &self.preferences.processing_profiles, &mut self.repro_log);
+        let transformed = spectrum.is_frequency_domain;
+        match result {
+            Ok(()) => {
+                self.status_message = format!("Processed with the template '{}' ({} steps)", template.name, template.steps.len())
             }
-            next_row_y += font_sm + row_gap;
+            Err(e) => self.status_message = format!("Template stopped: {}", e.user_message()),
         }
+        if transformed {
+            self.domain_tab = DomainTab::FrequencyDomain;
+        }
+        self.spectrum_view_state.auto_scale = true;
+        self.check_non_finite();
+    }
 
-        // X-axis title
-        svg.push_str(&format!(
-            "<text x='{}' y='{:.0}' font-family='sans-serif' font-size='{:.0}' fill='#3C3C46' text-anchor='middle'>Chemical Shift (ppm)</text>\n",
-            margin_left + plot_w / 2,
-            next_row_y + font_ax + row_gap,
-            font_ax
-        ));
+    /// Run a saved template on every dataset of a folder, on a background
+    /// thread
+    fn apply_template_to_folder(&mut self) {
+        let Some(path) = toolbar::open_template_dialog() else {
+            return;
+        };
+        let template = match PipelineTemplate::load(&path) {
+            Ok(template) => template,
+            Err(e) => {
+                self.status_message = format!("Template not loaded: {}", e.user_message());
+                return;
+            }
+        };
+        let Some(folder) = toolbar::open_folder_dialog() else {
+            return;
+        };
+        let result = Arc::new(Mutex::new(None));
+        let shared = Arc::clone(&result);
+        let (run_template, run_folder) = (template.clone(), folder.clone());
+        let profiles = self.preferences.processing_profiles.clone();
+        let ctx = self.egui_ctx.clone();
+        std::thread::spawn(move || {
+            let runs = template::apply_to_folder(&run_template, &run_folder, &profiles);
+            *shared.lock().unwrap() = Some(runs);
+            ctx.request_repaint();
+        });
+        self.status_message = format!("Applying the template '{}' to {}…", template.name, folder.display());
+        self.template_run = Some(TemplateFolderRun { template: template.name, folder, result, logged: false });
+    }
 
-        // Title
-        svg.push_str(&format!(
-            "<text x='{}' y='30' font-family='sans-serif' font-size='{:.0}' fill='#282832'>{}</text>\n",
-            margin_left,
-            font_lg,
-            title.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
-        ));
+    /// Progress and per-dataset outcome of a template run on a folder
+    fn show_template_run_window(&mut self, ctx: &egui::Context) {
+        let Some(run) = self.template_run.as_mut() else {
+            return;
+        };
+        // The worker writes the result once; keep it locked while it is shown
+        let shared = Arc::clone(&run.result);
+        let guard = shared.lock().unwrap();
+        let result = guard.as_ref();
+        if let (Some(result), false) = (result, run.logged) {
+            run.logged = true;
+            let (desc, status) = match result {
+                Ok(runs) => {
+                    let done = runs.iter().filter(|r| r.result.is_ok()).count();
+                    let lines: Vec<String> = runs
+                        .iter()
+                        .map(|r| match &r.result {
+                            Ok(files) => format!("{}: {} file(s)", r.input.display(), files.len()),
+                            Err(e) => format!("{}: {}", r.input.display(), e),
+                        })
+                        .collect();
+                    (
+                        format!(
+                            "Template '{}' applied to {} of {} datasets in {}\n{}",
+                            run.template,
+                            done,
+                            runs.len(),
+                            run.folder.display(),
+                            lines.join("\n")
+                        ),
+                        format!("Template '{}': {} of {} datasets processed", run.template, done, runs.len()),
+                    )
+                }
+                Err(e) => (
+                    format!("Template '{}' not applied to {}: {}", run.template, run.folder.display(), e),
+                    format!("Template run failed: {}", e.user_message()),
+                ),
+            };
+            self.repro_log.add_entry("Template Batch", &desc, "# each dataset has its own log in processed/");
+            self.status_message = status;
+        }
 
-        // PPM range annotation
-        svg.push_str(&format!(
-            "<text x='{}' y='30' font-family='sans-serif' font-size='{:.0}' fill='#78787E' text-anchor='end'>{:.1} – {:.1} ppm</text>\n",
-            margin_left + plot_w, font_rng, ppm_hi, ppm_lo
-        ));
+        let mut open = true;
+        egui::Window::new("📑 Template Run")
+            .open(&mut open)
+            .default_size([560.0, 300.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.label(format!("Template '{}' on {}", run.template, run.folder.display()));
+                ui.separator();
+                match result {
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("Converting and processing…");
+                        });
+                    }
+                    Some(Ok(runs)) => {
+                        egui::ScrollArea::vertical().show(ui, |ui| {
+                            for r in runs {
+                                let name = r.input.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                                match &r.result {
+                                    Ok(files) => {
+                                        ui.label(format!("✔ {} → {} file(s)", name, files.len()))
+                                            .on_hover_text(r.input.display().to_string());
+                                    }
+                                    Err(e) => {
+                                        ui.colored_label(egui::Color32::from_rgb(0xD0, 0x30, 0x30), format!("✖ {}: {}", name, e));
+                                    }
+                                }
+                            }
+                        });
+                        ui.separator();
+                        if ui.button("📂 Open Output Folder").clicked() {
+                            if let Err(e) = workdir::reveal_in_file_manager(&run.folder.join("processed")) {
+                                log::warn!("Could not open the output folder: {}", e);
+                            }
+                        }
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(egui::Color32::from_rgb(0xD0, 0x30, 0x30), e.user_message());
+                    }
+                }
+            });
+        if !open && result.is_some() {
+            self.template_run = None;
+        }
+    }
 
-        svg.push_str("</svg>\n");
-        std::fs::write(path, svg).map_err(|e| e.to_string())
+    /// What is loaded, for command-server replies
+    fn ipc_status(&self) -> serde_json::Value {
+        match self.session.active() {
+            Some(spectrum) => json!({
+                "file": spectrum.source_path,
+                "experiment": spectrum.experiment_type.to_string(),
+                "dimensions": if spectrum.is_2d() { 2 } else { 1 },
+                "frequency_domain": spectrum.is_frequency_domain,
+                "operations": self.repro_log.entries.len(),
+                "message": self.status_message,
+            }),
+            None => json!({ "file": null, "message": self.status_message }),
+        }
     }
 
-    /// Export peak list, integration, multiplet, and J-coupling data to CSV/TSV/TXT.
-    fn export_data_report(&self, path: &std::path::Path) -> Result<(), String> {
-        let spectrum = self.spectrum.as_ref().ok_or("No spectrum loaded")?;
+    /// Conversion cache configured from preferences
+    fn conversion_cache(&self) -> ConversionCache {
+        ConversionCache::new(ConversionCache::default_dir(), &self.preferences.cache)
+    }
 
-        let ext = path
-            .extension()
-            .map(|e| e.to_string_lossy().to_lowercase())
-            .unwrap_or_default();
-        let sep = match ext.as_str() {
-            "csv" => ",",
-            "tsv" => "\t",
-            _ => "\t",
+    /// Run an operation, attaching its wall-clock time and data sizes to the
+    /// log entry it produces.
+    fn timed<R>(&mut self, op: impl FnOnce(&mut Self) -> R) -> R {
+        let points = |app: &Self| app.session.active().map(|s| s.total_points()).unwrap_or(0);
+        let log_start = self.repro_log.len();
+        let points_in = points(self);
+        let start = std::time::Instant::now();
+        let result = op(self);
+        let metrics = OpMetrics {
+            duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+            points_in,
+            points_out: points(self),
         };
+        self.repro_log.attach_metrics(log_start, metrics);
+        result
+    }
 
-        let mut out = String::new();
+    /// Save a snapshot before an operation (for undo)
+    fn push_undo(&mut self, op: ProcessingOp) {
+        self.memory_confirmed = None;
+        if std::mem::take(&mut self.skip_undo_snapshot) {
+            // Undo would restore a state the log no longer matches
+            self.undo_stack.clear();
+            self.redo_stack.clear();
+            self.before_snapshot = None;
+            return;
+        }
+        if let Some(spectrum) = self.session.active() {
+            self.before_snapshot = Some(spectrum.clone());
+            let precision = self.preferences.history_precision;
+            self.undo_stack.push((op, StoredSpectrum::store(spectrum.clone(), precision)));
+            self.redo_stack.clear(); // Clear redo on new action
+        }
+    }
 
-        // ── Header ──
-        out.push_str(&format!(
-            "# NMR Data Report{}\n",
-            if ext == "csv" { "" } else { "" }
-        ));
-        out.push_str(&format!("# Sample: {}\n", spectrum.sample_name));
-        out.push_str(&format!("# Experiment: {}\n", spectrum.experiment_type));
+    /// Bytes held by spectrum arrays: (session spectra and comparison
+    /// snapshots, undo/redo history), parked documents included
+    fn memory_usage(&self) -> (usize, usize) {
+        let parked: Vec<&ParkedDocument> = self.session.entries.iter().filter_map(|e| e.parked.as_ref()).collect();
+        let data = self
+            .session
+            .entries
+            .iter()
+            .map(|e| &e.spectrum)
+            .chain(&self.fid_snapshot)
+            .chain(&self.before_snapshot)
+            .chain(parked.iter().flat_map(|p| p.fid_snapshot.iter().chain(&p.before_snapshot)))
+            .map(SpectrumData::heap_bytes)
+            .sum();
+        let history = self
+            .undo_stack
+            .iter()
+            .chain(&self.redo_stack)
+            .chain(parked.iter().flat_map(|p| p.undo_stack.iter().chain(&p.redo_stack)))
+            .map(|(_, s)| s.heap_bytes())
+            .sum();
+        (data, history)
+    }
+
+    /// The step `action` would record, for the actions that grow the data
+    fn planned_growth(&self, action: &PipelineAction) -> Option<ProcessingOp> {
+        let spectrum = self.session.active()?;
+        let state = &self.pipeline_state;
+        match action {
+            PipelineAction::ApplyZeroFill => {
+                Some(ProcessingOp::ZeroFill { target_size: spectrum.real.len() * (1 << state.zf_factor) })
+            }
+            PipelineAction::ApplyFT2D => Some(ProcessingOp::FourierTransform2D { mode: state.ft2d_mode }),
+            PipelineAction::ApplyLinearPrediction => Some(ProcessingOp::LinearPrediction {
+                order: state.lp_order,
+                predicted: state.lp_predicted,
+                mode: state.lp_mode,
+            }),
+            PipelineAction::ReconstructNus => {
+                Some(ProcessingOp::NusReconstruction { iterations: state.nus_iterations })
+            }
+            _ => None,
+        }
+    }
+
+    /// Memory warning for `action` when running it would go past the budget
+    fn check_memory_budget(&self, action: &PipelineAction) -> Option<MemoryWarning> {
+        let budget = &self.preferences.memory_budget;
+        if !budget.warn || self.memory_confirmed.as_ref() == Some(action) {
+            return None;
+        }
+        let op = self.planned_growth(action)?;
+        let spectrum = self.session.active()?;
+        let current = spectrum.heap_bytes();
+        let snapshot = match self.preferences.history_precision {
+            StoragePrecision::Double => current,
+            StoragePrecision::Single => current / 2,
+        };
+        let (data, history) = self.memory_usage();
+        let projected = data - current + processing::estimated_heap_bytes(spectrum, &op) + history + snapshot;
+        budget
+            .is_exceeded_by(projected)
+            .then(|| MemoryWarning { action: action.clone(), step: op.to_string(), projected, snapshot })
+    }
+
+    /// Undo the last operation
+    fn undo(&mut self) {
+        if let Some((op, snapshot)) = self.undo_stack.pop() {
+            if let Some(current) = self.session.active_mut() {
+                let previous = std::mem::replace(current, snapshot.restore());
+                let precision = self.preferences.history_precision;
+                self.redo_stack.push((op.clone(), StoredSpectrum::store(previous, precision)));
+            }
+            self.before_snapshot = None; // Clear stale comparison
+            self.repro_log.pop_entry();
+            self.refresh_annotation_intensities();
+            self.refresh_phase_2d_preview();
+            self.status_message = format!("Undone: {}", op);
+        }
+    }
+
+    /// Redo the last undone operation
+    fn redo(&mut self) {
+        if let Some((op, snapshot)) = self.redo_stack.pop() {
+            if let Some(current) = self.session.active_mut() {
+                let previous = std::mem::replace(current, snapshot.restore());
+                let precision = self.preferences.history_precision;
+                self.undo_stack.push((op.clone(), StoredSpectrum::store(previous, precision)));
+            }
+            self.refresh_annotation_intensities();
+            self.refresh_phase_2d_preview();
+            self.status_message = format!("Redone: {}", op);
+        }
+    }
+
+    /// Re-read peak intensities and integral areas from the current data so
+    /// they stay meaningful after scaling, phasing, baseline changes or undo.
+    fn refresh_annotation_intensities(&mut self) {
+        let Some(spectrum) = self.session.active() else {
+            return;
+        };
+        if !spectrum.is_frequency_domain || spectrum.is_2d() {
+            return;
+        }
+        let view = &mut self.spectrum_view_state;
+        processing::resample_peak_intensities(spectrum, &mut view.peaks);
+        for region in view.integrations.iter_mut() {
+            region.2 = processing::integrate_region_corrected(spectrum, region.0, region.1, view.integral_baseline);
+        }
+    }
+
+    /// Redraw the open 2D phasing trace from the current data
+    fn refresh_phase_2d_preview(&mut self) {
+        if let (true, Some(spectrum)) = (self.phase_2d_state.active, self.session.active()) {
+            self.phase_2d_state.compute_preview(spectrum);
+        }
+    }
+
+    /// Seed the export tab's custom ppm range with the spectrum's default
+    /// display window (nucleus-aware), unless the user set a custom range.
+    fn init_export_range(&mut self) {
+        let settings = &mut self.export_tab_state.image_settings;
+        if settings.use_custom_range {
+            return;
+        }
+        let Some(spectrum) = self.session.active().filter(|s| s.is_frequency_domain && !s.axes.is_empty()) else {
+            return;
+        };
+        let view_preset = self.spectrum_view_state.range_preset;
+        let (lo, hi) = view_preset.or_else(|| spectrum.default_display_range()).unwrap_or_else(|| {
+            let ppm_scale = spectrum.axes[0].ppm_scale();
+            let ppm_min = ppm_scale.iter().cloned().fold(f64::INFINITY, f64::min);
+            let ppm_max = ppm_scale.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            (ppm_min, ppm_max)
+        });
+        settings.ppm_start = hi;
+        settings.ppm_end = lo;
+    }
+
+    /// Export the current spectrum to a PNG, SVG or PDF file with configurable settings.
+    ///
+    /// Uses the same scene as the export tab preview, so the file matches it.
+    fn export_spectrum_image_with_settings(
+        &self,
+        path: &std::path::Path,
+        settings: &ExportSettings,
+    ) -> NmrResult<()> {
+        let spectrum = self.session.active().ok_or_else(|| NmrError::NotApplicable("No spectrum loaded".to_string()))?;
+        let scene = plot_scene::build_scene(spectrum, &self.spectrum_view_state, settings, &self.repro_log)?;
+        let threads = plot_scene::render_threads(self.preferences.export_threads);
+        plot_scene::write_figure(&scene, path, threads, &plot_scene::RenderProgress::default())
+    }
+
+    /// Export the current spectrum image on a worker thread; see
+    /// [`Self::show_export_progress`]
+    fn start_image_export(&mut self, path: &std::path::Path, settings: &ExportSettings) -> NmrResult<()> {
+        if self.pending_export.is_some() {
+            return Err(NmrError::NotApplicable("another export is still running".to_string()));
+        }
+        let spectrum = self.session.active().ok_or_else(|| NmrError::NotApplicable("No spectrum loaded".to_string()))?;
+        let scene = plot_scene::build_scene(spectrum, &self.spectrum_view_state, settings, &self.repro_log)?;
+        let threads = plot_scene::render_threads(self.preferences.export_threads);
+        self.status_message = format!("Exporting {}…", path.display());
+        self.pending_export = Some(plot_scene::start_export(scene, path, threads, self.egui_ctx.clone()));
+        Ok(())
+    }
+
+    /// Progress of a running image export, with a Cancel button; logs the
+    /// export once it is written
+    fn show_export_progress(&mut self, ctx: &egui::Context) {
+        let Some(export) = &self.pending_export else {
+            return;
+        };
+        if let Some(result) = export.take_result() {
+            let path = export.path.clone();
+            self.pending_export = None;
+            match result {
+                Ok(()) => {
+                    self.status_message = format!("✅ Image exported: {}", path.display());
+                    self.repro_log.add_entry(
+                        "Export Image",
+                        &format!("Exported spectrum image to {}", path.display()),
+                        "",
+                    );
+                }
+                Err(e) => self.status_message = format!("❌ Image export failed: {}", e.user_message()),
+            }
+            return;
+        }
+        egui::Modal::new(egui::Id::new("export_progress_overlay")).show(ctx, |ui| {
+            ui.set_min_width(320.0);
+            ui.strong(format!("Exporting {}…", export.path.display()));
+            ui.add(egui::ProgressBar::new(export.progress.fraction()).show_percentage());
+            ui.label(format!("Elapsed: {:.1} s", export.started.elapsed().as_secs_f64()));
+            ui.add_space(6.0);
+            if export.progress.is_cancelled() {
+                ui.label("Cancelling…");
+            } else if ui.button("✖ Cancel").clicked() {
+                export.progress.cancel();
+            }
+        });
+        ctx.request_repaint_after(std::time::Duration::from_millis(100));
+    }
+
+    /// Export peak list, integration, multiplet, and J-coupling data to CSV/TSV/TXT.
+    /// Write the LTTB-decimated JSON for web viewers; returns the log description.
+    fn export_web_json(&self, path: &std::path::Path) -> Result<String, String> {
+        let spectrum = self.session.active().ok_or("No spectrum loaded")?;
+        let settings = &self.export_tab_state.data_settings;
+        let peaks: Vec<[f64; 2]> = self
+            .spectrum_view_state
+            .peaks
+            .iter()
+            .filter(|_| settings.include_peaks)
+            .filter(|p| !settings.exclude_impurity_peaks || self.spectrum_view_state.impurity_at(p[0]).is_none())
+            .copied()
+            .collect();
+        let export =
+            processing::web_export(spectrum, &peaks, settings.web_points, settings.ppm_decimals).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string(&export).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| e.to_string())?;
+        Ok(format!(
+            "Exported {} of {} points (LTTB decimation) as JSON for web viewers to {}",
+            export.points.len(),
+            export.source_points,
+            path.display()
+        ))
+    }
+
+    fn export_data_report(&self, path: &std::path::Path) -> Result<(), String> {
+        let spectrum = self.session.active().ok_or("No spectrum loaded")?;
+
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let sep = match ext.as_str() {
+            "csv" => ",",
+            "tsv" => "\t",
+            _ => "\t",
+        };
+
+        let mut out = String::new();
+
+        // ── Header ──
+        out.push_str(&format!(
+            "# NMR Data Report{}\n",
+            if ext == "csv" { "" } else { "" }
+        ));
+        out.push_str(&format!("# Sample: {}\n", spectrum.sample_name));
+        out.push_str(&format!("# Experiment: {}\n", spectrum.experiment_type));
+        if let Some(array) = &spectrum.array {
+            out.push_str(&format!("# Array: {} ({} FIDs)\n", array.array, array.slices));
+        }
         out.push_str(&format!("# Data points: {}\n", spectrum.real.len()));
+        if let Some(full) = spectrum.axes.first().filter(|ax| ax.is_extracted()) {
+            out.push_str(&format!(
+                "# Extracted region: points {}–{} of {}\n",
+                full.first_point + 1,
+                full.first_point + full.num_points,
+                full.full_points
+            ));
+        }
         if !spectrum.axes.is_empty() {
-            let ax = &spectrum.axes[0];
+            let ax = spectrum.axes[0].to_standalone();
             out.push_str(&format!(
                 "# Observe freq: {:.4} MHz\n",
                 ax.observe_freq_mhz
@@ -1050,6 +2131,9 @@ impl NmrApp {
                 ax.spectral_width_hz / ax.observe_freq_mhz
             ));
             out.push_str(&format!("# Nucleus: {}\n", ax.nucleus));
+            if let (false, Some(dw), Some(aq)) = (spectrum.is_frequency_domain, ax.dwell_time(), ax.acquisition_time()) {
+                out.push_str(&format!("# Dwell time: {:.3} µs  |  Acquisition time: {:.4} s\n", dw * 1e6, aq));
+            }
         }
         out.push_str(&format!(
             "# Generated: {}\n",
@@ -1058,35 +2142,75 @@ impl NmrApp {
         out.push('\n');
 
         // ── Peak List ──
-        let peaks = &self.spectrum_view_state.peaks;
+        let exclude_impurities = self.export_tab_state.data_settings.exclude_impurity_peaks;
+        let peaks: Vec<[f64; 2]> = self
+            .spectrum_view_state
+            .peaks
+            .iter()
+            .filter(|p| !exclude_impurities || self.spectrum_view_state.impurity_at(p[0]).is_none())
+            .copied()
+            .collect();
+        let peaks = &peaks;
         if !peaks.is_empty() {
             out.push_str(&format!(
                 "# Peak List ({} peaks)\n",
                 peaks.len()
             ));
-            out.push_str(&format!(
-                "Peak_No{}Chemical_Shift_ppm{}Intensity{}Relative_Intensity\n",
-                sep, sep, sep
-            ));
-
-            let max_intensity = peaks
-                .iter()
-                .map(|p| p[1].abs())
-                .fold(0.0f64, f64::max)
-                .max(1e-20);
-
-            for (i, peak) in peaks.iter().enumerate() {
+            let excluded = self.spectrum_view_state.peaks.len() - peaks.len();
+            if excluded > 0 {
+                out.push_str(&format!("# {} solvent / impurity peaks left out\n", excluded));
+            }
+            let mode = self.export_tab_state.data_settings.peak_intensity_mode;
+            if mode == IntensityMode::Absolute {
+                out.push_str(&format!("Peak_No{}Chemical_Shift_ppm{}Intensity", sep, sep));
+            } else {
                 out.push_str(&format!(
-                    "{}{}  {:.4}{}  {:.6e}{}  {:.4}\n",
-                    i + 1,
-                    sep,
-                    peak[0],
-                    sep,
-                    peak[1],
-                    sep,
-                    peak[1] / max_intensity * 100.0
+                    "Peak_No{}Chemical_Shift_ppm{}Intensity{}{}",
+                    sep, sep, sep, mode.column_name()
                 ));
             }
+            out.push_str(&format!("{}FWHM_Hz{}FWHM_ppm", sep, sep));
+            let exchange = !self.spectrum_view_state.exchangeable.is_empty();
+            if exchange {
+                out.push_str(&format!("{}Exchange", sep));
+            }
+            let impurities = !exclude_impurities && !self.spectrum_view_state.common_impurities.is_empty();
+            if impurities {
+                out.push_str(&format!("{}Impurity", sep));
+            }
+            out.push('\n');
+
+            let normalized = processing::normalized_intensities(
+                peaks,
+                mode,
+                self.spectrum_view_state.integrations.first().map(|r| r.2),
+                self.spectrum_view_state.integration_reference_h,
+            );
+
+            for (i, (peak, norm)) in peaks.iter().zip(&normalized).enumerate() {
+                out.push_str(&format!("{}{}  {:.4}{}  {:.6e}", i + 1, sep, peak[0], sep, peak[1]));
+                if mode != IntensityMode::Absolute {
+                    out.push_str(&format!("{}  {:.4}", sep, norm));
+                }
+                match processing::measure_linewidth(spectrum, peak[0]) {
+                    Some(lw) => out.push_str(&format!("{}  {:.2}{}  {:.5}", sep, lw.fwhm_hz, sep, lw.fwhm_ppm())),
+                    None => out.push_str(&format!("{}  -{}  -", sep, sep)),
+                }
+                if exchange {
+                    let flag = if self.spectrum_view_state.is_exchangeable(peak[0]) { "OH/NH" } else { "-" };
+                    out.push_str(&format!("{}  {}", sep, flag));
+                }
+                if impurities {
+                    // Quoted when a name holds the separator (1,2-dichloroethane)
+                    let name = self.spectrum_view_state.impurity_at(peak[0]).unwrap_or("-");
+                    if name.contains(sep) {
+                        out.push_str(&format!("{}  \"{}\"", sep, name));
+                    } else {
+                        out.push_str(&format!("{}  {}", sep, name));
+                    }
+                }
+                out.push('\n');
+            }
             out.push('\n');
         }
 
@@ -1098,8 +2222,13 @@ impl NmrApp {
                 integrations.len()
             ));
             out.push_str(&format!(
-                "Region_No{}Start_ppm{}End_ppm{}Absolute_Integral{}Relative_H{}Width_ppm\n",
-                sep, sep, sep, sep, sep
+                "# Integral baseline: {}\n",
+                self.spectrum_view_state.integral_baseline
+            ));
+            out.push_str("# Uncertainties: noise RMS propagated over the points of each region (1σ)\n");
+            out.push_str(&format!(
+                "Region_No{}Start_ppm{}End_ppm{}Absolute_Integral{}Integral_Sigma{}Relative_H{}Relative_H_Sigma{}Width_ppm\n",
+                sep, sep, sep, sep, sep, sep, sep
             ));
 
             let first_raw = integrations
@@ -1110,11 +2239,18 @@ impl NmrApp {
                 .max(1e-20);
             let ref_h = self.spectrum_view_state.integration_reference_h;
 
-            for (i, &(start, end, raw_val)) in integrations.iter().enumerate() {
+            let errors = processing::integral_uncertainties(
+                spectrum,
+                integrations,
+                self.spectrum_view_state.integral_baseline,
+                ref_h,
+            );
+
+            for (i, (&(start, end, raw_val), error)) in integrations.iter().zip(&errors).enumerate() {
                 let lo = start.min(end);
                 let hi = start.max(end);
                 out.push_str(&format!(
-                    "{}{}  {:.4}{}  {:.4}{}  {:.6e}{}  {:.2}{}  {:.4}\n",
+                    "{}{}  {:.4}{}  {:.4}{}  {:.6e}{}  {:.2e}{}  {:.2}{}  {:.2}{}  {:.4}\n",
                     i + 1,
                     sep,
                     hi,  // higher ppm first (NMR convention)
@@ -1123,8 +2259,12 @@ impl NmrApp {
                     sep,
                     raw_val,
                     sep,
+                    error.sigma,
+                    sep,
                     (raw_val / first_raw) * ref_h,
                     sep,
+                    error.h_sigma,
+                    sep,
                     hi - lo
                 ));
             }
@@ -1139,8 +2279,8 @@ impl NmrApp {
                 multiplets.len()
             ));
             out.push_str(&format!(
-                "Multiplet_No{}Center_ppm{}Multiplicity{}J_Hz{}Num_Lines{}Peak_PPMs\n",
-                sep, sep, sep, sep, sep
+                "Multiplet_No{}Center_ppm{}Multiplicity{}J_Hz{}Num_Lines{}Couplings_Hz{}Peak_PPMs\n",
+                sep, sep, sep, sep, sep, sep
             ));
 
             for (i, mult) in multiplets.iter().enumerate() {
@@ -1149,8 +2289,13 @@ impl NmrApp {
                     .iter()
                     .map(|p| format!("{:.4}", p[0]))
                     .collect();
+                let couplings: Vec<String> = mult
+                    .couplings
+                    .iter()
+                    .map(|c| format!("{:.2}±{:.2} (n={})", c.j_hz, c.sd_hz, c.count))
+                    .collect();
                 out.push_str(&format!(
-                    "{}{}  {:.4}{}  {}{}  {:.2}{}  {}{}  {}\n",
+                    "{}{}  {:.4}{}  {}{}  {:.2}{}  {}{}  {}{}  {}\n",
                     i + 1,
                     sep,
                     mult.center_ppm,
@@ -1161,6 +2306,8 @@ impl NmrApp {
                     sep,
                     mult.num_lines,
                     sep,
+                    couplings.join("; "),
+                    sep,
                     peak_ppms.join("; ")
                 ));
             }
@@ -1196,8 +2343,97 @@ impl NmrApp {
             out.push('\n');
         }
 
+        // ── Fitted Peaks ──
+        let peak_fits = &self.spectrum_view_state.peak_fits;
+        let n_lines: usize = peak_fits.iter().map(|f| f.peaks.len()).sum();
+        if n_lines > 0 {
+            out.push_str(&format!("# Fitted Peaks ({} lines in {} regions)\n", n_lines, peak_fits.len()));
+            out.push_str("# Areas are in integral units (intensity summed over points)\n");
+            out.push_str(&format!(
+                "Line_No{}Region{}Shape{}Center_ppm{}FWHM_Hz{}Amplitude{}Area{}Lorentz_Fraction{}Region_Residual\n",
+                sep, sep, sep, sep, sep, sep, sep, sep
+            ));
+            let lines = peak_fits.iter().flat_map(|fit| fit.peaks.iter().map(move |peak| (fit, peak)));
+            for (i, (fit, peak)) in lines.enumerate() {
+                out.push_str(&format!(
+                    "{}{}  {:.4}-{:.4}{}  {}{}  {:.4}{}  {:.2}{}  {:.6e}{}  {:.6e}{}  {:.2}{}  {:.4}\n",
+                    i + 1,
+                    sep,
+                    fit.hi_ppm,
+                    fit.lo_ppm,
+                    sep,
+                    fit.shape,
+                    sep,
+                    peak.center_ppm,
+                    sep,
+                    peak.fwhm_hz,
+                    sep,
+                    peak.amplitude,
+                    sep,
+                    peak.area,
+                    sep,
+                    peak.lorentz_fraction,
+                    sep,
+                    fit.relative_residual
+                ));
+            }
+            out.push('\n');
+        }
+
+        // ── Fitted Component Traces ──
+        for fit in peak_fits.iter().filter(|f| !f.peaks.is_empty()) {
+            out.push_str(&format!(
+                "# Fitted Components {:.4}-{:.4} ppm ({}): sum of the fit and each line on the offset, in the order of the table above\n",
+                fit.hi_ppm, fit.lo_ppm, fit.shape
+            ));
+            out.push_str("PPM");
+            out.push_str(&format!("{}Fit", sep));
+            for i in 0..fit.components.len() {
+                out.push_str(&format!("{}Line_{}", sep, i + 1));
+            }
+            out.push('\n');
+            for (j, point) in fit.trace.iter().enumerate() {
+                out.push_str(&format!("{:.6}{}{:.6e}", point[0], sep, point[1]));
+                for component in &fit.components {
+                    out.push_str(&format!("{}{:.6e}", sep, component[j][1]));
+                }
+                out.push('\n');
+            }
+            out.push('\n');
+        }
+
+        // ── Carbon Types ──
+        let carbons = &self.spectrum_view_state.carbon_types;
+        if !carbons.is_empty() {
+            out.push_str(&format!(
+                "# Carbon Types ({} peaks: {})\n",
+                carbons.len(),
+                processing::carbon_type_summary(carbons)
+            ));
+            out.push_str("# From the peak signs of the edited spectrum; DEPT90_Ratio is the matching DEPT-90 peak relative to its tallest\n");
+            out.push_str(&format!(
+                "Peak_No{}Chemical_Shift_ppm{}Intensity{}Carbon_Type{}DEPT90_Ratio\n",
+                sep, sep, sep, sep
+            ));
+            for (i, carbon) in carbons.iter().enumerate() {
+                out.push_str(&format!(
+                    "{}{}  {:.4}{}  {:.6e}{}  {}{}  {}\n",
+                    i + 1,
+                    sep,
+                    carbon.ppm,
+                    sep,
+                    carbon.intensity,
+                    sep,
+                    carbon.carbon,
+                    sep,
+                    carbon.dept90.map(|r| format!("{:.3}", r)).unwrap_or_else(|| "-".to_string())
+                ));
+            }
+            out.push('\n');
+        }
+
         // ── Summary ──
-        if peaks.is_empty() && integrations.is_empty() && multiplets.is_empty() && j_couplings.is_empty() {
+        if peaks.is_empty() && integrations.is_empty() && multiplets.is_empty() && j_couplings.is_empty() && n_lines == 0 {
             out.push_str("# No peak, integration, multiplet, or J-coupling data to export.\n");
             out.push_str("# Run peak detection or define integrations first.\n");
         } else {
@@ -1206,6 +2442,10 @@ impl NmrApp {
             out.push_str(&format!("# Integrations: {}\n", integrations.len()));
             out.push_str(&format!("# Multiplets: {}\n", multiplets.len()));
             out.push_str(&format!("# J-Couplings: {}\n", j_couplings.len()));
+            out.push_str(&format!("# Fitted lines: {}\n", n_lines));
+            if !carbons.is_empty() {
+                out.push_str(&format!("# Carbon types: {}\n", processing::carbon_type_summary(carbons)));
+            }
         }
 
         std::fs::write(path, out).map_err(|e| e.to_string())
@@ -1213,17 +2453,108 @@ impl NmrApp {
 
     /// Handle pipeline actions
     fn handle_pipeline_action(&mut self, action: PipelineAction) {
-        let spectrum = match self.spectrum.as_mut() {
+        if let Some(warning) = self.check_memory_budget(&action) {
+            self.confirm_memory = Some(warning);
+            return;
+        }
+        self.run_pipeline_action(action);
+        // The memory choices hold for this one step however it ended,
+        // unless it waits on the magnitude confirmation
+        if self.confirm_magnitude.is_none() {
+            self.memory_confirmed = None;
+            self.skip_undo_snapshot = false;
+        }
+    }
+
+    /// Run a pipeline action that is within the memory budget
+    fn run_pipeline_action(&mut self, action: PipelineAction) {
+        let spectrum = match self.session.active_mut() {
             Some(s) => s,
             None => return,
         };
 
         match action {
+            PipelineAction::QuickProcess => {
+                let experiment = spectrum.experiment_type.clone();
+                let Some(profile) = self.preferences.processing_profiles.profile_for(&experiment).cloned() else {
+                    self.status_message = format!(
+                        "No processing profile for {} data (Preferences → Processing profiles)",
+                        experiment
+                    );
+                    return;
+                };
+                self.fid_snapshot = Some(spectrum.clone());
+                self.push_undo(ProcessingOp::QuickProcess { experiment: experiment.clone() });
+                let spectrum = self.session.active_mut().unwrap();
+                profiles::apply_profile(spectrum, &profile, &mut self.repro_log);
+                let is_2d = spectrum.is_2d();
+                self.status_message = format!("Processed with the {} profile", experiment);
+                if !is_2d {
+                    self.auto_reference_solvent();
+                    self.check_center_glitch();
+                }
+                self.domain_tab = DomainTab::FrequencyDomain;
+            }
+            PipelineAction::RemoveGroupDelay => {
+                let Some(grpdly) = self.session.active().and_then(|s| s.group_delay) else {
+                    return;
+                };
+                self.push_undo(ProcessingOp::GroupDelayCorrection { grpdly });
+                let spectrum = self.session.active_mut().unwrap();
+                match processing::correct_group_delay(spectrum, grpdly, &mut self.repro_log) {
+                    Ok(()) => self.status_message = format!("Removed the digital-filter group delay ({:.2} points)", grpdly),
+                    Err(e) => {
+                        self.undo_stack.pop();
+                        self.status_message = format!("Group delay not removed: {}", e);
+                    }
+                }
+            }
+            PipelineAction::ApplyDeadTime => {
+                let points = self.pipeline_state.dead_time_points;
+                let mode = self.pipeline_state.dead_time_mode;
+                self.push_undo(ProcessingOp::DeadTimeCorrection { points, mode });
+                let spectrum = self.session.active_mut().unwrap();
+                processing::dead_time_correct(spectrum, points, mode, &mut self.repro_log);
+                self.status_message = format!("Dead time: first {} points ({})", points, mode);
+            }
+            PipelineAction::ApplyLinearPrediction => {
+                let order = self.pipeline_state.lp_order;
+                let predicted = self.pipeline_state.lp_predicted;
+                let mode = self.pipeline_state.lp_mode;
+                self.push_undo(ProcessingOp::LinearPrediction { order, predicted, mode });
+                let spectrum = self.session.active_mut().unwrap();
+                match processing::linear_predict(spectrum, order, predicted, mode, &mut self.repro_log) {
+                    Ok(()) => {
+                        self.status_message = format!("Linear prediction: +{} points ({}, order {})", predicted, mode, order);
+                    }
+                    Err(e) => {
+                        self.undo_stack.pop();
+                        self.status_message = format!("Linear prediction failed: {}", e);
+                    }
+                }
+            }
+            PipelineAction::ReconstructNus => {
+                let iterations = self.pipeline_state.nus_iterations;
+                self.push_undo(ProcessingOp::NusReconstruction { iterations });
+                let spectrum = self.session.active_mut().unwrap();
+                match processing::nus_reconstruct(spectrum, iterations, &mut self.repro_log) {
+                    Ok(()) => {
+                        self.status_message = format!(
+                            "NUS reconstructed by IST ({} iterations) — ready for the 2D FT",
+                            iterations
+                        );
+                    }
+                    Err(e) => {
+                        self.undo_stack.pop();
+                        self.status_message = format!("NUS reconstruction failed: {}", e);
+                    }
+                }
+            }
             PipelineAction::ApplyApodization => {
                 let wf = pipeline_panel::get_window_function(&self.pipeline_state);
                 let op = ProcessingOp::Apodization(wf.clone());
                 self.push_undo(op);
-                let spectrum = self.spectrum.as_mut().unwrap();
+                let spectrum = self.session.active_mut().unwrap();
                 processing::apply_apodization(spectrum, &wf, &mut self.repro_log);
                 self.status_message = format!("Applied apodization: {}", wf);
             }
@@ -1234,42 +2565,72 @@ impl NmrApp {
                     target_size: target,
                 };
                 self.push_undo(op);
-                let spectrum = self.spectrum.as_mut().unwrap();
+                let spectrum = self.session.active_mut().unwrap();
                 processing::zero_fill(spectrum, target, &mut self.repro_log);
                 self.status_message = format!("Zero-filled to {} points", target);
             }
             PipelineAction::ApplyFT => {
                 // Snapshot the FID before transforming so user can flip back
-                if let Some(s) = &self.spectrum {
+                if let Some(s) = self.session.active() {
                     self.fid_snapshot = Some(s.clone());
                 }
                 let use_imaginary = self.pipeline_state.ft_use_imaginary;
                 let op = ProcessingOp::FourierTransform { use_imaginary };
                 self.push_undo(op);
-                let spectrum = self.spectrum.as_mut().unwrap();
+                let spectrum = self.session.active_mut().unwrap();
                 processing::fourier_transform(spectrum, use_imaginary, &mut self.repro_log);
                 self.status_message = format!(
                     "Fourier Transform applied ({})",
                     if use_imaginary { "Complex" } else { "Real-only" }
                 );
+                self.auto_reference_solvent();
+                self.check_center_glitch();
                 self.domain_tab = DomainTab::FrequencyDomain;
             }
+            PipelineAction::ApplyInverseFT => {
+                self.push_undo(ProcessingOp::InverseFourierTransform);
+                let spectrum = self.session.active_mut().unwrap();
+                processing::inverse_fourier_transform(spectrum, &mut self.repro_log);
+                let vendor = spectrum.provenance == Provenance::VendorProcessed;
+                // The old FID would hide the new one on the time domain tab
+                self.fid_snapshot = None;
+                self.domain_tab = DomainTab::TimeDomain;
+                self.status_message = if vendor {
+                    "Inverse FT applied — pseudo-FID of the vendor spectrum, ready to re-apodize".to_string()
+                } else {
+                    "Inverse FT applied — back in the time domain".to_string()
+                };
+            }
             PipelineAction::ApplyFT2D => {
+                let mode = self.pipeline_state.ft2d_mode;
+                if mode == Ft2dMode::Magnitude && !self.magnitude_confirmed && processing::has_2d_imaginary(spectrum) {
+                    self.confirm_magnitude = Some(PipelineAction::ApplyFT2D);
+                    return;
+                }
+                self.magnitude_confirmed = false;
                 // Snapshot the FID before transforming so user can undo
-                if let Some(s) = &self.spectrum {
+                if let Some(s) = self.session.active() {
                     self.fid_snapshot = Some(s.clone());
                 }
-                let op = ProcessingOp::FourierTransform2D;
+                let op = ProcessingOp::FourierTransform2D { mode };
                 self.push_undo(op);
-                let spectrum = self.spectrum.as_mut().unwrap();
+                let spectrum = self.session.active_mut().unwrap();
                 let n_rows = spectrum.data_2d.len();
                 let n_cols = spectrum.data_2d.first().map(|r| r.len()).unwrap_or(0);
-                processing::fourier_transform_2d(spectrum, &mut self.repro_log);
+                let nus_pending = spectrum.nus_schedule.is_some();
+                spectrum.f1_acquisition = self.pipeline_state.f1_acquisition;
+                processing::fourier_transform_2d(spectrum, mode, &mut self.repro_log);
                 let new_rows = spectrum.data_2d.len();
                 let new_cols = spectrum.data_2d.first().map(|r| r.len()).unwrap_or(0);
                 self.status_message = format!(
-                    "2D Fourier Transform: {}×{} → {}×{} (magnitude mode)",
-                    n_rows, n_cols, new_rows, new_cols
+                    "2D Fourier Transform: {}×{} → {}×{} ({} mode, {} in t1){}",
+                    n_rows,
+                    n_cols,
+                    new_rows,
+                    new_cols,
+                    mode,
+                    spectrum.f1_acquisition,
+                    if nus_pending { " — ⚠ NUS data was not reconstructed, expect artefacts" } else { "" }
                 );
                 self.domain_tab = DomainTab::FrequencyDomain;
             }
@@ -1278,25 +2639,27 @@ impl NmrApp {
                 let ph1 = self.pipeline_state.ph1;
                 let op = ProcessingOp::PhaseCorrection { ph0, ph1 };
                 self.push_undo(op);
-                let spectrum = self.spectrum.as_mut().unwrap();
+                let spectrum = self.session.active_mut().unwrap();
                 processing::phase_correct(spectrum, ph0, ph1, &mut self.repro_log);
                 self.status_message = format!("Phase correction: PH0={:.1}°, PH1={:.1}°", ph0, ph1);
             }
             PipelineAction::ApplyAutoPhase => {
                 let op = ProcessingOp::AutoPhase;
                 self.push_undo(op);
-                let spectrum = self.spectrum.as_mut().unwrap();
+                let spectrum = self.session.active_mut().unwrap();
                 let (ph0, ph1) = processing::auto_phase(spectrum, &mut self.repro_log);
                 self.pipeline_state.ph0 = ph0;
                 self.pipeline_state.ph1 = ph1;
                 self.status_message = format!("Auto phase: PH0={:.1}°, PH1={:.1}°", ph0, ph1);
             }
             PipelineAction::ApplyBaselineCorrection => {
-                let op = ProcessingOp::BaselineCorrection;
+                let method = pipeline_panel::get_baseline_method(&self.pipeline_state);
+                let op = ProcessingOp::BaselineCorrection { method };
                 self.push_undo(op);
-                let spectrum = self.spectrum.as_mut().unwrap();
-                processing::baseline_correct(spectrum, &mut self.repro_log);
-                self.status_message = "Baseline correction applied".to_string();
+                let spectrum = self.session.active_mut().unwrap();
+                processing::baseline_correct(spectrum, &method, &mut self.repro_log);
+                self.pipeline_state.show_baseline_preview = false;
+                self.status_message = format!("Baseline correction applied ({})", method);
             }
             PipelineAction::ApplyManualBaseline => {
                 let points = self.spectrum_view_state.baseline_points.clone();
@@ -1309,7 +2672,7 @@ impl NmrApp {
                         num_points: points.len(),
                     };
                     self.push_undo(op);
-                    let spectrum = self.spectrum.as_mut().unwrap();
+                    let spectrum = self.session.active_mut().unwrap();
                     processing::manual_baseline_correct(spectrum, &points, &mut self.repro_log);
                     self.spectrum_view_state.baseline_points.clear();
                     self.spectrum_view_state.baseline_picking = false;
@@ -1324,9 +2687,14 @@ impl NmrApp {
                     !self.spectrum_view_state.baseline_picking;
                 if self.spectrum_view_state.baseline_picking {
                     // Disable other picking modes
+                    self.spectrum_view_state.enhance.picking = false;
                     self.spectrum_view_state.peak_picking = false;
                     self.spectrum_view_state.integration_picking = false;
                     self.spectrum_view_state.j_coupling_picking = false;
+                    self.spectrum_view_state.auto_j_picking = false;
+                    self.spectrum_view_state.linewidth_picking = false;
+                    self.spectrum_view_state.fit_picking = false;
+                    self.spectrum_view_state.solvent_picking = false;
                     self.status_message =
                         "Baseline picking ON — click on the spectrum to place anchor points"
                             .to_string();
@@ -1339,6 +2707,16 @@ impl NmrApp {
                 self.status_message = "Baseline points cleared".to_string();
             }
             PipelineAction::ApplySolventSuppression => {
+                let regions = std::mem::take(&mut self.spectrum_view_state.solvent_regions);
+                if !regions.is_empty() {
+                    self.push_undo(ProcessingOp::SolventSuppressionRegions { regions: regions.clone() });
+                    let spectrum = self.session.active_mut().unwrap();
+                    processing::solvent_suppress_regions(spectrum, &regions, &mut self.repro_log);
+                    self.spectrum_view_state.solvent_picking = false;
+                    self.spectrum_view_state.solvent_drag = None;
+                    self.status_message = format!("Solvent suppression of {} picked regions", regions.len());
+                    return;
+                }
                 let center = self.pipeline_state.solvent_center;
                 let width = self.pipeline_state.solvent_width;
                 let op = ProcessingOp::SolventSuppression {
@@ -1346,41 +2724,122 @@ impl NmrApp {
                     width_ppm: width,
                 };
                 self.push_undo(op);
-                let spectrum = self.spectrum.as_mut().unwrap();
+                let spectrum = self.session.active_mut().unwrap();
                 processing::solvent_suppress(spectrum, center, width, &mut self.repro_log);
                 self.status_message = format!("Solvent suppression at {:.2} ppm", center);
             }
+            PipelineAction::ToggleSolventPicking => {
+                let view = &mut self.spectrum_view_state;
+                view.solvent_picking = !view.solvent_picking;
+                view.solvent_drag = None;
+                if view.solvent_picking {
+                    view.enhance.picking = false;
+                    view.peak_picking = false;
+                    view.baseline_picking = false;
+                    view.integration_picking = false;
+                    view.j_coupling_picking = false;
+                    view.auto_j_picking = false;
+                    view.linewidth_picking = false;
+                    view.fit_picking = false;
+                    self.status_message =
+                        "Solvent region picking ON — drag across each signal to suppress".to_string();
+                } else {
+                    self.status_message = "Solvent region picking OFF".to_string();
+                }
+            }
+            PipelineAction::RemoveSolventRegion(index) => {
+                if index < self.spectrum_view_state.solvent_regions.len() {
+                    let (lo, hi) = self.spectrum_view_state.solvent_regions.remove(index);
+                    self.status_message = format!("Removed suppression region {:.2}–{:.2} ppm", lo, hi);
+                }
+            }
+            PipelineAction::ClearSolventRegions => {
+                self.spectrum_view_state.solvent_regions.clear();
+                self.spectrum_view_state.solvent_drag = None;
+                self.status_message = "Solvent suppression regions cleared".to_string();
+            }
+            PipelineAction::ApplyReferenceDeconvolution => {
+                let center_ppm = self.pipeline_state.refdec_center;
+                let width_ppm = self.pipeline_state.refdec_width;
+                let target_lw_hz = self.pipeline_state.refdec_lw;
+                self.push_undo(ProcessingOp::ReferenceDeconvolution { center_ppm, width_ppm, target_lw_hz });
+                let spectrum = self.session.active_mut().unwrap();
+                match processing::reference_deconvolution(spectrum, center_ppm, width_ppm, target_lw_hz, &mut self.repro_log) {
+                    Ok(peak_ppm) => {
+                        self.status_message = format!(
+                            "Reference deconvolution on the line at {:.3} ppm ({:.2} Hz target)",
+                            peak_ppm, target_lw_hz
+                        );
+                    }
+                    Err(e) => {
+                        self.undo_stack.pop();
+                        self.status_message = format!("Reference deconvolution failed: {}", e);
+                    }
+                }
+            }
+            PipelineAction::RemoveCenterGlitch => {
+                let threshold = self.preferences.center_glitch.threshold_sigma;
+                let Some(glitch) = processing::detect_center_glitch(spectrum, threshold) else {
+                    self.status_message = format!(
+                        "No center glitch above {:.0}× noise (1D frequency-domain spectra only)",
+                        threshold
+                    );
+                    return;
+                };
+                self.push_undo(ProcessingOp::CenterGlitchRemoval { ppm: glitch.ppm });
+                let spectrum = self.session.active_mut().unwrap();
+                processing::remove_center_glitch(spectrum, &glitch, &mut self.repro_log);
+                self.status_message = format!(
+                    "Center glitch at {:.3} ppm removed ({} point(s) interpolated)",
+                    glitch.ppm,
+                    glitch.end - glitch.start + 1
+                );
+            }
             PipelineAction::DetectPeaks => {
                 let threshold = self.pipeline_state.peak_threshold;
                 let min_spacing_hz = self.pipeline_state.min_peak_spacing_hz;
-                // Convert Hz to index distance using spectral width and data size
-                let n = spectrum.real.len();
-                let sw_hz = spectrum
-                    .axes
-                    .first()
-                    .map(|a| a.spectral_width_hz)
-                    .unwrap_or(n as f64);
-                let pts_per_hz = if sw_hz > 0.0 { n as f64 / sw_hz } else { 1.0 };
-                let min_dist = ((min_spacing_hz * pts_per_hz) as usize).max(2);
-                let peaks = processing::detect_peaks(spectrum, threshold, min_dist);
+                let sign = self.pipeline_state.peak_sign;
+                let peaks = auto_peaks(spectrum, threshold, min_spacing_hz, sign);
+                let negative = peaks.iter().filter(|p| p[1] < 0.0).count();
                 let peak_ppm_list: Vec<String> = peaks.iter().take(20).map(|p| format!("{:.3}", p[0])).collect();
                 let desc = format!(
-                    "Found {} peaks (threshold {:.0}%, min spacing {:.1} Hz): [{}]{}",
-                    peaks.len(), threshold * 100.0, min_spacing_hz,
+                    "Found {} peaks, {} negative (threshold {:.0}%, min spacing {:.1} Hz, {}): [{}]{}",
+                    peaks.len(), negative, threshold * 100.0, min_spacing_hz, sign,
                     peak_ppm_list.join(", "),
                     if peaks.len() > 20 { "..." } else { "" }
                 );
                 self.repro_log.add_entry("Peak Detection", &desc, "# automatic peak picking (no NMRPipe equivalent)");
                 self.status_message = format!(
-                    "Detected {} peaks (threshold {:.0}%, min spacing {:.1} Hz)",
-                    peaks.len(), threshold * 100.0, min_spacing_hz
+                    "Detected {} peaks, {} negative (threshold {:.0}%, min spacing {:.1} Hz)",
+                    peaks.len(), negative, threshold * 100.0, min_spacing_hz
                 );
+                let nucleus = spectrum.display_nucleus();
+                if let Some(nucleus) = nucleus.filter(|_| {
+                    self.preferences.solvent_reference.annotate_peaks && spectrum.is_frequency_domain && !spectrum.is_2d()
+                }) {
+                    let labels = processing::annotate_solvent_and_impurities(&peaks, &spectrum.solvent, &nucleus);
+                    if !labels.is_empty() {
+                        let listed: Vec<String> = labels.iter().map(|(ppm, name)| format!("{} {:.2}", name, ppm)).collect();
+                        self.repro_log.add_entry(
+                            "Solvent & Impurity Peaks",
+                            &format!("{} peaks flagged in {}: {}", labels.len(), spectrum.solvent.trim(), listed.join(", ")),
+                            "# solvent / impurity table lookup (no NMRPipe equivalent)",
+                        );
+                        self.status_message.push_str(&format!(", {} flagged as solvent or impurity", labels.len()));
+                    }
+                    self.spectrum_view_state.common_impurities = labels;
+                }
+                self.spectrum_view_state.carbon_types.clear();
                 self.spectrum_view_state.peaks = peaks;
             }
             PipelineAction::ClearPeaks => {
                 let n = self.spectrum_view_state.peaks.len();
                 self.spectrum_view_state.peaks.clear();
                 self.spectrum_view_state.multiplets.clear();
+                self.spectrum_view_state.exchangeable.clear();
+                self.spectrum_view_state.common_impurities.clear();
+                self.spectrum_view_state.carbon_types.clear();
+                self.spectrum_view_state.d2o_difference = None;
                 self.repro_log.add_entry("Clear Peaks", &format!("Cleared {} peaks and associated multiplets", n), "");
                 self.status_message = "Peaks cleared".to_string();
             }
@@ -1389,9 +2848,14 @@ impl NmrApp {
                     !self.spectrum_view_state.peak_picking;
                 if self.spectrum_view_state.peak_picking {
                     // Disable other picking modes
+                    self.spectrum_view_state.enhance.picking = false;
                     self.spectrum_view_state.baseline_picking = false;
                     self.spectrum_view_state.integration_picking = false;
                     self.spectrum_view_state.j_coupling_picking = false;
+                    self.spectrum_view_state.auto_j_picking = false;
+                    self.spectrum_view_state.linewidth_picking = false;
+                    self.spectrum_view_state.fit_picking = false;
+                    self.spectrum_view_state.solvent_picking = false;
                     self.status_message =
                         "Peak picking ON — click to add peaks, Shift+click to remove nearest"
                             .to_string();
@@ -1409,42 +2873,72 @@ impl NmrApp {
                     self.status_message = "No peaks to remove".to_string();
                 }
             }
-            PipelineAction::DetectMultiplets => {
+            PipelineAction::CompareIsotopeShifts => self.compare_isotope_shifts(),
+            PipelineAction::ComparePeakLists => self.compare_peak_lists(false),
+            PipelineAction::CompareD2oShake => self.compare_peak_lists(true),
+            PipelineAction::IdentifyCommonImpurities => self.identify_common_impurities(),
+            PipelineAction::ClassifyCarbons => self.open_carbon_typing(),
+            PipelineAction::DetectMultiplets | PipelineAction::DetectMultipletsInView => {
+                let range = match action {
+                    PipelineAction::DetectMultipletsInView => match self.spectrum_view_state.visible_ppm {
+                        Some(range) => Some(range),
+                        None => {
+                            self.status_message = "No spectrum view to restrict to".to_string();
+                            return;
+                        }
+                    },
+                    _ => None,
+                };
                 // Detect peaks first if not done yet
                 if self.spectrum_view_state.peaks.is_empty() {
-                    let threshold = self.pipeline_state.peak_threshold;
-                    let min_spacing_hz = self.pipeline_state.min_peak_spacing_hz;
-                    let n = spectrum.real.len();
-                    let sw_hz = spectrum
-                        .axes
-                        .first()
-                        .map(|a| a.spectral_width_hz)
-                        .unwrap_or(n as f64);
-                    let pts_per_hz = if sw_hz > 0.0 { n as f64 / sw_hz } else { 1.0 };
-                    let min_dist = ((min_spacing_hz * pts_per_hz) as usize).max(2);
-                    self.spectrum_view_state.peaks =
-                        processing::detect_peaks(spectrum, threshold, min_dist);
+                    self.spectrum_view_state.peaks = auto_peaks(
+                        spectrum,
+                        self.pipeline_state.peak_threshold,
+                        self.pipeline_state.min_peak_spacing_hz,
+                        self.pipeline_state.peak_sign,
+                    );
                 }
                 let obs_mhz = spectrum
                     .axes
                     .first()
                     .map(|a| a.observe_freq_mhz)
                     .unwrap_or(400.0);
-                let multiplets = processing::detect_multiplets(
-                    &self.spectrum_view_state.peaks,
-                    20.0, // max J = 20 Hz
-                    obs_mhz,
-                );
+                let params = self.pipeline_state.multiplet;
+                let in_range = |ppm: f64| range.is_none_or(|(lo, hi)| ppm >= lo && ppm <= hi);
+                let peaks: Vec<[f64; 2]> =
+                    self.spectrum_view_state.peaks.iter().copied().filter(|p| in_range(p[0])).collect();
+                let multiplets = processing::detect_multiplets(&peaks, &params, obs_mhz);
                 let summary: Vec<String> = multiplets.iter().map(|m| m.to_string()).collect();
-                let desc = format!("Detected {} multiplets from {} peaks: {}",
-                    multiplets.len(), self.spectrum_view_state.peaks.len(), summary.join("; "));
+                let region = match range {
+                    Some((lo, hi)) => format!(" in {:.3}–{:.3} ppm", lo, hi),
+                    None => String::new(),
+                };
+                let desc = format!(
+                    "Detected {} multiplets from {} peaks{} (max J {:.1} Hz, max line ratio {:.1}, min {} lines): {}",
+                    multiplets.len(),
+                    peaks.len(),
+                    region,
+                    params.max_j_hz,
+                    params.max_intensity_ratio,
+                    params.min_lines,
+                    summary.join("; ")
+                );
                 self.repro_log.add_entry("Multiplet Detection", &desc, "# automatic multiplet analysis (no NMRPipe equivalent)");
                 self.status_message = format!(
-                    "Detected {} multiplets: {}",
+                    "Detected {} multiplets{}: {}",
                     multiplets.len(),
+                    region,
                     summary.join("; ")
                 );
-                self.spectrum_view_state.multiplets = multiplets;
+                let view = &mut self.spectrum_view_state;
+                if range.is_some() {
+                    // Replace only the multiplets inside the region
+                    view.multiplets.retain(|m| !in_range(m.center_ppm));
+                    view.multiplets.extend(multiplets);
+                    view.multiplets.sort_by(|a, b| b.center_ppm.total_cmp(&a.center_ppm));
+                } else {
+                    view.multiplets = multiplets;
+                }
             }
             PipelineAction::ClearMultiplets => {
                 let n = self.spectrum_view_state.multiplets.len();
@@ -1457,9 +2951,14 @@ impl NmrApp {
                     !self.spectrum_view_state.integration_picking;
                 if self.spectrum_view_state.integration_picking {
                     // Disable other picking modes
+                    self.spectrum_view_state.enhance.picking = false;
                     self.spectrum_view_state.peak_picking = false;
                     self.spectrum_view_state.baseline_picking = false;
                     self.spectrum_view_state.j_coupling_picking = false;
+                    self.spectrum_view_state.auto_j_picking = false;
+                    self.spectrum_view_state.linewidth_picking = false;
+                    self.spectrum_view_state.fit_picking = false;
+                    self.spectrum_view_state.solvent_picking = false;
                     self.spectrum_view_state.integration_start = None;
                     self.status_message =
                         "Integration picking ON — click start and end points on the spectrum"
@@ -1481,9 +2980,11 @@ impl NmrApp {
                     !self.spectrum_view_state.j_coupling_picking;
                 if self.spectrum_view_state.j_coupling_picking {
                     // Disable other picking modes
+                    self.spectrum_view_state.enhance.picking = false;
                     self.spectrum_view_state.peak_picking = false;
                     self.spectrum_view_state.baseline_picking = false;
                     self.spectrum_view_state.integration_picking = false;
+                    self.spectrum_view_state.auto_j_picking = false;
                     self.spectrum_view_state.j_coupling_first = None;
                     self.status_message =
                         "J-coupling measurement ON — click two peaks to measure spacing"
@@ -1493,6 +2994,28 @@ impl NmrApp {
                     self.status_message = "J-coupling measurement OFF".to_string();
                 }
             }
+            PipelineAction::ToggleAutoJPicking => {
+                let view = &mut self.spectrum_view_state;
+                view.auto_j_picking = !view.auto_j_picking;
+                if view.auto_j_picking {
+                    view.enhance.picking = false;
+                    view.peak_picking = false;
+                    view.baseline_picking = false;
+                    view.integration_picking = false;
+                    view.j_coupling_picking = false;
+                    view.linewidth_picking = false;
+                    view.fit_picking = false;
+                    view.solvent_picking = false;
+                    view.j_coupling_first = None;
+                    self.status_message = if view.multiplets.is_empty() {
+                        "Automatic J ON — detect multiplets first, then click one".to_string()
+                    } else {
+                        "Automatic J ON — click a multiplet to extract its couplings".to_string()
+                    };
+                } else {
+                    self.status_message = "Automatic J OFF".to_string();
+                }
+            }
             PipelineAction::ClearJCouplings => {
                 let n = self.spectrum_view_state.j_couplings.len();
                 self.spectrum_view_state.j_couplings.clear();
@@ -1500,55 +3023,571 @@ impl NmrApp {
                 self.repro_log.add_entry("Clear J-Couplings", &format!("Cleared {} J-coupling measurements", n), "");
                 self.status_message = "J-coupling measurements cleared".to_string();
             }
-            PipelineAction::None => {}
-        }
-    }
-
-    /// Save the current project (spectrum + annotations) to a JSON file
-    fn save_project(&self, path: &std::path::Path) -> Result<(), String> {
-        let save = ProjectSave {
-            spectrum: self.spectrum.clone(),
-            fid_snapshot: self.fid_snapshot.clone(),
-            is_frequency_domain: self.spectrum.as_ref().map(|s| s.is_frequency_domain).unwrap_or(false),
+            PipelineAction::ToggleLinewidthPicking => {
+                let view = &mut self.spectrum_view_state;
+                view.linewidth_picking = !view.linewidth_picking;
+                if view.linewidth_picking {
+                    view.enhance.picking = false;
+                    view.peak_picking = false;
+                    view.baseline_picking = false;
+                    view.integration_picking = false;
+                    view.j_coupling_picking = false;
+                    view.auto_j_picking = false;
+                    view.fit_picking = false;
+                    view.solvent_picking = false;
+                    self.status_message =
+                        "Linewidth measurement ON — click a peak to measure its FWHM".to_string();
+                } else {
+                    self.status_message = "Linewidth measurement OFF".to_string();
+                }
+            }
+            PipelineAction::ClearLinewidths => {
+                self.spectrum_view_state.linewidths.clear();
+                self.status_message = "Linewidth measurements cleared".to_string();
+            }
+            PipelineAction::TogglePeakFitPicking => {
+                let view = &mut self.spectrum_view_state;
+                view.fit_picking = !view.fit_picking;
+                view.fit_start = None;
+                if view.fit_picking {
+                    view.enhance.picking = false;
+                    view.peak_picking = false;
+                    view.baseline_picking = false;
+                    view.integration_picking = false;
+                    view.j_coupling_picking = false;
+                    view.auto_j_picking = false;
+                    view.linewidth_picking = false;
+                    self.status_message = if view.peaks.is_empty() {
+                        "Peak fitting ON — click start and end of a region (one line per picked peak, else the tallest)"
+                            .to_string()
+                    } else {
+                        "Peak fitting ON — click start and end of a region to fit its picked peaks".to_string()
+                    };
+                } else {
+                    self.status_message = "Peak fitting OFF".to_string();
+                }
+            }
+            PipelineAction::ClearPeakFits => {
+                let n = self.spectrum_view_state.peak_fits.len();
+                self.spectrum_view_state.peak_fits.clear();
+                self.spectrum_view_state.fit_start = None;
+                self.repro_log.add_entry("Clear Peak Fits", &format!("Cleared {} peak fits", n), "");
+                self.status_message = "Peak fits cleared".to_string();
+            }
+            PipelineAction::ShowPeakTable => self.show_peak_table = true,
+            PipelineAction::ApplyT1NoiseSuppression => {
+                let method = self.pipeline_state.t1_method;
+                let strength = self.pipeline_state.t1_strength;
+                self.push_undo(ProcessingOp::T1NoiseSuppression { method, strength });
+                let spectrum = self.session.active_mut().unwrap();
+                processing::suppress_t1_noise(spectrum, method, strength, &mut self.repro_log);
+                self.status_message = format!(
+                    "t1 noise suppressed ({}, {:.0}%) — cosmetic, undo to restore intensities",
+                    method,
+                    strength * 100.0
+                );
+            }
+            PipelineAction::CalibrateF1 => {
+                let (observed, target) = (self.pipeline_state.f1_observed_ppm, self.pipeline_state.f1_true_ppm);
+                if observed == target {
+                    self.status_message = "F1 calibration: the signal already reads that ppm".to_string();
+                    return;
+                }
+                self.push_undo(ProcessingOp::F1Calibration { shift_ppm: target - observed });
+                let spectrum = self.session.active_mut().unwrap();
+                if let Some(shift) = processing::calibrate_f1(spectrum, observed, target, &mut self.repro_log) {
+                    self.status_message = format!("F1 axis calibrated ({:+.4} ppm)", shift);
+                }
+            }
+            PipelineAction::ApplyMagnitude2D => {
+                if !self.session.active().is_some_and(processing::has_2d_imaginary) {
+                    self.status_message = "No imaginary 2D data — the spectrum is already real/magnitude".to_string();
+                    return;
+                }
+                if !std::mem::take(&mut self.magnitude_confirmed) {
+                    self.confirm_magnitude = Some(PipelineAction::ApplyMagnitude2D);
+                    return;
+                }
+                self.push_undo(ProcessingOp::Magnitude2D);
+                let spectrum = self.session.active_mut().unwrap();
+                processing::magnitude_2d(spectrum, &mut self.repro_log);
+                self.status_message = "Converted to 2D magnitude (undo to restore the quadrants)".to_string();
+            }
+            PipelineAction::ToggleEnhancePicking => {
+                let view = &mut self.spectrum_view_state;
+                view.enhance.picking = !view.enhance.picking;
+                view.enhance.start = None;
+                if view.enhance.picking {
+                    view.peak_picking = false;
+                    view.baseline_picking = false;
+                    view.integration_picking = false;
+                    view.j_coupling_picking = false;
+                    view.auto_j_picking = false;
+                    view.linewidth_picking = false;
+                    view.fit_picking = false;
+                    view.solvent_picking = false;
+                    self.status_message =
+                        "Resolution enhancement preview — click start and end of the region".to_string();
+                } else {
+                    self.status_message = "Resolution enhancement picking OFF".to_string();
+                }
+            }
+            PipelineAction::ClearEnhancePreview => {
+                self.spectrum_view_state.enhance = ResolutionPreview::default();
+                self.status_message = "Resolution enhancement preview cleared".to_string();
+            }
+            PipelineAction::PickCrossPeaks => {
+                let threshold = self.pipeline_state.cross_threshold;
+                let spectrum = self.session.active().unwrap();
+                let mut picked = processing::pick_cross_peaks(spectrum, threshold);
+                // Re-picking keeps the labels of peaks found again
+                let previous = std::mem::take(&mut self.contour_view_state.cross_peaks);
+                for peak in &mut picked {
+                    if let Some(old) = previous
+                        .iter()
+                        .find(|o| o.f2_ppm == peak.f2_ppm && o.f1_ppm == peak.f1_ppm)
+                    {
+                        peak.label = old.label.clone();
+                    }
+                }
+                self.repro_log.add_entry(
+                    "Cross Peak Picking",
+                    &format!("Picked {} cross peaks above {:.0}% of max |I|", picked.len(), threshold * 100.0),
+                    "# 2D cross-peak picking (no NMRPipe equivalent)",
+                );
+                self.status_message = format!("{} cross peaks picked", picked.len());
+                self.contour_view_state.cross_peaks = picked;
+            }
+            PipelineAction::IntegrateVolumes => {
+                let region = self.pipeline_state.volume_region;
+                let spectrum = self.session.active().unwrap();
+                let peaks = &mut self.contour_view_state.cross_peaks;
+                if peaks.is_empty() {
+                    self.status_message = "Pick cross peaks before integrating volumes".to_string();
+                } else {
+                    for peak in peaks.iter_mut() {
+                        peak.volume = processing::cross_peak_volume(spectrum, peak.f2_ppm, peak.f1_ppm, &region);
+                    }
+                    let integrated = peaks.iter().filter(|p| p.volume.is_some()).count();
+                    self.repro_log.add_entry(
+                        "Cross Peak Volumes",
+                        &format!(
+                            "Integrated {} cross peaks over {} regions ±{:.3} ppm (F2) × ±{:.2} ppm (F1), local plane baseline subtracted",
+                            integrated, region.shape, region.f2_half_width, region.f1_half_width
+                        ),
+                        "# 2D volume integration (no NMRPipe equivalent)",
+                    );
+                    self.status_message = format!("{} cross-peak volumes integrated", integrated);
+                    self.contour_view_state.volume_region = Some(region);
+                    self.show_correlation_table = true;
+                }
+            }
+            PipelineAction::TransferAssignments => self.transfer_assignments(),
+            PipelineAction::ShowCorrelationTable => self.show_correlation_table = true,
+            PipelineAction::ShowReferenceLibrary => self.show_reference_library = true,
+            PipelineAction::SaveTemplate => self.save_template(),
+            PipelineAction::ApplyTemplate => self.apply_template(),
+            PipelineAction::ApplyTemplateToFolder => self.apply_template_to_folder(),
+            PipelineAction::RunExternalTool(index) => self.run_external_tool(index),
+            PipelineAction::ClearToolResults => {
+                self.spectrum_view_state.predicted_sticks.clear();
+                self.spectrum_view_state.suggested_assignments.clear();
+            }
+            PipelineAction::None => {}
+        }
+    }
+
+    /// Save the current project (spectrum + annotations) to a JSON file.
+    /// `raw_data` is the bundled raw data path relative to the project file.
+    fn save_project(&self, path: &std::path::Path, raw_data: Option<std::path::PathBuf>) -> Result<(), String> {
+        write_project(path, &self.project_save(path, raw_data))
+    }
+
+    /// Project state of the open spectrum, with paths relative to `path`
+    fn project_save(&self, path: &std::path::Path, raw_data: Option<std::path::PathBuf>) -> ProjectSave {
+        ProjectSave {
+            spectrum: self.session.active().cloned(),
+            fid_snapshot: self.fid_snapshot.clone(),
+            is_frequency_domain: self.session.active().map(|s| s.is_frequency_domain).unwrap_or(false),
             peaks: self.spectrum_view_state.peaks.clone(),
+            exchangeable: self.spectrum_view_state.exchangeable.clone(),
+            common_impurities: self.spectrum_view_state.common_impurities.clone(),
+            carbon_types: self.spectrum_view_state.carbon_types.clone(),
+            intensity_mode: self.spectrum_view_state.intensity_mode,
             multiplets: self.spectrum_view_state.multiplets.clone(),
             integrations: self.spectrum_view_state.integrations.clone(),
             integration_reference_h: self.spectrum_view_state.integration_reference_h,
+            integral_baseline: Some(self.spectrum_view_state.integral_baseline),
+            integral_labels: self.spectrum_view_state.integral_labels,
+            integral_trace: self.spectrum_view_state.integral_trace,
             j_couplings: self.spectrum_view_state.j_couplings.clone(),
             baseline_points: self.spectrum_view_state.baseline_points.clone(),
+            cross_peaks: self.contour_view_state.cross_peaks.clone(),
+            volume_region: self.contour_view_state.volume_region,
+            overlays: self.spectrum_view_state.reference_overlays.clone(),
+            axis_direction: Some(self.spectrum_view_state.axis_direction),
             theme: format!("{:?}", self.current_theme),
-            sample_name: self.spectrum.as_ref().map(|s| s.sample_name.clone()).unwrap_or_default(),
+            sample_name: self.session.active().map(|s| s.sample_name.clone()).unwrap_or_default(),
+            raw_data,
+            source_relative: self.session.active().and_then(|s| {
+                relink::relative_to(&s.source_path, path.parent()?)
+            }),
+        }
+    }
+
+    /// Save the zoomed ppm range of the 1D spectrum as a project of its
+    /// own, with the annotations inside the range, and open it in a new
+    /// window to be processed and exported independently
+    fn extract_visible_region(&mut self) {
+        let Some((lo, hi)) = self.spectrum_view_state.visible_ppm else {
+            self.status_message = "No spectrum view to extract".to_string();
+            return;
+        };
+        let Some(region) = self.session.active().and_then(|s| s.region(lo, hi)) else {
+            self.status_message = "Only a zoomed region of a 1D spectrum can be extracted".to_string();
+            return;
+        };
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("Save Region as Project")
+            .set_file_name(format!("{}.nmrproj", region.sample_name))
+            .add_filter("NMR Project", &["nmrproj"])
+            .save_file()
+        else {
+            return;
+        };
+
+        let inside = |ppm: f64| ppm >= lo && ppm <= hi;
+        let mut save = self.project_save(&path, None);
+        save.sample_name = region.sample_name.clone();
+        save.spectrum = Some(region);
+        save.fid_snapshot = None;
+        save.peaks.retain(|p| inside(p[0]));
+        save.exchangeable.retain(|&ppm| inside(ppm));
+        save.common_impurities.retain(|(ppm, _)| inside(*ppm));
+        save.carbon_types.retain(|c| inside(c.ppm));
+        save.multiplets.retain(|m| inside(m.center_ppm));
+        save.integrations.retain(|&(a, b, _)| inside(a) && inside(b));
+        save.j_couplings.retain(|&(a, b, _, _)| inside(a) && inside(b));
+        save.baseline_points.retain(|p| inside(p[0]));
+        if let Err(e) = write_project(&path, &save) {
+            self.status_message = format!("Save failed: {}", e);
+            return;
+        }
+
+        let axis = &save.spectrum.as_ref().unwrap().axes[0];
+        self.repro_log.add_entry(
+            "Extract Region",
+            &format!(
+                "{:.3}–{:.3} ppm ({} points) saved as {}",
+                axis.index_to_ppm(axis.num_points - 1),
+                axis.reference_ppm,
+                axis.num_points,
+                path.display()
+            ),
+            &format!("nmrPipe -fn EXT -x1 {:.3}ppm -xn {:.3}ppm -sw", hi, lo),
+        );
+        let opened = std::env::current_exe().and_then(|exe| std::process::Command::new(exe).arg(&path).spawn());
+        self.status_message = match opened {
+            Ok(_) => format!("Region {:.2}–{:.2} ppm opened in a new window", lo, hi),
+            Err(e) => format!("Region saved to {} (could not open a new window: {})", path.display(), e),
+        };
+    }
+
+    /// Regenerate the exports chosen under Preferences → Automatic reports
+    /// into `reports/` next to `project`; returns how many files were written.
+    fn write_auto_reports(&mut self, project: &std::path::Path) -> Result<usize, String> {
+        let report = self.preferences.auto_report.clone();
+        let Some(is_2d) = self.session.active().map(|s| s.is_2d()) else {
+            return Ok(0);
         };
-        let json = serde_json::to_string_pretty(&save).map_err(|e| format!("Serialize error: {}", e))?;
-        std::fs::write(path, json).map_err(|e| format!("Write error: {}", e))?;
+        if !report.enabled {
+            return Ok(0);
+        }
+        let dir = AutoReportSettings::dir_for(project);
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Create error: {}", e))?;
+        let stem = project
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "report".to_string());
+        let mut written = 0;
+
+        // Figures use the Export tab's settings (1D only, like the tab)
+        if !is_2d {
+            self.init_export_range();
+            let settings = self.export_tab_state.image_settings.to_export_settings();
+            for (on, ext) in [(report.png, "png"), (report.svg, "svg"), (report.pdf, "pdf")] {
+                if on {
+                    self.export_spectrum_image_with_settings(&dir.join(format!("{}.{}", stem, ext)), &settings)
+                        .map_err(|e| e.user_message())?;
+                    written += 1;
+                }
+            }
+        }
+        if report.csv {
+            self.export_data_report(&dir.join(format!("{}.csv", stem)))?;
+            written += 1;
+        }
+        if report.log {
+            self.repro_log
+                .save_text(&dir.join(format!("{}_log.txt", stem)))
+                .map_err(|e| format!("Write error: {}", e))?;
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// Status line after saving `project`, regenerating its reports
+    fn saved_project_status(&mut self, project: &std::path::Path) -> String {
+        let saved = format!("Project saved: {}", project.display());
+        self.log_mirror.project = Some(project.to_path_buf());
+        match self.write_auto_reports(project) {
+            Ok(0) => saved,
+            Ok(n) => format!("{} ({} reports updated in {})", saved, n, AutoReportSettings::dir_for(project).display()),
+            Err(e) => format!("{}, but writing reports failed: {}", saved, e),
+        }
+    }
+
+    /// Save the project as a portable bundle: `bundle/<name>.nmrproj` plus
+    /// a copy of the raw vendor data under `bundle/raw/`.  The copy runs in
+    /// the background; [`Self::show_bundle_progress`] writes the project
+    /// file when it finishes, and packs the bundle into `archive` when the
+    /// bundle is saved as a zip file.
+    fn start_project_bundle(&mut self, bundle_dir: &std::path::Path, archive: Option<PathBuf>) -> Result<(), String> {
+        let spectrum = self.session.active().ok_or("No spectrum loaded")?;
+        std::fs::create_dir_all(bundle_dir).map_err(|e| format!("Create error: {}", e))?;
+        let mut copy = bundle::start_copy_raw_data(
+            &spectrum.source_path,
+            &spectrum.vendor_format,
+            bundle_dir,
+            self.egui_ctx.clone(),
+        );
+        copy.archive = archive;
+        self.bundle_copy = Some(copy);
         Ok(())
     }
 
+    /// Progress of a running bundle copy; saves the project once it is done.
+    fn show_bundle_progress(&mut self, ctx: &egui::Context) {
+        let Some(copy) = &self.bundle_copy else {
+            return;
+        };
+        if let Some(result) = copy.take_result() {
+            let bundle_dir = copy.bundle.clone();
+            let archive = copy.archive.clone();
+            let zipped = copy.zipping;
+            self.bundle_copy = None;
+            // A bundle built for a zip file lives in a temporary folder
+            let remove_staging = || {
+                let _ = std::fs::remove_dir_all(&bundle_dir);
+                if let Some(staging) = bundle_dir.parent() {
+                    let _ = std::fs::remove_dir(staging);
+                }
+            };
+            if zipped {
+                remove_staging();
+                self.status_message = match result {
+                    Ok(archive) => format!("Project bundle saved: {}", archive.display()),
+                    Err(e) => format!("Zipping the bundle failed: {}", e),
+                };
+                return;
+            }
+            let project_file = bundle::project_file_in(&bundle_dir);
+            let saved = result
+                .map_err(|e| format!("Copying raw data failed: {}", e))
+                .and_then(|raw| self.save_project(&project_file, Some(raw)));
+            self.status_message = match (saved, archive) {
+                (Ok(()), Some(archive)) => {
+                    if let Err(e) = self.write_auto_reports(&project_file) {
+                        log::warn!("Writing reports into the bundle failed: {}", e);
+                    }
+                    self.bundle_copy = Some(bundle::start_zip_bundle(&bundle_dir, &archive, self.egui_ctx.clone()));
+                    format!("Zipping the bundle into {}…", archive.display())
+                }
+                (Ok(()), None) => {
+                    self.log_mirror.project = Some(project_file.clone());
+                    match self.write_auto_reports(&project_file) {
+                        Ok(n) if n > 0 => format!("Project bundle saved: {} ({} reports updated)", bundle_dir.display(), n),
+                        Ok(_) => format!("Project bundle saved: {}", bundle_dir.display()),
+                        Err(e) => format!("Project bundle saved: {}, but writing reports failed: {}", bundle_dir.display(), e),
+                    }
+                }
+                (Err(e), archive) => {
+                    if archive.is_some() {
+                        remove_staging();
+                    }
+                    format!("Save failed: {}", e)
+                }
+            };
+            return;
+        }
+        egui::Modal::new(egui::Id::new("bundle_progress_overlay")).show(ctx, |ui| {
+            ui.set_min_width(320.0);
+            ui.strong(format!("Saving bundle {}…", copy.archive.as_ref().unwrap_or(&copy.bundle).display()));
+            ui.label(if copy.zipping { "Zipping the bundle" } else { "Copying raw data" });
+            ui.add(egui::ProgressBar::new(copy.progress()).show_percentage());
+        });
+    }
+
+    /// Send the current peaks to the configured external tool `index`.
+    fn run_external_tool(&mut self, index: usize) {
+        let (Some(spectrum), Some(tool)) = (self.session.active(), self.preferences.external_tools.get(index)) else {
+            return;
+        };
+        if self.pending_tool.is_some() {
+            self.status_message = "An external tool is already running".to_string();
+            return;
+        }
+        let request = external::ToolRequest::new(
+            spectrum,
+            &self.spectrum_view_state.peaks,
+            &self.spectrum_view_state.multiplets,
+            &self.contour_view_state.cross_peaks,
+        );
+        self.status_message = format!("Running {}…", tool.name);
+        self.pending_tool = Some(external::start_tool(tool, request, self.egui_ctx.clone()));
+    }
+
+    /// Wait for the external tool, then show its predictions.
+    fn show_tool_progress(&mut self, ctx: &egui::Context) {
+        let Some(pending) = &self.pending_tool else {
+            return;
+        };
+        if let Some(result) = pending.take_result() {
+            let tool = pending.tool.clone();
+            self.pending_tool = None;
+            let response = match result {
+                Ok(r) => r,
+                Err(e) => {
+                    self.status_message = format!("External tool failed: {}", e.user_message());
+                    return;
+                }
+            };
+            let mut desc = format!(
+                "{}: {} predicted lines, {} suggested assignments",
+                tool.name,
+                response.sticks.len(),
+                response.assignments.len()
+            );
+            if !self.contour_view_state.cross_peaks.is_empty() && !response.assignments.is_empty() {
+                let pairs: Vec<(f64, String)> =
+                    response.assignments.iter().map(|a| (a.ppm, a.label.clone())).collect();
+                let labelled = processing::transfer_assignments(
+                    &mut self.contour_view_state.cross_peaks,
+                    &pairs,
+                    self.pipeline_state.assign_tolerance_ppm,
+                );
+                desc.push_str(&format!(" ({} cross peaks labelled)", labelled));
+            }
+            self.repro_log.add_entry(
+                "External Tool",
+                &desc,
+                &format!("# {} < request.json (no NMRPipe equivalent)", tool.command_line()),
+            );
+            self.status_message = if response.message.is_empty() {
+                desc
+            } else {
+                format!("{} — {}", desc, response.message)
+            };
+            self.spectrum_view_state.predicted_sticks = response.sticks;
+            self.spectrum_view_state.suggested_assignments = response.assignments;
+            return;
+        }
+        let mut cancel = false;
+        egui::Window::new("🧩 External Tool")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-12.0, -36.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(format!("Running {}…", pending.tool.name));
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+        if cancel {
+            pending.cancel.cancel();
+        }
+    }
+
+    /// Show 1D spectra with the ppm axis in `direction`.  A linked
+    /// expansion recorded in the other direction is dropped.
+    fn set_axis_direction(&mut self, direction: AxisDirection) {
+        let view = &mut self.spectrum_view_state;
+        if view.axis_direction != direction {
+            view.axis_direction = direction;
+            view.linked_view = None;
+            view.auto_scale = true;
+        }
+    }
+
+    /// Load a project and report the outcome in the status bar
+    fn open_project(&mut self, path: &std::path::Path) {
+        match self.load_project(path) {
+            Ok(_) => {
+                let name = self.session.active()
+                    .map(|s| s.sample_name.clone())
+                    .unwrap_or_else(|| "unknown".to_string());
+                self.status_message = format!("Project loaded: {}", name);
+            }
+            Err(e) => self.status_message = format!("Load failed: {}", e),
+        }
+    }
+
     /// Load a project from a JSON file
     fn load_project(&mut self, path: &std::path::Path) -> Result<(), String> {
         let json = std::fs::read_to_string(path).map_err(|e| format!("Read error: {}", e))?;
         let save: ProjectSave = serde_json::from_str(&json).map_err(|e| format!("Parse error: {}", e))?;
 
-        self.spectrum = save.spectrum;
+        match save.spectrum {
+            Some(spectrum) => self.session.replace_active(spectrum, plot_scene::session_color(0)),
+            None => self.session = Session::default(),
+        }
         self.fid_snapshot = save.fid_snapshot;
         self.spectrum_view_state.peaks = save.peaks;
+        self.spectrum_view_state.exchangeable = save.exchangeable;
+        self.spectrum_view_state.common_impurities = save.common_impurities;
+        self.spectrum_view_state.carbon_types = save.carbon_types;
+        self.spectrum_view_state.d2o_difference = None;
+        self.spectrum_view_state.intensity_mode = save.intensity_mode;
         self.spectrum_view_state.multiplets = save.multiplets;
         self.spectrum_view_state.integrations = save.integrations;
         self.spectrum_view_state.integration_reference_h = save.integration_reference_h;
+        self.spectrum_view_state.integral_baseline = save.integral_baseline.unwrap_or(IntegralBaseline::None);
+        self.spectrum_view_state.integral_labels = save.integral_labels;
+        self.spectrum_view_state.integral_trace = save.integral_trace;
         self.spectrum_view_state.j_couplings = save.j_couplings;
         self.spectrum_view_state.baseline_points = save.baseline_points;
         self.spectrum_view_state.auto_scale = true;
+        self.set_axis_direction(save.axis_direction.unwrap_or(self.preferences.axis_direction));
+        self.spectrum_view_state.restore_linked_view = self.spectrum_view_state.link_zoom;
+        self.contour_view_state.cross_peaks = save.cross_peaks;
+        self.contour_view_state.volume_region = save.volume_region;
+        self.overlaid_references = save.overlays.iter().map(|t| t.name.clone()).collect();
+        if !save.overlays.is_empty() {
+            self.reference_library
+                .get_or_insert_with(|| ReferenceLibrary::open(&ReferenceLibrary::default_dir()));
+        }
+        self.spectrum_view_state.reference_overlays = save.overlays;
+        self.sync_reference_overlays();
 
         // Reset picking modes from previous session
         self.spectrum_view_state.peak_picking = false;
         self.spectrum_view_state.baseline_picking = false;
         self.spectrum_view_state.integration_picking = false;
         self.spectrum_view_state.j_coupling_picking = false;
+        self.spectrum_view_state.auto_j_picking = false;
+        self.spectrum_view_state.linewidth_picking = false;
+        self.spectrum_view_state.fit_picking = false;
+        self.spectrum_view_state.solvent_picking = false;
         self.spectrum_view_state.integration_start = None;
         self.spectrum_view_state.j_coupling_first = None;
+        self.spectrum_view_state.enhance = ResolutionPreview::default();
 
         // Reset phase dialog
         self.phase_dialog_state = PhaseDialogState::default();
+        self.phase_2d_state = Phase2dState::default();
 
         // Reset pipeline state
         self.pipeline_state = PipelinePanelState::default();
@@ -1560,22 +3599,1870 @@ impl NmrApp {
             self.domain_tab = DomainTab::TimeDomain;
         }
 
-        // Restore theme
-        let new_theme = if save.theme.contains("Cyberpunk") {
-            AppTheme::Cyberpunk
-        } else {
-            AppTheme::Light
+        // Restore theme
+        let new_theme = if save.theme.contains("Cyberpunk") {
+            AppTheme::Cyberpunk
+        } else {
+            AppTheme::Light
+        };
+        self.current_theme = new_theme;
+        self.theme_colors = ThemeColors::from_theme(new_theme);
+
+        // Reset processing state
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.before_snapshot = None;
+        self.repro_log = ReproLog::new();
+        self.log_mirror.project = Some(path.to_path_buf());
+
+        // Find the source data: bundled raw copy, then project-relative, then absolute
+        self.missing_source = None;
+        let old_source = self.session.active().map(|s| s.source_path.clone()).unwrap_or_default();
+        let project_dir = path.parent().unwrap_or(std::path::Path::new("."));
+        let found = save
+            .raw_data
+            .map(|r| project_dir.join(r))
+            .filter(|p| p.exists())
+            .or_else(|| relink::resolve_source(path, &old_source, save.source_relative.as_deref()));
+        match found {
+            Some(new_source) => self.relink_source(&old_source, &new_source),
+            None if !old_source.as_os_str().is_empty() => {
+                log::warn!("Project source data missing: {}", old_source.display());
+                self.missing_source = Some(old_source);
+            }
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /// Point every dataset at `new_source`, rebasing paths that lived next
+    /// to the old source (converted NMRPipe files) onto its new folder.
+    fn relink_source(&mut self, old_source: &std::path::Path, new_source: &std::path::Path) {
+        if old_source == new_source {
+            return;
+        }
+        let old_root = old_source.parent().unwrap_or(old_source).to_path_buf();
+        let new_root = new_source.parent().unwrap_or(new_source).to_path_buf();
+        let datasets = self
+            .session
+            .active_mut()
+            .into_iter()
+            .chain(self.fid_snapshot.iter_mut())
+            .chain(self.before_snapshot.iter_mut())
+            .chain(self.undo_stack.iter_mut().map(|(_, s)| s.meta_mut()))
+            .chain(self.redo_stack.iter_mut().map(|(_, s)| s.meta_mut()));
+        for s in datasets {
+            if s.source_path == old_source {
+                s.source_path = new_source.to_path_buf();
+            }
+            if let Some(p) = &mut s.nmrpipe_path {
+                *p = relink::rebase(p, &old_root, &new_root);
+            }
+        }
+    }
+
+    /// Label the 2D cross peaks with the assignments of a saved 1D project
+    /// whose nucleus matches F2 (a 1H project for an HSQC).
+    fn transfer_assignments(&mut self) {
+        let Some(spectrum) = self.session.active() else {
+            return;
+        };
+        if self.contour_view_state.cross_peaks.is_empty() {
+            self.status_message = "Pick cross peaks before transferring assignments".to_string();
+            return;
+        }
+        let Some(f2_nucleus) = spectrum.axes.first().map(|a| a.nucleus.clone()) else {
+            return;
+        };
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("Transfer Assignments from 1D Project")
+            .add_filter("NMR Project", &["nmrproj"])
+            .pick_file()
+        else {
+            return;
+        };
+        let assignments = match load_1d_assignments(&path, &f2_nucleus) {
+            Ok(a) => a,
+            Err(e) => {
+                self.status_message = format!("Assignment transfer failed: {}", e);
+                return;
+            }
+        };
+        let tolerance = self.pipeline_state.assign_tolerance_ppm;
+        let peaks = &mut self.contour_view_state.cross_peaks;
+        let labelled = processing::transfer_assignments(peaks, &assignments, tolerance);
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        self.repro_log.add_entry(
+            "Assignment Transfer",
+            &format!(
+                "Labelled {} of {} cross peaks from {} ({} assignments, F2 tolerance {:.3} ppm)",
+                labelled,
+                peaks.len(),
+                name,
+                assignments.len(),
+                tolerance
+            ),
+            "# 1D → 2D assignment transfer (no NMRPipe equivalent)",
+        );
+        self.status_message = format!("Labelled {} of {} cross peaks from {}", labelled, peaks.len(), name);
+        self.show_correlation_table = true;
+    }
+
+    /// Editable table of the picked 2D cross peaks and their assignments.
+    fn show_correlation_window(&mut self, ctx: &egui::Context) {
+        if !self.show_correlation_table {
+            return;
+        }
+        let (f2, f1) = match self.session.active().map(|s| &s.axes[..]) {
+            Some([x, y, ..]) => (x.nucleus.to_string(), y.nucleus.to_string()),
+            _ => ("F2".to_string(), "F1".to_string()),
+        };
+        let peaks = &mut self.contour_view_state.cross_peaks;
+        let mut remove = None;
+        let mut clear = false;
+        let mut export = false;
+        egui::Window::new("📋 2D Correlations")
+            .open(&mut self.show_correlation_table)
+            .default_size([420.0, 360.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                if peaks.is_empty() {
+                    ui.label("No cross peaks — use Pick Cross Peaks in the pipeline panel.");
+                    return;
+                }
+                egui::ScrollArea::vertical().max_height(280.0).show(ui, |ui| {
+                    egui::Grid::new("correlation_grid").striped(true).show(ui, |ui| {
+                        ui.strong(format!("δ {} (ppm)", f2));
+                        ui.strong(format!("δ {} (ppm)", f1));
+                        ui.strong("Intensity");
+                        ui.strong("Volume");
+                        ui.strong("Assignment");
+                        ui.end_row();
+                        for (i, p) in peaks.iter_mut().enumerate() {
+                            ui.label(format!("{:.3}", p.f2_ppm));
+                            ui.label(format!("{:.2}", p.f1_ppm));
+                            ui.label(format!("{:.3e}", p.intensity));
+                            ui.label(p.volume.map_or("—".to_string(), |v| format!("{:.3e}", v)));
+                            ui.add(egui::TextEdit::singleline(&mut p.label).desired_width(110.0));
+                            if ui.small_button("🗑").clicked() {
+                                remove = Some(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+                ui.separator();
+                let assigned = peaks.iter().filter(|p| !p.label.is_empty()).count();
+                ui.label(format!("{} of {} cross peaks assigned", assigned, peaks.len()));
+                ui.horizontal(|ui| {
+                    if ui.button("📋 Copy as CSV").clicked() {
+                        let mut csv = String::from("f2_ppm,f1_ppm,intensity,volume,assignment\n");
+                        for p in peaks.iter() {
+                            let volume = p.volume.map(|v| format!("{:.6e}", v)).unwrap_or_default();
+                            csv.push_str(&format!(
+                                "{:.4},{:.3},{:.6e},{},{}\n",
+                                p.f2_ppm, p.f1_ppm, p.intensity, volume, p.label
+                            ));
+                        }
+                        ui.ctx().copy_text(csv);
+                    }
+                    if ui
+                        .button("💾 Export Peak List…")
+                        .on_hover_text("Sparky .list or XEASY .peaks, with the assignments")
+                        .clicked()
+                    {
+                        export = true;
+                    }
+                    if ui.button("🗑 Clear All").clicked() {
+                        clear = true;
+                    }
+                });
+            });
+        if let Some(i) = remove {
+            peaks.remove(i);
+        }
+        if clear {
+            peaks.clear();
+        }
+        if export {
+            self.export_cross_peak_list();
+        }
+    }
+
+    /// Write the cross peaks as a Sparky `.list` or XEASY `.peaks` file,
+    /// chosen by the extension.
+    fn export_cross_peak_list(&mut self) {
+        let Some([f2, f1, ..]) = self.session.active().map(|s| &s.axes[..]) else {
+            return;
+        };
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("Export 2D Peak List")
+            .set_file_name("peaks.list")
+            .add_filter("Sparky peak list", &["list"])
+            .add_filter("XEASY peak list", &["peaks"])
+            .save_file()
+        else {
+            return;
+        };
+        let peaks = &self.contour_view_state.cross_peaks;
+        let xeasy = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("peaks"));
+        let (format, text) = if xeasy {
+            ("XEASY", processing::xeasy_peak_list(peaks, &f2.nucleus, &f1.nucleus))
+        } else {
+            ("Sparky", processing::sparky_peak_list(peaks))
+        };
+        match std::fs::write(&path, text) {
+            Ok(()) => {
+                let assigned = peaks.iter().filter(|p| !p.label.is_empty()).count();
+                self.repro_log.add_entry(
+                    "Export Data",
+                    &format!(
+                        "{} cross peaks ({} assigned) written as a {} peak list to {}",
+                        peaks.len(),
+                        assigned,
+                        format,
+                        path.display()
+                    ),
+                    "",
+                );
+                self.status_message = format!("{} peak list exported to {}", format, path.display());
+            }
+            Err(e) => self.status_message = format!("Export failed: {}", e),
+        }
+    }
+
+    /// Picked 1D peaks with their intensities and linewidths.
+    fn show_peak_table_window(&mut self, ctx: &egui::Context) {
+        if !self.show_peak_table {
+            return;
+        }
+        let Some(spectrum) = self.session.active() else {
+            return;
+        };
+        let peaks = &mut self.spectrum_view_state.peaks;
+        let exchangeable = &self.spectrum_view_state.exchangeable;
+        let is_exchangeable = |ppm: f64| exchangeable.iter().any(|&p| (p - ppm).abs() < 1e-9);
+        let impurities = &self.spectrum_view_state.common_impurities;
+        let impurity_at = |ppm: f64| impurities.iter().find(|(p, _)| (p - ppm).abs() < 1e-9).map(|(_, n)| n.as_str());
+        let mut remove = None;
+        egui::Window::new("📍 Peak Table")
+            .open(&mut self.show_peak_table)
+            .default_size([420.0, 360.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                if peaks.is_empty() {
+                    ui.label("No peaks — detect or pick peaks in the pipeline panel.");
+                    return;
+                }
+                let widths: Vec<_> = peaks.iter().map(|p| processing::measure_linewidth(spectrum, p[0])).collect();
+                egui::ScrollArea::vertical().max_height(280.0).show(ui, |ui| {
+                    egui::Grid::new("peak_table_grid").striped(true).show(ui, |ui| {
+                        ui.strong("#");
+                        ui.strong("δ (ppm)");
+                        ui.strong("Intensity");
+                        ui.strong("FWHM (Hz)");
+                        ui.strong("FWHM (ppm)");
+                        if !exchangeable.is_empty() {
+                            ui.strong("Exchange");
+                        }
+                        if !impurities.is_empty() {
+                            ui.strong("Impurity");
+                        }
+                        ui.end_row();
+                        for (i, (p, lw)) in peaks.iter().zip(&widths).enumerate() {
+                            ui.label(format!("{}", i + 1));
+                            ui.label(format!("{:.4}", p[0]));
+                            ui.label(format!("{:.3e}", p[1]));
+                            match lw {
+                                Some(lw) => {
+                                    ui.label(format!("{:.2}", lw.fwhm_hz));
+                                    ui.label(format!("{:.5}", lw.fwhm_ppm()));
+                                }
+                                None => {
+                                    ui.label("—").on_hover_text("No half-height crossing on both sides");
+                                    ui.label("—");
+                                }
+                            }
+                            if !exchangeable.is_empty() {
+                                if is_exchangeable(p[0]) {
+                                    ui.label("OH/NH").on_hover_text("Disappears after the D₂O shake");
+                                } else {
+                                    ui.label("");
+                                }
+                            }
+                            if !impurities.is_empty() {
+                                ui.label(impurity_at(p[0]).unwrap_or(""));
+                            }
+                            if ui.small_button("🗑").clicked() {
+                                remove = Some(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+                ui.separator();
+                if ui.button("📋 Copy as CSV").clicked() {
+                    let mut csv = String::from("ppm,intensity,fwhm_hz,fwhm_ppm,exchangeable,impurity\n");
+                    for (p, lw) in peaks.iter().zip(&widths) {
+                        let (hz, ppm) = lw.map(|lw| (format!("{:.2}", lw.fwhm_hz), format!("{:.5}", lw.fwhm_ppm()))).unwrap_or_default();
+                        let exchange = if is_exchangeable(p[0]) { "OH/NH" } else { "" };
+                        let impurity = impurity_at(p[0]).map(|n| format!("\"{}\"", n)).unwrap_or_default();
+                        csv.push_str(&format!("{:.4},{:.6e},{},{},{},{}\n", p[0], p[1], hz, ppm, exchange, impurity));
+                    }
+                    ui.ctx().copy_text(csv);
+                }
+            });
+        if let Some(i) = remove {
+            peaks.remove(i);
+        }
+    }
+
+    /// Overlay the checked library references that match the current
+    /// spectrum's nucleus, keeping the legend settings of those already shown
+    fn sync_reference_overlays(&mut self) {
+        let nucleus = self.session.active().and_then(|s| s.axes.first()).map(|a| a.nucleus.clone());
+        let previous = std::mem::take(&mut self.spectrum_view_state.reference_overlays);
+        let overlays = match (&self.reference_library, nucleus) {
+            (Some(library), Some(nucleus)) => library
+                .entries
+                .iter()
+                .filter(|r| r.nucleus == nucleus && self.overlaid_references.contains(&r.name))
+                .map(|r| match previous.iter().find(|t| t.name == r.name) {
+                    Some(t) => OverlayTrace { points: r.points.clone(), ..t.clone() },
+                    None => OverlayTrace::new(r.name.clone(), r.points.clone()),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        self.spectrum_view_state.reference_overlays = overlays;
+    }
+
+    /// Save / delete / overlay reference spectra.
+    fn show_reference_library_window(&mut self, ctx: &egui::Context) {
+        if !self.show_reference_library {
+            return;
+        }
+        let library = self
+            .reference_library
+            .get_or_insert_with(|| ReferenceLibrary::open(&ReferenceLibrary::default_dir()));
+        let current = self
+            .session
+            .active()
+            .filter(|s| s.is_frequency_domain && !s.is_2d());
+        let nucleus = current.and_then(|s| s.axes.first()).map(|a| a.nucleus.clone());
+        let mut save = false;
+        let mut delete = None;
+        let mut changed = false;
+        let mut fit = false;
+        let main_integral = self.spectrum_view_state.integrations.first().map(|r| r.2);
+        egui::Window::new("📚 Reference Library")
+            .open(&mut self.show_reference_library)
+            .default_size([440.0, 360.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Save current spectrum as:");
+                    ui.add(egui::TextEdit::singleline(&mut self.reference_name).desired_width(160.0));
+                    let can_save = current.is_some() && !self.reference_name.trim().is_empty();
+                    if ui
+                        .add_enabled(can_save, egui::Button::new("💾 Save"))
+                        .on_disabled_hover_text("Needs a processed 1D spectrum and a name")
+                        .clicked()
+                    {
+                        save = true;
+                    }
+                });
+                ui.separator();
+                if library.entries.is_empty() {
+                    ui.label("No references yet.");
+                    return;
+                }
+                egui::ScrollArea::vertical().max_height(260.0).show(ui, |ui| {
+                    egui::Grid::new("reference_library_grid").striped(true).show(ui, |ui| {
+                        ui.strong("Overlay");
+                        ui.strong("Name");
+                        ui.strong("Nucleus");
+                        ui.strong("Saved");
+                        ui.end_row();
+                        for r in &library.entries {
+                            let mut on = self.overlaid_references.contains(&r.name);
+                            let matches = nucleus.as_ref() == Some(&r.nucleus);
+                            if ui
+                                .add_enabled(matches, egui::Checkbox::without_text(&mut on))
+                                .on_disabled_hover_text("Different nucleus from the current spectrum")
+                                .changed()
+                            {
+                                if on {
+                                    self.overlaid_references.push(r.name.clone());
+                                } else {
+                                    self.overlaid_references.retain(|n| n != &r.name);
+                                }
+                                changed = true;
+                            }
+                            ui.label(&r.name);
+                            ui.label(format!("{} ({:.0} MHz)", r.nucleus, r.observe_freq_mhz));
+                            ui.label(r.saved.get(..10).unwrap_or(&r.saved));
+                            if ui.small_button("🗑").on_hover_text("Delete reference").clicked() {
+                                delete = Some(r.name.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+                ui.separator();
+                if ui.button("Clear overlays").clicked() {
+                    self.overlaid_references.clear();
+                    changed = true;
+                }
+
+                ui.separator();
+                ui.strong("⚖ Quantify impurity");
+                ui.label("Scales a reference onto an impurity signal; mol% is relative to the first integral.");
+                let matching: Vec<&String> = library
+                    .entries
+                    .iter()
+                    .filter(|r| nucleus.as_ref() == Some(&r.nucleus))
+                    .map(|r| &r.name)
+                    .collect();
+                ui.horizontal(|ui| {
+                    ui.label("Reference:");
+                    egui::ComboBox::from_id_salt("impurity_reference")
+                        .selected_text(if self.impurity_reference.is_empty() { "—" } else { &self.impurity_reference })
+                        .show_ui(ui, |ui| {
+                            for name in &matching {
+                                ui.selectable_value(&mut self.impurity_reference, name.to_string(), name.as_str());
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Region:");
+                    ui.add(egui::DragValue::new(&mut self.impurity_region.0).speed(0.01).suffix(" ppm"));
+                    ui.label("–");
+                    ui.add(egui::DragValue::new(&mut self.impurity_region.1).speed(0.01).suffix(" ppm"));
+                    ui.label("H:");
+                    ui.add(egui::DragValue::new(&mut self.impurity_h).speed(0.1).range(0.1..=100.0));
+                });
+                let can_fit = current.is_some()
+                    && main_integral.is_some()
+                    && matching.contains(&&self.impurity_reference);
+                if ui
+                    .add_enabled(can_fit, egui::Button::new("Fit reference"))
+                    .on_disabled_hover_text("Needs a processed 1D spectrum, an integral of the main compound and a matching reference")
+                    .clicked()
+                {
+                    fit = true;
+                }
+                if let Some(result) = &self.impurity_result {
+                    ui.label(format!(
+                        "Scale {:.4e}, offset {:.3e} → {:.2} mol% (residual {:.1}%)",
+                        result.scale,
+                        result.offset,
+                        result.mol_percent,
+                        result.relative_residual * 100.0
+                    ));
+                    if result.relative_residual > 0.2 {
+                        ui.colored_label(egui::Color32::from_rgb(0xD0, 0x8A, 0x20), "⚠ Poor fit; check the region and reference");
+                    }
+                }
+            });
+
+        if fit {
+            let reference = library.entries.iter().find(|r| r.name == self.impurity_reference);
+            let (lo, hi) = self.impurity_region;
+            let result = match (current, reference, main_integral) {
+                (Some(spectrum), Some(reference), Some(main)) => processing::quantify_impurity(
+                    spectrum,
+                    &reference.points,
+                    lo,
+                    hi,
+                    self.impurity_h,
+                    main,
+                    self.spectrum_view_state.integration_reference_h,
+                ),
+                _ => None,
+            };
+            match result {
+                Some(result) => {
+                    self.repro_log.add_entry(
+                        "Impurity Quantification",
+                        &format!(
+                            "reference \"{}\" scaled by {:.6e} (offset {:.3e}) over {:.3}–{:.3} ppm, {} H vs {} H: {:.3} mol%",
+                            self.impurity_reference,
+                            result.scale,
+                            result.offset,
+                            lo.min(hi),
+                            lo.max(hi),
+                            self.impurity_h,
+                            self.spectrum_view_state.integration_reference_h,
+                            result.mol_percent
+                        ),
+                        "# impurity quantification against reference library (no NMRPipe equivalent)",
+                    );
+                    self.status_message = format!("{}: {:.2} mol%", self.impurity_reference, result.mol_percent);
+                    self.spectrum_view_state.impurity_fit = Some((self.impurity_reference.clone(), result.trace.clone()));
+                    self.impurity_result = Some(result);
+                }
+                None => {
+                    self.status_message = "Impurity fit failed: the region must cover the reference signal".to_string();
+                    self.spectrum_view_state.impurity_fit = None;
+                    self.impurity_result = None;
+                }
+            }
+        }
+
+        if save {
+            let name = self.reference_name.trim().to_string();
+            match current.and_then(|s| ReferenceSpectrum::from_spectrum(&name, s)) {
+                Some(reference) => match library.add(reference) {
+                    Ok(()) => {
+                        self.status_message = format!("Saved reference \"{}\"", name);
+                        self.reference_name.clear();
+                    }
+                    Err(e) => self.status_message = format!("Saving reference failed: {}", e),
+                },
+                None => self.status_message = "The spectrum is empty; nothing to save".to_string(),
+            }
+        }
+        if let Some(name) = delete {
+            match library.remove(&name) {
+                Ok(()) => self.status_message = format!("Deleted reference \"{}\"", name),
+                Err(e) => self.status_message = format!("Deleting reference failed: {}", e),
+            }
+            self.overlaid_references.retain(|n| n != &name);
+            changed = true;
+        }
+        if changed || save {
+            self.sync_reference_overlays();
+        }
+    }
+
+    /// Pair the current 1H (or 2H) spectrum's peaks with those of a 2H (or
+    /// 1H) spectrum of the same sample picked by the user.
+    fn compare_isotope_shifts(&mut self) {
+        let Some(spectrum) = self.session.active() else {
+            return;
+        };
+        let nucleus = spectrum.axes.first().map(|a| a.nucleus.clone());
+        let wanted = match nucleus {
+            Some(Nucleus::H1) => Nucleus::H2,
+            Some(Nucleus::H2) => Nucleus::H1,
+            _ => {
+                self.status_message = "1H/2H comparison needs a 1H or 2H spectrum".to_string();
+                return;
+            }
+        };
+        if !spectrum.is_frequency_domain || spectrum.is_2d() {
+            self.status_message = "1H/2H comparison needs a processed 1D spectrum".to_string();
+            return;
+        }
+        let Some(path) = toolbar::open_file_dialog() else {
+            return;
+        };
+
+        // Converted like a normal load (background, cancellable, cached),
+        // but into its own log; see `finish_isotope_load`
+        if let Some(previous) = self.isotope_load.take() {
+            previous.load.cancel();
+        }
+        let mut settings = self.make_settings(None);
+        settings.output_dir = Some(self.work_dir.output_dir_for(&path));
+        let cache = self.preferences.cache.enabled.then(|| self.conversion_cache());
+        let load = loader::start_load(&path, ReproLog::new(), settings, cache, self.egui_ctx.clone());
+        self.status_message = format!("Loading {} spectrum {}…", wanted, path.display());
+        self.isotope_load = Some(IsotopeLoad { load, wanted });
+    }
+
+    /// Build the 1H/2H comparison once the partner spectrum has loaded.
+    fn finish_isotope_load(&mut self) {
+        let Some(result) = self.isotope_load.as_ref().and_then(|l| l.load.take_result()) else {
+            return;
+        };
+        let Some(IsotopeLoad { load, wanted }) = self.isotope_load.take() else {
+            return;
+        };
+        let path = load.path.clone();
+        if load.is_cancelled() {
+            self.status_message = format!("Cancelled loading {}", path.display());
+            return;
+        }
+        let Some(spectrum) = self.session.active() else {
+            return;
+        };
+
+        // The partner is only read for its peaks; its conversion and
+        // processing steps are summarised in a single log entry
+        let mut partner_log = result.log;
+        let mut partner = match result.spectrum {
+            Ok(s) => s,
+            Err(e) => {
+                self.status_message = format!("Could not load {}: {}", path.display(), e.user_message());
+                return;
+            }
+        };
+        let partner_nucleus = partner.axes.first().map(|a| a.nucleus.clone());
+        if partner_nucleus.as_ref() != Some(&wanted) || partner.is_2d() {
+            self.status_message = format!(
+                "{} is not a 1D {} spectrum",
+                path.display(),
+                wanted
+            );
+            return;
+        }
+        let processed = !partner.is_frequency_domain;
+        if processed {
+            let mut defaults = PipelinePanelState::default();
+            defaults.apply_nucleus_defaults(&wanted);
+            processing::quick_process_1d(&mut partner, defaults.em_lb, &mut partner_log);
+        }
+
+        let state = &self.pipeline_state;
+        let own_peaks = if self.spectrum_view_state.peaks.is_empty() {
+            auto_peaks(spectrum, state.peak_threshold, state.min_peak_spacing_hz, state.peak_sign)
+        } else {
+            self.spectrum_view_state.peaks.clone()
+        };
+        let mut comparison = IsotopeComparison {
+            partner: path.clone(),
+            partner_spectrum: partner,
+            partner_is_d: wanted == Nucleus::H2,
+            own_peaks,
+            partner_peaks: Vec::new(),
+            partner_threshold: state.peak_threshold,
+            partner_spacing_hz: state.min_peak_spacing_hz,
+            tolerance_ppm: 0.05,
+        };
+        comparison.pick_partner_peaks();
+
+        let pairs = comparison.pairs();
+        let matched: Vec<String> = pairs
+            .iter()
+            .filter_map(|p| Some(format!("{:.3}/{:.3}", p.h_ppm, p.d_ppm?)))
+            .collect();
+        self.repro_log.add_entry(
+            "Isotope Shift Comparison",
+            &format!(
+                "Compared with {} spectrum {}{}\n# {} peaks picked at threshold {:.3}, min spacing {:.1} Hz\n# {} of {} 1H sites show 2H (tolerance {:.3} ppm): [{}]",
+                wanted,
+                path.display(),
+                if processed { " (EM, zero fill, FT, auto phase, baseline)" } else { "" },
+                wanted,
+                comparison.partner_threshold,
+                comparison.partner_spacing_hz,
+                matched.len(),
+                pairs.len(),
+                comparison.tolerance_ppm,
+                matched.join(", "),
+            ),
+            "# 1H/2H isotope-shift pairing (no NMRPipe equivalent)",
+        );
+        self.status_message = format!(
+            "{} of {} 1H sites matched in the 2H spectrum",
+            matched.len(),
+            pairs.len()
+        );
+        self.isotope_comparison = Some(comparison);
+    }
+
+    /// Compare the current spectrum's peaks with those of another spectrum
+    /// of the same nucleus picked by the user; with `d2o_shake` it is the
+    /// same sample after shaking with D₂O.
+    /// Label the peaks of a 1H spectrum that match common impurities in
+    /// the sample's solvent (CDCl3 when it is not recorded)
+    fn identify_common_impurities(&mut self) {
+        let Some(spectrum) = self.session.active() else {
+            return;
+        };
+        if !spectrum.is_frequency_domain || spectrum.is_2d() || spectrum.display_nucleus() != Some(Nucleus::H1) {
+            self.status_message = "Impurity tables are for 1D 1H spectra".to_string();
+            return;
+        }
+        let solvent = crate::data::solvent::identify(&spectrum.solvent).map(|s| s.name);
+        let (column, assumed) = match solvent.and_then(crate::data::impurities::column) {
+            Some(column) => (column, false),
+            None => (0, true),
+        };
+        if self.spectrum_view_state.peaks.is_empty() {
+            self.spectrum_view_state.peaks = auto_peaks(
+                spectrum,
+                self.pipeline_state.peak_threshold,
+                self.pipeline_state.min_peak_spacing_hz,
+                self.pipeline_state.peak_sign,
+            );
+        }
+        let tolerance = processing::IMPURITY_TOLERANCE_PPM;
+        let matches = processing::identify_common_impurities(&self.spectrum_view_state.peaks, column, tolerance);
+        let column_name = crate::data::impurities::SOLVENT_COLUMNS[column];
+        let listed: Vec<String> = matches.iter().map(|(ppm, name)| format!("{} {:.2}", name, ppm)).collect();
+        self.repro_log.add_entry(
+            "Identify Impurities",
+            &format!(
+                "{} peak{} matched common impurities (Gottlieb et al. 1997, {} column{}, ±{:.2} ppm){}{}",
+                matches.len(),
+                if matches.len() == 1 { "" } else { "s" },
+                column_name,
+                if assumed { ", solvent not recorded" } else { "" },
+                tolerance,
+                if listed.is_empty() { "" } else { ": " },
+                listed.join(", ")
+            ),
+            "# impurity table lookup (no NMRPipe equivalent)",
+        );
+        self.status_message = format!(
+            "{} peak{} labelled as common impurities in {}{}",
+            matches.len(),
+            if matches.len() == 1 { "" } else { "s" },
+            column_name,
+            if assumed { " (solvent not recorded, assumed)" } else { "" }
+        );
+        self.spectrum_view_state.common_impurities = matches;
+    }
+
+    /// Open the carbon-type window for a DEPT-135 or APT spectrum and
+    /// classify its peaks, with a DEPT-90 of the session when there is one
+    fn open_carbon_typing(&mut self) {
+        let Some(spectrum) = self.session.active() else {
+            return;
+        };
+        if !spectrum.is_frequency_domain || spectrum.is_2d() || spectrum.display_nucleus() != Some(Nucleus::C13) {
+            self.status_message = "Carbon types need a processed 1D DEPT-135 or APT spectrum".to_string();
+            return;
+        }
+        if processing::is_dept90(spectrum) {
+            self.status_message = "This is a DEPT-90: open the DEPT-135 and add this spectrum to the session".to_string();
+            return;
+        }
+        let active = self.session.active_index();
+        let dept90 = self
+            .session
+            .entries
+            .iter()
+            .enumerate()
+            .find(|(i, e)| *i != active && e.is_overlayable() && processing::is_dept90(&e.spectrum))
+            .map(|(_, e)| e.spectrum.source_path.clone());
+        self.carbon_typing = Some(CarbonTyping {
+            editing: processing::SpectralEditing::detect(spectrum).unwrap_or(processing::SpectralEditing::Dept135),
+            inverted: false,
+            dept90,
+            tolerance_ppm: 0.2,
+        });
+        self.classify_carbons();
+    }
+
+    /// Carbon type of every picked peak (picked with both signs when there
+    /// are none) from the settings of the carbon-type window
+    fn classify_carbons(&mut self) {
+        let (Some(typing), Some(spectrum)) = (self.carbon_typing.as_ref(), self.session.active()) else {
+            return;
+        };
+        let state = &self.pipeline_state;
+        let auto_picked = self.spectrum_view_state.peaks.is_empty();
+        if auto_picked {
+            let peaks = auto_peaks(spectrum, state.peak_threshold, state.min_peak_spacing_hz, PeakSign::Both);
+            self.repro_log.add_entry(
+                "Peak Detection",
+                &format!(
+                    "Found {} peaks, {} negative (threshold {:.0}%, min spacing {:.1} Hz, {}) for the carbon types",
+                    peaks.len(),
+                    peaks.iter().filter(|p| p[1] < 0.0).count(),
+                    state.peak_threshold * 100.0,
+                    state.min_peak_spacing_hz,
+                    PeakSign::Both
+                ),
+                "# automatic peak picking (no NMRPipe equivalent)",
+            );
+            self.spectrum_view_state.peaks = peaks;
+        }
+        // The DEPT-90's own peaks when it was picked, else its positive peaks
+        let active = self.session.active_index();
+        let dept90 = typing.dept90.as_ref().and_then(|path| {
+            self.session.entries.iter().enumerate().find(|(i, e)| *i != active && e.spectrum.source_path == *path)
+        });
+        let dept90 = dept90.map(|(_, entry)| {
+            let picked = entry.parked.as_ref().map(|p| p.annotations.peaks()).unwrap_or_default();
+            let peaks = if picked.is_empty() {
+                auto_peaks(&entry.spectrum, state.peak_threshold, state.min_peak_spacing_hz, PeakSign::Positive)
+            } else {
+                picked.to_vec()
+            };
+            (entry.name(), peaks)
+        });
+        let carbons = processing::classify_carbons(
+            &self.spectrum_view_state.peaks,
+            typing.editing,
+            typing.inverted,
+            dept90.as_ref().map(|(_, peaks)| peaks.as_slice()),
+            typing.tolerance_ppm,
+        );
+        let summary = processing::carbon_type_summary(&carbons);
+        self.repro_log.add_entry(
+            "Carbon Types",
+            &format!(
+                "{} peaks of the {} spectrum{} classified by sign: {}{}",
+                carbons.len(),
+                typing.editing,
+                if typing.inverted { " (inverted phase)" } else { "" },
+                summary,
+                match &dept90 {
+                    Some((name, peaks)) => format!(
+                        "\n# CH told from CH3 with the DEPT-90 {} ({} peaks, ±{:.2} ppm, CH at ≥{:.0}% of its tallest peak)",
+                        name,
+                        peaks.len(),
+                        typing.tolerance_ppm,
+                        processing::DEPT90_MIN_RATIO * 100.0
+                    ),
+                    None => String::new(),
+                }
+            ),
+            "# DEPT / APT editing analysis (no NMRPipe equivalent)",
+        );
+        self.status_message = format!(
+            "Carbon types: {}{}",
+            summary,
+            if auto_picked { " (peaks picked automatically with both signs)" } else { "" }
+        );
+        self.spectrum_view_state.carbon_types = carbons;
+    }
+
+    /// Table of the carbon types with the editing settings
+    fn show_carbon_types_window(&mut self, ctx: &egui::Context) {
+        let Some(typing) = self.carbon_typing.as_mut() else {
+            return;
+        };
+        let active = self.session.active_index();
+        let candidates: Vec<(PathBuf, String)> = self
+            .session
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(i, e)| {
+                *i != active && e.is_overlayable() && e.spectrum.display_nucleus() == Some(Nucleus::C13)
+            })
+            .map(|(_, e)| (e.spectrum.source_path.clone(), e.name()))
+            .collect();
+        let carbons = &self.spectrum_view_state.carbon_types;
+        let mut open = true;
+        let mut classify = false;
+        egui::Window::new("🧬 Carbon Types")
+            .open(&mut open)
+            .default_size([420.0, 380.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Experiment:");
+                    for editing in [processing::SpectralEditing::Dept135, processing::SpectralEditing::Apt] {
+                        ui.radio_value(&mut typing.editing, editing, editing.to_string());
+                    }
+                    ui.checkbox(&mut typing.inverted, "Inverted phase")
+                        .on_hover_text("The spectrum is phased with the opposite sign convention");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("DEPT-90:");
+                    let selected = typing
+                        .dept90
+                        .as_ref()
+                        .and_then(|path| candidates.iter().find(|(p, _)| p == path))
+                        .map_or("None", |(_, name)| name.as_str());
+                    egui::ComboBox::from_id_salt("carbon_dept90").selected_text(selected).show_ui(ui, |ui| {
+                        ui.selectable_value(&mut typing.dept90, None, "None");
+                        for (path, name) in &candidates {
+                            ui.selectable_value(&mut typing.dept90, Some(path.clone()), name);
+                        }
+                    })
+                    .response
+                    .on_hover_text("A DEPT-90 of the sample in the session tells CH (present) from CH3 (absent)");
+                    ui.add_enabled(
+                        typing.dept90.is_some(),
+                        egui::DragValue::new(&mut typing.tolerance_ppm)
+                            .speed(0.01)
+                            .range(0.01..=2.0)
+                            .prefix("± ")
+                            .suffix(" ppm"),
+                    );
+                });
+                if ui.button("▶ Classify").clicked() {
+                    classify = true;
+                }
+                ui.separator();
+
+                egui::ScrollArea::vertical().max_height(260.0).show(ui, |ui| {
+                    egui::Grid::new("carbon_type_grid").striped(true).show(ui, |ui| {
+                        ui.strong("δ (ppm)");
+                        ui.strong("Intensity");
+                        ui.strong("Type");
+                        ui.strong("DEPT-90");
+                        ui.end_row();
+                        for carbon in carbons {
+                            ui.label(format!("{:.2}", carbon.ppm));
+                            ui.label(format!("{:.3e}", carbon.intensity));
+                            ui.label(carbon.carbon.to_string());
+                            ui.label(carbon.dept90.map(|r| format!("{:.2}", r)).unwrap_or_else(|| "—".to_string()))
+                                .on_hover_text("Height of the matching DEPT-90 peak, relative to its tallest");
+                            ui.end_row();
+                        }
+                    });
+                });
+                ui.separator();
+                ui.label(processing::carbon_type_summary(carbons));
+                if ui
+                    .button("📋 Copy as CSV")
+                    .on_hover_text("The table is also a section of the data report (Export tab)")
+                    .clicked()
+                {
+                    let mut csv = String::from("ppm,intensity,carbon_type,dept90_ratio\n");
+                    for c in carbons {
+                        let ratio = c.dept90.map(|r| format!("{:.3}", r)).unwrap_or_default();
+                        csv.push_str(&format!("{:.4},{:.6e},{},{}\n", c.ppm, c.intensity, c.carbon, ratio));
+                    }
+                    ui.ctx().copy_text(csv);
+                }
+            });
+        if !open {
+            self.carbon_typing = None;
+        } else if classify {
+            self.classify_carbons();
+        }
+    }
+
+    fn compare_peak_lists(&mut self, d2o_shake: bool) {
+        let Some(spectrum) = self.session.active() else {
+            return;
+        };
+        if !spectrum.is_frequency_domain || spectrum.is_2d() {
+            self.status_message = "Peak comparison needs a processed 1D spectrum".to_string();
+            return;
+        }
+        if d2o_shake && spectrum.axes.first().map(|a| &a.nucleus) != Some(&Nucleus::H1) {
+            self.status_message = "A D₂O shake comparison needs a 1H spectrum".to_string();
+            return;
+        }
+        let Some(path) = toolbar::open_file_dialog() else {
+            return;
+        };
+        if let Some(previous) = self.comparison_load.take() {
+            previous.cancel();
+        }
+        let mut settings = self.make_settings(None);
+        settings.output_dir = Some(self.work_dir.output_dir_for(&path));
+        let cache = self.preferences.cache.enabled.then(|| self.conversion_cache());
+        self.comparison_load = Some(loader::start_load(&path, ReproLog::new(), settings, cache, self.egui_ctx.clone()));
+        self.comparison_d2o = d2o_shake;
+        self.status_message = format!("Loading {} for comparison…", path.display());
+    }
+
+    /// Build the peak comparison once the compared spectrum has loaded.
+    fn finish_comparison_load(&mut self) {
+        let Some(result) = self.comparison_load.as_ref().and_then(|l| l.take_result()) else {
+            return;
+        };
+        let Some(load) = self.comparison_load.take() else {
+            return;
+        };
+        let path = load.path.clone();
+        if load.is_cancelled() {
+            self.status_message = format!("Cancelled loading {}", path.display());
+            return;
+        }
+        let Some(spectrum) = self.session.active() else {
+            return;
+        };
+        let Some(nucleus) = spectrum.axes.first().map(|a| a.nucleus.clone()) else {
+            return;
+        };
+
+        // Like the 1H/2H partner, only read for its peaks
+        let mut compared_log = result.log;
+        let mut compared = match result.spectrum {
+            Ok(s) => s,
+            Err(e) => {
+                self.status_message = format!("Could not load {}: {}", path.display(), e.user_message());
+                return;
+            }
+        };
+        if compared.axes.first().map(|a| &a.nucleus) != Some(&nucleus) || compared.is_2d() {
+            self.status_message = format!("{} is not a 1D {} spectrum", path.display(), nucleus);
+            return;
+        }
+        let processed = !compared.is_frequency_domain;
+        if processed {
+            let mut defaults = PipelinePanelState::default();
+            defaults.apply_nucleus_defaults(&nucleus);
+            processing::quick_process_1d(&mut compared, defaults.em_lb, &mut compared_log);
+        }
+
+        let state = &self.pipeline_state;
+        let own_peaks = if self.spectrum_view_state.peaks.is_empty() {
+            auto_peaks(spectrum, state.peak_threshold, state.min_peak_spacing_hz, state.peak_sign)
+        } else {
+            self.spectrum_view_state.peaks.clone()
+        };
+        let mut comparison = SpectrumComparison {
+            compared: path.clone(),
+            compared_spectrum: compared,
+            own_peaks,
+            compared_peaks: Vec::new(),
+            threshold: state.peak_threshold,
+            spacing_hz: state.min_peak_spacing_hz,
+            tolerance_ppm: if nucleus == Nucleus::C13 { 0.2 } else { 0.02 },
+            align: true,
+            offset_ppm: 0.0,
+            d2o_shake: self.comparison_d2o,
+            exchange_ratio: 0.3,
+        };
+        comparison.pick_compared_peaks();
+        if comparison.d2o_shake && self.spectrum_view_state.peaks.is_empty() {
+            // The flags are shown on the peak list, so it needs one
+            self.spectrum_view_state.peaks = comparison.own_peaks.clone();
+        }
+
+        let rows = comparison.rows();
+        let count = |change| rows.iter().filter(|r| r.change == change).count();
+        let (common, new, missing) = (
+            count(processing::PeakChange::Common),
+            count(processing::PeakChange::New),
+            count(processing::PeakChange::Missing),
+        );
+        self.repro_log.add_entry(
+            "Peak List Comparison",
+            &format!(
+                "Compared with {}{}\n# {} peaks picked at threshold {:.3}, min spacing {:.1} Hz; aligned by {:+.4} ppm\n# {} common, {} new, {} missing (tolerance {:.3} ppm)",
+                path.display(),
+                if processed { " (EM, zero fill, FT, auto phase, baseline)" } else { "" },
+                comparison.compared_peaks.len(),
+                comparison.threshold,
+                comparison.spacing_hz,
+                comparison.offset_ppm,
+                common,
+                new,
+                missing,
+                comparison.tolerance_ppm,
+            ),
+            "# peak list comparison (no NMRPipe equivalent)",
+        );
+        self.status_message = format!("{} common, {} new, {} missing peaks", common, new, missing);
+        self.spectrum_comparison = Some(comparison);
+        self.annotate_exchange();
+    }
+
+    /// Flag the exchangeable peaks of a D₂O shake comparison on the peak
+    /// list and overlay the difference spectrum
+    fn annotate_exchange(&mut self) {
+        let (Some(comparison), Some(spectrum)) = (self.spectrum_comparison.as_ref(), self.session.active()) else {
+            return;
+        };
+        if !comparison.d2o_shake {
+            return;
+        }
+        let exchange = comparison.d2o_exchange(spectrum);
+        let shifts: Vec<String> = exchange.exchanged.iter().map(|p| format!("{:.2}", p[0])).collect();
+        self.repro_log.add_entry(
+            "D2O Exchange",
+            &format!(
+                "{} exchangeable (OH/NH) peak{} after D₂O shake ({}): {}\n# flagged when missing or below {:.0}% of the normalised intensity; after spectrum scaled by {:.3}",
+                shifts.len(),
+                if shifts.len() == 1 { "" } else { "s" },
+                comparison.compared.display(),
+                if shifts.is_empty() { "none".to_string() } else { format!("δ {} ppm", shifts.join(", ")) },
+                comparison.exchange_ratio * 100.0,
+                exchange.scale,
+            ),
+            "# D2O exchange comparison (no NMRPipe equivalent)",
+        );
+        self.status_message = format!("{} exchangeable (OH/NH) peaks flagged", shifts.len());
+        self.spectrum_view_state.exchangeable = exchange.exchanged.iter().map(|p| p[0]).collect();
+        self.spectrum_view_state.d2o_difference = Some(exchange.difference);
+    }
+
+    /// Common / new / missing peaks against the compared spectrum
+    fn show_comparison_window(&mut self, ctx: &egui::Context) {
+        self.finish_comparison_load();
+        let title = |d2o: bool| if d2o { "💧 D₂O Shake" } else { "⇄ Peak Comparison" };
+        if let Some(pending) = &self.comparison_load {
+            egui::Window::new(title(self.comparison_d2o)).show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(format!("Loading {}…", pending.path.display()));
+                });
+                if ui.button("✖ Cancel").clicked() {
+                    pending.cancel();
+                }
+            });
+            if pending.is_cancelled() {
+                self.comparison_load = None;
+            }
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+            return;
+        }
+        let Some(comparison) = self.spectrum_comparison.as_mut() else {
+            return;
+        };
+        let mut open = true;
+        let mut repick = false;
+        let mut realign = false;
+        let mut export = false;
+        let mut reflag = false;
+        let exchanged = self.spectrum_view_state.exchangeable.clone();
+        egui::Window::new(title(comparison.d2o_shake))
+            .open(&mut open)
+            .default_size([560.0, 400.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                let name = comparison
+                    .compared
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                ui.label(format!("Reference: current spectrum — compared: {}", name));
+                ui.horizontal(|ui| {
+                    ui.label("Match tolerance:");
+                    realign |= ui
+                        .add(
+                            egui::DragValue::new(&mut comparison.tolerance_ppm)
+                                .speed(0.005)
+                                .range(0.001..=1.0)
+                                .suffix(" ppm"),
+                        )
+                        .changed();
+                    realign |= ui
+                        .checkbox(&mut comparison.align, "Align")
+                        .on_hover_text("Correct a referencing difference of up to 0.2 ppm before matching")
+                        .changed();
+                    ui.label(format!("offset {:+.4} ppm", comparison.offset_ppm));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Compared peaks: threshold");
+                    ui.add(egui::DragValue::new(&mut comparison.threshold).speed(0.005).range(0.001..=1.0));
+                    ui.label("spacing");
+                    ui.add(
+                        egui::DragValue::new(&mut comparison.spacing_hz)
+                            .speed(0.5)
+                            .range(0.1..=500.0)
+                            .suffix(" Hz"),
+                    );
+                    if ui.button("Re-pick").clicked() {
+                        repick = true;
+                    }
+                });
+                if comparison.d2o_shake {
+                    ui.horizontal(|ui| {
+                        ui.label("Exchanged below");
+                        reflag |= ui
+                            .add(
+                                egui::DragValue::new(&mut comparison.exchange_ratio)
+                                    .speed(0.01)
+                                    .range(0.0..=1.0)
+                                    .fixed_decimals(2),
+                            )
+                            .on_hover_text("Normalised intensity ratio after/before below which a peak counts as exchanged")
+                            .changed();
+                        ui.label("of the intensity before the shake");
+                    });
+                }
+                ui.separator();
+
+                let rows = comparison.rows();
+                let fmt_ppm = |p: Option<[f64; 2]>| p.map(|p| format!("{:.4}", p[0])).unwrap_or_else(|| "—".to_string());
+                let fmt_int = |p: Option<[f64; 2]>| p.map(|p| format!("{:.3e}", p[1])).unwrap_or_else(|| "—".to_string());
+                egui::ScrollArea::vertical().max_height(280.0).show(ui, |ui| {
+                    egui::Grid::new("peak_comparison_grid").striped(true).show(ui, |ui| {
+                        ui.strong("Status");
+                        ui.strong("δ ref (ppm)");
+                        ui.strong("Intensity");
+                        ui.strong("δ compared (ppm)");
+                        ui.strong("Intensity");
+                        ui.strong("Ratio");
+                        ui.end_row();
+                        for row in &rows {
+                            let color = match row.change {
+                                processing::PeakChange::Common => ui.visuals().text_color(),
+                                processing::PeakChange::New => egui::Color32::from_rgb(0x20, 0xA0, 0x40),
+                                processing::PeakChange::Missing => egui::Color32::from_rgb(0xD0, 0x30, 0x30),
+                            };
+                            let flagged = row.reference.is_some_and(|p| exchanged.iter().any(|&x| (x - p[0]).abs() < 1e-9));
+                            if comparison.d2o_shake && flagged {
+                                ui.colored_label(egui::Color32::from_rgb(0x2E, 0x86, 0xC1), "Exchanged (OH/NH)");
+                            } else {
+                                ui.colored_label(color, row.change.to_string());
+                            }
+                            ui.label(fmt_ppm(row.reference));
+                            ui.label(fmt_int(row.reference));
+                            ui.label(fmt_ppm(row.compared));
+                            ui.label(fmt_int(row.compared));
+                            ui.label(row.intensity_ratio.map(|r| format!("{:.2}", r)).unwrap_or_else(|| "—".to_string()))
+                                .on_hover_text("Intensity change, normalised to the common peaks of each spectrum");
+                            ui.end_row();
+                        }
+                    });
+                });
+
+                ui.separator();
+                let count = |change| rows.iter().filter(|r| r.change == change).count();
+                ui.label(format!(
+                    "{} common, {} new, {} missing",
+                    count(processing::PeakChange::Common),
+                    count(processing::PeakChange::New),
+                    count(processing::PeakChange::Missing),
+                ));
+                if comparison.d2o_shake {
+                    ui.label(format!("{} exchangeable (OH/NH) peaks, marked in the peak table", exchanged.len()));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("📋 Copy as CSV").clicked() {
+                        ui.ctx().copy_text(comparison.to_csv());
+                    }
+                    if ui.button("💾 Export CSV…").clicked() {
+                        export = true;
+                    }
+                });
+            });
+        if repick {
+            comparison.pick_compared_peaks();
+            self.repro_log.add_entry(
+                "Peak List Comparison",
+                &format!(
+                    "Re-picked {} compared peaks at threshold {:.3}, min spacing {:.1} Hz; aligned by {:+.4} ppm",
+                    comparison.compared_peaks.len(),
+                    comparison.threshold,
+                    comparison.spacing_hz,
+                    comparison.offset_ppm
+                ),
+                "# peak list comparison (no NMRPipe equivalent)",
+            );
+        } else if realign {
+            comparison.realign();
+        }
+        if repick || realign || reflag {
+            self.annotate_exchange();
+        }
+        let Some(comparison) = self.spectrum_comparison.as_ref() else {
+            return;
+        };
+        if export {
+            if let Some(path) = rfd::FileDialog::new()
+                .set_title("Export Peak Comparison")
+                .set_file_name("peak_comparison.csv")
+                .add_filter("CSV (comma-separated)", &["csv"])
+                .save_file()
+            {
+                match std::fs::write(&path, comparison.to_csv()) {
+                    Ok(()) => {
+                        self.repro_log.add_entry(
+                            "Export Data",
+                            &format!("Peak comparison table written to {}", path.display()),
+                            "",
+                        );
+                        self.status_message = format!("Peak comparison exported to {}", path.display());
+                    }
+                    Err(e) => self.status_message = format!("Export failed: {}", e),
+                }
+            }
+        }
+        if !open {
+            self.spectrum_comparison = None;
+        }
+    }
+
+    /// Table of 1H/2H peak pairs with the isotope shift of each site.
+    /// Ask before a magnitude calculation throws away imaginary 2D data
+    fn show_magnitude_confirmation(&mut self, ctx: &egui::Context) {
+        let Some(action) = self.confirm_magnitude.clone() else {
+            return;
+        };
+        let mut choice = None;
+        egui::Window::new("⚠ Discard phase information?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label("This 2D data set has imaginary (hypercomplex) components.");
+                ui.label("A magnitude spectrum discards them: it can no longer be phased,\nand lines become broader. Undo is the only way back.");
+                ui.add_space(6.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Compute magnitude").clicked() {
+                        choice = Some(true);
+                    }
+                    if action == PipelineAction::ApplyFT2D && ui.button("Phase-sensitive FT instead").clicked() {
+                        self.pipeline_state.ft2d_mode = Ft2dMode::PhaseSensitive;
+                        choice = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        choice = Some(false);
+                    }
+                });
+            });
+        match choice {
+            Some(true) => {
+                self.confirm_magnitude = None;
+                self.magnitude_confirmed = true;
+                self.handle_pipeline_action(action);
+            }
+            Some(false) => {
+                self.confirm_magnitude = None;
+                self.memory_confirmed = None;
+                self.skip_undo_snapshot = false;
+                self.status_message = "Magnitude calculation cancelled".to_string();
+            }
+            None => {}
+        }
+    }
+
+    /// Ask before a step takes memory use past the budget, offering ways
+    /// to make it fit
+    fn show_memory_warning(&mut self, ctx: &egui::Context) {
+        let Some(warning) = self.confirm_memory.clone() else {
+            return;
+        };
+        let mb = |b: usize| b as f64 / (1024.0 * 1024.0);
+        let single = self.preferences.history_precision == StoragePrecision::Single;
+        let history = self.undo_stack.len() + self.redo_stack.len();
+        let mut choice = None;
+        egui::Window::new("⚠ Memory budget")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} would take memory use to about {:.0} MB,\npast the budget of {} MB{}.",
+                    warning.step,
+                    mb(warning.projected),
+                    self.preferences.memory_budget.budget_mb,
+                    if self.viewer { "" } else { " (Preferences → Undo history)" }
+                ));
+                ui.label(format!(
+                    "Its undo snapshot alone is {:.0} MB; the history holds {} snapshots.",
+                    mb(warning.snapshot),
+                    history
+                ));
+                ui.add_space(6.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Continue").clicked() {
+                        choice = Some(MemoryChoice::Continue);
+                    }
+                    if !single
+                        && ui
+                            .button("Store history in f32")
+                            .on_hover_text("Undo snapshots at single precision, half the memory")
+                            .clicked()
+                    {
+                        choice = Some(MemoryChoice::SinglePrecision);
+                    }
+                    if ui
+                        .button("Continue without undo")
+                        .on_hover_text("No snapshot for this step, and the undo/redo history is dropped")
+                        .clicked()
+                    {
+                        choice = Some(MemoryChoice::WithoutUndo);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        choice = Some(MemoryChoice::Cancel);
+                    }
+                });
+            });
+        let Some(choice) = choice else {
+            return;
+        };
+        self.confirm_memory = None;
+        match choice {
+            MemoryChoice::Cancel => {
+                self.status_message = format!("{} cancelled (memory budget)", warning.step);
+                return;
+            }
+            MemoryChoice::SinglePrecision => {
+                self.preferences.history_precision = StoragePrecision::Single;
+                // The viewer has no Preferences to turn it back
+                if !self.viewer {
+                    if let Err(e) = self.preferences.save() {
+                        log::warn!("Could not save preferences: {}", e);
+                    }
+                }
+            }
+            MemoryChoice::WithoutUndo => self.skip_undo_snapshot = true,
+            MemoryChoice::Continue => {}
+        }
+        self.memory_confirmed = Some(warning.action.clone());
+        self.handle_pipeline_action(warning.action);
+    }
+
+    /// Report NaN/Inf values in the data and offer to replace them
+    fn show_non_finite_dialog(&mut self, ctx: &egui::Context) {
+        let Some(report) = self.non_finite.clone() else {
+            return;
+        };
+        let mut choice = None;
+        let mut keep = false;
+        egui::Window::new("⚠ Non-finite values in the data")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "The data holds {} NaN and {} infinite value(s), usually from a corrupted conversion.",
+                    report.nan, report.infinite
+                ));
+                ui.label("They break autoscaling and spread through every Fourier transform.");
+                ui.add_space(4.0);
+                ui.label(egui::RichText::new(format!("At {}", report.locations.join(", "))).small().weak());
+                if report.total() > report.locations.len() {
+                    ui.label(egui::RichText::new(format!("and {} more", report.total() - report.locations.len())).small().weak());
+                }
+                ui.add_space(6.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Interpolate").on_hover_text("Straight line between the nearest finite points").clicked() {
+                        choice = Some(NonFiniteFix::Interpolate);
+                    }
+                    if ui.button("Replace with zero").clicked() {
+                        choice = Some(NonFiniteFix::Zero);
+                    }
+                    if ui.button("Leave as is").clicked() {
+                        keep = true;
+                    }
+                });
+            });
+        if keep {
+            self.non_finite = None;
+            self.non_finite_kept = report.total();
+            self.status_message = format!("{} NaN/Inf value(s) left in the data", report.total());
+        }
+        let Some(fix) = choice else {
+            return;
+        };
+        self.non_finite = None;
+        self.push_undo(ProcessingOp::ReplaceNonFinite { fix, count: report.total() });
+        if let Some(spectrum) = self.session.active_mut() {
+            let replaced = processing::replace_non_finite(spectrum, fix, &mut self.repro_log);
+            self.status_message = format!("Replaced {} NaN/Inf value(s) with {} values", replaced, fix);
+        }
+    }
+
+    /// Experiments of a Bruker sample folder, with their pulse programs
+    fn show_bruker_experiments_window(&mut self, ctx: &egui::Context) {
+        let Some((sample, experiments)) = &self.bruker_experiments else {
+            return;
+        };
+        let mut open = true;
+        let mut chosen = None;
+        egui::Window::new("📂 Bruker Experiments")
+            .open(&mut open)
+            .default_size([520.0, 320.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.label(format!("Sample: {}", sample.display()));
+                ui.separator();
+                egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                    egui::Grid::new("bruker_experiments_grid").striped(true).show(ui, |ui| {
+                        for heading in ["Expno", "Pulse program", "Nuclei", "NS", "Procnos", "Title", ""] {
+                            ui.strong(heading);
+                        }
+                        ui.end_row();
+                        for exp in experiments {
+                            ui.label(exp.expno.to_string());
+                            ui.monospace(&exp.pulprog);
+                            match &exp.indirect_nucleus {
+                                Some(f1) => ui.label(format!("{} / {} (2D)", exp.nucleus, f1)),
+                                None => ui.label(&exp.nucleus),
+                            };
+                            ui.label(exp.ns.to_string());
+                            let procnos: Vec<String> = exp.procnos.iter().map(u32::to_string).collect();
+                            ui.label(if procnos.is_empty() { "—".to_string() } else { procnos.join(", ") });
+                            ui.label(&exp.title);
+                            if ui.button("Load").clicked() {
+                                chosen = Some(exp.path.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+        if let Some(path) = chosen {
+            self.bruker_experiments = None;
+            self.load_path(path);
+        } else if !open {
+            self.bruker_experiments = None;
+            self.session_add = false;
+        }
+    }
+
+    fn show_open_candidates_window(&mut self, ctx: &egui::Context) {
+        let Some((dir, candidates)) = &self.open_candidates else {
+            return;
+        };
+        let mut open = true;
+        let mut chosen = None;
+        egui::Window::new("📂 Choose Dataset")
+            .open(&mut open)
+            .default_size([520.0, 320.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.label(format!("Several datasets in {}", dir.display()));
+                ui.separator();
+                egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                    egui::Grid::new("open_candidates_grid").striped(true).show(ui, |ui| {
+                        for heading in ["Name", "Kind", ""] {
+                            ui.strong(heading);
+                        }
+                        ui.end_row();
+                        for candidate in candidates {
+                            let name = candidate.path.strip_prefix(dir).unwrap_or(&candidate.path);
+                            ui.monospace(name.display().to_string());
+                            ui.label(&candidate.kind);
+                            if ui.button("Load").clicked() {
+                                chosen = Some(candidate.path.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+        if let Some(path) = chosen {
+            self.open_candidates = None;
+            self.load_path(path);
+        } else if !open {
+            self.open_candidates = None;
+            self.session_add = false;
+        }
+    }
+
+    fn show_isotope_window(&mut self, ctx: &egui::Context) {
+        self.finish_isotope_load();
+        if let Some(pending) = &self.isotope_load {
+            egui::Window::new("⚖ 1H/2H Isotope Shifts").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(format!("Loading {}…", pending.load.path.display()));
+                });
+                if ui.button("✖ Cancel").clicked() {
+                    pending.load.cancel();
+                }
+            });
+            if pending.load.is_cancelled() {
+                self.isotope_load = None;
+            }
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+            return;
+        }
+        let Some(comparison) = self.isotope_comparison.as_mut() else {
+            return;
+        };
+        let mut open = true;
+        let mut repick = false;
+        egui::Window::new("⚖ 1H/2H Isotope Shifts")
+            .open(&mut open)
+            .default_size([420.0, 360.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                let name = comparison
+                    .partner
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                ui.label(format!("Compared with: {}", name));
+                ui.horizontal(|ui| {
+                    ui.label("Match tolerance:");
+                    ui.add(
+                        egui::DragValue::new(&mut comparison.tolerance_ppm)
+                            .speed(0.005)
+                            .range(0.001..=0.5)
+                            .suffix(" ppm"),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    let partner = if comparison.partner_is_d { "2H" } else { "1H" };
+                    ui.label(format!("{} peaks: threshold", partner));
+                    ui.add(
+                        egui::DragValue::new(&mut comparison.partner_threshold)
+                            .speed(0.005)
+                            .range(0.001..=1.0),
+                    );
+                    ui.label("spacing");
+                    ui.add(
+                        egui::DragValue::new(&mut comparison.partner_spacing_hz)
+                            .speed(0.5)
+                            .range(0.1..=500.0)
+                            .suffix(" Hz"),
+                    );
+                    if ui.button("Re-pick").clicked() {
+                        repick = true;
+                    }
+                });
+                ui.separator();
+
+                let pairs = comparison.pairs();
+                let fmt = |v: Option<f64>, decimals: usize| {
+                    v.map(|v| format!("{:.*}", decimals, v)).unwrap_or_else(|| "—".to_string())
+                };
+                egui::ScrollArea::vertical().max_height(260.0).show(ui, |ui| {
+                    egui::Grid::new("isotope_shift_grid").striped(true).show(ui, |ui| {
+                        ui.strong("δ 1H (ppm)");
+                        ui.strong("δ 2H (ppm)");
+                        ui.strong("Δδ (ppb)");
+                        ui.strong("2H rel.");
+                        ui.end_row();
+                        for p in &pairs {
+                            ui.label(format!("{:.3}", p.h_ppm));
+                            ui.label(fmt(p.d_ppm, 3));
+                            ui.label(fmt(p.shift_ppb, 1));
+                            ui.label(fmt(p.d_relative, 2));
+                            ui.end_row();
+                        }
+                    });
+                });
+
+                ui.separator();
+                let matched = pairs.iter().filter(|p| p.d_ppm.is_some()).count();
+                ui.label(format!("{} of {} 1H sites show 2H", matched, pairs.len()));
+                if ui.button("📋 Copy as CSV").clicked() {
+                    let mut csv = String::from("h_ppm,d_ppm,shift_ppb,d_relative\n");
+                    for p in &pairs {
+                        csv.push_str(&format!(
+                            "{:.4},{},{},{}\n",
+                            p.h_ppm,
+                            fmt(p.d_ppm, 4).replace('—', ""),
+                            fmt(p.shift_ppb, 2).replace('—', ""),
+                            fmt(p.d_relative, 3).replace('—', ""),
+                        ));
+                    }
+                    ui.ctx().copy_text(csv);
+                }
+            });
+        if repick {
+            comparison.pick_partner_peaks();
+            let (threshold, spacing, count) =
+                (comparison.partner_threshold, comparison.partner_spacing_hz, comparison.partner_peaks.len());
+            self.repro_log.add_entry(
+                "Isotope Shift Comparison",
+                &format!(
+                    "Re-picked {} partner peaks at threshold {:.3}, min spacing {:.1} Hz",
+                    count, threshold, spacing
+                ),
+                "# 1H/2H isotope-shift pairing (no NMRPipe equivalent)",
+            );
+        }
+        if !open {
+            self.isotope_comparison = None;
+        }
+    }
+
+    /// Convert `source` with both backends on a background thread.
+    fn start_validation(&mut self, source: PathBuf) {
+        let result = Arc::new(Mutex::new(None));
+        let shared = Arc::clone(&result);
+        let path = source.clone();
+        let ctx = self.egui_ctx.clone();
+        std::thread::spawn(move || {
+            let report = validation::validate_conversion(&path, &crate::gui::conversion_dialog::ConversionSettings::default())
+                .map_err(|e| e.to_string());
+            *shared.lock().unwrap() = Some(report);
+            ctx.request_repaint();
+        });
+        self.status_message = format!("Validating conversion of {}…", source.display());
+        self.conversion_validation = Some(ConversionValidation { source, result, logged: false });
+    }
+
+    /// Progress and result of a conversion validation.
+    fn show_validation_window(&mut self, ctx: &egui::Context) {
+        let Some(run) = self.conversion_validation.as_mut() else {
+            return;
+        };
+        let result = run.result.lock().unwrap().clone();
+        let tolerance = validation::DEFAULT_TOLERANCE;
+
+        if let (Some(result), false) = (&result, run.logged) {
+            run.logged = true;
+            let (desc, status) = match result {
+                Ok(report) => (
+                    report.to_text(tolerance),
+                    format!(
+                        "Conversion validation {}",
+                        if report.passed(tolerance) { "passed" } else { "found differences" }
+                    ),
+                ),
+                Err(e) => (
+                    format!("Validation of {} failed: {}", run.source.display(), e),
+                    format!("Conversion validation failed: {}", e),
+                ),
+            };
+            self.repro_log.add_entry("Conversion Validation", &desc, "# built-in reader vs NMRPipe converter");
+            self.status_message = status;
+        }
+
+        let mut open = true;
+        egui::Window::new("🛠 Conversion Validation")
+            .open(&mut open)
+            .default_size([560.0, 260.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.label(format!("Dataset: {}", run.source.display()));
+                ui.separator();
+                match &result {
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("Converting with the built-in reader and NMRPipe…");
+                        });
+                    }
+                    Some(Ok(report)) => {
+                        ui.style_mut().override_font_id = Some(egui::FontId::monospace(11.0));
+                        ui.label(report.to_text(tolerance));
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(egui::Color32::from_rgb(0xD0, 0x30, 0x30), e);
+                    }
+                }
+            });
+        if !open && result.is_some() {
+            self.conversion_validation = None;
+        }
+    }
+
+    /// Pick two NMRPipe files and show the differences of their headers.
+    fn compare_headers(&mut self) {
+        let pick = |title: &str| {
+            rfd::FileDialog::new()
+                .set_title(title)
+                .add_filter("NMRPipe", &["fid", "ft1", "ft2", "ft3"])
+                .add_filter("All Files", &["*"])
+                .pick_file()
+        };
+        let Some(a) = pick("First NMRPipe File") else {
+            return;
+        };
+        let Some(b) = pick("Second NMRPipe File") else {
+            return;
+        };
+        let title = format!("{} vs {}", a.display(), b.display());
+        match validation::header_diff(&a, &b) {
+            Ok((text, differences)) => {
+                self.status_message = format!("{} header parameters differ", differences);
+                self.header_diff = Some((title, text));
+            }
+            Err(e) => self.status_message = format!("Header comparison failed: {}", e),
+        }
+    }
+
+    /// Result of Compare NMRPipe Headers.
+    fn show_header_diff_window(&mut self, ctx: &egui::Context) {
+        let Some((title, text)) = &self.header_diff else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new("🛠 NMRPipe Header Diff")
+            .open(&mut open)
+            .default_size([520.0, 320.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.label(title);
+                ui.separator();
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.style_mut().override_font_id = Some(egui::FontId::monospace(11.0));
+                    ui.label(text);
+                });
+                ui.separator();
+                if ui.button("📋 Copy").clicked() {
+                    ui.ctx().copy_text(text.clone());
+                }
+            });
+        if !open {
+            self.header_diff = None;
+        }
+    }
+
+    /// Teaching mode: explain the operations logged since the last frame.
+    fn show_explanation_window(&mut self, ctx: &egui::Context) {
+        // Undo and new files shorten the log
+        self.explained_upto = self.explained_upto.min(self.repro_log.len());
+        if self.preferences.teaching_mode && !self.viewer {
+            let new = &self.repro_log.entries[self.explained_upto..];
+            self.explanations
+                .extend(new.iter().filter_map(|e| teaching::explain(e).map(|x| (e.clone(), x))));
+        }
+        self.explained_upto = self.repro_log.len();
+        if self.explanations.is_empty() {
+            return;
+        }
+
+        let mut open = true;
+        let mut turn_off = false;
+        egui::Window::new("🎓 What just happened?")
+            .open(&mut open)
+            .default_size([460.0, 300.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().max_height(420.0).show(ui, |ui| {
+                    for (entry, explanation) in &self.explanations {
+                        ui.heading(&entry.operation);
+                        ui.label(egui::RichText::new(&entry.description).small().italics());
+                        ui.add_space(4.0);
+                        ui.label(egui::RichText::new("What it does").strong());
+                        ui.label(&explanation.what);
+                        ui.label(egui::RichText::new("Why").strong());
+                        ui.label(&explanation.why);
+                        if !entry.nmrpipe_command.is_empty() && !entry.nmrpipe_command.starts_with('#') {
+                            ui.label(egui::RichText::new(&entry.nmrpipe_command).monospace().small());
+                        }
+                        ui.separator();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("✔ Got it").clicked() {
+                        self.explanations.clear();
+                    }
+                    if ui.button("Turn off teaching mode").clicked() {
+                        turn_off = true;
+                    }
+                });
+            });
+        if !open {
+            self.explanations.clear();
+        }
+        if turn_off {
+            self.explanations.clear();
+            self.preferences.teaching_mode = false;
+            if let Err(e) = self.preferences.save() {
+                log::warn!("Could not save preferences: {}", e);
+            }
+        }
+    }
+
+    /// Ask the user where a project's missing source data went.
+    fn show_relink_dialog(&mut self, ctx: &egui::Context) {
+        let Some(missing) = self.missing_source.clone() else {
+            return;
         };
-        self.current_theme = new_theme;
-        self.theme_colors = ThemeColors::from_theme(new_theme);
-
-        // Reset processing state
-        self.undo_stack.clear();
-        self.redo_stack.clear();
-        self.before_snapshot = None;
-        self.repro_log = ReproLog::new();
-
-        Ok(())
+        let name = missing
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| missing.display().to_string());
+        egui::Modal::new(egui::Id::new("relink_source_dialog")).show(ctx, |ui| {
+            ui.set_max_width(420.0);
+            ui.strong("Source data not found");
+            ui.label(format!("The project's data was at:\n{}", missing.display()));
+            ui.label(format!("Pick the folder that now contains \"{}\" (or the dataset itself).", name));
+            ui.add_space(6.0);
+            ui.horizontal(|ui| {
+                if ui.button("📂 Locate…").clicked() {
+                    if let Some(folder) = toolbar::open_folder_dialog() {
+                        match relink::relink_candidate(&missing, &folder) {
+                            Some(new_source) => {
+                                self.relink_source(&missing, &new_source);
+                                self.repro_log.add_entry(
+                                    "Relink Source",
+                                    &format!("Source data moved\n# From: {}\n# To: {}", missing.display(), new_source.display()),
+                                    "# relinked missing source data",
+                                );
+                                self.status_message = format!("Relinked source data: {}", new_source.display());
+                                self.missing_source = None;
+                            }
+                            None => {
+                                self.status_message = format!("\"{}\" not found in {}", name, folder.display());
+                            }
+                        }
+                    }
+                }
+                if ui.button("Ignore").clicked() {
+                    self.missing_source = None;
+                }
+            });
+        });
     }
 
     /// Handle toolbar actions
@@ -1583,17 +5470,58 @@ impl NmrApp {
         match action {
             ToolbarAction::OpenFile => {
                 if let Some(path) = toolbar::open_file_dialog() {
+                    self.session_add = false;
                     self.load_path(path);
                 }
             }
+            ToolbarAction::AddToSession => self.add_to_session(),
+            ToolbarAction::ShowSession => self.show_session = true,
+            ToolbarAction::RunNmrPipeScript => self.run_nmrpipe_script(),
             ToolbarAction::OpenFolder => {
                 if let Some(path) = toolbar::open_folder_dialog() {
+                    self.session_add = false;
                     self.load_path(path);
                 }
             }
+            ToolbarAction::BatchConvert => {
+                if self.batch_conversion.as_ref().map(|b| !b.status().finished).unwrap_or(false) {
+                    self.status_message = "A batch conversion is already running".to_string();
+                } else if let Some(dir) = toolbar::open_folder_dialog() {
+                    let out_dir = self.work_dir.output_dir_for(&dir);
+                    let settings = self.make_settings(Some(&self.conversion_dialog_state.settings));
+                    let profiles = &self.preferences.processing_profiles;
+                    let profiles = profiles.batch_process.then(|| profiles.clone());
+                    match batch::start_batch_conversion(&dir, &out_dir, &settings, profiles, self.egui_ctx.clone()) {
+                        Ok(handle) => {
+                            self.status_message = format!(
+                                "Batch converting {} datasets → {}",
+                                handle.status().total,
+                                out_dir.display()
+                            );
+                            self.batch_conversion = Some(handle);
+                        }
+                        Err(e) => self.status_message = e,
+                    }
+                }
+            }
+            ToolbarAction::RevealConvertedFiles => self.reveal_converted_files(),
+            ToolbarAction::ShowPreferences => {
+                self.preferences_dialog_state.draft = self.preferences.clone();
+                self.preferences_dialog_state.open = true;
+            }
+            ToolbarAction::OpenInEditor => {
+                let Some(source) = self.session.active().map(|s| s.source_path.clone()) else {
+                    return;
+                };
+                let started = std::env::current_exe().and_then(|exe| std::process::Command::new(exe).arg(&source).spawn());
+                self.status_message = match started {
+                    Ok(_) => format!("Opening {} in the full editor", source.display()),
+                    Err(e) => format!("Could not start the full editor: {}", e),
+                };
+            }
             ToolbarAction::SaveProject => {
-                if self.spectrum.is_some() {
-                    let default_name = self.spectrum.as_ref()
+                if self.session.active().is_some() {
+                    let default_name = self.session.active()
                         .map(|s| format!("{}.nmrproj", s.sample_name))
                         .unwrap_or_else(|| "project.nmrproj".to_string());
                     if let Some(path) = rfd::FileDialog::new()
@@ -1602,8 +5530,34 @@ impl NmrApp {
                         .add_filter("NMR Project", &["nmrproj"])
                         .save_file()
                     {
-                        match self.save_project(&path) {
-                            Ok(_) => self.status_message = format!("Project saved: {}", path.display()),
+                        match self.save_project(&path, None) {
+                            Ok(_) => self.status_message = self.saved_project_status(&path),
+                            Err(e) => self.status_message = format!("Save failed: {}", e),
+                        }
+                    }
+                } else {
+                    self.status_message = "No spectrum loaded to save".to_string();
+                }
+            }
+            ToolbarAction::SaveProjectBundle => {
+                if let Some(spectrum) = self.session.active() {
+                    let default_name = format!("{}.{}", spectrum.sample_name, bundle::BUNDLE_EXTENSION);
+                    if let Some(path) = rfd::FileDialog::new()
+                        .set_title("Save Project as Bundle")
+                        .set_file_name(&default_name)
+                        .add_filter("Project bundle folder", &[bundle::BUNDLE_EXTENSION])
+                        .add_filter("Zipped project bundle", &["zip"])
+                        .save_file()
+                    {
+                        // A .zip is built in a temporary folder, then packed
+                        let (bundle_dir, archive) = if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("zip")) {
+                            (bundle::staging_dir_for(&path), Some(path))
+                        } else {
+                            (path.with_extension(bundle::BUNDLE_EXTENSION), None)
+                        };
+                        let target = archive.clone().unwrap_or_else(|| bundle_dir.clone());
+                        match self.start_project_bundle(&bundle_dir, archive) {
+                            Ok(()) => self.status_message = format!("Copying raw data for {}…", target.display()),
                             Err(e) => self.status_message = format!("Save failed: {}", e),
                         }
                     }
@@ -1617,29 +5571,12 @@ impl NmrApp {
                     .add_filter("NMR Project", &["nmrproj"])
                     .pick_file()
                 {
-                    match self.load_project(&path) {
-                        Ok(_) => {
-                            let name = self.spectrum.as_ref()
-                                .map(|s| s.sample_name.clone())
-                                .unwrap_or_else(|| "unknown".to_string());
-                            self.status_message = format!("Project loaded: {}", name);
-                        }
-                        Err(e) => self.status_message = format!("Load failed: {}", e),
-                    }
+                    self.open_project(&path);
                 }
             }
             ToolbarAction::ExportImage => {
-                if self.spectrum.is_some() {
-                    // Initialize ppm range from spectrum data
-                    if let Some(spectrum) = &self.spectrum {
-                        if spectrum.is_frequency_domain && !spectrum.axes.is_empty() {
-                            let ppm_scale = spectrum.axes[0].ppm_scale();
-                            let ppm_min = ppm_scale.iter().cloned().fold(f64::INFINITY, f64::min);
-                            let ppm_max = ppm_scale.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
-                            self.export_tab_state.image_settings.ppm_start = ppm_max;
-                            self.export_tab_state.image_settings.ppm_end = ppm_min;
-                        }
-                    }
+                if self.session.active().is_some() {
+                    self.init_export_range();
                     self.export_tab_state.active_section = 0;
                     self.domain_tab = DomainTab::Export;
                 } else {
@@ -1647,7 +5584,7 @@ impl NmrApp {
                 }
             }
             ToolbarAction::ExportData => {
-                if self.spectrum.is_some() {
+                if self.session.active().is_some() {
                     self.export_tab_state.active_section = 1;
                     self.domain_tab = DomainTab::Export;
                 } else {
@@ -1663,6 +5600,7 @@ impl NmrApp {
                     let result = match ext.as_str() {
                         "json" => self.repro_log.save_json(&path),
                         "sh" => self.repro_log.save_script(&path),
+                        "md" => self.repro_log.save_narrative(&path),
                         _ => self.repro_log.save_text(&path),
                     };
                     match result {
@@ -1680,6 +5618,17 @@ impl NmrApp {
             ToolbarAction::ShowAbout => {
                 self.show_about = true;
             }
+            ToolbarAction::ValidateConversion => {
+                let source = self
+                    .session
+                    .active()
+                    .map(|s| s.source_path.clone())
+                    .or_else(toolbar::open_file_dialog);
+                if let Some(source) = source {
+                    self.start_validation(source);
+                }
+            }
+            ToolbarAction::CompareHeaders => self.compare_headers(),
             ToolbarAction::ThemeToggle => {
                 self.current_theme = self.current_theme.next();
                 self.theme_colors = ThemeColors::from_theme(self.current_theme);
@@ -1697,6 +5646,19 @@ impl NmrApp {
                     self.conversion_method.label()
                 );
             }
+            ToolbarAction::ToggleTeachingMode => {
+                self.preferences.teaching_mode = !self.preferences.teaching_mode;
+                self.explained_upto = self.repro_log.len();
+                self.explanations.clear();
+                if let Err(e) = self.preferences.save() {
+                    log::warn!("Could not save preferences: {}", e);
+                }
+                self.status_message = if self.preferences.teaching_mode {
+                    "Teaching mode on — each processing step will be explained".to_string()
+                } else {
+                    "Teaching mode off".to_string()
+                };
+            }
             ToolbarAction::ZoomReset => {
                 self.spectrum_view_state.auto_scale = true;
                 self.status_message = "Zoom reset".to_string();
@@ -1705,16 +5667,84 @@ impl NmrApp {
         }
     }
 
+    /// Converted NMRPipe output for the current data: the spectrum's
+    /// converter output, else the last batch folder, else this session's
+    /// scratch folder (if it exists).
+    fn converted_files_location(&self) -> Option<PathBuf> {
+        self.session
+            .active()
+            .and_then(|s| s.nmrpipe_path.clone())
+            .or_else(|| self.batch_conversion.as_ref().map(|b| b.out_dir.clone()))
+            .or_else(|| self.work_dir.session_dir())
+            .filter(|p| p.exists())
+    }
+
+    /// Open the file manager at the converted NMRPipe files
+    fn reveal_converted_files(&mut self) {
+        match self.converted_files_location() {
+            Some(path) => match workdir::reveal_in_file_manager(&path) {
+                Ok(_) => self.status_message = format!("Converted files: {}", path.display()),
+                Err(e) => self.status_message = format!("Could not open file manager for {}: {}", path.display(), e),
+            },
+            None => {
+                self.status_message =
+                    "No converted files yet — built-in readers convert in memory".to_string();
+            }
+        }
+    }
+
+    /// Handle 2D phase correction; the mode stays open after Apply so the
+    /// other dimension can be phased next
+    fn handle_phase_2d_action(&mut self, action: PhaseAction) {
+        match action {
+            PhaseAction::Start => {
+                self.phase_2d_state.ph0 = 0.0;
+                self.phase_2d_state.ph1 = 0.0;
+                if let Some(spectrum) = self.session.active() {
+                    self.phase_2d_state.select_tallest(spectrum);
+                }
+            }
+            PhaseAction::UpdatePreview => {
+                if let Some(spectrum) = self.session.active() {
+                    self.phase_2d_state.compute_preview(spectrum);
+                }
+            }
+            PhaseAction::Apply => {
+                let Phase2dState { dim, ph0, ph1, .. } = self.phase_2d_state;
+                self.push_undo(ProcessingOp::PhaseCorrection2D { dim, ph0, ph1 });
+                let Some(spectrum) = self.session.active_mut() else {
+                    return;
+                };
+                match processing::phase_correct_2d(spectrum, dim, ph0, ph1, &mut self.repro_log) {
+                    Ok(()) => {
+                        self.phase_2d_state.ph0 = 0.0;
+                        self.phase_2d_state.ph1 = 0.0;
+                        self.phase_2d_state.compute_preview(spectrum);
+                        self.status_message = format!("2D phase applied along {}: PH0={:.1}°, PH1={:.1}°", dim, ph0, ph1);
+                    }
+                    Err(e) => {
+                        self.undo_stack.pop();
+                        self.status_message = format!("2D phase correction failed: {}", e);
+                    }
+                }
+            }
+            PhaseAction::Cancel => self.phase_2d_state = Phase2dState::default(),
+            PhaseAction::None => {}
+        }
+    }
+
     /// Handle interactive phase correction
     fn handle_phase_action(&mut self, action: PhaseAction) {
         match action {
             PhaseAction::Start => {
-                if let Some(spectrum) = &self.spectrum {
+                self.phase_dialog_state.on_peak = false;
+                self.phase_dialog_state.anchors.clear();
+                if let Some(spectrum) = self.session.active() {
                     self.phase_dialog_state.compute_preview(spectrum);
                 }
             }
             PhaseAction::UpdatePreview => {
-                if let Some(spectrum) = &self.spectrum {
+                if let Some(spectrum) = self.session.active() {
                     self.phase_dialog_state.compute_preview(spectrum);
                 }
             }
@@ -1726,9 +5756,24 @@ impl NmrApp {
                 // Apply the phase correction permanently
                 let op = ProcessingOp::PhaseCorrection { ph0, ph1 };
                 self.push_undo(op);
-                if let Some(spectrum) = self.spectrum.as_mut() {
+                let anchors = std::mem::take(&mut self.phase_dialog_state.anchors);
+                self.phase_dialog_state.on_peak = false;
+                if !anchors.is_empty() {
+                    let peaks: Vec<String> = anchors.iter().map(|a| format!("{:.3} ppm", a.ppm)).collect();
+                    self.repro_log.add_entry(
+                        "Phase on Peak",
+                        &format!(
+                            "{} from the absorptive phase of the peak(s) at {}",
+                            if anchors.len() == 2 { "PH0 and PH1" } else { "PH0" },
+                            peaks.join(" and ")
+                        ),
+                        "# phase on peak (values applied with PS below)",
+                    );
+                }
+                if let Some(spectrum) = self.session.active_mut() {
                     processing::phase_correct(spectrum, ph0, ph1, &mut self.repro_log);
                 }
+                self.refresh_annotation_intensities();
                 self.pipeline_state.ph0 = ph0;
                 self.pipeline_state.ph1 = ph1;
                 self.status_message =
@@ -1736,6 +5781,8 @@ impl NmrApp {
             }
             PhaseAction::Cancel => {
                 self.phase_dialog_state.active = false;
+                self.phase_dialog_state.on_peak = false;
+                self.phase_dialog_state.anchors.clear();
                 self.phase_dialog_state.ph0 = 0.0;
                 self.phase_dialog_state.ph1 = 0.0;
                 self.phase_dialog_state.preview.clear();
@@ -1746,6 +5793,12 @@ impl NmrApp {
 }
 
 impl eframe::App for NmrApp {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if let Err(e) = self.work_dir.cleanup() {
+            log::warn!("Failed to clean up conversion outputs: {}", e);
+        }
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // ── Re-apply theme each frame (ensures toggle takes effect) ──
         theme::apply_theme(ctx, self.current_theme);
@@ -1761,11 +5814,48 @@ impl eframe::App for NmrApp {
             }
         });
 
-        // Process dropped files
-        if let Some(path) = self.dropped_files.pop() {
+        // Process dropped files: one opens like File → Open, several are
+        // offered for picking
+        if self.dropped_files.len() > 1 {
+            self.session_add = false;
+            let dropped = std::mem::take(&mut self.dropped_files);
+            let dir = dropped[0].parent().map(PathBuf::from).unwrap_or_default();
+            let candidates: Vec<Candidate> = dropped.iter().filter_map(|p| Candidate::for_path(p)).collect();
+            match candidates.len() {
+                0 => self.status_message = "None of the dropped files is NMR data or a project".to_string(),
+                1 => self.load_path(candidates[0].path.clone()),
+                _ => self.open_candidates = Some((dir, candidates)),
+            }
+        } else if let Some(path) = self.dropped_files.pop() {
+            self.session_add = false;
             self.load_path(path);
         }
 
+        // ── Background load progress ──
+        self.show_load_overlay(ctx);
+        self.poll_ipc();
+        self.show_bundle_progress(ctx);
+        self.show_export_progress(ctx);
+        self.show_tool_progress(ctx);
+        self.show_relink_dialog(ctx);
+        self.show_bruker_experiments_window(ctx);
+        self.show_open_candidates_window(ctx);
+        self.show_magnitude_confirmation(ctx);
+        self.show_memory_warning(ctx);
+        self.show_non_finite_dialog(ctx);
+        self.show_isotope_window(ctx);
+        self.show_comparison_window(ctx);
+        self.show_carbon_types_window(ctx);
+        self.show_correlation_window(ctx);
+        self.show_peak_table_window(ctx);
+        self.show_reference_library_window(ctx);
+        self.show_session_window(ctx);
+        self.show_titration_window(ctx);
+        self.show_validation_window(ctx);
+        self.show_template_run_window(ctx);
+        self.show_header_diff_window(ctx);
+        self.show_explanation_window(ctx);
+
         // ── Conversion Dialog ──
         let conv_action =
             conversion_dialog::show_conversion_dialog(ctx, &mut self.conversion_dialog_state);
@@ -1774,6 +5864,7 @@ impl eframe::App for NmrApp {
                 let path = self.conversion_dialog_state.pending_path.take();
                 let settings = self.conversion_dialog_state.settings.clone();
                 self.conversion_dialog_state.open = false;
+                self.conversion_dialog_state.dmx_hint = None;
                 if let Some(path) = path {
                     self.do_load(&path, Some(&settings));
                 }
@@ -1781,6 +5872,8 @@ impl eframe::App for NmrApp {
             ConversionAction::Cancel => {
                 self.conversion_dialog_state.open = false;
                 self.conversion_dialog_state.pending_path = None;
+                self.conversion_dialog_state.dmx_hint = None;
+                self.session_add = false;
                 self.status_message = "Conversion cancelled".to_string();
             }
             ConversionAction::None => {}
@@ -1814,18 +5907,8 @@ impl eframe::App for NmrApp {
                 };
                 if let Some(path) = dialog {
                     let settings = self.export_dialog_state.settings.clone();
-                    match self.export_spectrum_image_with_settings(&path, &settings) {
-                        Ok(_) => {
-                            self.status_message = format!("Image exported: {}", path.display());
-                            self.repro_log.add_entry(
-                                "Export Image",
-                                &format!("Exported spectrum image to {}", path.display()),
-                                "",
-                            );
-                        }
-                        Err(e) => {
-                            self.status_message = format!("Image export failed: {}", e);
-                        }
+                    if let Err(e) = self.start_image_export(&path, &settings) {
+                        self.status_message = format!("Image export failed: {}", e.user_message());
                     }
                 }
             }
@@ -1835,24 +5918,81 @@ impl eframe::App for NmrApp {
             ExportAction::None => {}
         }
 
+        // ── Preferences Window ──
+        let session_dir = self.work_dir.session_dir();
+        let cache_usage = if self.preferences_dialog_state.open {
+            self.conversion_cache().usage()
+        } else {
+            (0, 0)
+        };
+        match preferences::show_preferences_dialog(
+            ctx,
+            &mut self.preferences_dialog_state,
+            session_dir.as_deref(),
+            cache_usage,
+        ) {
+            PreferencesAction::Apply => {
+                self.preferences = self.preferences_dialog_state.draft.clone();
+                self.preferences_dialog_state.open = false;
+                self.pipeline_state.external_tools =
+                    self.preferences.external_tools.iter().map(|t| t.name.clone()).collect();
+                if !self.viewer {
+                    self.work_dir.settings = self.preferences.work_dir.clone();
+                }
+                self.spectrum_view_state.shift_regions = self.preferences.shift_regions.regions.clone();
+                self.set_axis_direction(self.preferences.axis_direction);
+                if let Err(e) = self.conversion_cache().evict() {
+                    log::warn!("Cache eviction failed: {}", e);
+                }
+                self.status_message = match self.preferences.save() {
+                    Ok(_) => "Preferences saved".to_string(),
+                    Err(e) => format!("Preferences applied but not saved: {}", e),
+                };
+            }
+            PreferencesAction::Cancel => {
+                self.preferences_dialog_state.open = false;
+            }
+            PreferencesAction::RevealWorkDir => {
+                if let Some(dir) = session_dir.filter(|d| d.exists()) {
+                    if let Err(e) = workdir::reveal_in_file_manager(&dir) {
+                        self.status_message = format!("Could not open file manager: {}", e);
+                    }
+                } else {
+                    self.status_message = "Nothing converted into this session's folder yet".to_string();
+                }
+            }
+            PreferencesAction::ClearCache => {
+                self.status_message = match self.conversion_cache().clear() {
+                    Ok(_) => "Conversion cache cleared".to_string(),
+                    Err(e) => format!("Failed to clear conversion cache: {}", e),
+                };
+            }
+            PreferencesAction::None => {}
+        }
+
         // ── Toolbar ──
         let theme_label = self.current_theme.label();
         let method_label = self.conversion_method.short_label();
-        let toolbar_action = toolbar::show_toolbar(
-            ctx,
-            theme_label,
-            method_label,
-            !self.undo_stack.is_empty(),
-            !self.redo_stack.is_empty(),
-        );
+        let toolbar_action = if self.viewer {
+            toolbar::show_viewer_toolbar(ctx, theme_label, self.session.active().is_some())
+        } else {
+            toolbar::show_toolbar(
+                ctx,
+                theme_label,
+                method_label,
+                self.preferences.teaching_mode,
+                !self.undo_stack.is_empty(),
+                !self.redo_stack.is_empty(),
+                self.converted_files_location().is_some(),
+            )
+        };
         if toolbar_action != ToolbarAction::None {
             self.handle_toolbar_action(toolbar_action);
         }
 
         // ── Status Bar ──
         let tc = &self.theme_colors;
-        let phase_active = self.phase_dialog_state.active;
-        let cursor_mode = theme::cursor_mode_label(&self.spectrum_view_state, phase_active);
+        let cursor_mode = theme::cursor_mode_label(&self.spectrum_view_state, &self.phase_dialog_state);
         let sb_bg = tc.status_bar_bg;
         let sb_text = tc.status_text;
         let sb_muted = tc.text_muted;
@@ -1924,7 +6064,7 @@ impl eframe::App for NmrApp {
                         }
                     }
                     // Show what method was used for current spectrum
-                    if let Some(spectrum) = &self.spectrum {
+                    if let Some(spectrum) = self.session.active() {
                         if !spectrum.conversion_method_used.is_empty() {
                             ui.separator();
                             ui.label(
@@ -1956,64 +6096,127 @@ impl eframe::App for NmrApp {
                             .size(11.0)
                             .color(sb_muted),
                     );
+                    ui.separator();
+                    let (data_bytes, history_bytes) = self.memory_usage();
+                    let mb = |b: usize| b as f64 / (1024.0 * 1024.0);
+                    let budget = &self.preferences.memory_budget;
+                    let over = budget.is_exceeded_by(data_bytes + history_bytes);
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "{} {:.1} MB",
+                            if over { "⚠" } else { "🧠" },
+                            mb(data_bytes + history_bytes)
+                        ))
+                        .size(11.0)
+                        .color(if over { sb_warning } else { sb_muted }),
+                    )
+                    .on_hover_text(format!(
+                        "Spectrum data: {:.1} MB\nUndo/redo history: {:.1} MB ({} snapshots, {})\nBudget: {}",
+                        mb(data_bytes),
+                        mb(history_bytes),
+                        self.undo_stack.len() + self.redo_stack.len(),
+                        self.preferences.history_precision.label(),
+                        if budget.warn { format!("{} MB", budget.budget_mb) } else { "not checked".to_string() }
+                    ));
                 });
             });
         });
 
         // ── Left Panel: Processing Pipeline ──
-        let has_data = self.spectrum.is_some();
+        let has_data = self.session.active().is_some();
         let is_freq = self
-            .spectrum
-            .as_ref()
+            .session
+            .active()
             .map(|s| s.is_frequency_domain)
             .unwrap_or(false);
         let op_count = self.repro_log.len();
 
         let is_2d = self
-            .spectrum
-            .as_ref()
+            .session
+            .active()
             .map(|s| s.is_2d())
             .unwrap_or(false);
+        let has_imaginary = self.session.active().is_some_and(|s| s.has_imaginary());
+        let provenance = self.session.active().map(|s| s.provenance).unwrap_or_default();
+        let nus = self
+            .session
+            .active()
+            .and_then(|s| s.nus_schedule.as_ref())
+            .map(|n| (n.sampled.len(), n.grid));
+        let group_delay = self.session.active().and_then(|s| s.group_delay);
 
         let mut pipeline_action_deferred = PipelineAction::None;
-        let picking_modes = pipeline_panel::PickingModes {
+        let integral_baseline = self.spectrum_view_state.integral_baseline;
+        let picking = pipeline_panel::PickingModes {
             peak_picking: self.spectrum_view_state.peak_picking,
             baseline_picking: self.spectrum_view_state.baseline_picking,
             integration_picking: self.spectrum_view_state.integration_picking,
             j_coupling_picking: self.spectrum_view_state.j_coupling_picking,
+            auto_j_picking: self.spectrum_view_state.auto_j_picking,
+            linewidth_picking: self.spectrum_view_state.linewidth_picking,
+            fit_picking: self.spectrum_view_state.fit_picking,
+            enhance_picking: self.spectrum_view_state.enhance.picking,
+            solvent_picking: self.spectrum_view_state.solvent_picking,
+            solvent_regions: self.spectrum_view_state.solvent_regions.clone(),
+        };
+        let context = pipeline_panel::PipelinePanelContext {
+            has_data,
+            is_freq_domain: is_freq,
+            is_2d,
+            has_imaginary,
+            nus,
+            group_delay,
+            provenance,
+            operation_count: op_count,
+            picking,
+            has_before_snapshot: self.before_snapshot.is_some(),
         };
+        // The viewer has no processing pipeline
         egui::SidePanel::left("pipeline_panel")
             .resizable(true)
             .default_width(260.0)
             .min_width(200.0)
             .max_width(400.0)
-            .show(ctx, |ui| {
+            .show_animated(ctx, !self.viewer, |ui| {
                 egui::ScrollArea::vertical()
                     .auto_shrink([false, false])
                     .show(ui, |ui| {
                     pipeline_action_deferred = pipeline_panel::show_pipeline_panel(
                         ui,
                         &mut self.pipeline_state,
-                        has_data,
-                        is_freq,
-                        is_2d,
-                        op_count,
-                        &picking_modes,
+                        &context,
                         &mut self.spectrum_view_state.integration_reference_h,
-                        self.before_snapshot.is_some(),
+                        &mut self.spectrum_view_state.integral_baseline,
+                        &mut self.spectrum_view_state.intensity_mode,
                     );
                 });
             });
+        if self.spectrum_view_state.integral_baseline != integral_baseline
+            && !self.spectrum_view_state.integrations.is_empty()
+        {
+            self.refresh_annotation_intensities();
+            self.repro_log.add_entry(
+                "Integration",
+                &format!(
+                    "Recomputed {} integration regions with baseline: {}",
+                    self.spectrum_view_state.integrations.len(),
+                    self.spectrum_view_state.integral_baseline
+                ),
+                "# manual integration (no NMRPipe equivalent)",
+            );
+        }
 
         // ── Central Panel: Spectrum Display with Domain Tabs ──
         let mut phase_action_deferred = PhaseAction::None;
+        let mut phase_2d_action_deferred = PhaseAction::None;
+        let mut extract_region_deferred = false;
         let tab_active_bg = self.theme_colors.tab_active_bg;
         let tab_active_text = self.theme_colors.tab_active_text;
         let tab_inactive_bg = self.theme_colors.tab_inactive_bg;
         let tab_inactive_text = self.theme_colors.tab_inactive_text;
         egui::CentralPanel::default().show(ctx, |ui| {
             // Domain tabs: show only when we have a FID snapshot (i.e. after FT)
-            if self.fid_snapshot.is_some() && self.spectrum.is_some() {
+            if self.fid_snapshot.is_some() && self.session.active().is_some() {
                 ui.horizontal(|ui| {
                     ui.add_space(4.0);
                     // Time Domain tab
@@ -2032,8 +6235,14 @@ impl eframe::App for NmrApp {
                         self.spectrum_view_state.baseline_picking = false;
                         self.spectrum_view_state.integration_picking = false;
                         self.spectrum_view_state.j_coupling_picking = false;
+                        self.spectrum_view_state.auto_j_picking = false;
+                        self.spectrum_view_state.linewidth_picking = false;
+                        self.spectrum_view_state.fit_picking = false;
+                        self.spectrum_view_state.solvent_picking = false;
                         self.spectrum_view_state.integration_start = None;
                         self.spectrum_view_state.j_coupling_first = None;
+                        self.spectrum_view_state.enhance.picking = false;
+                        self.spectrum_view_state.enhance.start = None;
                     }
 
                     ui.add_space(4.0);
@@ -2054,8 +6263,14 @@ impl eframe::App for NmrApp {
                         self.spectrum_view_state.baseline_picking = false;
                         self.spectrum_view_state.integration_picking = false;
                         self.spectrum_view_state.j_coupling_picking = false;
+                        self.spectrum_view_state.auto_j_picking = false;
+                        self.spectrum_view_state.linewidth_picking = false;
+                        self.spectrum_view_state.fit_picking = false;
+                        self.spectrum_view_state.solvent_picking = false;
                         self.spectrum_view_state.integration_start = None;
                         self.spectrum_view_state.j_coupling_first = None;
+                        self.spectrum_view_state.enhance.picking = false;
+                        self.spectrum_view_state.enhance.start = None;
                     }
 
                     ui.add_space(4.0);
@@ -2070,24 +6285,29 @@ impl eframe::App for NmrApp {
                         .corner_radius(6.0);
                     if ui.add(ex_btn).clicked() {
                         // Initialize PPM range on first switch
-                        if let Some(spectrum) = &self.spectrum {
-                            if spectrum.is_frequency_domain && !spectrum.axes.is_empty() {
-                                let ppm_scale = spectrum.axes[0].ppm_scale();
-                                let ppm_min = ppm_scale.iter().cloned().fold(f64::INFINITY, f64::min);
-                                let ppm_max = ppm_scale.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
-                                if !self.export_tab_state.image_settings.use_custom_range {
-                                    self.export_tab_state.image_settings.ppm_start = ppm_max;
-                                    self.export_tab_state.image_settings.ppm_end = ppm_min;
-                                }
-                            }
-                        }
+                        self.init_export_range();
                         self.domain_tab = DomainTab::Export;
                     }
+
+                    // Pulse program tab, when the raw data stored one
+                    if self.session.active().is_some_and(|s| s.pulse_program.is_some()) {
+                        ui.add_space(4.0);
+                        let pp_active = self.domain_tab == DomainTab::PulseProgram;
+                        let pp_label = egui::RichText::new("📜 Pulse Program")
+                            .size(13.0)
+                            .color(if pp_active { tab_active_text } else { tab_inactive_text });
+                        let pp_btn = egui::Button::new(pp_label)
+                            .fill(if pp_active { tab_active_bg } else { tab_inactive_bg })
+                            .corner_radius(6.0);
+                        if ui.add(pp_btn).clicked() {
+                            self.domain_tab = DomainTab::PulseProgram;
+                        }
+                    }
                 });
                 ui.add_space(2.0);
 
                 // Also show Export tab even without FID snapshot (freq-only data)
-            } else if self.spectrum.is_some() {
+            } else if self.session.active().is_some() {
                 // No FID snapshot (haven't done FT) — show just export button
                 ui.horizontal(|ui| {
                     ui.add_space(4.0);
@@ -2099,19 +6319,24 @@ impl eframe::App for NmrApp {
                         .fill(if ex_active { tab_active_bg } else { tab_inactive_bg })
                         .corner_radius(6.0);
                     if ui.add(ex_btn).clicked() {
-                        if let Some(spectrum) = &self.spectrum {
-                            if spectrum.is_frequency_domain && !spectrum.axes.is_empty() {
-                                let ppm_scale = spectrum.axes[0].ppm_scale();
-                                let ppm_min = ppm_scale.iter().cloned().fold(f64::INFINITY, f64::min);
-                                let ppm_max = ppm_scale.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
-                                if !self.export_tab_state.image_settings.use_custom_range {
-                                    self.export_tab_state.image_settings.ppm_start = ppm_max;
-                                    self.export_tab_state.image_settings.ppm_end = ppm_min;
-                                }
-                            }
-                        }
+                        self.init_export_range();
                         self.domain_tab = DomainTab::Export;
                     }
+
+                    // Pulse program tab, when the raw data stored one
+                    if self.session.active().is_some_and(|s| s.pulse_program.is_some()) {
+                        ui.add_space(4.0);
+                        let pp_active = self.domain_tab == DomainTab::PulseProgram;
+                        let pp_label = egui::RichText::new("📜 Pulse Program")
+                            .size(13.0)
+                            .color(if pp_active { tab_active_text } else { tab_inactive_text });
+                        let pp_btn = egui::Button::new(pp_label)
+                            .fill(if pp_active { tab_active_bg } else { tab_inactive_bg })
+                            .corner_radius(6.0);
+                        if ui.add(pp_btn).clicked() {
+                            self.domain_tab = DomainTab::PulseProgram;
+                        }
+                    }
                 });
                 ui.add_space(2.0);
             }
@@ -2122,78 +6347,58 @@ impl eframe::App for NmrApp {
             {
                 self.fid_snapshot.as_ref()
             } else {
-                self.spectrum.as_ref()
+                self.session.active()
             };
 
-            if self.domain_tab == DomainTab::Export {
+            let pulse_program = self.session.active().and_then(|s| s.pulse_program.as_ref());
+            if let (DomainTab::PulseProgram, Some(program)) = (self.domain_tab, pulse_program) {
+                // ── Pulse Program Tab ──
+                pulse_program_view::show_pulse_program(ui, program);
+            } else if self.domain_tab == DomainTab::Export {
                 // ── Export Tab ──
-                if let Some(spectrum) = self.spectrum.as_ref() {
+                if let Some(spectrum) = self.session.active() {
                     let export_action = export_tab::show_export_tab(
                         ui,
                         &mut self.export_tab_state,
                         spectrum,
                         &self.spectrum_view_state,
+                        &self.repro_log,
                     );
                     match export_action {
                         ExportTabAction::ExportImage => {
                             let s = &self.export_tab_state.image_settings;
-                            let dialog = if s.format == 1 {
-                                rfd::FileDialog::new()
-                                    .set_title("Export Spectrum Image")
-                                    .add_filter("SVG Image", &["svg"])
-                                    .save_file()
-                            } else {
-                                rfd::FileDialog::new()
-                                    .set_title("Export Spectrum Image")
-                                    .add_filter("PNG Image", &["png"])
-                                    .save_file()
+                            let (filter, ext) = match s.format {
+                                1 => ("SVG Image", "svg"),
+                                2 => ("PDF Document", "pdf"),
+                                _ => ("PNG Image", "png"),
                             };
+                            let dialog = rfd::FileDialog::new()
+                                .set_title("Export Spectrum Image")
+                                .add_filter(filter, &[ext])
+                                .save_file();
                             if let Some(path) = dialog {
-                                // Convert to old ExportSettings for existing export methods
-                                let settings = ExportSettings {
-                                    ppm_start: s.ppm_start,
-                                    ppm_end: s.ppm_end,
-                                    use_custom_range: s.use_custom_range,
-                                    width: s.width,
-                                    height: s.height,
-                                    show_peaks: s.show_peaks,
-                                    show_integrations: s.show_integrations,
-                                    show_multiplets: s.show_multiplets,
-                                    custom_title: s.custom_title.clone(),
-                                    use_custom_title: s.use_custom_title,
-                                    line_width: s.line_width,
-                                    show_grid: s.show_grid,
-                                    format: s.format,
-                                    clip_negatives: s.clip_negatives,
-                                    dpi: s.dpi,
-                                    marker_scale: s.marker_scale,
-                                    font_scale: s.font_scale,
-                                };
-                                match self.export_spectrum_image_with_settings(&path, &settings) {
-                                    Ok(_) => {
-                                        self.status_message = format!("✅ Image exported: {}", path.display());
-                                        self.repro_log.add_entry(
-                                            "Export Image",
-                                            &format!("Exported spectrum image to {}", path.display()),
-                                            "",
-                                        );
-                                    }
-                                    Err(e) => {
-                                        self.status_message = format!("❌ Image export failed: {}", e);
-                                    }
+                                let settings = s.to_export_settings();
+                                if let Err(e) = self.start_image_export(&path, &settings) {
+                                    self.status_message = format!("❌ Image export failed: {}", e.user_message());
                                 }
                             }
                         }
                         ExportTabAction::ExportData => {
                             if let Some(path) = toolbar::save_data_dialog() {
-                                match self.export_data_report(&path) {
-                                    Ok(_) => {
+                                let is_json = path
+                                    .extension()
+                                    .map(|e| e.eq_ignore_ascii_case("json"))
+                                    .unwrap_or(false);
+                                let result = if is_json {
+                                    self.export_web_json(&path)
+                                } else {
+                                    self.export_data_report(&path)
+                                        .map(|_| format!("Exported peak/integration data to {}", path.display()))
+                                };
+                                match result {
+                                    Ok(desc) => {
                                         self.status_message = format!("✅ Data exported: {}", path.display());
-                                        self.repro_log.add_entry(
-                                            "Export Data",
-                                            &format!("Exported peak/integration data to {}", path.display()),
-                                            "",
-                                        );
+                                        self.repro_log.add_entry("Export Data", &desc, "");
                                     }
                                     Err(e) => {
                                         self.status_message = format!("❌ Data export failed: {}", e);
@@ -2201,6 +6406,65 @@ impl eframe::App for NmrApp {
                                 }
                             }
                         }
+                        ExportTabAction::ExportNmrPipe => {
+                            let ext = match (spectrum.is_frequency_domain, spectrum.is_2d()) {
+                                (false, _) => "fid",
+                                (true, false) => "ft1",
+                                (true, true) => "ft2",
+                            };
+                            let series = spectrum.is_2d() && self.export_tab_state.data_settings.nmrpipe_series;
+                            let name = if spectrum.sample_name.is_empty() { "spectrum" } else { &spectrum.sample_name };
+                            let dialog = rfd::FileDialog::new()
+                                .set_title("Export NMRPipe")
+                                .set_file_name(format!("{}.{}", name, ext))
+                                .add_filter("NMRPipe", &[ext])
+                                .save_file();
+                            if let Some(path) = dialog {
+                                let result = if series {
+                                    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                                    let template = path.with_file_name(format!("{}%03d.{}", stem, ext));
+                                    nmrpipe_format::write_nmrpipe_series(spectrum, &template).map(|files| {
+                                        format!("NMRPipe plane series written to {} ({} files)", template.display(), files.len())
+                                    })
+                                } else {
+                                    nmrpipe_format::write_nmrpipe_file(spectrum, &path)
+                                        .map(|_| format!("NMRPipe data written to {}", path.display()))
+                                };
+                                match result {
+                                    Ok(desc) => {
+                                        self.status_message = format!("✅ {}", desc);
+                                        self.repro_log.add_entry("Export Data", &desc, "");
+                                    }
+                                    Err(e) => {
+                                        self.status_message = format!("❌ NMRPipe export failed: {}", e);
+                                    }
+                                }
+                            }
+                        }
+                        ExportTabAction::ExportIntrng => {
+                            let regions: Vec<(f64, f64)> =
+                                self.spectrum_view_state.integrations.iter().map(|r| (r.0, r.1)).collect();
+                            let mut dialog = rfd::FileDialog::new()
+                                .set_title("Export Integration Regions (TopSpin intrng)")
+                                .set_file_name("intrng");
+                            // TopSpin reads intrng from the processed-data folder
+                            let pdata = spectrum.source_path.join("pdata").join("1");
+                            if spectrum.vendor_format == VendorFormat::Bruker && pdata.is_dir() {
+                                dialog = dialog.set_directory(&pdata);
+                            }
+                            if let Some(path) = dialog.save_file() {
+                                match std::fs::write(&path, bruker::format_intrng(&regions)) {
+                                    Ok(()) => {
+                                        let desc = format!("{} integration regions written to {} (TopSpin intrng)", regions.len(), path.display());
+                                        self.status_message = format!("✅ {}", desc);
+                                        self.repro_log.add_entry("Export Data", &desc, "");
+                                    }
+                                    Err(e) => {
+                                        self.status_message = format!("❌ intrng export failed: {}", NmrError::writing(&path, e));
+                                    }
+                                }
+                            }
+                        }
                         ExportTabAction::ExportLog => {
                             if let Some(path) = toolbar::save_log_dialog() {
                                 let ext = path
@@ -2210,6 +6474,7 @@ impl eframe::App for NmrApp {
                                 let result = match ext.as_str() {
                                     "json" => self.repro_log.save_json(&path),
                                     "sh" => self.repro_log.save_script(&path),
+                                    "md" => self.repro_log.save_narrative(&path),
                                     _ => self.repro_log.save_text(&path),
                                 };
                                 match result {
@@ -2228,16 +6493,44 @@ impl eframe::App for NmrApp {
             } else if let Some(spectrum) = display_spectrum {
                 // Interactive phase controls (available on any 1D data — time or freq domain)
                 if !spectrum.is_2d() {
-                    let phase_action =
-                        phase_dialog::show_phase_controls(ui, &mut self.phase_dialog_state);
+                    let phase_action = ui
+                        .add_enabled_ui(spectrum.has_imaginary(), |ui| {
+                            phase_dialog::show_phase_controls(ui, &mut self.phase_dialog_state, spectrum.is_frequency_domain)
+                        });
+                    phase_action.response.on_disabled_hover_text(
+                        "No imaginary part was loaded, so the data cannot be phased",
+                    );
+                    let phase_action = phase_action.inner;
                     if phase_action != PhaseAction::None {
                         phase_action_deferred = phase_action;
                     }
                 }
 
+                if spectrum.is_2d() && spectrum.is_frequency_domain {
+                    let phase_action = ui.add_enabled_ui(processing::has_2d_imaginary(spectrum), |ui| {
+                        phase_dialog::show_phase_controls_2d(ui, &mut self.phase_2d_state, spectrum)
+                    });
+                    phase_action.response.on_disabled_hover_text(
+                        "A magnitude spectrum has no imaginary data left to phase with",
+                    );
+                    if phase_action.inner != PhaseAction::None {
+                        phase_2d_action_deferred = phase_action.inner;
+                    }
+                }
+
                 if spectrum.is_2d() {
                     // 2D contour display
-                    let ft_requested = contour_view::show_spectrum_2d(ui, spectrum, &mut self.contour_view_state);
+                    // Margin spectra: session 1D spectra with the nucleus of each axis
+                    let reference = |axis: usize| {
+                        let nucleus = &spectrum.axes.get(axis)?.nucleus;
+                        self.session
+                            .overlays()
+                            .map(|(_, e)| &e.spectrum)
+                            .find(|s| s.axes.first().map(|a| &a.nucleus) == Some(nucleus))
+                    };
+                    let references = [reference(0), reference(1)];
+                    let ft_requested =
+                        contour_view::show_spectrum_2d(ui, spectrum, references, &mut self.contour_view_state);
                     if ft_requested {
                         pipeline_action_deferred = PipelineAction::ApplyFT2D;
                     }
@@ -2248,15 +6541,54 @@ impl eframe::App for NmrApp {
                     } else {
                         None
                     };
-                    spectrum_view::show_spectrum_1d(
-                        ui,
-                        spectrum,
-                        before,
-                        &mut self.spectrum_view_state,
-                        self.pipeline_state.show_before_after,
-                        &mut self.phase_dialog_state,
-                        &self.theme_colors,
-                    );
+                    let side_by_side = self.pipeline_state.comparison_mode == ComparisonMode::SideBySide
+                        && !self.phase_dialog_state.active;
+                    if let (true, Some(before)) = (side_by_side, before) {
+                        spectrum_view::show_comparison_1d(
+                            ui,
+                            spectrum,
+                            before,
+                            self.pipeline_state.show_difference,
+                            &mut self.spectrum_view_state,
+                            &self.theme_colors,
+                        );
+                    } else {
+                        self.spectrum_view_state.enhance.update(
+                            spectrum,
+                            self.pipeline_state.re_lb,
+                            self.pipeline_state.re_gb,
+                        );
+                        self.spectrum_view_state.fit_shape = self.pipeline_state.fit_shape;
+                        self.spectrum_view_state.baseline_preview.update(
+                            spectrum,
+                            self.pipeline_state
+                                .show_baseline_preview
+                                .then(|| pipeline_panel::get_baseline_method(&self.pipeline_state)),
+                        );
+                        let compared = spectrum_view::ComparedSpectra {
+                            before,
+                            show_before_after: self.pipeline_state.show_before_after,
+                            overlays: self
+                                .session
+                                .overlays()
+                                .map(|(_, e)| spectrum_view::SpectrumOverlay {
+                                    name: e.name(),
+                                    spectrum: &e.spectrum,
+                                    color: e.color,
+                                    scale: e.scale,
+                                    offset: e.offset,
+                                })
+                                .collect(),
+                        };
+                        spectrum_view::show_spectrum_1d(
+                            ui,
+                            spectrum,
+                            &compared,
+                            &mut self.spectrum_view_state,
+                            &mut self.phase_dialog_state,
+                            &self.theme_colors,
+                        );
+                    }
 
                     // Drain pending analysis actions from click handlers and log them
                     for action in self.spectrum_view_state.pending_actions.drain(..) {
@@ -2278,7 +6610,10 @@ impl eframe::App for NmrApp {
                             spectrum_view::SpectrumAction::IntegrationAdded(lo, hi, raw) => {
                                 self.repro_log.add_entry(
                                     "Integration",
-                                    &format!("Integrated region {:.4}–{:.4} ppm (raw area = {:.2})", lo, hi, raw),
+                                    &format!(
+                                        "Integrated region {:.4}–{:.4} ppm (raw area = {:.2}, baseline: {})",
+                                        lo, hi, raw, self.spectrum_view_state.integral_baseline
+                                    ),
                                     "# manual integration (no NMRPipe equivalent)",
                                 );
                             }
@@ -2289,6 +6624,100 @@ impl eframe::App for NmrApp {
                                     "# J-coupling measurement (no NMRPipe equivalent)",
                                 );
                             }
+                            spectrum_view::SpectrumAction::MultipletCouplings(Some(index)) => {
+                                let Some(m) = self.spectrum_view_state.multiplets.get(index) else { continue };
+                                if m.couplings.is_empty() {
+                                    self.status_message =
+                                        format!("Multiplet at {:.4} ppm has no resolved line spacings", m.center_ppm);
+                                    continue;
+                                }
+                                let couplings: Vec<String> = m.couplings.iter().map(|c| c.to_string()).collect();
+                                self.repro_log.add_entry(
+                                    "J-Coupling Measurement",
+                                    &format!(
+                                        "Couplings of the {} at {:.4} ppm: {}",
+                                        m.label,
+                                        m.center_ppm,
+                                        couplings.join(", ")
+                                    ),
+                                    "# J-coupling measurement (no NMRPipe equivalent)",
+                                );
+                                self.status_message = format!("{} at {:.4} ppm: J = {}", m.label, m.center_ppm, couplings.join(", "));
+                            }
+                            spectrum_view::SpectrumAction::MultipletCouplings(None) => {
+                                self.status_message = "No multiplet here — detect multiplets first".to_string();
+                            }
+                            spectrum_view::SpectrumAction::LinewidthMeasured(_, Some(lw)) => {
+                                self.repro_log.add_entry(
+                                    "Linewidth Measurement",
+                                    &format!(
+                                        "FWHM of the peak at {:.4} ppm = {:.2} Hz ({:.5} ppm)",
+                                        lw.peak[0],
+                                        lw.fwhm_hz,
+                                        lw.fwhm_ppm()
+                                    ),
+                                    "# linewidth measurement (no NMRPipe equivalent)",
+                                );
+                                self.status_message = format!("FWHM at {:.4} ppm: {:.2} Hz", lw.peak[0], lw.fwhm_hz);
+                            }
+                            spectrum_view::SpectrumAction::LinewidthMeasured(ppm, None) => {
+                                self.status_message =
+                                    format!("No half-height crossing on both sides of the peak near {:.4} ppm", ppm);
+                            }
+                            spectrum_view::SpectrumAction::PeaksFitted(_, _, Some(fit)) => {
+                                let lines: Vec<String> = fit
+                                    .peaks
+                                    .iter()
+                                    .map(|p| format!("{:.4} ppm (FWHM {:.2} Hz, area {:.4e})", p.center_ppm, p.fwhm_hz, p.area))
+                                    .collect();
+                                self.repro_log.add_entry(
+                                    "Peak Fitting",
+                                    &format!(
+                                        "{} {} line(s) fitted to {:.4}–{:.4} ppm, residual {:.2}%: {}",
+                                        fit.peaks.len(),
+                                        fit.shape,
+                                        fit.hi_ppm,
+                                        fit.lo_ppm,
+                                        fit.relative_residual * 100.0,
+                                        lines.join("; ")
+                                    ),
+                                    "# peak fitting (no NMRPipe equivalent)",
+                                );
+                                self.status_message = format!(
+                                    "Fitted {} {} line(s) at {:.4}–{:.4} ppm (residual {:.2}%)",
+                                    fit.peaks.len(),
+                                    fit.shape,
+                                    fit.hi_ppm,
+                                    fit.lo_ppm,
+                                    fit.relative_residual * 100.0
+                                );
+                            }
+                            spectrum_view::SpectrumAction::PeaksFitted(lo, hi, None) => {
+                                self.status_message =
+                                    format!("Could not fit {:.4}–{:.4} ppm: too few points for the lines in it", hi, lo);
+                            }
+                            spectrum_view::SpectrumAction::PhasedOnPeak(_, Some(anchor)) => {
+                                let phase = &self.phase_dialog_state;
+                                self.status_message = format!(
+                                    "Phased on {:.3} ppm: PH0={:.1}°, PH1={:.1}°{}",
+                                    anchor.ppm,
+                                    phase.ph0,
+                                    phase.ph1,
+                                    if phase.anchors.len() == 1 { " — click a second peak to set PH1" } else { "" }
+                                );
+                            }
+                            spectrum_view::SpectrumAction::PhasedOnPeak(ppm, None) => {
+                                self.status_message = format!("No peak to phase on near {:.3} ppm", ppm);
+                            }
+                            spectrum_view::SpectrumAction::SolventRegionAdded((lo, hi)) => {
+                                self.status_message = format!(
+                                    "Suppression region {:.2}–{:.2} ppm added ({} regions) — Apply in Solvent Suppression",
+                                    lo,
+                                    hi,
+                                    self.spectrum_view_state.solvent_regions.len()
+                                );
+                            }
+                            spectrum_view::SpectrumAction::ExtractRegion => extract_region_deferred = true,
                         }
                     }
                 }
@@ -2335,15 +6764,28 @@ impl eframe::App for NmrApp {
         });
 
         // Handle deferred actions
+        let ran_op = pipeline_action_deferred != PipelineAction::None
+            || phase_action_deferred != PhaseAction::None
+            || phase_2d_action_deferred != PhaseAction::None;
         if pipeline_action_deferred != PipelineAction::None {
             // Switch to frequency domain view when applying freq-domain ops
-            if self.spectrum.as_ref().map(|s| s.is_frequency_domain).unwrap_or(false) {
+            if self.session.active().map(|s| s.is_frequency_domain).unwrap_or(false) {
                 self.domain_tab = DomainTab::FrequencyDomain;
             }
-            self.handle_pipeline_action(pipeline_action_deferred);
+            self.timed(|app| app.handle_pipeline_action(pipeline_action_deferred));
+            self.refresh_annotation_intensities();
         }
         if phase_action_deferred != PhaseAction::None {
-            self.handle_phase_action(phase_action_deferred);
+            self.timed(|app| app.handle_phase_action(phase_action_deferred));
+        }
+        if phase_2d_action_deferred != PhaseAction::None {
+            self.timed(|app| app.handle_phase_2d_action(phase_2d_action_deferred));
+        }
+        if ran_op {
+            self.check_non_finite();
+        }
+        if extract_region_deferred {
+            self.extract_visible_region();
         }
 
         // ── Log Window ──
@@ -2369,9 +6811,44 @@ impl eframe::App for NmrApp {
                                 let _ = self.repro_log.save_script(&path);
                             }
                         }
+                        if ui
+                            .button("🎓 Save as Narrative")
+                            .on_hover_text("Each step with its parameters, what it does and why (Markdown)")
+                            .clicked()
+                        {
+                            if let Some(path) = toolbar::save_log_dialog() {
+                                let _ = self.repro_log.save_narrative(&path);
+                            }
+                        }
                     });
                     ui.separator();
 
+                    let perf = self.repro_log.performance_summary();
+                    if !perf.is_empty() {
+                        egui::CollapsingHeader::new("⏱ Performance")
+                            .default_open(false)
+                            .show(ui, |ui| {
+                                egui::Grid::new("perf_summary_grid")
+                                    .striped(true)
+                                    .num_columns(5)
+                                    .show(ui, |ui| {
+                                        for header in ["Operation", "Runs", "Total (ms)", "Slowest (ms)", "Max points"] {
+                                            ui.strong(header);
+                                        }
+                                        ui.end_row();
+                                        for p in &perf {
+                                            ui.label(&p.operation);
+                                            ui.label(p.count.to_string());
+                                            ui.label(format!("{:.1}", p.total_ms));
+                                            ui.label(format!("{:.1}", p.max_ms));
+                                            ui.label(p.max_points.to_string());
+                                            ui.end_row();
+                                        }
+                                    });
+                            });
+                        ui.separator();
+                    }
+
                     egui::ScrollArea::vertical().show(ui, |ui| {
                         ui.style_mut().override_font_id =
                             Some(egui::FontId::monospace(12.0));
@@ -2380,6 +6857,93 @@ impl eframe::App for NmrApp {
                 });
         }
 
+        // ── Batch Conversion Window ──
+        let mut close_batch = false;
+        if let Some(handle) = self.batch_conversion.as_mut() {
+            let status = handle.status();
+            if status.finished && !handle.reported {
+                handle.reported = true;
+                let ok = status.total - status.failures.len();
+                self.repro_log.add_entry(
+                    "Batch Conversion",
+                    &format!(
+                        "Converted {}/{} datasets from {}\n# Output: {}{}{}",
+                        ok,
+                        status.total,
+                        handle.source_dir.display(),
+                        handle.out_dir.display(),
+                        status
+                            .processed
+                            .iter()
+                            .map(|p| format!("\n# Processed: {}", p))
+                            .chain(status.processing_failures.iter().map(|f| format!("\n# Processing failed: {}", f)))
+                            .collect::<String>(),
+                        status
+                            .failures
+                            .iter()
+                            .map(|f| format!("\n# Failed: {}", f))
+                            .collect::<String>(),
+                    ),
+                    "# built-in parallel batch conversion (delta2pipe/bruk2pipe crates)",
+                );
+                self.status_message = format!(
+                    "Batch conversion finished: {}/{} converted → {}",
+                    ok,
+                    status.total,
+                    handle.out_dir.display()
+                );
+            }
+
+            egui::Window::new("🗂 Batch Conversion")
+                .default_size([500.0, 300.0])
+                .resizable(true)
+                .show(ctx, |ui| {
+                    ui.label(format!("Source: {}", handle.source_dir.display()));
+                    ui.label(format!("Output: {}", handle.out_dir.display()));
+                    ui.add_space(4.0);
+                    let frac = if status.total > 0 {
+                        status.completed as f32 / status.total as f32
+                    } else {
+                        1.0
+                    };
+                    ui.add(
+                        egui::ProgressBar::new(frac)
+                            .text(format!("{}/{}", status.completed, status.total)),
+                    );
+                    if !status.finished && !status.current.is_empty() {
+                        ui.label(egui::RichText::new(&status.current).small());
+                    }
+                    if !status.processed.is_empty() {
+                        ui.label(format!("{} processed with their experiment's profile", status.processed.len()));
+                    }
+                    if !status.processing_failures.is_empty() {
+                        ui.label(format!("{} could not be processed:", status.processing_failures.len()));
+                        for f in &status.processing_failures {
+                            ui.label(egui::RichText::new(f).small());
+                        }
+                    }
+                    if !status.failures.is_empty() {
+                        ui.separator();
+                        ui.label(format!("{} failed:", status.failures.len()));
+                        egui::ScrollArea::vertical().max_height(150.0).show(ui, |ui| {
+                            ui.style_mut().override_font_id = Some(egui::FontId::monospace(11.0));
+                            for f in &status.failures {
+                                ui.label(f);
+                            }
+                        });
+                    }
+                    if status.finished {
+                        ui.separator();
+                        if ui.button("Close").clicked() {
+                            close_batch = true;
+                        }
+                    }
+                });
+        }
+        if close_batch {
+            self.batch_conversion = None;
+        }
+
         // ── About Dialog ──
         if self.show_about {
             egui::Window::new("About")
@@ -2406,7 +6970,7 @@ impl eframe::App for NmrApp {
         // Handle keyboard shortcuts
         ctx.input(|i| {
             if i.modifiers.ctrl || i.modifiers.command {
-                if i.key_pressed(egui::Key::Z) {
+                if i.key_pressed(egui::Key::Z) && !self.viewer {
                     if i.modifiers.shift {
                         self.redo();
                     } else {
@@ -2415,138 +6979,22 @@ impl eframe::App for NmrApp {
                 }
                 if i.key_pressed(egui::Key::O) {
                     if let Some(path) = toolbar::open_file_dialog() {
+                        self.session_add = false;
                         self.load_path(path);
                     }
                 }
             }
         });
-    }
-}
-
-/// Choose a nice tick spacing for x-axis labels given the total ppm range.
-fn smart_tick_step(range: f64) -> f64 {
-    let nice_steps = [0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0];
-    let target_ticks = 10.0;
-    let raw_step = range / target_ticks;
-    for &step in &nice_steps {
-        if step >= raw_step {
-            return step;
-        }
-    }
-    50.0
-}
-
-/// Draw a line between two points on an RgbImage using Bresenham's algorithm.
-fn draw_line(
-    img: &mut image::RgbImage,
-    x0: i32, y0: i32,
-    x1: i32, y1: i32,
-    color: image::Rgb<u8>,
-    w: u32, h: u32,
-) {
-    let dx = (x1 - x0).abs();
-    let dy = -(y1 - y0).abs();
-    let sx: i32 = if x0 < x1 { 1 } else { -1 };
-    let sy: i32 = if y0 < y1 { 1 } else { -1 };
-    let mut err = dx + dy;
-    let mut cx = x0;
-    let mut cy = y0;
-    loop {
-        if cx >= 0 && cx < w as i32 && cy >= 0 && cy < h as i32 {
-            img.put_pixel(cx as u32, cy as u32, color);
-        }
-        if cx == x1 && cy == y1 {
-            break;
-        }
-        let e2 = 2 * err;
-        if e2 >= dy {
-            err += dy;
-            cx += sx;
-        }
-        if e2 <= dx {
-            err += dx;
-            cy += sy;
-        }
-    }
-}
 
-/// Very simple built-in 3×5 bitmap font for labeling exported images.
-fn draw_simple_text(img: &mut image::RgbImage, text: &str, x: u32, y: u32, color: image::Rgb<u8>, text_scale: u32) {
-    // Minimal 3×5 font for digits, letters, and common symbols
-    let glyph = |c: char| -> [u8; 5] {
-        match c {
-            '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
-            '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
-            '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
-            '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
-            '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
-            '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
-            '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
-            '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
-            '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
-            '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
-            '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
-            ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
-            '-' | '—' | '–' => [0b000, 0b000, 0b111, 0b000, 0b000],
-            '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
-            '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
-            '(' => [0b010, 0b100, 0b100, 0b100, 0b010],
-            ')' => [0b010, 0b001, 0b001, 0b001, 0b010],
-            '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
-            ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
-            '|' => [0b010, 0b010, 0b010, 0b010, 0b010],
-            '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
-            ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
-            // Letters (case-insensitive)
-            'A' | 'a' => [0b010, 0b101, 0b111, 0b101, 0b101],
-            'B' | 'b' => [0b110, 0b101, 0b110, 0b101, 0b110],
-            'C' | 'c' => [0b011, 0b100, 0b100, 0b100, 0b011],
-            'D' | 'd' => [0b110, 0b101, 0b101, 0b101, 0b110],
-            'E' | 'e' => [0b111, 0b100, 0b110, 0b100, 0b111],
-            'F' | 'f' => [0b111, 0b100, 0b110, 0b100, 0b100],
-            'G' | 'g' => [0b011, 0b100, 0b101, 0b101, 0b011],
-            'H' | 'h' => [0b101, 0b101, 0b111, 0b101, 0b101],
-            'I' | 'i' => [0b111, 0b010, 0b010, 0b010, 0b111],
-            'J' | 'j' => [0b001, 0b001, 0b001, 0b101, 0b010],
-            'K' | 'k' => [0b101, 0b110, 0b100, 0b110, 0b101],
-            'L' | 'l' => [0b100, 0b100, 0b100, 0b100, 0b111],
-            'M' | 'm' => [0b101, 0b111, 0b111, 0b101, 0b101],
-            'N' | 'n' => [0b101, 0b111, 0b111, 0b101, 0b101],
-            'O' | 'o' => [0b010, 0b101, 0b101, 0b101, 0b010],
-            'P' | 'p' => [0b110, 0b101, 0b110, 0b100, 0b100],
-            'Q' | 'q' => [0b010, 0b101, 0b101, 0b110, 0b011],
-            'R' | 'r' => [0b110, 0b101, 0b110, 0b101, 0b101],
-            'S' | 's' => [0b011, 0b100, 0b010, 0b001, 0b110],
-            'T' | 't' => [0b111, 0b010, 0b010, 0b010, 0b010],
-            'U' | 'u' => [0b101, 0b101, 0b101, 0b101, 0b111],
-            'V' | 'v' => [0b101, 0b101, 0b101, 0b101, 0b010],
-            'W' | 'w' => [0b101, 0b101, 0b111, 0b111, 0b101],
-            'X' | 'x' => [0b101, 0b101, 0b010, 0b101, 0b101],
-            'Y' | 'y' => [0b101, 0b101, 0b010, 0b010, 0b010],
-            'Z' | 'z' => [0b111, 0b001, 0b010, 0b100, 0b111],
-            _ => [0b000, 0b000, 0b010, 0b000, 0b000], // fallback: small dot
-        }
-    };
-
-    let scale = text_scale.max(1);
-    let mut cx = x;
-    for ch in text.chars() {
-        let g = glyph(ch);
-        for (row, &bits) in g.iter().enumerate() {
-            for col in 0..3u32 {
-                if (bits >> (2 - col)) & 1 == 1 {
-                    for sy in 0..scale {
-                        for sx in 0..scale {
-                            let px = cx + col * scale + sx;
-                            let py = y + row as u32 * scale + sy;
-                            if px < img.width() && py < img.height() {
-                                img.put_pixel(px, py, color);
-                            }
-                        }
-                    }
-                }
+        // ── Mirror the log next to the data after this frame's operations ──
+        if self.preferences.mirror_log && !self.viewer {
+            if let Some(e) = self.log_mirror.update(&self.repro_log) {
+                log::warn!("Could not mirror the processing log: {}", e);
+                self.status_message = format!("Could not write {}: {}", mirror::MIRROR_FILE_NAME, e);
+            }
+            if self.log_mirror.busy() {
+                ctx.request_repaint_after(std::time::Duration::from_millis(100));
             }
         }
-        cx += 4 * scale; // 3 pixels + 1 gap, scaled
     }
 }
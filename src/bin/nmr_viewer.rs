@@ -0,0 +1,33 @@
+//! Launcher of the read-only quick viewer.
+//!
+//! Starts `nmr_gui --viewer` from the same folder with the arguments given,
+//! so a spectrum can be opened for a look (file association, desktop
+//! shortcut) without the processing pipeline.
+
+use std::path::PathBuf;
+use std::process::{Command, ExitCode};
+
+/// Names of the main binary: as built, and as packaged for release
+const GUI_NAMES: [&str; 2] = ["nmr_gui", "nmr-gui"];
+
+fn main() -> ExitCode {
+    let dir = std::env::current_exe().ok().and_then(|exe| exe.parent().map(PathBuf::from));
+    let gui = dir.and_then(|dir| {
+        GUI_NAMES
+            .iter()
+            .map(|name| dir.join(format!("{}{}", name, std::env::consts::EXE_SUFFIX)))
+            .find(|p| p.is_file())
+    });
+    let Some(gui) = gui else {
+        eprintln!("nmr_viewer: nmr_gui was not found next to this launcher");
+        return ExitCode::from(2);
+    };
+    match Command::new(&gui).arg("--viewer").args(std::env::args_os().skip(1)).status() {
+        Ok(status) if status.success() => ExitCode::SUCCESS,
+        Ok(status) => ExitCode::from(status.code().and_then(|c| u8::try_from(c).ok()).unwrap_or(1)),
+        Err(e) => {
+            eprintln!("nmr_viewer: cannot start {}: {}", gui.display(), e);
+            ExitCode::from(2)
+        }
+    }
+}
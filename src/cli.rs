@@ -0,0 +1,178 @@
+//! Command line modes that run without opening the GUI.
+//!
+//! `nmr_gui --validate [--tolerance X] <dataset>...` converts each dataset
+//! with both the built-in readers and NMRPipe and prints the differences.
+//! Exit status: 0 all passed, 1 a dataset differs, 2 a dataset could not be
+//! validated.
+//!
+//! `nmr_gui --header-diff <a.fid> <b.fid>` prints the FDATA parameters that
+//! differ between two NMRPipe files.  Exit status: 0 identical headers,
+//! 1 differences, 2 a file could not be read.
+//!
+//! `nmr_gui --batch <script.toml>` loads, processes and exports every
+//! dataset of a batch script (see [`crate::pipeline::script`]).  Exit
+//! status: 0 all datasets done, 1 a dataset failed, 2 the script could not
+//! be run.
+//!
+//! Otherwise the GUI starts, opening the first path given.  `--listen
+//! [address]` also starts the command server (see [`crate::pipeline::ipc`]).
+//! `--viewer` starts the read-only quick viewer instead of the full
+//! processing window; the `nmr_viewer` launcher does the same.
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use crate::gui::conversion_dialog::ConversionSettings;
+use crate::pipeline::ipc;
+use crate::pipeline::script::{self, BatchScript};
+use crate::pipeline::validation;
+
+const USAGE: &str = "usage: --validate [--tolerance <relative RMS>] <dataset>...";
+const HEADER_DIFF_USAGE: &str = "usage: --header-diff <a.fid> <b.fid>";
+const BATCH_USAGE: &str = "usage: --batch <script.toml>";
+const LISTEN_USAGE: &str = "usage: [--viewer] [--listen [host:port | port]] [dataset]";
+
+/// Run a command line mode if `args` (without the program name) ask for
+/// one.  Returns the exit code, or `None` to start the GUI.
+pub fn run(args: &[String]) -> Option<i32> {
+    match args.first().map(String::as_str) {
+        Some("--validate") => Some(validate(&args[1..])),
+        Some("--header-diff") => Some(header_diff(&args[1..])),
+        Some("--batch") => Some(batch(&args[1..])),
+        _ => None,
+    }
+}
+
+/// What the GUI starts with
+#[derive(Debug, Default, PartialEq)]
+pub struct GuiArgs {
+    /// File, folder, archive, project or NMRPipe series pattern to open
+    pub open: Option<PathBuf>,
+    /// Address of the command server, with `--listen`
+    pub listen: Option<SocketAddr>,
+    /// Quick viewer: default processing, display and image export only
+    pub viewer: bool,
+}
+
+/// Parse the arguments of a GUI start
+pub fn gui_args(args: &[String]) -> Result<GuiArgs, String> {
+    let mut gui = GuiArgs::default();
+    let mut iter = args.iter().peekable();
+    while let Some(arg) = iter.next() {
+        if arg == "--listen" {
+            // The address is optional: take the next argument only if it
+            // reads as one
+            let value = iter
+                .next_if(|next| next.parse::<u16>().is_ok() || next.parse::<SocketAddr>().is_ok())
+                .map(String::as_str);
+            gui.listen = Some(ipc::parse_listen_address(value).map_err(|e| format!("--listen: {}\n{}", e, LISTEN_USAGE))?);
+        } else if arg == "--viewer" {
+            gui.viewer = true;
+        } else if !arg.starts_with("--") && gui.open.is_none() {
+            gui.open = Some(PathBuf::from(arg));
+        }
+    }
+    Ok(gui)
+}
+
+fn validate(args: &[String]) -> i32 {
+    let mut tolerance = validation::DEFAULT_TOLERANCE;
+    let mut paths = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--tolerance" {
+            match iter.next().and_then(|v| v.parse::<f64>().ok()) {
+                Some(t) if t > 0.0 => tolerance = t,
+                _ => {
+                    eprintln!("--tolerance needs a positive number\n{}", USAGE);
+                    return 2;
+                }
+            }
+        } else {
+            paths.push(Path::new(arg));
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        return 2;
+    }
+
+    let settings = ConversionSettings::default();
+    let mut code = 0;
+    for path in paths {
+        match validation::validate_conversion(path, &settings) {
+            Ok(report) => {
+                print!("{}", report.to_text(tolerance));
+                if !report.passed(tolerance) {
+                    code = code.max(1);
+                }
+            }
+            Err(e) => {
+                eprintln!("Validation of {} failed: {}", path.display(), e);
+                code = 2;
+            }
+        }
+    }
+    code
+}
+
+fn header_diff(args: &[String]) -> i32 {
+    let [a, b] = args else {
+        eprintln!("{}", HEADER_DIFF_USAGE);
+        return 2;
+    };
+    match validation::header_diff(Path::new(a), Path::new(b)) {
+        Ok((text, differences)) => {
+            print!("{}", text);
+            i32::from(differences > 0)
+        }
+        Err(e) => {
+            eprintln!("Header diff failed: {}", e);
+            2
+        }
+    }
+}
+
+fn batch(args: &[String]) -> i32 {
+    let [path] = args else {
+        eprintln!("{}", BATCH_USAGE);
+        return 2;
+    };
+    let script = match BatchScript::load(Path::new(path)) {
+        Ok(script) => script,
+        Err(e) => {
+            eprintln!("Batch script error: {}", e);
+            return 2;
+        }
+    };
+    let jobs = match script.datasets() {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            eprintln!("Batch script error: {}", e);
+            return 2;
+        }
+    };
+    if let Err(e) = std::fs::create_dir_all(&script.output_dir) {
+        eprintln!("Cannot create {}: {}", script.output_dir.display(), e);
+        return 2;
+    }
+
+    let settings = ConversionSettings::default();
+    let mut failed = 0;
+    for (i, job) in jobs.iter().enumerate() {
+        println!("[{}/{}] {}", i + 1, jobs.len(), job.input.display());
+        match script::run_job(&script, job, &settings) {
+            Ok(outputs) => {
+                for output in outputs {
+                    println!("  → {}", output.display());
+                }
+            }
+            Err(e) => {
+                eprintln!("  failed: {}", e);
+                failed += 1;
+            }
+        }
+    }
+    println!("{} of {} datasets processed into {}", jobs.len() - failed, jobs.len(), script.output_dir.display());
+    i32::from(failed > 0)
+}
@@ -9,13 +9,15 @@
 /// arguments for bruk2pipe (SW, OBS, TD, DECIM, DSPFVS, GRPDLY, etc.)
 /// so the conversion doesn't use hardcoded garbage values.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 use std::fs;
 
+use super::nuclide;
 use super::spectrum::*;
+use crate::pipeline::command::{run_cancellable, CancelToken};
+use crate::pipeline::tools::{self, ToolLocation};
 
 // ────────────────────────────────────────────────────────────────
 //  Locate bruk2pipe
@@ -23,58 +25,10 @@ use super::spectrum::*;
 
 /// Locate the bruk2pipe executable.
 ///
-/// Checks PATH first, then falls back to common NMRPipe installation directories.
-pub fn find_bruk2pipe() -> Option<PathBuf> {
-    // Check PATH via `which`
-    if let Ok(output) = Command::new("which").arg("bruk2pipe").output() {
-        if output.status.success() {
-            let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            if !path.is_empty() {
-                return Some(PathBuf::from(path));
-            }
-        }
-    }
-
-    // Check common NMRPipe installation paths relative to $HOME
-    if let Ok(home) = std::env::var("HOME") {
-        let home_paths = [
-            format!("{}/Documents/NMRpipe/nmrbin.linux239_64/bruk2pipe", home),
-            format!("{}/NMRPipe/nmrbin.linux239_64/bruk2pipe", home),
-            format!("{}/nmrpipe/bin/bruk2pipe", home),
-        ];
-        for p in &home_paths {
-            if Path::new(p).exists() {
-                return Some(PathBuf::from(p));
-            }
-        }
-    }
-
-    // Check NMR_BASE environment variable (NMRPipe convention)
-    if let Ok(nmr_base) = std::env::var("NMR_BASE") {
-        let p = format!("{}/bin/bruk2pipe", nmr_base);
-        if Path::new(&p).exists() {
-            return Some(PathBuf::from(p));
-        }
-    }
-
-    // System-wide paths
-    let system_paths = [
-        "/usr/local/nmrpipe/bin/bruk2pipe",
-        "/opt/nmrpipe/bin/bruk2pipe",
-    ];
-    for p in &system_paths {
-        if Path::new(p).exists() {
-            return Some(PathBuf::from(*p));
-        }
-    }
-
-    // Hard-coded fallback for this system
-    let fallback = PathBuf::from("/home/raaf/Documents/NMRpipe/nmrbin.linux239_64/bruk2pipe");
-    if fallback.exists() {
-        return Some(fallback);
-    }
-
-    None
+/// See [`tools::find_tool`] for the search order (PATH, NMRPipe install
+/// folders on Linux/macOS, WSL on Windows).
+pub fn find_bruk2pipe() -> Option<ToolLocation> {
+    tools::find_tool("bruk2pipe")
 }
 
 // ────────────────────────────────────────────────────────────────
@@ -98,6 +52,8 @@ pub struct BrukerParams {
     pub dtypa: i32,
     /// Byte order: 0 = little-endian, 1 = big-endian
     pub bytorda: i32,
+    /// Whether acqus sets BYTORDA (AM-series archives do not)
+    pub has_bytorda: bool,
     /// Number of scans
     pub ns: i32,
     /// Nucleus name (e.g. "1H", "13C")
@@ -114,6 +70,8 @@ pub struct BrukerParams {
     pub dspfvs: i32,
     /// Acquisition mode (AQ_mod)
     pub aq_mod: i32,
+    /// Digitization mode (DIGMOD): 0 = analog, ≥ 1 = digital; `None` if absent
+    pub digmod: Option<i32>,
     /// Indirect dimension TD
     pub td_f1: usize,
     /// Indirect dimension SW (Hz)
@@ -122,10 +80,45 @@ pub struct BrukerParams {
     pub sfo1_f1: f64,
     /// Indirect dimension nucleus
     pub nuc1_f1: String,
+    /// Indirect dimension carrier offset in Hz
+    pub o1_f1: f64,
+    /// Indirect dimension base frequency in MHz
+    pub bf1_f1: f64,
     /// FnMODE (indirect dim acquisition mode for 2D)
     pub fnmode: i32,
 }
 
+impl BrukerParams {
+    /// Acquisition parameters for [`SpectrumData::metadata`]
+    pub fn metadata(&self) -> BTreeMap<String, String> {
+        let mut meta = BTreeMap::new();
+        if !self.pulprog.is_empty() {
+            meta.insert(META_PULSE_PROGRAM.to_string(), self.pulprog.clone());
+        }
+        if self.ns > 0 {
+            meta.insert(META_SCANS.to_string(), self.ns.to_string());
+        }
+        meta
+    }
+
+    /// Indirect dimension carrier in ppm
+    pub fn carrier_ppm_f1(&self) -> f64 {
+        if self.bf1_f1 > 0.0 { self.o1_f1 / self.bf1_f1 } else { 0.0 }
+    }
+
+    /// Indirect dimension ppm of the first (highest) point, i.e. the carrier
+    /// plus half the spectral width
+    pub fn ref_ppm_f1(&self) -> f64 {
+        if self.bf1_f1 > 0.0 {
+            self.carrier_ppm_f1() + self.sw_h_f1 / (2.0 * self.bf1_f1)
+        } else if self.sfo1_f1 > 0.0 {
+            self.sw_h_f1 / (2.0 * self.sfo1_f1)
+        } else {
+            0.0
+        }
+    }
+}
+
 /// Parse a Bruker `acqus` or `acqu2s` parameter file.
 ///
 /// These files use a JCAMP-DX–like format with `##$PARAM= value` lines.
@@ -185,6 +178,23 @@ fn get_i32(params: &HashMap<String, String>, key: &str) -> i32 {
         .unwrap_or(0)
 }
 
+/// Strip transform recorded in a procs file: the first point (STSR) and
+/// size (STSI) of the region stored out of the SI-point spectrum, or `None`
+/// when the full spectrum is stored
+fn strip_region(procs: &HashMap<String, String>, si: usize) -> Option<(usize, usize)> {
+    let (stsr, stsi) = (get_i32(procs, "STSR").max(0) as usize, get_i32(procs, "STSI").max(0) as usize);
+    (stsi > 0 && stsi < si && stsr + stsi <= si).then_some((stsr, stsi))
+}
+
+/// Mark `axis` as the strip `region` of its `si` points: SW_p and OFFSET
+/// describe the full spectrum, of which only the strip is stored
+fn with_strip(axis: AxisParams, region: Option<(usize, usize)>, si: usize) -> AxisParams {
+    match region {
+        Some((stsr, _)) => AxisParams { first_point: stsr, full_points: si, ..axis },
+        None => axis,
+    }
+}
+
 fn get_str(params: &HashMap<String, String>, key: &str) -> String {
     params
         .get(key)
@@ -202,6 +212,7 @@ pub fn extract_params(acq: &HashMap<String, String>, acq2: Option<&HashMap<Strin
     p.td = get_i32(acq, "TD") as usize;
     p.dtypa = get_i32(acq, "DTYPA");
     p.bytorda = get_i32(acq, "BYTORDA");
+    p.has_bytorda = acq.contains_key("BYTORDA");
     p.ns = get_i32(acq, "NS");
     p.nuc1 = get_str(acq, "NUC1");
     p.pulprog = get_str(acq, "PULPROG");
@@ -210,6 +221,7 @@ pub fn extract_params(acq: &HashMap<String, String>, acq2: Option<&HashMap<Strin
     p.decim = get_i32(acq, "DECIM");
     p.dspfvs = get_i32(acq, "DSPFVS");
     p.aq_mod = get_i32(acq, "AQ_mod");
+    p.digmod = acq.get("DIGMOD").and_then(|v| v.parse().ok());
 
     if let Some(a2) = acq2 {
         p.td_f1 = get_i32(a2, "TD") as usize;
@@ -222,6 +234,19 @@ pub fn extract_params(acq: &HashMap<String, String>, acq2: Option<&HashMap<Strin
             let s = get_str(a2, "NUC1");
             if s.is_empty() { get_str(acq, "NUC2") } else { s }
         };
+        // F1 base frequency: BF1 of acqu2s, else the acqus channel pulsed at
+        // the F1 frequency (BF2 for the heteronucleus of an HSQC, BF1 for a COSY)
+        p.bf1_f1 = get_f64(a2, "BF1");
+        if p.bf1_f1 <= 0.0 {
+            let sfo = p.sfo1_f1;
+            p.bf1_f1 = (1..=8)
+                .map(|n| get_f64(acq, &format!("BF{}", n)))
+                .filter(|&bf| bf > 0.0 && (bf - sfo).abs() < 0.01 * bf)
+                .min_by(|a, b| (a - sfo).abs().total_cmp(&(b - sfo).abs()))
+                .unwrap_or(0.0);
+        }
+        // SFO = BF + O, so the carrier offset follows from the two frequencies
+        p.o1_f1 = if p.bf1_f1 > 0.0 && p.sfo1_f1 > 0.0 { (p.sfo1_f1 - p.bf1_f1) * 1e6 } else { 0.0 };
         p.fnmode = get_i32(acq, "FnMODE");
     }
 
@@ -261,6 +286,81 @@ fn fnmode_string(fnmode: i32) -> &'static str {
     }
 }
 
+/// Group delay in points: GRPDLY if set, otherwise from DECIM/DSPFVS.
+pub fn resolve_grpdly(params: &BrukerParams) -> f64 {
+    if params.grpdly > 0.0 {
+        params.grpdly
+    } else {
+        compute_grpdly(params.decim, params.dspfvs)
+    }
+}
+
+/// Whether the digital-filter group delay is removed on conversion, and why
+#[derive(Debug, Clone, PartialEq)]
+pub struct DmxDecision {
+    pub apply: bool,
+    pub reason: String,
+}
+
+/// Decide whether a raw Bruker FID needs group-delay (DMX) correction.
+///
+/// `forced` is the user's override from the conversion dialog; otherwise
+/// data flagged as analog (`DIGMOD = 0`) or whose FID already peaks at the
+/// first points (see [`detect_precorrected`]) is left alone, so it is not
+/// shifted a second time.
+pub fn dmx_decision(dir: &Path, params: &BrukerParams, forced: Option<bool>) -> DmxDecision {
+    let grpdly = resolve_grpdly(params);
+    let (apply, reason) = match forced {
+        Some(true) => (true, format!("group delay {:.3} points removed (forced)", grpdly)),
+        Some(false) => (false, "marked as already DMX-corrected by the user".to_string()),
+        None if grpdly <= 0.0 && params.decim <= 1 => (false, "no digital filter (DECIM ≤ 1, no GRPDLY)".to_string()),
+        None => match detect_precorrected(dir, params) {
+            Some(why) => (false, format!("already corrected: {}", why)),
+            None => (true, format!("group delay {:.3} points removed", grpdly)),
+        },
+    };
+    DmxDecision { apply, reason }
+}
+
+/// Evidence that a digitally filtered FID has already had its group delay
+/// removed (TopSpin `convdta` exports, FIDs saved after processing).
+///
+/// A raw DMX FID starts with a near-silent ramp and peaks around the group
+/// delay; a corrected one peaks within the first few points.
+pub fn detect_precorrected(dir: &Path, params: &BrukerParams) -> Option<String> {
+    if params.digmod == Some(0) {
+        return Some("DIGMOD = 0 (analog / converted with convdta)".to_string());
+    }
+    let grpdly = resolve_grpdly(params);
+    if grpdly < 8.0 {
+        return None;
+    }
+    let peak = fid_peak_index(dir, params, grpdly)?;
+    (peak as f64 <= grpdly / 4.0).then(|| {
+        format!("FID maximum at point {}, expected near the group delay ({:.1} points)", peak, grpdly)
+    })
+}
+
+/// Index of the largest complex point among the first ~2×`grpdly` points
+/// of the FID (first row of a `ser` file).
+fn fid_peak_index(dir: &Path, params: &BrukerParams, grpdly: f64) -> Option<usize> {
+    use std::io::Read;
+
+    let file = ["fid", "ser"].iter().map(|f| dir.join(f)).find(|p| p.exists())?;
+    let layout = fid_layout(params, params.td_f1.max(1), fs::metadata(&file).ok()?.len());
+    let complex_points = ((2.0 * grpdly).ceil() as usize + 16).min((params.td / 2).max(1));
+    let mut raw = vec![0u8; complex_points * 2 * layout.format.size()];
+    let read = fs::File::open(file).ok()?.take(raw.len() as u64).read(&mut raw).ok()?;
+    let values = read_fid_words(&raw[..read], &layout);
+
+    let (index, max) = values
+        .chunks_exact(2)
+        .map(|c| c[0].hypot(c[1]))
+        .enumerate()
+        .fold((0, 0.0), |best, (i, m)| if m > best.1 { (i, m) } else { best });
+    (max > 0.0).then_some(index)
+}
+
 /// Compute the Bruker digital filter group delay from DECIM and DSPFVS.
 ///
 /// Lookup table from NMRPipe documentation and Bruker manuals.
@@ -300,13 +400,8 @@ fn compute_grpdly(decim: i32, dspfvs: i32) -> f64 {
 /// Parse nucleus string to Nucleus enum
 fn parse_nucleus(nuc: &str) -> Nucleus {
     match nuc.trim().to_uppercase().as_str() {
-        "1H" | "H1" => Nucleus::H1,
-        "13C" | "C13" => Nucleus::C13,
-        "15N" | "N15" => Nucleus::N15,
-        "19F" | "F19" => Nucleus::F19,
-        "31P" | "P31" => Nucleus::P31,
         "" | "OFF" => Nucleus::Other("Unknown".into()),
-        other => Nucleus::Other(other.to_string()),
+        other => nuclide::parse_label(other).unwrap_or_else(|| Nucleus::Other(other.to_string())),
     }
 }
 
@@ -321,6 +416,8 @@ pub fn detect_experiment_from_pulprog(pulprog: &str) -> ExperimentType {
         ExperimentType::Cosy
     } else if upper.contains("DEPT") || upper.contains("135") {
         ExperimentType::Dept135
+    } else if nuclide::names_deuterium(pulprog) || upper.contains("LOCK") {
+        ExperimentType::Deuterium
     } else if upper.contains("ZGPG") || upper.contains("C13") || upper.contains("CARBON") {
         ExperimentType::Carbon
     } else if upper.contains("ZG") {
@@ -358,6 +455,64 @@ pub fn read_bruker_params(dir: &Path) -> io::Result<(BrukerParams, bool)> {
     Ok((params, is_2d))
 }
 
+/// One experiment (expno) of a Bruker sample folder
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrukerExperiment {
+    pub expno: u32,
+    pub path: PathBuf,
+    pub pulprog: String,
+    /// Direct-dimension nucleus (NUC1)
+    pub nucleus: String,
+    /// Indirect-dimension nucleus for 2D experiments
+    pub indirect_nucleus: Option<String>,
+    pub ns: i32,
+    /// Processing numbers found under `pdata/`
+    pub procnos: Vec<u32>,
+    /// First line of `pdata/<first procno>/title`
+    pub title: String,
+}
+
+/// List the experiments of a Bruker sample folder: every numbered
+/// subdirectory with an `acqus`/`acqu` file, sorted by expno.
+/// Returns an empty list for anything else (including an expno folder).
+pub fn list_experiments(sample_dir: &Path) -> Vec<BrukerExperiment> {
+    let numbered = |dir: &Path| -> Vec<(u32, PathBuf)> {
+        let mut found: Vec<(u32, PathBuf)> = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|e| {
+                let n = e.file_name().to_string_lossy().parse::<u32>().ok()?;
+                e.path().is_dir().then(|| (n, e.path()))
+            })
+            .collect();
+        found.sort_by_key(|(n, _)| *n);
+        found
+    };
+    numbered(sample_dir)
+        .into_iter()
+        .filter_map(|(expno, path)| {
+            let (params, is_2d) = read_bruker_params(&path).ok()?;
+            let procnos: Vec<u32> = numbered(&path.join("pdata")).into_iter().map(|(n, _)| n).collect();
+            let title = procnos
+                .first()
+                .and_then(|p| fs::read_to_string(path.join("pdata").join(p.to_string()).join("title")).ok())
+                .and_then(|t| t.lines().map(str::trim).find(|l| !l.is_empty()).map(str::to_string))
+                .unwrap_or_default();
+            Some(BrukerExperiment {
+                expno,
+                pulprog: params.pulprog,
+                nucleus: params.nuc1,
+                indirect_nucleus: is_2d.then_some(params.nuc1_f1),
+                ns: params.ns,
+                procnos,
+                title,
+                path,
+            })
+        })
+        .collect()
+}
+
 /// Convert a Bruker dataset to NMRPipe format using bruk2pipe.
 ///
 /// Reads `acqus` (and `acqu2s` for 2D) to get the correct conversion
@@ -365,10 +520,14 @@ pub fn read_bruker_params(dir: &Path) -> io::Result<(BrukerParams, bool)> {
 ///
 /// For 1D data: creates a single `<stem>.fid` file.
 /// For 2D data: creates a series `<stem>%03d.fid` files.
+/// With `apply_dmx` false the data is converted as AMX (no group-delay
+/// correction).  If `cancel` is triggered the bruk2pipe process is killed.
 pub fn convert_bruker_data(
     dir: &Path,
     output_dir: &Path,
     stem: &str,
+    apply_dmx: bool,
+    cancel: Option<&CancelToken>,
 ) -> io::Result<Bruk2PipeResult> {
     let exe = find_bruk2pipe().ok_or_else(|| {
         io::Error::new(
@@ -383,12 +542,8 @@ pub fn convert_bruker_data(
     // Read acquisition parameters from acqus
     let (params, is_2d) = read_bruker_params(dir)?;
 
-    // Resolve group delay: use GRPDLY if set, otherwise compute from DECIM/DSPFVS
-    let grpdly = if params.grpdly > 0.0 {
-        params.grpdly
-    } else {
-        compute_grpdly(params.decim, params.dspfvs)
-    };
+    // Resolve group delay; zero for data that is already corrected
+    let grpdly = if apply_dmx { resolve_grpdly(&params) } else { 0.0 };
 
     // Determine input file
     let in_file = if is_2d && dir.join("ser").exists() {
@@ -396,6 +551,8 @@ pub fn convert_bruker_data(
     } else {
         dir.join("fid")
     };
+    let rows = if is_2d { params.td_f1 } else { 1 };
+    let layout = fid_layout(&params, rows, fs::metadata(&in_file)?.len());
 
     // Output pattern
     let out_pattern = if is_2d {
@@ -421,26 +578,37 @@ pub fn convert_bruker_data(
         "-bad".into(), "0.0".into(),
         "-ext".into(),
         "-apts".into(),
-        "-AMX".into(),
+        // Exactly one console type: -AMX leaves the group delay in place
+        match layout.format {
+            FidWordFormat::Int24 => "-AM",
+            _ if apply_dmx => "-DMX",
+            _ => "-AMX",
+        }.into(),
         "-decim".into(), format!("{}", params.decim),
         "-dspfvs".into(), format!("{}", params.dspfvs),
         "-grpdly".into(), format!("{:.4}", grpdly),
-        "-DMX".into(),
+        // Swap when the data's byte order differs from the host's
+        if layout.big_endian == cfg!(target_endian = "little") { "-swap" } else { "-noswap" }.into(),
+    ];
+    if layout.format == FidWordFormat::Float64 {
+        args.extend_from_slice(&["-ws".into(), "8".into(), "-noi2f".into()]);
+    }
+    args.extend_from_slice(&[
         "-ndim".into(), format!("{}", ndim),
         // F2 (direct / x) dimension
-        "-xN".into(), format!("{}", params.td),
+        "-xN".into(), format!("{}", layout.row_words),
         "-xT".into(), format!("{}", params.td / 2),
         "-xMODE".into(), "DQD".into(),
         "-xSW".into(), format!("{:.3}", params.sw_h),
         "-xOBS".into(), format!("{:.4}", params.sfo1),
         "-xCAR".into(), format!("{:.4}", car_ppm),
         "-xLAB".into(), params.nuc1.clone(),
-    ];
+    ]);
 
     // 2D indirect dimension parameters
     if is_2d {
         let y_mode = fnmode_string(params.fnmode);
-        let car_f1 = if params.sfo1_f1 > 0.0 { 0.0 } else { 0.0 };
+        let car_f1 = params.carrier_ppm_f1();
 
         args.extend_from_slice(&[
             "-yN".into(), format!("{}", params.td_f1),
@@ -460,16 +628,14 @@ pub fn convert_bruker_data(
 
     // Build command string for logging / reproducibility
     let cmd_string = {
-        let mut parts = vec![exe.to_string_lossy().to_string()];
+        let mut parts = vec![exe.display()];
         parts.extend(args.clone());
         parts.join(" \\\n  ")
     };
 
     log::info!("Running: {}", cmd_string);
 
-    let output = Command::new(&exe)
-        .args(&args)
-        .output()?;
+    let output = run_cancellable(&mut exe.command(&args), cancel)?;
 
     let log_output = format!(
         "{}{}",
@@ -538,21 +704,21 @@ pub fn convert_bruker_data(
 /// Read Bruker processed data from `pdata/1/` (1r = real, 1i = imaginary).
 ///
 /// This is the built-in reader that works without NMRPipe.
-/// It reads the processed spectrum directly from the `1r` file.
+/// It reads the processed spectrum directly from the `1r` file, or for 2D
+/// data from `2rr` plus the `2ri`/`2ir`/`2ii` quadrants when present, so
+/// phase-sensitive spectra keep their imaginary parts.
 pub fn read_bruker_processed(dir: &Path) -> io::Result<SpectrumData> {
     let (params, is_2d) = read_bruker_params(dir)?;
 
     // Find pdata/1/ directory
-    let pdata_dir = if dir.join("pdata/1/1r").exists() {
-        dir.join("pdata/1")
-    } else if dir.join("pdata").join("1").join("1r").exists() {
-        dir.join("pdata").join("1")
-    } else {
+    let pdata_dir = dir.join("pdata").join("1");
+    let has_processed = pdata_dir.join("1r").exists() || (is_2d && pdata_dir.join("2rr").exists());
+    if !has_processed {
         return Err(io::Error::new(
             io::ErrorKind::NotFound,
             format!("No processed data (pdata/1/1r) found in {}", dir.display()),
         ));
-    };
+    }
 
     // Parse procs to get processing parameters
     let procs_path = pdata_dir.join("procs");
@@ -569,13 +735,8 @@ pub fn read_bruker_processed(dir: &Path) -> io::Result<SpectrumData> {
     let offset = get_f64(&proc_params, "OFFSET");
     let bytordp = get_i32(&proc_params, "BYTORDP");
     let dtypp = get_i32(&proc_params, "DTYPP");
-
-    // Read the 1r binary file
-    let real_path = pdata_dir.join("1r");
-    let raw = fs::read(&real_path)?;
-
-    let npoints = if si > 0 { si } else { raw.len() / 4 };
     let scale = (2.0f64).powi(nc_proc);
+    let strip = strip_region(&proc_params, si);
 
     // Build metadata
     let sw_hz = if sw_p > 0.0 { sw_p } else { params.sw_h };
@@ -610,55 +771,92 @@ pub fn read_bruker_processed(dir: &Path) -> io::Result<SpectrumData> {
         let sw_p2 = get_f64(&proc2_params, "SW_p");
         let sf2 = get_f64(&proc2_params, "SF");
         let offset2 = get_f64(&proc2_params, "OFFSET");
+        let strip2 = strip_region(&proc2_params, si2);
 
         let scale2 = (2.0f64).powi(nc_proc2);
-        let rr_raw = fs::read(&rr_path)?;
-
-        // Read all processed 2D data
-        let total_pts = if dtypp == 0 { rr_raw.len() / 4 } else { rr_raw.len() / 8 };
-        let all_vals = if dtypp == 0 {
-            read_int32_data(&rr_raw, total_pts, bytordp, scale2)
-        } else {
-            read_float64_data(&rr_raw, total_pts, bytordp, scale2)
+        let ncols = match strip {
+            Some((_, stsi)) => stsi,
+            None if si > 0 => si,
+            None => 1024,
+        };
+        // Submatrix (tile) sizes; files written without tiling have none
+        let tile = (
+            get_i32(&proc2_params, "XDIM").max(0) as usize,
+            get_i32(&proc_params, "XDIM").max(0) as usize,
+        );
+        let read_quadrant = |name: &str| -> io::Result<Vec<Vec<f64>>> {
+            let raw = fs::read(pdata_dir.join(name))?;
+            let total_pts = if dtypp == 0 { raw.len() / 4 } else { raw.len() / 8 };
+            let values = if dtypp == 0 {
+                read_int32_data(&raw, total_pts, bytordp, scale2)
+            } else {
+                read_float64_data(&raw, total_pts, bytordp, scale2)
+            };
+            // Split into rows: nrows = si2, ncols = si (direct dim), or the strip sizes
+            let nrows = match strip2 {
+                Some((_, stsi)) => stsi,
+                None if si2 > 0 => si2,
+                None => values.len() / ncols,
+            };
+            Ok(untile_2d(&values, nrows, ncols, tile))
         };
 
-        // Split into rows: nrows = si2, ncols = si (direct dim)
-        let ncols = if si > 0 { si } else { 1024 };
-        let nrows = if si2 > 0 { si2 } else if ncols > 0 { all_vals.len() / ncols } else { 0 };
-
-        let mut data_2d = Vec::with_capacity(nrows);
-        for row_idx in 0..nrows {
-            let start = row_idx * ncols;
-            let end = (start + ncols).min(all_vals.len());
-            if start >= all_vals.len() { break; }
-            data_2d.push(all_vals[start..end].to_vec());
-        }
+        let data_2d = read_quadrant("2rr")?;
+        let nrows = data_2d.len();
+        // Imaginary quadrants, if processing kept them (and they match 2rr)
+        let quadrant = |name: &str| -> io::Result<Option<Vec<Vec<f64>>>> {
+            if !pdata_dir.join(name).exists() {
+                return Ok(None);
+            }
+            let q = read_quadrant(name)?;
+            Ok((q.len() == nrows && q.iter().zip(&data_2d).all(|(a, b)| a.len() == b.len())).then_some(q))
+        };
+        let data_2d_imag = quadrant("2ri")?.unwrap_or_default();
+        let f1_quadrants = match (quadrant("2ir")?, quadrant("2ii")?) {
+            (Some(ir), Some(ii)) => Some(F1Quadrants { ir, ii }),
+            _ => None,
+        };
 
         // F2 (direct, x) axis
-        let axis_x = AxisParams {
-            nucleus: nucleus.clone(),
-            num_points: ncols,
-            spectral_width_hz: sw_hz,
-            observe_freq_mhz: obs_mhz,
-            reference_ppm: ref_ppm,
-            label: params.nuc1.clone(),
-        };
+        let axis_x = with_strip(
+            AxisParams {
+                nucleus: nucleus.clone(),
+                num_points: ncols,
+                spectral_width_hz: sw_hz,
+                observe_freq_mhz: obs_mhz,
+                reference_ppm: ref_ppm,
+                label: params.nuc1.clone(),
+                ..Default::default()
+            },
+            strip,
+            si,
+        );
 
         // F1 (indirect, y) axis
         let nucleus_f1 = parse_nucleus(&params.nuc1_f1);
         let sw_hz_f1 = if sw_p2 > 0.0 { sw_p2 } else { params.sw_h_f1 };
-        let obs_mhz_f1 = if sf2 > 0.0 { sf2 } else { params.sfo1_f1 };
-        let ref_ppm_f1 = if offset2 != 0.0 { offset2 } else {
-            if params.sfo1_f1 > 0.0 { params.sw_h_f1 / (2.0 * params.sfo1_f1) } else { 0.0 }
-        };
-        let axis_y = AxisParams {
-            nucleus: nucleus_f1,
-            num_points: nrows,
-            spectral_width_hz: sw_hz_f1,
-            observe_freq_mhz: obs_mhz_f1,
-            reference_ppm: ref_ppm_f1,
-            label: params.nuc1_f1.clone(),
+        let obs_mhz_f1 = if sf2 > 0.0 {
+            sf2
+        } else if params.sfo1_f1 > 0.0 {
+            params.sfo1_f1
+        } else {
+            // No F1 frequency stored: derive it from F2 via the nuclide table
+            nuclide::convert_frequency(&nucleus, obs_mhz, &nucleus_f1).unwrap_or(0.0)
         };
+        let ref_ppm_f1 = if offset2 != 0.0 { offset2 } else { params.ref_ppm_f1() };
+        let axis_y = with_strip(
+            AxisParams {
+                nucleus: nucleus_f1,
+                num_points: nrows,
+                spectral_width_hz: sw_hz_f1,
+                observe_freq_mhz: obs_mhz_f1,
+                reference_ppm: ref_ppm_f1,
+                label: params.nuc1_f1.clone(),
+                ..Default::default()
+            },
+            strip2,
+            si2,
+        );
 
         let real = data_2d.first().cloned().unwrap_or_default();
 
@@ -668,18 +866,33 @@ pub fn read_bruker_processed(dir: &Path) -> io::Result<SpectrumData> {
             experiment_type,
             dimensionality: Dimensionality::TwoD,
             sample_name,
+            solvent: params.solvent.clone(),
+            array: None,
+            metadata: params.metadata(),
             axes: vec![axis_x, axis_y],
             real,
             imag: Vec::new(),
             data_2d,
-            data_2d_imag: Vec::new(),
+            data_2d_imag,
+            f1_quadrants,
+            nus_schedule: None,
+            group_delay: None,
+            f1_acquisition: F1Acquisition::default(),
+            pulse_program: None,
             is_frequency_domain: true,
+            provenance: Provenance::VendorProcessed,
             nmrpipe_path: None,
             conversion_method_used: "Built-in (Bruker 2D processed data reader)".to_string(),
         });
     }
 
     // 1D processed data
+    let raw = fs::read(pdata_dir.join("1r"))?;
+    let npoints = match strip {
+        Some((_, stsi)) => stsi,
+        None if si > 0 => si,
+        None => raw.len() / 4,
+    };
     let real: Vec<f64> = if dtypp == 0 {
         // 32-bit integers
         read_int32_data(&raw, npoints, bytordp, scale)
@@ -701,14 +914,19 @@ pub fn read_bruker_processed(dir: &Path) -> io::Result<SpectrumData> {
         Vec::new()
     };
 
-    let axis = AxisParams {
-        nucleus: nucleus.clone(),
-        num_points: real.len(),
-        spectral_width_hz: sw_hz,
-        observe_freq_mhz: obs_mhz,
-        reference_ppm: ref_ppm,
-        label: params.nuc1.clone(),
-    };
+    let axis = with_strip(
+        AxisParams {
+            nucleus: nucleus.clone(),
+            num_points: real.len(),
+            spectral_width_hz: sw_hz,
+            observe_freq_mhz: obs_mhz,
+            reference_ppm: ref_ppm,
+            label: params.nuc1.clone(),
+            ..Default::default()
+        },
+        strip,
+        si,
+    );
 
     Ok(SpectrumData {
         source_path: dir.to_path_buf(),
@@ -716,20 +934,121 @@ pub fn read_bruker_processed(dir: &Path) -> io::Result<SpectrumData> {
         experiment_type,
         dimensionality: Dimensionality::OneD,
         sample_name,
+        solvent: params.solvent.clone(),
+        array: None,
+        metadata: params.metadata(),
         axes: vec![axis],
         real,
         imag,
         data_2d: Vec::new(),
         data_2d_imag: Vec::new(),
+        f1_quadrants: None,
+        nus_schedule: None,
+        group_delay: None,
+        f1_acquisition: F1Acquisition::default(),
+        pulse_program: None,
         is_frequency_domain: true, // processed data is always in frequency domain
+        provenance: Provenance::VendorProcessed,
         nmrpipe_path: None,
         conversion_method_used: "Built-in (Bruker processed data reader)".to_string(),
     })
 }
 
+/// Integration regions as a TopSpin `intrng` file: a header line, then one
+/// `high low` ppm pair per region, left to right as TopSpin lists them
+pub fn format_intrng(regions: &[(f64, f64)]) -> String {
+    let mut sorted: Vec<(f64, f64)> = regions.iter().map(|&(a, b)| (a.max(b), a.min(b))).collect();
+    sorted.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut out = String::from("A 1.0 #regions in PPM\n");
+    for (hi, lo) in sorted {
+        out.push_str(&format!("{:>16.4}{:>16.4}\n", hi, lo));
+    }
+    out
+}
+
+/// How the words of a raw `fid` / `ser` file are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FidWordFormat {
+    /// 32-bit integers (DTYPA = 0)
+    #[default]
+    Int32,
+    /// 24-bit integers of AM-series (Aspect) consoles
+    Int24,
+    /// 64-bit floats (DTYPA = 2, TopSpin 4)
+    Float64,
+}
+
+impl FidWordFormat {
+    /// Bytes per word
+    pub fn size(self) -> usize {
+        match self {
+            FidWordFormat::Int32 => 4,
+            FidWordFormat::Int24 => 3,
+            FidWordFormat::Float64 => 8,
+        }
+    }
+}
+
+/// Layout of a raw `fid` / `ser` file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FidLayout {
+    pub format: FidWordFormat,
+    pub big_endian: bool,
+    /// Words stored per FID: TD, plus any padding of the row to a whole
+    /// 1024-byte block
+    pub row_words: usize,
+}
+
+/// Bytes a `ser` row is padded to by acquisition software that pads
+const FID_BLOCK_BYTES: usize = 1024;
+
+/// Work out how the `rows` FIDs of a file of `file_len` bytes are stored.
+///
+/// The word type comes from DTYPA, except that integer data whose size
+/// matches TD 3-byte words rather than 4-byte ones is AM data; those
+/// archives carry no BYTORDA and are big-endian.  Older TopSpin versions
+/// pad every row of a `ser` file to a 1024-byte block and TopSpin 4 double
+/// data may not be padded, so row padding is read from the file size.
+pub fn fid_layout(params: &BrukerParams, rows: usize, file_len: u64) -> FidLayout {
+    let rows = rows.max(1);
+    let td = params.td.max(1);
+    let padded = |word: usize| (td * word).div_ceil(FID_BLOCK_BYTES) * FID_BLOCK_BYTES;
+    let fits = |word: usize| [td * word, padded(word)].iter().any(|&row| (rows * row) as u64 == file_len);
+    let format = if params.dtypa == 2 {
+        FidWordFormat::Float64
+    } else if !fits(4) && fits(3) {
+        FidWordFormat::Int24
+    } else {
+        FidWordFormat::Int32
+    };
+    let word = format.size();
+    let row_words = if rows > 1 && format != FidWordFormat::Int24 && file_len >= (rows * padded(word)) as u64 {
+        padded(word) / word
+    } else {
+        td
+    };
+    let big_endian = match format {
+        FidWordFormat::Int24 => params.bytorda == 1 || !params.has_bytorda,
+        _ => params.bytorda == 1,
+    };
+    FidLayout { format, big_endian, row_words }
+}
+
+/// Values of the words in a raw `fid` / `ser` file
+pub fn read_fid_words(raw: &[u8], layout: &FidLayout) -> Vec<f64> {
+    let bytorda = if layout.big_endian { 1 } else { 0 };
+    let count = raw.len() / layout.format.size();
+    match layout.format {
+        FidWordFormat::Int32 => read_int32_data(raw, count, bytorda, 1.0),
+        FidWordFormat::Int24 => read_int24_data(raw, count, layout.big_endian),
+        FidWordFormat::Float64 => read_float64_data(raw, count, bytorda, 1.0),
+    }
+}
+
 /// Read raw Bruker FID data natively (built-in reader).
 ///
-/// Reads the `fid` or `ser` binary file using parameters from `acqus`.
+/// Reads the `fid` or `ser` binary file using parameters from `acqus`;
+/// the word type, byte order and row padding come from [`fid_layout`].
 /// For 2D data (ser file with acqu2s), reads all rows as a 2D matrix.
 pub fn read_bruker_fid(dir: &Path) -> io::Result<SpectrumData> {
     let (params, is_2d) = read_bruker_params(dir)?;
@@ -748,15 +1067,13 @@ pub fn read_bruker_fid(dir: &Path) -> io::Result<SpectrumData> {
     };
 
     let raw = fs::read(&fid_path)?;
-    let npoints = if params.td > 0 { params.td } else {
-        if params.dtypa == 0 { raw.len() / 4 } else { raw.len() / 8 }
-    };
-
-    let all_vals = if params.dtypa == 0 {
-        read_int32_data(&raw, npoints, params.bytorda, 1.0)
-    } else {
-        read_float64_data(&raw, npoints, params.bytorda, 1.0)
-    };
+    let rows = if is_2d { params.td_f1 } else { 1 };
+    let layout = fid_layout(&params, rows, raw.len() as u64);
+    let mut all_vals = read_fid_words(&raw, &layout);
+    let npoints = if params.td > 0 { params.td.min(all_vals.len()) } else { all_vals.len() };
+    if !is_2d {
+        all_vals.truncate(npoints);
+    }
 
     let nucleus = parse_nucleus(&params.nuc1);
     let experiment_type = detect_experiment_from_pulprog(&params.pulprog);
@@ -773,9 +1090,10 @@ pub fn read_bruker_fid(dir: &Path) -> io::Result<SpectrumData> {
 
     if is_2d && params.td_f1 > 1 {
         // 2D data: ser file contains multiple FIDs (rows)
-        // Each row has TD (direct dim) points, complex interleaved
+        // Each row has TD (direct dim) points, complex interleaved, and
+        // possibly padding up to `row_words`
         let row_len = params.td; // points per row (complex interleaved)
-        let nrows = if row_len > 0 { all_vals.len() / row_len } else { 0 };
+        let nrows = if row_len > 0 { all_vals.len() / layout.row_words.max(row_len) } else { 0 };
 
         if nrows == 0 || row_len == 0 {
             return Err(io::Error::new(
@@ -789,7 +1107,7 @@ pub fn read_bruker_fid(dir: &Path) -> io::Result<SpectrumData> {
         let mut data_2d = Vec::with_capacity(nrows);
         let mut data_2d_imag = Vec::with_capacity(nrows);
         for row_idx in 0..nrows {
-            let start = row_idx * row_len;
+            let start = row_idx * layout.row_words.max(row_len);
             let end = (start + row_len).min(all_vals.len());
             let row_data = &all_vals[start..end];
             // Deinterleave: even indices = real, odd indices = imaginary
@@ -807,16 +1125,12 @@ pub fn read_bruker_fid(dir: &Path) -> io::Result<SpectrumData> {
             observe_freq_mhz: params.sfo1,
             reference_ppm: ref_ppm,
             label: params.nuc1.clone(),
+            ..Default::default()
         };
 
         // F1 (indirect, y) axis
         let nucleus_f1 = parse_nucleus(&params.nuc1_f1);
-        let ref_ppm_f1 = if params.sfo1_f1 > 0.0 {
-            // Carrier position in ppm for indirect dim
-            params.sw_h_f1 / (2.0 * params.sfo1_f1)
-        } else {
-            0.0
-        };
+        let ref_ppm_f1 = params.ref_ppm_f1();
         let axis_y = AxisParams {
             nucleus: nucleus_f1,
             num_points: nrows,
@@ -824,6 +1138,7 @@ pub fn read_bruker_fid(dir: &Path) -> io::Result<SpectrumData> {
             observe_freq_mhz: params.sfo1_f1,
             reference_ppm: ref_ppm_f1,
             label: params.nuc1_f1.clone(),
+            ..Default::default()
         };
 
         // Use first row as the 1D projection
@@ -835,12 +1150,21 @@ pub fn read_bruker_fid(dir: &Path) -> io::Result<SpectrumData> {
             experiment_type,
             dimensionality: Dimensionality::TwoD,
             sample_name,
+            solvent: params.solvent.clone(),
+            array: None,
+            metadata: params.metadata(),
             axes: vec![axis_x, axis_y],
             real,
             imag: Vec::new(),
             data_2d,
             data_2d_imag,
+            f1_quadrants: None,
+            nus_schedule: None,
+            group_delay: None,
+            f1_acquisition: F1Acquisition::from_bruker_fnmode(params.fnmode),
+            pulse_program: None,
             is_frequency_domain: false,
+            provenance: Provenance::Raw,
             nmrpipe_path: None,
             conversion_method_used: "Built-in (Bruker raw 2D FID reader)".to_string(),
         })
@@ -862,6 +1186,7 @@ pub fn read_bruker_fid(dir: &Path) -> io::Result<SpectrumData> {
             observe_freq_mhz: params.sfo1,
             reference_ppm: ref_ppm,
             label: params.nuc1.clone(),
+            ..Default::default()
         };
 
         Ok(SpectrumData {
@@ -870,18 +1195,56 @@ pub fn read_bruker_fid(dir: &Path) -> io::Result<SpectrumData> {
             experiment_type,
             dimensionality: Dimensionality::OneD,
             sample_name,
+            solvent: params.solvent.clone(),
+            array: None,
+            metadata: params.metadata(),
             axes: vec![axis],
             real,
             imag,
             data_2d: Vec::new(),
             data_2d_imag: Vec::new(),
+            f1_quadrants: None,
+            nus_schedule: None,
+            group_delay: None,
+            f1_acquisition: F1Acquisition::default(),
+            pulse_program: None,
             is_frequency_domain: false,
+            provenance: Provenance::Raw,
             nmrpipe_path: None,
             conversion_method_used: "Built-in (Bruker raw FID reader)".to_string(),
         })
     }
 }
 
+/// Split a processed 2D file into `rows` rows of `cols` points.  Bruker
+/// stores it in submatrices of `tile` = (XDIM F1, XDIM F2) points, each
+/// row-major, laid out row-major themselves; without usable tile sizes the
+/// file is plain row-major.
+fn untile_2d(values: &[f64], rows: usize, cols: usize, tile: (usize, usize)) -> Vec<Vec<f64>> {
+    let (tile_rows, tile_cols) = tile;
+    let tiled = tile_rows > 0
+        && tile_cols > 0
+        && (tile_rows, tile_cols) != (rows, cols)
+        && rows.is_multiple_of(tile_rows)
+        && cols.is_multiple_of(tile_cols)
+        && values.len() >= rows * cols;
+    if !tiled {
+        return values.chunks(cols.max(1)).take(rows).map(<[f64]>::to_vec).collect();
+    }
+    let tiles_per_row = cols / tile_cols;
+    let tile_len = tile_rows * tile_cols;
+    (0..rows)
+        .map(|r| {
+            (0..cols)
+                .map(|c| {
+                    let tile_index = (r / tile_rows) * tiles_per_row + c / tile_cols;
+                    values[tile_index * tile_len + (r % tile_rows) * tile_cols + c % tile_cols]
+                })
+                .collect()
+        })
+        .collect()
+}
+
 /// Read binary data as 32-bit integers, scaled
 fn read_int32_data(raw: &[u8], npoints: usize, bytorda: i32, scale: f64) -> Vec<f64> {
     let mut data = Vec::with_capacity(npoints);
@@ -899,6 +1262,18 @@ fn read_int32_data(raw: &[u8], npoints: usize, bytorda: i32, scale: f64) -> Vec<
     data
 }
 
+/// Read binary data as 24-bit signed integers (AM consoles)
+fn read_int24_data(raw: &[u8], npoints: usize, big_endian: bool) -> Vec<f64> {
+    raw.chunks_exact(3)
+        .take(npoints)
+        .map(|w| {
+            let bytes = if big_endian { [w[0], w[1], w[2], 0] } else { [w[2], w[1], w[0], 0] };
+            // Into the top three bytes, then an arithmetic shift for the sign
+            (i32::from_be_bytes(bytes) >> 8) as f64
+        })
+        .collect()
+}
+
 /// Read binary data as 64-bit floats, scaled
 fn read_float64_data(raw: &[u8], npoints: usize, bytorda: i32, scale: f64) -> Vec<f64> {
     let mut data = Vec::with_capacity(npoints);
@@ -977,6 +1352,35 @@ mod tests {
         assert_eq!(params.dspfvs, 12);
     }
 
+    #[test]
+    fn test_f1_carrier_from_the_indirect_channel() {
+        let acqus = "##$TD= 16\n##$SW_h= 4000\n##$SFO1= 400.1324\n##$BF1= 400.13\n##$O1= 1880\n\
+                     ##$SFO2= 100.6232\n##$BF2= 100.6128\n##$O2= 10400\n##$NUC1= <1H>\n##$NUC2= <13C>\n\
+                     ##$FnMODE= 6\n##$DTYPA= 0\n##$BYTORDA= 0\n##END=\n";
+        // acqu2s without BF1: the carrier comes from the acqus channel at SFO1 of F1
+        let acqu2s = "##$TD= 64\n##$SW_h= 16600\n##$SFO1= 100.6232\n##$NUC1= <13C>\n##END=\n";
+        let params = extract_params(&parse_acqus(acqus), Some(&parse_acqus(acqu2s)));
+        assert!((params.bf1_f1 - 100.6128).abs() < 1e-9);
+        assert!((params.o1_f1 - 10400.0).abs() < 1e-3);
+        assert!((params.carrier_ppm_f1() - 10400.0 / 100.6128).abs() < 1e-6);
+        assert!((params.ref_ppm_f1() - (103.366 + 82.497)).abs() < 1e-2);
+
+        // The raw reader and the built-in bruk2pipe place F1 at the same ppm
+        let dir = std::env::temp_dir().join(format!("nmr_f1_carrier_test_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("acqus"), acqus).unwrap();
+        fs::write(dir.join("acqu2s"), acqu2s).unwrap();
+        fs::write(dir.join("ser"), vec![0u8; 16 * 64 * 4]).unwrap();
+        let raw = read_bruker_fid(&dir).unwrap();
+        let native = crate::data::native_converter::convert_bruker_native(&dir, Some(false)).unwrap();
+        let center = |axis: &AxisParams| axis.reference_ppm - axis.spectral_width_hz / (2.0 * axis.observe_freq_mhz);
+        assert!((center(&raw.axes[1]) - params.carrier_ppm_f1()).abs() < 0.01);
+        // bruk2pipe puts the carrier at point N/2 + 1 of the N frequency points
+        let point = 16600.0 / (32.0 * 100.6232);
+        assert!((center(&native.axes[1]) - params.carrier_ppm_f1()).abs() < point + 0.01);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_parse_nucleus() {
         assert_eq!(parse_nucleus("1H"), Nucleus::H1);
@@ -985,6 +1389,23 @@ mod tests {
         assert_eq!(parse_nucleus("19F"), Nucleus::F19);
         assert_eq!(parse_nucleus("31P"), Nucleus::P31);
         assert_eq!(parse_nucleus("OFF"), Nucleus::Other("Unknown".into()));
+        assert_eq!(parse_nucleus("2H"), Nucleus::H2);
+    }
+
+    #[test]
+    fn test_deuterium_frequency_and_experiment() {
+        assert_eq!(detect_experiment_from_pulprog("lock2h"), ExperimentType::Deuterium);
+        // zg2h reads as a plain zg; the 2H observe nucleus makes it deuterium
+        let mut spectrum = SpectrumData {
+            experiment_type: detect_experiment_from_pulprog("zg2h"),
+            axes: vec![AxisParams { nucleus: Nucleus::H2, ..Default::default() }],
+            ..Default::default()
+        };
+        spectrum.refine_experiment_type();
+        assert_eq!(spectrum.experiment_type, ExperimentType::Deuterium);
+        // 2H on a 400 MHz magnet
+        let f = nuclide::convert_frequency(&Nucleus::H1, 400.13, &Nucleus::H2).unwrap();
+        assert!((f - 61.422).abs() < 0.001);
     }
 
     #[test]
@@ -997,6 +1418,192 @@ mod tests {
         assert_eq!(detect_experiment_from_pulprog("dept135"), ExperimentType::Dept135);
     }
 
+    #[test]
+    fn test_detect_precorrected_fid() {
+        let dir = std::env::temp_dir().join(format!("nmr_dmx_test_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let params = BrukerParams { td: 1024, grpdly: 67.98, decim: 16, dspfvs: 20, ..Default::default() };
+        let write_fid = |peak: usize| {
+            let bytes: Vec<u8> = (0..512usize)
+                .flat_map(|i| {
+                    let re = (10_000.0 * (-((i as f64 - peak as f64).abs()) / 20.0).exp()) as i32;
+                    [re, 0]
+                })
+                .flat_map(|v| v.to_le_bytes())
+                .collect();
+            fs::write(dir.join("fid"), bytes).unwrap();
+        };
+
+        // Raw DMX data peaks at the group delay: correct it
+        write_fid(68);
+        assert!(detect_precorrected(&dir, &params).is_none());
+        assert!(dmx_decision(&dir, &params, None).apply);
+
+        // Already corrected: the FID starts at its maximum
+        write_fid(0);
+        assert!(detect_precorrected(&dir, &params).is_some());
+        assert!(!dmx_decision(&dir, &params, None).apply);
+        assert!(dmx_decision(&dir, &params, Some(true)).apply);
+
+        let analog = BrukerParams { digmod: Some(0), ..params };
+        write_fid(68);
+        assert!(!dmx_decision(&dir, &analog, None).apply);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_fid_layout_from_acqus_and_file_size() {
+        let params = BrukerParams { td: 1000, bytorda: 0, has_bytorda: true, ..Default::default() };
+        let layout = fid_layout(&params, 1, 4000);
+        assert_eq!((layout.format, layout.big_endian, layout.row_words), (FidWordFormat::Int32, false, 1000));
+
+        // ser rows padded to whole 1024-byte blocks
+        let layout = fid_layout(&params, 8, 8 * 4096);
+        assert_eq!((layout.format, layout.row_words), (FidWordFormat::Int32, 1024));
+
+        // TopSpin 4 doubles, unpadded
+        let doubles = BrukerParams { td: 1000, dtypa: 2, has_bytorda: true, ..Default::default() };
+        let layout = fid_layout(&doubles, 8, 8 * 8000);
+        assert_eq!((layout.format, layout.row_words), (FidWordFormat::Float64, 1000));
+
+        // AM: 3-byte words and no BYTORDA, so big-endian
+        let am = BrukerParams { has_bytorda: false, ..params };
+        let layout = fid_layout(&am, 1, 3000);
+        assert_eq!((layout.format, layout.big_endian, layout.row_words), (FidWordFormat::Int24, true, 1000));
+    }
+
+    #[test]
+    fn test_read_am_and_double_precision_fids() {
+        let dir = std::env::temp_dir().join(format!("nmr_fid_words_test_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let values: Vec<i32> = (0..64).map(|i| (i - 32) * 100_003).collect();
+
+        // AM-series acqus has no BYTORDA or DTYPA
+        fs::write(dir.join("acqus"), "##$TD= 64\n##$SW_h= 5000\n##$SFO1= 400.13\n##END=\n").unwrap();
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_be_bytes()[1..].to_vec()).collect();
+        fs::write(dir.join("fid"), bytes).unwrap();
+        let fid = read_bruker_fid(&dir).unwrap();
+        let expected: Vec<f64> = values.iter().step_by(2).map(|&v| v as f64).collect();
+        assert_eq!(fid.real, expected);
+        let native = crate::data::native_converter::convert_bruker_native(&dir, Some(false)).unwrap();
+        assert_eq!(native.real, expected);
+
+        // TopSpin 4 double-precision FID
+        fs::write(dir.join("acqus"), "##$TD= 64\n##$DTYPA= 2\n##$BYTORDA= 0\n##$SW_h= 5000\n##END=\n").unwrap();
+        let bytes: Vec<u8> = values.iter().flat_map(|&v| (v as f64 * 0.25).to_le_bytes()).collect();
+        fs::write(dir.join("fid"), bytes).unwrap();
+        let fid = read_bruker_fid(&dir).unwrap();
+        let expected: Vec<f64> = values.iter().skip(1).step_by(2).map(|&v| v as f64 * 0.25).collect();
+        assert_eq!(fid.imag, expected);
+
+        // The built-in converter reads the words as doubles too
+        let native = crate::data::native_converter::convert_bruker_native(&dir, Some(false)).unwrap();
+        assert_eq!(native.imag, expected);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_list_experiments_in_sample_folder() {
+        let sample = std::env::temp_dir().join(format!("nmr_sample_test_{}", uuid::Uuid::new_v4()));
+        for (expno, pulprog, nuc) in [("10", "hsqcetgpsi", "13C"), ("2", "zg30", "1H")] {
+            fs::create_dir_all(sample.join(expno).join("pdata").join("1")).unwrap();
+            fs::write(sample.join(expno).join("acqus"), format!("##$NS= 8\n##$NUC1= <1H>\n##$PULPROG= <{}>\n", pulprog)).unwrap();
+            if nuc != "1H" {
+                fs::write(sample.join(expno).join("acqu2s"), format!("##$TD= 256\n##$NUC1= <{}>\n", nuc)).unwrap();
+            }
+        }
+        fs::create_dir_all(sample.join("2").join("pdata").join("3")).unwrap();
+        fs::write(sample.join("2").join("pdata").join("1").join("title"), "\n  Menthol in CDCl3\nrun 2\n").unwrap();
+        // Not experiments: no acqus, or not numbered
+        fs::create_dir_all(sample.join("5")).unwrap();
+        fs::create_dir_all(sample.join("scratch")).unwrap();
+
+        let experiments = list_experiments(&sample);
+        assert_eq!(experiments.iter().map(|e| e.expno).collect::<Vec<_>>(), vec![2, 10]);
+        let proton = &experiments[0];
+        assert_eq!((proton.pulprog.as_str(), proton.nucleus.as_str(), proton.ns), ("zg30", "1H", 8));
+        assert_eq!(proton.indirect_nucleus, None);
+        assert_eq!(proton.procnos, vec![1, 3]);
+        assert_eq!(proton.title, "Menthol in CDCl3");
+        assert_eq!(experiments[1].indirect_nucleus.as_deref(), Some("13C"));
+        assert!(list_experiments(&sample.join("2")).is_empty());
+        fs::remove_dir_all(&sample).unwrap();
+    }
+
+    #[test]
+    fn test_read_processed_2d_quadrants() {
+        let dir = std::env::temp_dir().join(format!("nmr_2rr_test_{}", uuid::Uuid::new_v4()));
+        let pdata = dir.join("pdata").join("1");
+        fs::create_dir_all(&pdata).unwrap();
+        fs::write(dir.join("acqus"), "##$TD= 2048\n##$SW_h= 5000\n##$SFO1= 500.13\n##$BF1= 500.13\n##$NUC1= <1H>\n##$PULPROG= <cosygpph>\n").unwrap();
+        fs::write(dir.join("acqu2s"), "##$TD= 16\n##$NUC1= <1H>\n").unwrap();
+        // 4 × 8 matrix in 2 × 4 submatrices; 2ii is missing, so only RI is kept
+        fs::write(pdata.join("procs"), "##$SI= 8\n##$XDIM= 4\n##$DTYPP= 0\n##$BYTORDP= 0\n##$NC_proc= 0\n").unwrap();
+        fs::write(pdata.join("proc2s"), "##$SI= 4\n##$XDIM= 2\n##$NC_proc= 0\n").unwrap();
+        let value = |quadrant: i32, r: usize, c: usize| quadrant * 1000 + (r * 8 + c) as i32;
+        let tiled = |quadrant: i32| -> Vec<u8> {
+            let mut out = Vec::new();
+            for (tr, tc) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+                for r in 0..2 {
+                    for c in 0..4 {
+                        out.extend(value(quadrant, tr * 2 + r, tc * 4 + c).to_le_bytes());
+                    }
+                }
+            }
+            out
+        };
+        for (i, name) in ["2rr", "2ri", "2ir"].iter().enumerate() {
+            fs::write(pdata.join(name), tiled(i as i32)).unwrap();
+        }
+
+        let s = read_bruker_processed(&dir).unwrap();
+        assert_eq!(s.dimensionality, Dimensionality::TwoD);
+        assert_eq!((s.data_2d.len(), s.data_2d[0].len()), (4, 8));
+        assert!((0..4).all(|r| (0..8).all(|c| s.data_2d[r][c] == value(0, r, c) as f64)));
+        assert_eq!(s.data_2d_imag[3][5], value(1, 3, 5) as f64);
+        assert!(s.f1_quadrants.is_none());
+
+        fs::write(pdata.join("2ii"), tiled(3)).unwrap();
+        let q = read_bruker_processed(&dir).unwrap().f1_quadrants.unwrap();
+        assert_eq!((q.ir[2][6], q.ii[1][1]), (value(2, 2, 6) as f64, value(3, 1, 1) as f64));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_processed_strip() {
+        let dir = std::env::temp_dir().join(format!("nmr_strip_test_{}", uuid::Uuid::new_v4()));
+        let pdata = dir.join("pdata").join("1");
+        fs::create_dir_all(&pdata).unwrap();
+        fs::write(dir.join("acqus"), "##$TD= 32\n##$SW_h= 4000\n##$SFO1= 400\n##$BF1= 400\n##$NUC1= <1H>\n").unwrap();
+        // Points 4..8 of a 16-point spectrum from 10 ppm over 10 ppm
+        fs::write(
+            pdata.join("procs"),
+            "##$SI= 16\n##$STSR= 4\n##$STSI= 4\n##$SW_p= 4000\n##$SF= 400\n##$OFFSET= 10\n##$DTYPP= 0\n##$BYTORDP= 0\n##$NC_proc= 0\n",
+        )
+        .unwrap();
+        fs::write(pdata.join("1r"), (0..4i32).flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>()).unwrap();
+
+        let s = read_bruker_processed(&dir).unwrap();
+        let ax = &s.axes[0];
+        assert_eq!(s.real, vec![0.0, 1.0, 2.0, 3.0]);
+        assert_eq!((ax.num_points, ax.first_point, ax.full_points), (4, 4, 16));
+        assert!((ax.index_to_ppm(0) - 7.5).abs() < 1e-9);
+        assert!((ax.index_to_ppm(3) - 5.625).abs() < 1e-9);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_format_intrng() {
+        let text = format_intrng(&[(1.2, 1.35), (7.4, 7.1), (3.71, 3.6)]);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "A 1.0 #regions in PPM");
+        assert_eq!(lines[1], "          7.4000          7.1000");
+        assert_eq!(lines[3].split_whitespace().collect::<Vec<_>>(), ["1.3500", "1.2000"]);
+        assert_eq!(lines.len(), 4);
+    }
+
     #[test]
     fn test_compute_grpdly() {
         assert!((compute_grpdly(2, 12) - 46.311).abs() < 0.001);
@@ -1009,7 +1616,7 @@ mod tests {
         // Just verifies the function doesn't panic.
         // On systems with NMRPipe it finds something, otherwise None.
         let result = find_bruk2pipe();
-        if let Some(path) = &result {
+        if let Some(ToolLocation::Native(path)) = &result {
             assert!(path.exists());
         }
     }
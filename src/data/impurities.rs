@@ -0,0 +1,248 @@
+//! 1H and 13C shifts of common laboratory impurities in the usual NMR
+//! solvents.
+//!
+//! Values are those of Gottlieb, Kotlyar & Nudelman, J. Org. Chem. 62,
+//! 7512 (1997), relative to TMS.  Water, acids and alcohols move with
+//! concentration and temperature, so matches are only suggestions.
+
+use super::spectrum::Nucleus;
+
+/// Solvent columns of the table, as named in [`super::solvent::SOLVENTS`]
+pub const SOLVENT_COLUMNS: [&str; 7] = ["CDCl3", "Acetone-d6", "DMSO-d6", "C6D6", "CD3CN", "CD3OD", "D2O"];
+
+/// Marks a signal not listed for a solvent (insoluble, or hidden by the
+/// solvent)
+const NA: f64 = f64::NAN;
+
+/// One signal of an impurity, per solvent column
+#[derive(Debug)]
+pub struct ImpuritySignal {
+    /// Group giving the signal, e.g. "CH3"
+    pub group: &'static str,
+    shifts: [f64; 7],
+}
+
+impl ImpuritySignal {
+    /// Shift in the solvent at `column`, if listed
+    pub fn ppm(&self, column: usize) -> Option<f64> {
+        self.shifts.get(column).copied().filter(|v| !v.is_nan())
+    }
+}
+
+/// One entry of the impurity table
+#[derive(Debug)]
+pub struct Impurity {
+    pub name: &'static str,
+    pub signals: &'static [ImpuritySignal],
+}
+
+const fn sig(group: &'static str, shifts: [f64; 7]) -> ImpuritySignal {
+    ImpuritySignal { group, shifts }
+}
+
+pub static IMPURITIES: &[Impurity] = &[
+    Impurity { name: "H2O", signals: &[sig("OH", [1.56, 2.84, 3.33, 0.40, 2.13, 4.87, 4.79])] },
+    Impurity { name: "acetic acid", signals: &[sig("CH3", [2.10, 1.96, 1.91, 1.55, 1.96, 1.99, 2.08])] },
+    Impurity { name: "acetone", signals: &[sig("CH3", [2.17, 2.09, 2.09, 1.55, 2.08, 2.15, 2.22])] },
+    Impurity { name: "acetonitrile", signals: &[sig("CH3", [2.10, 2.05, 2.07, 1.55, 1.96, 2.03, 2.06])] },
+    Impurity { name: "benzene", signals: &[sig("CH", [7.36, 7.36, 7.37, 7.15, 7.37, 7.33, NA])] },
+    Impurity { name: "tert-butanol", signals: &[sig("CH3", [1.28, 1.18, 1.11, 1.05, 1.16, 1.40, 1.24])] },
+    Impurity { name: "chloroform", signals: &[sig("CH", [7.26, 8.02, 8.32, 6.15, 7.58, 7.90, NA])] },
+    Impurity { name: "cyclohexane", signals: &[sig("CH2", [1.43, 1.43, 1.40, 1.40, 1.44, 1.45, NA])] },
+    Impurity { name: "1,2-dichloroethane", signals: &[sig("CH2", [3.73, 3.87, 3.90, 2.90, 3.81, 3.78, NA])] },
+    Impurity { name: "dichloromethane", signals: &[sig("CH2", [5.30, 5.63, 5.76, 4.27, 5.44, 5.49, NA])] },
+    Impurity {
+        name: "diethyl ether",
+        signals: &[
+            sig("CH3", [1.21, 1.11, 1.09, 1.11, 1.12, 1.18, 1.17]),
+            sig("CH2", [3.48, 3.41, 3.38, 3.26, 3.42, 3.49, 3.56]),
+        ],
+    },
+    Impurity {
+        name: "DMF",
+        signals: &[
+            sig("CH", [8.02, 7.96, 7.95, 7.63, 7.92, 7.79, 7.92]),
+            sig("CH3", [2.96, 2.94, 2.89, 2.36, 2.89, 2.99, 3.01]),
+            sig("CH3", [2.88, 2.78, 2.73, 1.86, 2.77, 2.86, 2.85]),
+        ],
+    },
+    Impurity { name: "DMSO", signals: &[sig("CH3", [2.62, 2.52, 2.54, 1.68, 2.50, 2.65, 2.71])] },
+    Impurity { name: "dioxane", signals: &[sig("CH2", [3.71, 3.59, 3.57, 3.35, 3.60, 3.66, 3.75])] },
+    Impurity {
+        name: "ethanol",
+        signals: &[
+            sig("CH3", [1.25, 1.12, 1.06, 0.96, 1.12, 1.19, 1.17]),
+            sig("CH2", [3.72, 3.57, 3.44, 3.34, 3.54, 3.60, 3.65]),
+        ],
+    },
+    Impurity {
+        name: "ethyl acetate",
+        signals: &[
+            sig("CH3CO", [2.05, 1.97, 1.99, 1.65, 1.97, 2.01, 2.07]),
+            sig("CH2", [4.12, 4.05, 4.03, 3.89, 4.06, 4.09, 4.14]),
+            sig("CH3", [1.26, 1.20, 1.17, 0.92, 1.20, 1.24, 1.24]),
+        ],
+    },
+    Impurity {
+        name: "grease",
+        signals: &[
+            sig("CH3", [0.86, 0.87, NA, 0.92, 0.86, 0.88, NA]),
+            sig("CH2", [1.26, 1.29, NA, 1.36, 1.27, 1.29, NA]),
+        ],
+    },
+    Impurity {
+        name: "n-hexane",
+        signals: &[
+            sig("CH3", [0.88, 0.88, 0.86, 0.89, 0.89, 0.90, NA]),
+            sig("CH2", [1.26, 1.28, 1.25, 1.24, 1.28, 1.29, NA]),
+        ],
+    },
+    Impurity { name: "HMPA", signals: &[sig("CH3", [2.65, 2.59, 2.53, 2.40, 2.57, 2.64, 2.61])] },
+    Impurity { name: "methanol", signals: &[sig("CH3", [3.49, 3.31, 3.16, 3.07, 3.28, 3.34, 3.34])] },
+    Impurity { name: "nitromethane", signals: &[sig("CH3", [4.33, 4.43, 4.42, 2.94, 4.31, 4.34, 4.40])] },
+    Impurity {
+        name: "n-pentane",
+        signals: &[
+            sig("CH3", [0.88, 0.88, 0.86, 0.87, 0.89, 0.90, NA]),
+            sig("CH2", [1.27, 1.27, 1.27, 1.23, 1.29, 1.29, NA]),
+        ],
+    },
+    Impurity {
+        name: "2-propanol",
+        signals: &[
+            sig("CH3", [1.22, 1.10, 1.04, 0.95, 1.09, 1.50, 1.17]),
+            sig("CH", [4.04, 3.90, 3.78, 3.67, 3.87, 3.92, 4.02]),
+        ],
+    },
+    Impurity {
+        name: "pyridine",
+        signals: &[
+            sig("CH(2)", [8.62, 8.58, 8.58, 8.53, 8.57, 8.53, 8.52]),
+            sig("CH(3)", [7.29, 7.35, 7.39, 6.66, 7.33, 7.44, 7.45]),
+            sig("CH(4)", [7.68, 7.76, 7.79, 6.98, 7.73, 7.85, 7.87]),
+        ],
+    },
+    Impurity { name: "silicone grease", signals: &[sig("CH3", [0.07, 0.13, NA, 0.29, 0.08, 0.10, NA])] },
+    Impurity {
+        name: "THF",
+        signals: &[
+            sig("CH2", [1.85, 1.79, 1.76, 1.40, 1.80, 1.87, 1.88]),
+            sig("OCH2", [3.76, 3.63, 3.60, 3.57, 3.64, 3.71, 3.74]),
+        ],
+    },
+    Impurity {
+        name: "toluene",
+        signals: &[
+            sig("CH3", [2.36, 2.32, 2.30, 2.11, 2.33, 2.32, NA]),
+            sig("CH(o/p)", [7.17, NA, 7.18, 7.02, NA, 7.16, NA]),
+            sig("CH(m)", [7.25, NA, 7.25, 7.13, NA, 7.16, NA]),
+        ],
+    },
+    Impurity {
+        name: "triethylamine",
+        signals: &[
+            sig("CH3", [1.03, 0.96, 0.93, 0.96, 0.96, 1.05, 0.99]),
+            sig("CH2", [2.53, 2.45, 2.43, 2.40, 2.45, 2.58, 2.57]),
+        ],
+    },
+];
+
+pub static IMPURITIES_13C: &[Impurity] = &[
+    Impurity {
+        name: "acetic acid",
+        signals: &[
+            sig("CO", [175.99, 172.31, 171.93, 175.82, 173.21, 175.11, 177.21]),
+            sig("CH3", [20.81, 20.51, 20.95, 20.37, 20.73, 20.56, 21.03]),
+        ],
+    },
+    Impurity {
+        name: "acetone",
+        signals: &[
+            sig("CO", [207.07, 205.87, 206.31, 204.43, 207.43, 209.67, 215.94]),
+            sig("CH3", [30.92, 30.60, 30.56, 30.14, 30.91, 30.67, 30.89]),
+        ],
+    },
+    Impurity {
+        name: "acetonitrile",
+        signals: &[
+            sig("CN", [116.43, 117.60, 117.91, 116.02, 118.26, 118.06, 119.68]),
+            sig("CH3", [1.89, 1.12, 1.03, 0.20, 1.79, 0.85, 1.47]),
+        ],
+    },
+    Impurity { name: "benzene", signals: &[sig("CH", [128.37, 129.15, 128.30, 128.62, 129.32, 129.34, NA])] },
+    Impurity { name: "chloroform", signals: &[sig("CH", [77.36, 79.19, 79.16, 77.79, 79.17, 79.44, NA])] },
+    Impurity { name: "cyclohexane", signals: &[sig("CH2", [26.94, 27.51, 26.33, 27.23, 27.63, 27.96, NA])] },
+    Impurity { name: "dichloromethane", signals: &[sig("CH2", [53.52, 54.95, 54.84, 53.46, 55.32, 54.78, NA])] },
+    Impurity {
+        name: "diethyl ether",
+        signals: &[
+            sig("CH3", [15.20, 15.78, 15.12, 15.46, 15.63, 15.46, 14.77]),
+            sig("CH2", [65.91, 66.12, 62.05, 65.94, 66.32, 66.88, 66.42]),
+        ],
+    },
+    Impurity {
+        name: "DMF",
+        signals: &[
+            sig("CH", [162.62, 162.79, 162.29, 162.13, 163.31, 164.73, 165.53]),
+            sig("CH3", [36.50, 36.15, 35.73, 35.25, 36.57, 36.89, 37.54]),
+            sig("CH3", [31.39, 31.03, 30.73, 30.72, 31.32, 31.61, 32.03]),
+        ],
+    },
+    Impurity { name: "DMSO", signals: &[sig("CH3", [40.76, 41.23, 40.45, 40.03, 41.31, 40.45, 39.39])] },
+    Impurity {
+        name: "ethanol",
+        signals: &[
+            sig("CH3", [18.41, 18.89, 18.51, 18.72, 18.80, 18.40, 17.47]),
+            sig("CH2", [58.28, 57.72, 56.07, 57.86, 57.96, 58.26, 58.05]),
+        ],
+    },
+    Impurity {
+        name: "ethyl acetate",
+        signals: &[
+            sig("CH3CO", [21.04, 20.83, 20.68, 20.56, 21.16, 20.88, 21.15]),
+            sig("CO", [171.36, 170.96, 170.31, 170.44, 171.68, 172.89, 175.26]),
+            sig("CH2", [60.49, 60.56, 59.74, 60.21, 60.98, 61.50, 62.32]),
+            sig("CH3", [14.19, 14.50, 14.40, 14.19, 14.54, 14.49, 13.92]),
+        ],
+    },
+    Impurity { name: "grease", signals: &[sig("CH2", [29.76, 30.73, 29.20, 30.21, 30.86, 31.29, NA])] },
+    Impurity {
+        name: "n-hexane",
+        signals: &[
+            sig("CH3", [14.14, 14.34, 13.88, 14.32, 14.43, 14.45, NA]),
+            sig("CH2(2)", [22.70, 23.28, 22.05, 23.04, 23.40, 23.68, NA]),
+            sig("CH2(3)", [31.64, 32.30, 30.95, 31.96, 32.36, 32.73, NA]),
+        ],
+    },
+    Impurity { name: "methanol", signals: &[sig("CH3", [50.41, 49.77, 48.59, 49.97, 49.90, 49.86, 49.50])] },
+    Impurity { name: "silicone grease", signals: &[sig("CH3", [1.19, 1.40, NA, 1.38, NA, NA, NA])] },
+    Impurity {
+        name: "THF",
+        signals: &[
+            sig("CH2", [25.62, 26.15, 25.14, 25.72, 26.27, 26.48, 25.67]),
+            sig("OCH2", [67.97, 68.07, 67.03, 67.80, 68.33, 68.83, 68.68]),
+        ],
+    },
+    Impurity { name: "toluene", signals: &[sig("CH3", [21.46, 21.46, 20.99, 21.10, 21.50, 21.50, NA])] },
+    Impurity {
+        name: "triethylamine",
+        signals: &[
+            sig("CH3", [11.61, 12.49, 11.74, 12.35, 12.38, 11.09, 9.07]),
+            sig("CH2", [46.25, 47.07, 45.74, 46.77, 47.10, 46.96, 47.19]),
+        ],
+    },
+];
+
+/// Impurity table for the observed nucleus (2H uses the 1H one)
+pub fn table(nucleus: &Nucleus) -> Option<&'static [Impurity]> {
+    match nucleus {
+        Nucleus::H1 | Nucleus::H2 => Some(IMPURITIES),
+        Nucleus::C13 => Some(IMPURITIES_13C),
+        _ => None,
+    }
+}
+
+/// Table column of a solvent, by its [`super::solvent::SOLVENTS`] name
+pub fn column(solvent_name: &str) -> Option<usize> {
+    SOLVENT_COLUMNS.iter().position(|&c| c == solvent_name)
+}
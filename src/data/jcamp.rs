@@ -27,9 +27,11 @@
 ///
 /// This reader handles the most common JCAMP-DX NMR spectral formats.
 
+use std::collections::BTreeMap;
 use std::io;
 use std::path::Path;
 
+use super::nuclide;
 use super::spectrum::*;
 
 /// Parsed JCAMP-DX header fields
@@ -49,6 +51,16 @@ struct JcampHeader {
     solvent: String,
     shift_reference: f64, // SHIFT REFERENCE
     data_class: String,   // XYDATA, XYPOINTS, PEAK TABLE, NTUPLES
+    metadata: BTreeMap<String, String>, // .PULSE SEQUENCE, $NS, ...
+}
+
+/// Metadata key for an acquisition-parameter label (standard or Bruker `$`)
+fn metadata_key(label: &str) -> Option<&'static str> {
+    match label {
+        ".PULSE SEQUENCE" | "$PULPROG" => Some(META_PULSE_PROGRAM),
+        "$NS" => Some(META_SCANS),
+        _ => None,
+    }
 }
 
 /// Parse a JCAMP-DX file into a SpectrumData
@@ -88,6 +100,10 @@ fn parse_jcamp(content: &str, source_path: &Path) -> io::Result<SpectrumData> {
                 let key = trimmed[2..eq_pos].trim().to_uppercase();
                 let value = trimmed[eq_pos + 1..].trim();
 
+                if let Some(meta) = metadata_key(&key) {
+                    let value = value.trim_matches(|c: char| c == '<' || c == '>');
+                    header.metadata.insert(meta.to_string(), value.to_string());
+                }
                 match key.as_str() {
                     "TITLE" => header.title = value.to_string(),
                     "DATA TYPE" | "DATATYPE" => header.data_type = value.to_uppercase(),
@@ -180,10 +196,12 @@ fn parse_jcamp(content: &str, source_path: &Path) -> io::Result<SpectrumData> {
     let is_frequency_domain = is_ppm || is_hz || header.data_type.contains("SPECTRUM");
 
     // Build the spectrum
+    let nucleus = parse_jcamp_nucleus(&header.observe_nucleus);
     let obs_mhz = if header.observe_freq > 0.0 {
         header.observe_freq
     } else {
-        400.0 // default fallback
+        // default fallback: a 400 MHz magnet, scaled to the observed nucleus
+        nuclide::observe_frequency(&nucleus, 400.0)
     };
 
     let (sw_hz, ref_ppm) = if is_ppm {
@@ -206,7 +224,6 @@ fn parse_jcamp(content: &str, source_path: &Path) -> io::Result<SpectrumData> {
         (sw, header.first_x.max(header.last_x))
     };
 
-    let nucleus = parse_jcamp_nucleus(&header.observe_nucleus);
     let npoints = y_data.len();
 
     let axis = AxisParams {
@@ -216,6 +233,7 @@ fn parse_jcamp(content: &str, source_path: &Path) -> io::Result<SpectrumData> {
         observe_freq_mhz: obs_mhz,
         reference_ppm: ref_ppm,
         label: header.observe_nucleus.clone(),
+        ..Default::default()
     };
 
     let filename = source_path
@@ -226,6 +244,7 @@ fn parse_jcamp(content: &str, source_path: &Path) -> io::Result<SpectrumData> {
         match nucleus {
             Nucleus::C13 => ExperimentType::Carbon,
             Nucleus::H1 => ExperimentType::Proton,
+            Nucleus::H2 => ExperimentType::Deuterium,
             _ => detect_experiment_type(&filename),
         }
     } else {
@@ -251,12 +270,21 @@ fn parse_jcamp(content: &str, source_path: &Path) -> io::Result<SpectrumData> {
         } else {
             header.title
         },
+        solvent: header.solvent,
+        array: None,
+        metadata: header.metadata,
         axes: vec![axis],
         real,
         imag: Vec::new(),
         data_2d: Vec::new(),
         data_2d_imag: Vec::new(),
+        f1_quadrants: None,
+        nus_schedule: None,
+        group_delay: None,
+        f1_acquisition: F1Acquisition::default(),
+        pulse_program: None,
         is_frequency_domain,
+        provenance: Provenance::on_load(is_frequency_domain),
         nmrpipe_path: None,
         conversion_method_used: "Built-in (JCAMP-DX reader)".to_string(),
     })
@@ -325,6 +353,10 @@ fn parse_jcamp_ntuples(content: &str, source_path: &Path) -> io::Result<Spectrum
                 let key = trimmed[2..eq_pos].trim().to_uppercase();
                 let value = trimmed[eq_pos + 1..].trim();
 
+                if let Some(meta) = metadata_key(&key) {
+                    let value = value.trim_matches(|c: char| c == '<' || c == '>');
+                    header.metadata.insert(meta.to_string(), value.to_string());
+                }
                 match key.as_str() {
                     "TITLE" => header.title = value.to_string(),
                     "DATA TYPE" | "DATATYPE" => header.data_type = value.to_uppercase(),
@@ -450,10 +482,11 @@ fn parse_jcamp_ntuples(content: &str, source_path: &Path) -> io::Result<Spectrum
     let is_hz = x_unit.contains("HZ");
     let is_frequency_domain = is_ppm || is_hz || header.data_type.contains("SPECTRUM");
 
+    let nucleus = parse_jcamp_nucleus(&header.observe_nucleus);
     let obs_mhz = if header.observe_freq > 0.0 {
         header.observe_freq
     } else {
-        400.0
+        nuclide::observe_frequency(&nucleus, 400.0)
     };
 
     let (sw_hz, ref_ppm) = if is_ppm {
@@ -474,7 +507,6 @@ fn parse_jcamp_ntuples(content: &str, source_path: &Path) -> io::Result<Spectrum
         (sw, first_x.max(last_x))
     };
 
-    let nucleus = parse_jcamp_nucleus(&header.observe_nucleus);
     let n = real_data.len();
 
     let axis = AxisParams {
@@ -484,6 +516,7 @@ fn parse_jcamp_ntuples(content: &str, source_path: &Path) -> io::Result<Spectrum
         observe_freq_mhz: obs_mhz,
         reference_ppm: ref_ppm,
         label: header.observe_nucleus.clone(),
+        ..Default::default()
     };
 
     let filename = source_path
@@ -494,6 +527,7 @@ fn parse_jcamp_ntuples(content: &str, source_path: &Path) -> io::Result<Spectrum
         match nucleus {
             Nucleus::C13 => ExperimentType::Carbon,
             Nucleus::H1 => ExperimentType::Proton,
+            Nucleus::H2 => ExperimentType::Deuterium,
             _ => detect_experiment_type(&filename),
         }
     } else {
@@ -524,12 +558,21 @@ fn parse_jcamp_ntuples(content: &str, source_path: &Path) -> io::Result<Spectrum
         } else {
             header.title
         },
+        solvent: header.solvent,
+        array: None,
+        metadata: header.metadata,
         axes: vec![axis],
         real,
         imag,
         data_2d: Vec::new(),
         data_2d_imag: Vec::new(),
+        f1_quadrants: None,
+        nus_schedule: None,
+        group_delay: None,
+        f1_acquisition: F1Acquisition::default(),
+        pulse_program: None,
         is_frequency_domain,
+        provenance: Provenance::on_load(is_frequency_domain),
         nmrpipe_path: None,
         conversion_method_used: "Built-in (JCAMP-DX NTUPLES reader)".to_string(),
     })
@@ -542,20 +585,10 @@ fn parse_jcamp_nucleus(nuc: &str) -> Nucleus {
         .replace('^', "")
         .replace(' ', "")
         .replace("NUC", "");
-    match cleaned.as_str() {
-        "1H" | "H1" | "H" => Nucleus::H1,
-        "13C" | "C13" | "C" => Nucleus::C13,
-        "15N" | "N15" | "N" => Nucleus::N15,
-        "19F" | "F19" | "F" => Nucleus::F19,
-        "31P" | "P31" | "P" => Nucleus::P31,
-        _ => {
-            if cleaned.is_empty() {
-                Nucleus::Other("Unknown".into())
-            } else {
-                Nucleus::Other(cleaned)
-            }
-        }
+    if cleaned.is_empty() {
+        return Nucleus::Other("Unknown".into());
     }
+    nuclide::parse_label(&cleaned).unwrap_or(Nucleus::Other(cleaned))
 }
 
 /// Parse (XY..XY) format — simple comma or space separated X,Y pairs
@@ -896,6 +929,8 @@ mod tests {
         assert_eq!(parse_jcamp_nucleus("^1H"), Nucleus::H1);
         assert_eq!(parse_jcamp_nucleus("13C"), Nucleus::C13);
         assert_eq!(parse_jcamp_nucleus("^13C"), Nucleus::C13);
+        assert_eq!(parse_jcamp_nucleus("^2H"), Nucleus::H2);
+        assert_eq!(parse_jcamp_nucleus("D"), Nucleus::H2);
     }
 
     #[test]
@@ -1006,6 +1041,8 @@ mod tests {
 ##YUNITS= ARBITRARY UNITS
 ##.OBSERVE FREQUENCY= 400.13
 ##.OBSERVE NUCLEUS= ^1H
+##.PULSE SEQUENCE= zg30
+##$NS= 16
 ##FIRSTX= 12.0
 ##LASTX= -1.0
 ##NPOINTS= 5
@@ -1020,5 +1057,7 @@ mod tests {
         assert_eq!(spectrum.real.len(), 5);
         assert!(spectrum.is_frequency_domain);
         assert!((spectrum.axes[0].observe_freq_mhz - 400.13).abs() < 0.01);
+        assert_eq!(spectrum.metadata[META_PULSE_PROGRAM], "zg30");
+        assert_eq!(spectrum.metadata[META_SCANS], "16");
     }
 }
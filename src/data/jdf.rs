@@ -6,63 +6,17 @@
 
 use std::io;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 use std::fs;
 
+use crate::pipeline::command::{run_cancellable, CancelToken};
+use crate::pipeline::tools::{self, ToolLocation};
+
 /// Locate the delta2pipe executable.
 ///
-/// Checks PATH first, then falls back to common NMRPipe installation directories.
-pub fn find_delta2pipe() -> Option<PathBuf> {
-    // Check PATH via `which`
-    if let Ok(output) = Command::new("which").arg("delta2pipe").output() {
-        if output.status.success() {
-            let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            if !path.is_empty() {
-                return Some(PathBuf::from(path));
-            }
-        }
-    }
-
-    // Check common NMRPipe installation paths relative to $HOME
-    if let Ok(home) = std::env::var("HOME") {
-        let home_paths = [
-            format!("{}/Documents/NMRpipe/nmrbin.linux239_64/delta2pipe", home),
-            format!("{}/NMRPipe/nmrbin.linux239_64/delta2pipe", home),
-            format!("{}/nmrpipe/bin/delta2pipe", home),
-        ];
-        for p in &home_paths {
-            if Path::new(p).exists() {
-                return Some(PathBuf::from(p));
-            }
-        }
-    }
-
-    // Check NMR_BASE environment variable (NMRPipe convention)
-    if let Ok(nmr_base) = std::env::var("NMR_BASE") {
-        let p = format!("{}/bin/delta2pipe", nmr_base);
-        if Path::new(&p).exists() {
-            return Some(PathBuf::from(p));
-        }
-    }
-
-    // System-wide paths
-    let system_paths = [
-        "/usr/local/nmrpipe/bin/delta2pipe",
-        "/opt/nmrpipe/bin/delta2pipe",
-    ];
-    for p in &system_paths {
-        if Path::new(p).exists() {
-            return Some(PathBuf::from(*p));
-        }
-    }
-
-    // Hard-coded fallback for this system
-    let fallback = PathBuf::from("/home/raaf/Documents/NMRpipe/nmrbin.linux239_64/delta2pipe");
-    if fallback.exists() {
-        return Some(fallback);
-    }
-
-    None
+/// See [`tools::find_tool`] for the search order (PATH, NMRPipe install
+/// folders on Linux/macOS, WSL on Windows).
+pub fn find_delta2pipe() -> Option<ToolLocation> {
+    tools::find_tool("delta2pipe")
 }
 
 /// Run `delta2pipe -in <file> -all -info` and return the raw output text.
@@ -74,9 +28,8 @@ pub fn get_jdf_info(jdf_path: &Path) -> io::Result<String> {
         )
     })?;
 
-    let output = Command::new(&exe)
-        .args(["-in", &jdf_path.to_string_lossy(), "-all", "-info"])
-        .output()?;
+    let args = ["-in", &jdf_path.to_string_lossy(), "-all", "-info"].map(String::from);
+    let output = exe.command(&args).output()?;
 
     let combined = format!(
         "{}{}",
@@ -141,13 +94,16 @@ pub struct Delta2PipeResult {
 ///
 /// `extra_args` are additional command-line arguments (e.g. `-xN 26214 -xMODE Complex`).
 ///
-/// Returns the list of output files and the command used.
+/// Returns the list of output files and the command used.  If `cancel` is
+/// triggered the delta2pipe process is killed and an `Interrupted` error
+/// returned.
 pub fn convert_jdf(
     jdf_path: &Path,
     output_dir: &Path,
     stem: &str,
     ndim_hint: Option<usize>,
     extra_args: &[String],
+    cancel: Option<&CancelToken>,
 ) -> io::Result<Delta2PipeResult> {
     let exe = find_delta2pipe().ok_or_else(|| {
         io::Error::new(
@@ -169,7 +125,7 @@ pub fn convert_jdf(
         output_dir.join(format!("{}.fid", stem))
     };
 
-    let exe_str = exe.to_string_lossy().to_string();
+    let exe_str = exe.display();
     let in_str = jdf_path.to_string_lossy().to_string();
     let out_str = out_pattern.to_string_lossy().to_string();
 
@@ -188,9 +144,7 @@ pub fn convert_jdf(
 
     log::info!("Running: {}", cmd_string);
 
-    let output = Command::new(&exe)
-        .args(&all_args)
-        .output()?;
+    let output = run_cancellable(&mut exe.command(&all_args), cancel)?;
 
     let log_output = format!(
         "{}{}",
@@ -1,6 +1,13 @@
 pub mod spectrum;
+pub mod nuclide;
+pub mod solvent;
+pub mod impurities;
 pub mod jdf;
 pub mod nmrpipe_format;
 pub mod bruker;
 pub mod jcamp;
+pub mod varian;
 pub mod native_converter;
+pub mod pulse_program;
+pub mod storage;
+pub mod session;
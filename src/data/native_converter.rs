@@ -7,7 +7,7 @@
 /// happens in-process using pure Rust.
 
 use std::io::{self, BufReader};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use nmrpipe_core::fdata::*;
 use nmrpipe_core::params::*;
@@ -20,22 +20,10 @@ use super::spectrum::*;
 
 /// Map nucleus label string (from FDATA header) to our `Nucleus` enum.
 fn nucleus_from_label(label: &str) -> Nucleus {
-    let upper = label.to_uppercase();
-    if upper.contains("1H") || upper == "H1" || upper == "H" {
-        Nucleus::H1
-    } else if upper.contains("13C") || upper == "C13" || upper == "C" {
-        Nucleus::C13
-    } else if upper.contains("15N") || upper == "N15" || upper == "N" {
-        Nucleus::N15
-    } else if upper.contains("19F") || upper == "F19" {
-        Nucleus::F19
-    } else if upper.contains("31P") || upper == "P31" {
-        Nucleus::P31
-    } else if label.is_empty() {
-        Nucleus::H1
-    } else {
-        Nucleus::Other(label.to_string())
+    if label.is_empty() {
+        return Nucleus::H1;
     }
+    super::nuclide::parse_label(label).unwrap_or_else(|| Nucleus::Other(label.to_string()))
 }
 
 /// Extract `AxisParams` for a given dimension from the FDATA header.
@@ -68,6 +56,7 @@ fn axis_from_fdata(fdata: &Fdata, dim: i32) -> AxisParams {
         } else {
             label
         },
+        ..Default::default()
     }
 }
 
@@ -129,12 +118,21 @@ fn fdata_planes_to_spectrum(
         experiment_type,
         dimensionality,
         sample_name: filename,
+        solvent: String::new(),
+        array: None,
+        metadata: Default::default(),
         axes,
         real: Vec::new(),
         imag: Vec::new(),
         data_2d: Vec::new(),
         data_2d_imag: Vec::new(),
+        f1_quadrants: None,
+        nus_schedule: None,
+        group_delay: None,
+        f1_acquisition: F1Acquisition::default(),
+        pulse_program: None,
         is_frequency_domain: is_freq,
+        provenance: Provenance::on_load(is_freq),
         nmrpipe_path: None,
         conversion_method_used: String::new(),
     };
@@ -246,6 +244,7 @@ pub fn convert_jdf_native(path: &Path, opts: &NativeJeolOptions) -> io::Result<S
 
     let mut spectrum = fdata_planes_to_spectrum(path, &result.fdata, &result.planes);
     spectrum.vendor_format = VendorFormat::Jeol;
+    spectrum.solvent = result.solvent.unwrap_or_default();
     spectrum.conversion_method_used = "Built-in (native delta2pipe)".to_string();
 
     // Fix dimensionality if data turns out to be 2D
@@ -269,12 +268,25 @@ pub fn convert_jdf_native(path: &Path, opts: &NativeJeolOptions) -> io::Result<S
 //  Bruker native conversion
 // ────────────────────────────────────────────────────────────────
 
-/// Convert a Bruker dataset to SpectrumData using the native
-/// `bruk2pipe` library crate (no external tools needed).
+/// Everything `bruk2pipe` needs to convert one Bruker experiment directory.
+pub struct BrukerNativeSetup {
+    /// The `fid` or `ser` file to read.
+    pub in_file: PathBuf,
+    /// Options with the FDATA header populated from acqus.
+    pub options: bruk2pipe::BrukerOptions,
+    /// The parsed acquisition parameters.
+    pub params: super::bruker::BrukerParams,
+    /// Whether the group delay is removed, and why.
+    pub dmx: super::bruker::DmxDecision,
+}
+
+/// Read acqus parameters from a Bruker experiment directory and build the
+/// `bruk2pipe` options (FDATA header, DMX filter, byte order, word size).
 ///
-/// This reads acqus parameters, populates an FDATA header, then calls
-/// `bruk2pipe::bruker_to_pipe()` for the raw binary conversion.
-pub fn convert_bruker_native(dir: &Path) -> io::Result<SpectrumData> {
+/// `dmx_override` forces group-delay correction on or off; `None` lets
+/// [`bruker::dmx_decision`](super::bruker::dmx_decision) decide.  Shared by
+/// the single-file loader and the parallel batch converter.
+pub fn bruker_native_setup(dir: &Path, dmx_override: Option<bool>) -> io::Result<BrukerNativeSetup> {
     use super::bruker;
 
     // Read acqus parameters
@@ -318,18 +330,20 @@ pub fn convert_bruker_native(dir: &Path) -> io::Result<SpectrumData> {
     fdata.set_parm(NDAPOD, x_td as f32, CUR_XDIM);
     fdata.set_parm(NDTDSIZE, x_td as f32, CUR_XDIM);
 
+    // Word type, byte order and row padding of the raw file
+    let rows = if is_2d { params.td_f1 } else { 1 };
+    let layout = bruker::fid_layout(&params, rows, std::fs::metadata(&in_file)?.len());
+    let padded = layout.row_words > params.td;
+    if padded {
+        // Read whole padded rows, then keep the TD / 2 valid complex points
+        fdata.set_parm(NDSIZE, layout.row_words as f32, CUR_XDIM);
+        fdata.set_parm(NDAPOD, (params.td / 2) as f32, CUR_XDIM);
+    }
+
     if is_2d {
         let y_td = params.td_f1 as i32;
         let y_obs = if params.sfo1_f1 > 0.0 { params.sfo1_f1 } else { params.sfo1 };
-        let y_car = if y_obs > 0.0 {
-            // Try to compute carrier from O1 of indirect dim
-            // In Bruker, the indirect carrier is in the acqu2s O1 field
-            // but we may not have parsed it separately.
-            // Fall back to 0.0 if needed — bruk2pipe will compute origin.
-            0.0
-        } else {
-            0.0
-        };
+        let y_car = params.carrier_ppm_f1();
 
         let nuc_f1 = if params.nuc1_f1.is_empty() {
             &params.nuc1
@@ -366,38 +380,38 @@ pub fn convert_bruker_native(dir: &Path) -> io::Result<SpectrumData> {
         fdata.set_parm(NDAQSIGN, aqsign_val, CUR_YDIM);
     }
 
-    // Determine Bruker type and group delay
-    let grpdly = if params.grpdly > 0.0 {
-        params.grpdly as f32
+    // Determine Bruker type and group delay; pre-corrected data is read as AMX
+    let dmx = bruker::dmx_decision(dir, &params, dmx_override);
+    let grpdly = if dmx.apply {
+        bruker::resolve_grpdly(&params) as f32
     } else {
-        bruker_compute_grpdly(params.decim, params.dspfvs) as f32
+        0.0
     };
 
-    let bruk_type = if params.decim > 1 || grpdly > 0.0 {
+    let bruk_type = if layout.format == bruker::FidWordFormat::Int24 {
+        bruk2pipe::BrukerType::Am
+    } else if dmx.apply {
         bruk2pipe::BrukerType::Dmx
     } else {
         bruk2pipe::BrukerType::Amx
     };
 
-    // Byte swap: Bruker data byte order from BYTORDA
-    let needs_swap = if cfg!(target_endian = "little") {
-        params.bytorda == 1 // big-endian data on little-endian host
-    } else {
-        params.bytorda == 0 // little-endian data on big-endian host
-    };
+    // Byte swap: data byte order from the layout (BYTORDA, or big-endian AM)
+    let needs_swap = layout.big_endian == cfg!(target_endian = "little");
 
-    // Word size
-    let word_size: usize = if params.dtypa == 2 { 8 } else { 4 };
+    // Double-precision words are read as floats; the bad-point clip is for
+    // integer overflow only
+    let is_float = layout.format == bruker::FidWordFormat::Float64;
 
     let bruker_opts = bruk2pipe::BrukerOptions {
         bruk_type,
         fdata,
         swap: needs_swap,
-        i2f: true,
-        word_size,
+        i2f: !is_float,
+        word_size: layout.format.size(),
         byte_offset: 0,
-        bad_thresh: 8_000_000.0,
-        ext_flag: false,
+        bad_thresh: if is_float { 0.0 } else { 8_000_000.0 },
+        ext_flag: padded,
         decim: params.decim,
         dspfvs: params.dspfvs,
         grpdly,
@@ -408,6 +422,26 @@ pub fn convert_bruker_native(dir: &Path) -> io::Result<SpectrumData> {
         verbose: false,
     };
 
+    Ok(BrukerNativeSetup {
+        in_file,
+        options: bruker_opts,
+        params,
+        dmx,
+    })
+}
+
+/// Convert a Bruker dataset to SpectrumData using the native
+/// `bruk2pipe` library crate (no external tools needed).
+///
+/// This reads acqus parameters, populates an FDATA header, then calls
+/// `bruk2pipe::bruker_to_pipe()` for the raw binary conversion.
+pub fn convert_bruker_native(dir: &Path, dmx_override: Option<bool>) -> io::Result<SpectrumData> {
+    use super::bruker;
+
+    let BrukerNativeSetup { in_file, options: bruker_opts, params, .. } = bruker_native_setup(dir, dmx_override)?;
+    let bruk_type = bruker_opts.bruk_type;
+    let grpdly = bruker_opts.grpdly;
+
     let file = std::fs::File::open(&in_file)?;
     let mut reader = BufReader::new(file);
 
@@ -420,6 +454,9 @@ pub fn convert_bruker_native(dir: &Path) -> io::Result<SpectrumData> {
 
     // Use experiment type from pulse program
     spectrum.experiment_type = bruker::detect_experiment_from_pulprog(&params.pulprog);
+    spectrum.solvent = params.solvent.clone();
+    spectrum.metadata = params.metadata();
+    spectrum.f1_acquisition = F1Acquisition::from_bruker_fnmode(params.fnmode);
 
     // Set sample name from directory
     spectrum.sample_name = dir
@@ -442,37 +479,3 @@ pub fn convert_bruker_native(dir: &Path) -> io::Result<SpectrumData> {
 
     Ok(spectrum)
 }
-
-/// Compute the Bruker digital filter group delay from DECIM and DSPFVS.
-/// (Fallback lookup table when GRPDLY is not set in acqus.)
-fn bruker_compute_grpdly(decim: i32, dspfvs: i32) -> f64 {
-    if decim <= 1 {
-        return 0.0;
-    }
-
-    match dspfvs {
-        10 => match decim {
-            2 => 44.75, 3 => 33.5, 4 => 66.625, 6 => 59.0833,
-            8 => 68.5625, 12 => 60.375, 16 => 69.5313, 24 => 61.0208,
-            32 => 70.0156, 48 => 61.3438, 64 => 70.2578, 96 => 61.5052,
-            128 => 70.3789, 192 => 61.5859, 256 => 70.4395, 384 => 61.6263,
-            512 => 70.4697, 768 => 61.6465, 1024 => 70.4849, 1536 => 61.6566,
-            2048 => 70.4924, _ => 0.0,
-        },
-        11 => match decim {
-            2 => 46.0, 3 => 36.5, 4 => 48.0, 6 => 50.1667,
-            8 => 53.25, 12 => 69.5, 16 => 72.25, 24 => 70.1667,
-            32 => 72.75, 48 => 70.5, 64 => 73.0, 96 => 70.6667,
-            128 => 72.5, 192 => 71.3333, 256 => 72.25, 384 => 71.6667,
-            512 => 72.125, 768 => 71.8333, 1024 => 72.0625, 1536 => 71.9167,
-            2048 => 72.0313, _ => 0.0,
-        },
-        12 => match decim {
-            2 => 46.311, 3 => 36.530, 4 => 47.870, 6 => 50.229,
-            8 => 53.289, 12 => 69.551, 16 => 71.600, 24 => 70.184,
-            32 => 72.138, 48 => 70.528, 64 => 72.348, 96 => 70.700,
-            128 => 72.524, _ => 0.0,
-        },
-        _ => 0.0,
-    }
-}
@@ -3,8 +3,9 @@
 /// NMRPipe uses a 2048-byte (512 float32) header followed by spectral data.
 /// This module can read NMRPipe .ft1/.ft2/.fid files and also write them.
 
-use byteorder::{LittleEndian, BigEndian, ReadBytesExt, WriteBytesExt};
-use std::io::{self, Cursor, Seek, SeekFrom};
+use byteorder::{LittleEndian, BigEndian, ReadBytesExt};
+use nmrpipe_io::writer::{PipeAxis, PipeSpectrum, WriteError};
+use std::io::{self, Cursor};
 use std::path::Path;
 
 use super::spectrum::*;
@@ -35,6 +36,42 @@ mod idx {
     pub const FDF1LABEL: usize = 18;    // F1 label
     pub const FDPIPEFLAG: usize = 57;   // Pipe mode flag
     pub const FDTRANSPOSED: usize = 221; // 1=transposed
+    pub const FDF2FTSIZE: usize = 96;   // F2 size after FT, before EXT
+    pub const FDF1FTSIZE: usize = 98;   // F1 size after FT, before EXT
+    pub const FDF2X1: usize = 257;      // First point kept by EXT (1-based), F2
+    pub const FDF1X1: usize = 259;      // First point kept by EXT (1-based), F1
+}
+
+/// Record an EXT region on `axis` from the header: NMRPipe rescales SW and
+/// ORIG to the kept points, and notes the first of them (`x1`, 1-based) and
+/// the size of the transformed spectrum they came from (`ftsize`).  The
+/// axis is re-expressed on the full spectrum with every point at its ppm.
+fn with_extract(axis: AxisParams, x1: f32, ftsize: f32) -> AxisParams {
+    let (first, full) = (x1.max(1.0) as usize - 1, ftsize.max(0.0) as usize);
+    if (first == 0 && full <= axis.num_points) || first + axis.num_points > full {
+        return axis;
+    }
+    AxisParams {
+        spectral_width_hz: axis.hz_per_point() * full as f64,
+        reference_ppm: axis.index_to_ppm(0) + first as f64 * axis.ppm_per_point(),
+        first_point: first,
+        full_points: full,
+        ..axis
+    }
+}
+
+/// Parse the 512-float header in the file's byte order, detected from
+/// FDFLTORDER (with fallbacks for a damaged order word, see
+/// [`nmrpipe_io::reader::detect_byte_order`]).  Returns the header and
+/// whether the file is big-endian.
+fn read_header(data: &[u8]) -> io::Result<(Vec<f32>, bool)> {
+    let order = nmrpipe_io::reader::detect_byte_order(data)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let header = data[..HEADER_BYTES]
+        .chunks_exact(4)
+        .map(|c| order.read_f32([c[0], c[1], c[2], c[3]]))
+        .collect();
+    Ok((header, order == nmrpipe_io::byteswap::ByteOrder::Big))
 }
 
 /// Read an NMRPipe format file
@@ -47,23 +84,7 @@ pub fn read_nmrpipe_file(path: &Path) -> io::Result<SpectrumData> {
         ));
     }
 
-    // Parse header as 512 little-endian floats first
-    let mut header = vec![0.0f32; HEADER_FLOATS];
-    let mut cursor = Cursor::new(&data[..HEADER_BYTES]);
-    for h in header.iter_mut() {
-        *h = cursor.read_f32::<LittleEndian>()?;
-    }
-
-    // NMRPipe byte order check: FDFLTORDER (index 2) should be ≈ 2.345
-    // If it's not, the file is big-endian and we re-read.
-    let order_val = header[idx::FDFLTORDER];
-    let is_big_endian = (order_val - 2.345).abs() > 0.01;
-    if is_big_endian {
-        cursor.seek(SeekFrom::Start(0))?;
-        for h in header.iter_mut() {
-            *h = cursor.read_f32::<BigEndian>()?;
-        }
-    }
+    let (header, is_big_endian) = read_header(&data)?;
 
     let ndim = header[idx::FDDIMCOUNT] as usize;
     let npts_x = header[idx::FDSIZE] as usize;
@@ -105,12 +126,21 @@ pub fn read_nmrpipe_file(path: &Path) -> io::Result<SpectrumData> {
         experiment_type,
         dimensionality: dimensionality.clone(),
         sample_name: filename,
+        solvent: String::new(),
+        array: None,
+        metadata: Default::default(),
         axes: Vec::new(),
         real: Vec::new(),
         imag: Vec::new(),
         data_2d: Vec::new(),
         data_2d_imag: Vec::new(),
+        f1_quadrants: None,
+        nus_schedule: None,
+        group_delay: None,
+        f1_acquisition: F1Acquisition::default(),
+        pulse_program: None,
         is_frequency_domain: is_freq_domain,
+        provenance: Provenance::on_load(is_freq_domain),
         nmrpipe_path: Some(path.to_path_buf()),
         conversion_method_used: String::new(),
     };
@@ -122,6 +152,7 @@ pub fn read_nmrpipe_file(path: &Path) -> io::Result<SpectrumData> {
         observe_freq_mhz: obs_x,
         reference_ppm: ref_ppm_x,
         label: "F2".to_string(),
+        ..Default::default()
     };
     spectrum.axes.push(axis_x);
 
@@ -150,6 +181,7 @@ pub fn read_nmrpipe_file(path: &Path) -> io::Result<SpectrumData> {
             observe_freq_mhz: obs_y,
             reference_ppm: ref_ppm_y,
             label: if label_f1.is_empty() { "F1".to_string() } else { label_f1 },
+            ..Default::default()
         };
         spectrum.axes.push(axis_y);
     }
@@ -224,83 +256,95 @@ pub fn read_nmrpipe_file(path: &Path) -> io::Result<SpectrumData> {
         }
     }
 
+    if is_freq_domain {
+        if let Some(ax) = spectrum.axes.first_mut() {
+            *ax = with_extract(ax.clone(), header[idx::FDF2X1], header[idx::FDF2FTSIZE]);
+        }
+    }
+    if header[idx::FDF1FTFLAG] as i32 == 1 {
+        if let Some(ax) = spectrum.axes.get_mut(1) {
+            *ax = with_extract(ax.clone(), header[idx::FDF1X1], header[idx::FDF1FTSIZE]);
+        }
+    }
+
     Ok(spectrum)
 }
 
-/// Write spectrum data to NMRPipe format
-pub fn write_nmrpipe_file(spectrum: &SpectrumData, path: &Path) -> io::Result<()> {
-    let mut file = std::fs::File::create(path)?;
-
-    // Create header
-    let mut header = vec![0.0f32; HEADER_FLOATS];
-
-    // Magic / format
-    header[idx::FDMAGIC] = 0.0;
-    header[idx::FDFLTFORMAT] = f32::from_bits(0x4f6eeeef); // NMRPipe IEEE marker
-    header[idx::FDFLTORDER] = 2.345f32; // Little-endian byte order marker
-
-    // Dimensions
-    let ndim = if spectrum.is_2d() { 2.0 } else { 1.0 };
-    header[idx::FDDIMCOUNT] = ndim;
-
-    // X axis
-    let npts = spectrum.real.len().max(1);
-    header[idx::FDSIZE] = npts as f32;
-    header[idx::FDREALSIZE] = npts as f32;
-
-    if let Some(ax) = spectrum.axes.first() {
-        header[idx::FDF2SW] = ax.spectral_width_hz as f32;
-        header[idx::FDF2OBS] = ax.observe_freq_mhz as f32;
-        header[idx::FDF2ORIG] = (ax.reference_ppm * ax.observe_freq_mhz) as f32;
-    }
-
-    header[idx::FDF2FTFLAG] = if spectrum.is_frequency_domain {
-        1.0
-    } else {
-        0.0
+/// NMRPipe description of `spectrum` for [`nmrpipe_io::writer`]
+fn pipe_spectrum(spectrum: &SpectrumData) -> PipeSpectrum {
+    let freq = spectrum.is_frequency_domain;
+    let axis = |ax: &AxisParams| {
+        // NMRPipe's SW and ORIG describe the stored points, also after EXT
+        let ax = ax.to_standalone();
+        PipeAxis {
+            sw_hz: ax.spectral_width_hz,
+            obs_mhz: ax.observe_freq_mhz,
+            // ORIG + SW is the first point, as the readers above expect
+            orig_hz: ax.reference_ppm * ax.observe_freq_mhz - ax.spectral_width_hz,
+            label: ax.nucleus.to_string(),
+            freq,
+        }
     };
-    header[idx::FDQUADFLAG] = if spectrum.imag.is_empty() {
-        1.0
-    } else {
-        0.0
+    let to_f32 = |rows: &[Vec<f64>]| -> Vec<Vec<f32>> {
+        rows.iter().map(|r| r.iter().map(|&v| v as f32).collect()).collect()
     };
-
+    let x = spectrum.axes.first().map(axis).unwrap_or(PipeAxis {
+        sw_hz: 0.0,
+        obs_mhz: 0.0,
+        orig_hz: 0.0,
+        label: String::new(),
+        freq,
+    });
     if spectrum.is_2d() {
-        let ny = spectrum.data_2d.len();
-        header[idx::FDSPECNUM] = ny as f32;
-        if let Some(ax) = spectrum.axes.get(1) {
-            header[idx::FDF1SW] = ax.spectral_width_hz as f32;
-            header[idx::FDF1OBS] = ax.observe_freq_mhz as f32;
-            header[idx::FDF1ORIG] = (ax.reference_ppm * ax.observe_freq_mhz) as f32;
+        // Readers fill missing imaginary rows with zeros: keep the file real then
+        let has_imag = spectrum.data_2d_imag.len() == spectrum.data_2d.len()
+            && spectrum.data_2d_imag.iter().flatten().any(|&v| v != 0.0);
+        let imag = if has_imag { to_f32(&spectrum.data_2d_imag) } else { Vec::new() };
+        PipeSpectrum {
+            x,
+            y: spectrum.axes.get(1).map(axis),
+            real: to_f32(&spectrum.data_2d),
+            y_imag: spectrum
+                .f1_quadrants
+                .as_ref()
+                .map(|q| (to_f32(&q.ir), if has_imag { to_f32(&q.ii) } else { Vec::new() })),
+            imag,
+            title: spectrum.sample_name.clone(),
+        }
+    } else {
+        let imag = if spectrum.imag.len() == spectrum.real.len() {
+            to_f32(std::slice::from_ref(&spectrum.imag))
+        } else {
+            Vec::new()
+        };
+        PipeSpectrum {
+            x,
+            y: None,
+            real: to_f32(std::slice::from_ref(&spectrum.real)),
+            imag,
+            y_imag: None,
+            title: spectrum.sample_name.clone(),
         }
     }
+}
 
-    // Write header
-    for &h in &header {
-        file.write_f32::<LittleEndian>(h)?;
+fn write_error(e: WriteError) -> io::Error {
+    match e {
+        WriteError::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidInput, e.to_string()),
     }
+}
 
-    // Write data
-    if spectrum.is_2d() {
-        for row in &spectrum.data_2d {
-            for &v in row {
-                file.write_f32::<LittleEndian>(v as f32)?;
-            }
-        }
-    } else if !spectrum.imag.is_empty() {
-        // Interleaved complex
-        for i in 0..spectrum.real.len() {
-            file.write_f32::<LittleEndian>(spectrum.real[i] as f32)?;
-            let im = spectrum.imag.get(i).copied().unwrap_or(0.0);
-            file.write_f32::<LittleEndian>(im as f32)?;
-        }
-    } else {
-        for &v in &spectrum.real {
-            file.write_f32::<LittleEndian>(v as f32)?;
-        }
-    }
+/// Write spectrum data to NMRPipe format: one file with the full header,
+/// real and imaginary parts, that NMRPipe and NMRDraw open directly
+pub fn write_nmrpipe_file(spectrum: &SpectrumData, path: &Path) -> io::Result<()> {
+    nmrpipe_io::writer::write_spectrum_file(path, &pipe_spectrum(spectrum)).map_err(write_error)
+}
 
-    Ok(())
+/// Write a 2D spectrum as a plane series named from `template`
+/// (`name%03d.ft2`), one row per file; returns the files written
+pub fn write_nmrpipe_series(spectrum: &SpectrumData, template: &Path) -> io::Result<Vec<std::path::PathBuf>> {
+    nmrpipe_io::writer::write_spectrum_series(template, &pipe_spectrum(spectrum)).map_err(write_error)
 }
 
 /// Read a 2D NMRPipe dataset stored as a series of plane files.
@@ -328,21 +372,7 @@ pub fn read_nmrpipe_2d_planes(plane_files: &[std::path::PathBuf]) -> io::Result<
         ));
     }
 
-    // Parse header
-    let mut header = vec![0.0f32; HEADER_FLOATS];
-    let mut cursor = Cursor::new(&first_data[..HEADER_BYTES]);
-    for h in header.iter_mut() {
-        *h = cursor.read_f32::<LittleEndian>()?;
-    }
-
-    let order_val = header[idx::FDFLTORDER];
-    let is_big_endian = (order_val - 2.345).abs() > 0.01;
-    if is_big_endian {
-        cursor.seek(SeekFrom::Start(0))?;
-        for h in header.iter_mut() {
-            *h = cursor.read_f32::<BigEndian>()?;
-        }
-    }
+    let (header, is_big_endian) = read_header(&first_data)?;
 
     let npts_x = header[idx::FDSIZE] as usize;
     let is_complex_x = header[idx::FDQUADFLAG] as i32 == 0;
@@ -380,6 +410,9 @@ pub fn read_nmrpipe_2d_planes(plane_files: &[std::path::PathBuf]) -> io::Result<
         experiment_type,
         dimensionality: super::spectrum::Dimensionality::TwoD,
         sample_name: filename,
+        solvent: String::new(),
+        array: None,
+        metadata: Default::default(),
         axes: vec![
             super::spectrum::AxisParams {
                 nucleus: nucleus_x,
@@ -388,6 +421,7 @@ pub fn read_nmrpipe_2d_planes(plane_files: &[std::path::PathBuf]) -> io::Result<
                 observe_freq_mhz: obs_x,
                 reference_ppm: ref_ppm_x,
                 label: if label_f2.is_empty() { "F2".to_string() } else { label_f2 },
+                ..Default::default()
             },
             super::spectrum::AxisParams {
                 nucleus: nucleus_y,
@@ -396,13 +430,20 @@ pub fn read_nmrpipe_2d_planes(plane_files: &[std::path::PathBuf]) -> io::Result<
                 observe_freq_mhz: obs_y,
                 reference_ppm: ref_ppm_y,
                 label: if label_f1.is_empty() { "F1".to_string() } else { label_f1 },
+                ..Default::default()
             },
         ],
         real: Vec::new(),
         imag: Vec::new(),
         data_2d: Vec::new(),
         data_2d_imag: Vec::new(),
+        f1_quadrants: None,
+        nus_schedule: None,
+        group_delay: None,
+        f1_acquisition: F1Acquisition::default(),
+        pulse_program: None,
         is_frequency_domain: is_freq_domain,
+        provenance: Provenance::on_load(is_freq_domain),
         nmrpipe_path: Some(plane_files[0].to_path_buf()),
         conversion_method_used: String::new(),
     };
@@ -450,6 +491,16 @@ pub fn read_nmrpipe_2d_planes(plane_files: &[std::path::PathBuf]) -> io::Result<
         // Store first row as 1D projection for status display / fallback
         spectrum.real = first_row.clone();
     }
+    if is_freq_domain {
+        if let Some(ax) = spectrum.axes.first_mut() {
+            *ax = with_extract(ax.clone(), header[idx::FDF2X1], header[idx::FDF2FTSIZE]);
+        }
+    }
+    if header[idx::FDF1FTFLAG] as i32 == 1 {
+        if let Some(ax) = spectrum.axes.get_mut(1) {
+            *ax = with_extract(ax.clone(), header[idx::FDF1X1], header[idx::FDF1FTSIZE]);
+        }
+    }
 
     log::info!(
         "Read 2D NMRPipe series: {} planes × {} points",
@@ -478,20 +529,113 @@ fn decode_label(header: &[f32], start_idx: usize) -> String {
 
 /// Map a label like "1H" or "13C" to a Nucleus enum
 fn nucleus_from_label(label: &str) -> super::spectrum::Nucleus {
-    let upper = label.to_uppercase();
-    if upper.contains("1H") || upper == "H1" || upper == "H" {
-        super::spectrum::Nucleus::H1
-    } else if upper.contains("13C") || upper == "C13" || upper == "C" {
-        super::spectrum::Nucleus::C13
-    } else if upper.contains("15N") || upper == "N15" || upper == "N" {
-        super::spectrum::Nucleus::N15
-    } else if upper.contains("19F") || upper == "F19" {
-        super::spectrum::Nucleus::F19
-    } else if upper.contains("31P") || upper == "P31" {
-        super::spectrum::Nucleus::P31
-    } else if label.is_empty() {
-        super::spectrum::Nucleus::H1
-    } else {
-        super::spectrum::Nucleus::Other(label.to_string())
+    if label.is_empty() {
+        return super::spectrum::Nucleus::H1;
+    }
+    super::nuclide::parse_label(label).unwrap_or_else(|| super::spectrum::Nucleus::Other(label.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nmrpipe_export_reads_back() {
+        let dir = std::env::temp_dir().join(format!("nmr_pipe_export_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let axis = |n: usize, reference_ppm: f64| AxisParams {
+            num_points: n,
+            spectral_width_hz: 4800.0,
+            observe_freq_mhz: 400.0,
+            reference_ppm,
+            ..Default::default()
+        };
+
+        // 1D: real and imaginary blocks, axis and domain survive
+        let spectrum = SpectrumData {
+            axes: vec![axis(8, 12.0)],
+            real: (0..8).map(|i| i as f64).collect(),
+            imag: (0..8).map(|i| -(i as f64) / 2.0).collect(),
+            is_frequency_domain: true,
+            ..Default::default()
+        };
+        let path = dir.join("one.ft1");
+        write_nmrpipe_file(&spectrum, &path).unwrap();
+        let back = read_nmrpipe_file(&path).unwrap();
+        assert_eq!(back.real, spectrum.real);
+        assert_eq!(back.imag, spectrum.imag);
+        assert!(back.is_frequency_domain);
+        assert!((back.axes[0].reference_ppm - 12.0).abs() < 1e-4);
+        assert!((back.axes[0].index_to_ppm(5) - spectrum.axes[0].index_to_ppm(5)).abs() < 1e-4);
+
+        // 2D as a plane series: one row per file, real rows in order
+        let rows: Vec<Vec<f64>> = (0..3).map(|r| (0..4).map(|c| (10 * r + c) as f64).collect()).collect();
+        let spectrum_2d = SpectrumData {
+            dimensionality: Dimensionality::TwoD,
+            axes: vec![axis(4, 10.0), axis(3, 150.0)],
+            data_2d: rows.clone(),
+            data_2d_imag: vec![vec![0.0; 4]; 3],
+            is_frequency_domain: true,
+            ..Default::default()
+        };
+        let planes = write_nmrpipe_series(&spectrum_2d, &dir.join("two%03d.ft2")).unwrap();
+        assert_eq!(planes.len(), 3);
+        let back = read_nmrpipe_2d_planes(&planes).unwrap();
+        assert_eq!(back.data_2d, rows);
+        assert!((back.axes[1].reference_ppm - 150.0).abs() < 1e-3);
+
+        // Hypercomplex data keeps all four quadrants: twice the vectors
+        let mut hyper = spectrum_2d.clone();
+        hyper.data_2d_imag = vec![vec![1.0; 4]; 3];
+        hyper.f1_quadrants = Some(F1Quadrants { ir: vec![vec![2.0; 4]; 3], ii: vec![vec![3.0; 4]; 3] });
+        let path = dir.join("hyper.ft2");
+        write_nmrpipe_file(&hyper, &path).unwrap();
+        let (header, _) = nmrpipe_io::reader::read_fdata_header(&mut std::fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(header.data[nmrpipe_core::fdata::FDSPECNUM], 6.0);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 2048 + 6 * 8 * 4);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_extracted_region_axis_through_nmrpipe() {
+        let axis = AxisParams { num_points: 1000, spectral_width_hz: 4000.0, reference_ppm: 12.0, ..Default::default() };
+        let s = SpectrumData {
+            real: (0..1000).map(|i| i as f64).collect(),
+            axes: vec![axis.clone()],
+            is_frequency_domain: true,
+            ..Default::default()
+        };
+        let region = s.region(5.5, 4.4).unwrap();
+        let ax = &region.axes[0];
+        assert!(ax.is_extracted());
+        assert_eq!((ax.full_points, ax.spectral_width_hz), (1000, 4000.0));
+        assert_eq!(ax.first_point, region.real[0] as usize);
+        assert!((ax.ppm_per_point() - axis.ppm_per_point()).abs() < 1e-12);
+        let alone = ax.to_standalone();
+        assert!(!alone.is_extracted());
+        assert!((alone.spectral_width_hz - 4.0 * ax.num_points as f64).abs() < 1e-9);
+        assert!((alone.index_to_ppm(7) - ax.index_to_ppm(7)).abs() < 1e-9);
+        assert!((ax.ppm_to_position(ax.index_to_ppm(7)).unwrap() - 7.0).abs() < 1e-9);
+
+        // Written with SW and ORIG of the region, as NMRPipe does after EXT
+        let path = std::env::temp_dir().join(format!("nmr_ext_{}.ft1", std::process::id()));
+        write_nmrpipe_file(&region, &path).unwrap();
+        let back = read_nmrpipe_file(&path).unwrap();
+        assert!(!back.axes[0].is_extracted());
+        assert!((back.axes[0].index_to_ppm(7) - ax.index_to_ppm(7)).abs() < 1e-4);
+
+        // With FDF2X1 and FDF2FTSIZE set the full axis comes back
+        let mut bytes = std::fs::read(&path).unwrap();
+        for (slot, value) in [(257, ax.first_point as f32 + 1.0), (96, 1000.0)] {
+            bytes[slot * 4..slot * 4 + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        std::fs::write(&path, bytes).unwrap();
+        let back = read_nmrpipe_file(&path).unwrap();
+        let bx = &back.axes[0];
+        assert_eq!((bx.first_point, bx.full_points, bx.num_points), (ax.first_point, 1000, ax.num_points));
+        assert!((bx.spectral_width_hz - 4000.0).abs() < 1e-2);
+        assert!((0..bx.num_points).all(|i| (bx.index_to_ppm(i) - ax.index_to_ppm(i)).abs() < 1e-4));
+        std::fs::remove_file(&path).unwrap();
     }
 }
@@ -0,0 +1,96 @@
+//! Nuclide table: magnetic properties of the supported nuclei.
+//!
+//! Frequencies follow the IUPAC unified scale: Ξ is the resonance frequency
+//! of a nucleus' reference compound as a percentage of the 1H frequency of
+//! TMS in the same magnet, so any observe frequency can be derived from the
+//! spectrometer's proton frequency.
+
+use super::spectrum::Nucleus;
+
+/// One entry of the nuclide table
+#[derive(Debug)]
+pub struct Nuclide {
+    pub nucleus: Nucleus,
+    /// Mass number, e.g. 13 for 13C
+    pub mass: u16,
+    /// Element symbol, e.g. "C"
+    pub symbol: &'static str,
+    /// Spin quantum number
+    pub spin: f64,
+    /// Gyromagnetic ratio in 10⁷ rad s⁻¹ T⁻¹
+    pub gamma: f64,
+    /// Ξ: reference frequency as a percentage of 1H (TMS)
+    pub xi_percent: f64,
+    /// Natural abundance in %
+    pub natural_abundance: f64,
+}
+
+/// IUPAC 2001 values (Harris et al., Pure Appl. Chem. 73, 1795)
+pub static NUCLIDES: &[Nuclide] = &[
+    Nuclide { nucleus: Nucleus::H1, mass: 1, symbol: "H", spin: 0.5, gamma: 26.7522128, xi_percent: 100.0, natural_abundance: 99.9885 },
+    Nuclide { nucleus: Nucleus::H2, mass: 2, symbol: "H", spin: 1.0, gamma: 4.10662791, xi_percent: 15.350609, natural_abundance: 0.0115 },
+    Nuclide { nucleus: Nucleus::C13, mass: 13, symbol: "C", spin: 0.5, gamma: 6.728284, xi_percent: 25.145020, natural_abundance: 1.07 },
+    Nuclide { nucleus: Nucleus::N15, mass: 15, symbol: "N", spin: 0.5, gamma: -2.71261804, xi_percent: 10.136767, natural_abundance: 0.368 },
+    Nuclide { nucleus: Nucleus::F19, mass: 19, symbol: "F", spin: 0.5, gamma: 25.18148, xi_percent: 94.094011, natural_abundance: 100.0 },
+    Nuclide { nucleus: Nucleus::P31, mass: 31, symbol: "P", spin: 0.5, gamma: 10.8394, xi_percent: 40.480742, natural_abundance: 100.0 },
+];
+
+impl Nuclide {
+    /// Observe frequency in MHz on a spectrometer with the given 1H frequency
+    pub fn frequency_mhz(&self, proton_mhz: f64) -> f64 {
+        proton_mhz * self.xi_percent / 100.0
+    }
+}
+
+/// Table entry for a nucleus (`None` for `Nucleus::Other`)
+pub fn nuclide(nucleus: &Nucleus) -> Option<&'static Nuclide> {
+    NUCLIDES.iter().find(|n| &n.nucleus == nucleus)
+}
+
+/// Parse a nucleus label as written by the vendors: "2H", "H2", "^2H",
+/// "D" for deuterium, or a bare symbol ("C") for the usual NMR isotope.
+pub fn parse_label(label: &str) -> Option<Nucleus> {
+    // First token only: "1H_F2" or "13C (dec)" name the nucleus up front
+    let upper = label.trim().to_uppercase().replace('^', "");
+    let cleaned = upper
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .find(|t| !t.is_empty())
+        .unwrap_or("");
+    if cleaned == "D" {
+        return Some(Nucleus::H2);
+    }
+
+    let digits: String = cleaned.chars().filter(|c| c.is_ascii_digit()).collect();
+    let letters: String = cleaned.chars().filter(|c| c.is_ascii_alphabetic()).collect();
+    // Mass number on one side of the symbol only ("13C" / "C13", not "1C3")
+    let (mass_first, mass_last) = (cleaned.starts_with(&digits), cleaned.ends_with(&digits));
+    if letters.is_empty() || (!digits.is_empty() && !mass_first && !mass_last) {
+        return None;
+    }
+
+    let mass: Option<u16> = if digits.is_empty() { None } else { Some(digits.parse().ok()?) };
+    NUCLIDES
+        .iter()
+        .find(|n| n.symbol.eq_ignore_ascii_case(&letters) && mass.is_none_or(|m| m == n.mass))
+        .map(|n| n.nucleus.clone())
+}
+
+/// `name` (a file or pulse-program name) has "2H" or "H2" as a word of its
+/// own, e.g. `sample_2H` but not `2HCl` or `H2O`
+pub fn names_deuterium(name: &str) -> bool {
+    name.to_uppercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|token| token == "2H" || token == "H2")
+}
+
+/// Observe frequency for `nucleus` on a spectrometer with the given 1H
+/// frequency; `proton_mhz` itself when the nucleus is not in the table.
+pub fn observe_frequency(nucleus: &Nucleus, proton_mhz: f64) -> f64 {
+    nuclide(nucleus).map_or(proton_mhz, |n| n.frequency_mhz(proton_mhz))
+}
+
+/// Frequency of `to` in the magnet where `from` resonates at `mhz`.
+pub fn convert_frequency(from: &Nucleus, mhz: f64, to: &Nucleus) -> Option<f64> {
+    let (from, to) = (nuclide(from)?, nuclide(to)?);
+    Some(mhz * to.xi_percent / from.xi_percent)
+}
@@ -0,0 +1,243 @@
+//! Pulse program of an experiment, to check how the data were acquired.
+//!
+//! Bruker experiments keep the pulse program as it was run in the
+//! `pulseprogram` file next to `acqus`; JEOL Delta files carry the sequence
+//! name and its full parameter list in the parameter section.  Either is
+//! shown read-only in the "Pulse Program" tab, with the relaxation delay,
+//! the 90° pulse and the number of scans picked out.
+
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use delta2pipe::header::{
+    param_float_val, DeltaParam, JVal, JEOL_SIUNIT_CELSIUS, JEOL_SIUNIT_HZ, JEOL_SIUNIT_NONE, JEOL_SIUNIT_PPM,
+    JEOL_SIUNIT_SECONDS,
+};
+
+use super::bruker;
+use super::spectrum::VendorFormat;
+
+/// An acquisition parameter picked out in the pulse program viewer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyParameter {
+    /// Bruker-style label, e.g. "D1"
+    pub label: String,
+    /// What the parameter is, e.g. "relaxation delay"
+    pub meaning: String,
+    /// Name as it appears in the text, e.g. "d1" or "relaxation_delay"
+    pub token: String,
+    /// Value with its unit, if the parameters were read
+    pub value: Option<String>,
+}
+
+/// Pulse program text of a dataset, with its key parameters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PulseProgram {
+    /// Sequence name (Bruker PULPROG, JEOL `experiment`)
+    pub name: String,
+    /// File the text was read from
+    pub source: PathBuf,
+    /// Listing as stored with the data
+    pub text: String,
+    pub key_parameters: Vec<KeyParameter>,
+}
+
+impl PulseProgram {
+    /// Byte ranges of the key parameter names in `text`, with the index of
+    /// the parameter each one names.  Matches are whole words, ignoring
+    /// case, so `d1` matches `D1` but not `d10` or `pd1`.
+    pub fn highlights(&self) -> Vec<(Range<usize>, usize)> {
+        let lower = self.text.to_ascii_lowercase();
+        let is_word = |c: u8| c.is_ascii_alphanumeric() || c == b'_';
+        let mut spans = Vec::new();
+        for (index, key) in self.key_parameters.iter().enumerate() {
+            let token = key.token.to_ascii_lowercase();
+            if token.is_empty() {
+                continue;
+            }
+            for (start, _) in lower.match_indices(&token) {
+                let end = start + token.len();
+                let before = start.checked_sub(1).map(|i| lower.as_bytes()[i]);
+                let after = lower.as_bytes().get(end).copied();
+                if !before.is_some_and(is_word) && !after.is_some_and(is_word) {
+                    spans.push((start..end, index));
+                }
+            }
+        }
+        spans.sort_by_key(|(range, _)| range.start);
+        spans
+    }
+}
+
+/// Pulse program of the dataset at `path`, for the formats that store one
+pub fn read_pulse_program(path: &Path, format: &VendorFormat) -> Option<PulseProgram> {
+    match format {
+        VendorFormat::Bruker => read_bruker(path),
+        VendorFormat::Jeol => read_jeol(path),
+        _ => None,
+    }
+}
+
+/// Bruker `pulseprogram` of the experiment folder at or above `path`
+/// (the dataset may be opened from `pdata/1` or through its `fid` file)
+pub fn read_bruker(path: &Path) -> Option<PulseProgram> {
+    let dir = path.ancestors().take(4).find(|d| d.join("pulseprogram").is_file())?;
+    let source = dir.join("pulseprogram");
+    let text = fs::read_to_string(&source)
+        .or_else(|_| fs::read(&source).map(|b| String::from_utf8_lossy(&b).into_owned()))
+        .ok()?;
+    let acqus = fs::read_to_string(dir.join("acqus")).map(|c| bruker::parse_acqus(&c)).unwrap_or_default();
+    let array = |key: &str, index: usize| -> Option<f64> {
+        // Arrays read as "(0..63) v0 v1 …"
+        let value = acqus.get(key)?;
+        let list = value.split_once(')').map_or(value.as_str(), |(_, rest)| rest);
+        list.split_whitespace().nth(index)?.parse().ok()
+    };
+    let name = acqus
+        .get("PULPROG")
+        .map(|v| v.trim_matches(|c| c == '<' || c == '>').to_string())
+        .unwrap_or_else(|| "pulseprogram".to_string());
+    let key_parameters = vec![
+        KeyParameter {
+            label: "D1".into(),
+            meaning: "relaxation delay".into(),
+            token: "d1".into(),
+            value: array("D", 1).map(format_seconds),
+        },
+        KeyParameter {
+            label: "P1".into(),
+            meaning: "90° pulse".into(),
+            token: "p1".into(),
+            value: array("P", 1).map(|us| format_seconds(us * 1e-6)),
+        },
+        KeyParameter {
+            label: "NS".into(),
+            meaning: "number of scans".into(),
+            token: "ns".into(),
+            value: acqus.get("NS").cloned(),
+        },
+    ];
+    Some(PulseProgram { name, source, text, key_parameters })
+}
+
+/// Sequence name and parameter list of a JEOL Delta file
+pub fn read_jeol(path: &Path) -> Option<PulseProgram> {
+    let mut file = fs::File::open(path).ok()?;
+    let (header, params) = match delta2pipe::read_delta_params(&mut file) {
+        Ok(read) => read,
+        Err(e) => {
+            log::warn!("Cannot read the JEOL parameters of {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    if params.is_empty() {
+        return None;
+    }
+    let find = |name: &str| params.iter().find(|p| p.name.eq_ignore_ascii_case(name));
+    let name = find("experiment")
+        .map(jeol_value)
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| header.title.clone());
+    let mut text = format!("; {}\n", name);
+    if !header.title.is_empty() {
+        text.push_str(&format!("; title: {}\n", header.title));
+    }
+    for param in params.iter().filter(|p| !p.name.is_empty()) {
+        text.push_str(&format!("{} = {}\n", param.name.to_ascii_lowercase(), jeol_value(param)));
+    }
+    let key = |label: &str, meaning: &str, token: &str| KeyParameter {
+        label: label.into(),
+        meaning: meaning.into(),
+        token: token.into(),
+        value: find(token).map(jeol_value),
+    };
+    let key_parameters = vec![
+        key("D1", "relaxation delay", "relaxation_delay"),
+        key("P1", "90° pulse", "x_pulse"),
+        key("NS", "number of scans", "scans"),
+    ];
+    Some(PulseProgram { name, source: path.to_path_buf(), text, key_parameters })
+}
+
+/// Value of a JEOL parameter with its unit
+fn jeol_value(param: &DeltaParam) -> String {
+    match &param.val {
+        JVal::Str(s) => s.clone(),
+        JVal::Int(i) if param.units[0].unit_type == JEOL_SIUNIT_NONE => i.to_string(),
+        JVal::Int(_) | JVal::Float(_) => {
+            let v = param_float_val(param);
+            match param.units[0].unit_type {
+                JEOL_SIUNIT_SECONDS => format_seconds(v),
+                JEOL_SIUNIT_HZ => format!("{} Hz", trim_number(v)),
+                JEOL_SIUNIT_PPM => format!("{} ppm", trim_number(v)),
+                JEOL_SIUNIT_CELSIUS => format!("{} °C", trim_number(v)),
+                _ => trim_number(v),
+            }
+        }
+        JVal::Complex(re, im) => format!("{} + {}i", trim_number(*re), trim_number(*im)),
+        JVal::Inf(i) => i.to_string(),
+        JVal::None => String::new(),
+    }
+}
+
+/// A duration in the unit that reads best: "2 s", "30 ms", "9.5 µs"
+pub fn format_seconds(seconds: f64) -> String {
+    let (value, unit) = match seconds.abs() {
+        s if s >= 1.0 || s == 0.0 => (seconds, "s"),
+        s if s >= 1e-3 => (seconds * 1e3, "ms"),
+        _ => (seconds * 1e6, "µs"),
+    };
+    format!("{} {}", trim_number(value), unit)
+}
+
+/// A number to four decimals without trailing zeros
+fn trim_number(v: f64) -> String {
+    let s = format!("{:.4}", v);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" { "0".to_string() } else { s.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bruker_pulse_program_with_key_parameters() {
+        let root = std::env::temp_dir().join(format!("nmr_pulprog_{}", std::process::id()));
+        let expno = root.join("sample").join("1");
+        fs::create_dir_all(expno.join("pdata").join("1")).unwrap();
+        fs::write(
+            expno.join("acqus"),
+            "##$PULPROG= <zg30>\n##$NS= 16\n##$D= (0..63)\n0 2 0.03 0\n##$P= (0..63)\n0 9.5 19 0\n##END=\n",
+        )
+        .unwrap();
+        fs::write(
+            expno.join("pulseprogram"),
+            ";zg30\n;d1 : relaxation delay; 1-5 * T1\n1 ze\n2 30m\n  d1\n  p1*0.33 ph1\n  go=2 ph31\n  d10 pd1\n",
+        )
+        .unwrap();
+
+        // Found from the processed data folder too
+        let program = read_pulse_program(&expno.join("pdata").join("1"), &VendorFormat::Bruker).unwrap();
+        assert_eq!(program.name, "zg30");
+        let values: Vec<_> = program.key_parameters.iter().map(|k| k.value.clone().unwrap()).collect();
+        assert_eq!(values, ["2 s", "9.5 µs", "16"]);
+
+        // d1 and p1 as whole words only: not d10 or pd1
+        let words: Vec<&str> = program.highlights().iter().map(|(r, _)| &program.text[r.clone()]).collect();
+        assert_eq!(words, ["d1", "d1", "p1"]);
+
+        assert!(read_pulse_program(&root, &VendorFormat::Varian).is_none());
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_format_seconds() {
+        assert_eq!(format_seconds(2.0), "2 s");
+        assert_eq!(format_seconds(0.03), "30 ms");
+        assert_eq!(format_seconds(9.5e-6), "9.5 µs");
+    }
+}
@@ -0,0 +1,209 @@
+//! Several spectra open at once, e.g. a titration series
+//!
+//! One spectrum is active: it is the one processed, annotated and exported.
+//! The others are overlaid on it in the 1D view, each with its own color,
+//! scale and vertical offset.  The per-document state of an inactive
+//! spectrum (undo history, log, annotations) is parked in its entry as `D`
+//! and handed back when it becomes active again.
+
+use crate::data::spectrum::SpectrumData;
+
+/// A spectrum of the session, with its overlay display settings
+#[derive(Debug, Clone)]
+pub struct SessionSpectrum<D> {
+    pub spectrum: SpectrumData,
+    pub visible: bool,
+    pub color: [u8; 3],
+    /// Multiplies the intensities when overlaid
+    pub scale: f64,
+    /// Vertical shift when overlaid, as a fraction of the active spectrum's
+    /// tallest point
+    pub offset: f64,
+    /// Document state kept while another spectrum is active
+    pub parked: Option<D>,
+}
+
+impl<D> SessionSpectrum<D> {
+    pub fn new(spectrum: SpectrumData, color: [u8; 3]) -> Self {
+        Self { spectrum, visible: true, color, scale: 1.0, offset: 0.0, parked: None }
+    }
+
+    /// Name shown in the session list and the plot legend
+    pub fn name(&self) -> String {
+        let file = self.spectrum.source_path.file_name().map(|f| f.to_string_lossy().to_string());
+        match (self.spectrum.sample_name.is_empty(), file) {
+            (false, _) => self.spectrum.sample_name.clone(),
+            (true, Some(file)) => file,
+            (true, None) => "Untitled".to_string(),
+        }
+    }
+
+    /// Whether the spectrum can be drawn over a 1D spectrum
+    pub fn is_overlayable(&self) -> bool {
+        self.spectrum.is_frequency_domain && !self.spectrum.is_2d() && !self.spectrum.real.is_empty()
+    }
+}
+
+/// The open spectra and which one is active
+#[derive(Debug, Clone)]
+pub struct Session<D> {
+    pub entries: Vec<SessionSpectrum<D>>,
+    active: usize,
+}
+
+impl<D> Default for Session<D> {
+    fn default() -> Self {
+        Self { entries: Vec::new(), active: 0 }
+    }
+}
+
+impl<D> Session<D> {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    /// The spectrum being processed
+    pub fn active(&self) -> Option<&SpectrumData> {
+        self.entries.get(self.active).map(|e| &e.spectrum)
+    }
+
+    pub fn active_mut(&mut self) -> Option<&mut SpectrumData> {
+        self.entries.get_mut(self.active).map(|e| &mut e.spectrum)
+    }
+
+    /// Put `spectrum` in place of the active one (opening a file), keeping
+    /// its display settings; an empty session starts with it
+    pub fn replace_active(&mut self, spectrum: SpectrumData, color: [u8; 3]) {
+        match self.entries.get_mut(self.active) {
+            Some(entry) => {
+                entry.spectrum = spectrum;
+                entry.parked = None;
+            }
+            None => {
+                self.entries.push(SessionSpectrum::new(spectrum, color));
+                self.active = self.entries.len() - 1;
+            }
+        }
+    }
+
+    /// Add `spectrum` and make it active, parking `current` in the entry
+    /// that was active
+    pub fn push(&mut self, spectrum: SpectrumData, color: [u8; 3], current: D) {
+        if let Some(entry) = self.entries.get_mut(self.active) {
+            entry.parked = Some(current);
+        }
+        self.entries.push(SessionSpectrum::new(spectrum, color));
+        self.active = self.entries.len() - 1;
+    }
+
+    /// Make `index` active, parking `current` in the entry that was active.
+    /// Returns the state parked in the new active entry (`None` when it was
+    /// never active, or `index` is out of range and nothing changed).
+    pub fn activate(&mut self, index: usize, current: D) -> Option<D> {
+        if index >= self.entries.len() || index == self.active {
+            return None;
+        }
+        if let Some(entry) = self.entries.get_mut(self.active) {
+            entry.parked = Some(current);
+        }
+        self.active = index;
+        self.entries[index].parked.take()
+    }
+
+    /// Close an inactive spectrum; the active one cannot be removed
+    pub fn remove(&mut self, index: usize) -> Option<SessionSpectrum<D>> {
+        if index >= self.entries.len() || index == self.active {
+            return None;
+        }
+        let removed = self.entries.remove(index);
+        if index < self.active {
+            self.active -= 1;
+        }
+        Some(removed)
+    }
+
+    /// Visible 1D spectra drawn over the active one, with their index
+    pub fn overlays(&self) -> impl Iterator<Item = (usize, &SessionSpectrum<D>)> {
+        let active = self.active;
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(move |(i, e)| *i != active && e.visible && e.is_overlayable())
+    }
+
+    /// Offset the spectra in list order, `step` apart, with the active one
+    /// staying on the baseline
+    pub fn stack(&mut self, step: f64) {
+        let active = self.active as f64;
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            entry.offset = (i as f64 - active) * step;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spectrum(name: &str) -> SpectrumData {
+        SpectrumData {
+            sample_name: name.to_string(),
+            real: vec![0.0; 8],
+            is_frequency_domain: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_session_parks_the_state_of_inactive_spectra() {
+        let mut session: Session<&str> = Session::default();
+        assert!(session.active().is_none());
+        session.replace_active(spectrum("a"), [0, 0, 0]);
+        session.push(spectrum("b"), [1, 1, 1], "state of a");
+        session.push(spectrum("c"), [2, 2, 2], "state of b");
+        assert_eq!(session.active().unwrap().sample_name, "c");
+
+        // Switching back hands over the parked state, and parks the current
+        assert_eq!(session.activate(0, "state of c"), Some("state of a"));
+        assert_eq!(session.active().unwrap().sample_name, "a");
+        assert_eq!(session.entries[2].parked, Some("state of c"));
+        assert_eq!(session.activate(0, "ignored"), None);
+
+        let overlays: Vec<usize> = session.overlays().map(|(i, _)| i).collect();
+        assert_eq!(overlays, vec![1, 2]);
+        session.entries[1].visible = false;
+        assert_eq!(session.overlays().count(), 1);
+
+        // Opening a file replaces the active spectrum, not the others
+        session.replace_active(spectrum("d"), [3, 3, 3]);
+        assert_eq!(session.len(), 3);
+        assert_eq!(session.entries[0].name(), "d");
+        assert_eq!(session.entries[0].color, [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_session_remove_and_stack_keep_the_active_spectrum() {
+        let mut session: Session<()> = Session::default();
+        session.replace_active(spectrum("a"), [0, 0, 0]);
+        session.push(spectrum("b"), [0, 0, 0], ());
+        session.push(spectrum("c"), [0, 0, 0], ());
+        session.activate(1, ());
+
+        assert!(session.remove(1).is_none());
+        assert_eq!(session.remove(0).unwrap().spectrum.sample_name, "a");
+        assert_eq!(session.active_index(), 0);
+        assert_eq!(session.active().unwrap().sample_name, "b");
+
+        session.stack(0.5);
+        let offsets: Vec<f64> = session.entries.iter().map(|e| e.offset).collect();
+        assert_eq!(offsets, vec![0.0, 0.5]);
+    }
+}
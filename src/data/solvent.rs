@@ -0,0 +1,82 @@
+//! Deuterated NMR solvents and their residual signals.
+//!
+//! Shifts are those of Fulmer et al., Organometallics 29, 2176 (2010),
+//! relative to TMS: the residual protio signal for 1H (the CHD2 quintet
+//! centre where there is one) and the solvent's own carbon for 13C.
+
+use super::spectrum::Nucleus;
+
+/// One entry of the solvent table
+#[derive(Debug)]
+pub struct Solvent {
+    /// Display name, e.g. "CDCl3"
+    pub name: &'static str,
+    /// Spellings used by the spectrometer software, lowercase and without
+    /// punctuation: Bruker "DMSO", JEOL "DMSO-D6", VnmrJ "dmso"
+    aliases: &'static [&'static str],
+    /// Residual 1H signal (ppm)
+    pub h1_ppm: Option<f64>,
+    /// 13C signal (ppm); `None` for solvents without carbon
+    pub c13_ppm: Option<f64>,
+}
+
+pub static SOLVENTS: &[Solvent] = &[
+    Solvent { name: "CDCl3", aliases: &["cdcl3", "chloroformd", "chloroformd1", "chcl3"], h1_ppm: Some(7.26), c13_ppm: Some(77.16) },
+    Solvent { name: "DMSO-d6", aliases: &["dmso", "dmsod6", "dmsod", "cd3socd3"], h1_ppm: Some(2.50), c13_ppm: Some(39.52) },
+    Solvent { name: "D2O", aliases: &["d2o", "h2od2o", "h2o+d2o", "waterd2"], h1_ppm: Some(4.79), c13_ppm: None },
+    Solvent { name: "CD3OD", aliases: &["cd3od", "meod", "methanold4", "methanold"], h1_ppm: Some(3.31), c13_ppm: Some(49.00) },
+    Solvent { name: "Acetone-d6", aliases: &["acetone", "acetoned6", "acetoned", "cd3cocd3"], h1_ppm: Some(2.05), c13_ppm: Some(29.84) },
+    Solvent { name: "C6D6", aliases: &["c6d6", "benzene", "benzened6", "benzened"], h1_ppm: Some(7.16), c13_ppm: Some(128.06) },
+    Solvent { name: "CD3CN", aliases: &["cd3cn", "acetonitrile", "acetonitriled3", "acn"], h1_ppm: Some(1.94), c13_ppm: Some(1.32) },
+    Solvent { name: "CD2Cl2", aliases: &["cd2cl2", "dichloromethaned2", "methylenechlorided2"], h1_ppm: Some(5.32), c13_ppm: Some(53.84) },
+    Solvent { name: "THF-d8", aliases: &["thf", "thfd8", "tetrahydrofurand8"], h1_ppm: Some(1.72), c13_ppm: Some(25.31) },
+    Solvent { name: "Toluene-d8", aliases: &["tol", "toluene", "toluened8", "c7d8"], h1_ppm: Some(2.08), c13_ppm: Some(20.43) },
+    Solvent { name: "Pyridine-d5", aliases: &["pyr", "pyridine", "pyridined5", "c5d5n"], h1_ppm: Some(8.74), c13_ppm: Some(149.84) },
+];
+
+impl Solvent {
+    /// Residual signal for the observed nucleus (2H uses the 1H value)
+    pub fn residual_ppm(&self, nucleus: &Nucleus) -> Option<f64> {
+        match nucleus {
+            Nucleus::H1 | Nucleus::H2 => self.h1_ppm,
+            Nucleus::C13 => self.c13_ppm,
+            _ => None,
+        }
+    }
+}
+
+/// Table entry for a solvent name as written in the acquisition
+/// parameters; case, hyphens and spaces are ignored
+pub fn identify(name: &str) -> Option<&'static Solvent> {
+    let key: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '+')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if key.is_empty() {
+        return None;
+    }
+    SOLVENTS.iter().find(|s| s.aliases.contains(&key.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identify_vendor_spellings() {
+        assert_eq!(identify("CDCl3").unwrap().name, "CDCl3");
+        assert_eq!(identify("CHLOROFORM-D").unwrap().name, "CDCl3");
+        assert_eq!(identify("DMSO").unwrap().name, "DMSO-d6");
+        assert_eq!(identify("DMSO-D6").unwrap().name, "DMSO-d6");
+        assert_eq!(identify("MeOD").unwrap().name, "CD3OD");
+        assert_eq!(identify(" Acetone ").unwrap().name, "Acetone-d6");
+        assert!(identify("").is_none());
+        assert!(identify("unknown").is_none());
+
+        let d2o = identify("D2O").unwrap();
+        assert_eq!(d2o.residual_ppm(&Nucleus::H1), Some(4.79));
+        assert_eq!(d2o.residual_ppm(&Nucleus::C13), None);
+        assert_eq!(d2o.residual_ppm(&Nucleus::P31), None);
+    }
+}
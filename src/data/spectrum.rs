@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
+use super::pulse_program::PulseProgram;
+
 /// Supported vendor formats for NMR data
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum VendorFormat {
@@ -29,6 +32,8 @@ impl std::fmt::Display for VendorFormat {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Nucleus {
     H1,
+    /// Deuterium (lock-channel or 2H-labelled samples)
+    H2,
     C13,
     N15,
     F19,
@@ -40,6 +45,7 @@ impl std::fmt::Display for Nucleus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Nucleus::H1 => write!(f, "1H"),
+            Nucleus::H2 => write!(f, "2H"),
             Nucleus::C13 => write!(f, "13C"),
             Nucleus::N15 => write!(f, "15N"),
             Nucleus::F19 => write!(f, "19F"),
@@ -49,6 +55,91 @@ impl std::fmt::Display for Nucleus {
     }
 }
 
+impl Nucleus {
+    /// Typical chemical-shift window (low, high ppm) used as the default
+    /// display and export range: the nucleus' first preset
+    pub fn typical_ppm_range(&self) -> Option<(f64, f64)> {
+        self.ppm_presets().next().map(|p| p.range)
+    }
+
+    /// Display/export range presets for this nucleus, default first
+    pub fn ppm_presets(&self) -> impl Iterator<Item = &'static PpmPreset> + '_ {
+        PPM_PRESETS.iter().filter(move |p| {
+            // 2H shifts match 1H to within the (ppb-scale) isotope effect
+            &p.nucleus == self || (*self == Nucleus::H2 && p.nucleus == Nucleus::H1)
+        })
+    }
+}
+
+/// A named chemical-shift window for one nucleus
+#[derive(Debug)]
+pub struct PpmPreset {
+    pub nucleus: Nucleus,
+    pub name: &'static str,
+    /// (low, high) ppm
+    pub range: (f64, f64),
+}
+
+/// Range presets; the first entry per nucleus is its default window
+pub static PPM_PRESETS: &[PpmPreset] = &[
+    PpmPreset { nucleus: Nucleus::H1, name: "Standard", range: (-1.0, 14.0) },
+    PpmPreset { nucleus: Nucleus::H1, name: "Aliphatic", range: (-0.5, 5.0) },
+    PpmPreset { nucleus: Nucleus::H1, name: "Aromatic", range: (6.0, 9.0) },
+    PpmPreset { nucleus: Nucleus::C13, name: "Standard", range: (-10.0, 230.0) },
+    PpmPreset { nucleus: Nucleus::C13, name: "Aliphatic", range: (-5.0, 90.0) },
+    PpmPreset { nucleus: Nucleus::C13, name: "Aromatic / carbonyl", range: (100.0, 220.0) },
+    PpmPreset { nucleus: Nucleus::N15, name: "Standard", range: (0.0, 350.0) },
+    // Organofluorine shifts are almost all negative (CF3 ≈ -60, ArF ≈ -110, CF ≈ -200)
+    PpmPreset { nucleus: Nucleus::F19, name: "Organofluorine", range: (-250.0, -50.0) },
+    PpmPreset { nucleus: Nucleus::F19, name: "CF3 / CF2", range: (-130.0, -40.0) },
+    PpmPreset { nucleus: Nucleus::F19, name: "Aryl F", range: (-180.0, -90.0) },
+    PpmPreset { nucleus: Nucleus::F19, name: "Full (incl. F⁻, BF4⁻, SF)", range: (-300.0, 100.0) },
+    // Phosphines down to ≈ -60, phosphates ≈ 0, phosphine oxides/phosphonium up to ≈ +60
+    PpmPreset { nucleus: Nucleus::P31, name: "Standard", range: (-60.0, 160.0) },
+    PpmPreset { nucleus: Nucleus::P31, name: "Phosphates", range: (-30.0, 30.0) },
+    PpmPreset { nucleus: Nucleus::P31, name: "Phosphines", range: (-80.0, 40.0) },
+    PpmPreset { nucleus: Nucleus::P31, name: "Full", range: (-250.0, 250.0) },
+];
+
+/// A functional-group chemical-shift region, shaded behind the spectrum
+/// as a reading aid
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShiftRegion {
+    pub nucleus: Nucleus,
+    pub name: String,
+    /// (low, high) ppm
+    pub range: (f64, f64),
+}
+
+impl ShiftRegion {
+    pub fn new(nucleus: Nucleus, name: &str, range: (f64, f64)) -> Self {
+        Self { nucleus, name: name.to_string(), range }
+    }
+
+    /// Whether the region is drawn on spectra of `nucleus` (2H uses the 1H
+    /// regions, as for the ppm presets)
+    pub fn applies_to(&self, nucleus: &Nucleus) -> bool {
+        &self.nucleus == nucleus || (*nucleus == Nucleus::H2 && self.nucleus == Nucleus::H1)
+    }
+
+    /// Textbook 1H and 13C regions of the common functional groups
+    pub fn standard() -> Vec<ShiftRegion> {
+        vec![
+            ShiftRegion::new(Nucleus::H1, "Aliphatic", (0.0, 2.0)),
+            ShiftRegion::new(Nucleus::H1, "Allylic / α-C=O", (2.0, 3.0)),
+            ShiftRegion::new(Nucleus::H1, "O–CH / N–CH", (3.0, 4.5)),
+            ShiftRegion::new(Nucleus::H1, "Olefinic", (4.5, 6.5)),
+            ShiftRegion::new(Nucleus::H1, "Aromatic", (6.5, 8.5)),
+            ShiftRegion::new(Nucleus::H1, "Aldehyde", (9.0, 10.5)),
+            ShiftRegion::new(Nucleus::H1, "Carboxylic acid", (10.5, 13.0)),
+            ShiftRegion::new(Nucleus::C13, "Aliphatic", (0.0, 50.0)),
+            ShiftRegion::new(Nucleus::C13, "C–O / C–N", (50.0, 90.0)),
+            ShiftRegion::new(Nucleus::C13, "Olefinic / aromatic", (100.0, 160.0)),
+            ShiftRegion::new(Nucleus::C13, "Carbonyl", (160.0, 220.0)),
+        ]
+    }
+}
+
 /// Experiment dimensionality
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Dimensionality {
@@ -56,10 +147,33 @@ pub enum Dimensionality {
     TwoD,
 }
 
+/// Where the data came from: an FID from the spectrometer, or a spectrum
+/// that was already processed (vendor `pdata`, JCAMP-DX spectra, NMRPipe
+/// `.ft` files) before it reached the app
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Provenance {
+    #[default]
+    Raw,
+    VendorProcessed,
+}
+
+impl Provenance {
+    /// Provenance of data as read from disk: a spectrum that is already in
+    /// the frequency domain was processed elsewhere
+    pub fn on_load(is_frequency_domain: bool) -> Self {
+        if is_frequency_domain {
+            Provenance::VendorProcessed
+        } else {
+            Provenance::Raw
+        }
+    }
+}
+
 /// Experiment type detected from filename/metadata
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ExperimentType {
     Proton,
+    Deuterium,
     Carbon,
     Dept135,
     Cosy,
@@ -72,6 +186,7 @@ impl std::fmt::Display for ExperimentType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ExperimentType::Proton => write!(f, "1H"),
+            ExperimentType::Deuterium => write!(f, "2H"),
             ExperimentType::Carbon => write!(f, "13C"),
             ExperimentType::Dept135 => write!(f, "DEPT-135"),
             ExperimentType::Cosy => write!(f, "COSY"),
@@ -91,6 +206,15 @@ pub struct AxisParams {
     pub observe_freq_mhz: f64,
     pub reference_ppm: f64,
     pub label: String,
+    /// Index of the first stored point on the axis that the spectral width
+    /// and reference describe, for an extracted region
+    #[serde(default)]
+    pub first_point: usize,
+    /// Points the spectral width spans when the data is a region extracted
+    /// from a larger spectrum (NMRPipe EXT, Bruker STSR/STSI); 0 when it
+    /// spans the stored points
+    #[serde(default)]
+    pub full_points: usize,
 }
 
 impl Default for AxisParams {
@@ -102,29 +226,256 @@ impl Default for AxisParams {
             observe_freq_mhz: 400.0,
             reference_ppm: 0.0,
             label: String::new(),
+            first_point: 0,
+            full_points: 0,
         }
     }
 }
 
 impl AxisParams {
+    /// Points the spectral width spans
+    fn span_points(&self) -> usize {
+        if self.full_points > 0 { self.full_points } else { self.num_points }
+    }
+
+    /// Whether the points are a region of a larger spectrum
+    pub fn is_extracted(&self) -> bool {
+        self.full_points > 0
+    }
+
+    /// Spacing of the points in Hz
+    pub fn hz_per_point(&self) -> f64 {
+        match self.span_points() {
+            0 => 0.0,
+            n => self.spectral_width_hz / n as f64,
+        }
+    }
+
+    /// Spacing of the points in ppm; 0 for a degenerate axis
+    pub fn ppm_per_point(&self) -> f64 {
+        if self.observe_freq_mhz == 0.0 {
+            return 0.0;
+        }
+        self.hz_per_point() / self.observe_freq_mhz
+    }
+
     /// Convert a point index to ppm
     pub fn index_to_ppm(&self, index: usize) -> f64 {
         if self.num_points == 0 || self.observe_freq_mhz == 0.0 {
             return 0.0;
         }
+        // NMRPipe convention: reference_ppm is the ppm of the first point of
+        // the full axis; the spectrum runs down from it by SW over its
+        // points, of which an extracted region starts at `first_point`.
         let sw_ppm = self.spectral_width_hz / self.observe_freq_mhz;
-        let frac = index as f64 / self.num_points as f64;
-        // NMRPipe convention: reference_ppm is the ppm of the first point (index 0).
-        // Spectrum runs from reference_ppm down to (reference_ppm - sw_ppm).
+        let frac = (self.first_point + index) as f64 / self.span_points() as f64;
         self.reference_ppm - frac * sw_ppm
     }
 
+    /// Fractional point position of a ppm value (inverse of
+    /// [`Self::index_to_ppm`]); `None` for a degenerate axis or NaN input
+    pub fn ppm_to_position(&self, ppm: f64) -> Option<f64> {
+        if self.num_points == 0 || self.observe_freq_mhz == 0.0 || self.spectral_width_hz == 0.0 {
+            return None;
+        }
+        let sw_ppm = self.spectral_width_hz / self.observe_freq_mhz;
+        let pos = (self.reference_ppm - ppm) / sw_ppm * self.span_points() as f64 - self.first_point as f64;
+        pos.is_finite().then_some(pos)
+    }
+
+    /// The stored points as an axis of their own: spectral width and
+    /// reference of just these points, as written to files that have no
+    /// place for an extracted region
+    pub fn to_standalone(&self) -> AxisParams {
+        AxisParams {
+            spectral_width_hz: self.hz_per_point() * self.num_points as f64,
+            reference_ppm: self.index_to_ppm(0),
+            first_point: 0,
+            full_points: 0,
+            ..self.clone()
+        }
+    }
+
     /// Generate a ppm scale array
     pub fn ppm_scale(&self) -> Vec<f64> {
         (0..self.num_points)
             .map(|i| self.index_to_ppm(i))
             .collect()
     }
+
+    /// Time between successive (complex) FID points, 1 / SW, in seconds;
+    /// `None` when the spectral width is unknown
+    pub fn dwell_time(&self) -> Option<f64> {
+        (self.spectral_width_hz > 0.0).then(|| 1.0 / self.spectral_width_hz)
+    }
+
+    /// Total acquisition time of the axis (points × dwell time) in seconds
+    pub fn acquisition_time(&self) -> Option<f64> {
+        self.dwell_time().map(|dw| self.num_points as f64 * dw)
+    }
+
+    /// Time of each of `n` FID points in seconds, or the point index when
+    /// the spectral width is unknown
+    pub fn time_scale(&self, n: usize) -> Vec<f64> {
+        let dw = self.dwell_time().unwrap_or(1.0);
+        (0..n).map(|i| i as f64 * dw).collect()
+    }
+}
+
+/// Sampling schedule of a non-uniformly sampled (NUS) 2D acquisition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NusSchedule {
+    /// Acquired t1 increments on the uniform grid, ascending
+    pub sampled: Vec<usize>,
+    /// Increments of the full uniform grid
+    pub grid: usize,
+    /// Rows of the matrix per increment: 2 for a States pair of FIDs
+    pub rows_per_increment: usize,
+    /// Where the schedule was read: a `nuslist` path, or the empty
+    /// increments of a zero-filled grid
+    pub source: String,
+}
+
+/// The quadrants of a hypercomplex 2D spectrum that are imaginary in F1
+/// (same layout as `data_2d`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct F1Quadrants {
+    /// F1 imaginary, F2 real
+    pub ir: Vec<Vec<f64>>,
+    /// Imaginary in both dimensions
+    pub ii: Vec<Vec<f64>>,
+}
+
+/// How the indirect (t1) dimension of a 2D acquisition was sampled, which
+/// decides how the F1 transform combines the rows of the FID matrix
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum F1Acquisition {
+    /// One row per t1 point, complex in F2 only (QF, or unknown): F1 is
+    /// transformed as a complex series and suits magnitude display
+    #[default]
+    Complex,
+    /// Cosine- and sine-modulated row pair per increment (hypercomplex)
+    States,
+    /// States with the phase stepped by 180° every increment, which moves
+    /// the axial peak to the edge of the spectrum
+    StatesTppi,
+    /// P-type (antiecho) and N-type (echo) row pair per increment from
+    /// gradient selection, recombined into a States pair
+    EchoAntiecho,
+    /// One real row per t1 point at twice the rate, phase stepped by 90°
+    Tppi,
+}
+
+impl F1Acquisition {
+    pub const ALL: [F1Acquisition; 5] = [
+        F1Acquisition::Complex,
+        F1Acquisition::States,
+        F1Acquisition::StatesTppi,
+        F1Acquisition::EchoAntiecho,
+        F1Acquisition::Tppi,
+    ];
+
+    /// From Bruker `FnMODE` (0 undefined, 1 QF, 2 QSEQ, 3 TPPI, 4 States,
+    /// 5 States-TPPI, 6 Echo-Antiecho)
+    pub fn from_bruker_fnmode(fnmode: i32) -> Self {
+        match fnmode {
+            2 | 3 => F1Acquisition::Tppi,
+            4 => F1Acquisition::States,
+            5 => F1Acquisition::StatesTppi,
+            6 => F1Acquisition::EchoAntiecho,
+            _ => F1Acquisition::Complex,
+        }
+    }
+
+    /// Whether the acquisition gives a hypercomplex spectrum with all four
+    /// quadrants, which can be phased in both dimensions
+    pub fn is_hypercomplex(self) -> bool {
+        self != F1Acquisition::Complex
+    }
+
+    /// Rows of the FID matrix per t1 increment: a cosine and sine row for
+    /// States-type pairs, else one
+    pub fn rows_per_increment(self) -> usize {
+        match self {
+            F1Acquisition::States | F1Acquisition::StatesTppi | F1Acquisition::EchoAntiecho => 2,
+            F1Acquisition::Complex | F1Acquisition::Tppi => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for F1Acquisition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            F1Acquisition::Complex => write!(f, "Complex (QF)"),
+            F1Acquisition::States => write!(f, "States"),
+            F1Acquisition::StatesTppi => write!(f, "States-TPPI"),
+            F1Acquisition::EchoAntiecho => write!(f, "Echo-Antiecho"),
+            F1Acquisition::Tppi => write!(f, "TPPI"),
+        }
+    }
+}
+
+/// [`SpectrumData::metadata`] key of the pulse program / sequence name
+pub const META_PULSE_PROGRAM: &str = "Pulse program";
+/// [`SpectrumData::metadata`] key of the number of scans
+pub const META_SCANS: &str = "Scans";
+
+/// An arrayed acquisition: one FID per value of the stepped parameter(s),
+/// e.g. the recovery delays of a T1 series or the time points of a
+/// kinetics run (Agilent `array` / `arraydim`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArrayedAcquisition {
+    /// The array expression as acquired: `d2`, `(d2,nt)` or `pw,d2`
+    pub array: String,
+    /// Each arrayed parameter with its values
+    pub parameters: Vec<(String, Vec<f64>)>,
+    /// Total number of FIDs
+    pub slices: usize,
+}
+
+impl ArrayedAcquisition {
+    /// Value of every arrayed parameter for FID `slice` (0-based).
+    /// Parameters in parentheses step together; of comma-separated groups
+    /// the last varies fastest.
+    pub fn values_at(&self, slice: usize) -> Vec<(&str, f64)> {
+        let mut groups: Vec<Vec<&str>> = vec![Vec::new()];
+        let mut depth = 0;
+        let mut start = 0;
+        for (i, c) in self.array.char_indices().chain([(self.array.len(), ',')]) {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' => {}
+                _ => continue,
+            }
+            let name = self.array[start..i].trim();
+            if !name.is_empty() {
+                groups.last_mut().unwrap().push(name);
+            }
+            if c == ',' && depth == 0 {
+                groups.push(Vec::new());
+            }
+            start = i + 1;
+        }
+
+        let values = |name: &str| self.parameters.iter().find(|(n, _)| n == name).map(|(_, v)| v);
+        let mut indices = vec![0; groups.len()];
+        let mut rem = slice;
+        for (group, index) in groups.iter().zip(indices.iter_mut()).rev() {
+            let size = group.iter().find_map(|n| values(n)).map_or(1, |v| v.len().max(1));
+            *index = rem % size;
+            rem /= size;
+        }
+        groups
+            .iter()
+            .zip(indices)
+            .flat_map(|(group, index)| group.iter().map(move |name| (*name, index)))
+            .filter_map(|(name, index)| {
+                let (n, v) = self.parameters.iter().find(|(n, _)| n == name)?;
+                Some((n.as_str(), *v.get(index)?))
+            })
+            .collect()
+    }
 }
 
 /// Spectrum data container
@@ -135,6 +486,17 @@ pub struct SpectrumData {
     pub experiment_type: ExperimentType,
     pub dimensionality: Dimensionality,
     pub sample_name: String,
+    /// Solvent as recorded by the spectrometer (Bruker SOLVENT, JEOL
+    /// `solvent`, JCAMP-DX `.SOLVENT NAME`); empty when unknown
+    #[serde(default)]
+    pub solvent: String,
+    /// Stepped parameters of an arrayed acquisition (one FID per row)
+    #[serde(default)]
+    pub array: Option<ArrayedAcquisition>,
+    /// Acquisition parameters from the vendor files that the axes don't
+    /// hold, keyed by display name ([`META_PULSE_PROGRAM`], [`META_SCANS`])
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
     /// Axis parameters (1 for 1D, 2 for 2D)
     pub axes: Vec<AxisParams>,
     /// Real data for 1D spectrum
@@ -146,8 +508,29 @@ pub struct SpectrumData {
     /// 2D imaginary data (same layout as data_2d)
     #[serde(default)]
     pub data_2d_imag: Vec<Vec<f64>>,
+    /// F1-imaginary quadrants of a phase-sensitive 2D spectrum; with these
+    /// `data_2d` / `data_2d_imag` are the RR / RI quadrants
+    #[serde(default)]
+    pub f1_quadrants: Option<F1Quadrants>,
+    /// Sampling schedule of NUS data, until it is reconstructed
+    #[serde(default)]
+    pub nus_schedule: Option<NusSchedule>,
+    /// Digital-filter group delay (points) of a Bruker FID loaded without
+    /// its correction, so it can still be removed; cleared once it is
+    #[serde(default)]
+    pub group_delay: Option<f64>,
+    /// t1 sampling of 2D time-domain data
+    #[serde(default)]
+    pub f1_acquisition: F1Acquisition,
+    /// Pulse program stored with the raw data (Bruker, JEOL)
+    #[serde(default)]
+    pub pulse_program: Option<PulseProgram>,
     /// Whether the data has been Fourier-transformed
     pub is_frequency_domain: bool,
+    /// Raw FID or already processed when loaded; stays set after an
+    /// inverse FT, whose FID is then recomputed rather than measured
+    #[serde(default)]
+    pub provenance: Provenance,
     /// NMRPipe format file path after conversion
     pub nmrpipe_path: Option<PathBuf>,
     /// Which conversion method was used to load the data
@@ -163,12 +546,21 @@ impl Default for SpectrumData {
             experiment_type: ExperimentType::Other("Unknown".into()),
             dimensionality: Dimensionality::OneD,
             sample_name: String::new(),
+            solvent: String::new(),
+            array: None,
+            metadata: BTreeMap::new(),
             axes: vec![AxisParams::default()],
             real: Vec::new(),
             imag: Vec::new(),
             data_2d: Vec::new(),
             data_2d_imag: Vec::new(),
+            f1_quadrants: None,
+            nus_schedule: None,
+            group_delay: None,
+            f1_acquisition: F1Acquisition::Complex,
+            pulse_program: None,
             is_frequency_domain: false,
+            provenance: Provenance::Raw,
             nmrpipe_path: None,
             conversion_method_used: String::new(),
         }
@@ -176,6 +568,106 @@ impl Default for SpectrumData {
 }
 
 impl SpectrumData {
+    /// Default ppm display range (low, high): the nucleus' typical shift
+    /// window limited to the acquired spectral window.  `None` for time
+    /// domain data or when nothing is known about the nucleus.
+    pub fn default_display_range(&self) -> Option<(f64, f64)> {
+        if !self.is_frequency_domain {
+            return None;
+        }
+        let axis = self.axes.first()?;
+        let (lo, hi) = self.display_nucleus()?.typical_ppm_range()?;
+        if axis.num_points < 2 {
+            return Some((lo, hi));
+        }
+        let first = axis.index_to_ppm(0);
+        let last = axis.index_to_ppm(axis.num_points - 1);
+        let (lo, hi) = (lo.max(first.min(last)), hi.min(first.max(last)));
+        (hi > lo).then_some((lo, hi))
+    }
+
+    /// Standalone copy of a 1D spectrum restricted to `lo..=hi` ppm, an
+    /// extracted region of the same axis so every kept point stays at its
+    /// ppm.  `None` for FIDs, 2D data, or a range holding fewer
+    /// than two points.
+    pub fn region(&self, lo: f64, hi: f64) -> Option<SpectrumData> {
+        if !self.is_frequency_domain || self.is_2d() {
+            return None;
+        }
+        let axis = self.axes.first()?;
+        let n = self.real.len().min(axis.num_points);
+        // Index runs from high to low ppm
+        let first = axis.ppm_to_position(lo.max(hi))?.ceil().max(0.0) as usize;
+        let last = (axis.ppm_to_position(lo.min(hi))?.floor().max(0.0) as usize).min(n.checked_sub(1)?);
+        if last <= first {
+            return None;
+        }
+        let count = last - first + 1;
+        let mut region = self.clone();
+        region.axes[0] = AxisParams {
+            num_points: count,
+            first_point: axis.first_point + first,
+            full_points: axis.span_points(),
+            ..axis.clone()
+        };
+        region.real = self.real[first..=last].to_vec();
+        region.imag = self.imag.get(first..=last).map(<[f64]>::to_vec).unwrap_or_default();
+        region.sample_name = format!("{} ({:.2}–{:.2} ppm)", self.sample_name, region.axes[0].index_to_ppm(count - 1), region.axes[0].reference_ppm);
+        // Like a vendor-processed spectrum, the region has no measured FID
+        // (nor a converted file) of its own
+        region.provenance = Provenance::VendorProcessed;
+        region.nmrpipe_path = None;
+        Some(region)
+    }
+
+    /// Display x values of the 1D trace: ppm in the frequency domain,
+    /// seconds for an FID (point index when the spectral width is unknown)
+    pub fn x_scale(&self) -> Vec<f64> {
+        match self.axes.first() {
+            Some(axis) if self.is_frequency_domain => axis.ppm_scale(),
+            Some(axis) => axis.time_scale(self.real.len()),
+            None => (0..self.real.len()).map(|i| i as f64).collect(),
+        }
+    }
+
+    /// Whether the time-domain x axis is in seconds (known spectral width)
+    pub fn has_time_axis(&self) -> bool {
+        !self.is_frequency_domain && self.axes.first().and_then(|a| a.dwell_time()).is_some()
+    }
+
+    /// Title of the x axis matching [`Self::x_scale`]
+    pub fn x_axis_label(&self) -> &'static str {
+        if self.is_frequency_domain {
+            "Chemical Shift (ppm)"
+        } else if self.has_time_axis() {
+            "Time (s)"
+        } else {
+            "Point"
+        }
+    }
+
+    /// Nucleus whose ppm presets apply: the F2 axis nucleus, or one guessed
+    /// from the experiment type when the axis label is unknown
+    pub fn display_nucleus(&self) -> Option<Nucleus> {
+        match self.axes.first().map(|a| &a.nucleus) {
+            Some(Nucleus::Other(_)) | None => match &self.experiment_type {
+                ExperimentType::Proton | ExperimentType::Deuterium => Some(Nucleus::H1),
+                ExperimentType::Carbon | ExperimentType::Dept135 => Some(Nucleus::C13),
+                _ => None,
+            },
+            Some(n) => Some(n.clone()),
+        }
+    }
+
+    /// Whether an imaginary part was loaded or kept, so the data can be
+    /// phased or transformed back (a real-only Bruker `1r` cannot)
+    pub fn has_imaginary(&self) -> bool {
+        match self.dimensionality {
+            Dimensionality::OneD => self.imag.iter().any(|&v| v != 0.0),
+            Dimensionality::TwoD => !self.data_2d_imag.is_empty() || self.f1_quadrants.is_some(),
+        }
+    }
+
     /// Get the maximum absolute value for normalization
     pub fn max_abs(&self) -> f64 {
         self.real
@@ -184,10 +676,34 @@ impl SpectrumData {
             .fold(0.0f64, f64::max)
     }
 
+    /// Total stored data points (real + imaginary, all 2D rows)
+    pub fn total_points(&self) -> usize {
+        let rows: usize = self.data_2d.iter().chain(&self.data_2d_imag).map(|r| r.len()).sum();
+        self.real.len() + self.imag.len() + rows
+    }
+
     /// Check if this is a 2D experiment
     pub fn is_2d(&self) -> bool {
         self.dimensionality == Dimensionality::TwoD
     }
+
+    /// Correct an experiment type guessed from a file or pulse-program name
+    /// using the observed nucleus: a 2H acquisition run with `zg` is a
+    /// deuterium spectrum, and a 1H spectrum of a "2H" sample is still 1H.
+    pub fn refine_experiment_type(&mut self) {
+        let Some(axis) = self.axes.first() else {
+            return;
+        };
+        match (&axis.nucleus, &self.experiment_type) {
+            (Nucleus::H2, ExperimentType::Proton | ExperimentType::Other(_)) => {
+                self.experiment_type = ExperimentType::Deuterium;
+            }
+            (Nucleus::H1, ExperimentType::Deuterium) => {
+                self.experiment_type = ExperimentType::Proton;
+            }
+            _ => {}
+        }
+    }
 }
 
 /// Detect experiment type from filename
@@ -202,6 +718,8 @@ pub fn detect_experiment_type(filename: &str) -> ExperimentType {
         ExperimentType::Cosy
     } else if upper.contains("135") || upper.contains("DEPT") {
         ExperimentType::Dept135
+    } else if upper.contains("DEUTERIUM") || super::nuclide::names_deuterium(filename) {
+        ExperimentType::Deuterium
     } else if upper.contains("PROTON") || upper.contains("1H") {
         ExperimentType::Proton
     } else if upper.contains("CARBON") || upper.contains("13C") {
@@ -218,3 +736,49 @@ pub fn experiment_dimensionality(exp: &ExperimentType) -> Dimensionality {
         _ => Dimensionality::OneD,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_keeps_ppm_of_every_point() {
+        let axis = AxisParams { num_points: 1000, spectral_width_hz: 4000.0, reference_ppm: 12.0, ..Default::default() };
+        let s = SpectrumData {
+            real: (0..1000).map(|i| i as f64).collect(),
+            imag: (0..1000).map(|i| -(i as f64)).collect(),
+            axes: vec![axis.clone()],
+            is_frequency_domain: true,
+            sample_name: "glucose".into(),
+            ..Default::default()
+        };
+
+        let region = s.region(5.5, 4.4).unwrap();
+        let ax = &region.axes[0];
+        assert_eq!(ax.num_points, region.real.len());
+        assert_eq!(region.imag.len(), region.real.len());
+        for (k, &v) in region.real.iter().enumerate() {
+            assert!((ax.index_to_ppm(k) - axis.index_to_ppm(v as usize)).abs() < 1e-9);
+        }
+        let (first, last) = (ax.index_to_ppm(0), ax.index_to_ppm(ax.num_points - 1));
+        assert!((5.5 - 0.01..=5.5).contains(&first) && (4.4..4.4 + 0.01).contains(&last));
+        assert_eq!(region.provenance, Provenance::VendorProcessed);
+        assert!(region.sample_name.starts_with("glucose ("));
+
+        // Ranges past the edges are clipped; FIDs and slivers have no region
+        assert_eq!(s.region(-50.0, 50.0).unwrap().real.len(), 1000);
+        assert!(s.region(20.0, 30.0).is_none());
+        assert!(s.region(5.0, 5.0001).is_none());
+        let fid = SpectrumData { is_frequency_domain: false, ..s.clone() };
+        assert!(fid.region(4.0, 6.0).is_none());
+    }
+
+    #[test]
+    fn test_deuterium_named_as_a_word_of_its_own() {
+        assert_eq!(detect_experiment_type("sample_2H"), ExperimentType::Deuterium);
+        assert_eq!(detect_experiment_type("lock-H2.jdf"), ExperimentType::Deuterium);
+        // A hydrochloride or a water-suppressed proton spectrum is not 2H
+        assert_eq!(detect_experiment_type("amine-2HCl_PROTON"), ExperimentType::Proton);
+        assert_eq!(detect_experiment_type("H2O_PROTON"), ExperimentType::Proton);
+    }
+}
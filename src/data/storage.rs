@@ -0,0 +1,189 @@
+//! Compact storage for spectra kept around but not processed
+//!
+//! Undo/redo snapshots are full copies of the spectrum, so a long history of
+//! a large 2D matrix dominates memory.  With single precision, snapshots keep
+//! their arrays as f32 (half the size); the working spectrum stays f64, so
+//! every processing step still runs in double precision and a snapshot is
+//! promoted back to f64 when it is restored.
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::spectrum::{F1Quadrants, SpectrumData};
+
+/// Precision of stored snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum StoragePrecision {
+    /// Exact copies (f64)
+    #[default]
+    Double,
+    /// Half the memory; restored data is rounded to ~7 significant digits
+    Single,
+}
+
+impl StoragePrecision {
+    pub fn label(&self) -> &str {
+        match self {
+            StoragePrecision::Double => "Double precision (f64, exact undo)",
+            StoragePrecision::Single => "Single precision (f32, half the memory)",
+        }
+    }
+}
+
+/// Memory the spectra and the undo history may use before a step that
+/// grows the data asks for confirmation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryBudget {
+    pub warn: bool,
+    pub budget_mb: usize,
+}
+
+impl Default for MemoryBudget {
+    fn default() -> Self {
+        Self { warn: true, budget_mb: 2048 }
+    }
+}
+
+impl MemoryBudget {
+    pub fn bytes(&self) -> usize {
+        self.budget_mb.saturating_mul(1024 * 1024)
+    }
+
+    /// Whether holding `bytes` calls for a warning
+    pub fn is_exceeded_by(&self, bytes: usize) -> bool {
+        self.warn && bytes > self.bytes()
+    }
+}
+
+/// A data array at its stored precision
+#[derive(Debug, Clone)]
+enum Samples {
+    F64(Vec<f64>),
+    F32(Vec<f32>),
+}
+
+impl Samples {
+    fn store(data: Vec<f64>, precision: StoragePrecision) -> Self {
+        match precision {
+            StoragePrecision::Double => Samples::F64(data),
+            StoragePrecision::Single => Samples::F32(data.into_iter().map(|v| v as f32).collect()),
+        }
+    }
+
+    fn restore(self) -> Vec<f64> {
+        match self {
+            Samples::F64(v) => v,
+            Samples::F32(v) => v.into_iter().map(f64::from).collect(),
+        }
+    }
+
+    fn heap_bytes(&self) -> usize {
+        match self {
+            Samples::F64(v) => v.len() * 8,
+            Samples::F32(v) => v.len() * 4,
+        }
+    }
+}
+
+/// A spectrum snapshot whose arrays are kept at a chosen precision
+#[derive(Debug, Clone)]
+pub struct StoredSpectrum {
+    /// Everything but the data arrays (which are left empty)
+    meta: SpectrumData,
+    real: Samples,
+    imag: Samples,
+    data_2d: Vec<Samples>,
+    data_2d_imag: Vec<Samples>,
+    /// IR and II rows of a phase-sensitive 2D spectrum
+    f1_quadrants: Option<(Vec<Samples>, Vec<Samples>)>,
+}
+
+impl StoredSpectrum {
+    pub fn store(mut spectrum: SpectrumData, precision: StoragePrecision) -> Self {
+        let real = Samples::store(std::mem::take(&mut spectrum.real), precision);
+        let imag = Samples::store(std::mem::take(&mut spectrum.imag), precision);
+        let rows = |m: Vec<Vec<f64>>| m.into_iter().map(|r| Samples::store(r, precision)).collect();
+        let data_2d = rows(std::mem::take(&mut spectrum.data_2d));
+        let data_2d_imag = rows(std::mem::take(&mut spectrum.data_2d_imag));
+        let f1_quadrants = spectrum.f1_quadrants.take().map(|q| (rows(q.ir), rows(q.ii)));
+        Self { meta: spectrum, real, imag, data_2d, data_2d_imag, f1_quadrants }
+    }
+
+    /// The full spectrum, promoted back to f64
+    pub fn restore(self) -> SpectrumData {
+        let rows = |m: Vec<Samples>| m.into_iter().map(Samples::restore).collect();
+        SpectrumData {
+            real: self.real.restore(),
+            imag: self.imag.restore(),
+            data_2d: rows(self.data_2d),
+            data_2d_imag: rows(self.data_2d_imag),
+            f1_quadrants: self.f1_quadrants.map(|(ir, ii)| F1Quadrants { ir: rows(ir), ii: rows(ii) }),
+            ..self.meta
+        }
+    }
+
+    /// Paths and parameters (the data arrays are empty)
+    pub fn meta_mut(&mut self) -> &mut SpectrumData {
+        &mut self.meta
+    }
+
+    /// Bytes held by the data arrays
+    pub fn heap_bytes(&self) -> usize {
+        self.real.heap_bytes()
+            + self.imag.heap_bytes()
+            + self
+                .data_2d
+                .iter()
+                .chain(&self.data_2d_imag)
+                .chain(self.f1_quadrants.iter().flat_map(|(ir, ii)| ir.iter().chain(ii)))
+                .map(Samples::heap_bytes)
+                .sum::<usize>()
+    }
+}
+
+impl SpectrumData {
+    /// Bytes held by the data arrays
+    pub fn heap_bytes(&self) -> usize {
+        let rows: usize = self
+            .data_2d
+            .iter()
+            .chain(&self.data_2d_imag)
+            .chain(self.f1_quadrants.iter().flat_map(|q| q.ir.iter().chain(&q.ii)))
+            .map(Vec::len)
+            .sum();
+        (self.real.len() + self.imag.len() + rows) * std::mem::size_of::<f64>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::spectrum::Dimensionality;
+
+    #[test]
+    fn test_single_precision_snapshot_halves_memory() {
+        let spectrum = SpectrumData {
+            sample_name: "hsqc".to_string(),
+            dimensionality: Dimensionality::TwoD,
+            data_2d: vec![vec![1.0 / 3.0; 256]; 64],
+            data_2d_imag: vec![vec![-2.5; 256]; 64],
+            ..Default::default()
+        };
+        let bytes = spectrum.heap_bytes();
+        assert_eq!(bytes, 2 * 64 * 256 * 8);
+
+        let exact = StoredSpectrum::store(spectrum.clone(), StoragePrecision::Double);
+        assert_eq!(exact.heap_bytes(), bytes);
+        assert_eq!(exact.restore().data_2d, spectrum.data_2d);
+
+        let mut compact = StoredSpectrum::store(spectrum.clone(), StoragePrecision::Single);
+        assert_eq!(compact.heap_bytes(), bytes / 2);
+        compact.meta_mut().sample_name = "moved".to_string();
+        let restored = compact.restore();
+        assert_eq!(restored.sample_name, "moved");
+        assert_eq!(restored.dimensionality, Dimensionality::TwoD);
+        assert_eq!(restored.data_2d_imag, spectrum.data_2d_imag); // exact in f32
+        let err = (restored.data_2d[5][7] - 1.0 / 3.0).abs();
+        assert!(err > 0.0 && err < 1e-7);
+    }
+}
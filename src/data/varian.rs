@@ -0,0 +1,564 @@
+//! Varian/Agilent `procpar` parameters and the built-in `fid` reader.
+//!
+//! With var2pipe the FID is converted by NMRPipe and this only reads the
+//! parameters the NMRPipe header has no room for: the solvent and, for
+//! arrayed acquisitions (`array` / `arraydim`), which parameter was
+//! stepped and its value for every FID.  Without it, [`read_varian_fid`]
+//! reads the binary `fid` file itself and builds the axes from procpar.
+//!
+//! Each procpar entry is a header line (`name subtype basictype …`), a
+//! value line `N v1 v2 …` (string values: `N "first"` followed by one
+//! quoted value per line) and an enumeration line.
+//!
+//! The `fid` file is big-endian: a 32-byte file header (`nblocks`,
+//! `ntraces`, `np`, `ebytes`, `tbytes`, `bbytes`, `vers_id`, `status`,
+//! `nbheaders`), then `nblocks` blocks of `nbheaders` 28-byte block headers
+//! followed by `ntraces` traces of `np` values (real and imaginary
+//! interleaved), stored as int16, int32 or float32.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::nuclide;
+use super::spectrum::*;
+
+/// Value(s) of one procpar parameter
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProcparValue {
+    Real(Vec<f64>),
+    Text(Vec<String>),
+}
+
+impl ProcparValue {
+    /// First value as a string (`""` when absent)
+    pub fn as_str(&self) -> &str {
+        match self {
+            ProcparValue::Text(v) => v.first().map(String::as_str).unwrap_or(""),
+            ProcparValue::Real(_) => "",
+        }
+    }
+
+    /// Numeric values (empty for text parameters)
+    pub fn reals(&self) -> &[f64] {
+        match self {
+            ProcparValue::Real(v) => v,
+            ProcparValue::Text(_) => &[],
+        }
+    }
+}
+
+/// Parse the contents of a procpar file into name → value(s)
+pub fn parse_procpar(content: &str) -> HashMap<String, ProcparValue> {
+    let mut params = HashMap::new();
+    let mut lines = content.lines();
+    while let Some(header) = lines.next() {
+        let mut fields = header.split_whitespace();
+        let (Some(name), Some(_subtype), Some(basictype)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        let Some(values) = lines.next() else { break };
+        let (count, rest) = split_count(values);
+        let value = if basictype == "2" {
+            let mut texts = Vec::with_capacity(count);
+            if count > 0 {
+                texts.push(unquote(rest));
+                for _ in 1..count {
+                    texts.push(lines.next().map(unquote).unwrap_or_default());
+                }
+            }
+            ProcparValue::Text(texts)
+        } else {
+            ProcparValue::Real(rest.split_whitespace().filter_map(|v| v.parse().ok()).collect())
+        };
+        lines.next(); // enumeration
+        params.insert(name.to_string(), value);
+    }
+    params
+}
+
+/// Read `procpar` from a Varian/Agilent experiment folder (or the folder
+/// of its `fid` file)
+pub fn read_procpar(path: &Path) -> io::Result<HashMap<String, ProcparValue>> {
+    let dir = if path.is_dir() { path } else { path.parent().unwrap_or(Path::new(".")) };
+    Ok(parse_procpar(&std::fs::read_to_string(dir.join("procpar"))?))
+}
+
+fn split_count(line: &str) -> (usize, &str) {
+    let line = line.trim_start();
+    let end = line.find(char::is_whitespace).unwrap_or(line.len());
+    (line[..end].parse().unwrap_or(0), line[end..].trim())
+}
+
+fn unquote(s: &str) -> String {
+    s.trim().trim_matches('"').to_string()
+}
+
+/// The arrayed parameters of an acquisition, or `None` when only one FID
+/// per increment was recorded.
+///
+/// `array='d2'` steps one parameter; `array='(d2,nt)'` steps several
+/// together; `array='pw,d2'` nests them, the last one varying fastest.
+/// `arraydim` is the total FID count, which for arrayed 2D data includes
+/// the t1 increments.
+pub fn arrayed_acquisition(params: &HashMap<String, ProcparValue>) -> Option<ArrayedAcquisition> {
+    let array = params.get("array")?.as_str();
+    let slices = params
+        .get("arraydim")
+        .and_then(|v| v.reals().first().copied())
+        .unwrap_or(1.0) as usize;
+    if array.is_empty() || slices < 2 {
+        return None;
+    }
+    let parameters: Vec<(String, Vec<f64>)> = array
+        .split([',', '(', ')'])
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| {
+            let values = params.get(name)?.reals().to_vec();
+            (!values.is_empty()).then(|| (name.to_string(), values))
+        })
+        .collect();
+    if parameters.is_empty() {
+        return None;
+    }
+    Some(ArrayedAcquisition { array: array.to_string(), parameters, slices })
+}
+
+/// Acquisition parameters for [`SpectrumData::metadata`](super::spectrum::SpectrumData::metadata):
+/// the sequence (`seqfil`) and number of scans (`nt`)
+pub fn metadata(params: &HashMap<String, ProcparValue>) -> BTreeMap<String, String> {
+    let mut meta = BTreeMap::new();
+    if let Some(seq) = params.get("seqfil").map(ProcparValue::as_str).filter(|s| !s.is_empty()) {
+        meta.insert(META_PULSE_PROGRAM.to_string(), seq.to_string());
+    }
+    if let Some(nt) = params.get("nt").and_then(|v| v.reals().first()) {
+        meta.insert(META_SCANS.to_string(), format!("{}", nt));
+    }
+    meta
+}
+
+/// Numeric value of a procpar parameter (`None` when absent or text)
+fn real(params: &HashMap<String, ProcparValue>, name: &str) -> Option<f64> {
+    params.get(name)?.reals().first().copied()
+}
+
+/// Text value of a procpar parameter (`""` when absent)
+fn text<'a>(params: &'a HashMap<String, ProcparValue>, name: &str) -> &'a str {
+    params.get(name).map(ProcparValue::as_str).unwrap_or("")
+}
+
+/// F1 quadrature detection of a 2D acquisition (procpar `phase`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum F1Quadrature {
+    /// `phase=1,2`: two FIDs per t1 increment (hypercomplex)
+    States,
+    /// `phase=3`: one FID per increment, real in t1
+    Tppi,
+    /// No phase cycling in t1 (absolute-value / gradient-selected)
+    Magnitude,
+}
+
+impl std::fmt::Display for F1Quadrature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            F1Quadrature::States => write!(f, "States"),
+            F1Quadrature::Tppi => write!(f, "TPPI"),
+            F1Quadrature::Magnitude => write!(f, "magnitude"),
+        }
+    }
+}
+
+impl From<F1Quadrature> for F1Acquisition {
+    fn from(quadrature: F1Quadrature) -> Self {
+        match quadrature {
+            F1Quadrature::States => F1Acquisition::States,
+            F1Quadrature::Tppi => F1Acquisition::Tppi,
+            F1Quadrature::Magnitude => F1Acquisition::Complex,
+        }
+    }
+}
+
+impl F1Quadrature {
+    pub fn from_procpar(params: &HashMap<String, ProcparValue>) -> Self {
+        let phase = params.get("phase").map(ProcparValue::reals).unwrap_or(&[]);
+        if phase.contains(&1.0) && phase.contains(&2.0) {
+            F1Quadrature::States
+        } else if phase.contains(&3.0) {
+            F1Quadrature::Tppi
+        } else {
+            F1Quadrature::Magnitude
+        }
+    }
+}
+
+/// `status` bits of the fid file header
+const S_32: i16 = 0x4;
+const S_FLOAT: i16 = 0x8;
+const FILE_HEADER_BYTES: usize = 32;
+const BLOCK_HEADER_BYTES: usize = 28;
+
+/// Storage of the fid values
+#[derive(Clone, Copy)]
+enum SampleKind {
+    Int16,
+    Int32,
+    Float32,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Split the contents of a Varian `fid` file into traces of `np` values
+/// (real and imaginary interleaved), in acquisition order
+pub fn parse_fid(raw: &[u8]) -> io::Result<Vec<Vec<f64>>> {
+    if raw.len() < FILE_HEADER_BYTES {
+        return Err(invalid(format!("fid file too short for its header ({} bytes)", raw.len())));
+    }
+    let i32_at = |pos: usize| i32::from_be_bytes([raw[pos], raw[pos + 1], raw[pos + 2], raw[pos + 3]]);
+    let [nblocks, ntraces, np, ebytes, tbytes, bbytes] = [0, 4, 8, 12, 16, 20].map(|pos| i32_at(pos).max(0) as usize);
+    let status = i16::from_be_bytes([raw[26], raw[27]]);
+    let nbheaders = i32_at(28).max(0) as usize;
+
+    let kind = match (ebytes, status & S_FLOAT != 0, status & S_32 != 0) {
+        (4, true, _) => SampleKind::Float32,
+        (4, false, true) => SampleKind::Int32,
+        (2, false, false) => SampleKind::Int16,
+        _ => return Err(invalid(format!("Unsupported fid data format: {} bytes per value, status 0x{:x}", ebytes, status))),
+    };
+    if np == 0 || tbytes != np * ebytes || bbytes != ntraces * tbytes + nbheaders * BLOCK_HEADER_BYTES {
+        return Err(invalid(format!(
+            "Inconsistent fid header: np={} ebytes={} tbytes={} ntraces={} bbytes={}",
+            np, ebytes, tbytes, ntraces, bbytes
+        )));
+    }
+    if raw.len() < FILE_HEADER_BYTES + nblocks * bbytes {
+        return Err(invalid(format!(
+            "fid file truncated: {} blocks of {} bytes need {} bytes, found {}",
+            nblocks,
+            bbytes,
+            FILE_HEADER_BYTES + nblocks * bbytes,
+            raw.len()
+        )));
+    }
+
+    let value = |b: &[u8]| -> f64 {
+        match kind {
+            SampleKind::Float32 => f32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            SampleKind::Int32 => i32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            SampleKind::Int16 => i16::from_be_bytes([b[0], b[1]]) as f64,
+        }
+    };
+    let mut traces = Vec::with_capacity(nblocks * ntraces);
+    for block in 0..nblocks {
+        let data = FILE_HEADER_BYTES + block * bbytes + nbheaders * BLOCK_HEADER_BYTES;
+        for trace in 0..ntraces {
+            let start = data + trace * tbytes;
+            traces.push(raw[start..start + tbytes].chunks_exact(ebytes).map(value).collect());
+        }
+    }
+    Ok(traces)
+}
+
+/// ppm of the first (highest-frequency) point: procpar references a line
+/// `rfl` Hz from the right-hand edge to `rfp` Hz; without a reference the
+/// window is centred on 0 ppm
+fn left_edge_ppm(sw: f64, obs_mhz: f64, rfl: Option<f64>, rfp: Option<f64>) -> f64 {
+    if obs_mhz <= 0.0 {
+        return 0.0;
+    }
+    match (rfl, rfp) {
+        (Some(rfl), Some(rfp)) => (rfp + sw - rfl) / obs_mhz,
+        _ => sw / (2.0 * obs_mhz),
+    }
+}
+
+/// Nucleus and frequency of the F1 axis: named by `refsource1` (`sfrq`,
+/// `dfrq`, `dfrq2`), or the decoupler channel for heteronuclear
+/// experiments and the observe channel otherwise
+fn indirect_channel(params: &HashMap<String, ProcparValue>, experiment: &ExperimentType) -> (String, f64) {
+    let source = match text(params, "refsource1") {
+        "" if matches!(experiment, ExperimentType::Hsqc | ExperimentType::Hmbc) => "dfrq",
+        "" => "sfrq",
+        other => other,
+    };
+    let nucleus = match source {
+        "dfrq" => "dn",
+        "dfrq2" => "dn2",
+        _ => "tn",
+    };
+    (text(params, nucleus).to_string(), real(params, source).unwrap_or(0.0))
+}
+
+fn parse_nucleus(label: &str) -> Nucleus {
+    nuclide::parse_label(label).unwrap_or_else(|| Nucleus::Other(label.to_string()))
+}
+
+/// Read a Varian/Agilent experiment folder (`fid` + `procpar`) natively.
+///
+/// A single FID loads as 1D; several (t1 increments, arrayed FIDs) load
+/// as rows of a 2D time-domain matrix, in acquisition order as var2pipe
+/// writes them.  For 2D data `sw1` and the F1 reference set the second
+/// axis, whose size is the number of stored rows (two per increment for
+/// States data).
+pub fn read_varian_fid(path: &Path) -> io::Result<SpectrumData> {
+    let dir = if path.is_dir() { path } else { path.parent().unwrap_or(Path::new(".")) };
+    let params = read_procpar(dir)?;
+    let traces = parse_fid(&fs::read(dir.join("fid"))?)?;
+    if traces.is_empty() {
+        return Err(invalid(format!("No FIDs in {}", dir.join("fid").display())));
+    }
+
+    let seqfil = text(&params, "seqfil");
+    let tn = text(&params, "tn");
+    let nucleus = parse_nucleus(tn);
+    let experiment_type = match detect_experiment_type(seqfil) {
+        ExperimentType::Other(_) => match nucleus {
+            Nucleus::H1 => ExperimentType::Proton,
+            Nucleus::H2 => ExperimentType::Deuterium,
+            Nucleus::C13 => ExperimentType::Carbon,
+            _ => ExperimentType::Other(seqfil.to_string()),
+        },
+        exp => exp,
+    };
+    let sample_name = dir
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Varian".to_string());
+
+    let split = |trace: &[f64]| -> (Vec<f64>, Vec<f64>) {
+        (trace.iter().step_by(2).copied().collect(), trace.iter().skip(1).step_by(2).copied().collect())
+    };
+    let sw = real(&params, "sw").unwrap_or(0.0);
+    let sfrq = real(&params, "sfrq").unwrap_or(0.0);
+    let axis_x = AxisParams {
+        nucleus,
+        num_points: traces[0].len() / 2,
+        spectral_width_hz: sw,
+        observe_freq_mhz: sfrq,
+        reference_ppm: left_edge_ppm(sw, sfrq, real(&params, "rfl"), real(&params, "rfp")),
+        label: tn.to_string(),
+        ..Default::default()
+    };
+
+    let mut spectrum = SpectrumData {
+        source_path: dir.to_path_buf(),
+        vendor_format: VendorFormat::Varian,
+        sample_name,
+        solvent: text(&params, "solvent").to_string(),
+        array: arrayed_acquisition(&params),
+        metadata: metadata(&params),
+        is_frequency_domain: false,
+        provenance: Provenance::Raw,
+        conversion_method_used: "Built-in (Varian/Agilent fid reader)".to_string(),
+        ..Default::default()
+    };
+
+    if traces.len() == 1 {
+        let (real, imag) = split(&traces[0]);
+        spectrum.experiment_type = experiment_type;
+        spectrum.dimensionality = Dimensionality::OneD;
+        spectrum.axes = vec![axis_x];
+        spectrum.real = real;
+        spectrum.imag = imag;
+        return Ok(spectrum);
+    }
+
+    let (label_y, obs_y) = indirect_channel(&params, &experiment_type);
+    let sw1 = real(&params, "sw1").unwrap_or(0.0);
+    let axis_y = AxisParams {
+        nucleus: parse_nucleus(&label_y),
+        num_points: traces.len(),
+        spectral_width_hz: sw1,
+        observe_freq_mhz: obs_y,
+        reference_ppm: left_edge_ppm(sw1, obs_y, real(&params, "rfl1"), real(&params, "rfp1")),
+        label: label_y,
+        ..Default::default()
+    };
+    for trace in &traces {
+        let (real, imag) = split(trace);
+        spectrum.data_2d.push(real);
+        spectrum.data_2d_imag.push(imag);
+    }
+    spectrum.real = spectrum.data_2d[0].clone();
+    spectrum.experiment_type = experiment_type;
+    spectrum.dimensionality = Dimensionality::TwoD;
+    spectrum.axes = vec![axis_x, axis_y];
+    Ok(spectrum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROCPAR: &str = r#"array 7 2 1024 0 0 2 1 0 1 64
+1 "d2"
+0
+arraydim 1 1 32768 1 1 1 1 0 1 64
+1 5
+0
+d2 1 1 8190 0 0 2 1 0 1 64
+5 0.01 0.1 0.5 1 5
+0
+solvent 1 2 8 0 0 2 1 0 1 64
+1 "cdcl3"
+0
+tpwr 1 1 63 -16 1 2 1 0 1 64
+1 56
+0
+seqfil 1 2 64 0 0 1 1 0 1 64
+2 "s2pul"
+"INVREC"
+0
+"#;
+
+    #[test]
+    fn test_procpar_arrayed_relaxation_delays() {
+        let params = parse_procpar(PROCPAR);
+        assert_eq!(params["solvent"].as_str(), "cdcl3");
+        assert_eq!(params["tpwr"].reals(), &[56.0]);
+        assert_eq!(params["seqfil"], ProcparValue::Text(vec!["s2pul".into(), "INVREC".into()]));
+        let meta = metadata(&params);
+        assert_eq!(meta.get(META_PULSE_PROGRAM).map(String::as_str), Some("s2pul"));
+        assert!(!meta.contains_key(META_SCANS));
+
+        let array = arrayed_acquisition(&params).unwrap();
+        assert_eq!(array.slices, 5);
+        assert_eq!(array.parameters, vec![("d2".to_string(), vec![0.01, 0.1, 0.5, 1.0, 5.0])]);
+        assert_eq!(array.values_at(3), vec![("d2", 1.0)]);
+
+        // Nested arrays: the last group varies fastest, parenthesised ones together
+        let nested = ArrayedAcquisition {
+            array: "pw,(d2,nt)".into(),
+            parameters: vec![
+                ("pw".into(), vec![5.0, 10.0]),
+                ("d2".into(), vec![0.1, 1.0, 10.0]),
+                ("nt".into(), vec![4.0, 8.0, 16.0]),
+            ],
+            slices: 6,
+        };
+        assert_eq!(nested.values_at(1), vec![("pw", 5.0), ("d2", 1.0), ("nt", 8.0)]);
+        assert_eq!(nested.values_at(5), vec![("pw", 10.0), ("d2", 10.0), ("nt", 16.0)]);
+
+        let mut single = params.clone();
+        single.insert("arraydim".into(), ProcparValue::Real(vec![1.0]));
+        assert!(arrayed_acquisition(&single).is_none());
+    }
+
+    /// A big-endian fid file: one block header per block, one trace per block
+    fn fid_bytes(status: i16, ebytes: usize, blocks: &[Vec<f64>]) -> Vec<u8> {
+        let np = blocks[0].len();
+        let tbytes = np * ebytes;
+        let mut out = Vec::new();
+        for v in [blocks.len(), 1, np, ebytes, tbytes, tbytes + BLOCK_HEADER_BYTES] {
+            out.extend((v as i32).to_be_bytes());
+        }
+        out.extend(0i16.to_be_bytes());
+        out.extend(status.to_be_bytes());
+        out.extend(1i32.to_be_bytes());
+        for block in blocks {
+            out.extend([0u8; BLOCK_HEADER_BYTES]);
+            for &v in block {
+                match (ebytes, status & S_FLOAT != 0) {
+                    (4, true) => out.extend((v as f32).to_be_bytes()),
+                    (4, false) => out.extend((v as i32).to_be_bytes()),
+                    _ => out.extend((v as i16).to_be_bytes()),
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_read_varian_fid_1d_and_states_2d() {
+        let dir = std::env::temp_dir().join(format!("nmr_varian_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let hsqc = dir.join("hsqc.fid");
+        fs::create_dir_all(&hsqc).unwrap();
+        let procpar = r#"seqfil 1 2 64 0 0 1 1 0 1 64
+1 "gHSQCAD"
+0
+tn 1 2 8 0 0 1 1 0 1 64
+1 "H1"
+0
+dn 1 2 8 0 0 1 1 0 1 64
+1 "C13"
+0
+sw 1 1 1e9 0 0 2 1 0 1 64
+1 4000
+0
+sfrq 1 1 1e9 0 0 2 1 0 1 64
+1 400
+0
+rfl 1 1 1e9 0 0 2 1 0 1 64
+1 1000
+0
+rfp 1 1 1e9 0 0 2 1 0 1 64
+1 0
+0
+sw1 1 1 1e9 0 0 2 1 0 1 64
+1 16000
+0
+dfrq 1 1 1e9 0 0 2 1 0 1 64
+1 100
+0
+rfl1 1 1 1e9 0 0 2 1 0 1 64
+1 6000
+0
+rfp1 1 1 1e9 0 0 2 1 0 1 64
+1 0
+0
+ni 1 1 1e9 0 0 2 1 0 1 64
+1 2
+0
+phase 1 1 1e9 0 0 2 1 0 1 64
+2 1 2
+0
+solvent 1 2 8 0 0 2 1 0 1 64
+1 "cdcl3"
+0
+"#;
+        fs::write(hsqc.join("procpar"), procpar).unwrap();
+        // Two increments × two States phases, 4 complex points each, int32
+        let blocks: Vec<Vec<f64>> = (0..4).map(|b| (0..8).map(|i| (b * 100 + i) as f64).collect()).collect();
+        fs::write(hsqc.join("fid"), fid_bytes(0x1 | S_32 | 0x10, 4, &blocks)).unwrap();
+
+        let params = read_procpar(&hsqc).unwrap();
+        assert_eq!(F1Quadrature::from_procpar(&params), F1Quadrature::States);
+        let spectrum = read_varian_fid(&hsqc.join("fid")).unwrap();
+        assert!(spectrum.is_2d() && !spectrum.is_frequency_domain);
+        assert_eq!(spectrum.experiment_type, ExperimentType::Hsqc);
+        assert_eq!(spectrum.sample_name, "hsqc");
+        assert_eq!(spectrum.solvent, "cdcl3");
+        assert_eq!(spectrum.data_2d.len(), 4);
+        assert_eq!(spectrum.data_2d[2], vec![200.0, 202.0, 204.0, 206.0]);
+        assert_eq!(spectrum.data_2d_imag[2], vec![201.0, 203.0, 205.0, 207.0]);
+        let (x, y) = (&spectrum.axes[0], &spectrum.axes[1]);
+        assert_eq!((x.nucleus.clone(), x.num_points, x.spectral_width_hz), (Nucleus::H1, 4, 4000.0));
+        // rfl Hz from the right edge is rfp: the left edge is (rfp + sw - rfl) / sfrq
+        assert!((x.reference_ppm - 7.5).abs() < 1e-12);
+        assert_eq!((y.nucleus.clone(), y.num_points, y.observe_freq_mhz), (Nucleus::C13, 4, 100.0));
+        assert!((y.reference_ppm - 100.0).abs() < 1e-12);
+
+        // A single float FID loads as 1D; without rfl/rfp the window is centred on 0 ppm
+        let proton = dir.join("proton.fid");
+        fs::create_dir_all(&proton).unwrap();
+        let one_d = procpar.replace("\"gHSQCAD\"", "\"s2pul\"").replace("rfl ", "xrfl ");
+        fs::write(proton.join("procpar"), one_d).unwrap();
+        fs::write(proton.join("fid"), fid_bytes(0x1 | S_FLOAT | 0x10, 4, &[vec![1.5, -0.5, 0.25, 2.0]])).unwrap();
+        let spectrum = read_varian_fid(&proton).unwrap();
+        assert!(!spectrum.is_2d());
+        assert_eq!(spectrum.experiment_type, ExperimentType::Proton);
+        assert_eq!((spectrum.real.clone(), spectrum.imag.clone()), (vec![1.5, 0.25], vec![-0.5, 2.0]));
+        assert!((spectrum.axes[0].reference_ppm - 5.0).abs() < 1e-12);
+
+        // int16 traces, and truncated files are rejected
+        let raw = fid_bytes(0x1 | 0x10, 2, &[vec![-3.0, 4.0]]);
+        assert_eq!(parse_fid(&raw).unwrap(), vec![vec![-3.0, 4.0]]);
+        assert!(parse_fid(&raw[..raw.len() - 1]).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
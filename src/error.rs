@@ -0,0 +1,149 @@
+//! Errors of the data readers, conversion, processing and export.
+//!
+//! Readers and converters still use `io::Result` internally; the conversion,
+//! processing and export entry points classify what went wrong into an
+//! [`NmrError`] variant, so the GUI can tell a missing file from an
+//! unsupported format or a damaged header and say what to do about it
+//! ([`NmrError::user_message`]).
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum NmrError {
+    /// The dataset, or a file it needs, is not there
+    #[error("{} not found", .0.display())]
+    NotFound(PathBuf),
+    /// A dataset folder with none of the files its reader looks for
+    #[error("{} holds none of {}", folder.display(), expected.join(", "))]
+    MissingFiles { folder: PathBuf, expected: Vec<&'static str> },
+    /// Not a format any reader recognises
+    #[error("unknown NMR data format: {}", .0.display())]
+    UnsupportedFormat(PathBuf),
+    /// A recognised format whose header or data cannot be read
+    #[error("cannot read {}: {reason}", path.display())]
+    Corrupted { path: PathBuf, reason: String },
+    /// An NMRPipe tool needed for the chosen conversion is not installed
+    #[error("{0} not found")]
+    ToolMissing(String),
+    /// An external tool ran and failed
+    #[error("{tool} failed: {message}")]
+    ToolFailed { tool: String, message: String },
+    /// A tool configured under Preferences → External tools could not
+    /// run or gave no usable answer
+    #[error("{tool}: {message}")]
+    ExternalTool { tool: String, message: String },
+    /// Stopped by the user
+    #[error("cancelled by user")]
+    Cancelled,
+    /// A processing step that does not apply to the data as it is
+    #[error("{0}")]
+    NotApplicable(String),
+    /// Data or parameters that do not fit together
+    #[error("{0}")]
+    InvalidInput(String),
+    /// An output file could not be written
+    #[error("cannot write {}: {message}", path.display())]
+    Export { path: PathBuf, message: String },
+    #[error(transparent)]
+    Io(io::Error),
+}
+
+pub type NmrResult<T> = Result<T, NmrError>;
+
+impl From<io::Error> for NmrError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::Interrupted => NmrError::Cancelled,
+            _ => NmrError::Io(e),
+        }
+    }
+}
+
+impl NmrError {
+    /// Classify an error from reading `path`: missing files, truncated or
+    /// malformed data and cancellation get their own variants
+    pub fn reading(path: &Path, e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => NmrError::NotFound(path.to_path_buf()),
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+                NmrError::Corrupted { path: path.to_path_buf(), reason: e.to_string() }
+            }
+            _ => e.into(),
+        }
+    }
+
+    /// Error from running the external `tool`
+    pub fn tool(tool: &str, e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::Interrupted => NmrError::Cancelled,
+            _ => NmrError::ToolFailed { tool: tool.to_string(), message: e.to_string() },
+        }
+    }
+
+    /// Error from writing `path`
+    pub fn writing(path: &Path, e: impl ToString) -> Self {
+        NmrError::Export { path: path.to_path_buf(), message: e.to_string() }
+    }
+
+    /// What the user can do about the error, if there is something
+    pub fn advice(&self) -> Option<&'static str> {
+        match self {
+            NmrError::NotFound(_) | NmrError::MissingFiles { .. } => {
+                Some("check the path, and that the whole experiment folder was copied")
+            }
+            NmrError::UnsupportedFormat(_) => Some(
+                "open a Bruker or Varian/Agilent folder, a JEOL .jdf, a JCAMP-DX (.jdx/.dx) or an NMRPipe file",
+            ),
+            NmrError::Corrupted { .. } => {
+                Some("the file may be truncated or damaged; copy or export it again from the spectrometer")
+            }
+            NmrError::ToolMissing(_) => {
+                Some("install NMRPipe and put it in PATH, or click the conversion method in the status bar to use Built-in")
+            }
+            NmrError::ToolFailed { .. } => Some("switch to Built-in conversion in the status bar, or check the NMRPipe installation"),
+            NmrError::ExternalTool { .. } => Some("check the tool's program and arguments under Preferences → External tools"),
+            NmrError::Export { .. } => Some("check that the folder exists and is writable"),
+            NmrError::Io(e) if e.kind() == io::ErrorKind::PermissionDenied => Some("check the file permissions"),
+            _ => None,
+        }
+    }
+
+    /// The error with its advice, for the status bar
+    pub fn user_message(&self) -> String {
+        match self.advice() {
+            Some(advice) => format!("{} — {}", self, advice),
+            None => self.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_say_what_to_do() {
+        let missing = NmrError::reading(Path::new("/data/gone.jdf"), io::Error::from(io::ErrorKind::NotFound));
+        assert!(matches!(missing, NmrError::NotFound(_)));
+        assert!(missing.user_message().starts_with("/data/gone.jdf not found — check the path"));
+
+        let empty = NmrError::MissingFiles { folder: PathBuf::from("/data/10"), expected: vec!["fid", "ser", "pdata/1/1r"] };
+        assert_eq!(empty.to_string(), "/data/10 holds none of fid, ser, pdata/1/1r");
+        assert!(empty.advice().is_some());
+
+        let truncated = io::Error::new(io::ErrorKind::UnexpectedEof, "header cut short");
+        let short = NmrError::reading(Path::new("short.ft1"), truncated);
+        assert_eq!(short.to_string(), "cannot read short.ft1: header cut short");
+
+        assert!(matches!(NmrError::tool("delta2pipe", io::Error::from(io::ErrorKind::Interrupted)), NmrError::Cancelled));
+        assert!(matches!(NmrError::from(io::Error::from(io::ErrorKind::Interrupted)), NmrError::Cancelled));
+
+        // Nothing to advise when a step does not apply to the data
+        let step = NmrError::NotApplicable("not NUS data".to_string());
+        assert!(step.advice().is_none());
+        assert_eq!(step.user_message(), "not NUS data");
+    }
+}
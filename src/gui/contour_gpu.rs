@@ -0,0 +1,333 @@
+//! GPU contour/heatmap rendering for large 2D spectra (`gpu-contours` feature)
+//!
+//! The matrix is uploaded once as an `R32Float` texture; a fragment shader
+//! then draws the heatmap and log-spaced contour lines for whatever part of
+//! the matrix is visible, so panning and zooming cost nothing on the CPU.
+//! Only available when eframe runs on its wgpu renderer; the contour view
+//! falls back to CPU drawing otherwise.
+
+use std::borrow::Cow;
+use std::sync::{Arc, OnceLock};
+
+use eframe::egui;
+use eframe::egui_wgpu::{self, wgpu};
+
+use crate::data::spectrum::SpectrumData;
+
+/// Largest texture side of the device, set once the pipeline is created
+static MAX_TEXTURE_SIDE: OnceLock<u32> = OnceLock::new();
+
+const SHADER: &str = r#"
+struct Uniforms {
+    uv_min: vec2<f32>,
+    uv_max: vec2<f32>,
+    pos_color: vec4<f32>,
+    neg_color: vec4<f32>,
+    background: vec4<f32>,
+    threshold: f32,
+    max_abs: f32,
+    levels: f32,
+    _pad: f32,
+};
+
+@group(0) @binding(0) var<uniform> u: Uniforms;
+@group(0) @binding(1) var data: texture_2d<f32>;
+
+struct VsOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) i: u32) -> VsOut {
+    // One triangle covering the viewport
+    let p = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u)) * 2.0 - 1.0;
+    var out: VsOut;
+    out.pos = vec4<f32>(p, 0.0, 1.0);
+    out.uv = vec2<f32>(p.x * 0.5 + 0.5, 0.5 - p.y * 0.5);
+    return out;
+}
+
+fn texel(c: vec2<i32>, dims: vec2<i32>) -> f32 {
+    return textureLoad(data, clamp(c, vec2<i32>(0), dims - 1), 0).r;
+}
+
+@fragment
+fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
+    let uv = mix(u.uv_min, u.uv_max, in.uv);
+    let dims = vec2<i32>(textureDimensions(data));
+
+    // Bilinear interpolation by hand: R32Float is not filterable everywhere
+    let p = uv * vec2<f32>(dims) - 0.5;
+    let i = vec2<i32>(floor(p));
+    let f = p - floor(p);
+    let top = mix(texel(i, dims), texel(i + vec2<i32>(1, 0), dims), f.x);
+    let bottom = mix(texel(i + vec2<i32>(0, 1), dims), texel(i + vec2<i32>(1, 1), dims), f.x);
+    let v = mix(top, bottom, f.y);
+
+    // Level index on a log scale from the threshold up to the maximum;
+    // derivatives are taken before any branching
+    let a = max(abs(v), u.threshold * 1e-6);
+    let t = log(a / u.threshold) / log(u.max_abs / u.threshold) * u.levels;
+    let d = abs(fract(t + 0.5) - 0.5) / max(fwidth(t), 1e-6);
+    let line = 1.0 - clamp(d - 0.5, 0.0, 1.0);
+    let fill = 0.12 + 0.5 * clamp(t / u.levels, 0.0, 1.0);
+
+    let inside = all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0));
+    let visible = inside && abs(v) > u.threshold;
+    let color = select(u.neg_color, u.pos_color, v > 0.0);
+    let alpha = select(0.0, max(fill, line), visible);
+    return vec4<f32>(mix(u.background.rgb, color.rgb, alpha), 1.0);
+}
+"#;
+
+/// Pipeline and the current matrix texture, kept in the renderer's
+/// callback resources
+struct GpuResources {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    uniforms: wgpu::Buffer,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+/// Create the contour pipeline; call once with eframe's wgpu render state.
+pub fn init(render_state: &egui_wgpu::RenderState) {
+    let device = &render_state.device;
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("contour_gpu"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SHADER)),
+    });
+    let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("contour_gpu"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+        ],
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("contour_gpu"),
+        bind_group_layouts: &[&layout],
+        push_constant_ranges: &[],
+    });
+    // eframe's default surface: no depth buffer, no multisampling
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("contour_gpu"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            compilation_options: Default::default(),
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            compilation_options: Default::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: render_state.target_format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    });
+    let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("contour_gpu"),
+        size: UNIFORM_FLOATS as u64 * 4,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    render_state.renderer.write().callback_resources.insert(GpuResources {
+        pipeline,
+        layout,
+        uniforms,
+        bind_group: None,
+    });
+    let _ = MAX_TEXTURE_SIDE.set(device.limits().max_texture_dimension_2d);
+    log::info!("GPU contour rendering enabled");
+}
+
+/// Whether the wgpu renderer is running and the pipeline was created
+pub fn available() -> bool {
+    MAX_TEXTURE_SIDE.get().is_some()
+}
+
+/// Whether a `rows × cols` matrix can be drawn on the GPU
+pub fn supports(rows: usize, cols: usize) -> bool {
+    MAX_TEXTURE_SIDE
+        .get()
+        .is_some_and(|&max| rows > 1 && cols > 1 && rows <= max as usize && cols <= max as usize)
+}
+
+/// Cheap fingerprint of a matrix (dimensions and a strided sample), used
+/// to decide when to upload it again
+pub fn fingerprint(data: &[Vec<f64>]) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut h = std::collections::hash_map::DefaultHasher::new();
+    data.len().hash(&mut h);
+    let total: usize = data.iter().map(Vec::len).sum();
+    total.hash(&mut h);
+    let stride = (total / 65536).max(1);
+    for v in data.iter().flatten().step_by(stride) {
+        v.to_bits().hash(&mut h);
+    }
+    h.finish()
+}
+
+/// Texture bytes of the first `cols` points of each row (row 0 at the
+/// top, native-endian f32)
+pub fn matrix_bytes(data: &[Vec<f64>], cols: usize) -> Vec<u8> {
+    data.iter()
+        .flat_map(|row| &row[..cols])
+        .flat_map(|&v| (v as f32).to_ne_bytes())
+        .collect()
+}
+
+const UNIFORM_FLOATS: usize = 20;
+
+/// One frame of GPU contours for the visible part of the matrix
+pub struct ContourCallback {
+    /// New matrix to upload (rows, cols, bytes), if it changed
+    pub upload: Option<(u32, u32, Arc<Vec<u8>>)>,
+    /// Visible region in texture coordinates (0–1, origin top-left)
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+    pub positive: egui::Color32,
+    pub negative: egui::Color32,
+    pub background: egui::Color32,
+    /// Absolute threshold and maximum |intensity|
+    pub threshold: f32,
+    pub max_abs: f32,
+    pub levels: f32,
+}
+
+fn rgba(c: egui::Color32) -> [f32; 4] {
+    let [r, g, b, a] = c.to_array();
+    [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a as f32 / 255.0]
+}
+
+impl egui_wgpu::CallbackTrait for ContourCallback {
+    fn prepare(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        _screen_descriptor: &egui_wgpu::ScreenDescriptor,
+        _egui_encoder: &mut wgpu::CommandEncoder,
+        resources: &mut egui_wgpu::CallbackResources,
+    ) -> Vec<wgpu::CommandBuffer> {
+        let Some(res) = resources.get_mut::<GpuResources>() else {
+            return Vec::new();
+        };
+        if let Some((rows, cols, bytes)) = &self.upload {
+            let size = wgpu::Extent3d { width: *cols, height: *rows, depth_or_array_layers: 1 };
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("contour_gpu_matrix"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R32Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                bytes,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * cols),
+                    rows_per_image: Some(*rows),
+                },
+                size,
+            );
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            res.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("contour_gpu"),
+                layout: &res.layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: res.uniforms.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&view) },
+                ],
+            }));
+        }
+
+        let mut u = [0.0f32; UNIFORM_FLOATS];
+        u[0..2].copy_from_slice(&self.uv_min);
+        u[2..4].copy_from_slice(&self.uv_max);
+        u[4..8].copy_from_slice(&rgba(self.positive));
+        u[8..12].copy_from_slice(&rgba(self.negative));
+        u[12..16].copy_from_slice(&rgba(self.background));
+        u[16] = self.threshold;
+        u[17] = self.max_abs;
+        u[18] = self.levels;
+        let bytes: Vec<u8> = u.iter().flat_map(|v| v.to_ne_bytes()).collect();
+        queue.write_buffer(&res.uniforms, 0, &bytes);
+        Vec::new()
+    }
+
+    fn paint(
+        &self,
+        _info: egui::PaintCallbackInfo,
+        render_pass: &mut wgpu::RenderPass<'static>,
+        resources: &egui_wgpu::CallbackResources,
+    ) {
+        let Some(res) = resources.get::<GpuResources>() else {
+            return;
+        };
+        let Some(bind_group) = &res.bind_group else {
+            return;
+        };
+        render_pass.set_pipeline(&res.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// Visible part of a `rows × cols` matrix for a plot showing `min`–`max`
+/// (x = −F2 ppm, y = F1 ppm), as texture coordinates of the plot frame's
+/// top-left and bottom-right corners.  Point `i` sits at `index_to_ppm(i)`,
+/// as in the CPU contours.  `None` without a usable ppm scale.
+pub fn visible_uv(
+    spectrum: &SpectrumData,
+    rows: usize,
+    cols: usize,
+    min: [f64; 2],
+    max: [f64; 2],
+) -> Option<([f32; 2], [f32; 2])> {
+    let (f2, f1) = (spectrum.axes.first()?, spectrum.axes.get(1)?);
+    let (x0, dx) = (-f2.index_to_ppm(0), f2.index_to_ppm(0) - f2.index_to_ppm(1));
+    let (y0, dy) = (f1.index_to_ppm(0), f1.index_to_ppm(1) - f1.index_to_ppm(0));
+    if dx == 0.0 || dy == 0.0 || !dx.is_finite() || !dy.is_finite() {
+        return None;
+    }
+    let u = |x: f64| (((x - x0) / dx + 0.5) / cols as f64) as f32;
+    let v = |y: f64| (((y - y0) / dy + 0.5) / rows as f64) as f32;
+    Some(([u(min[0]), v(max[1])], [u(max[0]), v(min[1])]))
+}
@@ -1,8 +1,347 @@
 /// 2D Contour plot viewer for 2D NMR experiments (COSY, HSQC, HMBC)
 
-use egui_plot::{Line, Plot, PlotPoints, Points, PlotUi};
+use egui_plot::{HLine, Line, MarkerShape, Plot, PlotPoints, Points, PlotUi, Text, VLine};
 
 use crate::data::spectrum::SpectrumData;
+#[cfg(feature = "gpu-contours")]
+use crate::gui::contour_gpu;
+use crate::pipeline::processing::{CrossPeak, VolumeRegion};
+
+/// Shift under the pointer in any of the 2D plots (contour or projections),
+/// shared so every plot can mark the same position.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LinkedCursor {
+    pub f2: Option<f64>,
+    pub f1: Option<f64>,
+}
+
+/// Which of the linked plots a cursor is drawn in
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CursorPlot {
+    /// x = −F2 ppm, y = F1 ppm
+    Contour,
+    /// x = −F2 ppm
+    F2Projection,
+    /// y = F1 ppm
+    F1Projection,
+}
+
+/// A cursor line in plot coordinates; `mirror` marks the symmetric
+/// position of a homonuclear spectrum (F2 shift on the F1 axis and vice versa).
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CursorMark {
+    Vertical { x: f64, mirror: bool },
+    Horizontal { y: f64, mirror: bool },
+}
+
+fn cursor_marks(cursor: LinkedCursor, symmetric: bool, plot: CursorPlot) -> Vec<CursorMark> {
+    let mut marks = Vec::new();
+    let (vertical, horizontal) = match plot {
+        CursorPlot::Contour => (true, true),
+        CursorPlot::F2Projection => (true, false),
+        CursorPlot::F1Projection => (false, true),
+    };
+    if vertical {
+        marks.extend(cursor.f2.map(|f2| CursorMark::Vertical { x: -f2, mirror: false }));
+        if symmetric {
+            marks.extend(cursor.f1.map(|f1| CursorMark::Vertical { x: -f1, mirror: true }));
+        }
+    }
+    if horizontal {
+        marks.extend(cursor.f1.map(|f1| CursorMark::Horizontal { y: f1, mirror: false }));
+        if symmetric {
+            marks.extend(cursor.f2.map(|f2| CursorMark::Horizontal { y: f2, mirror: true }));
+        }
+    }
+    marks
+}
+
+fn draw_cursor(plot_ui: &mut PlotUi, cursor: LinkedCursor, symmetric: bool, plot: CursorPlot) {
+    let solid = egui::Color32::from_rgba_unmultiplied(0xE0, 0x7B, 0x00, 200);
+    let faint = egui::Color32::from_rgba_unmultiplied(0xE0, 0x7B, 0x00, 110);
+    for mark in cursor_marks(cursor, symmetric, plot) {
+        match mark {
+            CursorMark::Vertical { x, mirror } => {
+                let line = VLine::new(x).color(if mirror { faint } else { solid }).width(1.0);
+                plot_ui.vline(if mirror { line.style(egui_plot::LineStyle::dashed_dense()) } else { line });
+            }
+            CursorMark::Horizontal { y, mirror } => {
+                let line = HLine::new(y).color(if mirror { faint } else { solid }).width(1.0);
+                plot_ui.hline(if mirror { line.style(egui_plot::LineStyle::dashed_dense()) } else { line });
+            }
+        }
+    }
+}
+
+/// Picked cross peaks as crosses, with their assignment labels and the
+/// outline of the volume region of integrated peaks
+fn draw_cross_peaks(plot_ui: &mut PlotUi, peaks: &[CrossPeak], region: Option<&VolumeRegion>, color: egui::Color32) {
+    if peaks.is_empty() {
+        return;
+    }
+    if let Some(region) = region {
+        for peak in peaks.iter().filter(|p| p.volume.is_some()) {
+            let outline: Vec<[f64; 2]> =
+                region.outline(peak.f2_ppm, peak.f1_ppm).into_iter().map(|[f2, f1]| [-f2, f1]).collect();
+            plot_ui.line(Line::new(PlotPoints::from(outline)).color(color).width(0.8));
+        }
+    }
+    let marks: Vec<[f64; 2]> = peaks.iter().map(|p| [-p.f2_ppm, p.f1_ppm]).collect();
+    plot_ui.points(
+        Points::new(PlotPoints::from(marks))
+            .shape(MarkerShape::Cross)
+            .color(color)
+            .radius(4.0)
+            .name("Cross peaks"),
+    );
+    for peak in peaks.iter().filter(|p| !p.label.is_empty()) {
+        plot_ui.text(
+            Text::new(
+                [-peak.f2_ppm, peak.f1_ppm].into(),
+                egui::RichText::new(format!(" {}", peak.label)).size(10.0).color(color),
+            )
+            .anchor(egui::Align2::LEFT_BOTTOM),
+        );
+    }
+}
+
+/// Contour level boundaries: `levels + 1` absolute intensities spaced
+/// logarithmically from the threshold to the maximum, as in the GPU shader.
+/// Shared by the colorbar of the view and of 2D exports.
+pub fn contour_levels(threshold_abs: f64, max_abs: f64, levels: usize) -> Vec<f64> {
+    let ratio = (max_abs / threshold_abs).max(1.0);
+    let levels = levels.max(1);
+    (0..=levels)
+        .map(|k| threshold_abs * ratio.powf(k as f64 / levels as f64))
+        .collect()
+}
+
+/// Contour levels fixed in absolute intensity rather than relative to the
+/// spectrum's maximum, so that several 2D spectra (e.g. before/after) are
+/// drawn on one scale and equal contours mean equal intensity
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LockedLevels {
+    /// Lowest contour, in data units
+    pub base: f64,
+    /// Ratio between successive contours
+    pub multiplier: f64,
+}
+
+impl LockedLevels {
+    /// The levels currently drawn for a spectrum whose largest |intensity|
+    /// is `max_abs`: relative `threshold`, `levels` bands up to the maximum
+    pub fn from_relative(threshold: f64, max_abs: f64, levels: usize) -> Self {
+        let multiplier = (1.0 / threshold).max(1.0).powf(1.0 / levels.max(1) as f64);
+        Self { base: threshold * max_abs, multiplier: multiplier.max(1.01) }
+    }
+
+    /// Top of the highest of `levels` bands; stronger signals stay in it
+    pub fn top(&self, levels: usize) -> f64 {
+        self.base * self.multiplier.powi(levels.max(1) as i32)
+    }
+}
+
+/// Band (0-based, below `levels`) that |intensity| `a` above the threshold falls in
+fn level_index(a: f64, threshold_abs: f64, max_abs: f64, levels: usize) -> usize {
+    let span = (max_abs / threshold_abs).ln();
+    let t = if span > 0.0 { (a / threshold_abs).ln() / span * levels as f64 } else { 0.0 };
+    (t.max(0.0) as usize).min(levels.max(1) - 1)
+}
+
+/// Colour of a band `fraction` (0–1) of the way up the levels: the sign
+/// colour blended over the background, as the GPU heatmap fills it
+pub fn level_color(color: egui::Color32, background: egui::Color32, fraction: f64) -> egui::Color32 {
+    let alpha = 0.12 + 0.5 * fraction.clamp(0.0, 1.0);
+    let mix = |c: u8, b: u8| (b as f64 + (c as f64 - b as f64) * alpha).round() as u8;
+    egui::Color32::from_rgb(
+        mix(color.r(), background.r()),
+        mix(color.g(), background.g()),
+        mix(color.b(), background.b()),
+    )
+}
+
+/// Intensity label in scientific notation on the absolute data scale, e.g. `1.25e6`
+pub fn format_intensity(value: f64) -> String {
+    format!("{:.2e}", value)
+}
+
+/// Level boundaries to label on a colorbar: at most `max_labels`, always
+/// including the threshold and the maximum
+fn colorbar_labels(levels: &[f64], max_labels: usize) -> Vec<(usize, String)> {
+    let n = levels.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let step = n.div_ceil(max_labels.max(2)).max(1);
+    let mut picked: Vec<usize> = (0..n).step_by(step).collect();
+    if picked.last() != Some(&(n - 1)) {
+        if picked.len() >= max_labels.max(2) {
+            picked.pop();
+        }
+        picked.push(n - 1);
+    }
+    picked.into_iter().map(|k| (k, format_intensity(levels[k]))).collect()
+}
+
+/// Draw the colorbar in the top-right corner of the contour plot: one band
+/// per contour level for positive (and, when present, negative) intensities
+fn draw_colorbar(
+    ui: &egui::Ui,
+    plot_rect: egui::Rect,
+    state: &ContourViewState,
+    threshold_abs: f64,
+    max_abs: f64,
+    has_negative: bool,
+) {
+    let levels = contour_levels(threshold_abs, max_abs, state.num_levels);
+    let bands = levels.len() - 1;
+    let background = ui.visuals().extreme_bg_color;
+    let text_color = ui.visuals().text_color();
+    let font = egui::FontId::proportional(10.0);
+    let (bar_w, label_w, pad) = (12.0, 58.0, 6.0);
+    let bars = if has_negative { 2.0 } else { 1.0 };
+    let height = (plot_rect.height() * 0.5).clamp(60.0, 220.0);
+    let frame = egui::Rect::from_min_size(
+        egui::pos2(plot_rect.right() - pad * 3.0 - label_w - bars * bar_w, plot_rect.top() + pad),
+        egui::vec2(label_w + bars * bar_w + pad * 2.0, height + 26.0),
+    );
+    let painter = ui.painter_at(plot_rect);
+    painter.rect_filled(frame, 3.0, background.gamma_multiply(0.85));
+    painter.text(
+        frame.center_top() + egui::vec2(0.0, 3.0),
+        egui::Align2::CENTER_TOP,
+        if has_negative { "±|I|" } else { "|I|" },
+        font.clone(),
+        text_color,
+    );
+
+    let bar_top = frame.top() + 18.0;
+    let bar_left = frame.right() - pad - bars * bar_w;
+    let band_h = height / bands as f32;
+    let y_of = |k: usize| bar_top + height - k as f32 * band_h;
+    let mut colors = vec![state.positive_color];
+    if has_negative {
+        colors.push(state.negative_color);
+    }
+    for (i, color) in colors.into_iter().enumerate() {
+        let x = bar_left + i as f32 * bar_w;
+        for k in 0..bands {
+            let band = egui::Rect::from_x_y_ranges(x..=x + bar_w, y_of(k + 1)..=y_of(k));
+            painter.rect_filled(band, 0.0, level_color(color, background, (k as f64 + 0.5) / bands as f64));
+        }
+    }
+    let bar = egui::Rect::from_x_y_ranges(bar_left..=bar_left + bars * bar_w, bar_top..=bar_top + height);
+    painter.rect_stroke(bar, 0.0, egui::Stroke::new(0.5, text_color), egui::StrokeKind::Outside);
+    for (k, label) in colorbar_labels(&levels, 6) {
+        let y = y_of(k);
+        painter.line_segment([egui::pos2(bar_left - 3.0, y), egui::pos2(bar_left, y)], egui::Stroke::new(0.5, text_color));
+        painter.text(egui::pos2(bar_left - 5.0, y), egui::Align2::RIGHT_CENTER, label, font.clone(), text_color);
+    }
+}
+
+/// Points above the threshold bucketed by contour level, one colour per band
+fn draw_level_points(plot_ui: &mut PlotUi, buckets: &[Vec<[f64; 2]>], color: egui::Color32, background: egui::Color32, name: &str) {
+    for (k, pts) in buckets.iter().enumerate().filter(|(_, p)| !p.is_empty()) {
+        let fraction = (k as f64 + 0.5) / buckets.len() as f64;
+        plot_ui.points(
+            Points::new(PlotPoints::from(pts.clone()))
+                .name(name)
+                .color(level_color(color, background, fraction))
+                .radius(1.5),
+        );
+    }
+}
+
+/// Whether this matrix is drawn by the GPU renderer
+#[cfg(feature = "gpu-contours")]
+fn use_gpu(state: &ContourViewState, spectrum: &SpectrumData, n_rows: usize, n_cols: usize) -> bool {
+    state.gpu
+        && spectrum.axes.len() >= 2
+        && contour_gpu::supports(n_rows, n_cols)
+        && spectrum.data_2d.iter().all(|r| r.len() >= n_cols)
+}
+
+#[cfg(not(feature = "gpu-contours"))]
+fn use_gpu(_: &ContourViewState, _: &SpectrumData, _: usize, _: usize) -> bool {
+    false
+}
+
+/// Fill the reserved `slot` under the plot items with GPU contours of the
+/// region the plot shows, uploading the matrix if it changed
+#[cfg(feature = "gpu-contours")]
+fn paint_gpu(
+    ui: &egui::Ui,
+    slot: egui::layers::ShapeIdx,
+    transform: &egui_plot::PlotTransform,
+    spectrum: &SpectrumData,
+    state: &mut ContourViewState,
+    (threshold_abs, max_abs): (f64, f64),
+) {
+    let (n_rows, n_cols) = (spectrum.data_2d.len(), spectrum.data_2d[0].len());
+    let bounds = transform.bounds();
+    let Some((uv_min, uv_max)) = contour_gpu::visible_uv(spectrum, n_rows, n_cols, bounds.min(), bounds.max())
+    else {
+        return;
+    };
+    let key = contour_gpu::fingerprint(&spectrum.data_2d);
+    let upload = (state.gpu_key != Some(key)).then(|| {
+        state.gpu_key = Some(key);
+        let bytes = contour_gpu::matrix_bytes(&spectrum.data_2d, n_cols);
+        (n_rows as u32, n_cols as u32, std::sync::Arc::new(bytes))
+    });
+    let callback = contour_gpu::ContourCallback {
+        upload,
+        uv_min,
+        uv_max,
+        positive: state.positive_color,
+        negative: state.negative_color,
+        background: ui.visuals().extreme_bg_color,
+        threshold: threshold_abs as f32,
+        max_abs: max_abs as f32,
+        levels: state.num_levels as f32,
+    };
+    ui.painter().set(
+        slot,
+        eframe::egui_wgpu::Callback::new_paint_callback(*transform.frame(), callback),
+    );
+}
+
+#[cfg(not(feature = "gpu-contours"))]
+fn paint_gpu(
+    _: &egui::Ui,
+    _: egui::layers::ShapeIdx,
+    _: &egui_plot::PlotTransform,
+    _: &SpectrumData,
+    _: &mut ContourViewState,
+    _: (f64, f64),
+) {
+}
+
+/// What the margin plots beside the contour show
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProjectionMode {
+    /// Largest |intensity| along each column (F2) or row (F1)
+    #[default]
+    Skyline,
+    /// Signed sum along each column or row
+    Sum,
+    /// 1D spectra of the session with the nucleus of each axis
+    External,
+}
+
+impl ProjectionMode {
+    pub const ALL: [ProjectionMode; 3] = [ProjectionMode::Skyline, ProjectionMode::Sum, ProjectionMode::External];
+}
+
+impl std::fmt::Display for ProjectionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProjectionMode::Skyline => write!(f, "Skyline"),
+            ProjectionMode::Sum => write!(f, "Sum"),
+            ProjectionMode::External => write!(f, "1D spectra"),
+        }
+    }
+}
 
 /// State for the 2D contour viewer
 #[derive(Debug, Clone)]
@@ -12,6 +351,35 @@ pub struct ContourViewState {
     pub positive_color: egui::Color32,
     pub negative_color: egui::Color32,
     pub show_projections: bool,
+    pub projection_mode: ProjectionMode,
+    /// Row and column through the cursor, drawn in the margins
+    pub show_traces: bool,
+    /// Colorbar with the contour level intensities
+    pub show_colorbar: bool,
+    /// Cursor linked across the contour and projection plots
+    pub cursor: LinkedCursor,
+    /// Picked cross peaks (the 2D correlation table)
+    pub cross_peaks: Vec<CrossPeak>,
+    /// Region the cross-peak volumes were last integrated over
+    pub volume_region: Option<VolumeRegion>,
+    /// Absolute contour levels shared by every 2D spectrum, instead of the
+    /// relative threshold
+    pub locked_levels: Option<LockedLevels>,
+    /// Draw on the GPU when the wgpu renderer is available
+    pub gpu: bool,
+    /// Fingerprint of the matrix last uploaded to the GPU
+    pub gpu_key: Option<u64>,
+}
+
+impl ContourViewState {
+    /// Lowest contour and top of the highest band, in data units, for a
+    /// spectrum whose largest |intensity| is `max_abs`
+    pub fn level_range(&self, max_abs: f64) -> (f64, f64) {
+        match self.locked_levels {
+            Some(locked) => (locked.base, locked.top(self.num_levels)),
+            None => (self.threshold * max_abs, max_abs),
+        }
+    }
 }
 
 impl Default for ContourViewState {
@@ -22,66 +390,121 @@ impl Default for ContourViewState {
             positive_color: egui::Color32::from_rgb(0x1A, 0x47, 0x80),
             negative_color: egui::Color32::from_rgb(0xB8, 0x3A, 0x3A),
             show_projections: true,
+            projection_mode: ProjectionMode::default(),
+            show_traces: true,
+            show_colorbar: true,
+            cursor: LinkedCursor::default(),
+            cross_peaks: Vec::new(),
+            volume_region: None,
+            locked_levels: None,
+            gpu: true,
+            gpu_key: None,
         }
     }
 }
 
-/// Compute the F2 projection (max absolute value per column) and F1 projection (per row).
+/// ppm of a column (F2) of the matrix, or its index without an axis
+fn f2_ppm(spectrum: &SpectrumData, col: usize) -> f64 {
+    spectrum.axes.first().map_or(col as f64, |a| a.index_to_ppm(col))
+}
+
+/// ppm of a row (F1) of the matrix, or its index without an axis
+fn f1_ppm(spectrum: &SpectrumData, row: usize) -> f64 {
+    spectrum.axes.get(1).map_or(row as f64, |a| a.index_to_ppm(row))
+}
+
+/// Compute the F2 projection (one value per column) and F1 projection (per
+/// row), as the skyline (largest absolute value) or the signed sum.
 ///
 /// F2 projection returns `[-ppm_x, intensity]` — X matches contour X, Y = intensity.
 /// F1 projection returns `[intensity, ppm_y]` — Y matches contour Y, X = intensity.
-fn compute_projections(spectrum: &SpectrumData) -> (Vec<[f64; 2]>, Vec<[f64; 2]>) {
+fn compute_projections(spectrum: &SpectrumData, mode: ProjectionMode) -> (Vec<[f64; 2]>, Vec<[f64; 2]>) {
     let n_rows = spectrum.data_2d.len();
     if n_rows == 0 {
         return (Vec::new(), Vec::new());
     }
     let n_cols = spectrum.data_2d[0].len();
+    let reduce = |values: &mut dyn Iterator<Item = f64>| match mode {
+        ProjectionMode::Sum => values.sum(),
+        _ => values.map(f64::abs).fold(0.0f64, f64::max),
+    };
 
-    // F2 projection: for each column, take the max across all rows
-    let mut f2_proj = Vec::with_capacity(n_cols);
-    for col_idx in 0..n_cols {
-        let mut max_val = 0.0f64;
-        for row in &spectrum.data_2d {
-            if col_idx < row.len() {
-                let v = row[col_idx].abs();
-                if v > max_val {
-                    max_val = v;
-                }
-            }
-        }
-        let x = if !spectrum.axes.is_empty() {
-            spectrum.axes[0].index_to_ppm(col_idx)
-        } else {
-            col_idx as f64
-        };
-        // X = -ppm (matches contour X), Y = intensity
-        f2_proj.push([-x, max_val]);
-    }
+    let f2_proj = (0..n_cols)
+        .map(|col| {
+            let value = reduce(&mut spectrum.data_2d.iter().filter_map(|row| row.get(col).copied()));
+            [-f2_ppm(spectrum, col), value]
+        })
+        .collect();
+    let f1_proj = spectrum
+        .data_2d
+        .iter()
+        .enumerate()
+        .map(|(row, values)| [reduce(&mut values.iter().copied()), f1_ppm(spectrum, row)])
+        .collect();
+    (f2_proj, f1_proj)
+}
 
-    // F1 projection: for each row, take the max across all columns
-    let mut f1_proj = Vec::with_capacity(n_rows);
-    for row_idx in 0..n_rows {
-        let max_val = spectrum.data_2d[row_idx]
-            .iter()
-            .map(|v| v.abs())
-            .fold(0.0f64, f64::max);
-        let y = if spectrum.axes.len() >= 2 {
-            spectrum.axes[1].index_to_ppm(row_idx)
-        } else {
-            row_idx as f64
-        };
-        // X = intensity, Y = +ppm (matches contour Y)
-        f1_proj.push([max_val, y]);
+/// A 1D spectrum along one axis of the matrix, over that axis' ppm range,
+/// in the layout of [`compute_projections`] (`f1`: `[intensity, ppm]`)
+fn external_projection(spectrum: &SpectrumData, reference: &SpectrumData, f1: bool) -> Vec<[f64; 2]> {
+    let (n, ppm_of): (usize, fn(&SpectrumData, usize) -> f64) = if f1 {
+        (spectrum.data_2d.len(), f1_ppm)
+    } else {
+        (spectrum.data_2d.first().map_or(0, Vec::len), f2_ppm)
+    };
+    if n == 0 {
+        return Vec::new();
     }
+    let (a, b) = (ppm_of(spectrum, 0), ppm_of(spectrum, n - 1));
+    let (lo, hi) = (a.min(b), a.max(b));
+    reference
+        .x_scale()
+        .into_iter()
+        .zip(&reference.real)
+        .filter(|(ppm, _)| (lo..=hi).contains(ppm))
+        .map(|(ppm, &v)| if f1 { [v, ppm] } else { [-ppm, v] })
+        .collect()
+}
 
-    (f2_proj, f1_proj)
+/// Row of the matrix nearest `f1` ppm, as `[-F2 ppm, intensity]`
+fn row_trace(spectrum: &SpectrumData, f1: f64) -> Option<Vec<[f64; 2]>> {
+    let pos = spectrum.axes.get(1)?.ppm_to_position(f1)?.round();
+    let row = spectrum.data_2d.get(usize::try_from(pos as i64).ok()?)?;
+    Some(row.iter().enumerate().map(|(col, &v)| [-f2_ppm(spectrum, col), v]).collect())
+}
+
+/// Column of the matrix nearest `f2` ppm, as `[intensity, F1 ppm]`
+fn column_trace(spectrum: &SpectrumData, f2: f64) -> Option<Vec<[f64; 2]>> {
+    let pos = spectrum.axes.first()?.ppm_to_position(f2)?.round();
+    let col = usize::try_from(pos as i64).ok()?;
+    spectrum
+        .data_2d
+        .iter()
+        .enumerate()
+        .map(|(row, values)| values.get(col).map(|&v| [v, f1_ppm(spectrum, row)]))
+        .collect()
+}
+
+/// Scale the intensities (coordinate `k` of each point) to a tallest
+/// |value| of 1, so projections, references and traces share a margin
+fn unit_height(mut points: Vec<[f64; 2]>, k: usize) -> Vec<[f64; 2]> {
+    let max = points.iter().fold(0.0f64, |m, p| m.max(p[k].abs()));
+    if max > 0.0 {
+        for p in &mut points {
+            p[k] /= max;
+        }
+    }
+    points
 }
 
 /// Show a 2D spectrum as a scatter/contour plot with 1D projections on axes.
+/// `references` are 1D spectra for the F2 and F1 margins in
+/// [`ProjectionMode::External`]; an axis without one shows its skyline.
 /// Returns `true` if the user clicked the "2D FT" button (time-domain only).
 pub fn show_spectrum_2d(
     ui: &mut egui::Ui,
     spectrum: &SpectrumData,
+    references: [Option<&SpectrumData>; 2],
     state: &mut ContourViewState,
 ) -> bool {
     let mut request_ft = false;
@@ -101,6 +524,8 @@ pub fn show_spectrum_2d(
     };
 
     // Controls row
+    let mut lock = state.locked_levels.is_some();
+    let mut lock_toggled = false;
     ui.horizontal(|ui| {
         ui.label(format!("{} | 2D ({}×{})", spectrum.experiment_type, n_rows, n_cols));
         ui.separator();
@@ -115,21 +540,81 @@ pub fn show_spectrum_2d(
             }
             ui.separator();
         }
-        ui.add(
-            egui::Slider::new(&mut state.threshold, 0.01..=1.0)
-                .text("Threshold")
-                .logarithmic(true)
-                .fixed_decimals(3),
-        );
+        match &mut state.locked_levels {
+            Some(locked) => {
+                let speed = locked.base * 0.01;
+                ui.label("Base");
+                ui.add(
+                    egui::DragValue::new(&mut locked.base)
+                        .speed(speed)
+                        .range(f64::MIN_POSITIVE..=f64::MAX)
+                        .custom_formatter(|v, _| format_intensity(v))
+                        .custom_parser(|text| text.trim().parse().ok()),
+                )
+                .on_hover_text("Lowest contour, in data units");
+                ui.label("×");
+                ui.add(egui::DragValue::new(&mut locked.multiplier).speed(0.01).range(1.01..=4.0).fixed_decimals(2))
+                    .on_hover_text("Ratio between successive contours");
+            }
+            None => {
+                ui.add(
+                    egui::Slider::new(&mut state.threshold, 0.01..=1.0)
+                        .text("Threshold")
+                        .logarithmic(true)
+                        .fixed_decimals(3),
+                );
+            }
+        }
         ui.separator();
         ui.add(
             egui::Slider::new(&mut state.num_levels, 2..=20)
                 .text("Levels"),
         );
+        lock_toggled = ui
+            .checkbox(&mut lock, "🔒 Absolute")
+            .on_hover_text(
+                "Keep the contour levels at fixed intensities for every 2D spectrum, \
+                 so cross peaks of spectra recorded and processed alike compare directly",
+            )
+            .changed();
         ui.separator();
         ui.checkbox(&mut state.show_projections, "Projections");
+        if state.show_projections {
+            egui::ComboBox::from_id_salt("projection_mode")
+                .selected_text(state.projection_mode.to_string())
+                .show_ui(ui, |ui| {
+                    for mode in ProjectionMode::ALL {
+                        ui.selectable_value(&mut state.projection_mode, mode, mode.to_string());
+                    }
+                })
+                .response
+                .on_hover_text("1D spectra: processed spectra of the session with the nucleus of each axis");
+            ui.checkbox(&mut state.show_traces, "Traces")
+                .on_hover_text("Row and column through the cursor, in the margins");
+        }
+        ui.checkbox(&mut state.show_colorbar, "Colorbar");
+        #[cfg(feature = "gpu-contours")]
+        if contour_gpu::available() {
+            ui.checkbox(&mut state.gpu, "GPU")
+                .on_hover_text("Draw contours on the GPU (heatmap + contour lines)");
+        }
+        let fmt = |v: Option<f64>| v.map(|p| format!("{:.3}", p)).unwrap_or_else(|| "—".to_string());
+        if state.cursor != LinkedCursor::default() {
+            ui.separator();
+            ui.label(format!("F2 {}  F1 {}", fmt(state.cursor.f2), fmt(state.cursor.f1)));
+        }
     });
 
+    // Homonuclear spectra (COSY, TOCSY, NOESY) are symmetric about the
+    // diagonal, so a shift on one axis is also marked on the other
+    let symmetric = spectrum.axes.len() >= 2 && spectrum.axes[0].nucleus == spectrum.axes[1].nucleus;
+    let cursor = state.cursor;
+    // Copied: the GPU painter below needs `state` mutably
+    let cross_peaks = state.cross_peaks.clone();
+    let volume_region = state.volume_region;
+    let peak_col = ui.visuals().strong_text_color();
+    let mut hovered = LinkedCursor::default();
+
     // Find the maximum value for normalization
     let max_val = spectrum
         .data_2d
@@ -143,15 +628,29 @@ pub fn show_spectrum_2d(
         return false;
     }
 
-    let threshold_abs = state.threshold * max_val;
+    // Locking keeps the levels now drawn; unlocking keeps the base level
+    if lock_toggled {
+        state.locked_levels = match state.locked_levels {
+            None => Some(LockedLevels::from_relative(state.threshold, max_val, state.num_levels)),
+            Some(locked) => {
+                state.threshold = (locked.base / max_val).clamp(0.01, 1.0);
+                None
+            }
+        };
+    }
+    let (threshold_abs, level_top) = state.level_range(max_val);
+    let gpu = use_gpu(state, spectrum, n_rows, n_cols);
 
-    // Collect points above threshold
+    // Collect points above threshold, bucketed by contour level
     // X axis: -ppm so high ppm is on the LEFT (NMR convention)
     // Y axis: +ppm so high ppm is at the TOP (NMR convention)
-    let mut pos_points: Vec<[f64; 2]> = Vec::new();
-    let mut neg_points: Vec<[f64; 2]> = Vec::new();
+    let num_levels = state.num_levels.max(1);
+    let mut pos_points: Vec<Vec<[f64; 2]>> = vec![Vec::new(); num_levels];
+    let mut neg_points: Vec<Vec<[f64; 2]>> = vec![Vec::new(); num_levels];
 
-    for row_idx in 0..n_rows {
+    // The GPU renderer draws straight from the matrix
+    let cpu_rows = if gpu { 0 } else { n_rows };
+    for row_idx in 0..cpu_rows {
         for col_idx in 0..n_cols {
             let val = spectrum.data_2d[row_idx][col_idx];
             if val.abs() > threshold_abs {
@@ -166,10 +665,11 @@ pub fn show_spectrum_2d(
                     row_idx as f64
                 };
 
+                let level = level_index(val.abs(), threshold_abs, level_top, num_levels);
                 if val > 0.0 {
-                    pos_points.push([-x, y]);
+                    pos_points[level].push([-x, y]);
                 } else {
-                    neg_points.push([-x, y]);
+                    neg_points[level].push([-x, y]);
                 }
             }
         }
@@ -187,12 +687,31 @@ pub fn show_spectrum_2d(
         "F1 (points)".to_string()
     };
 
-    // Compute projections
+    // Compute projections, scaled to a common height in the margins
     let (f2_proj, f1_proj) = if state.show_projections {
-        compute_projections(spectrum)
+        let (skyline_f2, skyline_f1) = compute_projections(spectrum, state.projection_mode);
+        let external = |reference: Option<&SpectrumData>, f1: bool| {
+            reference
+                .filter(|_| state.projection_mode == ProjectionMode::External)
+                .map(|r| external_projection(spectrum, r, f1))
+                .filter(|p| !p.is_empty())
+        };
+        (
+            unit_height(external(references[0], false).unwrap_or(skyline_f2), 1),
+            unit_height(external(references[1], true).unwrap_or(skyline_f1), 0),
+        )
     } else {
         (Vec::new(), Vec::new())
     };
+    // Traces through the cursor, on the scale of the skyline (the matrix maximum)
+    let traces = state.show_projections && state.show_traces;
+    let scale = |mut trace: Vec<[f64; 2]>, k: usize| {
+        trace.iter_mut().for_each(|p| p[k] /= max_val);
+        trace
+    };
+    let row = cursor.f1.filter(|_| traces).and_then(|f1| row_trace(spectrum, f1)).map(|t| scale(t, 1));
+    let column = cursor.f2.filter(|_| traces).and_then(|f2| column_trace(spectrum, f2)).map(|t| scale(t, 0));
+    let trace_col = egui::Color32::from_rgb(0xE0, 0x7B, 0x00);
 
     let has_axes = !spectrum.axes.is_empty();
     let has_y_axis = spectrum.axes.len() >= 2;
@@ -208,6 +727,12 @@ pub fn show_spectrum_2d(
 
     let pos_col = state.positive_color;
     let neg_col = state.negative_color;
+    let background = ui.visuals().extreme_bg_color;
+    let has_negative = if gpu {
+        state.show_colorbar && spectrum.data_2d.iter().flatten().any(|&v| v < -threshold_abs)
+    } else {
+        neg_points.iter().any(|b| !b.is_empty())
+    };
 
     if state.show_projections {
         let proj_height = 100.0;
@@ -242,18 +767,71 @@ pub fn show_spectrum_2d(
             .link_axis(link_id, [true, false]);
 
         let f2_data = f2_proj.clone();
-        f2_plot.show(ui, |plot_ui: &mut PlotUi| {
-            if !f2_data.is_empty() {
-                let line = Line::new(PlotPoints::from(f2_data))
-                    .color(egui::Color32::from_rgb(0x40, 0x80, 0xC0))
-                    .width(1.0)
-                    .name("F2 projection");
-                plot_ui.line(line);
-            }
-        });
+        let f2_hover = ui
+            .horizontal(|ui| {
+                // Line up with the contour, right of the F1 margin
+                if has_y_axis {
+                    ui.add_space(proj_width + ui.spacing().item_spacing.x);
+                }
+                f2_plot.show(ui, |plot_ui: &mut PlotUi| {
+                    if !f2_data.is_empty() {
+                        let line = Line::new(PlotPoints::from(f2_data))
+                            .color(egui::Color32::from_rgb(0x40, 0x80, 0xC0))
+                            .width(1.0)
+                            .name("F2 projection");
+                        plot_ui.line(line);
+                    }
+                    if let Some(row) = row {
+                        plot_ui.line(Line::new(PlotPoints::from(row)).color(trace_col).width(1.0).name("Row"));
+                    }
+                    draw_cursor(plot_ui, cursor, symmetric, CursorPlot::F2Projection);
+                    plot_ui.pointer_coordinate()
+                })
+            })
+            .inner;
+        if let Some(p) = f2_hover.inner {
+            hovered.f2 = Some(-p.x);
+        }
 
-        // ── Bottom row: main contour + F1 projection ──
+        // ── Bottom row: F1 projection + main contour ──
         ui.horizontal(|ui| {
+            // F1 projection (left side), intensity growing to the left
+            if has_y_axis {
+                let f1_plot = Plot::new("f1_projection")
+                    .height(main_h)
+                    .width(proj_width)
+                    .show_axes([false, false])
+                    .show_grid([false, false])
+                    .allow_drag([false, true])
+                    .allow_zoom([false, true])
+                    .allow_scroll([false, true])
+                    .allow_boxed_zoom(false)
+                    .x_axis_label("")
+                    .y_axis_label("")
+                    .link_axis(link_id, [false, true]);
+
+                let mirror = |points: Vec<[f64; 2]>| points.into_iter().map(|[v, ppm]| [-v, ppm]).collect::<Vec<_>>();
+                let f1_data = mirror(f1_proj.clone());
+                let column = column.map(mirror);
+                let f1_hover = f1_plot.show(ui, |plot_ui: &mut PlotUi| {
+                    if !f1_data.is_empty() {
+                        let line = Line::new(PlotPoints::from(f1_data))
+                            .color(egui::Color32::from_rgb(0x40, 0x80, 0xC0))
+                            .width(1.0)
+                            .name("F1 projection");
+                        plot_ui.line(line);
+                    }
+                    if let Some(column) = column {
+                        plot_ui.line(Line::new(PlotPoints::from(column)).color(trace_col).width(1.0).name("Column"));
+                    }
+                    draw_cursor(plot_ui, cursor, symmetric, CursorPlot::F1Projection);
+                    plot_ui.pointer_coordinate()
+                });
+                if let Some(p) = f1_hover.inner {
+                    hovered.f1 = Some(p.y);
+                }
+            }
+
             // Main 2D contour plot
             let mut main_plot = Plot::new("spectrum_2d")
                 .height(main_h)
@@ -266,6 +844,7 @@ pub fn show_spectrum_2d(
                 .allow_scroll(true)
                 .allow_boxed_zoom(true)
                 .show_grid([true, true])
+                .show_background(!gpu)
                 .link_axis(link_id, [true, true]);
 
             if has_axes {
@@ -277,49 +856,22 @@ pub fn show_spectrum_2d(
 
             let pos_pts = pos_points.clone();
             let neg_pts = neg_points.clone();
-            main_plot.show(ui, |plot_ui: &mut PlotUi| {
-                if !pos_pts.is_empty() {
-                    let pts = Points::new(PlotPoints::from(pos_pts))
-                        .name("Positive")
-                        .color(pos_col)
-                        .radius(1.5);
-                    plot_ui.points(pts);
-                }
-                if !neg_pts.is_empty() {
-                    let pts = Points::new(PlotPoints::from(neg_pts))
-                        .name("Negative")
-                        .color(neg_col)
-                        .radius(1.5);
-                    plot_ui.points(pts);
-                }
+            let gpu_slot = ui.painter().add(egui::Shape::Noop);
+            let main_hover = main_plot.show(ui, |plot_ui: &mut PlotUi| {
+                draw_level_points(plot_ui, &pos_pts, pos_col, background, "Positive");
+                draw_level_points(plot_ui, &neg_pts, neg_col, background, "Negative");
+                draw_cross_peaks(plot_ui, &cross_peaks, volume_region.as_ref(), peak_col);
+                draw_cursor(plot_ui, cursor, symmetric, CursorPlot::Contour);
+                plot_ui.pointer_coordinate()
             });
-
-            // F1 projection (right side)
-            if has_y_axis {
-                let f1_plot = Plot::new("f1_projection")
-                    .height(main_h)
-                    .width(proj_width)
-                    .show_axes([false, false])
-                    .show_grid([false, false])
-                    .allow_drag([false, true])
-                    .allow_zoom([false, true])
-                    .allow_scroll([false, true])
-                    .allow_boxed_zoom(false)
-                    .x_axis_label("")
-                    .y_axis_label("")
-                    .link_axis(link_id, [false, true]);
-
-                let f1_data = f1_proj.clone();
-                f1_plot.show(ui, |plot_ui: &mut PlotUi| {
-                    if !f1_data.is_empty() {
-                        // Data is already [intensity, ppm_y]
-                        let line = Line::new(PlotPoints::from(f1_data))
-                            .color(egui::Color32::from_rgb(0x40, 0x80, 0xC0))
-                            .width(1.0)
-                            .name("F1 projection");
-                        plot_ui.line(line);
-                    }
-                });
+            if let Some(p) = main_hover.inner {
+                hovered = LinkedCursor { f2: Some(-p.x), f1: Some(p.y) };
+            }
+            if gpu {
+                paint_gpu(ui, gpu_slot, &main_hover.transform, spectrum, state, (threshold_abs, level_top));
+            }
+            if state.show_colorbar {
+                draw_colorbar(ui, main_hover.response.rect, state, threshold_abs, level_top, has_negative);
             }
         });
     } else {
@@ -333,7 +885,8 @@ pub fn show_spectrum_2d(
             .allow_zoom(true)
             .allow_scroll(true)
             .allow_boxed_zoom(true)
-            .show_grid([true, true]);
+            .show_grid([true, true])
+            .show_background(!gpu);
 
         if has_axes {
             plot = plot.x_axis_formatter(x_fmt);
@@ -342,23 +895,158 @@ pub fn show_spectrum_2d(
             }
         }
 
-        plot.show(ui, |plot_ui: &mut PlotUi| {
-            if !pos_points.is_empty() {
-                let pts = Points::new(PlotPoints::from(pos_points))
-                    .name("Positive")
-                    .color(pos_col)
-                    .radius(1.5);
-                plot_ui.points(pts);
-            }
-            if !neg_points.is_empty() {
-                let pts = Points::new(PlotPoints::from(neg_points))
-                    .name("Negative")
-                    .color(neg_col)
-                    .radius(1.5);
-                plot_ui.points(pts);
-            }
+        let gpu_slot = ui.painter().add(egui::Shape::Noop);
+        let main_hover = plot.show(ui, |plot_ui: &mut PlotUi| {
+            draw_level_points(plot_ui, &pos_points, pos_col, background, "Positive");
+            draw_level_points(plot_ui, &neg_points, neg_col, background, "Negative");
+            draw_cross_peaks(plot_ui, &cross_peaks, volume_region.as_ref(), peak_col);
+            draw_cursor(plot_ui, cursor, symmetric, CursorPlot::Contour);
+            plot_ui.pointer_coordinate()
         });
+        if let Some(p) = main_hover.inner {
+            hovered = LinkedCursor { f2: Some(-p.x), f1: Some(p.y) };
+        }
+        if gpu {
+            paint_gpu(ui, gpu_slot, &main_hover.transform, spectrum, state, (threshold_abs, level_top));
+        }
+        if state.show_colorbar {
+            draw_colorbar(ui, main_hover.response.rect, state, threshold_abs, level_top, has_negative);
+        }
+    }
+
+    // Drawn next frame in every plot; repaint now so the cursors keep up
+    if hovered != state.cursor {
+        state.cursor = hovered;
+        ui.ctx().request_repaint();
     }
 
     request_ft
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linked_cursor_marks_mirror_homonuclear_axes() {
+        let cursor = LinkedCursor { f2: Some(7.2), f1: None };
+        // Hovering the F2 projection marks the contour column and, for a
+        // homonuclear spectrum, the matching F1 row
+        assert_eq!(
+            cursor_marks(cursor, true, CursorPlot::Contour),
+            vec![
+                CursorMark::Vertical { x: -7.2, mirror: false },
+                CursorMark::Horizontal { y: 7.2, mirror: true },
+            ]
+        );
+        assert_eq!(
+            cursor_marks(cursor, true, CursorPlot::F1Projection),
+            vec![CursorMark::Horizontal { y: 7.2, mirror: true }]
+        );
+        // Heteronuclear (HSQC/HMBC): no mirror lines
+        assert!(cursor_marks(cursor, false, CursorPlot::F1Projection).is_empty());
+
+        let both = LinkedCursor { f2: Some(3.5), f1: Some(55.0) };
+        assert_eq!(
+            cursor_marks(both, false, CursorPlot::Contour),
+            vec![
+                CursorMark::Vertical { x: -3.5, mirror: false },
+                CursorMark::Horizontal { y: 55.0, mirror: false },
+            ]
+        );
+        assert_eq!(cursor_marks(both, false, CursorPlot::F2Projection), vec![CursorMark::Vertical { x: -3.5, mirror: false }]);
+    }
+
+    #[test]
+    fn test_projections_and_traces_of_the_matrix() {
+        use crate::data::spectrum::AxisParams;
+
+        // 3 rows (F1 20 → 10 ppm) × 4 columns (F2 8 → 2 ppm)
+        let axis = |n: usize, sw_ppm: f64, reference_ppm: f64| AxisParams {
+            num_points: n,
+            spectral_width_hz: sw_ppm * 100.0,
+            observe_freq_mhz: 100.0,
+            reference_ppm,
+            ..Default::default()
+        };
+        let spectrum = SpectrumData {
+            data_2d: vec![vec![1.0, -4.0, 0.0, 2.0], vec![0.5, 3.0, 0.0, -1.0], vec![0.0, 1.0, 0.0, 0.0]],
+            axes: vec![axis(4, 8.0, 8.0), axis(3, 15.0, 20.0)],
+            is_frequency_domain: true,
+            ..Default::default()
+        };
+
+        let (f2, f1) = compute_projections(&spectrum, ProjectionMode::Skyline);
+        assert_eq!(f2, vec![[-8.0, 1.0], [-6.0, 4.0], [-4.0, 0.0], [-2.0, 2.0]]);
+        assert_eq!(f1, vec![[4.0, 20.0], [3.0, 15.0], [1.0, 10.0]]);
+        let (f2, f1) = compute_projections(&spectrum, ProjectionMode::Sum);
+        assert_eq!(f2.iter().map(|p| p[1]).collect::<Vec<_>>(), vec![1.5, 0.0, 0.0, 1.0]);
+        assert_eq!(f1.iter().map(|p| p[0]).collect::<Vec<_>>(), vec![-1.0, 2.5, 1.0]);
+        assert_eq!(unit_height(vec![[0.0, -4.0], [1.0, 2.0]], 1), vec![[0.0, -1.0], [1.0, 0.5]]);
+
+        // Row and column nearest the cursor
+        let row = row_trace(&spectrum, 14.0).unwrap();
+        assert_eq!(row.iter().map(|p| p[1]).collect::<Vec<_>>(), vec![0.5, 3.0, 0.0, -1.0]);
+        let column = column_trace(&spectrum, 6.3).unwrap();
+        assert_eq!(column, vec![[-4.0, 20.0], [3.0, 15.0], [1.0, 10.0]]);
+        assert!(row_trace(&spectrum, 60.0).is_none());
+        assert!(column_trace(&spectrum, 30.0).is_none());
+
+        // A 1D reference only over the ppm range of its axis
+        let reference = SpectrumData {
+            real: (0..10).map(|i| i as f64).collect(),
+            axes: vec![axis(10, 10.0, 10.0)],
+            is_frequency_domain: true,
+            ..Default::default()
+        };
+        let margin = external_projection(&spectrum, &reference, false);
+        assert_eq!(margin.first(), Some(&[-8.0, 2.0]));
+        assert_eq!(margin.last(), Some(&[-2.0, 8.0]));
+        assert_eq!(external_projection(&spectrum, &reference, true), vec![[0.0, 10.0]]);
+    }
+
+    #[test]
+    fn test_colorbar_levels_follow_the_data_scale() {
+        // Threshold 1e3, maximum 1e7, four log-spaced bands
+        let levels = contour_levels(1e3, 1e7, 4);
+        let expected = [1e3, 1e4, 1e5, 1e6, 1e7];
+        assert!(levels.iter().zip(expected).all(|(a, b)| (a / b - 1.0).abs() < 1e-12), "{:?}", levels);
+        assert_eq!(level_index(5e3, 1e3, 1e7, 4), 0);
+        assert_eq!(level_index(2e6, 1e3, 1e7, 4), 3);
+        assert_eq!(level_index(1e7, 1e3, 1e7, 4), 3);
+        assert_eq!(level_index(1.0, 1.0, 1.0, 4), 0);
+
+        assert_eq!(format_intensity(1.25e6), "1.25e6");
+        assert_eq!(format_intensity(-3.0e-2), "-3.00e-2");
+        let labels = colorbar_labels(&contour_levels(1e3, 1e7, 20), 6);
+        assert!(labels.len() <= 6);
+        assert_eq!(labels.first().map(|l| l.0), Some(0));
+        assert_eq!(labels.last().map(|l| l.1.as_str()), Some("1.00e7"));
+
+        let bg = egui::Color32::from_rgb(0, 0, 0);
+        let blue = egui::Color32::from_rgb(0, 0, 200);
+        assert!(level_color(blue, bg, 0.0).b() < level_color(blue, bg, 1.0).b());
+    }
+
+    #[test]
+    fn test_locked_levels_are_shared_across_spectra() {
+        let mut state = ContourViewState { threshold: 1e-4, num_levels: 4, ..Default::default() };
+        assert_eq!(state.level_range(1e7), (1e3, 1e7));
+
+        // Locking keeps the levels drawn for the current spectrum…
+        let locked = LockedLevels::from_relative(state.threshold, 1e7, state.num_levels);
+        assert!((locked.base - 1e3).abs() < 1e-9 && (locked.multiplier - 10.0).abs() < 1e-9);
+        state.locked_levels = Some(locked);
+        let (base, top) = state.level_range(1e7);
+        assert!((base - 1e3).abs() < 1e-9 && (top / 1e7 - 1.0).abs() < 1e-12);
+
+        // …and a spectrum half as intense is drawn on the same levels, not
+        // rescaled to its own maximum
+        assert_eq!(state.level_range(5e6), state.level_range(1e7));
+        assert_eq!(level_index(5e6, base, top, 4), 3);
+        assert_eq!(level_index(2e4, base, top, 4), 1);
+
+        // A threshold of 1 still gives distinct levels
+        assert!(LockedLevels::from_relative(1.0, 1.0, 10).multiplier > 1.0);
+    }
+}
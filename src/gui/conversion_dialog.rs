@@ -108,6 +108,37 @@ impl DfMode {
     }
 }
 
+/// Bruker digital-filter (DMX) group-delay correction
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum DmxCorrection {
+    /// Correct unless the data looks already corrected
+    #[default]
+    Auto,
+    /// Data already DMX-corrected: convert without correction
+    AlreadyCorrected,
+    /// Always remove the group delay
+    Apply,
+}
+
+impl DmxCorrection {
+    pub fn label(&self) -> &str {
+        match self {
+            DmxCorrection::Auto => "Auto (detect pre-corrected data)",
+            DmxCorrection::AlreadyCorrected => "Data already DMX-corrected",
+            DmxCorrection::Apply => "Always correct group delay",
+        }
+    }
+
+    /// Override for the converters; `None` = decide from the data
+    pub fn forced(&self) -> Option<bool> {
+        match self {
+            DmxCorrection::Auto => None,
+            DmxCorrection::AlreadyCorrected => Some(false),
+            DmxCorrection::Apply => Some(true),
+        }
+    }
+}
+
 /// Per-axis conversion parameters
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AxisConversionParams {
@@ -198,6 +229,9 @@ pub struct ConversionSettings {
     pub x_axis: AxisConversionParams,
     pub y_axis: AxisConversionParams,
     pub df_mode: DfMode,
+    /// Bruker group-delay handling
+    #[serde(default)]
+    pub dmx_correction: DmxCorrection,
     pub override_aq2d: bool,
     pub aq2d: Aq2D,
     pub override_ndim: bool,
@@ -207,6 +241,12 @@ pub struct ConversionSettings {
     pub extra_args: String,
     /// Which conversion backend to use
     pub conversion_method: ConversionMethod,
+    /// Where external converters write their output (None = next to the data)
+    #[serde(default)]
+    pub output_dir: Option<std::path::PathBuf>,
+    /// Cancels a running external converter (runtime only)
+    #[serde(skip)]
+    pub cancel: Option<crate::pipeline::command::CancelToken>,
 }
 
 impl Default for ConversionSettings {
@@ -215,6 +255,7 @@ impl Default for ConversionSettings {
             x_axis: AxisConversionParams::default(),
             y_axis: AxisConversionParams::default(),
             df_mode: DfMode::Auto,
+            dmx_correction: DmxCorrection::Auto,
             override_aq2d: false,
             aq2d: Aq2D::Magnitude,
             override_ndim: false,
@@ -222,6 +263,8 @@ impl Default for ConversionSettings {
             verbose: true,
             extra_args: String::new(),
             conversion_method: ConversionMethod::BuiltIn,
+            output_dir: None,
+            cancel: None,
         }
     }
 }
@@ -295,6 +338,9 @@ pub struct ConversionDialogState {
     /// Info text from delta2pipe -info
     pub info_text: String,
     pub info_loaded: bool,
+    /// Why the pending Bruker dataset looks already DMX-corrected; the
+    /// dialog shows the Bruker digital-filter choice instead of delta2pipe
+    pub dmx_hint: Option<String>,
 }
 
 impl Default for ConversionDialogState {
@@ -305,6 +351,7 @@ impl Default for ConversionDialogState {
             pending_path: None,
             info_text: String::new(),
             info_loaded: false,
+            dmx_hint: None,
         }
     }
 }
@@ -327,6 +374,9 @@ pub fn show_conversion_dialog(
     if !state.open {
         return action;
     }
+    if state.dmx_hint.is_some() {
+        return show_bruker_dmx_dialog(ctx, state);
+    }
 
     let mut open = state.open;
     egui::Window::new("⚙ delta2pipe Conversion Settings")
@@ -469,6 +519,56 @@ pub fn show_conversion_dialog(
     action
 }
 
+/// Bruker variant of the dialog: confirm how to treat a digitally filtered
+/// FID that looks already group-delay corrected.
+fn show_bruker_dmx_dialog(ctx: &egui::Context, state: &mut ConversionDialogState) -> ConversionAction {
+    let mut action = ConversionAction::None;
+    let mut open = state.open;
+    egui::Window::new("⚙ Bruker Conversion Settings")
+        .open(&mut open)
+        .default_width(460.0)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let file_label = state
+                .pending_path
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "—".to_string());
+            ui.label(format!("Dataset: {}", file_label));
+            ui.separator();
+
+            ui.strong("Digital filter (DMX)");
+            ui.label(
+                "This FID looks like its digital-filter group delay was already removed. \
+                 Correcting it again shifts the FID and causes baseline roll and phase errors.",
+            );
+            if let Some(hint) = &state.dmx_hint {
+                ui.label(egui::RichText::new(format!("Detected: {}", hint)).small());
+            }
+            ui.add_space(4.0);
+            for mode in [DmxCorrection::Auto, DmxCorrection::AlreadyCorrected, DmxCorrection::Apply] {
+                ui.radio_value(&mut state.settings.dmx_correction, mode, mode.label());
+            }
+
+            ui.add_space(8.0);
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("▶ Convert").clicked() {
+                    action = ConversionAction::Convert;
+                }
+                if ui.button("Cancel").clicked() {
+                    action = ConversionAction::Cancel;
+                }
+            });
+        });
+
+    state.open = open;
+    if !open {
+        action = ConversionAction::Cancel;
+    }
+    action
+}
+
 /// Show editable axis parameters
 fn show_axis_params(ui: &mut egui::Ui, params: &mut AxisConversionParams, prefix: &str) {
     let p = prefix.to_uppercase();
@@ -19,6 +19,8 @@ pub struct ExportSettings {
     pub show_integrations: bool,
     /// Include multiplet labels
     pub show_multiplets: bool,
+    /// Fill the fitted lines under the trace and draw their sum
+    pub show_peak_fits: bool,
     /// Custom title (empty = auto from spectrum metadata)
     pub custom_title: String,
     /// Use custom title
@@ -27,7 +29,9 @@ pub struct ExportSettings {
     pub line_width: f32,
     /// Show grid lines
     pub show_grid: bool,
-    /// Export format: 0 = PNG, 1 = SVG
+    /// Shade the functional-group shift regions of the spectrum's nucleus
+    pub show_regions: bool,
+    /// Export format: 0 = PNG, 1 = SVG, 2 = PDF
     pub format: usize,
     /// Y-axis: clip negatives (for 1H/13C)
     pub clip_negatives: bool,
@@ -37,6 +41,65 @@ pub struct ExportSettings {
     pub marker_scale: f32,
     /// Scale factor for all text elements (1.0 = default)
     pub font_scale: f32,
+    /// Corner of the acquisition/processing parameter box (`None` = no box)
+    pub parameter_box: Option<Corner>,
+    /// Corner of the legend of overlaid references (`None` = no legend)
+    pub legend: Option<Corner>,
+    /// Font family of all text
+    pub font: ExportFont,
+    /// Embed the font file in SVG/PDF so the figure renders the same
+    /// without it installed
+    pub embed_font: bool,
+}
+
+/// Font family of exported figures
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportFont {
+    /// egui's Ubuntu Light, as drawn in the preview
+    Builtin,
+    /// Helvetica / Arial
+    Helvetica,
+    /// Times / Times New Roman
+    Times,
+    /// A TrueType/OpenType file chosen by the user
+    File(std::path::PathBuf),
+}
+
+impl std::fmt::Display for ExportFont {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFont::Builtin => write!(f, "Ubuntu Light (built-in)"),
+            ExportFont::Helvetica => write!(f, "Helvetica / Arial"),
+            ExportFont::Times => write!(f, "Times"),
+            ExportFont::File(path) => {
+                write!(f, "{}", path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// Corner of the plot area
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    pub const ALL: [Corner; 4] = [Corner::TopLeft, Corner::TopRight, Corner::BottomLeft, Corner::BottomRight];
+}
+
+impl std::fmt::Display for Corner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Corner::TopLeft => write!(f, "Top left"),
+            Corner::TopRight => write!(f, "Top right"),
+            Corner::BottomLeft => write!(f, "Bottom left"),
+            Corner::BottomRight => write!(f, "Bottom right"),
+        }
+    }
 }
 
 impl Default for ExportSettings {
@@ -50,15 +113,21 @@ impl Default for ExportSettings {
             show_peaks: true,
             show_integrations: true,
             show_multiplets: true,
+            show_peak_fits: true,
             custom_title: String::new(),
             use_custom_title: false,
             line_width: 1.5,
             show_grid: true,
+            show_regions: false,
             format: 0, // PNG
             clip_negatives: false,
             dpi: 300,
             marker_scale: 1.0,
             font_scale: 1.0,
+            parameter_box: None,
+            legend: Some(Corner::TopRight),
+            font: ExportFont::Builtin,
+            embed_font: false,
         }
     }
 }
@@ -210,6 +279,10 @@ pub fn show_export_dialog(
                     );
                 }
                 ui.checkbox(&mut state.settings.show_grid, "Show grid lines");
+                let mut show_params = state.settings.parameter_box.is_some();
+                if ui.checkbox(&mut show_params, "Show parameter box").changed() {
+                    state.settings.parameter_box = show_params.then_some(Corner::TopLeft);
+                }
                 ui.checkbox(
                     &mut state.settings.clip_negatives,
                     "Clip negative intensities",
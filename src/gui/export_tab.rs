@@ -5,10 +5,59 @@
 /// all image- and data-export settings.
 
 use crate::data::spectrum::SpectrumData;
+use crate::gui::export_dialog::{Corner, ExportFont, ExportSettings};
+use crate::gui::plot_scene;
 use crate::gui::spectrum_view::SpectrumViewState;
+use crate::log::reproducibility::ReproLog;
+use crate::pipeline::processing::{self, IntensityMode};
 
 // ── Public types ───────────────────────────────────────────────────
 
+/// Unit the figure size is entered in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeUnit {
+    Pixels,
+    Centimetres,
+    Inches,
+}
+
+impl SizeUnit {
+    pub const ALL: [SizeUnit; 3] = [SizeUnit::Pixels, SizeUnit::Centimetres, SizeUnit::Inches];
+
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            SizeUnit::Pixels => "px",
+            SizeUnit::Centimetres => "cm",
+            SizeUnit::Inches => "in",
+        }
+    }
+
+    /// Length of one unit in inches (`None` for pixels)
+    fn inches(self) -> Option<f64> {
+        match self {
+            SizeUnit::Pixels => None,
+            SizeUnit::Centimetres => Some(1.0 / 2.54),
+            SizeUnit::Inches => Some(1.0),
+        }
+    }
+
+    /// Length of `px` pixels at `dpi` in this unit
+    pub fn length(self, px: u32, dpi: u32) -> f64 {
+        match self.inches() {
+            Some(inches) => px as f64 / dpi.max(1) as f64 / inches,
+            None => px as f64,
+        }
+    }
+
+    /// Pixels at `dpi` for a length `value` in this unit
+    pub fn pixels(self, value: f64, dpi: u32) -> u32 {
+        match self.inches() {
+            Some(inches) => (value * inches * dpi as f64).round() as u32,
+            None => value.round() as u32,
+        }
+    }
+}
+
 /// Settings for image export (PNG / SVG)
 #[derive(Debug, Clone)]
 pub struct ImageExportSettings {
@@ -18,10 +67,16 @@ pub struct ImageExportSettings {
     pub width: u32,
     pub height: u32,
     pub dpi: u32,
+    /// Unit the size is shown and entered in; pixels follow from the DPI
+    pub size_unit: SizeUnit,
     pub show_peaks: bool,
     pub show_integrations: bool,
     pub show_multiplets: bool,
+    /// Fitted lines as translucent areas, with their sum
+    pub show_peak_fits: bool,
     pub show_grid: bool,
+    /// Functional-group region shading, as in the view
+    pub show_regions: bool,
     pub clip_negatives: bool,
     pub custom_title: String,
     pub use_custom_title: bool,
@@ -30,8 +85,18 @@ pub struct ImageExportSettings {
     pub marker_scale: f32,
     /// Scale factor for all text elements (1.0 = default)
     pub font_scale: f32,
-    /// 0 = PNG, 1 = SVG
+    /// 0 = PNG, 1 = SVG, 2 = PDF
     pub format: usize,
+    /// Parameter box (nucleus, frequency, solvent, scans, pulse program,
+    /// LB, SI) in a corner of the plot, as required on archived spectra
+    pub show_parameters: bool,
+    pub parameter_corner: Corner,
+    /// Legend of the overlaid references (name, color, scale, offset)
+    pub show_legend: bool,
+    pub legend_corner: Corner,
+    pub font: ExportFont,
+    /// Embed the font in SVG/PDF exports
+    pub embed_font: bool,
 }
 
 impl Default for ImageExportSettings {
@@ -43,10 +108,13 @@ impl Default for ImageExportSettings {
             width: 2400,
             height: 1800,
             dpi: 300,
+            size_unit: SizeUnit::Pixels,
             show_peaks: true,
             show_integrations: true,
             show_multiplets: true,
+            show_peak_fits: true,
             show_grid: false,
+            show_regions: false,
             clip_negatives: false,
             custom_title: String::new(),
             use_custom_title: false,
@@ -54,33 +122,86 @@ impl Default for ImageExportSettings {
             marker_scale: 1.0,
             font_scale: 1.0,
             format: 0,
+            show_parameters: false,
+            parameter_corner: Corner::TopLeft,
+            show_legend: true,
+            legend_corner: Corner::TopRight,
+            font: ExportFont::Builtin,
+            embed_font: false,
         }
     }
 }
 
-/// Settings for data export (CSV / TSV / TXT)
+impl ImageExportSettings {
+    /// The equivalent settings for the image exporter
+    pub fn to_export_settings(&self) -> ExportSettings {
+        ExportSettings {
+            ppm_start: self.ppm_start,
+            ppm_end: self.ppm_end,
+            use_custom_range: self.use_custom_range,
+            width: self.width,
+            height: self.height,
+            show_peaks: self.show_peaks,
+            show_integrations: self.show_integrations,
+            show_multiplets: self.show_multiplets,
+            show_peak_fits: self.show_peak_fits,
+            custom_title: self.custom_title.clone(),
+            use_custom_title: self.use_custom_title,
+            line_width: self.line_width,
+            show_grid: self.show_grid,
+            show_regions: self.show_regions,
+            format: self.format,
+            clip_negatives: self.clip_negatives,
+            dpi: self.dpi,
+            marker_scale: self.marker_scale,
+            font_scale: self.font_scale,
+            parameter_box: self.show_parameters.then_some(self.parameter_corner),
+            legend: self.show_legend.then_some(self.legend_corner),
+            font: self.font.clone(),
+            embed_font: self.embed_font,
+        }
+    }
+}
+
+/// Settings for data export (CSV / TSV / TXT / web JSON)
 #[derive(Debug, Clone)]
 pub struct DataExportSettings {
-    /// 0 = CSV, 1 = TSV, 2 = TXT
+    /// 0 = CSV, 1 = TSV, 2 = TXT, 3 = decimated JSON for web viewers
     pub format: usize,
+    /// Point budget of the decimated JSON export
+    pub web_points: usize,
     pub include_peaks: bool,
+    /// Leave the peaks flagged as solvent or impurity out of the peak list
+    pub exclude_impurity_peaks: bool,
+    /// Normalization of the second peak intensity column
+    pub peak_intensity_mode: IntensityMode,
     pub include_integrations: bool,
     pub include_multiplets: bool,
     pub include_j_couplings: bool,
+    pub include_peak_fits: bool,
+    pub include_carbon_types: bool,
     pub ppm_decimals: usize,
     pub include_header: bool,
+    /// Write 2D NMRPipe exports as a `%03d` plane series, one row per file
+    pub nmrpipe_series: bool,
 }
 
 impl Default for DataExportSettings {
     fn default() -> Self {
         Self {
             format: 0,
+            web_points: 2000,
             include_peaks: true,
+            exclude_impurity_peaks: false,
+            peak_intensity_mode: IntensityMode::RelativeToTallest,
             include_integrations: true,
             include_multiplets: true,
             include_j_couplings: true,
+            include_peak_fits: true,
+            include_carbon_types: true,
             ppm_decimals: 4,
             include_header: true,
+            nmrpipe_series: false,
         }
     }
 }
@@ -113,6 +234,10 @@ pub enum ExportTabAction {
     None,
     ExportImage,
     ExportData,
+    /// The processed spectrum as an NMRPipe file (or 2D plane series)
+    ExportNmrPipe,
+    /// Integration regions as a TopSpin `intrng` file
+    ExportIntrng,
     ExportLog,
 }
 
@@ -124,6 +249,7 @@ pub fn show_export_tab(
     state: &mut ExportTabState,
     spectrum: &SpectrumData,
     view_state: &SpectrumViewState,
+    log: &ReproLog,
 ) -> ExportTabAction {
     let mut action = ExportTabAction::None;
 
@@ -189,10 +315,10 @@ pub fn show_export_tab(
 
                         match state.active_section {
                             0 => {
-                                action = show_image_settings(ui, &mut state.image_settings, view_state);
+                                action = show_image_settings(ui, &mut state.image_settings, spectrum, view_state, log);
                             }
                             1 => {
-                                action = show_data_settings(ui, &mut state.data_settings, view_state);
+                                action = show_data_settings(ui, &mut state.data_settings, spectrum, view_state);
                             }
                             _ => {}
                         }
@@ -213,7 +339,7 @@ pub fn show_export_tab(
             ui.add_space(2.0);
 
             match state.active_section {
-                0 => show_image_preview(ui, spectrum, view_state, &state.image_settings),
+                0 => show_image_preview(ui, spectrum, view_state, &state.image_settings, log),
                 1 => show_data_preview(ui, spectrum, view_state, &state.data_settings),
                 _ => {}
             }
@@ -228,7 +354,9 @@ pub fn show_export_tab(
 fn show_image_settings(
     ui: &mut egui::Ui,
     s: &mut ImageExportSettings,
+    spectrum: &SpectrumData,
     view_state: &SpectrumViewState,
+    log: &ReproLog,
 ) -> ExportTabAction {
     let mut action = ExportTabAction::None;
 
@@ -239,7 +367,15 @@ fn show_image_settings(
             .strong()
             .color(egui::Color32::from_rgb(0x2A, 0x2E, 0x36)),
     );
-    ui.checkbox(&mut s.use_custom_range, "Custom range");
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut s.use_custom_range, "Custom range");
+        let current = Some((s.ppm_start.min(s.ppm_end), s.ppm_start.max(s.ppm_end)));
+        if let Some((lo, hi)) = super::spectrum_view::ppm_preset_combo(ui, "export_ppm_preset", spectrum, current) {
+            s.use_custom_range = true;
+            s.ppm_start = hi;
+            s.ppm_end = lo;
+        }
+    });
     if s.use_custom_range {
         ui.horizontal(|ui| {
             ui.label("From");
@@ -266,29 +402,68 @@ fn show_image_settings(
             .color(egui::Color32::from_rgb(0x2A, 0x2E, 0x36)),
     );
     ui.horizontal(|ui| {
-        ui.add(
-            egui::DragValue::new(&mut s.width)
-                .speed(10)
-                .range(800..=8000)
-                .prefix("W ")
-                .suffix(" px"),
-        );
-        ui.label("×");
-        ui.add(
-            egui::DragValue::new(&mut s.height)
-                .speed(10)
-                .range(400..=4000)
-                .prefix("H ")
-                .suffix(" px"),
-        );
+        let unit = s.size_unit;
+        if unit == SizeUnit::Pixels {
+            ui.add(
+                egui::DragValue::new(&mut s.width)
+                    .speed(10)
+                    .range(200..=12000)
+                    .prefix("W ")
+                    .suffix(" px"),
+            );
+            ui.label("×");
+            ui.add(
+                egui::DragValue::new(&mut s.height)
+                    .speed(10)
+                    .range(200..=8000)
+                    .prefix("H ")
+                    .suffix(" px"),
+            );
+        } else {
+            // Physical size; the pixel count follows from the DPI
+            let mut w = unit.length(s.width, s.dpi);
+            let mut h = unit.length(s.height, s.dpi);
+            let suffix = format!(" {}", unit.suffix());
+            if ui
+                .add(egui::DragValue::new(&mut w).speed(0.05).range(1.0..=100.0).fixed_decimals(2).prefix("W ").suffix(suffix.clone()))
+                .changed()
+            {
+                s.width = unit.pixels(w, s.dpi).clamp(200, 12000);
+            }
+            ui.label("×");
+            if ui
+                .add(egui::DragValue::new(&mut h).speed(0.05).range(1.0..=100.0).fixed_decimals(2).prefix("H ").suffix(suffix))
+                .changed()
+            {
+                s.height = unit.pixels(h, s.dpi).clamp(200, 8000);
+            }
+        }
+        egui::ComboBox::from_id_salt("export_size_unit")
+            .width(48.0)
+            .selected_text(unit.suffix())
+            .show_ui(ui, |ui| {
+                for u in SizeUnit::ALL {
+                    ui.selectable_value(&mut s.size_unit, u, u.suffix());
+                }
+            });
     });
     ui.horizontal(|ui| {
         ui.label("DPI");
-        ui.add(
-            egui::DragValue::new(&mut s.dpi)
-                .speed(10)
-                .range(72..=1200),
-        );
+        let old_dpi = s.dpi;
+        if ui.add(egui::DragValue::new(&mut s.dpi).speed(10).range(72..=1200)).changed()
+            && s.size_unit != SizeUnit::Pixels
+        {
+            // Keep the physical size, change the pixel count
+            let rescale = |px: u32| (px as f64 * s.dpi as f64 / old_dpi as f64).round() as u32;
+            s.width = rescale(s.width).clamp(200, 12000);
+            s.height = rescale(s.height).clamp(200, 8000);
+        }
+        if s.size_unit == SizeUnit::Pixels {
+            let unit = SizeUnit::Centimetres;
+            ui.weak(format!("= {:.1} × {:.1} cm", unit.length(s.width, s.dpi), unit.length(s.height, s.dpi)));
+        } else {
+            ui.weak(format!("= {} × {} px", s.width, s.height));
+        }
     });
     ui.horizontal(|ui| {
         if ui.small_button("Screen").clicked() {
@@ -320,13 +495,51 @@ fn show_image_settings(
         ui.checkbox(&mut s.show_peaks, "Peak labels");
     }
     if !view_state.integrations.is_empty() {
-        ui.checkbox(&mut s.show_integrations, "Integrations");
+        ui.checkbox(&mut s.show_integrations, "Integrations").on_hover_text(format!(
+            "Values {} as in the spectrum view{}",
+            view_state.integral_labels.label().to_lowercase(),
+            if view_state.integral_trace { ", with the integral trace" } else { "" }
+        ));
     }
     if !view_state.multiplets.is_empty() {
         ui.checkbox(&mut s.show_multiplets, "Multiplets");
     }
+    if !view_state.peak_fits.is_empty() {
+        ui.checkbox(&mut s.show_peak_fits, "Fitted components")
+            .on_hover_text("Each fitted line as a translucent area under the spectrum, with the sum of the fit");
+    }
     ui.checkbox(&mut s.show_grid, "Grid lines");
+    ui.checkbox(&mut s.show_regions, "Functional-group regions")
+        .on_hover_text("Shade the shift regions defined in Preferences behind the spectrum");
     ui.checkbox(&mut s.clip_negatives, "Clip negative intensities");
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut s.show_parameters, "Parameter box")
+            .on_hover_text("Nucleus, frequency, solvent, scans, pulse program, LB and SI in a corner of the plot");
+        ui.add_enabled_ui(s.show_parameters, |ui| {
+            egui::ComboBox::from_id_salt("export_parameter_corner")
+                .selected_text(s.parameter_corner.to_string())
+                .show_ui(ui, |ui| {
+                    for corner in Corner::ALL {
+                        ui.selectable_value(&mut s.parameter_corner, corner, corner.to_string());
+                    }
+                });
+        });
+    });
+    if view_state.reference_overlays.iter().any(|t| t.visible) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut s.show_legend, "Overlay legend")
+                .on_hover_text("Name, color, scale factor and offset of each overlaid reference");
+            ui.add_enabled_ui(s.show_legend, |ui| {
+                egui::ComboBox::from_id_salt("export_legend_corner")
+                    .selected_text(s.legend_corner.to_string())
+                    .show_ui(ui, |ui| {
+                        for corner in Corner::ALL {
+                            ui.selectable_value(&mut s.legend_corner, corner, corner.to_string());
+                        }
+                    });
+            });
+        });
+    }
     ui.add_space(6.0);
 
     // Title
@@ -368,6 +581,28 @@ fn show_image_settings(
             .text("Font scale")
             .fixed_decimals(1),
     );
+    ui.horizontal(|ui| {
+        ui.label("Font");
+        egui::ComboBox::from_id_salt("export_font")
+            .selected_text(s.font.to_string())
+            .show_ui(ui, |ui| {
+                for font in [ExportFont::Builtin, ExportFont::Helvetica, ExportFont::Times] {
+                    let label = font.to_string();
+                    ui.selectable_value(&mut s.font, font, label);
+                }
+                if ui.selectable_label(matches!(s.font, ExportFont::File(_)), "Font file…").clicked() {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .set_title("Choose a font for exported figures")
+                        .add_filter("Fonts", &["ttf", "otf"])
+                        .pick_file()
+                    {
+                        s.font = ExportFont::File(path);
+                    }
+                }
+            });
+    });
+    ui.checkbox(&mut s.embed_font, "Embed font in SVG/PDF")
+        .on_hover_text("Figures render identically without the font installed (whole font file, larger output)");
     ui.add_space(6.0);
 
     // Format
@@ -380,7 +615,34 @@ fn show_image_settings(
     ui.horizontal(|ui| {
         ui.selectable_value(&mut s.format, 0, "PNG");
         ui.selectable_value(&mut s.format, 1, "SVG");
+        ui.selectable_value(&mut s.format, 2, "PDF");
     });
+    ui.add_space(6.0);
+
+    // Processing summary, embedded in the exported file
+    ui.label(
+        egui::RichText::new("Processing Summary")
+            .size(12.5)
+            .strong()
+            .color(egui::Color32::from_rgb(0x2A, 0x2E, 0x36)),
+    )
+    .on_hover_text("Written into PNG (tEXt \"Description\") and SVG (<desc>) exports");
+    let summary = log.processing_summary();
+    if summary.is_empty() {
+        ui.label(
+            egui::RichText::new("No processing steps yet")
+                .size(11.0)
+                .color(egui::Color32::from_rgb(0x88, 0x8C, 0x94)),
+        );
+    } else {
+        egui::Grid::new("export_processing_summary").num_columns(2).show(ui, |ui| {
+            for (label, value) in &summary {
+                ui.label(egui::RichText::new(label).size(11.0).color(egui::Color32::from_rgb(0x88, 0x8C, 0x94)));
+                ui.label(egui::RichText::new(value).size(11.0));
+                ui.end_row();
+            }
+        });
+    }
 
     ui.add_space(16.0);
     if ui
@@ -407,6 +669,7 @@ fn show_image_settings(
 fn show_data_settings(
     ui: &mut egui::Ui,
     s: &mut DataExportSettings,
+    spectrum: &SpectrumData,
     view_state: &SpectrumViewState,
 ) -> ExportTabAction {
     let mut action = ExportTabAction::None;
@@ -421,7 +684,15 @@ fn show_data_settings(
         ui.selectable_value(&mut s.format, 0, "CSV");
         ui.selectable_value(&mut s.format, 1, "TSV");
         ui.selectable_value(&mut s.format, 2, "TXT");
+        ui.selectable_value(&mut s.format, 3, "JSON (web)")
+            .on_hover_text("Decimated ppm/intensity pairs for interactive plots in web pages or ELNs");
     });
+    if s.format == 3 {
+        ui.horizontal(|ui| {
+            ui.label("Point budget:");
+            ui.add(egui::DragValue::new(&mut s.web_points).range(100..=100_000).speed(50));
+        });
+    }
     ui.add_space(6.0);
 
     ui.label(
@@ -446,11 +717,33 @@ fn show_data_settings(
     let n_int = view_state.integrations.len();
     let n_mult = view_state.multiplets.len();
     let n_j = view_state.j_couplings.len();
+    let n_fit: usize = view_state.peak_fits.iter().map(|f| f.peaks.len()).sum();
 
     ui.checkbox(
         &mut s.include_peaks,
         format!("Peak list ({} peaks)", n_peaks),
     );
+    ui.add_enabled_ui(s.include_peaks, |ui| {
+        ui.horizontal(|ui| {
+            ui.add_space(18.0);
+            ui.label("Normalize:");
+            egui::ComboBox::from_id_salt("export_intensity_mode")
+                .selected_text(s.peak_intensity_mode.label())
+                .show_ui(ui, |ui| {
+                    for mode in IntensityMode::ALL {
+                        ui.selectable_value(&mut s.peak_intensity_mode, mode, mode.label());
+                    }
+                });
+        });
+        ui.horizontal(|ui| {
+            ui.add_space(18.0);
+            let n_flagged = view_state.peaks.iter().filter(|p| view_state.impurity_at(p[0]).is_some()).count();
+            ui.checkbox(
+                &mut s.exclude_impurity_peaks,
+                format!("Leave out solvent / impurity peaks ({} flagged)", n_flagged),
+            );
+        });
+    });
     ui.checkbox(
         &mut s.include_integrations,
         format!("Integrations ({} regions)", n_int),
@@ -463,6 +756,16 @@ fn show_data_settings(
         &mut s.include_j_couplings,
         format!("J-couplings ({} measured)", n_j),
     );
+    ui.checkbox(
+        &mut s.include_peak_fits,
+        format!("Fitted peaks ({} lines)", n_fit),
+    );
+    if !view_state.carbon_types.is_empty() {
+        ui.checkbox(
+            &mut s.include_carbon_types,
+            format!("Carbon types ({} peaks)", view_state.carbon_types.len()),
+        );
+    }
     ui.add_space(4.0);
     ui.checkbox(&mut s.include_header, "Include header / metadata");
 
@@ -483,6 +786,36 @@ fn show_data_settings(
         action = ExportTabAction::ExportData;
     }
 
+    ui.add_space(8.0);
+    if ui
+        .add(
+            egui::Button::new(egui::RichText::new("🧪  Export NMRPipe…").size(13.0))
+                .corner_radius(5.0)
+                .min_size(egui::vec2(200.0, 28.0)),
+        )
+        .on_hover_text("The spectrum with its real and imaginary parts as an NMRPipe file for NMRPipe / NMRDraw")
+        .clicked()
+    {
+        action = ExportTabAction::ExportNmrPipe;
+    }
+    if spectrum.is_2d() {
+        ui.checkbox(&mut s.nmrpipe_series, "As plane series (name%03d.ft2)");
+    }
+    ui.add_space(4.0);
+    if ui
+        .add_enabled(
+            n_int > 0,
+            egui::Button::new(egui::RichText::new("∫  Export Regions (intrng)…").size(13.0))
+                .corner_radius(5.0)
+                .min_size(egui::vec2(200.0, 28.0)),
+        )
+        .on_hover_text("The integration regions as a TopSpin intrng file, to integrate the same regions in TopSpin")
+        .on_disabled_hover_text("Add integration regions first")
+        .clicked()
+    {
+        action = ExportTabAction::ExportIntrng;
+    }
+
     ui.add_space(12.0);
     ui.separator();
     ui.add_space(8.0);
@@ -505,13 +838,14 @@ fn show_data_settings(
     action
 }
 
-// ── Image preview (Painter-based, matches export layout) ──────────
+// ── Image preview (paints the export scene) ───────────────────────
 
 fn show_image_preview(
     ui: &mut egui::Ui,
     spectrum: &SpectrumData,
     view_state: &SpectrumViewState,
     settings: &ImageExportSettings,
+    log: &ReproLog,
 ) {
     // Show notice for 2D data
     if spectrum.is_2d() {
@@ -530,497 +864,75 @@ fn show_image_preview(
         return;
     }
 
-    // ── Data preparation (identical to export) ──
-    let ppm_scale = if spectrum.is_frequency_domain && !spectrum.axes.is_empty() {
-        spectrum.axes[0].ppm_scale()
-    } else {
-        (0..spectrum.real.len())
-            .map(|i| i as f64)
-            .collect::<Vec<_>>()
-    };
-
-    let (ppm_hi, ppm_lo) = if settings.use_custom_range {
-        (
-            settings.ppm_start.max(settings.ppm_end),
-            settings.ppm_start.min(settings.ppm_end),
-        )
-    } else {
-        let mn = ppm_scale.iter().cloned().fold(f64::INFINITY, f64::min);
-        let mx = ppm_scale.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
-        (mx, mn)
-    };
-    let x_range = ppm_hi - ppm_lo;
-    if x_range <= 0.0 {
-        ui.centered_and_justified(|ui| {
-            ui.label("Invalid PPM range");
-        });
-        return;
-    }
-
-    let clip = settings.clip_negatives;
-    let n = spectrum.real.len().min(ppm_scale.len());
-
-    let (y_min, y_max) = {
-        let mut ymin = f64::INFINITY;
-        let mut ymax = f64::NEG_INFINITY;
-        for i in 0..n {
-            let ppm = ppm_scale[i];
-            if ppm < ppm_lo || ppm > ppm_hi {
-                continue;
-            }
-            let v = if clip {
-                spectrum.real[i].max(0.0)
-            } else {
-                spectrum.real[i]
-            };
-            if v < ymin {
-                ymin = v;
-            }
-            if v > ymax {
-                ymax = v;
-            }
+    let scene = match plot_scene::build_scene(spectrum, view_state, &settings.to_export_settings(), log) {
+        Ok(scene) => scene,
+        Err(e) => {
+            ui.centered_and_justified(|ui| {
+                ui.label(e.to_string());
+            });
+            return;
         }
-        if clip {
-            ymin = 0.0;
-        }
-        (ymin, ymax)
     };
-    let y_range = (y_max - y_min).max(1e-12);
-    let y_max_padded = y_max + y_range * 0.05;
-    let y_range_padded = (y_max_padded - y_min).max(1e-12);
 
-    if y_range_padded <= 0.0 {
-        ui.centered_and_justified(|ui| {
-            ui.label("No data in selected range");
-        });
-        return;
-    }
-
-    // ── Allocate preview rect with correct aspect ratio ──
-    let aspect = settings.width as f32 / settings.height as f32;
+    // ── Allocate preview rect with the export's aspect ratio ──
+    let aspect = scene.width / scene.height;
     let avail = ui.available_size();
     let pw = avail.x.min(avail.y * aspect);
     let ph = (pw / aspect).min(avail.y);
 
-    let (response, painter) =
-        ui.allocate_painter(egui::vec2(pw, ph), egui::Sense::hover());
-    let canvas = response.rect;
-
-    // Background
-    painter.rect_filled(canvas, 0.0, egui::Color32::WHITE);
-
-    // ── Margins (same fractions as export) ──
-    let ml = (pw * 0.04).max(20.0);
-    let mr = (pw * 0.025).max(10.0);
-    let mt = (ph * 0.08).max(15.0);
-    let mb = (ph * 0.10).max(20.0);
-
-    let plot_rect = egui::Rect::from_min_max(
-        egui::pos2(canvas.left() + ml, canvas.top() + mt),
-        egui::pos2(canvas.right() - mr, canvas.bottom() - mb),
-    );
-    if plot_rect.width() < 10.0 || plot_rect.height() < 10.0 {
-        return;
-    }
-
-    // Scale from export to preview
-    let scale = pw / settings.width as f32;
-    let fs = settings.font_scale;
-    let ms = settings.marker_scale;
-
-    // Font sizes (scaled with minimum for readability)
-    let font_sm = (10.0 * fs * scale).max(7.0);
-    let font_lg = (16.0 * fs * scale).max(9.0);
-    let font_md = (12.0 * fs * scale).max(8.0);
-    let marker_r = (4.0 * ms * scale).max(1.5);
-
-    // ── Coordinate helpers ──
-    let ppm_to_x = |ppm: f64| -> f32 {
-        plot_rect.left() + ((ppm_hi - ppm) / x_range) as f32 * plot_rect.width()
-    };
-    let val_to_y = |val: f64| -> f32 {
-        let y_frac = 1.0 - (val - y_min) / y_range_padded;
-        plot_rect.top()
-            + (y_frac as f32 * plot_rect.height()).clamp(0.0, plot_rect.height())
-    };
-
-    // ── Grid ──
-    if settings.show_grid {
-        let grid_color = egui::Color32::from_rgb(230, 230, 235);
-        let tick_step = preview_tick_step(x_range);
-        let first_tick = (ppm_lo / tick_step).ceil() * tick_step;
-        let mut tick = first_tick;
-        while tick <= ppm_hi {
-            let x = ppm_to_x(tick);
-            painter.line_segment(
-                [
-                    egui::pos2(x, plot_rect.top()),
-                    egui::pos2(x, plot_rect.bottom()),
-                ],
-                egui::Stroke::new(0.5, grid_color),
-            );
-            tick += tick_step;
-        }
-    }
-
-    // ── Spectrum polyline ──
-    let spec_color = egui::Color32::from_rgb(0x1A, 0x3A, 0x6B);
-    let mut points: Vec<egui::Pos2> = Vec::with_capacity(n);
-    for i in 0..n {
-        let ppm = ppm_scale[i];
-        if ppm < ppm_lo || ppm > ppm_hi {
-            continue;
-        }
-        let x = ppm_to_x(ppm);
-        let y_val = if clip {
-            spectrum.real[i].max(0.0)
-        } else {
-            spectrum.real[i]
-        };
-        let y = val_to_y(y_val);
-        points.push(egui::pos2(x, y));
-    }
-    if points.len() >= 2 {
-        // Downsample for performance if needed
-        let max_pts = (pw * 2.0) as usize;
-        let pts = if points.len() > max_pts && max_pts > 2 {
-            let step = points.len() as f64 / max_pts as f64;
-            (0..max_pts)
-                .map(|i| points[(i as f64 * step) as usize])
-                .collect::<Vec<_>>()
-        } else {
-            points
-        };
-        painter.add(egui::Shape::line(
-            pts,
-            egui::Stroke::new(
-                (settings.line_width * scale).max(0.5),
-                spec_color,
-            ),
-        ));
-    }
-
-    // ── Plot border ──
-    painter.rect_stroke(
-        plot_rect,
-        0.0,
-        egui::Stroke::new(1.0, egui::Color32::from_rgb(100, 100, 110)),
-        egui::epaint::StrokeKind::Outside,
-    );
-
-    // ── Peak markers with collision-avoidant labels ──
-    if settings.show_peaks && !view_state.peaks.is_empty() {
-        let peak_color = egui::Color32::from_rgb(0xD0, 0x30, 0x30);
-        let leader_color = egui::Color32::from_rgb(0xC8, 0x78, 0x78);
-        let font = egui::FontId::proportional(font_sm);
-
-        struct PLabel {
-            px: f32,
-            py: f32,
-            text: String,
-            rect: egui::Rect,
-            natural_y: f32,
-        }
-
-        let mut labels: Vec<PLabel> = view_state
-            .peaks
-            .iter()
-            .filter(|p| p[0] >= ppm_lo && p[0] <= ppm_hi)
-            .map(|p| {
-                let px = ppm_to_x(p[0]);
-                let y_val = if clip { p[1].max(0.0) } else { p[1] };
-                let py = val_to_y(y_val);
-                let text = format!("{:.2}", p[0]);
-                let galley = painter.layout_no_wrap(text.clone(), font.clone(), peak_color);
-                let tw = galley.size().x;
-                let th = galley.size().y;
-                let lx = px - tw / 2.0;
-                let ly = py - marker_r * 3.5 - th - 2.0;
-                PLabel {
-                    px,
-                    py,
-                    text,
-                    rect: egui::Rect::from_min_size(
-                        egui::pos2(lx, ly),
-                        egui::vec2(tw, th),
-                    ),
-                    natural_y: ly,
-                }
-            })
-            .collect();
-
-        // Collision avoidance — sort by x, shift overlapping labels up
-        labels.sort_by(|a, b| {
-            a.rect
-                .left()
-                .partial_cmp(&b.rect.left())
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
-        let pad = 3.0_f32;
-        // Multi-pass collision avoidance: repeat until stable or max passes
-        for _pass in 0..5 {
-            let mut any_moved = false;
-            for i in 0..labels.len() {
-                for _iter in 0..20 {
-                    let mut needs_shift = false;
-                    let mut shift_to = f32::MAX;
-                    let ri = labels[i].rect.expand(pad);
-                    for j in 0..labels.len() {
-                        if j == i {
-                            continue;
-                        }
-                        let rj = labels[j].rect.expand(pad);
-                        if ri.intersects(rj) {
-                            let target = rj.top() - labels[i].rect.height() - pad * 2.0;
-                            if target < shift_to {
-                                shift_to = target;
-                            }
-                            needs_shift = true;
-                        }
-                    }
-                    if needs_shift {
-                        // Clamp so labels don't go above the title
-                        let min_y = canvas.top() + font_lg + 4.0;
-                        let sz = labels[i].rect.size();
-                        labels[i].rect = egui::Rect::from_min_size(
-                            egui::pos2(labels[i].rect.left(), shift_to.max(min_y)),
-                            sz,
-                        );
-                        any_moved = true;
-                    } else {
-                        break;
-                    }
-                }
-            }
-            if !any_moved {
-                break;
-            }
-        }
-
-        // Draw markers, leader lines, labels
-        for pl in &labels {
-            // Triangle marker
-            let tri_bot = pl.py - marker_r * 1.5;
-            let tri_top = pl.py - marker_r * 3.5;
-            painter.add(egui::Shape::convex_polygon(
-                vec![
-                    egui::pos2(pl.px, tri_bot),
-                    egui::pos2(pl.px - marker_r, tri_top),
-                    egui::pos2(pl.px + marker_r, tri_top),
-                ],
-                peak_color,
-                egui::Stroke::NONE,
-            ));
-
-            // Leader line if label was displaced
-            if pl.rect.top() < pl.natural_y - 3.0 {
-                painter.line_segment(
-                    [
-                        egui::pos2(pl.px, pl.rect.bottom() + 1.0),
-                        egui::pos2(pl.px, tri_top),
-                    ],
-                    egui::Stroke::new(0.5, leader_color),
-                );
-            }
-
-            // Label text
-            painter.text(
-                pl.rect.left_top(),
-                egui::Align2::LEFT_TOP,
-                &pl.text,
-                font.clone(),
-                peak_color,
-            );
-        }
-    }
-
-    // ── Below-plot stacked labels ──
-    let tick_step = preview_tick_step(x_range);
-    let tick_len = (4.0 * ms * scale).max(2.0);
-    let row_gap = (3.0 * scale).max(2.0);
-
-    // Row 1: axis tick labels
-    let tick_label_y = plot_rect.bottom() + tick_len + row_gap;
-    {
-        let tick_font = egui::FontId::proportional(font_md);
-        let tick_color = egui::Color32::from_rgb(60, 60, 70);
-        let first_tick = (ppm_lo / tick_step).ceil() * tick_step;
-        let mut tick = first_tick;
-        while tick <= ppm_hi {
-            let x = ppm_to_x(tick);
-            // Tick mark
-            painter.line_segment(
-                [
-                    egui::pos2(x, plot_rect.bottom()),
-                    egui::pos2(x, plot_rect.bottom() + tick_len),
-                ],
-                egui::Stroke::new(1.0, egui::Color32::from_rgb(100, 100, 110)),
-            );
-            // Label
-            painter.text(
-                egui::pos2(x, tick_label_y),
-                egui::Align2::CENTER_TOP,
-                format!("{:.1}", tick),
-                tick_font.clone(),
-                tick_color,
-            );
-            tick += tick_step;
-        }
-    }
-
-    // Measure tick label height
-    let tick_label_h = painter
-        .layout_no_wrap(
-            "0.0".to_string(),
-            egui::FontId::proportional(font_md),
-            egui::Color32::BLACK,
-        )
-        .size()
-        .y;
-    let mut next_row_y = tick_label_y + tick_label_h + row_gap;
-
-    // Row 2: Integration labels (if any)
-    if settings.show_integrations && !view_state.integrations.is_empty() {
-        let int_color = egui::Color32::from_rgb(76, 175, 80);
-        let int_font = egui::FontId::proportional(font_sm);
-        let first_raw = view_state
-            .integrations
-            .first()
-            .map(|r| r.2)
-            .unwrap_or(1.0)
-            .abs()
-            .max(1e-12);
-        let ref_h = view_state.integration_reference_h;
-
-        let mut int_label_h = 0.0f32;
-        for &(start_ppm, end_ppm, raw_val) in &view_state.integrations {
-            let lo = start_ppm.min(end_ppm).max(ppm_lo);
-            let hi = start_ppm.max(end_ppm).min(ppm_hi);
-            if lo >= hi {
-                continue;
-            }
-            let x_lo = ppm_to_x(hi);
-            let x_hi = ppm_to_x(lo);
-
-            // Dashed boundary lines
-            let dash = (4.0 * scale).max(2.0);
-            let gap = (3.0 * scale).max(1.5);
-            let mut y = plot_rect.top();
-            while y < plot_rect.bottom() {
-                let y_end = (y + dash).min(plot_rect.bottom());
-                painter.line_segment(
-                    [egui::pos2(x_lo, y), egui::pos2(x_lo, y_end)],
-                    egui::Stroke::new(0.5, int_color),
-                );
-                painter.line_segment(
-                    [egui::pos2(x_hi, y), egui::pos2(x_hi, y_end)],
-                    egui::Stroke::new(0.5, int_color),
-                );
-                y += dash + gap;
-            }
-
-            // Label
-            let mid_x = (x_lo + x_hi) / 2.0;
-            let rel_val = raw_val / first_raw;
-            let h_val = rel_val * ref_h;
-            let label = format!("{:.2}H", h_val);
-            let r = painter.text(
-                egui::pos2(mid_x, next_row_y),
-                egui::Align2::CENTER_TOP,
-                label,
-                int_font.clone(),
-                int_color,
-            );
-            if r.height() > int_label_h {
-                int_label_h = r.height();
-            }
-        }
-        next_row_y += int_label_h + row_gap;
-    }
+    let (response, painter) = ui.allocate_painter(egui::vec2(pw, ph), egui::Sense::hover());
+    plot_scene::paint(&scene, &painter, response.rect);
+}
 
-    // Row 3: Multiplet labels (if any)
-    if settings.show_multiplets && !view_state.multiplets.is_empty() {
-        let mult_color = egui::Color32::from_rgb(0, 96, 170);
-        let mult_font = egui::FontId::proportional(font_sm);
+// ── Data preview ──────────────────────────────────────────────────
 
-        for mult in &view_state.multiplets {
-            if mult.center_ppm < ppm_lo || mult.center_ppm > ppm_hi {
-                continue;
-            }
-            let x = ppm_to_x(mult.center_ppm);
-            let label = if mult.j_hz > 0.5 {
-                format!("{} J={:.1}", mult.label, mult.j_hz)
-            } else {
-                mult.label.clone()
-            };
-            painter.text(
-                egui::pos2(x, next_row_y),
-                egui::Align2::CENTER_TOP,
-                label,
-                mult_font.clone(),
-                mult_color,
+/// Preview of the decimated web JSON: point counts and the head of the file
+fn show_web_json_preview(
+    ui: &mut egui::Ui,
+    spectrum: &SpectrumData,
+    view_state: &SpectrumViewState,
+    settings: &DataExportSettings,
+) {
+    let peaks: Vec<[f64; 2]> = view_state
+        .peaks
+        .iter()
+        .filter(|_| settings.include_peaks)
+        .filter(|p| !settings.exclude_impurity_peaks || view_state.impurity_at(p[0]).is_none())
+        .copied()
+        .collect();
+    match processing::web_export(spectrum, &peaks, settings.web_points, settings.ppm_decimals) {
+        Ok(export) => {
+            ui.label(
+                egui::RichText::new(format!(
+                    "{} → {} points (largest-triangle-three-buckets)",
+                    export.source_points,
+                    export.points.len()
+                ))
+                .size(11.0),
             );
+            let json = serde_json::to_string_pretty(&export).unwrap_or_default();
+            let head: String = json.lines().take(40).collect::<Vec<_>>().join("\n");
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.label(egui::RichText::new(head).monospace().size(10.5));
+            });
         }
-    }
-
-    // ── Title ──
-    let title = if settings.use_custom_title && !settings.custom_title.is_empty() {
-        settings.custom_title.clone()
-    } else {
-        format!(
-            "{} — {}",
-            spectrum.sample_name, spectrum.experiment_type
-        )
-    };
-    painter.text(
-        egui::pos2(canvas.left() + ml, canvas.top() + 4.0),
-        egui::Align2::LEFT_TOP,
-        title,
-        egui::FontId::proportional(font_lg),
-        egui::Color32::from_rgb(40, 40, 50),
-    );
-
-    // ── X-axis title ──
-    let ax_title_y = canvas.bottom() - 4.0;
-    painter.text(
-        egui::pos2(plot_rect.center().x, ax_title_y),
-        egui::Align2::CENTER_BOTTOM,
-        "Chemical Shift (ppm)",
-        egui::FontId::proportional(font_md),
-        egui::Color32::from_rgb(60, 60, 70),
-    );
-
-    // ── PPM range info ──
-    painter.text(
-        egui::pos2(canvas.right() - mr, canvas.top() + 4.0),
-        egui::Align2::RIGHT_TOP,
-        format!("{:.1} – {:.1} ppm", ppm_hi, ppm_lo),
-        egui::FontId::proportional((font_sm - 1.0).max(6.0)),
-        egui::Color32::from_rgb(120, 120, 130),
-    );
-}
-
-/// Pick a nice tick step for axis labels.
-fn preview_tick_step(range: f64) -> f64 {
-    let nice_steps = [0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0];
-    let target_ticks = 10.0;
-    let raw_step = range / target_ticks;
-    for &step in &nice_steps {
-        if step >= raw_step {
-            return step;
+        Err(e) => {
+            ui.label(egui::RichText::new(format!("⚠ {}", e)).size(11.0).color(egui::Color32::from_rgb(0xCC, 0x88, 0x00)));
         }
     }
-    50.0
 }
 
-// ── Data preview ──────────────────────────────────────────────────
-
 fn show_data_preview(
     ui: &mut egui::Ui,
     spectrum: &SpectrumData,
     view_state: &SpectrumViewState,
     settings: &DataExportSettings,
 ) {
+    if settings.format == 3 {
+        show_web_json_preview(ui, spectrum, view_state, settings);
+        return;
+    }
+
     // Show notice for 2D data
     if spectrum.is_2d() {
         ui.label(
@@ -1048,37 +960,59 @@ fn show_data_preview(
             preview.push_str(&format!(
                 "# Observe: {:.4} MHz  |  SW: {:.2} Hz\n",
                 spectrum.axes[0].observe_freq_mhz,
-                spectrum.axes[0].spectral_width_hz,
+                spectrum.axes[0].to_standalone().spectral_width_hz,
             ));
         }
+        if let Some(text) = super::spectrum_view::acquisition_label(spectrum) {
+            preview.push_str(&format!("# {}\n", text));
+        }
         preview.push('\n');
     }
 
     // Peaks
-    if settings.include_peaks && !view_state.peaks.is_empty() {
-        let peaks = &view_state.peaks;
-        let max_i = peaks
-            .iter()
-            .map(|p| p[1].abs())
-            .fold(0.0f64, f64::max)
-            .max(1e-20);
+    let peaks: Vec<[f64; 2]> = view_state
+        .peaks
+        .iter()
+        .filter(|p| !settings.exclude_impurity_peaks || view_state.impurity_at(p[0]).is_none())
+        .copied()
+        .collect();
+    if settings.include_peaks && !peaks.is_empty() {
+        let peaks = &peaks;
+        let mode = settings.peak_intensity_mode;
+        let normalized = processing::normalized_intensities(
+            peaks,
+            mode,
+            view_state.integrations.first().map(|r| r.2),
+            view_state.integration_reference_h,
+        );
         preview.push_str(&format!("# Peak List ({} peaks)\n", peaks.len()));
-        preview.push_str(&format!(
-            "No{}PPM{}Intensity{}Rel%\n",
-            sep, sep, sep
-        ));
-        for (i, p) in peaks.iter().enumerate().take(20) {
+        if mode == IntensityMode::Absolute {
+            preview.push_str(&format!("No{}PPM{}Intensity", sep, sep));
+        } else {
+            preview.push_str(&format!(
+                "No{}PPM{}Intensity{}{}",
+                sep, sep, sep, mode.column_name()
+            ));
+        }
+        preview.push_str(&format!("{}FWHM_Hz{}FWHM_ppm\n", sep, sep));
+        for (i, (p, norm)) in peaks.iter().zip(&normalized).enumerate().take(20) {
             preview.push_str(&format!(
-                "{}{}{:.prec$}{}{:.4e}{}{:.1}\n",
+                "{}{}{:.prec$}{}{:.4e}",
                 i + 1,
                 sep,
                 p[0],
                 sep,
                 p[1],
-                sep,
-                p[1] / max_i * 100.0,
                 prec = dec,
             ));
+            if mode != IntensityMode::Absolute {
+                preview.push_str(&format!("{}{:.1}", sep, norm));
+            }
+            match processing::measure_linewidth(spectrum, p[0]) {
+                Some(lw) => preview.push_str(&format!("{}{:.2}{}{:.5}", sep, lw.fwhm_hz, sep, lw.fwhm_ppm())),
+                None => preview.push_str(&format!("{}-{}-", sep, sep)),
+            }
+            preview.push('\n');
         }
         if peaks.len() > 20 {
             preview.push_str(&format!("... ({} more)\n", peaks.len() - 20));
@@ -1092,12 +1026,14 @@ fn show_data_preview(
         let first_abs = ints.first().map(|r| r.2.abs()).unwrap_or(1.0).max(1e-20);
         let ref_h = view_state.integration_reference_h;
         preview.push_str(&format!("# Integrations ({} regions, ref={:.1}H)\n", ints.len(), ref_h));
-        preview.push_str(&format!("No{}Start{}End{}H_count\n", sep, sep, sep));
-        for (i, &(s, e, raw)) in ints.iter().enumerate() {
+        preview.push_str(&format!("# Integral baseline: {}\n", view_state.integral_baseline));
+        preview.push_str(&format!("No{}Start{}End{}H_count{}H_sigma\n", sep, sep, sep, sep));
+        let errors = processing::integral_uncertainties(spectrum, ints, view_state.integral_baseline, ref_h);
+        for (i, (&(s, e, raw), error)) in ints.iter().zip(&errors).enumerate() {
             let lo = s.min(e);
             let hi = s.max(e);
             preview.push_str(&format!(
-                "{}{}{:.prec$}{}{:.prec$}{}{:.2}\n",
+                "{}{}{:.prec$}{}{:.prec$}{}{:.2}{}{:.2}\n",
                 i + 1,
                 sep,
                 hi,
@@ -1105,6 +1041,8 @@ fn show_data_preview(
                 lo,
                 sep,
                 (raw / first_abs) * ref_h,
+                sep,
+                error.h_sigma,
                 prec = dec,
             ));
         }
@@ -1153,10 +1091,59 @@ fn show_data_preview(
         preview.push('\n');
     }
 
+    // Fitted peaks
+    let fitted: Vec<_> = view_state.peak_fits.iter().flat_map(|f| f.peaks.iter().map(move |p| (f, p))).collect();
+    if settings.include_peak_fits && !fitted.is_empty() {
+        preview.push_str(&format!("# Fitted Peaks ({} lines)\n", fitted.len()));
+        preview.push_str(&format!("No{}Shape{}Center{}FWHM_Hz{}Area\n", sep, sep, sep, sep));
+        for (i, (fit, p)) in fitted.iter().enumerate() {
+            preview.push_str(&format!(
+                "{}{}{}{}{:.prec$}{}{:.2}{}{:.4e}\n",
+                i + 1,
+                sep,
+                fit.shape,
+                sep,
+                p.center_ppm,
+                sep,
+                p.fwhm_hz,
+                sep,
+                p.area,
+                prec = dec,
+            ));
+        }
+        preview.push_str(&format!(
+            "# + component traces of {} regions (PPM, Fit, one column per line)\n",
+            view_state.peak_fits.len()
+        ));
+        preview.push('\n');
+    }
+
+    // Carbon types
+    let carbons = &view_state.carbon_types;
+    if settings.include_carbon_types && !carbons.is_empty() {
+        preview.push_str(&format!("# Carbon Types ({})\n", processing::carbon_type_summary(carbons)));
+        preview.push_str(&format!("No{}PPM{}Type{}DEPT90\n", sep, sep, sep));
+        for (i, c) in carbons.iter().enumerate() {
+            preview.push_str(&format!(
+                "{}{}{:.prec$}{}{}{}{}\n",
+                i + 1,
+                sep,
+                c.ppm,
+                sep,
+                c.carbon,
+                sep,
+                c.dept90.map(|r| format!("{:.2}", r)).unwrap_or_else(|| "-".to_string()),
+                prec = dec,
+            ));
+        }
+        preview.push('\n');
+    }
+
     if view_state.peaks.is_empty()
         && view_state.integrations.is_empty()
         && view_state.multiplets.is_empty()
         && view_state.j_couplings.is_empty()
+        && fitted.is_empty()
     {
         preview.push_str("No analysis data yet.\nRun peak detection or add integrations first.\n");
     }
@@ -3,7 +3,12 @@ pub mod phase_dialog;
 pub mod pipeline_panel;
 pub mod toolbar;
 pub mod contour_view;
+#[cfg(feature = "gpu-contours")]
+pub mod contour_gpu;
 pub mod conversion_dialog;
 pub mod export_dialog;
 pub mod export_tab;
+pub mod plot_scene;
+pub mod pulse_program_view;
 pub mod theme;
+pub mod preferences;
@@ -3,9 +3,13 @@
 /// Provides click-and-drag phase adjustment:
 /// - Horizontal drag → PH0 (zero-order)
 /// - Vertical drag → PH1 (first-order)
+/// - Phase on peak: click an isolated peak for PH0, a second one for PH1
 /// - Real-time preview of phase-corrected spectrum
+/// - 2D spectra: PH0/PH1 along F2 or F1, previewed on one row or column
 
 use crate::data::spectrum::SpectrumData;
+use crate::pipeline::processing::{self, PhaseAnchor, PhaseDim};
+use egui_plot::{Line, Plot, PlotPoints};
 use std::f64::consts::PI;
 
 /// State for the interactive phase correction mode
@@ -20,6 +24,10 @@ pub struct PhaseDialogState {
     pub sensitivity_ph1: f64,
     /// Preview spectrum (phased copy)
     pub preview: Vec<f64>,
+    /// Clicks on the spectrum phase on the peak under them
+    pub on_peak: bool,
+    /// Peaks phased on so far (one sets PH0, two set PH0 and PH1)
+    pub anchors: Vec<PhaseAnchor>,
 }
 
 impl Default for PhaseDialogState {
@@ -33,6 +41,8 @@ impl Default for PhaseDialogState {
             sensitivity_ph0: 0.5,
             sensitivity_ph1: 0.2,
             preview: Vec::new(),
+            on_peak: false,
+            anchors: Vec::new(),
         }
     }
 }
@@ -62,12 +72,32 @@ impl PhaseDialogState {
             self.preview[i] = re * phase.cos() - im * phase.sin();
         }
     }
+
+    /// Phase on the peak nearest `ppm`: the first peak sets PH0 (keeping
+    /// PH1), a second one sets both and ends peak picking.  Returns the
+    /// anchor, or `None` when no peak with an imaginary part is there.
+    pub fn phase_on_peak(&mut self, spectrum: &SpectrumData, ppm: f64) -> Option<PhaseAnchor> {
+        let anchor = processing::phase_anchor(spectrum, ppm)?;
+        if self.anchors.len() >= 2 {
+            self.anchors.clear();
+        }
+        self.anchors.push(anchor);
+        let (ph0, ph1) = processing::phase_from_anchors(&self.anchors[0], self.anchors.get(1), self.ph1);
+        self.ph0 = ph0;
+        self.ph1 = ph1;
+        if self.anchors.len() == 2 {
+            self.on_peak = false;
+        }
+        self.compute_preview(spectrum);
+        Some(anchor)
+    }
 }
 
 /// Show the interactive phase correction controls and handle drag input
 pub fn show_phase_controls(
     ui: &mut egui::Ui,
     state: &mut PhaseDialogState,
+    frequency_domain: bool,
 ) -> PhaseAction {
     let mut action = PhaseAction::None;
 
@@ -84,6 +114,17 @@ pub fn show_phase_controls(
             if ui.button("✖ Cancel").clicked() {
                 action = PhaseAction::Cancel;
             }
+            ui.separator();
+            let label = if state.on_peak { "🎯 Phase on Peak ●" } else { "🎯 Phase on Peak" };
+            if ui
+                .add_enabled(frequency_domain, egui::Button::new(label).selected(state.on_peak))
+                .on_hover_text("Click an isolated peak to make it absorptive (PH0); a second peak far from it sets PH1 too")
+                .on_disabled_hover_text("Phasing on a peak needs a frequency-domain spectrum")
+                .clicked()
+            {
+                state.on_peak = !state.on_peak;
+                state.anchors.clear();
+            }
         } else {
             if ui.button("⟳ Start Interactive Phasing").clicked() {
                 state.active = true;
@@ -114,9 +155,151 @@ pub fn show_phase_controls(
             }
         });
 
-        ui.label("Drag on spectrum: horizontal → PH0, vertical → PH1");
+        if state.on_peak {
+            ui.label(match state.anchors.len() {
+                0 => "Click an isolated peak to set PH0".to_string(),
+                _ => format!(
+                    "PH0 set on {:.3} ppm — click a second peak far from it to set PH1, or Apply",
+                    state.anchors[0].ppm
+                ),
+            });
+        } else {
+            ui.label("Drag on spectrum: horizontal → PH0, vertical → PH1");
+        }
+    }
+
+    action
+}
+
+/// State for 2D phase correction: one dimension at a time, previewed on a
+/// single row (F2) or column (F1)
+#[derive(Debug, Clone, Default)]
+pub struct Phase2dState {
+    pub active: bool,
+    pub dim: PhaseDim,
+    pub ph0: f64,
+    pub ph1: f64,
+    /// Row (F2) or column (F1) shown in the preview
+    pub trace: usize,
+    /// The trace as it is, and phased with the current values
+    pub original: Vec<f64>,
+    pub preview: Vec<f64>,
+}
+
+impl Phase2dState {
+    /// Number of traces along the current dimension
+    fn trace_count(&self, spectrum: &SpectrumData) -> usize {
+        match self.dim {
+            PhaseDim::F2 => spectrum.data_2d.len(),
+            PhaseDim::F1 => spectrum.data_2d.first().map_or(0, Vec::len),
+        }
+    }
+
+    /// Recompute the phased trace (non-destructive)
+    pub fn compute_preview(&mut self, spectrum: &SpectrumData) {
+        self.trace = self.trace.min(self.trace_count(spectrum).saturating_sub(1));
+        self.original = processing::phased_trace_2d(spectrum, self.dim, self.trace, 0.0, 0.0);
+        self.preview = processing::phased_trace_2d(spectrum, self.dim, self.trace, self.ph0, self.ph1);
+    }
+
+    /// Show the row or column through the largest point of the spectrum
+    pub fn select_tallest(&mut self, spectrum: &SpectrumData) {
+        let tallest = spectrum
+            .data_2d
+            .iter()
+            .enumerate()
+            .flat_map(|(r, row)| row.iter().enumerate().map(move |(c, v)| (r, c, v.abs())))
+            .max_by(|a, b| a.2.total_cmp(&b.2));
+        if let Some((r, c, _)) = tallest {
+            self.trace = match self.dim {
+                PhaseDim::F2 => r,
+                PhaseDim::F1 => c,
+            };
+        }
+        self.compute_preview(spectrum);
+    }
+}
+
+/// Show the 2D phase correction controls and the preview trace
+pub fn show_phase_controls_2d(ui: &mut egui::Ui, state: &mut Phase2dState, spectrum: &SpectrumData) -> PhaseAction {
+    let mut action = PhaseAction::None;
+
+    ui.horizontal(|ui| {
+        if !state.active {
+            if ui.button("⟳ 2D Phase Correction").clicked() {
+                state.active = true;
+                action = PhaseAction::Start;
+            }
+            return;
+        }
+        ui.colored_label(egui::Color32::from_rgb(0x1B, 0x7A, 0x3D), "⟳ 2D Phase Correction");
+        ui.separator();
+        if ui.button("✅ Apply").on_hover_text("Phase the whole spectrum along this dimension").clicked() {
+            action = PhaseAction::Apply;
+        }
+        if ui.button("✖ Close").clicked() {
+            action = PhaseAction::Cancel;
+        }
+        ui.separator();
+        for dim in [PhaseDim::F2, PhaseDim::F1] {
+            if ui.radio_value(&mut state.dim, dim, dim.to_string()).changed() {
+                state.select_tallest(spectrum);
+            }
+        }
+    });
+
+    if !state.active {
+        return action;
     }
 
+    ui.horizontal(|ui| {
+        let ph0 = ui.add(egui::Slider::new(&mut state.ph0, -360.0..=360.0).text("PH0 (°)").fixed_decimals(1));
+        let ph1 = ui.add(egui::Slider::new(&mut state.ph1, -360.0..=360.0).text("PH1 (°)").fixed_decimals(1));
+        ui.separator();
+        let last = state.trace_count(spectrum).saturating_sub(1);
+        let (label, axis) = match state.dim {
+            PhaseDim::F2 => ("Row", spectrum.axes.get(1)),
+            PhaseDim::F1 => ("Column", spectrum.axes.first()),
+        };
+        let trace = ui.add(egui::Slider::new(&mut state.trace, 0..=last).text(label));
+        if let Some(ax) = axis {
+            ui.label(format!("{:.2} ppm", ax.index_to_ppm(state.trace)));
+        }
+        if ui.button("📍 Tallest").on_hover_text("Trace through the largest point").clicked() {
+            state.select_tallest(spectrum);
+        }
+        if ph0.changed() || ph1.changed() || trace.changed() {
+            action = PhaseAction::UpdatePreview;
+        }
+    });
+
+    // Trace along the dimension being phased, high ppm on the left
+    let axis = match state.dim {
+        PhaseDim::F2 => spectrum.axes.first(),
+        PhaseDim::F1 => spectrum.axes.get(1),
+    };
+    let x = |i: usize| -axis.map_or(i as f64, |ax| ax.index_to_ppm(i));
+    let points = |values: &[f64]| -> PlotPoints { values.iter().enumerate().map(|(i, &v)| [x(i), v]).collect() };
+    Plot::new("phase_2d_trace")
+        .height(160.0)
+        .show_axes([true, false])
+        .x_axis_formatter(|mark, _| format!("{:.1}", -mark.value))
+        .allow_drag([true, false])
+        .allow_zoom([true, false])
+        .show(ui, |plot_ui| {
+            plot_ui.line(
+                Line::new(points(&state.original))
+                    .name("Original")
+                    .color(egui::Color32::from_rgba_premultiplied(170, 175, 190, 55))
+                    .width(0.8),
+            );
+            plot_ui.line(Line::new(points(&state.preview)).name("Phased").width(1.2));
+        });
+    ui.label(match state.dim {
+        PhaseDim::F2 => "Apply phases every row along F2; then pick F1 to phase the columns",
+        PhaseDim::F1 => "Apply phases every column along F1",
+    });
+
     action
 }
 
@@ -1,10 +1,23 @@
 /// Processing pipeline panel — left sidebar with processing controls
 
-use crate::pipeline::processing::WindowFunction;
+use crate::data::spectrum::{F1Acquisition, Nucleus, Provenance};
+use crate::pipeline::processing::{BaselineMethod, BASELINE_MAX_ORDER, DeadTimeMode, Ft2dMode, IntegralBaseline, IntensityMode, LineShape, LpMode, MultipletParams, NUS_DEFAULT_ITERATIONS, PeakSign, T1NoiseMethod, VolumeRegion, VolumeShape, WindowFunction};
 
 /// State for the pipeline panel UI
 #[derive(Debug, Clone)]
 pub struct PipelinePanelState {
+    // Dead time (set from Preferences on every load)
+    pub dead_time_points: usize,
+    pub dead_time_mode: DeadTimeMode,
+
+    // Linear prediction
+    pub lp_order: usize,
+    pub lp_predicted: usize,
+    pub lp_mode: LpMode,
+
+    // NUS reconstruction
+    pub nus_iterations: usize,
+
     // Apodization
     pub apod_type: usize, // 0=None, 1=EM, 2=GM, 3=SineBell, 4=CosineBell
     pub em_lb: f64,
@@ -13,6 +26,8 @@ pub struct PipelinePanelState {
     pub sp_power: f64,
     pub sp_offset: f64,
     pub sp_end: f64,
+    /// Nucleus whose line-broadening default `em_lb` was last set from
+    pub lb_defaults_for: Option<Nucleus>,
 
     // Zero fill
     pub zf_factor: usize, // multiply current size by 2^factor
@@ -21,25 +36,91 @@ pub struct PipelinePanelState {
     pub ph0: f64,
     pub ph1: f64,
 
+    // Baseline correction
+    pub baseline_model: usize, // 0=Linear, 1=Polynomial, 2=ALS
+    pub bl_order: usize,
+    pub bl_lambda: f64,
+    pub bl_p: f64,
+    /// Draw the fitted baseline over the spectrum before applying it
+    pub show_baseline_preview: bool,
+
     // Peak detection
     pub peak_threshold: f64, // 0.0–1.0 fraction of max
     pub min_peak_spacing_hz: f64, // minimum Hz between peaks (lower = more peaks)
+    pub peak_sign: PeakSign, // set from the experiment type on load
+
+    // Multiplet detection
+    pub multiplet: MultipletParams,
 
     // FT configuration
     pub ft_use_imaginary: bool,
+    /// 2D FT output (reset to the experiment's default on every load)
+    pub ft2d_mode: Ft2dMode,
+    /// t1 sampling the F1 transform assumes (read from the data on load)
+    pub f1_acquisition: F1Acquisition,
+
+    // Peak fitting
+    pub fit_shape: LineShape,
+
+    // Resolution-enhancement preview (Lorentz-to-Gauss)
+    pub re_lb: f64, // Lorentzian width removed (Hz)
+    pub re_gb: f64, // Gaussian FWHM added (Hz)
+
+    // t1 noise suppression (2D)
+    pub t1_method: T1NoiseMethod,
+    pub t1_strength: f64, // 0.0–1.0 fraction of the ridge estimate
+
+    // 2D cross peaks and assignment transfer
+    pub cross_threshold: f64, // fraction of max |intensity|
+    pub assign_tolerance_ppm: f64, // F2 match window for 1D assignments
+    pub volume_region: VolumeRegion, // summed around each cross peak
+
+    // Processing templates: name given to the next saved template
+    pub template_name: String,
+
+    // Manual F1 calibration: a signal seen at one ppm that belongs at another
+    pub f1_observed_ppm: f64,
+    pub f1_true_ppm: f64,
+
+    // External prediction tools (names from the preferences)
+    pub external_tools: Vec<String>,
+    pub selected_tool: usize,
 
     // Solvent suppression
     pub solvent_preset: usize, // 0=Custom, 1..N = preset solvents
     pub solvent_center: f64,
     pub solvent_width: f64,
 
+    // Reference deconvolution
+    pub refdec_center: f64,
+    pub refdec_width: f64,
+    pub refdec_lw: f64, // target Lorentzian linewidth (Hz)
+
     // State tracking
     pub show_before_after: bool,
+    pub comparison_mode: ComparisonMode,
+    /// Show an after − before trace below the side-by-side panes
+    pub show_difference: bool,
+}
+
+/// How the before/after comparison is displayed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComparisonMode {
+    /// Faded "before" line drawn under the current spectrum
+    Overlay,
+    /// Before and after in separate panes with linked zoom
+    SideBySide,
 }
 
 impl Default for PipelinePanelState {
     fn default() -> Self {
         Self {
+            dead_time_points: 0,
+            dead_time_mode: DeadTimeMode::BackwardLp,
+            lp_order: 8,
+            lp_predicted: 64,
+            lp_mode: LpMode::Mixed,
+            nus_iterations: NUS_DEFAULT_ITERATIONS,
             apod_type: 1, // Default to EM
             em_lb: 0.3,
             gm_gb: 0.1,
@@ -47,45 +128,157 @@ impl Default for PipelinePanelState {
             sp_power: 2.0,
             sp_offset: 0.5,
             sp_end: 1.0,
+            lb_defaults_for: None,
             zf_factor: 1,
             ph0: 0.0,
             ph1: 0.0,
+            baseline_model: 0,
+            bl_order: 3,
+            bl_lambda: 1e5,
+            bl_p: 0.01,
+            show_baseline_preview: false,
             peak_threshold: 0.05,
             min_peak_spacing_hz: 5.0,
+            peak_sign: PeakSign::Positive,
+            multiplet: MultipletParams::default(),
             ft_use_imaginary: true,
+            ft2d_mode: Ft2dMode::PhaseSensitive,
+            f1_acquisition: F1Acquisition::default(),
+            fit_shape: LineShape::default(),
+            re_lb: 1.0,
+            re_gb: 1.5,
+            t1_method: T1NoiseMethod::ColumnMedian,
+            t1_strength: 0.8,
+            cross_threshold: 0.05,
+            assign_tolerance_ppm: 0.03,
+            volume_region: VolumeRegion::default(),
+            template_name: String::new(),
+            f1_observed_ppm: 0.0,
+            f1_true_ppm: 0.0,
+            external_tools: Vec::new(),
+            selected_tool: 0,
             solvent_preset: 0, // Custom
             solvent_center: 4.7, // Water
             solvent_width: 0.1,
+            refdec_center: 0.0, // TMS
+            refdec_width: 0.1,
+            refdec_lw: 1.0,
             show_before_after: false,
+            comparison_mode: ComparisonMode::Overlay,
+            show_difference: false,
         }
     }
 }
 
+impl PipelinePanelState {
+    /// Nucleus-specific processing defaults: heteronuclear lines are broader
+    /// and noisier than 1H, so they get more exponential line broadening.
+    ///
+    /// Only applied when the nucleus changes and the user has not edited
+    /// the value since the last default, so a chosen LB sticks across loads.
+    pub fn apply_nucleus_defaults(&mut self, nucleus: &Nucleus) {
+        let Some(lb) = default_em_lb(nucleus) else {
+            return;
+        };
+        let untouched = match &self.lb_defaults_for {
+            Some(previous) => default_em_lb(previous) == Some(self.em_lb),
+            None => self.em_lb == Self::default().em_lb,
+        };
+        if self.lb_defaults_for.as_ref() != Some(nucleus) && untouched {
+            self.em_lb = lb;
+        }
+        self.lb_defaults_for = Some(nucleus.clone());
+    }
+}
+
+/// Default exponential line broadening (Hz) for a nucleus
+fn default_em_lb(nucleus: &Nucleus) -> Option<f64> {
+    match nucleus {
+        Nucleus::H1 => Some(0.3),
+        // Quadrupolar 2H lines are several Hz wide
+        Nucleus::H2 => Some(1.0),
+        Nucleus::C13 | Nucleus::F19 => Some(1.0),
+        Nucleus::P31 | Nucleus::N15 => Some(2.0),
+        Nucleus::Other(_) => None,
+    }
+}
+
 /// Actions triggered by the pipeline panel
 #[derive(Debug, Clone, PartialEq)]
 pub enum PipelineAction {
     None,
+    QuickProcess,
+    /// Remove the digital-filter group delay left in a Bruker FID
+    RemoveGroupDelay,
+    ApplyDeadTime,
+    ApplyLinearPrediction,
+    ReconstructNus,
     ApplyApodization,
     ApplyZeroFill,
     ApplyFT,
     ApplyFT2D,
+    ApplyInverseFT,
     ApplyPhaseCorrection,
     ApplyAutoPhase,
     ApplyBaselineCorrection,
     ApplyManualBaseline,
     ToggleBaselinePicking,
     ClearBaselinePoints,
+    /// Suppress the picked regions, or the center/width region when none
     ApplySolventSuppression,
+    ToggleSolventPicking,
+    RemoveSolventRegion(usize),
+    ClearSolventRegions,
+    /// FIDDLE against the reference line in the chosen window
+    ApplyReferenceDeconvolution,
+    /// Interpolate across the zero-frequency spike at the centre
+    RemoveCenterGlitch,
     DetectPeaks,
     ClearPeaks,
     TogglePeakPicking,
     RemoveLastPeak,
+    CompareIsotopeShifts,
+    ComparePeakLists,
+    /// Compare with the spectrum after a D₂O shake and flag exchangeable peaks
+    CompareD2oShake,
+    /// Label peaks matching the common-impurity table
+    IdentifyCommonImpurities,
+    /// Classify the peaks of a DEPT-135 / APT spectrum as CH, CH2 or CH3
+    ClassifyCarbons,
     DetectMultiplets,
+    /// Detect multiplets only within the visible ppm range
+    DetectMultipletsInView,
     ClearMultiplets,
     ToggleJCouplingPicking,
+    ToggleAutoJPicking,
     ClearJCouplings,
+    ToggleLinewidthPicking,
+    ClearLinewidths,
+    TogglePeakFitPicking,
+    ClearPeakFits,
+    ShowPeakTable,
     ToggleIntegrationPicking,
     ClearIntegrations,
+    ToggleEnhancePicking,
+    ClearEnhancePreview,
+    ApplyT1NoiseSuppression,
+    ApplyMagnitude2D,
+    PickCrossPeaks,
+    IntegrateVolumes,
+    TransferAssignments,
+    ShowCorrelationTable,
+    /// Shift the F1 axis so the observed ppm reads the true ppm
+    CalibrateF1,
+    ShowReferenceLibrary,
+    /// Save the steps applied so far as a processing template
+    SaveTemplate,
+    /// Run a saved template on the current spectrum
+    ApplyTemplate,
+    /// Run a saved template on every dataset of a folder
+    ApplyTemplateToFolder,
+    /// Run the external tool at this index of the preferences list
+    RunExternalTool(usize),
+    ClearToolResults,
 }
 
 /// Picking mode states passed from the spectrum view, so buttons can be highlighted
@@ -94,20 +287,74 @@ pub struct PickingModes {
     pub baseline_picking: bool,
     pub integration_picking: bool,
     pub j_coupling_picking: bool,
+    pub auto_j_picking: bool,
+    pub linewidth_picking: bool,
+    pub fit_picking: bool,
+    pub enhance_picking: bool,
+    pub solvent_picking: bool,
+    /// Suppression regions picked on the spectrum: (low_ppm, high_ppm)
+    pub solvent_regions: Vec<(f64, f64)>,
+}
+
+/// What the panel shows about the loaded data and the session; read only
+pub struct PipelinePanelContext {
+    pub has_data: bool,
+    pub is_freq_domain: bool,
+    pub is_2d: bool,
+    pub has_imaginary: bool,
+    /// Sampled and full t1 increments of non-uniformly sampled data
+    pub nus: Option<(usize, usize)>,
+    /// Group delay left in the data on loading, in points
+    pub group_delay: Option<f64>,
+    pub provenance: Provenance,
+    /// Entries in the processing log
+    pub operation_count: usize,
+    pub picking: PickingModes,
+    /// A spectrum to compare with is kept from before the last step
+    pub has_before_snapshot: bool,
+}
+
+/// Offer to remove a group delay that was left in the data on loading
+fn group_delay_section(ui: &mut egui::Ui, grpdly: f64, action: &mut PipelineAction) {
+    ui.collapsing("📶 Digital Filter", |ui| {
+        ui.label(
+            egui::RichText::new(format!(
+                "The group delay ({:.2} points) was kept:
+the FID looked already corrected.
+Remove it if the spectrum still shows
+a strong first-order phase roll.",
+                grpdly
+            ))
+            .size(11.0)
+            .color(egui::Color32::from_rgb(0x88, 0x8C, 0x94)),
+        );
+        if ui.button("▶ Remove Group Delay").clicked() {
+            *action = PipelineAction::RemoveGroupDelay;
+        }
+    });
 }
 
 /// Render the pipeline panel in the left sidebar
 pub fn show_pipeline_panel(
     ui: &mut egui::Ui,
     state: &mut PipelinePanelState,
-    has_data: bool,
-    is_freq_domain: bool,
-    is_2d: bool,
-    operation_count: usize,
-    picking: &PickingModes,
+    context: &PipelinePanelContext,
     integration_ref_h: &mut f64,
-    has_before_snapshot: bool,
+    integral_baseline: &mut IntegralBaseline,
+    intensity_mode: &mut IntensityMode,
 ) -> PipelineAction {
+    let &PipelinePanelContext {
+        has_data,
+        is_freq_domain,
+        is_2d,
+        has_imaginary,
+        nus,
+        group_delay,
+        provenance,
+        operation_count,
+        ref picking,
+        has_before_snapshot,
+    } = context;
     let mut action = PipelineAction::None;
 
     ui.vertical_centered(|ui| {
@@ -136,11 +383,81 @@ pub fn show_pipeline_panel(
             .size(11.5)
             .color(egui::Color32::from_rgb(0x66, 0x6C, 0x78)),
     );
+    if provenance == Provenance::VendorProcessed {
+        let note = if is_freq_domain {
+            "Loaded as a processed spectrum: window, zero fill and FT\nwere already applied by the spectrometer software."
+        } else {
+            "Pseudo-FID recomputed from a processed spectrum:\nit keeps the vendor's window and zero fill."
+        };
+        ui.label(
+            egui::RichText::new("📦 Vendor-processed data")
+                .size(11.5)
+                .color(egui::Color32::from_rgb(0xB0, 0x6A, 0x00)),
+        )
+        .on_hover_text(note);
+    }
     ui.add_space(4.0);
     ui.separator();
 
+    // Why an operation does not apply to the loaded data (greyed out, shown on hover)
+    let no_imaginary = (!has_imaginary).then_some(
+        "No imaginary part was loaded (e.g. Bruker 1r without 1i),\nso the data cannot be phased.",
+    );
+    let not_measured = (provenance == Provenance::VendorProcessed).then_some(
+        "This FID was recomputed from a processed spectrum:\nits first points are not the receiver's dead time.",
+    );
+
     // ── Time Domain Operations ──
     if !is_freq_domain {
+        if ui
+            .button("⚡ Quick Process")
+            .on_hover_text("Run the processing profile for this experiment type\n(Preferences → Processing profiles)")
+            .clicked()
+        {
+            action = PipelineAction::QuickProcess;
+        }
+        ui.add_space(4.0);
+        if let Some(grpdly) = group_delay {
+            group_delay_section(ui, grpdly, &mut action);
+        }
+        ui.collapsing("⏱ Dead Time", |ui| {
+            ui.add(
+                egui::Slider::new(&mut state.dead_time_points, 0..=64)
+                    .text("First points"),
+            );
+            ui.radio_value(&mut state.dead_time_mode, DeadTimeMode::BackwardLp, "Rebuild (backward LP)");
+            ui.radio_value(&mut state.dead_time_mode, DeadTimeMode::Drop, "Drop (left shift)");
+            ui.label(
+                egui::RichText::new("Removes probe ring-down that rolls\nthe baseline of 19F/31P spectra.")
+                    .size(11.0)
+                    .color(egui::Color32::from_rgb(0x88, 0x8C, 0x94)),
+            );
+            if state.dead_time_points > 0 && gated_button(ui, "▶ Apply Dead Time", not_measured).clicked() {
+                action = PipelineAction::ApplyDeadTime;
+            }
+        });
+
+        ui.collapsing("🔮 Linear Prediction", |ui| {
+            ui.add(egui::Slider::new(&mut state.lp_order, 2..=32).text("Coefficients"));
+            ui.add(
+                egui::Slider::new(&mut state.lp_predicted, 8..=1024)
+                    .logarithmic(true)
+                    .text("Predicted points"),
+            );
+            ui.radio_value(&mut state.lp_mode, LpMode::Forward, "Forward");
+            ui.radio_value(&mut state.lp_mode, LpMode::Backward, "Backward");
+            ui.radio_value(&mut state.lp_mode, LpMode::Mixed, "Forward-backward");
+            ui.label(
+                egui::RichText::new("Extends truncated FIDs (F1 for 2D)
+before FT instead of zero-filling.")
+                    .size(11.0)
+                    .color(egui::Color32::from_rgb(0x88, 0x8C, 0x94)),
+            );
+            if gated_button(ui, "▶ Apply LP", not_measured).clicked() {
+                action = PipelineAction::ApplyLinearPrediction;
+            }
+        });
+
         ui.collapsing("📊 Apodization", |ui| {
             egui::ComboBox::from_label("Window Function")
                 .selected_text(match state.apod_type {
@@ -215,13 +532,48 @@ pub fn show_pipeline_panel(
         });
 
         ui.separator();
+        if let Some((sampled, grid)) = nus.filter(|_| is_2d) {
+            ui.label(
+                egui::RichText::new(format!("🧩 NUS: {} of {} t1 increments", sampled, grid))
+                    .size(12.0)
+                    .color(egui::Color32::from_rgb(0xB0, 0x6A, 0x00)),
+            )
+            .on_hover_text("Non-uniformly sampled data FTs into artefacts
+until the skipped increments are reconstructed.");
+            ui.add(egui::Slider::new(&mut state.nus_iterations, 20..=1000).logarithmic(true).text("IST iterations"));
+            if ui.button("▶ Reconstruct NUS (IST)").clicked() {
+                action = PipelineAction::ReconstructNus;
+            }
+            ui.add_space(4.0);
+        }
         if is_2d {
             // 2D Fourier Transform
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut state.ft2d_mode, Ft2dMode::PhaseSensitive, "Phase-sensitive");
+                ui.radio_value(&mut state.ft2d_mode, Ft2dMode::Magnitude, "Magnitude");
+            });
+            ui.horizontal(|ui| {
+                ui.label("t1 sampling:");
+                egui::ComboBox::from_id_salt("f1_acquisition")
+                    .selected_text(state.f1_acquisition.to_string())
+                    .show_ui(ui, |ui| {
+                        for mode in F1Acquisition::ALL {
+                            ui.selectable_value(&mut state.f1_acquisition, mode, mode.to_string());
+                        }
+                    });
+            })
+            .response
+            .on_hover_text("Read from the acquisition parameters when known (Bruker FnMODE, Agilent phase)");
             if ui.button("🔄 2D Fourier Transform").clicked() {
                 action = PipelineAction::ApplyFT2D;
             }
+            let note = match (state.ft2d_mode, state.f1_acquisition.is_hypercomplex()) {
+                (Ft2dMode::PhaseSensitive, true) => "Applies FFT along F2, then a hypercomplex\nFFT along F1 keeping all four quadrants,\nso both dimensions can be phased.",
+                (Ft2dMode::PhaseSensitive, false) => "Applies complex FFT along F2 then F1,\nkeeping real and imaginary parts.",
+                (Ft2dMode::Magnitude, _) => "Applies FFT along F2 then F1,\nresult in magnitude mode (phase discarded).",
+            };
             ui.label(
-                egui::RichText::new("Applies complex FFT along F2 then F1,\nresult in magnitude mode.")
+                egui::RichText::new(note)
                     .size(11.0)
                     .color(egui::Color32::from_rgb(0x88, 0x8C, 0x94)),
             );
@@ -235,7 +587,121 @@ pub fn show_pipeline_panel(
     }
 
     // ── Frequency Domain Operations ──
+    if is_freq_domain && is_2d {
+        ui.collapsing("📐 Magnitude (2D)", |ui| {
+            ui.label(
+                egui::RichText::new("Combines the imaginary quadrants of\nphase-sensitive data (e.g. Bruker 2ri/2ir/2ii)\ninto a magnitude spectrum.")
+                    .size(11.0)
+                    .color(egui::Color32::from_rgb(0x88, 0x8C, 0x94)),
+            );
+            if gated_button(ui, "▶ Convert to Magnitude", no_imaginary).clicked() {
+                action = PipelineAction::ApplyMagnitude2D;
+            }
+        });
+
+        ui.collapsing("〰 t1 Noise Suppression", |ui| {
+            ui.radio_value(&mut state.t1_method, T1NoiseMethod::ColumnMedian, "Column median");
+            ui.radio_value(&mut state.t1_method, T1NoiseMethod::ReferenceRows, "Reference rows (quietest 10%)");
+            ui.add(
+                egui::Slider::new(&mut state.t1_strength, 0.0..=1.0)
+                    .text("Strength")
+                    .fixed_decimals(2),
+            );
+            ui.label(
+                egui::RichText::new("Cosmetic: cleans up HMBC streaks for\ncontour plots; not for quantification.")
+                    .size(11.0)
+                    .color(egui::Color32::from_rgb(0x88, 0x8C, 0x94)),
+            );
+            if ui.button("▶ Apply t1 Noise Suppression").clicked() {
+                action = PipelineAction::ApplyT1NoiseSuppression;
+            }
+        });
+
+        ui.collapsing("🎯 F1 Calibration", |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Signal at");
+                ui.add(egui::DragValue::new(&mut state.f1_observed_ppm).speed(0.01).fixed_decimals(3).suffix(" ppm"));
+            });
+            ui.horizontal(|ui| {
+                ui.label("belongs at");
+                ui.add(egui::DragValue::new(&mut state.f1_true_ppm).speed(0.01).fixed_decimals(3).suffix(" ppm"));
+            });
+            ui.label(
+                egui::RichText::new("Moves the indirect axis when the carrier\nwas not stored or is referenced differently\n(e.g. the solvent 13C line of an HSQC).")
+                    .size(11.0)
+                    .color(egui::Color32::from_rgb(0x88, 0x8C, 0x94)),
+            );
+            if ui.button("▶ Calibrate F1").clicked() {
+                action = PipelineAction::CalibrateF1;
+            }
+        });
+
+        ui.collapsing("🔗 Cross Peaks & Assignments", |ui| {
+            ui.add(
+                egui::Slider::new(&mut state.cross_threshold, 0.01..=0.5)
+                    .text("Threshold")
+                    .fixed_decimals(2),
+            );
+            if ui.button("🔍 Pick Cross Peaks").clicked() {
+                action = PipelineAction::PickCrossPeaks;
+            }
+            ui.separator();
+            let region = &mut state.volume_region;
+            ui.horizontal(|ui| {
+                ui.label("Volume region:");
+                ui.radio_value(&mut region.shape, VolumeShape::Ellipse, "Ellipse");
+                ui.radio_value(&mut region.shape, VolumeShape::Box, "Box");
+            });
+            ui.add(
+                egui::Slider::new(&mut region.f2_half_width, 0.005..=0.2)
+                    .text("± F2 (ppm)")
+                    .fixed_decimals(3),
+            );
+            ui.add(
+                egui::Slider::new(&mut region.f1_half_width, 0.05..=5.0)
+                    .text("± F1 (ppm)")
+                    .fixed_decimals(2),
+            );
+            ui.label(
+                egui::RichText::new("Sums each region around the picked peaks,\nless a plane fitted just outside it.")
+                    .size(11.0)
+                    .color(egui::Color32::from_rgb(0x88, 0x8C, 0x94)),
+            );
+            if ui.button("∫ Integrate Volumes").clicked() {
+                action = PipelineAction::IntegrateVolumes;
+            }
+            ui.separator();
+            ui.add(
+                egui::Slider::new(&mut state.assign_tolerance_ppm, 0.005..=0.1)
+                    .text("F2 tolerance (ppm)")
+                    .fixed_decimals(3),
+            );
+            ui.label(
+                egui::RichText::new("Labels unassigned cross peaks with the\nmultiplets (or peaks) of a saved 1H project.")
+                    .size(11.0)
+                    .color(egui::Color32::from_rgb(0x88, 0x8C, 0x94)),
+            );
+            if ui.button("📥 Transfer 1D Assignments…").clicked() {
+                action = PipelineAction::TransferAssignments;
+            }
+            if ui.button("📋 Correlation Table").clicked() {
+                action = PipelineAction::ShowCorrelationTable;
+            }
+        });
+    }
+
     if is_freq_domain {
+        let ift_note = if has_imaginary {
+            "Back to the time domain to change the window\nor zero fill and transform again"
+        } else {
+            "Back to the time domain to change the window\nor zero fill and transform again.\nThe imaginary part is rebuilt by a Hilbert\ntransform, keeping half the points."
+        };
+        if ui.button("↩ Inverse Fourier Transform").on_hover_text(ift_note).clicked() {
+            action = PipelineAction::ApplyInverseFT;
+        }
+        if let Some(grpdly) = group_delay.filter(|_| has_imaginary && !is_2d) {
+            group_delay_section(ui, grpdly, &mut action);
+        }
         ui.collapsing("🔧 Phase Correction", |ui| {
             ui.add(
                 egui::Slider::new(&mut state.ph0, -360.0..=360.0)
@@ -248,10 +714,10 @@ pub fn show_pipeline_panel(
                     .fixed_decimals(1),
             );
             ui.horizontal(|ui| {
-                if ui.button("▶ Apply").clicked() {
+                if gated_button(ui, "▶ Apply", no_imaginary).clicked() {
                     action = PipelineAction::ApplyPhaseCorrection;
                 }
-                if ui.button("🤖 Auto Phase").clicked() {
+                if gated_button(ui, "🤖 Auto Phase", no_imaginary).clicked() {
                     action = PipelineAction::ApplyAutoPhase;
                 }
             });
@@ -259,6 +725,43 @@ pub fn show_pipeline_panel(
         });
 
         ui.collapsing("📐 Baseline Correction", |ui| {
+            egui::ComboBox::from_label("Model")
+                .selected_text(match state.baseline_model {
+                    0 => "Linear",
+                    1 => "Polynomial",
+                    2 => "ALS (Whittaker)",
+                    _ => "Unknown",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut state.baseline_model, 0, "Linear");
+                    ui.selectable_value(&mut state.baseline_model, 1, "Polynomial");
+                    ui.selectable_value(&mut state.baseline_model, 2, "ALS (Whittaker)");
+                });
+            match state.baseline_model {
+                0 => {
+                    ui.label("Line between the outer 10% means.");
+                }
+                1 => {
+                    ui.add(egui::Slider::new(&mut state.bl_order, 1..=BASELINE_MAX_ORDER).text("Order"));
+                    ui.label("Fitted to signal-free points only.");
+                }
+                2 => {
+                    ui.add(
+                        egui::Slider::new(&mut state.bl_lambda, 1e1..=1e10)
+                            .logarithmic(true)
+                            .text("λ (smoothness)"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut state.bl_p, 0.001..=0.2)
+                            .logarithmic(true)
+                            .text("p (asymmetry)")
+                            .fixed_decimals(3),
+                    );
+                }
+                _ => {}
+            }
+            ui.checkbox(&mut state.show_baseline_preview, "Preview baseline")
+                .on_hover_text("Draw the fitted baseline dashed over the spectrum");
             if ui.button("▶ Auto Baseline").clicked() {
                 action = PipelineAction::ApplyBaselineCorrection;
             }
@@ -285,6 +788,56 @@ pub fn show_pipeline_panel(
             }
         });
 
+        if !is_2d {
+            ui.collapsing("🎯 Reference Deconvolution", |ui| {
+                let presets = [
+                    ("TMS (0.00 ppm)", 0.0),
+                    ("CHCl\u{2083} (7.26 ppm)", 7.26),
+                    ("DMSO-d\u{2086} (2.50 ppm)", 2.50),
+                    ("HDO (4.79 ppm)", 4.79),
+                ];
+                ui.horizontal_wrapped(|ui| {
+                    for (name, center) in presets {
+                        if ui.small_button(name).clicked() {
+                            state.refdec_center = center;
+                        }
+                    }
+                });
+                ui.add(
+                    egui::Slider::new(&mut state.refdec_center, -1.0..=15.0)
+                        .text("Reference (ppm)")
+                        .fixed_decimals(3),
+                );
+                ui.add(
+                    egui::Slider::new(&mut state.refdec_width, 0.02..=0.5)
+                        .text("Window (ppm)")
+                        .fixed_decimals(3),
+                );
+                ui.add(
+                    egui::Slider::new(&mut state.refdec_lw, 0.1..=5.0)
+                        .text("Target LW (Hz)")
+                        .fixed_decimals(2),
+                );
+                ui.label(
+                    egui::RichText::new("Reshapes every line by the correction that\nturns the reference into an ideal Lorentzian\n(FIDDLE): removes shimming distortions.")
+                        .size(11.0)
+                        .color(egui::Color32::from_rgb(0x88, 0x8C, 0x94)),
+                );
+                if gated_button(ui, "▶ Apply Reference Deconvolution", no_imaginary).clicked() {
+                    action = PipelineAction::ApplyReferenceDeconvolution;
+                }
+            });
+        }
+
+        ui.collapsing("✨ Center Glitch", |ui| {
+            ui.label("Spike at the carrier left by a DC offset");
+            ui.label("of the FID; detection settings are in");
+            ui.label("Preferences → Center glitch.");
+            if ui.button("▶ Remove Center Glitch").clicked() {
+                action = PipelineAction::RemoveCenterGlitch;
+            }
+        });
+
         ui.collapsing("🧪 Solvent Suppression", |ui| {
             // Solvent presets
             let presets = [
@@ -319,7 +872,37 @@ pub fn show_pipeline_panel(
                     .text("Width (ppm)")
                     .fixed_decimals(2),
             );
-            if ui.button("▶ Apply Solvent Suppression").clicked() {
+            ui.separator();
+            ui.label("Or drag across solvent signals on the");
+            ui.label("spectrum; the shaded red area is removed.");
+            ui.horizontal(|ui| {
+                let sol_label = if picking.solvent_picking { "🎯 Picking ●" } else { "🎯 Pick Regions" };
+                let sol_btn = egui::Button::new(
+                    egui::RichText::new(sol_label)
+                        .color(if picking.solvent_picking { egui::Color32::WHITE } else { ui.visuals().text_color() })
+                )
+                .fill(if picking.solvent_picking { egui::Color32::from_rgb(0x1E, 0x88, 0xE5) } else { ui.visuals().widgets.inactive.bg_fill });
+                if ui.add_enabled(is_freq_domain, sol_btn).clicked() {
+                    action = PipelineAction::ToggleSolventPicking;
+                }
+                if !picking.solvent_regions.is_empty() && ui.button("✕ Clear").clicked() {
+                    action = PipelineAction::ClearSolventRegions;
+                }
+            });
+            for (i, &(lo, hi)) in picking.solvent_regions.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{:.2}–{:.2} ppm", lo, hi));
+                    if ui.small_button("✕").on_hover_text("Remove this region").clicked() {
+                        action = PipelineAction::RemoveSolventRegion(i);
+                    }
+                });
+            }
+            let apply_label = if picking.solvent_regions.is_empty() {
+                "▶ Apply Solvent Suppression".to_string()
+            } else {
+                format!("▶ Suppress {} Regions", picking.solvent_regions.len())
+            };
+            if ui.button(apply_label).clicked() {
                 action = PipelineAction::ApplySolventSuppression;
             }
         });
@@ -335,6 +918,13 @@ pub fn show_pipeline_panel(
                     .text("Min spacing (Hz)")
                     .fixed_decimals(1),
             );
+            ui.horizontal(|ui| {
+                ui.label("Sign:");
+                ui.radio_value(&mut state.peak_sign, PeakSign::Positive, "+");
+                ui.radio_value(&mut state.peak_sign, PeakSign::Negative, "−");
+                ui.radio_value(&mut state.peak_sign, PeakSign::Both, "±")
+                    .on_hover_text("Both signs, for DEPT-135 / APT");
+            });
             ui.horizontal(|ui| {
                 if ui.button("▶ Detect Peaks").clicked() {
                     action = PipelineAction::DetectPeaks;
@@ -342,6 +932,19 @@ pub fn show_pipeline_panel(
                 if ui.button("✕ Clear").clicked() {
                     action = PipelineAction::ClearPeaks;
                 }
+                if ui.button("📋 Table").on_hover_text("Peak list with FWHM linewidths").clicked() {
+                    action = PipelineAction::ShowPeakTable;
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Intensity:");
+                egui::ComboBox::from_id_salt("peak_intensity_mode")
+                    .selected_text(intensity_mode.label())
+                    .show_ui(ui, |ui| {
+                        for mode in IntensityMode::ALL {
+                            ui.selectable_value(intensity_mode, mode, mode.label());
+                        }
+                    });
             });
             ui.separator();
             ui.label("✋ Manual peak picking:");
@@ -360,11 +963,72 @@ pub fn show_pipeline_panel(
                 }
             });
             ui.separator();
+            ui.label("⚖ 1H/2H exchange:");
+            if ui
+                .button("Compare with 2H/1H Spectrum…")
+                .on_hover_text("Pair these peaks with a deuterium (or proton) spectrum of the same sample")
+                .clicked()
+            {
+                action = PipelineAction::CompareIsotopeShifts;
+            }
+            ui.label("⇄ Reaction monitoring / stability:");
+            if ui
+                .button("Compare Peaks with Spectrum…")
+                .on_hover_text("List common, new and missing peaks against another spectrum of the same nucleus")
+                .clicked()
+            {
+                action = PipelineAction::ComparePeakLists;
+            }
+            ui.label("💧 Exchangeable protons:");
+            if ui
+                .button("Compare with D₂O Shake…")
+                .on_hover_text("Flag the OH/NH peaks that disappear in a spectrum recorded after shaking with D₂O")
+                .clicked()
+            {
+                action = PipelineAction::CompareD2oShake;
+            }
+            ui.label("🧴 Common impurities:");
+            if ui
+                .button("Identify Impurities")
+                .on_hover_text("Label peaks matching water, grease and common solvents
+(Gottlieb et al. table for the sample's solvent)")
+                .clicked()
+            {
+                action = PipelineAction::IdentifyCommonImpurities;
+            }
+            ui.label("🧬 Carbon types (DEPT-135 / APT):");
+            if ui
+                .button("Classify Carbons…")
+                .on_hover_text("CH/CH3 and CH2 from the peak signs; a DEPT-90 in the session tells CH from CH3")
+                .clicked()
+            {
+                action = PipelineAction::ClassifyCarbons;
+            }
+            ui.separator();
             ui.label("🎵 Multiplet analysis:");
+            ui.add(
+                egui::Slider::new(&mut state.multiplet.max_j_hz, 2.0..=40.0)
+                    .text("Max J (Hz)")
+                    .fixed_decimals(1),
+            );
+            ui.add(
+                egui::Slider::new(&mut state.multiplet.max_intensity_ratio, 1.5..=50.0)
+                    .logarithmic(true)
+                    .text("Max line ratio")
+                    .fixed_decimals(1),
+            )
+            .on_hover_text("Neighbouring lines differing in height by more than this factor are not grouped");
+            ui.add(egui::Slider::new(&mut state.multiplet.min_lines, 1..=8).text("Min lines"));
             ui.horizontal(|ui| {
                 if ui.button("▶ Detect Multiplets").clicked() {
                     action = PipelineAction::DetectMultiplets;
                 }
+                if ui.button("🔍 In View")
+                    .on_hover_text("Re-detect only within the zoomed ppm range, keeping the other multiplets")
+                    .clicked()
+                {
+                    action = PipelineAction::DetectMultipletsInView;
+                }
                 if ui.button("✕ Clear").clicked() {
                     action = PipelineAction::ClearMultiplets;
                 }
@@ -386,6 +1050,93 @@ pub fn show_pipeline_panel(
                     action = PipelineAction::ClearJCouplings;
                 }
             });
+            ui.label("Or click a detected multiplet to extract all its J values.");
+            let auto_label = if picking.auto_j_picking { "🎵 Auto J ●" } else { "🎵 Auto J from Multiplet" };
+            let auto_btn = egui::Button::new(
+                egui::RichText::new(auto_label)
+                    .color(if picking.auto_j_picking { egui::Color32::WHITE } else { ui.visuals().text_color() })
+            )
+            .fill(if picking.auto_j_picking { egui::Color32::from_rgb(0xCC, 0x66, 0x00) } else { ui.visuals().widgets.inactive.bg_fill });
+            if ui.add(auto_btn).clicked() {
+                action = PipelineAction::ToggleAutoJPicking;
+            }
+            ui.separator();
+            ui.label("↔ Linewidth (FWHM):");
+            ui.label("Click a peak to measure its width at half height.");
+            ui.horizontal(|ui| {
+                let lw_label = if picking.linewidth_picking { "↔ Measuring ●" } else { "↔ Measure Linewidth" };
+                let lw_btn = egui::Button::new(
+                    egui::RichText::new(lw_label)
+                        .color(if picking.linewidth_picking { egui::Color32::WHITE } else { ui.visuals().text_color() })
+                )
+                .fill(if picking.linewidth_picking { egui::Color32::from_rgb(0x00, 0x80, 0x80) } else { ui.visuals().widgets.inactive.bg_fill });
+                if ui.add(lw_btn).clicked() {
+                    action = PipelineAction::ToggleLinewidthPicking;
+                }
+                if ui.button("✕ Clear").clicked() {
+                    action = PipelineAction::ClearLinewidths;
+                }
+            });
+            ui.separator();
+            ui.label("〰 Peak fitting:");
+            ui.label("Click start and end of a region to fit its picked peaks.");
+            ui.horizontal(|ui| {
+                ui.label("Shape:");
+                egui::ComboBox::from_id_salt("fit_shape")
+                    .selected_text(state.fit_shape.label())
+                    .show_ui(ui, |ui| {
+                        for shape in LineShape::ALL {
+                            ui.selectable_value(&mut state.fit_shape, shape, shape.label());
+                        }
+                    });
+            });
+            ui.horizontal(|ui| {
+                let fit_label = if picking.fit_picking { "〰 Fitting ●" } else { "〰 Fit Peaks" };
+                let fit_btn = egui::Button::new(
+                    egui::RichText::new(fit_label)
+                        .color(if picking.fit_picking { egui::Color32::WHITE } else { ui.visuals().text_color() })
+                )
+                .fill(if picking.fit_picking { egui::Color32::from_rgb(0xD3, 0x54, 0x00) } else { ui.visuals().widgets.inactive.bg_fill });
+                if ui.add(fit_btn).clicked() {
+                    action = PipelineAction::TogglePeakFitPicking;
+                }
+                if ui.button("✕ Clear").clicked() {
+                    action = PipelineAction::ClearPeakFits;
+                }
+            });
+        });
+
+        ui.collapsing("🔬 Resolution Enhancement Preview", |ui| {
+            ui.label("Lorentz-to-Gauss over a picked region,");
+            ui.label("drawn dashed — the data is not changed.");
+            ui.add(
+                egui::Slider::new(&mut state.re_lb, 0.0..=10.0)
+                    .text("Remove LB (Hz)")
+                    .fixed_decimals(2),
+            );
+            ui.add(
+                egui::Slider::new(&mut state.re_gb, 0.1..=10.0)
+                    .text("Gauss (Hz)")
+                    .fixed_decimals(2),
+            );
+            ui.horizontal(|ui| {
+                let re_label = if picking.enhance_picking { "🎯 Picking ●" } else { "🎯 Pick Region" };
+                let re_btn = egui::Button::new(
+                    egui::RichText::new(re_label)
+                        .color(if picking.enhance_picking { egui::Color32::WHITE } else { ui.visuals().text_color() })
+                )
+                .fill(if picking.enhance_picking { egui::Color32::from_rgb(0xE0, 0x7B, 0x00) } else { ui.visuals().widgets.inactive.bg_fill });
+                if ui
+                    .add_enabled(has_imaginary, re_btn)
+                    .on_disabled_hover_text(no_imaginary.unwrap_or_default())
+                    .clicked()
+                {
+                    action = PipelineAction::ToggleEnhancePicking;
+                }
+                if ui.button("✕ Clear").clicked() {
+                    action = PipelineAction::ClearEnhancePreview;
+                }
+            });
         });
 
         ui.collapsing("∫ Integration", |ui| {
@@ -414,19 +1165,120 @@ pub fn show_pipeline_panel(
                     .suffix(" H")
                     .fixed_decimals(1),
             );
+            let mut two_point = *integral_baseline == IntegralBaseline::TwoPoint;
+            if ui
+                .checkbox(&mut two_point, "Subtract local baseline")
+                .on_hover_text("Subtract a straight line through each region's endpoints,\nso a baseline offset does not bias the integrals")
+                .changed()
+            {
+                *integral_baseline = if two_point { IntegralBaseline::TwoPoint } else { IntegralBaseline::None };
+            }
+        });
+
+        ui.collapsing("📚 Reference Library", |ui| {
+            ui.label(
+                egui::RichText::new("Save spectra as named references and\noverlay them to spot solvents and impurities.")
+                    .size(11.0)
+                    .color(egui::Color32::from_rgb(0x88, 0x8C, 0x94)),
+            );
+            if ui.button("📚 Open Library…").clicked() {
+                action = PipelineAction::ShowReferenceLibrary;
+            }
+        });
+
+        ui.collapsing("🧩 External Tools", |ui| {
+            if state.external_tools.is_empty() {
+                ui.label(
+                    egui::RichText::new("Add prediction scripts under\nSettings → Preferences → External tools.")
+                        .size(11.0)
+                        .color(egui::Color32::from_rgb(0x88, 0x8C, 0x94)),
+                );
+                return;
+            }
+            state.selected_tool = state.selected_tool.min(state.external_tools.len() - 1);
+            egui::ComboBox::from_id_salt("external_tool")
+                .selected_text(state.external_tools[state.selected_tool].as_str())
+                .show_ui(ui, |ui| {
+                    for (i, name) in state.external_tools.iter().enumerate() {
+                        ui.selectable_value(&mut state.selected_tool, i, name.as_str());
+                    }
+                });
+            ui.label(
+                egui::RichText::new("Sends the peaks and multiplets as JSON;\nshows predicted lines and assignments.")
+                    .size(11.0)
+                    .color(egui::Color32::from_rgb(0x88, 0x8C, 0x94)),
+            );
+            ui.horizontal(|ui| {
+                if ui.button("▶ Run Tool").clicked() {
+                    action = PipelineAction::RunExternalTool(state.selected_tool);
+                }
+                if ui.button("🗑 Clear Results").clicked() {
+                    action = PipelineAction::ClearToolResults;
+                }
+            });
         });
     }
 
+    ui.collapsing("📑 Templates", |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Name:");
+            ui.text_edit_singleline(&mut state.template_name);
+        });
+        let nothing_done = (operation_count == 0).then_some("No processing steps to save yet");
+        if gated_button(ui, "💾 Save Pipeline as Template…", nothing_done).clicked() {
+            action = PipelineAction::SaveTemplate;
+        }
+        if ui.button("▶ Apply Template…").clicked() {
+            action = PipelineAction::ApplyTemplate;
+        }
+        if ui.button("📁 Apply Template to Folder…").clicked() {
+            action = PipelineAction::ApplyTemplateToFolder;
+        }
+        ui.label(
+            egui::RichText::new("Saves the steps in the undo history with\ntheir parameters, to run them again on\nother data of the same kind.")
+                .size(11.0)
+                .color(egui::Color32::from_rgb(0x88, 0x8C, 0x94)),
+        );
+    });
+
     ui.separator();
 
     // Before/After toggle — only show when a snapshot exists
     if has_before_snapshot {
         ui.checkbox(&mut state.show_before_after, "👁 Show Before/After");
+        if state.show_before_after {
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut state.comparison_mode, ComparisonMode::Overlay, "Overlay");
+                ui.radio_value(&mut state.comparison_mode, ComparisonMode::SideBySide, "Side by side");
+            });
+            if state.comparison_mode == ComparisonMode::SideBySide {
+                ui.checkbox(&mut state.show_difference, "Difference trace (after − before)");
+            }
+        }
     }
 
     action
 }
 
+/// Button that is greyed out, with `blocked` as the reason on hover, when
+/// its operation does not apply to the loaded data
+fn gated_button(ui: &mut egui::Ui, text: &str, blocked: Option<&str>) -> egui::Response {
+    ui.add_enabled(blocked.is_none(), egui::Button::new(text))
+        .on_disabled_hover_text(blocked.unwrap_or_default())
+}
+
+/// Get the baseline model from the panel state
+pub fn get_baseline_method(state: &PipelinePanelState) -> BaselineMethod {
+    match state.baseline_model {
+        1 => BaselineMethod::Polynomial { order: state.bl_order },
+        2 => BaselineMethod::Als {
+            lambda: state.bl_lambda,
+            p: state.bl_p,
+        },
+        _ => BaselineMethod::Linear,
+    }
+}
+
 /// Get the window function from the panel state
 pub fn get_window_function(state: &PipelinePanelState) -> WindowFunction {
     match state.apod_type {
@@ -444,3 +1296,24 @@ pub fn get_window_function(state: &PipelinePanelState) -> WindowFunction {
         _ => WindowFunction::None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nucleus_defaults_and_presets() {
+        // A chosen LB survives reloads; the nucleus default only replaces an untouched value
+        let mut pipeline = PipelinePanelState::default();
+        pipeline.apply_nucleus_defaults(&Nucleus::F19);
+        assert_eq!(pipeline.em_lb, 1.0);
+        pipeline.em_lb = 4.0;
+        pipeline.apply_nucleus_defaults(&Nucleus::P31);
+        pipeline.apply_nucleus_defaults(&Nucleus::F19);
+        assert_eq!(pipeline.em_lb, 4.0);
+
+        let presets: Vec<&str> = Nucleus::F19.ppm_presets().map(|p| p.name).collect();
+        assert!(presets.len() > 1 && presets[0] == "Organofluorine");
+        assert_eq!(Nucleus::H2.typical_ppm_range(), Nucleus::H1.typical_ppm_range());
+    }
+}
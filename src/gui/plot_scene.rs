@@ -0,0 +1,2493 @@
+//! Shared plotting core for spectrum images
+//!
+//! Lays out a 1D spectrum figure (trace, fitted lines, peak markers,
+//! integrals, multiplets, axis and title) once, as a list of draw commands in export
+//! pixel coordinates.  The export tab preview paints the commands with egui,
+//! and the PNG/SVG exporters rasterize or serialize the very same list, so
+//! the exported file always matches the preview.
+//!
+//! Text is measured with the chosen export font (egui's Ubuntu Light by
+//! default, or a system/user TrueType font), which the PNG backend
+//! rasterizes and SVG/PDF name or embed; SVG text is stretched to the
+//! measured width.  The interactive
+//! spectrum view is an `egui_plot` and does not go through the scene; the
+//! figure carries over its annotations, vertical scale and intensity mode.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+
+use ab_glyph::{Font, FontArc, ScaleFont};
+use image::ImageEncoder;
+
+use crate::data::spectrum::{SpectrumData, META_PULSE_PROGRAM, META_SCANS};
+use crate::error::{NmrError, NmrResult};
+use crate::gui::export_dialog::{Corner, ExportFont, ExportSettings};
+use crate::gui::spectrum_view::{AxisDirection, IntegralLabels, OverlayTrace, SpectrumViewState};
+use crate::log::reproducibility::ReproLog;
+use crate::pipeline::processing::{self, IntensityMode};
+
+pub type Rgb = [u8; 3];
+
+const TRACE: Rgb = [0x1A, 0x3A, 0x6B];
+const BORDER: Rgb = [100, 100, 110];
+const GRID: Rgb = [230, 230, 235];
+const AXIS_TEXT: Rgb = [60, 60, 70];
+const TITLE: Rgb = [40, 40, 50];
+const RANGE_TEXT: Rgb = [120, 120, 130];
+const PEAK: Rgb = [0xD0, 0x30, 0x30];
+const LEADER: Rgb = [0xC8, 0x78, 0x78];
+const INTEGRAL: Rgb = [76, 175, 80];
+const MULTIPLET: Rgb = [0, 96, 170];
+/// Fitted lines and their sum, as in the view
+const FIT: Rgb = [0xD3, 0x54, 0x00];
+const PARAM_BG: Rgb = [255, 255, 255];
+/// Overlaid reference traces, cycled in order
+const OVERLAY_COLORS: [Rgb; 4] = [[0x2E, 0x9E, 0x6B], [0xB0, 0x4A, 0xC8], [0xD0, 0x8A, 0x20], [0x3A, 0x8E, 0xC8]];
+/// Other spectra of the session overlaid in the view, in turn
+const SESSION_COLORS: [Rgb; 6] = [
+    [0x1F, 0x77, 0xB4],
+    [0xD6, 0x27, 0x28],
+    [0x2C, 0xA0, 0x2C],
+    [0x94, 0x67, 0xBD],
+    [0xFF, 0x7F, 0x0E],
+    [0x17, 0xBE, 0xCF],
+];
+/// Functional-group region bands, alternating
+const REGION_COLORS: [Rgb; 2] = [[0xC8, 0xA0, 0x50], [0x50, 0x8C, 0xC8]];
+
+/// Opacity of [`DrawCmd::Area`] fills
+pub const AREA_OPACITY: f32 = 0.25;
+
+/// Height of a text line as a fraction of the font size
+pub const LINE_HEIGHT: f32 = 1.2;
+
+/// Color of the `i`-th overlaid reference, shared by the view and the figure
+pub fn overlay_color(i: usize) -> Rgb {
+    OVERLAY_COLORS[i % OVERLAY_COLORS.len()]
+}
+
+/// Default color of the `i`-th spectrum of the session
+pub fn session_color(i: usize) -> Rgb {
+    SESSION_COLORS[i % SESSION_COLORS.len()]
+}
+
+/// Color of the `i`-th shaded shift region, shared by the view and the figure
+pub fn region_color(i: usize) -> Rgb {
+    REGION_COLORS[i % REGION_COLORS.len()]
+}
+
+/// `color` washed out towards white, for opaque background bands
+fn tint(color: Rgb, strength: f32) -> Rgb {
+    color.map(|c| (255.0 - (255.0 - c as f32) * strength).round() as u8)
+}
+
+/// Horizontal alignment of a text command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextAnchor {
+    Left,
+    Center,
+    Right,
+}
+
+/// A single drawing primitive, in scene pixel coordinates (y down)
+#[derive(Debug, Clone)]
+pub enum DrawCmd {
+    /// Open polyline
+    Line { points: Vec<[f32; 2]>, color: Rgb, width: f32 },
+    /// Filled convex polygon
+    Polygon { points: Vec<[f32; 2]>, color: Rgb },
+    /// Translucent fill between a polyline running left to right (or right
+    /// to left) and the horizontal line `y = base`, at [`AREA_OPACITY`]
+    Area { points: Vec<[f32; 2]>, base: f32, color: Rgb },
+    /// Rectangle outline
+    Rect { min: [f32; 2], max: [f32; 2], color: Rgb, width: f32 },
+    /// Text; `pos` is the top of the line box at the anchor point
+    Text { pos: [f32; 2], text: String, size: f32, color: Rgb, anchor: TextAnchor },
+}
+
+/// A laid-out figure: canvas size plus draw commands in painting order
+#[derive(Debug, Clone)]
+pub struct PlotScene {
+    pub width: f32,
+    pub height: f32,
+    /// Pixels per inch, giving the figure's physical size in the exports
+    pub dpi: u32,
+    /// Font the text was laid out with
+    pub font: SceneFont,
+    pub commands: Vec<DrawCmd>,
+    /// One-line processing summary, embedded as PNG `tEXt` and SVG `<desc>`
+    /// so the figure documents itself (empty when nothing was processed)
+    pub summary: String,
+}
+
+impl PlotScene {
+    /// Physical size in millimetres at the scene's DPI
+    pub fn size_mm(&self) -> (f32, f32) {
+        let mm_per_px = 25.4 / self.dpi.max(1) as f32;
+        (self.width * mm_per_px, self.height * mm_per_px)
+    }
+}
+
+/// The font of a figure: metrics and outlines for layout and the PNG
+/// backend, and what the SVG/PDF backends name or embed
+#[derive(Clone)]
+pub struct SceneFont {
+    font: FontArc,
+    /// The font file, when the font came from one and can be embedded
+    data: Option<Arc<Vec<u8>>>,
+    /// `font-family` list for SVG
+    css_family: String,
+    css_weight: u16,
+    /// PDF base font when not embedded (standard-14 name where possible)
+    pdf_name: String,
+    /// PDF base font when embedded
+    embed_name: String,
+    /// Embed `data` in SVG/PDF
+    embed: bool,
+}
+
+impl std::fmt::Debug for SceneFont {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SceneFont")
+            .field("css_family", &self.css_family)
+            .field("pdf_name", &self.pdf_name)
+            .field("embed_name", &self.embed_name)
+            .field("embed", &self.embed)
+            .finish()
+    }
+}
+
+/// Where Helvetica-, Times-like system fonts usually live (Arial and
+/// Liberation are metric-compatible stand-ins)
+const HELVETICA_FILES: &[&str] = &[
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "/Library/Fonts/Arial.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+    "/usr/share/fonts/truetype/msttcorefonts/Arial.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+    "/usr/share/fonts/liberation-sans/LiberationSans-Regular.ttf",
+    "/usr/share/fonts/TTF/LiberationSans-Regular.ttf",
+];
+const TIMES_FILES: &[&str] = &[
+    "/System/Library/Fonts/Supplemental/Times New Roman.ttf",
+    "/Library/Fonts/Times New Roman.ttf",
+    "C:\\Windows\\Fonts\\times.ttf",
+    "/usr/share/fonts/truetype/msttcorefonts/Times_New_Roman.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationSerif-Regular.ttf",
+    "/usr/share/fonts/liberation-serif/LiberationSerif-Regular.ttf",
+    "/usr/share/fonts/TTF/LiberationSerif-Regular.ttf",
+];
+
+/// egui's default proportional font (Ubuntu Light) file
+fn builtin_font_data() -> &'static Arc<Vec<u8>> {
+    static DATA: OnceLock<Arc<Vec<u8>>> = OnceLock::new();
+    DATA.get_or_init(|| {
+        let defs = egui::FontDefinitions::default();
+        let name = &defs.families[&egui::FontFamily::Proportional][0];
+        Arc::new(defs.font_data[name].font.to_vec())
+    })
+}
+
+/// PDF name for a font file: its stem without spaces or delimiters
+fn pdf_font_name(path: &Path) -> String {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let name: String = stem.chars().filter(|c| c.is_ascii_alphanumeric() || *c == '-').collect();
+    if name.is_empty() { "EmbeddedFont".to_string() } else { name }
+}
+
+impl SceneFont {
+    /// Load the font chosen in the export settings.  Helvetica and Times
+    /// use a matching system font for metrics when one is installed, else
+    /// the built-in font's metrics under the standard PDF name.
+    pub fn load(choice: &ExportFont, embed: bool) -> NmrResult<Self> {
+        // Fonts are loaded once; the preview lays out a scene every frame
+        static CACHE: Mutex<Vec<(ExportFont, SceneFont)>> = Mutex::new(Vec::new());
+        let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, font)) = cache.iter().find(|(c, _)| c == choice) {
+            return Ok(SceneFont { embed, ..font.clone() });
+        }
+
+        let from_file = |path: &Path| -> NmrResult<(FontArc, Arc<Vec<u8>>)> {
+            let data = std::fs::read(path).map_err(|e| NmrError::reading(path, e))?;
+            let font = FontArc::try_from_vec(data.clone())
+                .map_err(|_| NmrError::InvalidInput(format!("{} is not a TrueType/OpenType font", path.display())))?;
+            Ok((font, Arc::new(data)))
+        };
+        let builtin = || {
+            let data = builtin_font_data().clone();
+            (FontArc::try_from_vec(data.to_vec()).expect("egui default font is valid"), data)
+        };
+        let system = |files: &[&str]| files.iter().map(Path::new).find(|p| p.exists()).and_then(|p| from_file(p).ok().map(|f| (f, pdf_font_name(p))));
+
+        let font = match choice {
+            ExportFont::Builtin => {
+                let (font, data) = builtin();
+                SceneFont {
+                    font,
+                    data: Some(data),
+                    css_family: "Ubuntu, sans-serif".to_string(),
+                    css_weight: 300,
+                    pdf_name: "Helvetica".to_string(),
+                    embed_name: "Ubuntu-Light".to_string(),
+                    embed,
+                }
+            }
+            ExportFont::Helvetica | ExportFont::Times => {
+                let (files, css_family, pdf_name) = match choice {
+                    ExportFont::Times => (TIMES_FILES, "'Times New Roman', Times, serif", "Times-Roman"),
+                    _ => (HELVETICA_FILES, "Helvetica, Arial, sans-serif", "Helvetica"),
+                };
+                let (font, data, embed_name) = match system(files) {
+                    Some(((font, data), name)) => (font, Some(data), name),
+                    None => (builtin().0, None, pdf_name.to_string()),
+                };
+                SceneFont {
+                    font,
+                    data,
+                    css_family: css_family.to_string(),
+                    css_weight: 400,
+                    pdf_name: pdf_name.to_string(),
+                    embed_name,
+                    embed,
+                }
+            }
+            ExportFont::File(path) => {
+                let (font, data) = from_file(path)?;
+                let name = pdf_font_name(path);
+                SceneFont {
+                    font,
+                    data: Some(data),
+                    css_family: format!("'{}', sans-serif", name),
+                    css_weight: 400,
+                    pdf_name: name.clone(),
+                    embed_name: name,
+                    embed,
+                }
+            }
+        };
+        cache.push((choice.clone(), font.clone()));
+        Ok(font)
+    }
+
+    /// Width of `text` at `size` pixels (advances + kerning).
+    fn text_width(&self, text: &str, size: f32) -> f32 {
+        let font = self.font.as_scaled(size);
+        let mut width = 0.0;
+        let mut prev = None;
+        for ch in text.chars() {
+            let id = font.glyph_id(ch);
+            if let Some(p) = prev {
+                width += font.kern(p, id);
+            }
+            width += font.h_advance(id);
+            prev = Some(id);
+        }
+        width
+    }
+
+    /// Distance from the top of the line box to the baseline at `size`
+    fn ascent(&self, size: f32) -> f32 {
+        self.font.as_scaled(size).ascent()
+    }
+
+    /// The font file to embed, if embedding is on and the file allows it
+    /// (single TrueType-outline fonts; not collections or CFF OpenType)
+    fn embedded(&self) -> Option<&[u8]> {
+        let data = self.data.as_deref().filter(|_| self.embed)?;
+        matches!(data.get(..4), Some([0, 1, 0, 0]) | Some(b"true")).then_some(data.as_slice())
+    }
+}
+
+/// Left edge of a text command of width `w`
+fn anchored_left(x: f32, w: f32, anchor: TextAnchor) -> f32 {
+    match anchor {
+        TextAnchor::Left => x,
+        TextAnchor::Center => x - w / 2.0,
+        TextAnchor::Right => x - w,
+    }
+}
+
+/// Choose a nice 1-2-5 tick spacing giving about ten ticks over `range`
+/// (0.1 ppm for a narrow proton region up to 20–50 ppm for 19F/31P sweeps).
+pub fn tick_step(range: f64) -> f64 {
+    let raw_step = (range / 10.0).max(1e-6);
+    let decade = 10f64.powf(raw_step.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * decade)
+        .find(|&step| step >= raw_step * (1.0 - 1e-9))
+        .unwrap_or(10.0 * decade)
+}
+
+/// Format a ppm axis value with as many decimals as the visible `span`
+/// needs (wide 19F/31P sweeps get none).  Never prints "-0".
+pub fn format_ppm(ppm: f64, span: f64) -> String {
+    let decimals = if span.abs() >= 50.0 {
+        0
+    } else if span.abs() >= 2.0 {
+        1
+    } else {
+        2
+    };
+    let rounded = (ppm * 10f64.powi(decimals)).round() / 10f64.powi(decimals);
+    format!("{:.*}", decimals as usize, rounded + 0.0)
+}
+
+/// Rows of the parameter box: acquisition values from the spectrum and its
+/// metadata, the window function from the last apodization in `log`.
+/// Unknown values are left out.
+pub fn parameter_lines(spectrum: &SpectrumData, log: &ReproLog) -> Vec<(String, String)> {
+    let mut rows = Vec::new();
+    if let Some(axis) = spectrum.axes.first() {
+        rows.push(("Nucleus".to_string(), axis.nucleus.to_string()));
+        if axis.observe_freq_mhz > 0.0 {
+            rows.push(("Frequency".to_string(), format!("{:.2} MHz", axis.observe_freq_mhz)));
+        }
+    }
+    if !spectrum.solvent.is_empty() {
+        rows.push(("Solvent".to_string(), spectrum.solvent.clone()));
+    }
+    for key in [META_SCANS, META_PULSE_PROGRAM] {
+        if let Some(value) = spectrum.metadata.get(key) {
+            rows.push((key.to_string(), value.clone()));
+        }
+    }
+    // "Apodization: EM (LB=0.3 Hz)" — show LB where the window has one
+    let window = log.entries.iter().rev().find_map(|e| e.operation.strip_prefix("Apodization: "));
+    if let Some(window) = window {
+        match window.split_once("LB=").and_then(|(_, rest)| rest.split_once(')')) {
+            Some((lb, _)) => rows.push(("LB".to_string(), lb.to_string())),
+            None => rows.push(("Window".to_string(), window.to_string())),
+        }
+    }
+    if spectrum.is_frequency_domain {
+        rows.push(("SI".to_string(), spectrum.real.len().to_string()));
+    } else if let Some(aq) = spectrum.axes.first().and_then(|a| a.acquisition_time()) {
+        rows.push(("AQ".to_string(), format!("{:.3} s", aq)));
+    }
+    rows
+}
+
+/// Lay out the figure for `spectrum` with the annotations in `view`;
+/// `log` supplies the processing parameters of the parameter box.
+pub fn build_scene(
+    spectrum: &SpectrumData,
+    view: &SpectrumViewState,
+    settings: &ExportSettings,
+    log: &ReproLog,
+) -> NmrResult<PlotScene> {
+    if spectrum.real.is_empty() {
+        return Err(NmrError::InvalidInput("Spectrum has no data".to_string()));
+    }
+    if settings.width == 0 || settings.height == 0 {
+        return Err(NmrError::InvalidInput("Invalid image size".to_string()));
+    }
+    let font = SceneFont::load(&settings.font, settings.embed_font)?;
+
+    let width = settings.width as f32;
+    let height = settings.height as f32;
+    let ml = (width * 0.04).max(80.0);
+    let mr = (width * 0.025).max(40.0);
+    let mt = (height * 0.08).max(50.0);
+    let plot_w = width - ml - mr;
+    let left = ml;
+
+    // Scale factors
+    let ms = settings.marker_scale;
+    let fs = settings.font_scale;
+    let font_sm = (10.0 * fs).round().max(6.0);
+    let font_md = (12.0 * fs).round().max(7.0);
+    let font_lg = (16.0 * fs).round().max(8.0);
+    let font_ax = (13.0 * fs).round().max(7.0);
+    let font_rng = (11.0 * fs).round().max(6.0);
+    let marker_h = 8.0 * ms;
+    let marker_w = 4.0 * ms;
+    let tick_len = (4.0 * ms).round().max(2.0);
+    let row_gap = (4.0 * fs).max(3.0);
+    let title_y = 14.0;
+
+    // Build ppm scale (seconds for an FID)
+    let is_freq = spectrum.is_frequency_domain;
+    let ppm_scale = spectrum.x_scale();
+
+    // Determine x range (ppm) — user-configurable
+    let (ppm_hi, ppm_lo) = if settings.use_custom_range && is_freq {
+        (
+            settings.ppm_start.max(settings.ppm_end),
+            settings.ppm_start.min(settings.ppm_end),
+        )
+    } else if let Some((lo, hi)) = spectrum.default_display_range() {
+        // Nucleus-aware default window (e.g. -50 … -250 ppm for 19F)
+        (hi, lo)
+    } else {
+        let ppm_min = ppm_scale.iter().cloned().fold(f64::INFINITY, f64::min);
+        let ppm_max = ppm_scale.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        (ppm_max, ppm_min)
+    };
+    let x_range = ppm_hi - ppm_lo;
+    if x_range <= 0.0 {
+        return Err(NmrError::InvalidInput("Invalid ppm range".to_string()));
+    }
+
+    // NMR convention (high ppm on the left) unless the view is reversed;
+    // an FID starts at t = 0 on the left
+    let ascending = view.axis_direction == AxisDirection::Ascending || !is_freq;
+    let ppm_to_x = |ppm: f64| {
+        let frac = if ascending { ppm - ppm_lo } else { ppm_hi - ppm } / x_range;
+        left + frac as f32 * plot_w
+    };
+
+    // Integral values, placed as in the view and stacked so that
+    // neighbours do not overlap; stacked rows below the axis widen the
+    // bottom margin
+    let line_h_sm = font_sm * LINE_HEIGHT;
+    let integral_marks = if settings.show_integrations {
+        integral_marks(spectrum, view, ppm_lo, ppm_hi)
+    } else {
+        Vec::new()
+    };
+    let integral_slot = |mark: &IntegralMark, top: f32| {
+        let (x, w) = (ppm_to_x((mark.lo + mark.hi) / 2.0), font.text_width(&mark.text, font_sm) + font_sm * 0.8);
+        LabelSlot { x: ((x - w / 2.0) as f64, (x + w / 2.0) as f64), y: (top as f64, (top + font_sm) as f64) }
+    };
+    let below_rows = if view.integral_labels == IntegralLabels::BelowAxis {
+        let slots: Vec<LabelSlot> = integral_marks.iter().map(|m| integral_slot(m, 0.0)).collect();
+        stack_labels(&slots, line_h_sm as f64)
+    } else {
+        Vec::new()
+    };
+    let extra_rows = below_rows.iter().copied().max().unwrap_or(0);
+
+    let mb = (height * 0.10).max(70.0) + extra_rows as f32 * line_h_sm;
+    let plot_h = height - mt - mb;
+    if plot_w < 10.0 || plot_h < 10.0 {
+        return Err(NmrError::InvalidInput("Image too small".to_string()));
+    }
+    let (top, right, bottom) = (mt, ml + plot_w, mt + plot_h);
+
+    // Filter data to ppm range
+    let clip_neg = settings.clip_negatives;
+    let clip = |y: f64| if clip_neg { y.max(0.0) } else { y };
+    let y_data: Vec<(f64, f64)> = ppm_scale
+        .iter()
+        .zip(spectrum.real.iter())
+        .filter(|(&ppm, _)| ppm >= ppm_lo && ppm <= ppm_hi)
+        .map(|(&ppm, &y)| (ppm, clip(y)))
+        .collect();
+    if y_data.is_empty() {
+        return Err(NmrError::InvalidInput("No data points in the selected PPM range".to_string()));
+    }
+
+    let y_min = if clip_neg {
+        0.0
+    } else {
+        y_data.iter().map(|d| d.1).fold(f64::INFINITY, f64::min)
+    };
+    let y_max = y_data.iter().map(|d| d.1).fold(f64::NEG_INFINITY, f64::max);
+    let y_range = (y_max - y_min).max(1e-12);
+    // Add 5% padding at top
+    let y_range_padded = (y_max + y_range * 0.05 - y_min).max(1e-12);
+
+    // The view's vertical zoom magnifies the trace; tall peaks clip at the top
+    let vscale = if view.vertical_scale > 0.0 { view.vertical_scale } else { 1.0 };
+    let val_to_y = |v: f64| {
+        let y_frac = 1.0 - (v * vscale - y_min) / y_range_padded;
+        top + (y_frac as f32 * plot_h).clamp(0.0, plot_h)
+    };
+
+    let mut cmds = Vec::new();
+    let tick = tick_step(x_range);
+    let ticks: Vec<f64> = {
+        let mut t = (ppm_lo / tick).ceil() * tick;
+        let mut v = Vec::new();
+        while t <= ppm_hi + 1e-9 {
+            v.push(t);
+            t += tick;
+        }
+        v
+    };
+
+    // Functional-group regions of the spectrum's nucleus, shaded behind the data
+    if settings.show_regions && spectrum.is_frequency_domain {
+        if let Some(nucleus) = spectrum.display_nucleus() {
+            let regions = view.shift_regions.iter().filter(|r| r.applies_to(&nucleus));
+            for (i, region) in regions.enumerate() {
+                let hi = region.range.0.max(region.range.1).min(ppm_hi);
+                let lo = region.range.0.min(region.range.1).max(ppm_lo);
+                if hi <= lo {
+                    continue;
+                }
+                let (x0, x1) = (ppm_to_x(hi), ppm_to_x(lo));
+                cmds.push(DrawCmd::Polygon {
+                    points: vec![[x0, top], [x1, top], [x1, bottom], [x0, bottom]],
+                    color: tint(region_color(i), 0.12),
+                });
+                if font.text_width(&region.name, font_sm) < (x1 - x0).abs() {
+                    cmds.push(DrawCmd::Text {
+                        pos: [(x0 + x1) / 2.0, top + 3.0],
+                        text: region.name.clone(),
+                        size: font_sm,
+                        color: region_color(i),
+                        anchor: TextAnchor::Center,
+                    });
+                }
+            }
+        }
+    }
+
+    // Grid lines
+    if settings.show_grid {
+        for &t in &ticks {
+            let x = ppm_to_x(t);
+            cmds.push(DrawCmd::Line { points: vec![[x, top], [x, bottom]], color: GRID, width: 0.5 });
+        }
+    }
+
+    // Fitted lines as translucent areas on the fit's offset, under the trace
+    let fits: &[processing::PeakFit] = if settings.show_peak_fits { &view.peak_fits } else { &[] };
+    let in_range = |points: &[[f64; 2]]| -> Vec<[f32; 2]> {
+        points.iter().filter(|p| p[0] >= ppm_lo && p[0] <= ppm_hi).map(|p| [ppm_to_x(p[0]), val_to_y(p[1])]).collect()
+    };
+    for fit in fits {
+        for component in &fit.components {
+            let points = in_range(component);
+            if points.len() > 1 {
+                cmds.push(DrawCmd::Area { points: decimate(points, plot_w), base: val_to_y(fit.offset), color: FIT });
+            }
+        }
+    }
+
+    // Spectrum trace
+    let trace: Vec<[f32; 2]> = y_data.iter().map(|&(ppm, y)| [ppm_to_x(ppm), val_to_y(y)]).collect();
+    cmds.push(DrawCmd::Line {
+        points: decimate(trace, plot_w),
+        color: TRACE,
+        width: settings.line_width,
+    });
+
+    // Overlaid references, scaled to the spectrum's tallest point as in the view
+    let overlay_height = spectrum.real.iter().fold(0.0f64, |m, v| m.max(v.abs()));
+    let overlays: Vec<(usize, &OverlayTrace)> =
+        view.reference_overlays.iter().enumerate().filter(|(_, t)| t.visible).collect();
+    for &(i, overlay) in &overlays {
+        let points: Vec<[f32; 2]> = overlay
+            .points
+            .iter()
+            .filter(|p| p[0] >= ppm_lo && p[0] <= ppm_hi)
+            .map(|p| [ppm_to_x(p[0]), val_to_y(overlay.intensity(p[1], overlay_height))])
+            .collect();
+        if points.len() > 1 {
+            cmds.push(DrawCmd::Line { points: decimate(points, plot_w), color: overlay_color(i), width: 1.0 });
+        }
+    }
+
+    // Sum of the fitted lines
+    for fit in fits {
+        let points = in_range(&fit.trace);
+        if points.len() > 1 {
+            cmds.push(DrawCmd::Line { points: decimate(points, plot_w), color: FIT, width: 1.0 });
+        }
+    }
+
+    cmds.push(DrawCmd::Rect { min: [left, top], max: [right, bottom], color: BORDER, width: 1.0 });
+
+    // Peak markers with collision-avoidant labels
+    if settings.show_peaks {
+        let line_h = font_sm * LINE_HEIGHT;
+        let label_pad = font_sm * 0.4;
+        let zero_y = val_to_y(0.0);
+        // Normalized intensities go on a second line, as in the spectrum view
+        let values = processing::normalized_intensities(
+            &view.peaks,
+            view.intensity_mode,
+            view.integrations.first().map(|r| r.2),
+            view.integration_reference_h,
+        );
+        let mut labels: Vec<PeakLabel> = view
+            .peaks
+            .iter()
+            .zip(values)
+            .filter(|(p, _)| p[0] >= ppm_lo && p[0] <= ppm_hi)
+            .map(|(p, value)| {
+                let x = ppm_to_x(p[0]);
+                let y = val_to_y(clip(p[1]));
+                // Labels of negative peaks sit above the baseline
+                let above = y.min(zero_y);
+                let mut lines = vec![format!("{:.2}", p[0])];
+                if view.intensity_mode != IntensityMode::Absolute {
+                    lines.push(format!("({:.1})", value));
+                }
+                if view.is_exchangeable(p[0]) {
+                    lines.push("OH/NH".to_string());
+                }
+                if let Some(carbon) = view.carbon_type_at(p[0]) {
+                    lines.push(carbon.to_string());
+                }
+                if let Some(name) = view.impurity_at(p[0]) {
+                    lines.push(name.to_string());
+                }
+                let w = lines.iter().map(|l| font.text_width(l, font_sm)).fold(0.0, f32::max);
+                let h = line_h * lines.len() as f32;
+                let natural_y = above - marker_h * 2.5 - h - label_pad;
+                PeakLabel { x, y, above, lines, left: x - w / 2.0, top: natural_y, w, h, natural_y }
+            })
+            .collect();
+        avoid_collisions(&mut labels, label_pad, title_y + font_lg * LINE_HEIGHT + 4.0);
+
+        for pl in &labels {
+            // ▼ above positive peaks, ▲ below negative ones
+            let dir = if pl.y > pl.above { -1.0 } else { 1.0 };
+            let tip = pl.y - dir * marker_h;
+            let base = pl.y - dir * marker_h * 2.5;
+            cmds.push(DrawCmd::Polygon {
+                points: vec![[pl.x, tip], [pl.x - marker_w, base], [pl.x + marker_w, base]],
+                color: PEAK,
+            });
+            // Leader line if the label was displaced
+            if pl.top < pl.natural_y - label_pad {
+                let anchor = pl.above - marker_h * 2.5;
+                cmds.push(DrawCmd::Line { points: vec![[pl.x, pl.top + pl.h], [pl.x, anchor]], color: LEADER, width: 0.5 });
+            }
+            for (i, line) in pl.lines.iter().enumerate() {
+                cmds.push(DrawCmd::Text {
+                    pos: [pl.x, pl.top + i as f32 * line_h],
+                    text: line.clone(),
+                    size: font_sm,
+                    color: PEAK,
+                    anchor: TextAnchor::Center,
+                });
+            }
+        }
+    }
+
+    // Parameter box in a corner of the plot area
+    let mut param_box_h = 0.0;
+    if let Some(corner) = settings.parameter_box {
+        let rows = parameter_lines(spectrum, log);
+        if !rows.is_empty() {
+            let line_h = font_rng * LINE_HEIGHT;
+            let pad = font_rng * 0.6;
+            let gap = font_rng;
+            let name_w = rows.iter().map(|(n, _)| font.text_width(n, font_rng)).fold(0.0, f32::max);
+            let value_w = rows.iter().map(|(_, v)| font.text_width(v, font_rng)).fold(0.0, f32::max);
+            let (w, h) = (name_w + gap + value_w + 2.0 * pad, rows.len() as f32 * line_h + 2.0 * pad);
+            let inset = pad;
+            let x0 = match corner {
+                Corner::TopLeft | Corner::BottomLeft => left + inset,
+                Corner::TopRight | Corner::BottomRight => right - inset - w,
+            };
+            let y0 = match corner {
+                Corner::TopLeft | Corner::TopRight => top + inset,
+                Corner::BottomLeft | Corner::BottomRight => bottom - inset - h,
+            };
+            let (x1, y1) = (x0 + w, y0 + h);
+            param_box_h = h + inset;
+            cmds.push(DrawCmd::Polygon { points: vec![[x0, y0], [x1, y0], [x1, y1], [x0, y1]], color: PARAM_BG });
+            cmds.push(DrawCmd::Rect { min: [x0, y0], max: [x1, y1], color: BORDER, width: 0.75 });
+            for (i, (name, value)) in rows.into_iter().enumerate() {
+                let y = y0 + pad + i as f32 * line_h;
+                cmds.push(DrawCmd::Text { pos: [x0 + pad, y], text: name, size: font_rng, color: RANGE_TEXT, anchor: TextAnchor::Left });
+                cmds.push(DrawCmd::Text {
+                    pos: [x0 + pad + name_w + gap, y],
+                    text: value,
+                    size: font_rng,
+                    color: AXIS_TEXT,
+                    anchor: TextAnchor::Left,
+                });
+            }
+        }
+    }
+
+    // Legend of the overlaid traces, stacked next to a parameter box in
+    // the same corner
+    if let (Some(corner), false) = (settings.legend, overlays.is_empty()) {
+        let sample = if spectrum.sample_name.is_empty() { "Spectrum" } else { spectrum.sample_name.as_str() };
+        let mut rows = vec![(TRACE, sample.to_string())];
+        rows.extend(overlays.iter().map(|&(i, t)| (overlay_color(i), t.legend_text())));
+        let line_h = font_rng * LINE_HEIGHT;
+        let pad = font_rng * 0.6;
+        let swatch = font_rng * 1.8;
+        let text_w = rows.iter().map(|(_, t)| font.text_width(t, font_rng)).fold(0.0, f32::max);
+        let (w, h) = (swatch + pad + text_w + 2.0 * pad, rows.len() as f32 * line_h + 2.0 * pad);
+        let inset = pad;
+        let shift = if settings.parameter_box == Some(corner) { param_box_h } else { 0.0 };
+        let x0 = match corner {
+            Corner::TopLeft | Corner::BottomLeft => left + inset,
+            Corner::TopRight | Corner::BottomRight => right - inset - w,
+        };
+        let y0 = match corner {
+            Corner::TopLeft | Corner::TopRight => top + inset + shift,
+            Corner::BottomLeft | Corner::BottomRight => bottom - inset - h - shift,
+        };
+        let (x1, y1) = (x0 + w, y0 + h);
+        cmds.push(DrawCmd::Polygon { points: vec![[x0, y0], [x1, y0], [x1, y1], [x0, y1]], color: PARAM_BG });
+        cmds.push(DrawCmd::Rect { min: [x0, y0], max: [x1, y1], color: BORDER, width: 0.75 });
+        for (i, (color, text)) in rows.into_iter().enumerate() {
+            let y = y0 + pad + i as f32 * line_h;
+            let mid = y + font_rng * 0.6;
+            cmds.push(DrawCmd::Line { points: vec![[x0 + pad, mid], [x0 + pad + swatch, mid]], color, width: 1.5 });
+            cmds.push(DrawCmd::Text {
+                pos: [x0 + 2.0 * pad + swatch, y],
+                text,
+                size: font_rng,
+                color: AXIS_TEXT,
+                anchor: TextAnchor::Left,
+            });
+        }
+    }
+
+    // ── Below-plot stacked labels ──
+    // Row 1: tick marks + axis labels
+    let tick_label_y = bottom + tick_len + row_gap;
+    for &t in &ticks {
+        let x = ppm_to_x(t);
+        cmds.push(DrawCmd::Line { points: vec![[x, bottom], [x, bottom + tick_len]], color: BORDER, width: 1.0 });
+        cmds.push(DrawCmd::Text {
+            pos: [x, tick_label_y],
+            text: format_ppm(t, x_range),
+            size: font_md,
+            color: AXIS_TEXT,
+            anchor: TextAnchor::Center,
+        });
+    }
+    let mut next_row_y = tick_label_y + font_md * LINE_HEIGHT + row_gap;
+
+    // Row 2: Integration regions and values
+    if !integral_marks.is_empty() {
+        let dash = (4.0 * ms).round().max(2.0);
+        let gap = (2.0 * ms).round().max(1.0);
+        for mark in &integral_marks {
+            for x in [ppm_to_x(mark.hi), ppm_to_x(mark.lo)] {
+                let mut y = top;
+                while y < bottom {
+                    cmds.push(DrawCmd::Line {
+                        points: vec![[x, y], [x, (y + dash).min(bottom)]],
+                        color: INTEGRAL,
+                        width: 1.0,
+                    });
+                    y += dash + gap;
+                }
+            }
+        }
+
+        // Running integrals, the largest scaled to a fixed share of the
+        // tallest point as in the view
+        let placement = view.integral_labels;
+        let traces: Vec<Vec<[f64; 2]>> = if view.integral_trace || placement == IntegralLabels::OnTrace {
+            integral_marks
+                .iter()
+                .map(|m| processing::integral_trace(spectrum, m.region.0, m.region.1, view.integral_baseline))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let tallest = spectrum.real.iter().fold(0.0f64, |m, v| m.max(v.abs()));
+        let trace_scale = integral_trace_scale(&traces, tallest);
+        for trace in &traces {
+            let points: Vec<[f32; 2]> = trace
+                .iter()
+                .filter(|p| p[0] >= ppm_lo && p[0] <= ppm_hi)
+                .map(|p| [ppm_to_x(p[0]), val_to_y(p[1] * trace_scale)])
+                .collect();
+            if points.len() > 1 {
+                cmds.push(DrawCmd::Line { points, color: INTEGRAL, width: 1.0 });
+            }
+        }
+
+        let label_gap = font_sm * 0.4;
+        let label_tops: Vec<f32> = match placement {
+            IntegralLabels::BelowAxis => below_rows.iter().map(|&k| next_row_y + k as f32 * line_h_sm).collect(),
+            IntegralLabels::AboveRegion | IntegralLabels::OnTrace => {
+                let slots: Vec<LabelSlot> = integral_marks
+                    .iter()
+                    .enumerate()
+                    .map(|(i, m)| {
+                        let height = match traces.get(i) {
+                            Some(trace) if placement == IntegralLabels::OnTrace => {
+                                trace.iter().fold(0.0f64, |h, p| h.max(p[1] * trace_scale))
+                            }
+                            _ => y_data
+                                .iter()
+                                .filter(|d| d.0 >= m.lo && d.0 <= m.hi)
+                                .fold(0.0f64, |h, d| h.max(d.1)),
+                        };
+                        integral_slot(m, val_to_y(height) - label_gap - font_sm)
+                    })
+                    .collect();
+                let rows = stack_labels(&slots, -line_h_sm as f64);
+                slots.iter().zip(rows).map(|(slot, k)| slot.y.0 as f32 - k as f32 * line_h_sm).collect()
+            }
+        };
+        for (mark, label_top) in integral_marks.iter().zip(label_tops) {
+            cmds.push(DrawCmd::Text {
+                pos: [ppm_to_x((mark.lo + mark.hi) / 2.0), label_top],
+                text: mark.text.clone(),
+                size: font_sm,
+                color: INTEGRAL,
+                anchor: TextAnchor::Center,
+            });
+        }
+        if placement == IntegralLabels::BelowAxis {
+            next_row_y += (extra_rows + 1) as f32 * line_h_sm + row_gap;
+        }
+    }
+
+    // Row 3: Multiplet labels
+    if settings.show_multiplets && !view.multiplets.is_empty() {
+        for mult in view.multiplets.iter().filter(|m| m.center_ppm >= ppm_lo && m.center_ppm <= ppm_hi) {
+            let text = match mult.j_text() {
+                Some(j) => format!("{} J={}", mult.label, j),
+                None => mult.label.clone(),
+            };
+            cmds.push(DrawCmd::Text {
+                pos: [ppm_to_x(mult.center_ppm), next_row_y],
+                text,
+                size: font_sm,
+                color: MULTIPLET,
+                anchor: TextAnchor::Center,
+            });
+        }
+        next_row_y += font_sm * LINE_HEIGHT + row_gap;
+    }
+
+    // X-axis title
+    cmds.push(DrawCmd::Text {
+        pos: [left + plot_w / 2.0, next_row_y + row_gap],
+        text: spectrum.x_axis_label().to_string(),
+        size: font_ax,
+        color: AXIS_TEXT,
+        anchor: TextAnchor::Center,
+    });
+
+    // Title and ppm range
+    let title = if settings.use_custom_title && !settings.custom_title.is_empty() {
+        settings.custom_title.clone()
+    } else {
+        format!("{} — {} — {} pts", spectrum.sample_name, spectrum.experiment_type, spectrum.real.len())
+    };
+    cmds.push(DrawCmd::Text { pos: [left, title_y], text: title, size: font_lg, color: TITLE, anchor: TextAnchor::Left });
+    let (range_left, range_right) = if ascending { (ppm_lo, ppm_hi) } else { (ppm_hi, ppm_lo) };
+    let unit = if is_freq { "ppm" } else if spectrum.has_time_axis() { "s" } else { "pts" };
+    let mut range_text =
+        format!("{} – {} {}", format_ppm(range_left, x_range), format_ppm(range_right, x_range), unit);
+    if vscale != 1.0 {
+        range_text.push_str(&format!("  ·  vertical ×{}", vscale));
+    }
+    cmds.push(DrawCmd::Text {
+        pos: [right, title_y + (font_lg - font_rng)],
+        text: range_text,
+        size: font_rng,
+        color: RANGE_TEXT,
+        anchor: TextAnchor::Right,
+    });
+
+    Ok(PlotScene {
+        width,
+        height,
+        dpi: settings.dpi,
+        font,
+        commands: cmds,
+        summary: log.processing_summary_text(),
+    })
+}
+
+/// Chemical-shift perturbation plot of a titration series: Δδ of each
+/// tracked peak against the titration coordinate `x`, one line with
+/// markers per peak (gaps where it was not found) and a legend naming the
+/// peaks by the shift they start at.  Size, DPI and font come from the
+/// export `settings`.
+pub fn csp_scene(
+    x: &[f64],
+    x_label: &str,
+    tracks: &[processing::PeakTrack],
+    title: &str,
+    settings: &ExportSettings,
+) -> NmrResult<PlotScene> {
+    let series: Vec<Vec<Option<f64>>> = tracks.iter().map(|t| t.perturbations()).collect();
+    let values: Vec<f64> = series.iter().flatten().flatten().copied().collect();
+    if x.is_empty() || values.is_empty() {
+        return Err(NmrError::InvalidInput("No tracked peaks".to_string()));
+    }
+    if settings.width == 0 || settings.height == 0 {
+        return Err(NmrError::InvalidInput("Invalid image size".to_string()));
+    }
+    let font = SceneFont::load(&settings.font, settings.embed_font)?;
+
+    let width = settings.width as f32;
+    let height = settings.height as f32;
+    let fs = settings.font_scale;
+    let font_sm = (10.0 * fs).round().max(6.0);
+    let font_ax = (13.0 * fs).round().max(7.0);
+    let font_lg = (16.0 * fs).round().max(8.0);
+    let legend_w = tracks
+        .iter()
+        .filter_map(|t| t.start_ppm())
+        .map(|ppm| font.text_width(&format!("δ {:.3}", ppm), font_sm))
+        .fold(0.0, f32::max)
+        + 30.0;
+    let left = (width * 0.04).max(80.0);
+    let top = (height * 0.08).max(50.0) + font_ax * LINE_HEIGHT;
+    let right = width - (width * 0.025).max(20.0) - legend_w;
+    let bottom = height - (height * 0.10).max(70.0);
+    if right - left < 10.0 || bottom - top < 10.0 {
+        return Err(NmrError::InvalidInput("Image too small".to_string()));
+    }
+
+    // Padded ranges; Δδ always includes 0, the unperturbed start
+    let (x_lo, x_hi) = x.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+    let x_pad = if x_hi > x_lo { (x_hi - x_lo) * 0.05 } else { 0.5 };
+    let (x_lo, x_hi) = (x_lo - x_pad, x_hi + x_pad);
+    let (y_lo, y_hi) = values.iter().fold((0.0f64, 0.0f64), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+    let y_pad = if y_hi > y_lo { (y_hi - y_lo) * 0.08 } else { 0.01 };
+    let (y_lo, y_hi) = (y_lo - y_pad, y_hi + y_pad);
+    let to_x = |v: f64| left + ((v - x_lo) / (x_hi - x_lo)) as f32 * (right - left);
+    let to_y = |v: f64| bottom - ((v - y_lo) / (y_hi - y_lo)) as f32 * (bottom - top);
+    let ticks = |lo: f64, hi: f64| {
+        let step = tick_step(hi - lo);
+        let mut t = (lo / step).ceil() * step;
+        let mut v = Vec::new();
+        while t <= hi + 1e-9 * step {
+            v.push(t);
+            t += step;
+        }
+        (v, step)
+    };
+
+    let mut cmds = Vec::new();
+    let (x_ticks, x_step) = ticks(x_lo, x_hi);
+    let (y_ticks, y_step) = ticks(y_lo, y_hi);
+    let decimals = |step: f64| (-step.log10().floor()).max(0.0) as usize;
+    for &t in &x_ticks {
+        let px = to_x(t);
+        cmds.push(DrawCmd::Line { points: vec![[px, top], [px, bottom]], color: GRID, width: 0.5 });
+        cmds.push(DrawCmd::Line { points: vec![[px, bottom], [px, bottom + 4.0]], color: BORDER, width: 1.0 });
+        cmds.push(DrawCmd::Text {
+            pos: [px, bottom + 6.0],
+            text: format!("{:.*}", decimals(x_step), t + 0.0),
+            size: font_sm,
+            color: AXIS_TEXT,
+            anchor: TextAnchor::Center,
+        });
+    }
+    for &t in &y_ticks {
+        let py = to_y(t);
+        let color = if t.abs() < y_step * 1e-6 { BORDER } else { GRID };
+        cmds.push(DrawCmd::Line { points: vec![[left, py], [right, py]], color, width: 0.5 });
+        cmds.push(DrawCmd::Text {
+            pos: [left - 6.0, py - font_sm * LINE_HEIGHT / 2.0],
+            text: format!("{:.*}", decimals(y_step), t + 0.0),
+            size: font_sm,
+            color: AXIS_TEXT,
+            anchor: TextAnchor::Right,
+        });
+    }
+
+    // One polyline per unbroken run of a track, markers at every point
+    let marker = (3.0 * settings.marker_scale).max(1.5);
+    for (i, deltas) in series.iter().enumerate() {
+        let color = session_color(i);
+        let mut run: Vec<[f32; 2]> = Vec::new();
+        for (k, delta) in deltas.iter().enumerate() {
+            let Some((&xv, &d)) = x.get(k).zip(delta.as_ref()) else {
+                let points = std::mem::take(&mut run);
+                if points.len() > 1 {
+                    cmds.push(DrawCmd::Line { points, color, width: settings.line_width });
+                }
+                continue;
+            };
+            let p = [to_x(xv), to_y(d)];
+            let (x0, y0, x1, y1) = (p[0] - marker, p[1] - marker, p[0] + marker, p[1] + marker);
+            cmds.push(DrawCmd::Polygon { points: vec![[x0, y0], [x1, y0], [x1, y1], [x0, y1]], color });
+            run.push(p);
+        }
+        if run.len() > 1 {
+            cmds.push(DrawCmd::Line { points: run, color, width: settings.line_width });
+        }
+    }
+    cmds.push(DrawCmd::Rect { min: [left, top], max: [right, bottom], color: BORDER, width: 1.0 });
+
+    // Legend right of the plot
+    let line_h = font_sm * LINE_HEIGHT * 1.2;
+    for (i, track) in tracks.iter().enumerate() {
+        let Some(start) = track.start_ppm() else {
+            continue;
+        };
+        let y = top + i as f32 * line_h;
+        if y + line_h > bottom {
+            break;
+        }
+        let mid = y + font_sm * LINE_HEIGHT / 2.0;
+        cmds.push(DrawCmd::Line {
+            points: vec![[right + 10.0, mid], [right + 26.0, mid]],
+            color: session_color(i),
+            width: settings.line_width.max(1.5),
+        });
+        cmds.push(DrawCmd::Text {
+            pos: [right + 30.0, y],
+            text: format!("δ {:.3}", start),
+            size: font_sm,
+            color: AXIS_TEXT,
+            anchor: TextAnchor::Left,
+        });
+    }
+
+    cmds.push(DrawCmd::Text {
+        pos: [(left + right) / 2.0, bottom + 10.0 + font_sm * LINE_HEIGHT],
+        text: x_label.to_string(),
+        size: font_ax,
+        color: AXIS_TEXT,
+        anchor: TextAnchor::Center,
+    });
+    cmds.push(DrawCmd::Text {
+        pos: [left, top - font_ax * LINE_HEIGHT - 4.0],
+        text: "Δδ (ppm)".to_string(),
+        size: font_ax,
+        color: AXIS_TEXT,
+        anchor: TextAnchor::Left,
+    });
+    cmds.push(DrawCmd::Text { pos: [left, 14.0], text: title.to_string(), size: font_lg, color: TITLE, anchor: TextAnchor::Left });
+
+    Ok(PlotScene {
+        width,
+        height,
+        dpi: settings.dpi,
+        font,
+        commands: cmds,
+        summary: format!("Chemical-shift perturbation of {} peaks over {} spectra", tracks.len(), x.len()),
+    })
+}
+
+/// An integral region in the figure's ppm range, with its value as written
+struct IntegralMark {
+    /// Whole region, which the running integral covers
+    region: (f64, f64),
+    /// Part of the region in the ppm range
+    lo: f64,
+    hi: f64,
+    text: String,
+}
+
+/// Integral regions of `view` that reach into `ppm_lo`…`ppm_hi`
+fn integral_marks(spectrum: &SpectrumData, view: &SpectrumViewState, ppm_lo: f64, ppm_hi: f64) -> Vec<IntegralMark> {
+    if view.integrations.is_empty() {
+        return Vec::new();
+    }
+    let first_raw = view.integrations.first().map(|r| r.2).unwrap_or(1.0).abs().max(1e-12);
+    let errors = processing::integral_uncertainties(
+        spectrum,
+        &view.integrations,
+        view.integral_baseline,
+        view.integration_reference_h,
+    );
+    view.integrations
+        .iter()
+        .zip(&errors)
+        .filter_map(|(&(start_ppm, end_ppm, raw_val), error)| {
+            let region = (start_ppm.min(end_ppm), start_ppm.max(end_ppm));
+            let (lo, hi) = (region.0.max(ppm_lo), region.1.min(ppm_hi));
+            let h_val = raw_val / first_raw * view.integration_reference_h;
+            (lo < hi).then(|| IntegralMark { region, lo, hi, text: processing::format_h_count(h_val, error.h_sigma) })
+        })
+        .collect()
+}
+
+struct PeakLabel {
+    x: f32,
+    y: f32,
+    /// Top of the peak or the baseline, whichever is higher
+    above: f32,
+    lines: Vec<String>,
+    left: f32,
+    top: f32,
+    w: f32,
+    h: f32,
+    natural_y: f32,
+}
+
+/// Shift overlapping peak labels upwards (multi-pass, all pairs), never
+/// above `min_y`.
+fn avoid_collisions(labels: &mut [PeakLabel], pad: f32, min_y: f32) {
+    labels.sort_by(|a, b| a.left.partial_cmp(&b.left).unwrap_or(std::cmp::Ordering::Equal));
+    for _pass in 0..5 {
+        let mut any_moved = false;
+        for i in 0..labels.len() {
+            for _iter in 0..20 {
+                let mut shift_to: Option<f32> = None;
+                for j in 0..labels.len() {
+                    if j == i {
+                        continue;
+                    }
+                    let (a, b) = (&labels[i], &labels[j]);
+                    if a.left < b.left + b.w + pad
+                        && b.left < a.left + a.w + pad
+                        && a.top < b.top + b.h + pad
+                        && b.top < a.top + a.h + pad
+                    {
+                        let target = b.top - a.h - pad;
+                        shift_to = Some(shift_to.map_or(target, |s: f32| s.min(target)));
+                    }
+                }
+                match shift_to {
+                    Some(target) if labels[i].top > min_y => {
+                        labels[i].top = target.max(min_y);
+                        any_moved = true;
+                    }
+                    _ => break,
+                }
+            }
+        }
+        if !any_moved {
+            break;
+        }
+    }
+}
+
+/// Height of the tallest integral trace, as a fraction of the tallest
+/// point of the spectrum
+pub const INTEGRAL_TRACE_HEIGHT: f64 = 0.3;
+
+/// Factor taking running integrals to intensities: the largest value of
+/// `traces` is drawn at [`INTEGRAL_TRACE_HEIGHT`] of `tallest`
+pub fn integral_trace_scale(traces: &[Vec<[f64; 2]>], tallest: f64) -> f64 {
+    let largest = traces.iter().flatten().fold(0.0f64, |m, p| m.max(p[1].abs()));
+    if largest > 0.0 {
+        INTEGRAL_TRACE_HEIGHT * tallest / largest
+    } else {
+        0.0
+    }
+}
+
+/// A label for [`stack_labels`]: the horizontal and vertical extent it
+/// takes where it would naturally sit, in the units of the plot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LabelSlot {
+    pub x: (f64, f64),
+    pub y: (f64, f64),
+}
+
+/// How many `step`s each label moves away from its place so that no two
+/// overlap.  Labels are taken left to right, each at the first step where
+/// it clears those already placed.  The view and the exported figure both
+/// stack integral values this way, so they end up in the same order.
+pub fn stack_labels(slots: &[LabelSlot], step: f64) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..slots.len()).collect();
+    order.sort_by(|&a, &b| slots[a].x.0.total_cmp(&slots[b].x.0));
+    let mut steps = vec![0; slots.len()];
+    let mut placed: Vec<LabelSlot> = Vec::with_capacity(slots.len());
+    for i in order {
+        let slot = slots[i];
+        let moved = |k: usize| {
+            let dy = k as f64 * step;
+            LabelSlot { x: slot.x, y: (slot.y.0 + dy, slot.y.1 + dy) }
+        };
+        let clear = |s: &LabelSlot| {
+            !placed.iter().any(|p| s.x.0 < p.x.1 && p.x.0 < s.x.1 && s.y.0 < p.y.1 && p.y.0 < s.y.1)
+        };
+        // Each placed label blocks a few steps at most, so this ends
+        let k = (0..).find(|&k| clear(&moved(k))).unwrap_or(0);
+        steps[i] = k;
+        placed.push(moved(k));
+    }
+    steps
+}
+
+/// Reduce a dense trace to the min/max envelope per pixel column, which
+/// draws identically at the target resolution.
+fn decimate(points: Vec<[f32; 2]>, plot_w: f32) -> Vec<[f32; 2]> {
+    if points.len() <= (plot_w * 4.0) as usize {
+        return points;
+    }
+    let mut out = Vec::with_capacity(plot_w as usize * 4);
+    let mut i = 0;
+    while i < points.len() {
+        let column = points[i][0].floor();
+        let start = i;
+        let (mut lo, mut hi) = (i, i);
+        while i < points.len() && points[i][0].floor() == column {
+            if points[i][1] < points[lo][1] {
+                lo = i;
+            }
+            if points[i][1] > points[hi][1] {
+                hi = i;
+            }
+            i += 1;
+        }
+        let mut keep = [start, lo.min(hi), lo.max(hi), i - 1];
+        keep.sort_unstable();
+        let mut last = usize::MAX;
+        for k in keep {
+            if k != last {
+                out.push(points[k]);
+                last = k;
+            }
+        }
+    }
+    out
+}
+
+// ── egui backend ──────────────────────────────────────────────────
+
+fn color32(c: Rgb) -> egui::Color32 {
+    egui::Color32::from_rgb(c[0], c[1], c[2])
+}
+
+/// Paint the scene into `rect`, scaled uniformly from scene pixels.
+pub fn paint(scene: &PlotScene, painter: &egui::Painter, rect: egui::Rect) {
+    let scale = rect.width() / scene.width;
+    let pos = |p: [f32; 2]| rect.min + egui::vec2(p[0], p[1]) * scale;
+    painter.rect_filled(rect, 0.0, egui::Color32::WHITE);
+    for cmd in &scene.commands {
+        match cmd {
+            DrawCmd::Line { points, color, width } => {
+                painter.add(egui::Shape::line(
+                    points.iter().map(|&p| pos(p)).collect(),
+                    egui::Stroke::new(width * scale, color32(*color)),
+                ));
+            }
+            DrawCmd::Polygon { points, color } => {
+                painter.add(egui::Shape::convex_polygon(
+                    points.iter().map(|&p| pos(p)).collect(),
+                    color32(*color),
+                    egui::Stroke::NONE,
+                ));
+            }
+            DrawCmd::Area { points, base, color } => {
+                // A strip of quads down to the base; a mesh has no feathered
+                // edges, so neighbouring quads do not leave seams
+                let fill = egui::Color32::from_rgba_unmultiplied(color[0], color[1], color[2], (AREA_OPACITY * 255.0) as u8);
+                let mut mesh = egui::Mesh::default();
+                for (i, &p) in points.iter().enumerate() {
+                    mesh.colored_vertex(pos(p), fill);
+                    mesh.colored_vertex(pos([p[0], *base]), fill);
+                    if i > 0 {
+                        let k = 2 * i as u32;
+                        mesh.add_triangle(k - 2, k - 1, k);
+                        mesh.add_triangle(k - 1, k + 1, k);
+                    }
+                }
+                painter.add(egui::Shape::mesh(mesh));
+            }
+            DrawCmd::Rect { min, max, color, width } => {
+                painter.rect_stroke(
+                    egui::Rect::from_min_max(pos(*min), pos(*max)),
+                    0.0,
+                    egui::Stroke::new(width * scale, color32(*color)),
+                    egui::epaint::StrokeKind::Middle,
+                );
+            }
+            DrawCmd::Text { pos: p, text, size, color, anchor } => {
+                let align = match anchor {
+                    TextAnchor::Left => egui::Align2::LEFT_TOP,
+                    TextAnchor::Center => egui::Align2::CENTER_TOP,
+                    TextAnchor::Right => egui::Align2::RIGHT_TOP,
+                };
+                painter.text(pos(*p), align, text, egui::FontId::proportional(size * scale), color32(*color));
+            }
+        }
+    }
+}
+
+// ── SVG backend ───────────────────────────────────────────────────
+
+fn hex(c: Rgb) -> String {
+    format!("#{:02X}{:02X}{:02X}", c[0], c[1], c[2])
+}
+
+/// Standard base64 with padding, for fonts embedded in SVG
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Serialize the scene as an SVG document.  The document size is given in
+/// millimetres and the drawing in pixels through the view box, so the
+/// figure opens at its physical size.
+pub fn to_svg(scene: &PlotScene) -> String {
+    let (w_mm, h_mm) = scene.size_mm();
+    let mut svg = format!(
+        "<svg xmlns='http://www.w3.org/2000/svg' width='{:.2}mm' height='{:.2}mm' viewBox='0 0 {} {}'>\n",
+        w_mm, h_mm, scene.width, scene.height
+    );
+    if !scene.summary.is_empty() {
+        svg.push_str(&format!("<desc>{}</desc>\n", escape_xml(&scene.summary)));
+    }
+    let family = match scene.font.embedded() {
+        Some(data) => {
+            svg.push_str(&format!(
+                "<defs><style>@font-face {{ font-family: 'NMRFigureFont'; src: url(data:font/ttf;base64,{}); }}</style></defs>\n",
+                base64(data)
+            ));
+            format!("'NMRFigureFont', {}", scene.font.css_family)
+        }
+        None => scene.font.css_family.clone(),
+    };
+    svg.push_str("<rect width='100%' height='100%' fill='white'/>\n");
+    for cmd in &scene.commands {
+        match cmd {
+            DrawCmd::Line { points, color, width } => {
+                let pts: Vec<String> = points.iter().map(|p| format!("{:.1},{:.1}", p[0], p[1])).collect();
+                svg.push_str(&format!(
+                    "<polyline fill='none' stroke='{}' stroke-width='{:.1}' points='{}'/>\n",
+                    hex(*color),
+                    width,
+                    pts.join(" ")
+                ));
+            }
+            DrawCmd::Polygon { points, color } => {
+                let pts: Vec<String> = points.iter().map(|p| format!("{:.1},{:.1}", p[0], p[1])).collect();
+                svg.push_str(&format!("<polygon points='{}' fill='{}'/>\n", pts.join(" "), hex(*color)));
+            }
+            DrawCmd::Area { points, base, color } => {
+                let mut pts: Vec<String> = points.iter().map(|p| format!("{:.1},{:.1}", p[0], p[1])).collect();
+                if let (Some(first), Some(last)) = (points.first(), points.last()) {
+                    pts.push(format!("{:.1},{:.1} {:.1},{:.1}", last[0], base, first[0], base));
+                }
+                svg.push_str(&format!(
+                    "<polygon points='{}' fill='{}' fill-opacity='{}'/>\n",
+                    pts.join(" "),
+                    hex(*color),
+                    AREA_OPACITY
+                ));
+            }
+            DrawCmd::Rect { min, max, color, width } => {
+                svg.push_str(&format!(
+                    "<rect x='{:.1}' y='{:.1}' width='{:.1}' height='{:.1}' fill='none' stroke='{}' stroke-width='{:.1}'/>\n",
+                    min[0],
+                    min[1],
+                    max[0] - min[0],
+                    max[1] - min[1],
+                    hex(*color),
+                    width
+                ));
+            }
+            DrawCmd::Text { pos, text, size, color, anchor } => {
+                // SVG positions text by its baseline; the viewer's font may
+                // differ, so pin the advance to the measured layout width
+                let w = scene.font.text_width(text, *size);
+                svg.push_str(&format!(
+                    "<text x='{:.1}' y='{:.1}' font-family=\"{}\" font-weight='{}' font-size='{:.0}' fill='{}' textLength='{:.1}' lengthAdjust='spacingAndGlyphs'>{}</text>\n",
+                    anchored_left(pos[0], w, *anchor),
+                    pos[1] + scene.font.ascent(*size),
+                    family,
+                    scene.font.css_weight,
+                    size,
+                    hex(*color),
+                    w,
+                    escape_xml(text)
+                ));
+            }
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// ── PDF backend ───────────────────────────────────────────────────
+
+/// Text as a PDF string literal in WinAnsi (Latin-1) encoding; characters
+/// outside it become '?'
+fn pdf_string(text: &str) -> Vec<u8> {
+    let mut out = vec![b'('];
+    for ch in text.chars() {
+        match ch {
+            '(' | ')' | '\\' => out.extend([b'\\', ch as u8]),
+            c if (c as u32) < 0x20 => {}
+            c if (c as u32) < 0x80 || (0xA0..=0xFF).contains(&(c as u32)) => out.push(c as u32 as u8),
+            '–' => out.push(0x96),
+            '—' => out.push(0x97),
+            _ => out.push(b'?'),
+        }
+    }
+    out.push(b')');
+    out
+}
+
+fn pdf_rgb(c: Rgb) -> String {
+    format!("{:.3} {:.3} {:.3}", c[0] as f32 / 255.0, c[1] as f32 / 255.0, c[2] as f32 / 255.0)
+}
+
+/// Objects 5–7 of a PDF with an embedded TrueType font: the font
+/// dictionary (WinAnsi widths from the font), its descriptor and the file.
+/// The whole file is embedded; there is no subsetting.
+fn pdf_truetype_font(font: &SceneFont, data: &[u8]) -> Vec<Vec<u8>> {
+    let f = &font.font;
+    let em = f.units_per_em().unwrap_or(1000.0);
+    let to_pdf_units = |v: f32| (v / em * 1000.0).round() as i32;
+    let widths: Vec<String> = (32u8..=255)
+        .map(|code| {
+            let ch = match code {
+                0x96 => '–',
+                0x97 => '—',
+                _ => code as char,
+            };
+            to_pdf_units(f.h_advance_unscaled(f.glyph_id(ch))).to_string()
+        })
+        .collect();
+    let (ascent, descent) = (to_pdf_units(f.ascent_unscaled()), to_pdf_units(f.descent_unscaled()));
+    vec![
+        format!(
+            "<< /Type /Font /Subtype /TrueType /BaseFont /{} /FirstChar 32 /LastChar 255 /Widths [{}] /Encoding /WinAnsiEncoding /FontDescriptor 6 0 R >>",
+            font.embed_name,
+            widths.join(" ")
+        )
+        .into_bytes(),
+        format!(
+            "<< /Type /FontDescriptor /FontName /{} /Flags 32 /FontBBox [-500 {} 1500 {}] /ItalicAngle 0 /Ascent {} /Descent {} /CapHeight {} /StemV 80 /FontFile2 7 0 R >>",
+            font.embed_name, descent, ascent, ascent, descent, ascent
+        )
+        .into_bytes(),
+        [format!("<< /Length {} /Length1 {} >>\nstream\n", data.len(), data.len()).as_bytes(), data, b"\nendstream"].concat(),
+    ]
+}
+
+/// Serialize the scene as a single-page vector PDF at the scene's DPI.
+/// Text uses the scene font, embedded or as a standard PDF font, placed at
+/// the laid-out positions.
+pub fn to_pdf(scene: &PlotScene) -> Vec<u8> {
+    let h = scene.height;
+    let mut content: Vec<u8> = Vec::new();
+    // Scene coordinates have y pointing down
+    let path = |points: &[[f32; 2]]| {
+        points
+            .iter()
+            .enumerate()
+            .map(|(i, p)| format!("{:.2} {:.2} {}", p[0], h - p[1], if i == 0 { "m" } else { "l" }))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    // Scene pixels to points at the scene's DPI
+    let scale = 72.0 / scene.dpi.max(1) as f32;
+    content.extend(format!("{:.4} 0 0 {:.4} 0 0 cm\n", scale, scale).bytes());
+    content.extend(b"1 1 1 rg 0 0 ");
+    content.extend(format!("{:.2} {:.2} re f\n1 J 1 j\n", scene.width, h).bytes());
+    for cmd in &scene.commands {
+        match cmd {
+            DrawCmd::Line { points, color, width } if points.len() >= 2 => {
+                content.extend(format!("{} RG {:.2} w\n{}\nS\n", pdf_rgb(*color), width, path(points)).bytes());
+            }
+            DrawCmd::Line { .. } => {}
+            DrawCmd::Polygon { points, color } => {
+                content.extend(format!("{} rg\n{}\nh f\n", pdf_rgb(*color), path(points)).bytes());
+            }
+            DrawCmd::Area { points, base, color } if points.len() >= 2 => {
+                let (first, last) = (points[0], points[points.len() - 1]);
+                let outline = [points.as_slice(), &[[last[0], *base], [first[0], *base]]].concat();
+                content.extend(format!("q /GA gs {} rg\n{}\nh f Q\n", pdf_rgb(*color), path(&outline)).bytes());
+            }
+            DrawCmd::Area { .. } => {}
+            DrawCmd::Rect { min, max, color, width } => {
+                content.extend(
+                    format!(
+                        "{} RG {:.2} w {:.2} {:.2} {:.2} {:.2} re S\n",
+                        pdf_rgb(*color),
+                        width,
+                        min[0],
+                        h - max[1],
+                        max[0] - min[0],
+                        max[1] - min[1]
+                    )
+                    .bytes(),
+                );
+            }
+            DrawCmd::Text { pos, text, size, color, anchor } => {
+                let x = anchored_left(pos[0], scene.font.text_width(text, *size), *anchor);
+                let y = h - (pos[1] + scene.font.ascent(*size));
+                content.extend(format!("BT {} rg /F1 {:.1} Tf {:.2} {:.2} Td ", pdf_rgb(*color), size, x, y).bytes());
+                content.extend(pdf_string(text));
+                content.extend(b" Tj ET\n");
+            }
+        }
+    }
+
+    let mut objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] /Contents 4 0 R /Resources << /Font << /F1 5 0 R >> /ExtGState << /GA << /ca {} >> >> >> >>",
+            scene.width * scale,
+            h * scale,
+            AREA_OPACITY
+        )
+        .into_bytes(),
+        [format!("<< /Length {} >>\nstream\n", content.len()).as_bytes(), &content, b"endstream"].concat(),
+    ];
+    match scene.font.embedded() {
+        Some(data) => objects.extend(pdf_truetype_font(&scene.font, data)),
+        None => objects.push(
+            format!("<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>", scene.font.pdf_name)
+                .into_bytes(),
+        ),
+    }
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, body) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n", i + 1).bytes());
+        pdf.extend(body);
+        pdf.extend(b"\nendobj\n");
+    }
+    let xref = pdf.len();
+    pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).bytes());
+    for offset in offsets {
+        pdf.extend(format!("{:010} 00000 n \n", offset).bytes());
+    }
+    pdf.extend(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).bytes());
+    pdf
+}
+
+// ── PNG backend ───────────────────────────────────────────────────
+
+/// Progress of a PNG render, shared with the thread showing it, and the
+/// flag that stops it
+#[derive(Debug, Default)]
+pub struct RenderProgress {
+    /// Draw commands finished, summed over the bands
+    done: AtomicUsize,
+    total: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl RenderProgress {
+    /// Fraction done, 0–1
+    pub fn fraction(&self) -> f32 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0.0;
+        }
+        (self.done.load(Ordering::Relaxed) as f64 / total as f64).min(1.0) as f32
+    }
+
+    /// Stop the render at the next draw command
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Threads used to rasterize a figure: `requested`, or every core for 0
+pub fn render_threads(requested: usize) -> usize {
+    if requested > 0 {
+        return requested;
+    }
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Horizontal strip of the raster, drawn by one thread.  Coordinates are
+/// those of the whole image; pixels outside the strip are dropped, so every
+/// strip is drawn exactly as it would be in one piece.
+struct Band<'a> {
+    pixels: &'a mut [u8],
+    width: i32,
+    top: i32,
+    rows: i32,
+}
+
+impl Band<'_> {
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        let row = y - self.top;
+        (x >= 0 && x < self.width && row >= 0 && row < self.rows)
+            .then(|| (row as usize * self.width as usize + x as usize) * 3)
+    }
+
+    fn put(&mut self, x: i32, y: i32, color: Rgb) {
+        if let Some(i) = self.index(x, y) {
+            self.pixels[i..i + 3].copy_from_slice(&color);
+        }
+    }
+
+    /// Mix `color` over the pixel with opacity `a`
+    fn blend(&mut self, x: i32, y: i32, color: Rgb, a: f32) {
+        if let Some(i) = self.index(x, y) {
+            for (dst, &src) in self.pixels[i..i + 3].iter_mut().zip(&color) {
+                *dst = (*dst as f32 * (1.0 - a) + src as f32 * a).round() as u8;
+            }
+        }
+    }
+
+    /// Whether anything between rows `y0` and `y1`, widened by `margin`,
+    /// can land in the strip
+    fn overlaps(&self, y0: f32, y1: f32, margin: f32) -> bool {
+        y0.min(y1) - margin <= (self.top + self.rows) as f32 && y0.max(y1) + margin >= self.top as f32
+    }
+}
+
+/// Draw the scene into one band
+fn render_band(scene: &PlotScene, band: &mut Band, progress: &RenderProgress) {
+    for cmd in &scene.commands {
+        if progress.is_cancelled() {
+            return;
+        }
+        match cmd {
+            DrawCmd::Line { points, color, width } => {
+                for seg in points.windows(2) {
+                    if band.overlaps(seg[0][1], seg[1][1], *width + 1.0) {
+                        draw_line(band, seg[0], seg[1], *color, *width);
+                    }
+                }
+            }
+            DrawCmd::Polygon { points, color } => fill_polygon(band, points, *color),
+            DrawCmd::Area { points, base, color } => fill_area(band, points, *base, *color),
+            DrawCmd::Rect { min, max, color, width } => {
+                let corners = [[min[0], min[1]], [max[0], min[1]], [max[0], max[1]], [min[0], max[1]], [min[0], min[1]]];
+                for seg in corners.windows(2) {
+                    draw_line(band, seg[0], seg[1], *color, *width);
+                }
+            }
+            DrawCmd::Text { pos, text, size, color, anchor } => {
+                if band.overlaps(pos[1], pos[1] + size * LINE_HEIGHT, *size) {
+                    let x = anchored_left(pos[0], scene.font.text_width(text, *size), *anchor);
+                    draw_text(band, &scene.font.font, text, [x, pos[1] + scene.font.ascent(*size)], *size, *color);
+                }
+            }
+        }
+        progress.done.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Rasterize the scene.  Text is drawn with the same font used for layout.
+pub fn to_png(scene: &PlotScene) -> image::RgbImage {
+    to_png_with(scene, render_threads(0), &RenderProgress::default()).expect("render without cancellation")
+}
+
+/// Rasterize the scene in `threads` horizontal bands drawn in parallel,
+/// reporting to `progress`; `None` when cancelled through it
+pub fn to_png_with(scene: &PlotScene, threads: usize, progress: &RenderProgress) -> Option<image::RgbImage> {
+    let (width, height) = (scene.width as u32, scene.height as u32);
+    let mut img = image::RgbImage::from_pixel(width, height, image::Rgb([255, 255, 255]));
+    let rows_per_band = height.div_ceil(threads.max(1) as u32).max(1);
+    let bands = height.div_ceil(rows_per_band) as usize;
+    progress.done.store(0, Ordering::Relaxed);
+    progress.total.store(scene.commands.len() * bands, Ordering::Relaxed);
+
+    let row_bytes = width as usize * 3;
+    std::thread::scope(|s| {
+        for (i, pixels) in img.chunks_mut((rows_per_band as usize * row_bytes).max(1)).enumerate() {
+            let mut band = Band {
+                rows: (pixels.len() / row_bytes.max(1)) as i32,
+                pixels,
+                width: width as i32,
+                top: (i as u32 * rows_per_band) as i32,
+            };
+            s.spawn(move || render_band(scene, &mut band, progress));
+        }
+    });
+    (!progress.is_cancelled()).then_some(img)
+}
+
+/// Rasterize the scene and encode it as PNG with a `pHYs` chunk, so the
+/// image opens at its physical size, and the processing summary as a
+/// `Description` text chunk
+pub fn to_png_bytes(scene: &PlotScene) -> NmrResult<Vec<u8>> {
+    encode_png(scene, &to_png(scene))
+}
+
+/// Encode a rasterized scene as [`to_png_bytes`] does
+fn encode_png(scene: &PlotScene, img: &image::RgbImage) -> NmrResult<Vec<u8>> {
+    let mut png = Vec::new();
+    image::codecs::png::PngEncoder::new(&mut png)
+        .write_image(img.as_raw(), img.width(), img.height(), image::ExtendedColorType::Rgb8)
+        .map_err(std::io::Error::other)?;
+    // Signature (8 bytes) + IHDR (4 length + 4 type + 13 data + 4 CRC)
+    const AFTER_IHDR: usize = 33;
+    let pixels_per_metre = (scene.dpi as f64 / 0.0254).round() as u32;
+    let mut phys = pixels_per_metre.to_be_bytes().to_vec();
+    phys.extend(pixels_per_metre.to_be_bytes());
+    phys.push(1); // unit: metre
+    let mut chunks = png_chunk(b"pHYs", &phys);
+    if !scene.summary.is_empty() {
+        // tEXt is Latin-1: keyword, NUL separator, text
+        let mut text = b"Description\0".to_vec();
+        text.extend(scene.summary.chars().map(|c| u8::try_from(c).unwrap_or(b'?')));
+        chunks.extend(png_chunk(b"tEXt", &text));
+    }
+    png.splice(AFTER_IHDR..AFTER_IHDR, chunks);
+    Ok(png)
+}
+
+/// Length, type, data and CRC of one PNG chunk
+fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+    chunk.extend(kind);
+    chunk.extend(data);
+    chunk.extend(crc32(&chunk[4..]).to_be_bytes());
+    chunk
+}
+
+/// CRC-32 (ISO 3309) as used for PNG chunks
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xEDB8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Draw a line between two points using Bresenham's algorithm, stamping a
+/// square brush for widths above one pixel.
+fn draw_line(band: &mut Band, from: [f32; 2], to: [f32; 2], color: Rgb, width: f32) {
+    let brush = width.round().max(1.0) as i32;
+    let (x0, y0) = (from[0].round() as i32, from[1].round() as i32);
+    let (x1, y1) = (to[0].round() as i32, to[1].round() as i32);
+    let dx = (x1 - x0).abs();
+    let dy = -(y1 - y0).abs();
+    let sx: i32 = if x0 < x1 { 1 } else { -1 };
+    let sy: i32 = if y0 < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    let (mut cx, mut cy) = (x0, y0);
+    loop {
+        for bx in 0..brush {
+            for by in 0..brush {
+                band.put(cx + bx - brush / 2, cy + by - brush / 2, color);
+            }
+        }
+        if cx == x1 && cy == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            cx += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            cy += sy;
+        }
+    }
+}
+
+/// Fill a convex polygon by testing pixel centers in its bounding box.
+fn fill_polygon(band: &mut Band, points: &[[f32; 2]], color: Rgb) {
+    if points.len() < 3 {
+        return;
+    }
+    let bottom = (band.top + band.rows - 1) as f32;
+    let min_x = points.iter().map(|p| p[0]).fold(f32::INFINITY, f32::min).floor().max(0.0) as i32;
+    let max_x = points.iter().map(|p| p[0]).fold(f32::NEG_INFINITY, f32::max).ceil().min(band.width as f32 - 1.0);
+    let min_y = points.iter().map(|p| p[1]).fold(f32::INFINITY, f32::min).floor().max(band.top as f32) as i32;
+    let max_y = points.iter().map(|p| p[1]).fold(f32::NEG_INFINITY, f32::max).ceil().min(bottom);
+    if max_x < 0.0 || max_y < band.top as f32 {
+        return;
+    }
+    for y in min_y..=max_y as i32 {
+        for x in min_x..=max_x as i32 {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let mut sign = 0.0f32;
+            let inside = (0..points.len()).all(|i| {
+                let a = points[i];
+                let b = points[(i + 1) % points.len()];
+                let cross = (b[0] - a[0]) * (py - a[1]) - (b[1] - a[1]) * (px - a[0]);
+                if cross == 0.0 {
+                    return true;
+                }
+                if sign == 0.0 {
+                    sign = cross.signum();
+                }
+                cross.signum() == sign
+            });
+            if inside {
+                band.put(x, y, color);
+            }
+        }
+    }
+}
+
+/// Blend the area between a polyline and the row `base` over the pixels,
+/// one column of pixel centers at a time so that no pixel is blended twice
+fn fill_area(band: &mut Band, points: &[[f32; 2]], base: f32, color: Rgb) {
+    for seg in points.windows(2) {
+        let (a, b) = if seg[0][0] <= seg[1][0] { (seg[0], seg[1]) } else { (seg[1], seg[0]) };
+        if b[0] <= a[0] || !band.overlaps(a[1].min(b[1]).min(base), a[1].max(b[1]).max(base), 1.0) {
+            continue;
+        }
+        let x0 = ((a[0] - 0.5).ceil() as i32).max(0);
+        let x1 = ((b[0] - 0.5).ceil() as i32).min(band.width);
+        for x in x0..x1 {
+            let t = (x as f32 + 0.5 - a[0]) / (b[0] - a[0]);
+            let y = a[1] + t * (b[1] - a[1]);
+            let y0 = ((y.min(base) - 0.5).ceil() as i32).max(band.top);
+            let y1 = ((y.max(base) - 0.5).ceil() as i32).min(band.top + band.rows);
+            for row in y0..y1 {
+                band.blend(x, row, color, AREA_OPACITY);
+            }
+        }
+    }
+}
+
+/// Draw `text` with its baseline starting at `origin`, blending glyph
+/// coverage over the existing pixels.
+fn draw_text(band: &mut Band, font: &FontArc, text: &str, origin: [f32; 2], size: f32, color: Rgb) {
+    let outlines = font;
+    let font = font.as_scaled(size);
+    let mut caret = origin[0];
+    let mut prev = None;
+    for ch in text.chars() {
+        let id = font.glyph_id(ch);
+        if let Some(p) = prev {
+            caret += font.kern(p, id);
+        }
+        let glyph = id.with_scale_and_position(size, ab_glyph::point(caret, origin[1]));
+        caret += font.h_advance(id);
+        prev = Some(id);
+        let Some(outlined) = outlines.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            if coverage > 0.0 {
+                band.blend(bounds.min.x as i32 + gx as i32, bounds.min.y as i32 + gy as i32, color, coverage.min(1.0));
+            }
+        });
+    }
+}
+
+// ── Writing figures ───────────────────────────────────────────────
+
+/// Write the figure to `path` as SVG or PDF by its extension, otherwise as
+/// PNG rendered with `threads` threads and reporting to `progress`
+pub fn write_figure(scene: &PlotScene, path: &Path, threads: usize, progress: &RenderProgress) -> NmrResult<()> {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let bytes = match ext.as_str() {
+        "svg" => to_svg(scene).into_bytes(),
+        "pdf" => to_pdf(scene),
+        _ => {
+            let img = to_png_with(scene, threads, progress).ok_or(NmrError::Cancelled)?;
+            encode_png(scene, &img)?
+        }
+    };
+    std::fs::write(path, bytes).map_err(|e| NmrError::writing(path, e))
+}
+
+/// Figure export running on a worker thread, so large PNGs do not block
+/// the GUI
+pub struct PendingExport {
+    pub path: PathBuf,
+    pub started: Instant,
+    pub progress: Arc<RenderProgress>,
+    result: Arc<Mutex<Option<NmrResult<()>>>>,
+}
+
+impl PendingExport {
+    /// The outcome of [`write_figure`] once the worker is done
+    pub fn take_result(&self) -> Option<NmrResult<()>> {
+        self.result.lock().unwrap().take()
+    }
+}
+
+/// Run [`write_figure`] on a worker thread; nothing is written when it is
+/// cancelled before the PNG is rendered
+pub fn start_export(scene: PlotScene, path: &Path, threads: usize, ctx: egui::Context) -> PendingExport {
+    let progress = Arc::new(RenderProgress::default());
+    let result = Arc::new(Mutex::new(None));
+    let (shared_progress, shared) = (Arc::clone(&progress), Arc::clone(&result));
+    let target = path.to_path_buf();
+    std::thread::spawn(move || {
+        let outcome = write_figure(&scene, &target, threads, &shared_progress);
+        *shared.lock().unwrap() = Some(outcome);
+        ctx.request_repaint();
+    });
+    PendingExport { path: path.to_path_buf(), started: Instant::now(), progress, result }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::spectrum::{AxisParams, Nucleus, ShiftRegion};
+
+    #[test]
+    fn test_backends_render_the_same_scene() {
+        let n = 20_000;
+        let spectrum = SpectrumData {
+            real: (0..n).map(|i| if i == 5_000 { 100.0 } else { (i % 7) as f64 * 0.01 }).collect(),
+            is_frequency_domain: true,
+            axes: vec![AxisParams {
+                num_points: n,
+                spectral_width_hz: 4000.0,
+                observe_freq_mhz: 400.0,
+                reference_ppm: 10.0,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let peak_ppm = spectrum.axes[0].index_to_ppm(5_000);
+        let view = SpectrumViewState {
+            peaks: vec![[peak_ppm, 100.0]],
+            ..Default::default()
+        };
+        let settings = ExportSettings { width: 800, height: 600, ..Default::default() };
+        let scene = build_scene(&spectrum, &view, &settings, &ReproLog::new()).unwrap();
+
+        // Dense traces are reduced to the per-pixel envelope, keeping the peak
+        let trace = scene
+            .commands
+            .iter()
+            .find_map(|c| match c {
+                DrawCmd::Line { points, color, .. } if *color == TRACE => Some(points),
+                _ => None,
+            })
+            .unwrap();
+        assert!(trace.len() < n / 2);
+        let top = trace.iter().map(|p| p[1]).fold(f32::INFINITY, f32::min);
+        assert!((top - 48.0).abs() < 30.0, "peak should reach the top of the plot, got y={}", top);
+
+        let label = format!("{:.2}", peak_ppm);
+        let svg = to_svg(&scene);
+        assert!(svg.contains(&format!(">{}</text>", label)));
+        // SVG text is pinned to the width used for layout
+        assert!(svg.contains(&format!("textLength='{:.1}'", scene.font.text_width(&label, 10.0))));
+        let pdf = to_pdf(&scene);
+        let pdf_text = String::from_utf8_lossy(&pdf);
+        assert!(pdf_text.starts_with("%PDF-1.4") && pdf_text.ends_with("%%EOF\n"));
+        // 800 × 600 px at the default 300 DPI
+        assert!(pdf_text.contains("/MediaBox [0 0 192.00 144.00]"));
+        assert!(pdf_text.contains(&format!("({}) Tj", label)));
+        // The xref table points at each object
+        let obj3 = pdf_text.find("3 0 obj").unwrap();
+        assert!(pdf_text.contains(&format!("{:010} 00000 n", obj3)));
+        assert!(svg.contains("width='67.73mm' height='50.80mm' viewBox='0 0 800 600'"));
+        // PNG carries 300 DPI (11811 px/m) in a valid pHYs chunk
+        let bytes = to_png_bytes(&scene).unwrap();
+        let phys = bytes.windows(4).position(|w| w == b"pHYs").unwrap();
+        assert_eq!(bytes[phys + 4..phys + 8], 11811u32.to_be_bytes());
+        assert_eq!(bytes[phys + 12], 1);
+        assert_eq!(image::load_from_memory(&bytes).unwrap().width(), 800);
+        let png = to_png(&scene);
+        assert_eq!((png.width(), png.height()), (800, 600));
+        assert!(png.pixels().any(|p| p.0 == PEAK));
+        // Bands drawn on several threads give the very same pixels
+        let progress = RenderProgress::default();
+        for threads in [1, 3, 7] {
+            assert!(to_png_with(&scene, threads, &progress).unwrap() == png, "{} threads", threads);
+            assert_eq!(progress.fraction(), 1.0);
+        }
+        progress.cancel();
+        assert!(to_png_with(&scene, 4, &progress).is_none());
+
+        // Rasterized label ink stays inside its measured box
+        let (x, y) = scene
+            .commands
+            .iter()
+            .find_map(|c| match c {
+                DrawCmd::Text { pos, text, .. } if *text == label => Some((pos[0], pos[1])),
+                _ => None,
+            })
+            .unwrap();
+        let w = scene.font.text_width(&label, 10.0);
+        assert!(w > 10.0 && w < 40.0, "width {}", w);
+        let ink: Vec<u32> = (0..800)
+            .filter(|&px| {
+                (y as u32..(y + 12.0) as u32).any(|py| {
+                    let [r, g, _] = png.get_pixel(px, py).0;
+                    r as i32 - g as i32 > 40
+                })
+            })
+            .filter(|&px| (px as f32 - x).abs() < 60.0)
+            .collect();
+        let (lo, hi) = (*ink.first().unwrap() as f32, *ink.last().unwrap() as f32);
+        assert!(lo >= x - w / 2.0 - 1.0 && hi <= x + w / 2.0 + 1.0, "ink {}..{} vs box {}", lo, hi, w);
+
+        // Vertical zoom and normalized intensities carry over from the view
+        let zoomed = SpectrumViewState {
+            peaks: vec![[peak_ppm, 100.0]],
+            vertical_scale: 4.0,
+            intensity_mode: IntensityMode::RelativeToTallest,
+            ..Default::default()
+        };
+        let scene = build_scene(&spectrum, &zoomed, &settings, &ReproLog::new()).unwrap();
+        let texts: Vec<&str> = scene
+            .commands
+            .iter()
+            .filter_map(|c| match c {
+                DrawCmd::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert!(texts.contains(&"(100.0)"), "{:?}", texts);
+        assert!(texts.iter().any(|t| t.ends_with("vertical ×4")), "{:?}", texts);
+    }
+
+    #[test]
+    fn test_wide_sweep_fluorine_axis() {
+        use crate::data::spectrum::Nucleus;
+
+        assert_eq!(tick_step(15.0), 2.0);
+        assert_eq!(tick_step(200.0), 20.0);
+        assert_eq!(tick_step(0.5), 0.05);
+        assert_eq!(format_ppm(-0.01, 10.0), "0.0");
+        assert_eq!(format_ppm(-123.4, 200.0), "-123");
+
+        // 19F acquired from +20 to -280 ppm: default window is -250 … -50
+        let n = 1000;
+        let fluorine = SpectrumData {
+            real: vec![0.0; n],
+            is_frequency_domain: true,
+            axes: vec![AxisParams {
+                nucleus: Nucleus::F19,
+                num_points: n,
+                spectral_width_hz: 300.0 * 376.0,
+                observe_freq_mhz: 376.0,
+                reference_ppm: 20.0,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert_eq!(fluorine.default_display_range(), Some((-250.0, -50.0)));
+
+        let settings = ExportSettings { width: 1200, height: 800, ..Default::default() };
+        let scene = build_scene(&fluorine, &SpectrumViewState::default(), &settings, &ReproLog::new()).unwrap();
+        let labels: Vec<&str> = scene
+            .commands
+            .iter()
+            .filter_map(|c| match c {
+                DrawCmd::Text { text, color, .. } if *color == AXIS_TEXT => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert!(labels.contains(&"-100") && labels.contains(&"-240"), "{:?}", labels);
+    }
+
+    #[test]
+    fn test_parameter_box_from_metadata_and_log() {
+        use crate::data::spectrum::Nucleus;
+        use crate::pipeline::processing::WindowFunction;
+
+        let n = 4096;
+        let mut spectrum = SpectrumData {
+            real: vec![1.0; n],
+            imag: vec![0.0; n],
+            solvent: "CDCl3".to_string(),
+            axes: vec![AxisParams {
+                nucleus: Nucleus::H1,
+                num_points: n,
+                spectral_width_hz: 8000.0,
+                observe_freq_mhz: 400.13,
+                reference_ppm: 12.0,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        spectrum.metadata.insert(META_PULSE_PROGRAM.to_string(), "zg30".to_string());
+        spectrum.metadata.insert(META_SCANS.to_string(), "16".to_string());
+        let mut log = ReproLog::new();
+        processing::apply_apodization(&mut spectrum, &WindowFunction::Exponential { lb_hz: 0.3 }, &mut log);
+        processing::zero_fill(&mut spectrum, 2 * n, &mut log);
+        processing::fourier_transform(&mut spectrum, true, &mut log);
+
+        let rows = parameter_lines(&spectrum, &log);
+        let value = |name: &str| rows.iter().find(|r| r.0 == name).map(|r| r.1.as_str());
+        assert_eq!(value("Nucleus"), Some("1H"));
+        assert_eq!(value("Frequency"), Some("400.13 MHz"));
+        assert_eq!(value("Solvent"), Some("CDCl3"));
+        assert_eq!(value("Scans"), Some("16"));
+        assert_eq!(value("Pulse program"), Some("zg30"));
+        assert_eq!(value("LB"), Some("0.3 Hz"));
+        assert_eq!(value("SI"), Some(spectrum.real.len().to_string().as_str()));
+
+        // Off by default; when on, the box sits inside the chosen corner
+        let view = SpectrumViewState::default();
+        let mut settings = ExportSettings { width: 1200, height: 800, ..Default::default() };
+        let has_box = |scene: &PlotScene| {
+            scene.commands.iter().any(|c| matches!(c, DrawCmd::Text { text, .. } if text == "zg30"))
+        };
+        assert!(!has_box(&build_scene(&spectrum, &view, &settings, &log).unwrap()));
+        settings.parameter_box = Some(Corner::BottomRight);
+        let scene = build_scene(&spectrum, &view, &settings, &log).unwrap();
+        assert!(has_box(&scene));
+        let bg = scene
+            .commands
+            .iter()
+            .find_map(|c| match c {
+                DrawCmd::Polygon { points, color } if *color == PARAM_BG => Some(points.clone()),
+                _ => None,
+            })
+            .unwrap();
+        assert!(bg[0][0] > scene.width / 2.0 && bg[0][1] > scene.height / 2.0);
+
+        // The processing summary travels with exported files
+        processing::phase_correct(&mut spectrum, 10.0, 2.0, &mut log);
+        processing::phase_correct(&mut spectrum, -4.0, 1.0, &mut log);
+        let scene = build_scene(&spectrum, &view, &settings, &log).unwrap();
+        assert_eq!(scene.summary, "Window: EM (LB=0.3 Hz); Zero fill: 8192 points; Phase: PH0 6.0°, PH1 3.0°");
+        assert!(to_svg(&scene).contains(&format!("<desc>{}</desc>", scene.summary)));
+        let bytes = to_png_bytes(&scene).unwrap();
+        let text = bytes.windows(4).position(|w| w == b"tEXt").unwrap();
+        assert!(bytes[text + 4..].starts_with(b"Description\0Window: EM (LB=0.3 Hz)"));
+        assert!(image::load_from_memory(&bytes).is_ok());
+    }
+
+    #[test]
+    fn test_font_family_and_embedding() {
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"M"), "TQ==");
+
+        let n = 2000;
+        let spectrum = SpectrumData {
+            real: (0..n).map(|i| if i == 1000 { 1.0 } else { 0.0 }).collect(),
+            is_frequency_domain: true,
+            axes: vec![AxisParams { num_points: n, spectral_width_hz: 4000.0, observe_freq_mhz: 400.0, reference_ppm: 10.0, ..Default::default() }],
+            ..Default::default()
+        };
+        let view = SpectrumViewState::default();
+        let log = ReproLog::new();
+        let mut settings = ExportSettings { width: 800, height: 600, ..Default::default() };
+
+        // Standard font by name, not embedded
+        settings.font = ExportFont::Times;
+        let scene = build_scene(&spectrum, &view, &settings, &log).unwrap();
+        let pdf = String::from_utf8_lossy(&to_pdf(&scene)).to_string();
+        assert!(pdf.contains("/BaseFont /Times-Roman") && !pdf.contains("/FontFile2"));
+        assert!(to_svg(&scene).contains("font-family=\"'Times New Roman', Times, serif\""));
+
+        // The built-in font embedded as TrueType with its widths
+        settings.font = ExportFont::Builtin;
+        settings.embed_font = true;
+        let scene = build_scene(&spectrum, &view, &settings, &log).unwrap();
+        let bytes = to_pdf(&scene);
+        let pdf = String::from_utf8_lossy(&bytes).to_string();
+        assert!(pdf.contains("/Subtype /TrueType /BaseFont /Ubuntu-Light"));
+        assert!(pdf.contains("/FontFile2 7 0 R"));
+        let obj7 = bytes.windows(7).position(|w| w == b"7 0 obj").unwrap();
+        assert!(pdf.contains(&format!("{:010} 00000 n", obj7)));
+        let svg = to_svg(&scene);
+        assert!(svg.contains("@font-face") && svg.contains("font-family=\"'NMRFigureFont', Ubuntu, sans-serif\""));
+
+        settings.font = ExportFont::File("/nonexistent/font.ttf".into());
+        let e = build_scene(&spectrum, &view, &settings, &log).unwrap_err();
+        assert!(matches!(&e, NmrError::NotFound(path) if path.ends_with("font.ttf")), "{:?}", e);
+    }
+
+    #[test]
+    fn test_overlay_legend() {
+        let n = 2000;
+        let spectrum = SpectrumData {
+            real: (0..n).map(|i| if i == 1000 { 2.0 } else { 0.0 }).collect(),
+            is_frequency_domain: true,
+            sample_name: "Crude".to_string(),
+            axes: vec![AxisParams { num_points: n, spectral_width_hz: 4000.0, observe_freq_mhz: 400.0, reference_ppm: 10.0, ..Default::default() }],
+            ..Default::default()
+        };
+        let trace = |name: &str| OverlayTrace::new(name.to_string(), (0..100).map(|i| [i as f64 * 0.1, 1.0]).collect());
+        let mut view = SpectrumViewState { reference_overlays: vec![trace("Water"), trace("Acetone")], ..Default::default() };
+        view.reference_overlays[0].scale = 0.5;
+        view.reference_overlays[0].offset = 0.2;
+        view.reference_overlays[1].visible = false;
+        let log = ReproLog::new();
+        let mut settings = ExportSettings { width: 1200, height: 800, ..Default::default() };
+
+        // Only visible traces are drawn and listed, with scale and offset
+        let scene = build_scene(&spectrum, &view, &settings, &log).unwrap();
+        let texts: Vec<&str> = scene
+            .commands
+            .iter()
+            .filter_map(|c| match c {
+                DrawCmd::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert!(texts.contains(&"Crude") && texts.contains(&"Water  ×0.50  +0.20"));
+        assert!(!texts.iter().any(|t| t.contains("Acetone")));
+        let water = scene
+            .commands
+            .iter()
+            .find_map(|c| match c {
+                DrawCmd::Line { points, color, .. } if *color == overlay_color(0) && points.len() > 2 => Some(points.clone()),
+                _ => None,
+            })
+            .unwrap();
+        // (1.0 × 0.5 + 0.2) × tallest point sits at 70% of the trace's peak
+        let peak_y = |v: f64| {
+            let y_range_padded = 2.0 * 1.05;
+            let plot_h = scene.height - (scene.height * 0.08).max(50.0) - (scene.height * 0.10).max(70.0);
+            (scene.height * 0.08).max(50.0) + (1.0 - v / y_range_padded) as f32 * plot_h
+        };
+        assert!((water[0][1] - peak_y(1.4)).abs() < 0.5);
+
+        // A legend in the parameter box's corner is stacked below it
+        settings.parameter_box = Some(Corner::TopRight);
+        let scene = build_scene(&spectrum, &view, &settings, &log).unwrap();
+        let boxes: Vec<f32> = scene
+            .commands
+            .iter()
+            .filter_map(|c| match c {
+                DrawCmd::Polygon { points, color } if *color == PARAM_BG => Some(points[0][1]),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(boxes.len(), 2);
+        assert!(boxes[1] > boxes[0]);
+
+        settings.legend = None;
+        let scene = build_scene(&spectrum, &view, &settings, &log).unwrap();
+        assert!(!scene.commands.iter().any(|c| matches!(c, DrawCmd::Text { text, .. } if text == "Crude")));
+
+        // Legend settings round-trip through the project file; the trace
+        // itself is reloaded from the library
+        let json = serde_json::to_string(&view.reference_overlays).unwrap();
+        let saved: Vec<OverlayTrace> = serde_json::from_str(&json).unwrap();
+        assert!(saved[0].points.is_empty() && saved[0].scale == 0.5 && !saved[1].visible);
+    }
+
+    #[test]
+    fn test_shift_region_bands() {
+        let n = 2000;
+        let spectrum = SpectrumData {
+            real: (0..n).map(|i| if i == 1000 { 1.0 } else { 0.0 }).collect(),
+            is_frequency_domain: true,
+            axes: vec![AxisParams {
+                nucleus: Nucleus::H1,
+                num_points: n,
+                spectral_width_hz: 6000.0,
+                observe_freq_mhz: 400.0,
+                reference_ppm: 14.0,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut view = SpectrumViewState::default();
+        view.shift_regions.push(ShiftRegion::new(Nucleus::F19, "CF3", (-70.0, -50.0)));
+        let log = ReproLog::new();
+        let mut settings = ExportSettings { width: 1200, height: 800, ..Default::default() };
+        let bands = |scene: &PlotScene| -> Vec<[f32; 2]> {
+            scene
+                .commands
+                .iter()
+                .filter_map(|c| match c {
+                    DrawCmd::Polygon { points, color } if *color != PARAM_BG => Some([points[0][0], points[1][0]]),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // Off by default
+        assert!(bands(&build_scene(&spectrum, &view, &settings, &log).unwrap()).is_empty());
+
+        // Only the 1H regions are drawn, behind the trace, labelled
+        settings.show_regions = true;
+        let scene = build_scene(&spectrum, &view, &settings, &log).unwrap();
+        let h1 = view.shift_regions.iter().filter(|r| r.nucleus == Nucleus::H1).count();
+        assert_eq!(bands(&scene).len(), h1);
+        let first_band = scene.commands.iter().position(|c| matches!(c, DrawCmd::Polygon { .. })).unwrap();
+        let trace = scene
+            .commands
+            .iter()
+            .position(|c| matches!(c, DrawCmd::Line { color, .. } if *color == TRACE))
+            .unwrap();
+        assert!(first_band < trace);
+        assert!(scene.commands.iter().any(|c| matches!(c, DrawCmd::Text { text, .. } if text == "Aromatic")));
+        assert!(!scene.commands.iter().any(|c| matches!(c, DrawCmd::Text { text, .. } if text == "CF3")));
+
+        // The aromatic band spans 8.5 … 6.5 ppm of the default -1 … 14 ppm window
+        let (left, plot_w) = (80.0f32, 1200.0 - 80.0 - 40.0);
+        let x = |ppm: f32| left + (14.0 - ppm) / 15.0 * plot_w;
+        let aromatic = bands(&scene)[4];
+        assert!((aromatic[0] - x(8.5)).abs() < 0.5 && (aromatic[1] - x(6.5)).abs() < 0.5);
+
+        // Regions outside the plotted range are skipped
+        settings.use_custom_range = true;
+        settings.ppm_start = 5.0;
+        settings.ppm_end = 0.0;
+        let scene = build_scene(&spectrum, &view, &settings, &log).unwrap();
+        assert_eq!(bands(&scene).len(), 4);
+    }
+
+    #[test]
+    fn test_ascending_axis_mirrors_scene() {
+        let n = 2000;
+        let spectrum = SpectrumData {
+            real: (0..n).map(|i| if i == 500 { 1.0 } else { 0.0 }).collect(),
+            is_frequency_domain: true,
+            axes: vec![AxisParams {
+                nucleus: Nucleus::H1,
+                num_points: n,
+                spectral_width_hz: 6000.0,
+                observe_freq_mhz: 400.0,
+                reference_ppm: 14.0,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let peak_ppm = spectrum.axes[0].ppm_scale()[500];
+        let mut view = SpectrumViewState { peaks: vec![[peak_ppm, 1.0]], ..Default::default() };
+        let log = ReproLog::new();
+        let settings = ExportSettings { width: 1200, height: 800, ..Default::default() };
+        // x of the trace's highest point, the peak label and the range text
+        let layout = |view: &SpectrumViewState| {
+            let scene = build_scene(&spectrum, view, &settings, &log).unwrap();
+            let apex = scene
+                .commands
+                .iter()
+                .find_map(|c| match c {
+                    DrawCmd::Line { points, color, .. } if *color == TRACE => {
+                        points.iter().copied().min_by(|a, b| a[1].total_cmp(&b[1]))
+                    }
+                    _ => None,
+                })
+                .unwrap()[0];
+            let text = |wanted: &dyn Fn(&str) -> bool| {
+                scene
+                    .commands
+                    .iter()
+                    .find_map(|c| match c {
+                        DrawCmd::Text { pos, text, .. } if wanted(text) => Some((pos[0], text.clone())),
+                        _ => None,
+                    })
+                    .unwrap()
+            };
+            let label = text(&|t| t == format!("{:.2}", peak_ppm)).0;
+            let range = text(&|t| t.ends_with(" ppm") && t.contains(" – ")).1;
+            (apex, label, range)
+        };
+
+        let (left, plot_w) = (80.0f32, 1200.0 - 80.0 - 40.0);
+        let (apex, label, range) = layout(&view);
+        view.axis_direction = AxisDirection::Ascending;
+        let (apex_asc, label_asc, range_asc) = layout(&view);
+        // The high-ppm peak moves from the left half to the right half
+        assert!(apex < left + plot_w / 2.0 && apex_asc > left + plot_w / 2.0);
+        assert!((apex + apex_asc - (2.0 * left + plot_w)).abs() < 1.0);
+        assert!((label + label_asc - (2.0 * left + plot_w)).abs() < 1.0);
+        assert_eq!(range, "14.0 – -1.0 ppm");
+        assert_eq!(range_asc, "-1.0 – 14.0 ppm");
+    }
+
+    #[test]
+    fn test_fitted_components_as_translucent_areas() {
+        let n = 1000;
+        let spectrum = SpectrumData {
+            real: (0..n).map(|i| if i == 200 { 100.0 } else { 0.0 }).collect(),
+            is_frequency_domain: true,
+            axes: vec![AxisParams {
+                num_points: n,
+                spectral_width_hz: 4000.0,
+                observe_freq_mhz: 400.0,
+                reference_ppm: 10.0,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        // Two triangular lines on an offset of 1 between 3.0 and 3.5 ppm
+        let line = |center: f64, height: f64| -> Vec<[f64; 2]> {
+            (0..=50).map(|i| 3.0 + i as f64 * 0.01).map(|ppm| [ppm, 1.0 + (height - (ppm - center).abs() * 400.0).max(0.0)]).collect()
+        };
+        let components = vec![line(3.2, 40.0), line(3.3, 20.0)];
+        let trace = components[0].iter().zip(&components[1]).map(|(a, b)| [a[0], a[1] + b[1] - 1.0]).collect();
+        let peak = |center_ppm| processing::FittedPeak { center_ppm, fwhm_hz: 40.0, amplitude: 1.0, area: 1.0, lorentz_fraction: 1.0 };
+        let view = SpectrumViewState {
+            peak_fits: vec![processing::PeakFit {
+                lo_ppm: 3.0,
+                hi_ppm: 3.5,
+                shape: processing::LineShape::Lorentzian,
+                peaks: vec![peak(3.3), peak(3.2)],
+                offset: 1.0,
+                relative_residual: 0.0,
+                trace,
+                components,
+            }],
+            ..Default::default()
+        };
+        let mut settings = ExportSettings { width: 800, height: 600, show_grid: false, ..Default::default() };
+        let scene = build_scene(&spectrum, &view, &settings, &ReproLog::new()).unwrap();
+
+        let areas: Vec<(&Vec<[f32; 2]>, f32)> = scene
+            .commands
+            .iter()
+            .filter_map(|c| match c {
+                DrawCmd::Area { points, base, color } if *color == FIT => Some((points, *base)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(areas.len(), 2);
+        // Under the trace, with the sum of the fit drawn over it
+        let trace_at = scene.commands.iter().position(|c| matches!(c, DrawCmd::Line { color, .. } if *color == TRACE)).unwrap();
+        let area_at = scene.commands.iter().position(|c| matches!(c, DrawCmd::Area { .. })).unwrap();
+        assert!(area_at < trace_at);
+        assert!(scene.commands.iter().any(|c| matches!(c, DrawCmd::Line { color, .. } if *color == FIT)));
+
+        // The PNG blends the fill once over white, away from the edges
+        let (points, base) = areas[0];
+        let apex = points.iter().fold([0.0, f32::INFINITY], |m, p| if p[1] < m[1] { *p } else { m });
+        let img = to_png(&scene);
+        let pixel = img.get_pixel(apex[0].round() as u32, ((apex[1] + base) / 2.0) as u32).0;
+        let expected = FIT.map(|c| (255.0 * (1.0 - AREA_OPACITY) + c as f32 * AREA_OPACITY).round() as u8);
+        assert_eq!(pixel, expected);
+
+        let svg = to_svg(&scene);
+        assert_eq!(svg.matches(&format!("fill-opacity='{}'", AREA_OPACITY)).count(), 2);
+        let pdf = String::from_utf8_lossy(&to_pdf(&scene)).to_string();
+        assert!(pdf.contains("/ExtGState << /GA << /ca 0.25 >> >>"));
+        assert_eq!(pdf.matches("/GA gs").count(), 2);
+
+        settings.show_peak_fits = false;
+        let scene = build_scene(&spectrum, &view, &settings, &ReproLog::new()).unwrap();
+        assert!(!scene.commands.iter().any(|c| matches!(c, DrawCmd::Area { .. })));
+    }
+
+    #[test]
+    fn test_csp_plot_breaks_lines_at_missing_points() {
+        let tracks = vec![
+            processing::PeakTrack { shifts: vec![Some(8.30), Some(8.26), Some(8.22)] },
+            processing::PeakTrack { shifts: vec![Some(8.10), None, Some(8.16)] },
+        ];
+        let settings = ExportSettings { width: 800, height: 500, ..Default::default() };
+        let scene = csp_scene(&[0.0, 0.5, 1.0], "Equivalents", &tracks, "CSP", &settings).unwrap();
+
+        let lines = |color: Rgb| {
+            scene
+                .commands
+                .iter()
+                .filter(|c| matches!(c, DrawCmd::Line { color: c, points, .. } if *c == color && points.len() > 2))
+                .count()
+        };
+        // A line through three points for the first peak, none for the
+        // second: it was not found in the middle spectrum
+        assert_eq!(lines(session_color(0)), 1);
+        assert_eq!(lines(session_color(1)), 0);
+        let markers = scene.commands.iter().filter(|c| matches!(c, DrawCmd::Polygon { .. })).count();
+        assert_eq!(markers, 5);
+
+        let svg = to_svg(&scene);
+        assert!(svg.contains(">Equivalents</text>") && svg.contains(">δ 8.100</text>"));
+        assert!(csp_scene(&[0.0], "x", &[], "CSP", &settings).is_err());
+    }
+
+    #[test]
+    fn test_integral_trace_scale_and_stacked_labels() {
+        // The largest trace value, of either sign, reaches the trace
+        // height as a fraction of the tallest peak
+        let traces = [vec![[5.0, 0.0], [4.9, 3.0], [4.8, 6.0]], vec![[2.0, 0.0], [1.9, -8.0]]];
+        let scale = integral_trace_scale(&traces, 2.0);
+        assert!((8.0 * scale - INTEGRAL_TRACE_HEIGHT * 2.0).abs() < 1e-9);
+
+        // Overlapping labels step away in order; a clear one stays put
+        let slot = |x: f64| LabelSlot { x: (x, x + 10.0), y: (0.0, 4.0) };
+        let slots = [slot(20.0), slot(0.0), slot(5.0), slot(40.0), slot(8.0)];
+        assert_eq!(stack_labels(&slots, 5.0), vec![0, 0, 1, 0, 2]);
+        assert_eq!(stack_labels(&slots, -5.0), vec![0, 0, 1, 0, 2]);
+    }
+}
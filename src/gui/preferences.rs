@@ -0,0 +1,740 @@
+//! Application preferences — persisted settings and the Preferences window
+//!
+//! Preferences are stored as JSON in the per-user config directory
+//! (`$XDG_CONFIG_HOME/nmr_gui`, `~/.config/nmr_gui`, or `%APPDATA%\nmr_gui`).
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::spectrum::{Nucleus, ShiftRegion};
+use crate::data::storage::{MemoryBudget, StoragePrecision};
+use crate::gui::conversion_dialog::DmxCorrection;
+use crate::gui::plot_scene;
+use crate::gui::spectrum_view::AxisDirection;
+use crate::pipeline::cache::CacheSettings;
+use crate::pipeline::external::ExternalTool;
+use crate::pipeline::processing::{DeadTimeMode, Ft2dMode, WindowFunction, CENTER_GLITCH_SIGMA};
+use crate::pipeline::profiles::{ProcessingProfile, ProcessingProfiles, PROFILE_EXPERIMENTS};
+use crate::pipeline::workdir::{WorkDirLocation, WorkDirSettings};
+
+/// Persisted user preferences
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    /// Where conversion outputs go and whether they are kept
+    pub work_dir: WorkDirSettings,
+    /// Conversion result cache
+    pub cache: CacheSettings,
+    /// Bruker digital-filter handling for every open and batch conversion
+    pub bruker_dmx: DmxCorrection,
+    /// Precision of undo/redo snapshots
+    pub history_precision: StoragePrecision,
+    /// Warn before a step takes memory use past this budget
+    pub memory_budget: MemoryBudget,
+    /// Prediction / structure tools run from the pipeline panel
+    pub external_tools: Vec<ExternalTool>,
+    /// Exports regenerated whenever a project is saved
+    pub auto_report: AutoReportSettings,
+    /// Threads rasterizing PNG figures; 0 uses every core
+    pub export_threads: usize,
+    /// Keep `processing_log.json` next to the data up to date after every
+    /// operation
+    pub mirror_log: bool,
+    /// Referencing to the residual solvent signal when data is loaded
+    pub solvent_reference: SolventReferenceSettings,
+    /// Detection and removal of the zero-frequency spike after the FT
+    pub center_glitch: CenterGlitchSettings,
+    /// Dead-time correction offered in the pipeline panel for newly loaded
+    /// data
+    pub dead_time: DeadTimeSettings,
+    /// Default processing recipe per experiment type (Quick Process, batch)
+    pub processing_profiles: ProcessingProfiles,
+    /// Functional-group regions shaded in the view and exports
+    pub shift_regions: ShiftRegionSettings,
+    /// Explain each processing step in a pop-up panel
+    pub teaching_mode: bool,
+    /// Direction of the ppm axis in 1D views and exports; projects saved
+    /// with another direction keep theirs
+    pub axis_direction: AxisDirection,
+}
+
+/// Editable functional-group shift regions, per nucleus
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShiftRegionSettings {
+    pub regions: Vec<ShiftRegion>,
+}
+
+impl Default for ShiftRegionSettings {
+    fn default() -> Self {
+        Self { regions: ShiftRegion::standard() }
+    }
+}
+
+/// Which nuclei are referenced to the residual solvent signal of the
+/// solvent named in the acquisition parameters, once the spectrum is in the
+/// frequency domain after loading
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SolventReferenceSettings {
+    /// 1H (and 2H)
+    pub proton: bool,
+    pub carbon: bool,
+    /// Flag the solvent and common impurity peaks after peak picking
+    pub annotate_peaks: bool,
+}
+
+impl Default for SolventReferenceSettings {
+    fn default() -> Self {
+        Self { proton: true, carbon: false, annotate_peaks: true }
+    }
+}
+
+impl SolventReferenceSettings {
+    /// Whether spectra of `nucleus` are referenced automatically
+    pub fn applies_to(&self, nucleus: &Nucleus) -> bool {
+        match nucleus {
+            Nucleus::H1 | Nucleus::H2 => self.proton,
+            Nucleus::C13 => self.carbon,
+            _ => false,
+        }
+    }
+}
+
+/// What happens when a 1D spectrum comes out of the Fourier transform
+/// with a spike at zero frequency (a DC offset of the FID)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CenterGlitchSettings {
+    /// Interpolate across the spike straight away, as an undoable step;
+    /// otherwise it is only reported
+    pub auto_remove: bool,
+    /// Height above the local baseline, in noise standard deviations, for
+    /// a spike to count
+    pub threshold_sigma: f64,
+}
+
+impl Default for CenterGlitchSettings {
+    fn default() -> Self {
+        Self { auto_remove: false, threshold_sigma: CENTER_GLITCH_SIGMA }
+    }
+}
+
+/// Points at the start of the FID treated as probe ring-down, and how they
+/// are handled; loading data sets the pipeline panel to these
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeadTimeSettings {
+    /// 0 leaves the FID alone
+    pub points: usize,
+    pub mode: DeadTimeMode,
+}
+
+impl Default for DeadTimeSettings {
+    fn default() -> Self {
+        Self { points: 0, mode: DeadTimeMode::BackwardLp }
+    }
+}
+
+/// Exports written to a `reports/` folder next to the project file on every
+/// save, named after the project
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoReportSettings {
+    pub enabled: bool,
+    pub png: bool,
+    pub svg: bool,
+    pub pdf: bool,
+    /// Peak / integration / multiplet tables
+    pub csv: bool,
+    /// Reproducibility log (text)
+    pub log: bool,
+}
+
+impl Default for AutoReportSettings {
+    fn default() -> Self {
+        Self { enabled: false, png: true, svg: false, pdf: true, csv: true, log: true }
+    }
+}
+
+impl AutoReportSettings {
+    /// Folder the reports for `project` go into
+    pub fn dir_for(project: &Path) -> PathBuf {
+        project.parent().unwrap_or(Path::new(".")).join("reports")
+    }
+}
+
+impl Preferences {
+    /// Per-user config directory for this application
+    pub fn config_dir() -> Option<PathBuf> {
+        if cfg!(target_os = "windows") {
+            std::env::var_os("APPDATA").map(|d| PathBuf::from(d).join("nmr_gui"))
+        } else if let Some(xdg) = std::env::var_os("XDG_CONFIG_HOME") {
+            Some(PathBuf::from(xdg).join("nmr_gui"))
+        } else {
+            std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config").join("nmr_gui"))
+        }
+    }
+
+    fn file_path() -> Option<PathBuf> {
+        Self::config_dir().map(|d| d.join("preferences.json"))
+    }
+
+    /// Load preferences, falling back to defaults if missing or unreadable
+    pub fn load() -> Self {
+        let Some(path) = Self::file_path() else {
+            return Self::default();
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                log::warn!("Ignoring invalid preferences {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Write preferences to the config directory
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::file_path().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "No user config directory available")
+        })?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, json)
+    }
+}
+
+/// State for the Preferences window
+#[derive(Debug, Clone, Default)]
+pub struct PreferencesDialogState {
+    pub open: bool,
+    /// Edited copy; applied on "Apply"
+    pub draft: Preferences,
+}
+
+/// Actions from the Preferences window
+#[derive(Debug, Clone, PartialEq)]
+pub enum PreferencesAction {
+    None,
+    Apply,
+    Cancel,
+    /// Open the current session's conversion folder
+    RevealWorkDir,
+    /// Delete all cached conversions
+    ClearCache,
+}
+
+/// Show the Preferences window.
+///
+/// `session_dir` is this session's scratch folder (if outputs are not
+/// written next to the data), shown so users know where files end up.
+/// `cache_usage` is the current (entry count, bytes) of the conversion cache.
+pub fn show_preferences_dialog(
+    ctx: &egui::Context,
+    state: &mut PreferencesDialogState,
+    session_dir: Option<&Path>,
+    cache_usage: (usize, u64),
+) -> PreferencesAction {
+    let mut action = PreferencesAction::None;
+    if !state.open {
+        return action;
+    }
+
+    let mut open = state.open;
+    egui::Window::new("⚙ Preferences")
+        .open(&mut open)
+        .default_size([520.0, 300.0])
+        .resizable(true)
+        .show(ctx, |ui| {
+            ui.heading("Conversion work directory");
+            ui.label(
+                egui::RichText::new(
+                    "Where converted NMRPipe files (.fid) are written by delta2pipe, \
+                     bruk2pipe, var2pipe and batch conversion.",
+                )
+                .small(),
+            );
+            ui.add_space(4.0);
+
+            let work = &mut state.draft.work_dir;
+            let is_custom = matches!(work.location, WorkDirLocation::Custom(_));
+            if ui
+                .radio(work.location == WorkDirLocation::NextToData, WorkDirLocation::NextToData.label())
+                .clicked()
+            {
+                work.location = WorkDirLocation::NextToData;
+            }
+            if ui
+                .radio(work.location == WorkDirLocation::SystemTemp, WorkDirLocation::SystemTemp.label())
+                .clicked()
+            {
+                work.location = WorkDirLocation::SystemTemp;
+            }
+            ui.horizontal(|ui| {
+                if ui.radio(is_custom, "Custom directory").clicked() && !is_custom {
+                    work.location = WorkDirLocation::Custom(std::env::temp_dir());
+                }
+                if let WorkDirLocation::Custom(dir) = &mut work.location {
+                    ui.label(dir.display().to_string());
+                    if ui.button("Browse…").clicked() {
+                        if let Some(picked) = rfd::FileDialog::new()
+                            .set_title("Choose Work Directory")
+                            .pick_folder()
+                        {
+                            *dir = picked;
+                        }
+                    }
+                }
+            });
+            if work.location != WorkDirLocation::NextToData {
+                ui.label(
+                    egui::RichText::new("Each session writes into its own session-<date>-<id> subfolder.")
+                        .small()
+                        .italics(),
+                );
+            }
+
+            ui.add_space(4.0);
+            ui.checkbox(&mut work.keep_intermediates, "Keep converted files when the application exits");
+
+            if let Some(dir) = session_dir {
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(format!("This session: {}", dir.display())).small());
+                    if ui.small_button("📂 Reveal").clicked() {
+                        action = PreferencesAction::RevealWorkDir;
+                    }
+                });
+            }
+
+            ui.add_space(8.0);
+            ui.separator();
+            ui.heading("Bruker digital filter");
+            ui.label(
+                egui::RichText::new(
+                    "Group-delay (DMX) correction for Bruker FIDs. Choose \"already corrected\" \
+                     for exports whose filter delay was removed when auto-detection misses them.",
+                )
+                .small(),
+            );
+            for mode in [DmxCorrection::Auto, DmxCorrection::AlreadyCorrected, DmxCorrection::Apply] {
+                ui.radio_value(&mut state.draft.bruker_dmx, mode, mode.label());
+            }
+
+            ui.add_space(8.0);
+            ui.separator();
+            ui.heading("Processing profiles");
+            ui.label(
+                egui::RichText::new(
+                    "Default recipe for each experiment type, used by Quick Process and \
+                     batch conversion. The window, zero filling, phase and baseline apply to \
+                     1D data; 2D data is transformed in the chosen mode.",
+                )
+                .small(),
+            );
+            let profiles = &mut state.draft.processing_profiles;
+            let mut remove = None;
+            egui::Grid::new("processing_profiles_grid").num_columns(7).show(ui, |ui| {
+                ui.strong("Experiment");
+                ui.strong("Window");
+                ui.strong("Zero fill");
+                ui.strong("Phase");
+                ui.strong("Baseline");
+                ui.strong("2D mode");
+                ui.end_row();
+                for (i, profile) in profiles.profiles.iter_mut().enumerate() {
+                    egui::ComboBox::from_id_salt(("profile_experiment", i))
+                        .selected_text(profile.experiment.to_string())
+                        .show_ui(ui, |ui| {
+                            for exp in PROFILE_EXPERIMENTS {
+                                let label = exp.to_string();
+                                ui.selectable_value(&mut profile.experiment, exp, label);
+                            }
+                        });
+                    let is_2d = profile.is_2d();
+                    ui.add_enabled_ui(!is_2d, |ui| window_editor(ui, &mut profile.window, i));
+                    ui.add_enabled(
+                        !is_2d,
+                        egui::DragValue::new(&mut profile.zero_fill_doublings).range(0..=3).prefix("×2^"),
+                    );
+                    ui.add_enabled(!is_2d, egui::Checkbox::without_text(&mut profile.auto_phase));
+                    ui.add_enabled(!is_2d, egui::Checkbox::without_text(&mut profile.baseline));
+                    ui.add_enabled_ui(is_2d, |ui| {
+                        egui::ComboBox::from_id_salt(("profile_ft2d", i))
+                            .selected_text(profile.ft2d_mode.to_string())
+                            .show_ui(ui, |ui| {
+                                for mode in [Ft2dMode::PhaseSensitive, Ft2dMode::Magnitude] {
+                                    ui.selectable_value(&mut profile.ft2d_mode, mode, mode.to_string());
+                                }
+                            });
+                    });
+                    if ui.small_button("🗑").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(i) = remove {
+                profiles.profiles.remove(i);
+            }
+            ui.horizontal(|ui| {
+                let unbound = PROFILE_EXPERIMENTS
+                    .into_iter()
+                    .find(|exp| profiles.profile_for(exp).is_none());
+                if let Some(exp) = unbound {
+                    if ui.button("➕ Add profile").clicked() {
+                        profiles.profiles.push(ProcessingProfile::default_for(exp));
+                    }
+                }
+                if ui.button("Restore defaults").clicked() {
+                    profiles.profiles = ProcessingProfiles::default().profiles;
+                }
+            });
+            ui.checkbox(
+                &mut profiles.batch_process,
+                "Batch conversion also writes processed spectra (.ft1 / .ft2)",
+            );
+
+            ui.add_space(8.0);
+            ui.separator();
+            ui.heading("Solvent referencing");
+            ui.label(
+                egui::RichText::new(
+                    "Shift the ppm axis so the residual signal of the solvent named in the \
+                     acquisition parameters sits at its tabulated value, when a spectrum is \
+                     opened or first Fourier-transformed. The shift applied is logged.",
+                )
+                .small(),
+            );
+            let solvent = &mut state.draft.solvent_reference;
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut solvent.proton, "1H");
+                ui.checkbox(&mut solvent.carbon, "13C");
+            });
+            ui.checkbox(
+                &mut solvent.annotate_peaks,
+                "Label solvent and impurity peaks (water, grease, …) after peak picking",
+            );
+
+            ui.add_space(8.0);
+            ui.separator();
+            ui.heading("Center glitch");
+            ui.label(
+                egui::RichText::new(
+                    "Look for the spike a DC offset leaves at the centre of a 1D spectrum \
+                     after the Fourier transform. It is reported in the status bar, or \
+                     interpolated away as a logged step that Undo reverts.",
+                )
+                .small(),
+            );
+            let glitch = &mut state.draft.center_glitch;
+            ui.checkbox(&mut glitch.auto_remove, "Remove automatically after FT");
+            ui.add(
+                egui::Slider::new(&mut glitch.threshold_sigma, 4.0..=50.0)
+                    .logarithmic(true)
+                    .text("Threshold (× noise)")
+                    .fixed_decimals(0),
+            );
+
+            ui.add_space(8.0);
+            ui.separator();
+            ui.heading("Dead time");
+            ui.label(
+                egui::RichText::new(
+                    "First FID points the pipeline panel offers to rebuild or drop for \
+                     newly loaded data, e.g. for probe ring-down in 19F or 31P spectra. \
+                     Nothing is applied until \"Apply Dead Time\" is pressed.",
+                )
+                .small(),
+            );
+            let dead_time = &mut state.draft.dead_time;
+            ui.add(egui::Slider::new(&mut dead_time.points, 0..=64).text("Points"));
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut dead_time.mode, DeadTimeMode::BackwardLp, "Rebuild (backward LP)");
+                ui.radio_value(&mut dead_time.mode, DeadTimeMode::Drop, "Drop (left shift)");
+            });
+
+            ui.add_space(8.0);
+            ui.separator();
+            ui.heading("Spectrum axis");
+            ui.label(
+                egui::RichText::new(
+                    "Direction of the ppm axis in 1D views, picking and exports. \
+                     \"⇄ ppm\" in the view reverses it for the session; projects keep \
+                     the direction they were saved with. 2D maps keep the NMR convention.",
+                )
+                .small(),
+            );
+            for direction in [AxisDirection::Descending, AxisDirection::Ascending] {
+                ui.radio_value(&mut state.draft.axis_direction, direction, direction.label());
+            }
+
+            ui.add_space(8.0);
+            ui.separator();
+            ui.heading("Functional-group regions");
+            ui.label(
+                egui::RichText::new(
+                    "Shift ranges shaded behind spectra of their nucleus when \"Regions\" \
+                     is ticked in the view or the Export tab.",
+                )
+                .small(),
+            );
+            let regions = &mut state.draft.shift_regions.regions;
+            let mut remove = None;
+            egui::Grid::new("shift_regions_grid").num_columns(5).show(ui, |ui| {
+                ui.strong("Nucleus");
+                ui.strong("Region");
+                ui.strong("From (ppm)");
+                ui.strong("To (ppm)");
+                ui.end_row();
+                for (i, region) in regions.iter_mut().enumerate() {
+                    egui::ComboBox::from_id_salt(("shift_region_nucleus", i))
+                        .selected_text(region.nucleus.to_string())
+                        .width(60.0)
+                        .show_ui(ui, |ui| {
+                            for nucleus in [Nucleus::H1, Nucleus::C13, Nucleus::N15, Nucleus::F19, Nucleus::P31] {
+                                let label = nucleus.to_string();
+                                ui.selectable_value(&mut region.nucleus, nucleus, label);
+                            }
+                        });
+                    ui.add(egui::TextEdit::singleline(&mut region.name).desired_width(140.0));
+                    ui.add(egui::DragValue::new(&mut region.range.0).speed(0.1).max_decimals(2));
+                    ui.add(egui::DragValue::new(&mut region.range.1).speed(0.1).max_decimals(2));
+                    if ui.small_button("🗑").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(i) = remove {
+                regions.remove(i);
+            }
+            ui.horizontal(|ui| {
+                if ui.button("➕ Add region").clicked() {
+                    regions.push(ShiftRegion::new(Nucleus::H1, "Region", (0.0, 1.0)));
+                }
+                if ui.button("Restore defaults").clicked() {
+                    *regions = ShiftRegion::standard();
+                }
+            });
+
+            ui.add_space(8.0);
+            ui.separator();
+            ui.heading("Undo history");
+            ui.label(
+                egui::RichText::new(
+                    "Each undo step keeps a full copy of the spectrum. Single precision halves \
+                     that for large 2D data; processing always runs in double precision, but \
+                     undone data comes back rounded to ~7 digits.",
+                )
+                .small(),
+            );
+            for precision in [StoragePrecision::Double, StoragePrecision::Single] {
+                ui.radio_value(&mut state.draft.history_precision, precision, precision.label());
+            }
+            let budget = &mut state.draft.memory_budget;
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut budget.warn, "Warn before a step takes memory use past");
+                ui.add_enabled(
+                    budget.warn,
+                    egui::DragValue::new(&mut budget.budget_mb).range(64..=262_144).speed(64).suffix(" MB"),
+                );
+            })
+            .response
+            .on_hover_text(
+                "Spectra plus undo/redo history, as shown in the status bar. Zero filling, \
+                 linear prediction, NUS reconstruction and the 2D FT are checked beforehand.",
+            );
+
+            ui.add_space(8.0);
+            ui.separator();
+            ui.heading("Conversion cache");
+            ui.label(
+                egui::RichText::new(
+                    "Reopening a dataset with unchanged files and settings reuses the \
+                     previous conversion instead of re-running it.",
+                )
+                .small(),
+            );
+            let cache = &mut state.draft.cache;
+            ui.checkbox(&mut cache.enabled, "Cache conversion results");
+            ui.horizontal(|ui| {
+                ui.label("Size limit:");
+                ui.add(
+                    egui::DragValue::new(&mut cache.max_size_mb)
+                        .range(16..=65536)
+                        .speed(16)
+                        .suffix(" MB"),
+                );
+            });
+            ui.horizontal(|ui| {
+                let (count, bytes) = cache_usage;
+                ui.label(format!(
+                    "{} cached conversions, {:.1} MB",
+                    count,
+                    bytes as f64 / (1024.0 * 1024.0)
+                ));
+                if ui.add_enabled(count > 0, egui::Button::new("🗑 Clear cache")).clicked() {
+                    action = PreferencesAction::ClearCache;
+                }
+            });
+
+            ui.add_space(8.0);
+            ui.separator();
+            ui.heading("Automatic reports");
+            ui.label(
+                egui::RichText::new(
+                    "Regenerate exports into a reports/ folder next to the project file \
+                     every time it is saved, using the Export tab's settings.",
+                )
+                .small(),
+            );
+            let report = &mut state.draft.auto_report;
+            ui.checkbox(&mut report.enabled, "Write reports when saving a project");
+            ui.add_enabled_ui(report.enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut report.png, "PNG");
+                    ui.checkbox(&mut report.svg, "SVG");
+                    ui.checkbox(&mut report.pdf, "PDF");
+                    ui.checkbox(&mut report.csv, "CSV tables");
+                    ui.checkbox(&mut report.log, "Processing log");
+                });
+            });
+
+            ui.add_space(8.0);
+            ui.separator();
+            ui.heading("Image export");
+            ui.label(
+                egui::RichText::new(
+                    "PNG figures are rendered in horizontal bands on several threads, in \
+                     the background with a progress bar. 0 uses every core.",
+                )
+                .small(),
+            );
+            let cores = plot_scene::render_threads(0);
+            ui.add(egui::Slider::new(&mut state.draft.export_threads, 0..=cores.max(1)).text("Render threads"));
+
+            ui.add_space(8.0);
+            ui.separator();
+            ui.heading("Processing log");
+            ui.label(
+                egui::RichText::new(
+                    "Rewrite the reproducibility log as processing_log.json next to the \
+                     source data (or the project file, once saved) after every operation, \
+                     so it survives a crash and collaborators find it with the data.",
+                )
+                .small(),
+            );
+            ui.checkbox(&mut state.draft.mirror_log, "Mirror the log next to the data");
+
+            ui.add_space(8.0);
+            ui.separator();
+            ui.heading("External tools");
+            ui.label(
+                egui::RichText::new(
+                    "Programs that read the peak list as JSON on stdin and answer with \
+                     predicted lines and assignments as JSON on stdout.",
+                )
+                .small(),
+            );
+            let tools = &mut state.draft.external_tools;
+            let mut remove = None;
+            if !tools.is_empty() {
+                egui::Grid::new("external_tools_grid").num_columns(4).show(ui, |ui| {
+                    ui.strong("Name");
+                    ui.strong("Program");
+                    ui.strong("Arguments");
+                    ui.end_row();
+                    for (i, tool) in tools.iter_mut().enumerate() {
+                        ui.add(egui::TextEdit::singleline(&mut tool.name).desired_width(110.0));
+                        ui.horizontal(|ui| {
+                            ui.add(egui::TextEdit::singleline(&mut tool.program).desired_width(110.0));
+                            if ui.small_button("…").on_hover_text("Browse").clicked() {
+                                if let Some(picked) = rfd::FileDialog::new().set_title("Choose Program").pick_file() {
+                                    tool.program = picked.to_string_lossy().to_string();
+                                }
+                            }
+                        });
+                        ui.add(egui::TextEdit::singleline(&mut tool.args).desired_width(180.0));
+                        if ui.small_button("🗑").clicked() {
+                            remove = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                tools.remove(i);
+            }
+            if ui.button("➕ Add tool").clicked() {
+                tools.push(ExternalTool { name: format!("Tool {}", tools.len() + 1), ..Default::default() });
+            }
+
+            ui.add_space(8.0);
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("✔ Apply").clicked() {
+                    action = PreferencesAction::Apply;
+                }
+                if ui.button("Cancel").clicked() {
+                    action = PreferencesAction::Cancel;
+                }
+            });
+        });
+
+    if !open {
+        action = PreferencesAction::Cancel;
+    }
+    state.open = open;
+    action
+}
+
+/// Window function kind and its main parameters, edited in place
+fn window_editor(ui: &mut egui::Ui, window: &mut WindowFunction, row: usize) {
+    let kind = match window {
+        WindowFunction::Exponential { .. } => "EM",
+        WindowFunction::Gaussian { .. } => "GM",
+        WindowFunction::SineBell { .. } => "Sine bell",
+        WindowFunction::CosineBell => "Cosine bell",
+        WindowFunction::None => "None",
+    };
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_salt(("profile_window", row))
+            .selected_text(kind)
+            .width(90.0)
+            .show_ui(ui, |ui| {
+                let choices = [
+                    ("EM", WindowFunction::Exponential { lb_hz: 1.0 }),
+                    ("GM", WindowFunction::Gaussian { gb: 0.1, lb_hz: -1.0 }),
+                    ("Sine bell", WindowFunction::SineBell { power: 2.0, offset: 0.5, end: 1.0 }),
+                    ("Cosine bell", WindowFunction::CosineBell),
+                    ("None", WindowFunction::None),
+                ];
+                for (label, default) in choices {
+                    if ui.selectable_label(kind == label, label).clicked() && kind != label {
+                        *window = default;
+                    }
+                }
+            });
+        match window {
+            WindowFunction::Exponential { lb_hz } => {
+                ui.add(egui::DragValue::new(lb_hz).range(0.0..=50.0).speed(0.1).suffix(" Hz"));
+            }
+            WindowFunction::Gaussian { gb, lb_hz } => {
+                ui.add(egui::DragValue::new(gb).range(0.0..=1.0).speed(0.01).prefix("GB "));
+                ui.add(egui::DragValue::new(lb_hz).range(-50.0..=0.0).speed(0.1).suffix(" Hz"));
+            }
+            WindowFunction::SineBell { power, offset, .. } => {
+                ui.add(egui::DragValue::new(power).range(1.0..=4.0).speed(0.1).prefix("pow "));
+                ui.add(egui::DragValue::new(offset).range(0.0..=0.5).speed(0.01).prefix("off "));
+            }
+            WindowFunction::CosineBell | WindowFunction::None => {}
+        }
+    });
+}
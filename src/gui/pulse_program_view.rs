@@ -0,0 +1,64 @@
+//! Pulse program tab — read-only listing of the sequence the data were
+//! acquired with
+//!
+//! The key acquisition parameters (D1, P1, NS) are listed with their values
+//! above the text, and every mention of them in the text is highlighted in
+//! the same colour.
+
+use egui::text::LayoutJob;
+use egui::{Color32, FontId, TextFormat};
+
+use crate::data::pulse_program::PulseProgram;
+
+/// Highlight colour of each key parameter, in order
+const KEY_COLORS: [Color32; 3] = [
+    Color32::from_rgb(0x1E, 0x88, 0xE5),
+    Color32::from_rgb(0xE5, 0x39, 0x35),
+    Color32::from_rgb(0x43, 0xA0, 0x47),
+];
+
+fn key_color(index: usize) -> Color32 {
+    KEY_COLORS[index % KEY_COLORS.len()]
+}
+
+/// Render the pulse program tab
+pub fn show_pulse_program(ui: &mut egui::Ui, program: &PulseProgram) {
+    ui.horizontal(|ui| {
+        ui.heading(format!("📜 {}", program.name));
+        ui.label(egui::RichText::new(program.source.display().to_string()).weak().small());
+    });
+    ui.add_space(4.0);
+
+    egui::Grid::new("pulse_program_keys").striped(true).show(ui, |ui| {
+        for (i, key) in program.key_parameters.iter().enumerate() {
+            ui.label(egui::RichText::new(&key.label).strong().monospace().color(key_color(i)));
+            ui.label(&key.meaning);
+            match &key.value {
+                Some(value) => ui.strong(value),
+                None => ui.weak("not recorded"),
+            };
+            ui.end_row();
+        }
+    });
+    ui.separator();
+
+    let font = FontId::monospace(12.0);
+    let plain = TextFormat::simple(font.clone(), ui.visuals().text_color());
+    let mut job = LayoutJob::default();
+    let mut pos = 0;
+    for (range, index) in program.highlights() {
+        if range.start < pos {
+            continue;
+        }
+        job.append(&program.text[pos..range.start], 0.0, plain.clone());
+        let mut marked = TextFormat::simple(font.clone(), key_color(index));
+        marked.background = key_color(index).gamma_multiply(0.18);
+        job.append(&program.text[range.clone()], 0.0, marked);
+        pos = range.end;
+    }
+    job.append(&program.text[pos..], 0.0, plain);
+
+    egui::ScrollArea::both().auto_shrink([false, false]).show(ui, |ui| {
+        ui.add(egui::Label::new(job).selectable(true));
+    });
+}
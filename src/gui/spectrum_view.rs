@@ -1,9 +1,12 @@
 /// 1D Spectrum viewer widget — interactive plot with zoom/pan and ppm axis
 
-use egui_plot::{Line, Plot, PlotPoints, PlotUi, Points, Text, VLine};
+use egui_plot::{Line, Plot, PlotBounds, PlotPoints, PlotUi, Points, Polygon, Text, VLine};
 
-use crate::data::spectrum::{ExperimentType, Nucleus, SpectrumData};
+use crate::data::spectrum::{Nucleus, ShiftRegion, SpectrumData};
+use crate::gui::plot_scene;
 use crate::gui::phase_dialog::PhaseDialogState;
+use crate::pipeline::external::{Assignment, Stick};
+use crate::pipeline::processing::{self, BaselineMethod, IntegralBaseline, IntensityMode};
 
 /// An analysis action performed by a click in the spectrum view,
 /// to be logged by the app after the frame.
@@ -17,6 +20,238 @@ pub enum SpectrumAction {
     IntegrationAdded(f64, f64, f64),
     /// J-coupling measured (ppm1, ppm2, delta_ppm, j_hz)
     JCouplingMeasured(f64, f64, f64, f64),
+    /// Couplings extracted from the multiplet at this index (`None`: no
+    /// multiplet under the click)
+    MultipletCouplings(Option<usize>),
+    /// Linewidth measured at the clicked ppm (`None`: no half-height crossing)
+    LinewidthMeasured(f64, Option<processing::Linewidth>),
+    /// Lines fitted between (lo_ppm, hi_ppm) (`None`: the fit failed)
+    PeaksFitted(f64, f64, Option<processing::PeakFit>),
+    /// Phased on the peak clicked at ppm (`None`: no peak to phase on)
+    PhasedOnPeak(f64, Option<processing::PhaseAnchor>),
+    /// Solvent suppression region dragged out: (low_ppm, high_ppm)
+    SolventRegionAdded((f64, f64)),
+    /// Save the visible ppm range as a new document
+    ExtractRegion,
+}
+
+/// A library reference overlaid on the spectrum.  Name, scale, offset and
+/// visibility are saved in the project; the trace itself is reloaded from
+/// the library.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct OverlayTrace {
+    pub name: String,
+    /// Multiplies the trace, which is normalised to the spectrum's tallest point
+    pub scale: f64,
+    /// Vertical shift, as a fraction of the spectrum's tallest point
+    pub offset: f64,
+    pub visible: bool,
+    /// Normalised [ppm, intensity] trace
+    #[serde(skip)]
+    pub points: Vec<[f64; 2]>,
+}
+
+impl OverlayTrace {
+    pub fn new(name: String, points: Vec<[f64; 2]>) -> Self {
+        Self { name, scale: 1.0, offset: 0.0, visible: true, points }
+    }
+
+    /// Plot intensity of a normalised trace value, relative to the
+    /// spectrum's tallest point `height`
+    pub fn intensity(&self, value: f64, height: f64) -> f64 {
+        (value * self.scale + self.offset) * height
+    }
+
+    /// Legend text: name, scale factor and offset
+    pub fn legend_text(&self) -> String {
+        format!("{}  ×{:.2}  {:+.2}", self.name, self.scale, self.offset)
+    }
+}
+
+/// Another spectrum of the session drawn over the active one
+pub struct SpectrumOverlay<'a> {
+    pub name: String,
+    pub spectrum: &'a SpectrumData,
+    pub color: [u8; 3],
+    /// Multiplies the intensities
+    pub scale: f64,
+    /// Vertical shift, as a fraction of the active spectrum's tallest point
+    pub offset: f64,
+}
+
+/// Spectra drawn with the active one in the 1D view
+pub struct ComparedSpectra<'a> {
+    /// The spectrum before the last step, drawn when `show_before_after`
+    pub before: Option<&'a SpectrumData>,
+    pub show_before_after: bool,
+    /// Other spectra of the session
+    pub overlays: Vec<SpectrumOverlay<'a>>,
+}
+
+/// Annotations of one spectrum, kept aside while another spectrum of the
+/// session is active
+#[derive(Debug, Clone, Default)]
+pub struct Annotations {
+    peaks: Vec<[f64; 2]>,
+    multiplets: Vec<processing::Multiplet>,
+    integrations: Vec<(f64, f64, f64)>,
+    integration_reference_h: f64,
+    j_couplings: Vec<(f64, f64, f64, f64)>,
+    linewidths: Vec<processing::Linewidth>,
+    peak_fits: Vec<processing::PeakFit>,
+    baseline_points: Vec<[f64; 2]>,
+    solvent_regions: Vec<(f64, f64)>,
+    impurity_fit: Option<(String, Vec<[f64; 2]>)>,
+    exchangeable: Vec<f64>,
+    common_impurities: Vec<(f64, String)>,
+    carbon_types: Vec<processing::CarbonPeak>,
+    d2o_difference: Option<Vec<[f64; 2]>>,
+    predicted_sticks: Vec<Stick>,
+    suggested_assignments: Vec<Assignment>,
+}
+
+impl Annotations {
+    /// Picked peaks as `[ppm, intensity]`
+    pub fn peaks(&self) -> &[[f64; 2]] {
+        &self.peaks
+    }
+}
+
+/// Lorentz-to-Gauss preview over a picked region (never applied to the data)
+#[derive(Debug, Clone, Default)]
+pub struct ResolutionPreview {
+    /// Region picking mode: two clicks define the region
+    pub picking: bool,
+    pub start: Option<f64>,
+    /// (lo_ppm, hi_ppm)
+    pub region: Option<(f64, f64)>,
+    /// Enhanced trace in the region: [ppm, intensity]
+    pub points: Vec<[f64; 2]>,
+    /// Fingerprint of the data and parameters `points` was computed from
+    key: u64,
+}
+
+impl ResolutionPreview {
+    /// Recompute the preview trace if the spectrum, region or parameters
+    /// changed since the last frame.
+    pub fn update(&mut self, spectrum: &SpectrumData, lb_hz: f64, gb_hz: f64) {
+        let Some((lo, hi)) = self.region else {
+            self.points.clear();
+            return;
+        };
+        let key = [lb_hz, gb_hz, lo, hi]
+            .iter()
+            .chain(&spectrum.real)
+            .fold(spectrum.real.len() as u64, |h, v| h.rotate_left(7) ^ v.to_bits());
+        if key != self.key {
+            self.points = processing::lorentz_gauss_preview(spectrum, lb_hz, gb_hz, lo, hi).unwrap_or_default();
+            self.key = key;
+        }
+    }
+}
+
+/// Fitted baseline drawn over the spectrum before it is subtracted
+#[derive(Debug, Clone, Default)]
+pub struct BaselinePreview {
+    /// Baseline under the spectrum: [ppm, intensity]
+    pub points: Vec<[f64; 2]>,
+    /// Fingerprint of the data and model `points` was computed from
+    key: u64,
+}
+
+impl BaselinePreview {
+    /// Refit the baseline if the spectrum or model changed since the last
+    /// frame; `None` hides the preview.
+    pub fn update(&mut self, spectrum: &SpectrumData, method: Option<BaselineMethod>) {
+        let Some(method) = method.filter(|_| spectrum.is_frequency_domain && !spectrum.is_2d()) else {
+            self.points.clear();
+            self.key = 0;
+            return;
+        };
+        let params = match method {
+            BaselineMethod::Linear => [0.0, 0.0, 0.0],
+            BaselineMethod::Polynomial { order } => [1.0, order as f64, 0.0],
+            BaselineMethod::Als { lambda, p } => [2.0, lambda, p],
+        };
+        let key = params
+            .iter()
+            .chain(&spectrum.real)
+            .fold(spectrum.real.len() as u64, |h, v| h.rotate_left(7) ^ v.to_bits());
+        if key != self.key {
+            let baseline = processing::fit_baseline(&spectrum.real, &method);
+            self.points = spectrum.x_scale().into_iter().zip(baseline).map(|(x, y)| [x, y]).collect();
+            self.key = key;
+        }
+    }
+}
+
+/// Direction of the ppm axis in 1D views and exports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum AxisDirection {
+    /// High ppm on the left, the NMR convention
+    #[default]
+    Descending,
+    /// Low ppm on the left, increasing rightward
+    Ascending,
+}
+
+impl AxisDirection {
+    /// Factor turning ppm into the plot's x coordinate
+    pub fn sign(self) -> f64 {
+        match self {
+            AxisDirection::Descending => -1.0,
+            AxisDirection::Ascending => 1.0,
+        }
+    }
+
+    pub fn flipped(self) -> Self {
+        match self {
+            AxisDirection::Descending => AxisDirection::Ascending,
+            AxisDirection::Ascending => AxisDirection::Descending,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AxisDirection::Descending => "High → low ppm (NMR convention)",
+            AxisDirection::Ascending => "Low → high ppm",
+        }
+    }
+}
+
+/// Where integral values are written, in the view and in image exports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum IntegralLabels {
+    /// In a row under the ppm axis
+    #[default]
+    BelowAxis,
+    /// Over the tallest point of each region
+    AboveRegion,
+    /// At the top of each region's integral trace
+    OnTrace,
+}
+
+impl IntegralLabels {
+    pub const ALL: [IntegralLabels; 3] = [IntegralLabels::BelowAxis, IntegralLabels::AboveRegion, IntegralLabels::OnTrace];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            IntegralLabels::BelowAxis => "Below axis",
+            IntegralLabels::AboveRegion => "Above region",
+            IntegralLabels::OnTrace => "On integral trace",
+        }
+    }
+}
+
+/// Expansion of the last frequency-domain spectrum shown, carried over to
+/// the next spectrum opened while the zoom is linked
+#[derive(Debug, Clone)]
+pub struct LinkedView {
+    /// Only spectra of the same nucleus share a view
+    pub nucleus: Option<Nucleus>,
+    /// Plot bounds (x is ppm times the axis direction sign, y the scaled
+    /// intensity)
+    pub bounds: PlotBounds,
 }
 
 /// State for the spectrum viewer
@@ -25,11 +260,16 @@ pub struct SpectrumViewState {
     pub show_imaginary: bool,
     pub vertical_scale: f64,
     pub auto_scale: bool,
+    /// ppm window picked from the nucleus presets; replaces the default
+    /// window on auto-scale (reset on load)
+    pub range_preset: Option<(f64, f64)>,
     pub baseline_picking: bool,
     pub baseline_points: Vec<[f64; 2]>,
     /// Detected peaks: [ppm, intensity]
     pub peaks: Vec<[f64; 2]>,
     pub show_peaks: bool,
+    /// How peak intensities are shown in labels
+    pub intensity_mode: IntensityMode,
     /// Manual peak picking mode
     pub peak_picking: bool,
     /// Detected multiplets
@@ -42,12 +282,74 @@ pub struct SpectrumViewState {
     pub integration_start: Option<f64>,
     /// Number of H for the reference (first) integral — user-settable
     pub integration_reference_h: f64,
+    /// Local baseline subtracted from each integration region
+    pub integral_baseline: IntegralBaseline,
+    /// Placement of the integral values
+    pub integral_labels: IntegralLabels,
+    /// Draw the running integral over each region (always with labels
+    /// placed on it)
+    pub integral_trace: bool,
     /// J-coupling measurement: pick two peaks to measure the distance
     pub j_coupling_picking: bool,
     pub j_coupling_first: Option<f64>, // ppm of first clicked peak
+    /// Click a multiplet to extract all its couplings from the line spacings
+    pub auto_j_picking: bool,
     /// Measured J-coupling results: (ppm1, ppm2, delta_ppm, j_hz)
     pub j_couplings: Vec<(f64, f64, f64, f64)>,
     pub show_j_couplings: bool,
+    /// Linewidth (FWHM) measurement: click a peak, e.g. for shimming QC
+    pub linewidth_picking: bool,
+    pub linewidths: Vec<processing::Linewidth>,
+    pub show_linewidths: bool,
+    /// Peak fitting: two clicks define a region whose picked peaks are
+    /// fitted with `fit_shape`
+    pub fit_picking: bool,
+    pub fit_start: Option<f64>,
+    pub fit_shape: processing::LineShape,
+    pub peak_fits: Vec<processing::PeakFit>,
+    pub show_peak_fits: bool,
+    /// Resolution-enhancement preview overlay
+    pub enhance: ResolutionPreview,
+    /// Solvent suppression: drag across the spectrum to add a region
+    pub solvent_picking: bool,
+    /// Picked suppression regions: (low_ppm, high_ppm)
+    pub solvent_regions: Vec<(f64, f64)>,
+    /// Region being dragged: (start_ppm, current_ppm)
+    pub solvent_drag: Option<(f64, f64)>,
+    /// Fitted-baseline preview overlay
+    pub baseline_preview: BaselinePreview,
+    /// Library references overlaid on the spectrum, with their legend
+    /// settings
+    pub reference_overlays: Vec<OverlayTrace>,
+    /// Reference scaled onto an impurity signal: (name, [ppm, intensity])
+    pub impurity_fit: Option<(String, Vec<[f64; 2]>)>,
+    /// ppm of the peaks flagged as exchangeable (OH/NH) by a D₂O shake
+    pub exchangeable: Vec<f64>,
+    /// Peaks matching common impurities (Gottlieb table): (ppm, names)
+    pub common_impurities: Vec<(f64, String)>,
+    /// Carbon types of the peaks of a DEPT-135 / APT spectrum
+    pub carbon_types: Vec<processing::CarbonPeak>,
+    /// Before − after D₂O shake difference, as [ppm, intensity]
+    pub d2o_difference: Option<Vec<[f64; 2]>>,
+    /// Shade the functional-group regions of the spectrum's nucleus
+    pub show_shift_regions: bool,
+    /// Region definitions, from the preferences
+    pub shift_regions: Vec<ShiftRegion>,
+    /// Direction of the ppm axis, from the preferences or the project
+    pub axis_direction: AxisDirection,
+    /// Lines predicted by an external tool, drawn as a stick spectrum
+    pub predicted_sticks: Vec<Stick>,
+    /// Assignments suggested by an external tool, labelled below the axis
+    pub suggested_assignments: Vec<Assignment>,
+    /// Keep the ppm range and intensity scale when another spectrum is
+    /// opened, to compare related samples at the same expansion
+    pub link_zoom: bool,
+    pub linked_view: Option<LinkedView>,
+    /// ppm range (low, high) of the plot as last drawn, for actions
+    /// restricted to the current zoom
+    pub visible_ppm: Option<(f64, f64)>,
+    /// Set on load: show the next frequency-domain spectrum at `linked_view`
+    pub restore_linked_view: bool,
     /// Incremented on auto-scale to give the plot a fresh ID (resets zoom)
     pub plot_generation: u32,
     /// Pending actions from clicks, to be drained and logged by app.rs
@@ -60,10 +362,12 @@ impl Default for SpectrumViewState {
             show_imaginary: false,
             vertical_scale: 1.0,
             auto_scale: true,
+            range_preset: None,
             baseline_picking: false,
             baseline_points: Vec::new(),
             peaks: Vec::new(),
             show_peaks: true,
+            intensity_mode: IntensityMode::Absolute,
             peak_picking: false,
             multiplets: Vec::new(),
             show_multiplets: true,
@@ -72,34 +376,136 @@ impl Default for SpectrumViewState {
             integration_picking: false,
             integration_start: None,
             integration_reference_h: 1.0,
+            integral_baseline: IntegralBaseline::TwoPoint,
+            integral_labels: IntegralLabels::default(),
+            integral_trace: false,
             j_coupling_picking: false,
             j_coupling_first: None,
+            auto_j_picking: false,
             j_couplings: Vec::new(),
             show_j_couplings: true,
+            linewidth_picking: false,
+            linewidths: Vec::new(),
+            show_linewidths: true,
+            fit_picking: false,
+            fit_start: None,
+            fit_shape: processing::LineShape::default(),
+            peak_fits: Vec::new(),
+            show_peak_fits: true,
+            enhance: ResolutionPreview::default(),
+            solvent_picking: false,
+            solvent_regions: Vec::new(),
+            solvent_drag: None,
+            baseline_preview: BaselinePreview::default(),
+            reference_overlays: Vec::new(),
+            impurity_fit: None,
+            exchangeable: Vec::new(),
+            common_impurities: Vec::new(),
+            carbon_types: Vec::new(),
+            d2o_difference: None,
+            show_shift_regions: false,
+            shift_regions: ShiftRegion::standard(),
+            axis_direction: AxisDirection::Descending,
+            predicted_sticks: Vec::new(),
+            suggested_assignments: Vec::new(),
+            link_zoom: false,
+            linked_view: None,
+            visible_ppm: None,
+            restore_linked_view: false,
             plot_generation: 0,
             pending_actions: Vec::new(),
         }
     }
 }
 
-/// Default ppm display range for a given nucleus / experiment
-fn default_ppm_range(spectrum: &SpectrumData) -> Option<(f64, f64)> {
-    if !spectrum.is_frequency_domain {
-        return None;
+impl SpectrumViewState {
+    /// Whether the peak at `ppm` was flagged as exchangeable
+    pub fn is_exchangeable(&self, ppm: f64) -> bool {
+        self.exchangeable.iter().any(|&p| (p - ppm).abs() < 1e-9)
     }
-    let nuc = spectrum.axes.first().map(|a| &a.nucleus);
-    match nuc {
-        Some(Nucleus::H1) => Some((-1.0, 14.0)),
-        Some(Nucleus::C13) => Some((-10.0, 230.0)),
-        Some(Nucleus::F19) => Some((-230.0, 30.0)),
-        Some(Nucleus::P31) => Some((-50.0, 100.0)),
-        Some(Nucleus::N15) => Some((0.0, 350.0)),
-        _ => match &spectrum.experiment_type {
-            ExperimentType::Proton => Some((-1.0, 14.0)),
-            ExperimentType::Carbon | ExperimentType::Dept135 => Some((-10.0, 230.0)),
-            _ => None,
-        },
+
+    /// Move the annotations out, leaving the view without any
+    pub fn take_annotations(&mut self) -> Annotations {
+        self.integration_start = None;
+        self.j_coupling_first = None;
+        self.fit_start = None;
+        self.solvent_drag = None;
+        Annotations {
+            peaks: std::mem::take(&mut self.peaks),
+            multiplets: std::mem::take(&mut self.multiplets),
+            integrations: std::mem::take(&mut self.integrations),
+            integration_reference_h: std::mem::replace(&mut self.integration_reference_h, 1.0),
+            j_couplings: std::mem::take(&mut self.j_couplings),
+            linewidths: std::mem::take(&mut self.linewidths),
+            peak_fits: std::mem::take(&mut self.peak_fits),
+            baseline_points: std::mem::take(&mut self.baseline_points),
+            solvent_regions: std::mem::take(&mut self.solvent_regions),
+            impurity_fit: self.impurity_fit.take(),
+            exchangeable: std::mem::take(&mut self.exchangeable),
+            common_impurities: std::mem::take(&mut self.common_impurities),
+            carbon_types: std::mem::take(&mut self.carbon_types),
+            d2o_difference: self.d2o_difference.take(),
+            predicted_sticks: std::mem::take(&mut self.predicted_sticks),
+            suggested_assignments: std::mem::take(&mut self.suggested_assignments),
+        }
     }
+
+    /// Put back annotations taken with [`Self::take_annotations`]
+    pub fn restore_annotations(&mut self, annotations: Annotations) {
+        self.peaks = annotations.peaks;
+        self.multiplets = annotations.multiplets;
+        self.integrations = annotations.integrations;
+        self.integration_reference_h = annotations.integration_reference_h;
+        self.j_couplings = annotations.j_couplings;
+        self.linewidths = annotations.linewidths;
+        self.peak_fits = annotations.peak_fits;
+        self.baseline_points = annotations.baseline_points;
+        self.solvent_regions = annotations.solvent_regions;
+        self.impurity_fit = annotations.impurity_fit;
+        self.exchangeable = annotations.exchangeable;
+        self.common_impurities = annotations.common_impurities;
+        self.carbon_types = annotations.carbon_types;
+        self.d2o_difference = annotations.d2o_difference;
+        self.predicted_sticks = annotations.predicted_sticks;
+        self.suggested_assignments = annotations.suggested_assignments;
+    }
+
+    /// Impurities matched to the peak at `ppm`, if any
+    pub fn impurity_at(&self, ppm: f64) -> Option<&str> {
+        self.common_impurities.iter().find(|(p, _)| (p - ppm).abs() < 1e-9).map(|(_, name)| name.as_str())
+    }
+
+    /// Carbon type of the peak at `ppm`, if the peaks were classified
+    pub fn carbon_type_at(&self, ppm: f64) -> Option<processing::CarbonType> {
+        self.carbon_types.iter().find(|c| (c.ppm - ppm).abs() < 1e-9).map(|c| c.carbon)
+    }
+}
+
+/// Default ppm display range for a given nucleus / experiment, unless the
+/// user picked a preset
+fn default_ppm_range(spectrum: &SpectrumData, preset: Option<(f64, f64)>) -> Option<(f64, f64)> {
+    preset.or_else(|| spectrum.default_display_range())
+}
+
+/// Combo box of the nucleus' ppm presets; `Some(range)` when one is picked
+pub fn ppm_preset_combo(ui: &mut egui::Ui, id: &str, spectrum: &SpectrumData, current: Option<(f64, f64)>) -> Option<(f64, f64)> {
+    let nucleus = spectrum.display_nucleus()?;
+    let mut picked = None;
+    let selected = nucleus
+        .ppm_presets()
+        .find(|p| Some(p.range) == current)
+        .map_or("Preset…".to_string(), |p| p.name.to_string());
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(selected)
+        .show_ui(ui, |ui| {
+            for preset in nucleus.ppm_presets() {
+                let label = format!("{} {} ({} … {} ppm)", nucleus, preset.name, preset.range.1, preset.range.0);
+                if ui.selectable_label(Some(preset.range) == current, label).clicked() {
+                    picked = Some(preset.range);
+                }
+            }
+        });
+    picked
 }
 
 /// Whether to clip negative display values.
@@ -116,12 +522,13 @@ fn should_clip_negatives(_spectrum: &SpectrumData) -> bool {
 pub fn show_spectrum_1d(
     ui: &mut egui::Ui,
     spectrum: &SpectrumData,
-    before_spectrum: Option<&SpectrumData>,
+    compared: &ComparedSpectra<'_>,
     state: &mut SpectrumViewState,
-    show_before_after: bool,
     phase_state: &mut PhaseDialogState,
     colors: &super::theme::ThemeColors,
 ) {
+    let (before_spectrum, show_before_after, overlays) =
+        (compared.before, compared.show_before_after, compared.overlays.as_slice());
     if spectrum.real.is_empty() {
         ui.centered_and_justified(|ui| {
             ui.heading("No spectrum data loaded");
@@ -138,6 +545,33 @@ pub fn show_spectrum_1d(
         if ui.button("⊞ Auto Scale").clicked() {
             state.auto_scale = true;
         }
+        if spectrum.is_frequency_domain {
+            if let Some(range) = ppm_preset_combo(ui, "view_ppm_preset", spectrum, state.range_preset) {
+                state.range_preset = Some(range);
+                state.auto_scale = true;
+            }
+            ui.checkbox(&mut state.link_zoom, "🔗 Link zoom").on_hover_text(
+                "Open the next spectrum of the same nucleus at this ppm range and intensity scale",
+            );
+            ui.checkbox(&mut state.show_shift_regions, "Regions")
+                .on_hover_text("Shade the functional-group shift regions (edit them in Preferences)");
+            if ui
+                .button("⇄ ppm")
+                .on_hover_text(format!("Axis: {} — click to reverse", state.axis_direction.label()))
+                .clicked()
+            {
+                state.axis_direction = state.axis_direction.flipped();
+                state.linked_view = None;
+                state.auto_scale = true;
+            }
+            if ui
+                .button("✂ Extract")
+                .on_hover_text("Save the zoomed ppm range as a new project and open it in its own window")
+                .clicked()
+            {
+                state.pending_actions.push(SpectrumAction::ExtractRegion);
+            }
+        }
         ui.separator();
         ui.label(format!(
             "{} | {} pts | {}",
@@ -145,6 +579,10 @@ pub fn show_spectrum_1d(
             spectrum.real.len(),
             if spectrum.is_frequency_domain { "Freq" } else { "Time" }
         ));
+        if let Some(text) = acquisition_label(spectrum) {
+            ui.separator();
+            ui.label(text).on_hover_text("Dwell time = 1 / spectral width; AQ = points × dwell time");
+        }
         if !state.peaks.is_empty() {
             ui.separator();
             ui.checkbox(&mut state.show_peaks, &format!("📍 {} peaks", state.peaks.len()));
@@ -159,6 +597,20 @@ pub fn show_spectrum_1d(
                 &mut state.show_integrations,
                 &format!("∫ {} regions", state.integrations.len()),
             );
+            ui.add_enabled_ui(state.show_integrations, |ui| {
+                egui::ComboBox::from_id_salt("integral_labels")
+                    .selected_text(state.integral_labels.label())
+                    .width(120.0)
+                    .show_ui(ui, |ui| {
+                        for placement in IntegralLabels::ALL {
+                            ui.selectable_value(&mut state.integral_labels, placement, placement.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Where integral values are written; image exports follow");
+                ui.checkbox(&mut state.integral_trace, "Trace")
+                    .on_hover_text("Draw the running integral over each region");
+            });
         }
         if state.peak_picking {
             ui.separator();
@@ -185,6 +637,39 @@ pub fn show_spectrum_1d(
             };
             ui.colored_label(egui::Color32::from_rgb(0xCC, 0x66, 0x00), msg);
         }
+        if state.auto_j_picking {
+            ui.separator();
+            ui.colored_label(egui::Color32::from_rgb(0xCC, 0x66, 0x00), "🎵 Click a multiplet…");
+        }
+        if state.linewidth_picking {
+            ui.separator();
+            ui.colored_label(egui::Color32::from_rgb(0x00, 0x80, 0x80), "↔ Click a peak…");
+        }
+        if state.fit_picking {
+            ui.separator();
+            let msg = if state.fit_start.is_some() {
+                "〰 Click end of fit region…"
+            } else {
+                "〰 Click start of fit region…"
+            };
+            ui.colored_label(egui::Color32::from_rgb(0xD3, 0x54, 0x00), msg);
+        }
+        if state.solvent_picking {
+            ui.separator();
+            ui.colored_label(
+                egui::Color32::from_rgb(0x1E, 0x88, 0xE5),
+                format!("🧪 Drag across a solvent signal… ({} regions)", state.solvent_regions.len()),
+            );
+        }
+        if state.enhance.picking {
+            ui.separator();
+            let msg = if state.enhance.start.is_some() {
+                "🔬 Click end of preview region…"
+            } else {
+                "🔬 Click start of preview region…"
+            };
+            ui.colored_label(egui::Color32::from_rgb(0xE0, 0x7B, 0x00), msg);
+        }
         if !state.j_couplings.is_empty() {
             ui.separator();
             ui.checkbox(
@@ -192,6 +677,13 @@ pub fn show_spectrum_1d(
                 &format!("📏 {} J", state.j_couplings.len()),
             );
         }
+        if !state.linewidths.is_empty() {
+            ui.separator();
+            ui.checkbox(
+                &mut state.show_linewidths,
+                format!("↔ {} FWHM", state.linewidths.len()),
+            );
+        }
         if is_phasing {
             ui.separator();
             ui.colored_label(
@@ -201,29 +693,35 @@ pub fn show_spectrum_1d(
         }
     });
 
-    // Build ppm/point scale
-    let raw_ppm = if spectrum.is_frequency_domain && !spectrum.axes.is_empty() {
-        spectrum.axes[0].ppm_scale()
-    } else {
-        (0..spectrum.real.len())
-            .map(|i| i as f64)
-            .collect::<Vec<_>>()
-    };
+    // Legend of the overlaid references: visibility, scale and offset
+    if spectrum.is_frequency_domain && !is_phasing && !state.reference_overlays.is_empty() {
+        ui.horizontal_wrapped(|ui| {
+            for (i, trace) in state.reference_overlays.iter_mut().enumerate() {
+                let [r, g, b] = plot_scene::overlay_color(i);
+                ui.checkbox(&mut trace.visible, "")
+                    .on_hover_text("Show this reference");
+                ui.colored_label(egui::Color32::from_rgb(r, g, b), "━━");
+                ui.label(&trace.name);
+                ui.add(egui::DragValue::new(&mut trace.scale).speed(0.01).range(0.0..=100.0).prefix("×"))
+                    .on_hover_text("Scale factor");
+                ui.add(egui::DragValue::new(&mut trace.offset).speed(0.01).range(-10.0..=10.0).prefix("offset "))
+                    .on_hover_text("Vertical offset, as a fraction of the spectrum's tallest point");
+                ui.separator();
+            }
+        });
+    }
+
+    // Build ppm/time scale
+    let raw_ppm = spectrum.x_scale();
 
     // For frequency domain: negate ppm so high ppm appears on left in the plot
-    // (egui_plot puts lower x on the left; negating flips the axis)
+    // (egui_plot puts lower x on the left; negating flips the axis), unless
+    // the user asked for ppm increasing to the right
     let is_freq = spectrum.is_frequency_domain;
-    let ppm_scale: Vec<f64> = if is_freq {
-        raw_ppm.iter().map(|&x| -x).collect()
-    } else {
-        raw_ppm.clone()
-    };
+    let flip = if is_freq { state.axis_direction.sign() } else { 1.0 };
+    let ppm_scale: Vec<f64> = raw_ppm.iter().map(|&x| flip * x).collect();
 
-    let x_label = if is_freq {
-        "Chemical Shift (ppm)"
-    } else {
-        "Point"
-    };
+    let x_label = spectrum.x_axis_label();
 
     // Select which data to plot as the primary line
     let primary_data = if is_phasing && !phase_state.preview.is_empty() {
@@ -249,17 +747,48 @@ pub fn show_spectrum_1d(
     } else {
         colors.spectrum_line
     };
+    // With other spectra overlaid, the legend tells the active one apart
+    let real_name = if is_phasing {
+        "Phased Preview".to_string()
+    } else if !overlays.is_empty() && !spectrum.sample_name.is_empty() {
+        format!("{} (active)", spectrum.sample_name)
+    } else {
+        "Real".to_string()
+    };
     let real_line = Line::new(real_points)
-        .name(if is_phasing { "Phased Preview" } else { "Real" })
+        .name(real_name)
         .color(line_color)
         .width(1.2);
 
+    // A newly opened spectrum takes over the expansion of the previous one
+    let mut linked_bounds = None;
+    if state.restore_linked_view && is_freq {
+        state.restore_linked_view = false;
+        linked_bounds = state
+            .linked_view
+            .as_ref()
+            .filter(|v| state.link_zoom && v.nucleus == spectrum.display_nucleus())
+            .map(|v| v.bounds);
+        if linked_bounds.is_some() {
+            state.auto_scale = true;
+        }
+    }
+
     // Bump generation to reset internal plot view state on auto-scale
     if state.auto_scale {
         state.plot_generation = state.plot_generation.wrapping_add(1);
     }
 
-    let no_interact = is_phasing || state.baseline_picking || state.integration_picking || state.j_coupling_picking || state.peak_picking;
+    let no_interact = is_phasing
+        || state.baseline_picking
+        || state.integration_picking
+        || state.j_coupling_picking
+        || state.auto_j_picking
+        || state.linewidth_picking
+        || state.fit_picking
+        || state.peak_picking
+        || state.enhance.picking
+        || state.solvent_picking;
 
     // X-axis: NMR convention — high ppm on left, low ppm on right
     let mut plot = Plot::new(format!("spectrum_1d_{}", state.plot_generation))
@@ -275,20 +804,19 @@ pub fn show_spectrum_1d(
         .legend(egui_plot::Legend::default().position(egui_plot::Corner::RightTop)
             .background_alpha(0.6));
 
-    // Format x labels as positive ppm (we negated the values for flipping)
+    // Format x labels as ppm (undoing the flip)
     if is_freq {
-        plot = plot.x_axis_formatter(|val, _range| {
-            format!("{:.1}", -val.value)
+        plot = plot.x_axis_formatter(move |val, range| {
+            plot_scene::format_ppm(flip * val.value, range.end() - range.start())
         });
     }
 
     // Set default bounds on first display (auto_scale)
     if state.auto_scale && is_freq {
-        if let Some((lo, hi)) = default_ppm_range(spectrum) {
-            // Negate: high ppm → more-negative x, low ppm → less-negative x
+        if let Some((lo, hi)) = default_ppm_range(spectrum, state.range_preset) {
             plot = plot
-                .include_x(-lo)
-                .include_x(-hi);
+                .include_x(flip * lo)
+                .include_x(flip * hi);
         }
     }
     // When clipping negatives, anchor the y-axis at 0 and show
@@ -303,16 +831,87 @@ pub fn show_spectrum_1d(
     let is_picking_bl = state.baseline_picking;
     let peaks_clone = state.peaks.clone();
     let show_peaks_flag = state.show_peaks;
+    let intensity_mode = state.intensity_mode;
+    let peak_values = processing::normalized_intensities(
+        &state.peaks,
+        intensity_mode,
+        state.integrations.first().map(|r| r.2),
+        state.integration_reference_h,
+    );
     let integrations_clone = state.integrations.clone();
     let show_integrations_flag = state.show_integrations;
+    let integral_labels = state.integral_labels;
+    let show_integral_trace = state.integral_trace || integral_labels == IntegralLabels::OnTrace;
+    let integral_baseline = state.integral_baseline;
     let multiplets_clone = state.multiplets.clone();
     let show_multiplets_flag = state.show_multiplets;
     let j_couplings_clone = state.j_couplings.clone();
     let show_j_couplings_flag = state.show_j_couplings;
+    let linewidths_clone = state.linewidths.clone();
+    let show_linewidths_flag = state.show_linewidths;
+    let peak_fits = if state.show_peak_fits { state.peak_fits.as_slice() } else { &[] };
     let vert_scale = state.vertical_scale;
     let ref_h = state.integration_reference_h;
+    let integral_errors = if show_integrations_flag && !integrations_clone.is_empty() {
+        processing::integral_uncertainties(spectrum, &integrations_clone, state.integral_baseline, ref_h)
+    } else {
+        Vec::new()
+    };
+    let enhance_region = state.enhance.region;
+    // Stored regions plus the one being dragged, for the suppression preview
+    let solvent_regions: Vec<(f64, f64)> = state
+        .solvent_regions
+        .iter()
+        .copied()
+        .chain(state.solvent_drag.map(|(a, b)| (a.min(b), a.max(b))))
+        .collect();
+    let enhance_points = &state.enhance.points;
+    let baseline_preview = &state.baseline_preview.points;
+    let reference_overlays = &state.reference_overlays;
+    let impurity_fit = &state.impurity_fit;
+    let d2o_difference = &state.d2o_difference;
+    let exchangeable = &state.exchangeable;
+    let common_impurities = &state.common_impurities;
+    let carbon_types = &state.carbon_types;
+    let predicted_sticks = &state.predicted_sticks;
+    let shift_regions: Vec<&ShiftRegion> = match spectrum.display_nucleus() {
+        Some(nucleus) if is_freq && state.show_shift_regions => {
+            state.shift_regions.iter().filter(|r| r.applies_to(&nucleus)).collect()
+        }
+        _ => Vec::new(),
+    };
+    let suggested_assignments = &state.suggested_assignments;
+    let reference_height = spectrum.real.iter().fold(0.0f64, |m, v| m.max(v.abs())) * vert_scale;
 
     let plot_resp = plot.show(ui, |plot_ui: &mut PlotUi| {
+        if let Some(bounds) = linked_bounds {
+            plot_ui.set_plot_bounds(bounds);
+        }
+
+        // ── Functional-group regions, behind everything else ──
+        if !shift_regions.is_empty() {
+            let y_lo = primary_data.iter().fold(0.0f64, |m, v| m.min(*v)) * vert_scale;
+            let y_hi = reference_height * 1.05;
+            for (i, region) in shift_regions.iter().enumerate() {
+                let (x0, x1) = (flip * region.range.0, flip * region.range.1);
+                let [r, g, b] = plot_scene::region_color(i);
+                let fill = egui::Color32::from_rgba_unmultiplied(r, g, b, 28);
+                plot_ui.polygon(
+                    Polygon::new(PlotPoints::from(vec![[x0, y_lo], [x1, y_lo], [x1, y_hi], [x0, y_hi]]))
+                        .fill_color(fill)
+                        .stroke(egui::Stroke::new(0.0, fill))
+                        .allow_hover(false),
+                );
+                plot_ui.text(
+                    Text::new(
+                        [(x0 + x1) / 2.0, y_hi].into(),
+                        egui::RichText::new(&region.name).size(10.0).color(egui::Color32::from_rgb(r, g, b)),
+                    )
+                    .anchor(egui::Align2::CENTER_BOTTOM),
+                );
+            }
+        }
+
         // When phasing, show original spectrum as faded background
         if is_phasing {
             let orig_points: PlotPoints = ppm_scale
@@ -346,15 +945,9 @@ pub fn show_spectrum_1d(
         // Before spectrum overlay (faded) — only when not phasing
         if show_before_after && !is_phasing {
             if let Some(before) = before_spectrum {
-                let before_ppm_raw = if before.is_frequency_domain && !before.axes.is_empty() {
-                    before.axes[0].ppm_scale()
-                } else {
-                    (0..before.real.len())
-                        .map(|i| i as f64)
-                        .collect::<Vec<_>>()
-                };
+                let before_ppm_raw = before.x_scale();
                 let before_ppm: Vec<f64> = if before.is_frequency_domain {
-                    before_ppm_raw.iter().map(|&x| -x).collect()
+                    before_ppm_raw.iter().map(|&x| state.axis_direction.sign() * x).collect()
                 } else {
                     before_ppm_raw
                 };
@@ -371,6 +964,183 @@ pub fn show_spectrum_1d(
             }
         }
 
+        // ── Other spectra of the session, in their own colors ──
+        if is_freq && !is_phasing {
+            let height = spectrum.real.iter().fold(0.0f64, |m, v| m.max(v.abs()));
+            for overlay in overlays {
+                let pts: PlotPoints = overlay
+                    .spectrum
+                    .x_scale()
+                    .iter()
+                    .zip(&overlay.spectrum.real)
+                    .map(|(&x, &y)| [flip * x, (y * overlay.scale + overlay.offset * height) * vert_scale])
+                    .collect();
+                let [r, g, b] = overlay.color;
+                plot_ui.line(Line::new(pts).name(&overlay.name).color(egui::Color32::from_rgb(r, g, b)).width(1.0));
+            }
+        }
+
+        // ── Library reference overlays ──
+        if is_freq && !is_phasing {
+            for (i, trace) in reference_overlays.iter().enumerate().filter(|(_, t)| t.visible) {
+                let pts: PlotPoints = trace
+                    .points
+                    .iter()
+                    .map(|p| [flip * p[0], trace.intensity(p[1], reference_height)])
+                    .collect();
+                let [r, g, b] = plot_scene::overlay_color(i);
+                plot_ui.line(
+                    Line::new(pts)
+                        .name(format!("Ref: {}", trace.name))
+                        .color(egui::Color32::from_rgb(r, g, b).gamma_multiply(0.8))
+                        .width(1.0),
+                );
+            }
+            if let Some((name, points)) = impurity_fit {
+                let pts: PlotPoints = points.iter().map(|p| [flip * p[0], p[1] * vert_scale]).collect();
+                plot_ui.line(
+                    Line::new(pts)
+                        .name(format!("Fit: {}", name))
+                        .color(egui::Color32::from_rgb(0xC8, 0x3A, 0x3A))
+                        .width(1.2)
+                        .style(egui_plot::LineStyle::dashed_dense()),
+                );
+            }
+            // Fitted lines dotted, their sum solid
+            let fit_color = egui::Color32::from_rgb(0xD3, 0x54, 0x00);
+            for fit in peak_fits {
+                for component in &fit.components {
+                    let pts: PlotPoints = component.iter().map(|p| [flip * p[0], p[1] * vert_scale]).collect();
+                    plot_ui.line(
+                        Line::new(pts)
+                            .color(fit_color.gamma_multiply(0.7))
+                            .width(1.0)
+                            .style(egui_plot::LineStyle::dotted_dense()),
+                    );
+                }
+                let pts: PlotPoints = fit.trace.iter().map(|p| [flip * p[0], p[1] * vert_scale]).collect();
+                plot_ui.line(Line::new(pts).name(format!("Fit: {}", fit.shape)).color(fit_color).width(1.5));
+            }
+            if let Some(points) = d2o_difference {
+                let pts: PlotPoints = points.iter().map(|p| [flip * p[0], p[1] * vert_scale]).collect();
+                plot_ui.line(
+                    Line::new(pts)
+                        .name("D₂O difference")
+                        .color(egui::Color32::from_rgb(0x2E, 0x86, 0xC1))
+                        .width(1.0)
+                        .style(egui_plot::LineStyle::dashed_dense()),
+                );
+            }
+        }
+
+        // ── External tool results ──
+        if is_freq && !is_phasing {
+            let stick_color = egui::Color32::from_rgb(0x8E, 0x5A, 0xD8);
+            let tallest = predicted_sticks.iter().fold(0.0f64, |m, s| m.max(s.intensity.abs())).max(1e-30);
+            for stick in predicted_sticks {
+                let top = stick.intensity / tallest * reference_height;
+                plot_ui.line(
+                    Line::new(PlotPoints::from(vec![[flip * stick.ppm, 0.0], [flip * stick.ppm, top]]))
+                        .name("Predicted")
+                        .color(stick_color)
+                        .width(1.5),
+                );
+                if !stick.label.is_empty() {
+                    plot_ui.text(
+                        Text::new(
+                            [flip * stick.ppm, top].into(),
+                            egui::RichText::new(&stick.label).size(9.0).color(stick_color),
+                        )
+                        .anchor(egui::Align2::CENTER_BOTTOM),
+                    );
+                }
+            }
+            for assignment in suggested_assignments {
+                plot_ui.text(
+                    Text::new(
+                        [flip * assignment.ppm, -reference_height * 0.14].into(),
+                        egui::RichText::new(format!("▲ {}", assignment.label)).size(10.0).color(stick_color),
+                    )
+                    .anchor(egui::Align2::CENTER_TOP),
+                );
+            }
+        }
+
+        // ── Resolution-enhancement preview (dashed, region only) ──
+        if let Some((lo, hi)) = enhance_region {
+            let preview_color = egui::Color32::from_rgb(0xE0, 0x7B, 0x00);
+            for edge in [lo, hi] {
+                plot_ui.vline(
+                    VLine::new(flip * edge)
+                        .color(egui::Color32::from_rgba_premultiplied(0xE0, 0x7B, 0x00, 60)),
+                );
+            }
+            if !enhance_points.is_empty() {
+                let pts: PlotPoints = enhance_points
+                    .iter()
+                    .map(|p| [flip * p[0], p[1] * vert_scale])
+                    .collect();
+                plot_ui.line(
+                    Line::new(pts)
+                        .name("LG preview")
+                        .color(preview_color)
+                        .width(1.2)
+                        .style(egui_plot::LineStyle::dashed_dense()),
+                );
+            }
+        }
+
+        // ── Fitted baseline preview (dashed) ──
+        if !baseline_preview.is_empty() && !is_phasing {
+            let pts: PlotPoints = baseline_preview
+                .iter()
+                .map(|p| [flip * p[0], p[1] * vert_scale])
+                .collect();
+            plot_ui.line(
+                Line::new(pts)
+                    .name("Baseline preview")
+                    .color(egui::Color32::from_rgb(0xD3, 0x2F, 0x2F))
+                    .width(1.2)
+                    .style(egui_plot::LineStyle::dashed_loose()),
+            );
+        }
+
+        // ── Solvent suppression regions: band plus the signal to be removed ──
+        if is_freq && !solvent_regions.is_empty() {
+            let y_lo = primary_data.iter().fold(0.0f64, |m, v| m.min(*v)) * vert_scale;
+            let y_hi = reference_height * 1.05;
+            let band = egui::Color32::from_rgba_unmultiplied(0x1E, 0x88, 0xE5, 24);
+            let removed = egui::Color32::from_rgba_unmultiplied(0xE5, 0x39, 0x35, 90);
+            for &(lo, hi) in &solvent_regions {
+                let (x0, x1) = (flip * lo, flip * hi);
+                plot_ui.polygon(
+                    Polygon::new(PlotPoints::from(vec![[x0, y_lo], [x1, y_lo], [x1, y_hi], [x0, y_hi]]))
+                        .fill_color(band)
+                        .stroke(egui::Stroke::new(1.0, band))
+                        .allow_hover(false),
+                );
+                let (center, width) = ((lo + hi) / 2.0, hi - lo);
+                let removed_pts: Vec<[f64; 2]> = ppm_scale
+                    .iter()
+                    .zip(primary_data.iter())
+                    .filter(|(&x, _)| (lo..=hi).contains(&(flip * x)))
+                    .map(|(&x, &y)| {
+                        let kept = processing::solvent_suppression_factor(flip * x, center, width);
+                        [x, y * (1.0 - kept) * vert_scale]
+                    })
+                    .collect();
+                if !removed_pts.is_empty() {
+                    plot_ui.line(
+                        Line::new(PlotPoints::from(removed_pts))
+                            .color(removed)
+                            .fill(0.0)
+                            .width(0.0)
+                            .allow_hover(false),
+                    );
+                }
+            }
+        }
+
         // ── Integration regions ──
         if show_integrations_flag && !integrations_clone.is_empty() {
             let fill_colors = [
@@ -394,6 +1164,8 @@ pub fn show_spectrum_1d(
                 .abs()
                 .max(1e-12);
 
+            // (display x, top of the region, value, color) of each label
+            let mut labels: Vec<(f64, f64, String, egui::Color32)> = Vec::with_capacity(integrations_clone.len());
             for (idx, &(start_ppm, end_ppm, raw_val)) in integrations_clone.iter().enumerate() {
                 let c = idx % fill_colors.len();
                 let lo = start_ppm.min(end_ppm);
@@ -404,7 +1176,7 @@ pub fn show_spectrum_1d(
                     .iter()
                     .zip(primary_data.iter())
                     .filter(|(&x, _)| {
-                        let real_ppm = if is_freq { -x } else { x };
+                        let real_ppm = flip * x;
                         real_ppm >= lo && real_ppm <= hi
                     })
                     .map(|(&x, &y)| {
@@ -423,8 +1195,8 @@ pub fn show_spectrum_1d(
                 }
 
                 // Boundary dashed lines
-                let disp_lo = if is_freq { -hi } else { lo };
-                let disp_hi = if is_freq { -lo } else { hi };
+                let disp_lo = flip * hi;
+                let disp_hi = flip * lo;
                 plot_ui.vline(
                     VLine::new(disp_lo)
                         .color(border_colors[c])
@@ -436,25 +1208,84 @@ pub fn show_spectrum_1d(
                         .style(egui_plot::LineStyle::dashed_dense()),
                 );
 
-                // Integral value label centered in region
-                let mid_ppm = (lo + hi) / 2.0;
-                let disp_mid = if is_freq { -mid_ppm } else { mid_ppm };
+                // Integral value, placed once all regions are known
                 let max_y_in_region = primary_data
                     .iter()
                     .zip(ppm_scale.iter())
                     .filter(|(_, &x)| {
-                        let rp = if is_freq { -x } else { x };
+                        let rp = flip * x;
                         rp >= lo && rp <= hi
                     })
-                    .map(|(&y, _)| y * vert_scale)
+                    .map(|(&y, _)| {
+                        let ys = y * vert_scale;
+                        if clip_neg { ys.max(0.0) } else { ys }
+                    })
                     .fold(0.0f64, f64::max);
-                let label_y = max_y_in_region * 1.08;
                 let rel_val = (raw_val / first_raw) * ref_h;
+                let text = processing::format_h_count(rel_val, integral_errors.get(idx).map_or(0.0, |u| u.h_sigma));
+                labels.push((flip * (lo + hi) / 2.0, max_y_in_region, text, border_colors[c]));
+            }
+
+            // Running integrals, the largest at a fixed share of the tallest
+            // point, as in image exports
+            let traces: Vec<Vec<[f64; 2]>> = if show_integral_trace {
+                integrations_clone
+                    .iter()
+                    .map(|&(start_ppm, end_ppm, _)| processing::integral_trace(spectrum, start_ppm, end_ppm, integral_baseline))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            let tallest = spectrum.real.iter().fold(0.0f64, |m, v| m.max(v.abs()));
+            let trace_scale = plot_scene::integral_trace_scale(&traces, tallest) * vert_scale;
+            let mut trace_tops = Vec::with_capacity(traces.len());
+            for (idx, trace) in traces.iter().enumerate() {
+                let pts: Vec<[f64; 2]> = trace.iter().map(|p| [flip * p[0], p[1] * trace_scale]).collect();
+                trace_tops.push(pts.iter().fold(0.0f64, |m, p| m.max(p[1])));
+                plot_ui.line(
+                    Line::new(PlotPoints::from(pts))
+                        .color(border_colors[idx % border_colors.len()])
+                        .width(1.2),
+                );
+            }
+
+            // Stack the values so that neighbours do not overlap, measuring
+            // them in plot units at the current zoom
+            let [dx, dy] = plot_ui.transform().dvalue_dpos();
+            let (dx, dy) = (dx.abs(), dy.abs());
+            let font = egui::FontId::proportional(11.0);
+            let y_floor = primary_data.iter().fold(0.0f64, |m, v| m.min(*v)) * vert_scale;
+            let slots: Vec<plot_scene::LabelSlot> = labels
+                .iter()
+                .enumerate()
+                .map(|(idx, (x, region_top, text, color))| {
+                    let size = plot_ui.ctx().fonts(|f| f.layout_no_wrap(text.clone(), font.clone(), *color).size());
+                    let (w, h) = ((size.x as f64 + 8.0) * dx, size.y as f64 * dy);
+                    let bottom = match integral_labels {
+                        IntegralLabels::BelowAxis => (if clip_neg { 0.0 } else { y_floor }) - 4.0 * dy - h,
+                        IntegralLabels::AboveRegion => region_top + 4.0 * dy,
+                        IntegralLabels::OnTrace => trace_tops.get(idx).copied().unwrap_or(*region_top) + 4.0 * dy,
+                    };
+                    plot_scene::LabelSlot { x: (x - w / 2.0, x + w / 2.0), y: (bottom, bottom + h) }
+                })
+                .collect();
+            let row = slots.first().map_or(0.0, |s| (s.y.1 - s.y.0) * f64::from(plot_scene::LINE_HEIGHT));
+            let step = if integral_labels == IntegralLabels::BelowAxis { -row } else { row };
+            let rows = plot_scene::stack_labels(&slots, step);
+            // Rows under the axis stay inside the plot when it is zoomed
+            // in vertically, as they stay in the margin of an export
+            let bounds = plot_ui.plot_bounds();
+            let lowest = rows.iter().copied().max().unwrap_or(0) as f64 * row;
+            let clamp = |bottom: f64, h: f64| {
+                if integral_labels != IntegralLabels::BelowAxis {
+                    return bottom;
+                }
+                bottom.max(bounds.min()[1] + 2.0 * dy + lowest).min(bounds.max()[1] - 2.0 * dy - h)
+            };
+            for ((slot, k), (x, _, text, color)) in slots.iter().zip(rows).zip(labels) {
                 let label = Text::new(
-                    [disp_mid, label_y].into(),
-                    egui::RichText::new(format!("{:.2}H", rel_val))
-                        .size(11.0)
-                        .color(border_colors[c]),
+                    [x, clamp(slot.y.0, slot.y.1 - slot.y.0) + k as f64 * step].into(),
+                    egui::RichText::new(text).size(11.0).color(color),
                 )
                 .anchor(egui::Align2::CENTER_BOTTOM);
                 plot_ui.text(label);
@@ -462,41 +1293,54 @@ pub fn show_spectrum_1d(
         }
 
         // ── Peak markers and labels ──
+        // Positive peaks get a ▼ above them, negative ones a ▲ below
         if show_peaks_flag && !peaks_clone.is_empty() {
-            let peak_pts: PlotPoints = peaks_clone
-                .iter()
-                .map(|p| {
-                    let x = if is_freq { -p[0] } else { p[0] };
-                    let y = if clip_neg {
-                        (p[1] * vert_scale).max(0.0)
-                    } else {
-                        p[1] * vert_scale
-                    };
-                    [x, y]
-                })
-                .collect();
-            let markers = Points::new(peak_pts)
-                .name("Peaks")
-                .color(colors.peak_marker)
-                .radius(2.5)
-                .shape(egui_plot::MarkerShape::Down);
-            plot_ui.points(markers);
+            let peak_y = |p: &[f64; 2]| if clip_neg { (p[1] * vert_scale).max(0.0) } else { p[1] * vert_scale };
+            for negative in [false, true] {
+                let pts: PlotPoints = peaks_clone
+                    .iter()
+                    .filter(|p| (p[1] < 0.0) == negative)
+                    .map(|p| [flip * p[0], peak_y(p)])
+                    .collect();
+                if pts.points().is_empty() {
+                    continue;
+                }
+                let markers = Points::new(pts)
+                    .name("Peaks")
+                    .color(colors.peak_marker)
+                    .radius(2.5)
+                    .shape(if negative { egui_plot::MarkerShape::Up } else { egui_plot::MarkerShape::Down });
+                plot_ui.points(markers);
+            }
 
-            // Peak ppm labels above each marker
-            for peak in &peaks_clone {
-                let x = if is_freq { -peak[0] } else { peak[0] };
-                let y = if clip_neg {
-                    (peak[1] * vert_scale).max(0.0)
-                } else {
-                    peak[1] * vert_scale
+            // Peak ppm labels beyond each marker (plus normalized intensity)
+            for (peak, value) in peaks_clone.iter().zip(&peak_values) {
+                let x = flip * peak[0];
+                let y = peak_y(peak);
+                let anchor = if y < 0.0 { egui::Align2::CENTER_TOP } else { egui::Align2::CENTER_BOTTOM };
+                let mut text = match intensity_mode {
+                    IntensityMode::Absolute => format!("{:.2}", peak[0]),
+                    _ => format!("{:.2}\n({:.1})", peak[0], value),
                 };
+                if exchangeable.iter().any(|&p| (p - peak[0]).abs() < 1e-9) {
+                    text.push_str("\nOH/NH");
+                }
+                if let Some(c) = carbon_types.iter().find(|c| (c.ppm - peak[0]).abs() < 1e-9) {
+                    text.push_str(&format!("\n{}", c.carbon));
+                }
+                // Solvent and impurity peaks are labelled, and greyed out
+                let impurity = common_impurities.iter().find(|(p, _)| (p - peak[0]).abs() < 1e-9);
+                if let Some((_, name)) = impurity {
+                    text.push('\n');
+                    text.push_str(name);
+                }
                 let label = Text::new(
                     [x, y * 1.06].into(),
-                    egui::RichText::new(format!("{:.2}", peak[0]))
-                        .size(9.0)
-                        .color(colors.peak_label),
+                    egui::RichText::new(text)
+                    .size(9.0)
+                    .color(if impurity.is_some() { colors.text_muted } else { colors.peak_label }),
                 )
-                .anchor(egui::Align2::CENTER_BOTTOM);
+                .anchor(anchor);
                 plot_ui.text(label);
             }
         }
@@ -512,13 +1356,12 @@ pub fn show_spectrum_1d(
             let label_base_y = -global_max * 0.06; // below the x-axis baseline
 
             for mult in &multiplets_clone {
-                let cx = if is_freq { -mult.center_ppm } else { mult.center_ppm };
+                let cx = flip * mult.center_ppm;
 
-                // Build label text: "d" or "t, J=7.2"
-                let lbl = if mult.j_hz > 0.5 {
-                    format!("{}, J={:.1}", mult.label, mult.j_hz)
-                } else {
-                    mult.label.clone()
+                // Build label text: "d", "t, J=7.2" or "dd, J=10.2, 4.1"
+                let lbl = match mult.j_text() {
+                    Some(j) => format!("{}, J={}", mult.label, j),
+                    None => mult.label.clone(),
                 };
 
                 let label = Text::new(
@@ -534,8 +1377,8 @@ pub fn show_spectrum_1d(
                 if mult.peaks.len() >= 2 {
                     let lo_ppm = mult.peaks.first().unwrap()[0];
                     let hi_ppm = mult.peaks.last().unwrap()[0];
-                    let x1 = if is_freq { -hi_ppm } else { lo_ppm };
-                    let x2 = if is_freq { -lo_ppm } else { hi_ppm };
+                    let x1 = flip * hi_ppm;
+                    let x2 = flip * lo_ppm;
                     let bracket = Line::new(PlotPoints::from(vec![
                         [x1, label_base_y * 0.8],
                         [x1, label_base_y * 0.6],
@@ -552,8 +1395,8 @@ pub fn show_spectrum_1d(
         // ── J-coupling measurement lines ──
         if show_j_couplings_flag && !j_couplings_clone.is_empty() {
             for &(ppm1, ppm2, _delta_ppm, j_hz) in &j_couplings_clone {
-                let x1 = if is_freq { -ppm1 } else { ppm1 };
-                let x2 = if is_freq { -ppm2 } else { ppm2 };
+                let x1 = flip * ppm1;
+                let x2 = flip * ppm2;
 
                 // Find the intensity at each ppm to position the line
                 let y1 = find_intensity_at_ppm(primary_data, &ppm_scale, x1) * vert_scale;
@@ -607,11 +1450,39 @@ pub fn show_spectrum_1d(
             }
         }
 
+        // ── Linewidth measurements: a bar across the peak at half height ──
+        if show_linewidths_flag && !is_phasing {
+            for lw in &linewidths_clone {
+                let (x1, x2) = (flip * lw.hi_ppm, flip * lw.lo_ppm);
+                let y = lw.half_height() * vert_scale;
+                let tick_h = (lw.peak[1].abs() * vert_scale * 0.03).max(0.001);
+                for segment in [
+                    [[x1, y], [x2, y]],
+                    [[x1, y - tick_h], [x1, y + tick_h]],
+                    [[x2, y - tick_h], [x2, y + tick_h]],
+                ] {
+                    plot_ui.line(
+                        Line::new(PlotPoints::from(segment.to_vec()))
+                            .color(colors.linewidth_color)
+                            .width(1.5),
+                    );
+                }
+                let label = Text::new(
+                    [x2, y].into(),
+                    egui::RichText::new(format!(" Δν½ = {:.2} Hz", lw.fwhm_hz))
+                        .size(10.0)
+                        .color(colors.linewidth_color),
+                )
+                .anchor(egui::Align2::LEFT_CENTER);
+                plot_ui.text(label);
+            }
+        }
+
         // ── Baseline anchor points ──
         if !bl_points_clone.is_empty() {
             let pts: PlotPoints = bl_points_clone
                 .iter()
-                .map(|p| [if is_freq { -p[0] } else { p[0] }, p[1]])
+                .map(|p| [flip * p[0], p[1]])
                 .collect();
             let markers = Points::new(pts)
                 .name("Baseline Points")
@@ -626,7 +1497,7 @@ pub fn show_spectrum_1d(
                 sorted.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap());
                 let sorted_display: Vec<[f64; 2]> = sorted
                     .iter()
-                    .map(|p| [if is_freq { -p[0] } else { p[0] }, p[1]])
+                    .map(|p| [flip * p[0], p[1]])
                     .collect();
                 let bl_line = Line::new(PlotPoints::from(sorted_display))
                     .name("Baseline")
@@ -638,7 +1509,7 @@ pub fn show_spectrum_1d(
 
             // Draw vertical guidelines at each baseline point
             for pt in &bl_points_clone {
-                let display_x = if is_freq { -pt[0] } else { pt[0] };
+                let display_x = flip * pt[0];
                 plot_ui.vline(
                     VLine::new(display_x)
                         .color(egui::Color32::from_rgba_premultiplied(0xD4, 0x3F, 0x00, 40))
@@ -646,15 +1517,50 @@ pub fn show_spectrum_1d(
             }
         }
     });
+    if is_freq && !is_phasing {
+        let bounds = *plot_resp.transform.bounds();
+        let (a, b) = (flip * bounds.min()[0], flip * bounds.max()[0]);
+        state.visible_ppm = Some((a.min(b), a.max(b)));
+        state.linked_view = Some(LinkedView { nucleus: spectrum.display_nucleus(), bounds });
+    }
 
     // ── Handle clicks: only ONE picking mode active at a time ──
-    let any_picking = is_picking_bl || state.integration_picking || state.j_coupling_picking || state.peak_picking;
+    let any_picking = is_picking_bl
+        || state.integration_picking
+        || state.j_coupling_picking
+        || state.auto_j_picking
+        || state.linewidth_picking
+        || state.fit_picking
+        || state.peak_picking
+        || state.enhance.picking;
+    if state.solvent_picking && is_freq {
+        let pointer_ppm = plot_resp
+            .response
+            .interact_pointer_pos()
+            .map(|pos| flip * plot_resp.transform.value_from_position(pos).x);
+        if plot_resp.response.drag_started() {
+            state.solvent_drag = pointer_ppm.map(|ppm| (ppm, ppm));
+        } else if plot_resp.response.dragged() {
+            if let (Some(drag), Some(ppm)) = (state.solvent_drag.as_mut(), pointer_ppm) {
+                drag.1 = ppm;
+            }
+        }
+        if plot_resp.response.drag_stopped() {
+            if let Some((a, b)) = state.solvent_drag.take() {
+                let region = (a.min(b), a.max(b));
+                if region.1 - region.0 > 1e-4 {
+                    state.solvent_regions.push(region);
+                    state.pending_actions.push(SpectrumAction::SolventRegionAdded(region));
+                }
+            }
+        }
+    }
     if any_picking {
         if let Some(pos) = plot_resp.response.hover_pos() {
             if plot_resp.response.clicked() {
                 let coord = plot_resp.transform.value_from_position(pos);
                 // Store in real ppm (un-negate the x if freq domain)
-                let real_x = if is_freq { -coord.x } else { coord.x };
+                let real_x = flip * coord.x;
                 let shift_held = ui.input(|i| i.modifiers.shift);
 
                 if is_picking_bl {
@@ -665,8 +1571,9 @@ pub fn show_spectrum_1d(
                         remove_nearest_peak(&mut state.peaks, real_x, 0.1);
                         state.pending_actions.push(SpectrumAction::PeakRemoved(real_x));
                     } else {
-                        // Normal click: add peak at nearest local maximum
-                        let peak = find_nearest_local_max(spectrum, real_x, &raw_ppm);
+                        // Normal click: add peak at the nearest local maximum,
+                        // or minimum when clicking below the baseline
+                        let peak = find_nearest_local_max(spectrum, real_x, &raw_ppm, coord.y < 0.0);
                         state.pending_actions.push(SpectrumAction::PeakAdded(peak));
                         state.peaks.push(peak);
                         // Re-sort peaks by ppm descending
@@ -677,14 +1584,39 @@ pub fn show_spectrum_1d(
                         // Second click → compute integral
                         let lo = start.min(real_x);
                         let hi = start.max(real_x);
-                        let raw_integral =
-                            crate::pipeline::processing::integrate_region(spectrum, lo, hi);
+                        let raw_integral = processing::integrate_region_corrected(
+                            spectrum,
+                            lo,
+                            hi,
+                            state.integral_baseline,
+                        );
                         state.integrations.push((lo, hi, raw_integral));
                         state.pending_actions.push(SpectrumAction::IntegrationAdded(lo, hi, raw_integral));
                     } else {
                         // First click → mark start
                         state.integration_start = Some(real_x);
                     }
+                } else if state.fit_picking {
+                    if let Some(start) = state.fit_start.take() {
+                        let (lo, hi) = (start.min(real_x), start.max(real_x));
+                        let starts: Vec<f64> = state.peaks.iter().map(|p| p[0]).collect();
+                        let fit = processing::fit_peaks(spectrum, lo, hi, &starts, state.fit_shape);
+                        if let Some(fit) = &fit {
+                            // Fitting a region again replaces the fits it overlaps
+                            state.peak_fits.retain(|f| f.hi_ppm < lo || f.lo_ppm > hi);
+                            state.peak_fits.push(fit.clone());
+                        }
+                        state.pending_actions.push(SpectrumAction::PeaksFitted(lo, hi, fit));
+                    } else {
+                        state.fit_start = Some(real_x);
+                    }
+                } else if state.enhance.picking {
+                    if let Some(start) = state.enhance.start.take() {
+                        state.enhance.region = Some((start.min(real_x), start.max(real_x)));
+                        state.enhance.picking = false;
+                    } else {
+                        state.enhance.start = Some(real_x);
+                    }
                 } else if state.j_coupling_picking {
                     // Snap to nearest detected peak if possible
                     let snapped = snap_to_nearest_peak(real_x, &state.peaks, 0.05);
@@ -703,11 +1635,36 @@ pub fn show_spectrum_1d(
                         // First click
                         state.j_coupling_first = Some(snapped);
                     }
+                } else if state.auto_j_picking {
+                    let obs_mhz = spectrum.axes.first().map(|a| a.observe_freq_mhz).unwrap_or(400.0);
+                    let index = multiplet_at(&state.multiplets, real_x);
+                    if let Some(m) = index.map(|i| &mut state.multiplets[i]) {
+                        m.couplings = processing::multiplet_couplings(&m.peaks, obs_mhz, processing::J_CONSISTENCY_HZ);
+                    }
+                    state.pending_actions.push(SpectrumAction::MultipletCouplings(index));
+                } else if state.linewidth_picking {
+                    let snapped = snap_to_nearest_peak(real_x, &state.peaks, 0.05);
+                    let measured = processing::measure_linewidth(spectrum, snapped);
+                    if let Some(lw) = measured {
+                        // Re-measuring a peak replaces its previous width
+                        state.linewidths.retain(|m| m.peak[0] != lw.peak[0]);
+                        state.linewidths.push(lw);
+                    }
+                    state.pending_actions.push(SpectrumAction::LinewidthMeasured(snapped, measured));
                 }
             }
         }
     }
 
+    // Phase on peak: a click (not a drag) while phasing
+    if is_phasing && phase_state.on_peak && plot_resp.response.clicked() {
+        if let Some(pos) = plot_resp.response.hover_pos() {
+            let real_x = flip * plot_resp.transform.value_from_position(pos).x;
+            let anchor = phase_state.phase_on_peak(spectrum, real_x);
+            state.pending_actions.push(SpectrumAction::PhasedOnPeak(real_x, anchor));
+        }
+    }
+
     // Handle drag for interactive phasing
     if is_phasing && plot_resp.response.dragged() {
         let delta = plot_resp.response.drag_delta();
@@ -721,6 +1678,164 @@ pub fn show_spectrum_1d(
     }
 }
 
+/// Show before and after spectra side by side with linked zoom/pan, and
+/// optionally an `after − before` difference trace underneath.
+pub fn show_comparison_1d(
+    ui: &mut egui::Ui,
+    after: &SpectrumData,
+    before: &SpectrumData,
+    show_difference: bool,
+    state: &mut SpectrumViewState,
+    colors: &super::theme::ThemeColors,
+) {
+    ui.horizontal(|ui| {
+        if ui.button("⊞ Auto Scale").clicked() {
+            state.auto_scale = true;
+        }
+        ui.separator();
+        ui.label("Before / After — zoom and pan are linked");
+    });
+
+    if state.auto_scale {
+        state.plot_generation = state.plot_generation.wrapping_add(1);
+    }
+    let link = egui::Id::new(("comparison_link", state.plot_generation));
+    let auto_scale = state.auto_scale;
+    let preset = state.range_preset;
+    let vert_scale = state.vertical_scale;
+    state.auto_scale = false;
+
+    let spacing = ui.spacing().item_spacing;
+    let total_h = ui.available_height() - 4.0;
+    let pane_h = if show_difference { total_h * 0.65 } else { total_h };
+    let pane_w = (ui.available_width() - spacing.x) / 2.0;
+
+    ui.horizontal(|ui| {
+        for (name, spectrum, color) in [
+            ("Before", before, colors.text_muted),
+            ("After", after, colors.spectrum_line),
+        ] {
+            ui.vertical(|ui| {
+                ui.set_width(pane_w);
+                ui.label(egui::RichText::new(name).strong());
+                let plot = comparison_plot(
+                    format!("comparison_{}_{}", name, state.plot_generation),
+                    spectrum,
+                    auto_scale,
+                    preset,
+                    link,
+                    [true, true],
+                    state.axis_direction,
+                )
+                .height(pane_h - ui.spacing().interact_size.y);
+                let values: Vec<f64> = spectrum.real.iter().map(|v| v * vert_scale).collect();
+                let points = display_points(spectrum, &values, state.axis_direction);
+                plot.show(ui, |plot_ui| {
+                    plot_ui.line(Line::new(points).name(name).color(color).width(1.2));
+                });
+            });
+        }
+    });
+
+    if !show_difference {
+        return;
+    }
+
+    ui.add_space(spacing.y);
+    match crate::pipeline::processing::difference_spectrum(after, before) {
+        Some(diff) => {
+            let values: Vec<f64> = diff.iter().map(|v| v * vert_scale).collect();
+            let points = display_points(after, &values, state.axis_direction);
+            let plot = comparison_plot(
+                format!("comparison_diff_{}", state.plot_generation),
+                after,
+                auto_scale,
+                preset,
+                link,
+                [true, false],
+                state.axis_direction,
+            )
+            .height((ui.available_height() - 4.0).max(60.0));
+            plot.show(ui, |plot_ui| {
+                plot_ui.hline(egui_plot::HLine::new(0.0).color(colors.text_muted).width(0.5));
+                plot_ui.line(
+                    Line::new(points)
+                        .name("After − Before")
+                        .color(colors.spectrum_phase)
+                        .width(1.0),
+                );
+            });
+        }
+        None => {
+            ui.label(
+                egui::RichText::new(
+                    "Difference unavailable: before and after differ in domain or dimensionality",
+                )
+                .color(colors.warning),
+            );
+        }
+    }
+}
+
+/// "DW 125.0 µs | AQ 2.048 s" for an FID with a known spectral width
+pub fn acquisition_label(spectrum: &SpectrumData) -> Option<String> {
+    if spectrum.is_frequency_domain {
+        return None;
+    }
+    let axis = spectrum.axes.first()?;
+    Some(format!("DW {:.1} µs | AQ {:.3} s", axis.dwell_time()? * 1e6, axis.acquisition_time()?))
+}
+
+/// Plot configured like the main 1D view, in an axis/cursor link group.
+fn comparison_plot<'a>(
+    id: String,
+    spectrum: &SpectrumData,
+    auto_scale: bool,
+    preset: Option<(f64, f64)>,
+    link: egui::Id,
+    link_axes: [bool; 2],
+    direction: AxisDirection,
+) -> Plot<'a> {
+    let is_freq = spectrum.is_frequency_domain;
+    let flip = direction.sign();
+    let mut plot = Plot::new(id)
+        .x_axis_label(spectrum.x_axis_label())
+        .show_axes([true, false])
+        .show_grid([true, false])
+        .link_axis(link, link_axes)
+        .link_cursor(link, [true, false])
+        .legend(egui_plot::Legend::default().position(egui_plot::Corner::RightTop)
+            .background_alpha(0.6));
+    if is_freq {
+        plot = plot.x_axis_formatter(move |val, range| plot_scene::format_ppm(flip * val.value, range.end() - range.start()));
+        if auto_scale {
+            if let Some((lo, hi)) = default_ppm_range(spectrum, preset) {
+                plot = plot.include_x(flip * lo).include_x(flip * hi);
+            }
+        }
+    }
+    plot
+}
+
+/// Plot points for `values` on the spectrum's display x axis (ppm, negated
+/// unless `direction` is ascending, in the frequency domain; seconds for an
+/// FID).  Non-finite values are skipped.
+fn display_points(spectrum: &SpectrumData, values: &[f64], direction: AxisDirection) -> PlotPoints<'static> {
+    let xs: Vec<f64> = if spectrum.is_frequency_domain && !spectrum.axes.is_empty() {
+        spectrum.axes[0].ppm_scale().iter().map(|&p| direction.sign() * p).collect()
+    } else {
+        match spectrum.axes.first() {
+            Some(axis) => axis.time_scale(values.len()),
+            None => (0..values.len()).map(|i| i as f64).collect(),
+        }
+    };
+    xs.iter()
+        .zip(values)
+        .filter(|(_, y)| y.is_finite())
+        .map(|(&x, &y)| [x, y])
+        .collect()
+}
+
 /// Find the intensity at the nearest data point to a given display x-coordinate.
 fn find_intensity_at_ppm(data: &[f64], ppm_scale: &[f64], display_x: f64) -> f64 {
     if data.is_empty() || ppm_scale.is_empty() {
@@ -763,12 +1878,14 @@ fn snap_to_nearest_peak(ppm: f64, peaks: &[[f64; 2]], tolerance: f64) -> f64 {
     }
 }
 
-/// Find the nearest local maximum to the clicked ppm position.
-/// Returns [ppm, intensity] of the nearest local max.
+/// Find the nearest local maximum (minimum if `negative`) to the clicked
+/// ppm position.  Returns [ppm, intensity] of the extremum, the ppm refined
+/// between data points.
 fn find_nearest_local_max(
     spectrum: &SpectrumData,
     clicked_ppm: f64,
     ppm_scale: &[f64],
+    negative: bool,
 ) -> [f64; 2] {
     let n = spectrum.real.len().min(ppm_scale.len());
     if n < 3 {
@@ -787,20 +1904,42 @@ fn find_nearest_local_max(
     }
 
     // Search a window around the closest point for a local maximum
+    // (minimum for negative peaks)
     let window = 20; // search ±20 data points
     let lo = closest_idx.saturating_sub(window);
     let hi = (closest_idx + window).min(n - 1);
+    let sign = if negative { -1.0 } else { 1.0 };
 
     let mut best_idx = closest_idx;
-    let mut best_val = spectrum.real[closest_idx];
+    let mut best_val = sign * spectrum.real[closest_idx];
     for i in lo..=hi {
-        if spectrum.real[i] > best_val {
-            best_val = spectrum.real[i];
+        if sign * spectrum.real[i] > best_val {
+            best_val = sign * spectrum.real[i];
             best_idx = i;
         }
     }
 
-    [ppm_scale[best_idx], spectrum.real[best_idx]]
+    [processing::refined_position(&spectrum.real[..n], ppm_scale, best_idx), spectrum.real[best_idx]]
+}
+
+/// Multiplet whose lines span the clicked ppm (with a small margin), else
+/// the one with the nearest centre within 0.05 ppm
+fn multiplet_at(multiplets: &[processing::Multiplet], ppm: f64) -> Option<usize> {
+    const MARGIN_PPM: f64 = 0.01;
+    let spans = |m: &processing::Multiplet| {
+        let lo = m.peaks.iter().map(|p| p[0]).fold(f64::INFINITY, f64::min);
+        let hi = m.peaks.iter().map(|p| p[0]).fold(f64::NEG_INFINITY, f64::max);
+        ppm >= lo - MARGIN_PPM && ppm <= hi + MARGIN_PPM
+    };
+    multiplets.iter().position(spans).or_else(|| {
+        multiplets
+            .iter()
+            .enumerate()
+            .map(|(i, m)| (i, (m.center_ppm - ppm).abs()))
+            .filter(|&(_, d)| d <= 0.05)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    })
 }
 
 /// Remove the nearest peak within `tolerance` ppm of the clicked position.
@@ -69,6 +69,7 @@ pub struct ThemeColors {
     pub multiplet_label: egui::Color32,
     pub integration_colors: [egui::Color32; 4],
     pub j_coupling_color: egui::Color32,
+    pub linewidth_color: egui::Color32,
     pub baseline_marker: egui::Color32,
 
     // Tab buttons
@@ -142,6 +143,7 @@ impl ThemeColors {
                 egui::Color32::from_rgba_premultiplied(0x90, 0x40, 0xC0, 0x35),
             ],
             j_coupling_color: egui::Color32::from_rgb(0xCC, 0x66, 0x00),
+            linewidth_color: egui::Color32::from_rgb(0x00, 0x80, 0x80),
             baseline_marker: egui::Color32::from_rgb(0x60, 0x60, 0x60),
 
             tab_active_bg: egui::Color32::from_rgb(0x3B, 0x7D, 0xC0),
@@ -209,6 +211,7 @@ impl ThemeColors {
                 egui::Color32::from_rgba_premultiplied(0xFF, 0xD6, 0x00, 0x40), // yellow
             ],
             j_coupling_color: egui::Color32::from_rgb(0xFF, 0x8C, 0x00), // orange neon
+            linewidth_color: egui::Color32::from_rgb(0x39, 0xFF, 0x14),  // neon lime
             baseline_marker: egui::Color32::from_rgb(0x8B, 0x5C, 0xF6),
 
             // Tabs: neon pink active
@@ -275,9 +278,19 @@ pub fn apply_theme(ctx: &egui::Context, theme: AppTheme) {
 }
 
 /// Get the active cursor mode description for the spectrum view
-pub fn cursor_mode_label(state: &super::spectrum_view::SpectrumViewState, phase_active: bool) -> Option<(&'static str, &'static str, egui::Color32)> {
-    if phase_active {
-        return Some(("⟳ PHASING", "Drag H→PH0, V→PH1", egui::Color32::from_rgb(0x00, 0xCC, 0x66)));
+pub fn cursor_mode_label(
+    state: &super::spectrum_view::SpectrumViewState,
+    phase: &super::phase_dialog::PhaseDialogState,
+) -> Option<(&'static str, &'static str, egui::Color32)> {
+    if phase.active {
+        let msg = if !phase.on_peak {
+            "Drag H→PH0, V→PH1"
+        } else if phase.anchors.is_empty() {
+            "Click an isolated peak for PH0"
+        } else {
+            "Click a second peak for PH1"
+        };
+        return Some(("⟳ PHASING", msg, egui::Color32::from_rgb(0x00, 0xCC, 0x66)));
     }
     if state.peak_picking {
         return Some(("🎯 PEAK PICK", "Click to add · Shift+click to remove", egui::Color32::from_rgb(0xFF, 0x44, 0x44)));
@@ -301,5 +314,16 @@ pub fn cursor_mode_label(state: &super::spectrum_view::SpectrumViewState, phase_
         };
         return Some(("📏 J-COUPLE", msg, egui::Color32::from_rgb(0xFF, 0x88, 0x00)));
     }
+    if state.linewidth_picking {
+        return Some(("↔ LINEWIDTH", "Click a peak to measure its FWHM", egui::Color32::from_rgb(0x00, 0xA0, 0xA0)));
+    }
+    if state.fit_picking {
+        let msg = if state.fit_start.is_some() {
+            "Click end point of fit region"
+        } else {
+            "Click start point of fit region"
+        };
+        return Some(("〰 PEAK FIT", msg, egui::Color32::from_rgb(0xE6, 0x7E, 0x22)));
+    }
     None
 }
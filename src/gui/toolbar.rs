@@ -8,7 +8,15 @@ pub enum ToolbarAction {
     None,
     OpenFile,
     OpenFolder,
+    /// Load another spectrum, keeping the current one in the session
+    AddToSession,
+    ShowSession,
+    /// Process the current spectrum with the steps of an NMRPipe script
+    RunNmrPipeScript,
+    BatchConvert,
+    RevealConvertedFiles,
     SaveProject,
+    SaveProjectBundle,
     LoadProject,
     ExportImage,
     ExportData,
@@ -18,7 +26,13 @@ pub enum ToolbarAction {
     ZoomReset,
     ThemeToggle,
     ShowAbout,
+    ValidateConversion,
+    CompareHeaders,
     ToggleConversionMethod,
+    ToggleTeachingMode,
+    ShowPreferences,
+    /// Open the viewed data in the full processing window
+    OpenInEditor,
 }
 
 /// Render the toolbar and return any triggered action
@@ -26,8 +40,10 @@ pub fn show_toolbar(
     ctx: &egui::Context,
     theme_label: &str,
     conversion_method_label: &str,
+    teaching_mode: bool,
     can_undo: bool,
     can_redo: bool,
+    can_reveal: bool,
 ) -> ToolbarAction {
     let mut action = ToolbarAction::None;
 
@@ -43,11 +59,37 @@ pub fn show_toolbar(
                     action = ToolbarAction::OpenFolder;
                     ui.close_menu();
                 }
+                if ui.button("➕ Add Spectrum to Session…").clicked() {
+                    action = ToolbarAction::AddToSession;
+                    ui.close_menu();
+                }
+                if ui
+                    .button("📜 Process with NMRPipe Script…")
+                    .on_hover_text("Run the -fn steps of a fid.com / ft2.com script on the current spectrum")
+                    .clicked()
+                {
+                    action = ToolbarAction::RunNmrPipeScript;
+                    ui.close_menu();
+                }
+                if ui.button("🗂 Batch Convert Folder…").clicked() {
+                    action = ToolbarAction::BatchConvert;
+                    ui.close_menu();
+                }
+                ui.add_enabled_ui(can_reveal, |ui| {
+                    if ui.button("🔎 Reveal Converted Files").clicked() {
+                        action = ToolbarAction::RevealConvertedFiles;
+                        ui.close_menu();
+                    }
+                });
                 ui.separator();
                 if ui.button("💾 Save Project…     Ctrl+S").clicked() {
                     action = ToolbarAction::SaveProject;
                     ui.close_menu();
                 }
+                if ui.button("📦 Save Project as Bundle…").clicked() {
+                    action = ToolbarAction::SaveProjectBundle;
+                    ui.close_menu();
+                }
                 if ui.button("📂 Load Project…").clicked() {
                     action = ToolbarAction::LoadProject;
                     ui.close_menu();
@@ -89,6 +131,10 @@ pub fn show_toolbar(
                     action = ToolbarAction::ZoomReset;
                     ui.close_menu();
                 }
+                if ui.button("🗂 Session Spectra…").clicked() {
+                    action = ToolbarAction::ShowSession;
+                    ui.close_menu();
+                }
                 ui.separator();
                 if ui.button(format!("🎨 Theme: {}", theme_label)).clicked() {
                     action = ToolbarAction::ThemeToggle;
@@ -102,6 +148,19 @@ pub fn show_toolbar(
                     action = ToolbarAction::ToggleConversionMethod;
                     ui.close_menu();
                 }
+                if ui
+                    .button(format!("🎓 Teaching Mode: {}", if teaching_mode { "On" } else { "Off" }))
+                    .on_hover_text("Explain what each processing step does, and why, as it is applied")
+                    .clicked()
+                {
+                    action = ToolbarAction::ToggleTeachingMode;
+                    ui.close_menu();
+                }
+                ui.separator();
+                if ui.button("⚙ Preferences…").clicked() {
+                    action = ToolbarAction::ShowPreferences;
+                    ui.close_menu();
+                }
             });
 
             // Help menu
@@ -110,6 +169,24 @@ pub fn show_toolbar(
                     action = ToolbarAction::ShowAbout;
                     ui.close_menu();
                 }
+                ui.menu_button("🛠 Developer", |ui| {
+                    if ui
+                        .button("Validate Conversion Against NMRPipe…")
+                        .on_hover_text("Convert with the built-in reader and bruk2pipe/delta2pipe and compare")
+                        .clicked()
+                    {
+                        action = ToolbarAction::ValidateConversion;
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Compare NMRPipe Headers…")
+                        .on_hover_text("List the FDATA parameters that differ between two NMRPipe files")
+                        .clicked()
+                    {
+                        action = ToolbarAction::CompareHeaders;
+                        ui.close_menu();
+                    }
+                });
             });
 
             // Spacer + quick theme toggle
@@ -133,13 +210,64 @@ pub fn show_toolbar(
     action
 }
 
+/// Toolbar of the quick viewer: opening data, the view and image export
+pub fn show_viewer_toolbar(ctx: &egui::Context, theme_label: &str, has_data: bool) -> ToolbarAction {
+    let mut action = ToolbarAction::None;
+
+    egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            if ui.button("📂 Open File…").clicked() {
+                action = ToolbarAction::OpenFile;
+            }
+            if ui.button("📁 Open Folder…").clicked() {
+                action = ToolbarAction::OpenFolder;
+            }
+            ui.separator();
+            ui.add_enabled_ui(has_data, |ui| {
+                if ui.button("🔄 Reset Zoom").clicked() {
+                    action = ToolbarAction::ZoomReset;
+                }
+                if ui.button("🖼 Export Image…").clicked() {
+                    action = ToolbarAction::ExportImage;
+                }
+                if ui
+                    .button("🛠 Open in Full Editor")
+                    .on_hover_text("Open the data in the processing window, with the whole pipeline")
+                    .clicked()
+                {
+                    action = ToolbarAction::OpenInEditor;
+                }
+            });
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.add(egui::Button::new(
+                    egui::RichText::new(theme_label).size(12.0)
+                ).corner_radius(12.0)).clicked() {
+                    action = ToolbarAction::ThemeToggle;
+                }
+                ui.separator();
+                ui.label(
+                    egui::RichText::new("NMR Viewer")
+                        .color(egui::Color32::from_rgb(0x70, 0x75, 0x80))
+                        .size(12.0),
+                );
+            });
+        });
+    });
+
+    action
+}
+
 /// Show file-open dialog for NMR files
 pub fn open_file_dialog() -> Option<PathBuf> {
     rfd::FileDialog::new()
         .set_title("Open NMR Data File")
+        .add_filter("NMR data, projects and archives", &["jdf", "jdx", "dx", "jcamp", "fid", "ft1", "ft2", "nmrproj", "zip"])
         .add_filter("JEOL Delta", &["jdf"])
         .add_filter("JCAMP-DX", &["jdx", "dx", "jcamp"])
         .add_filter("NMRPipe", &["fid", "ft1", "ft2"])
+        .add_filter("NMR Project", &["nmrproj"])
+        .add_filter("Zip archive", &["zip"])
         .add_filter("All Files", &["*"])
         .pick_file()
 }
@@ -151,6 +279,33 @@ pub fn open_folder_dialog() -> Option<PathBuf> {
         .pick_folder()
 }
 
+/// Show open dialog for an NMRPipe processing script
+pub fn open_nmrpipe_script_dialog() -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .set_title("Open NMRPipe Script")
+        .add_filter("NMRPipe script", &["com", "sh", "csh"])
+        .add_filter("All files", &["*"])
+        .pick_file()
+}
+
+/// Show save dialog for a processing template
+pub fn save_template_dialog(name: &str) -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .set_title("Save Processing Template")
+        .set_file_name(format!("{}.{}", name, crate::pipeline::template::TEMPLATE_EXTENSION))
+        .add_filter("Processing template", &[crate::pipeline::template::TEMPLATE_EXTENSION])
+        .save_file()
+}
+
+/// Show open dialog for a processing template
+pub fn open_template_dialog() -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .set_title("Open Processing Template")
+        .add_filter("Processing template", &[crate::pipeline::template::TEMPLATE_EXTENSION])
+        .add_filter("All files", &["*"])
+        .pick_file()
+}
+
 /// Show save dialog for image export
 pub fn save_image_dialog() -> Option<PathBuf> {
     rfd::FileDialog::new()
@@ -167,6 +322,7 @@ pub fn save_data_dialog() -> Option<PathBuf> {
         .add_filter("CSV (comma-separated)", &["csv"])
         .add_filter("TSV (tab-separated)", &["tsv"])
         .add_filter("Text File", &["txt"])
+        .add_filter("JSON (decimated, for web viewers)", &["json"])
         .save_file()
 }
 
@@ -177,5 +333,6 @@ pub fn save_log_dialog() -> Option<PathBuf> {
         .add_filter("Text File", &["txt"])
         .add_filter("JSON", &["json"])
         .add_filter("Shell Script", &["sh"])
+        .add_filter("Teaching Narrative (Markdown)", &["md"])
         .save_file()
 }
@@ -0,0 +1,140 @@
+//! Continuous copy of the reproducibility log next to the data
+//!
+//! When enabled, the log is rewritten as `processing_log.json` beside the
+//! source data (or the project file, once the session has been saved as a
+//! project) after every operation, so the record survives a crash and is
+//! found by anyone browsing the data folder.  Writes run on a background
+//! thread and replace the file atomically.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+
+use chrono::{DateTime, Local};
+
+use super::reproducibility::ReproLog;
+
+/// File name of the mirrored log
+pub const MIRROR_FILE_NAME: &str = "processing_log.json";
+
+/// What was last written: target, entry count and time of the last entry,
+/// so an undo followed by a new operation still counts as a change
+type MirrorState = (PathBuf, usize, Option<DateTime<Local>>);
+
+/// Background writer of the mirrored log
+#[derive(Debug, Default)]
+pub struct LogMirror {
+    /// Project file the session was last saved to or loaded from; the
+    /// mirror goes next to it instead of the source data
+    pub project: Option<PathBuf>,
+    written: Option<MirrorState>,
+    writing: Option<(MirrorState, JoinHandle<io::Result<()>>)>,
+}
+
+impl LogMirror {
+    /// Where the log of `source` is mirrored: inside a dataset folder, or
+    /// beside a data or project file (`None` without either)
+    pub fn target_for(source: &Path, project: Option<&Path>) -> Option<PathBuf> {
+        let dir = match project {
+            Some(project) => project.parent()?,
+            None if source.is_dir() => source,
+            None => source.parent()?,
+        };
+        Some(dir.join(MIRROR_FILE_NAME))
+    }
+
+    /// Whether a write is in progress
+    pub fn busy(&self) -> bool {
+        self.writing.is_some()
+    }
+
+    /// Write the log if it changed since the last write and no write is in
+    /// progress.  Returns the error of a write that finished since the
+    /// last call, if it failed.
+    pub fn update(&mut self, log: &ReproLog) -> Option<io::Error> {
+        let mut failed = None;
+        let finished = self.writing.as_ref().is_some_and(|(_, handle)| handle.is_finished());
+        if let Some((state, handle)) = self.writing.take_if(|_| finished) {
+            match handle.join() {
+                Ok(Ok(())) => self.written = Some(state),
+                Ok(Err(e)) => {
+                    // Keep the failed state so the same content is not retried every frame
+                    self.written = Some(state);
+                    failed = Some(e);
+                }
+                Err(_) => failed = Some(io::Error::other("log mirror thread panicked")),
+            }
+        }
+        if self.writing.is_some() {
+            return failed;
+        }
+        let Some(target) = Self::target_for(Path::new(&log.source_file), self.project.as_deref()) else {
+            return failed;
+        };
+        let state = (target.clone(), log.len(), log.entries.last().map(|e| e.timestamp));
+        if self.written.as_ref() == Some(&state) {
+            return failed;
+        }
+        let json = log.to_json();
+        let handle = std::thread::spawn(move || write_atomically(&target, json.as_bytes()));
+        self.writing = Some((state, handle));
+        failed
+    }
+}
+
+/// Write `bytes` to a temporary file beside `path` and rename it over
+/// `path`, so readers never see a half-written log
+fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirror_follows_log_changes() {
+        let dir = std::env::temp_dir().join(format!("nmr_log_mirror_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let data = dir.join("sample.jdf");
+        std::fs::write(&data, b"").unwrap();
+
+        let mut log = ReproLog::new();
+        log.set_source(&data.to_string_lossy());
+        log.add_entry("Zero Fill", "zf", "nmrPipe -fn ZF -size 32768");
+        let mut mirror = LogMirror::default();
+        let target = dir.join(MIRROR_FILE_NAME);
+        let settle = |mirror: &mut LogMirror, log: &ReproLog| {
+            while mirror.writing.is_some() {
+                assert!(mirror.update(log).is_none());
+                std::thread::yield_now();
+            }
+        };
+
+        assert!(mirror.update(&log).is_none());
+        settle(&mut mirror, &log);
+        let saved: ReproLog = serde_json::from_str(&std::fs::read_to_string(&target).unwrap()).unwrap();
+        assert_eq!(saved.entries.len(), 1);
+
+        // Unchanged log: nothing is rewritten
+        std::fs::remove_file(&target).unwrap();
+        mirror.update(&log);
+        assert!(mirror.writing.is_none() && !target.exists());
+
+        // A new operation is mirrored; a project moves the file next to it
+        log.add_entry("Fourier Transform", "fft", "nmrPipe -fn FT -auto");
+        let project_dir = dir.join("projects");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        mirror.project = Some(project_dir.join("sample.nmrproj"));
+        mirror.update(&log);
+        settle(&mut mirror, &log);
+        let saved: ReproLog =
+            serde_json::from_str(&std::fs::read_to_string(project_dir.join(MIRROR_FILE_NAME)).unwrap()).unwrap();
+        assert_eq!(saved.entries.len(), 2);
+        assert_eq!(LogMirror::target_for(&dir, None), Some(dir.join(MIRROR_FILE_NAME)));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
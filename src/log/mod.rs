@@ -1 +1,3 @@
+pub mod mirror;
 pub mod reproducibility;
+pub mod teaching;
@@ -6,6 +6,7 @@
 /// - Exact NMRPipe command/flags used
 /// - Parameter values
 /// - Sequential order
+/// - Wall-clock duration and data size (when measured)
 ///
 /// The log can be exported as:
 /// - Human-readable text
@@ -17,6 +18,8 @@ use serde::{Deserialize, Serialize};
 use std::io;
 use std::path::Path;
 
+use super::teaching::flag_value;
+
 /// A single log entry representing one operation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
@@ -30,12 +33,49 @@ pub struct LogEntry {
     pub description: String,
     /// The exact NMRPipe command equivalent
     pub nmrpipe_command: String,
+    /// Timing and data size of the operation, if measured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<OpMetrics>,
+}
+
+/// Wall-clock duration and data size of one logged operation
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OpMetrics {
+    pub duration_ms: f64,
+    /// Data points before the operation (real + imaginary, all 2D rows)
+    pub points_in: usize,
+    /// Data points after the operation
+    pub points_out: usize,
+}
+
+impl OpMetrics {
+    /// One-line summary, e.g. `12.3 ms | 32768 → 65536 pts (512 KiB)`
+    pub fn summary(&self) -> String {
+        format!(
+            "{:.1} ms | {} → {} pts ({:.0} KiB)",
+            self.duration_ms,
+            self.points_in,
+            self.points_out,
+            (self.points_out * std::mem::size_of::<f64>()) as f64 / 1024.0
+        )
+    }
+}
+
+/// Aggregated timings for one operation name
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerfSummary {
+    pub operation: String,
+    pub count: usize,
+    pub total_ms: f64,
+    pub max_ms: f64,
+    /// Largest output size seen for this operation
+    pub max_points: usize,
 }
 
 impl LogEntry {
     /// Format as human-readable text line
     pub fn to_text(&self) -> String {
-        format!(
+        let mut text = format!(
             "[{:03}] {} | {} | {}\n      Command: {}",
             self.sequence,
             self.timestamp.format("%Y-%m-%d %H:%M:%S"),
@@ -46,7 +86,11 @@ impl LogEntry {
             } else {
                 self.nmrpipe_command.clone()
             }
-        )
+        );
+        if let Some(m) = &self.metrics {
+            text.push_str(&format!("\n      Time:    {}", m.summary()));
+        }
+        text
     }
 
     /// Format as shell script line
@@ -112,10 +156,49 @@ impl ReproLog {
             operation: operation.to_string(),
             description: description.to_string(),
             nmrpipe_command: nmrpipe_command.to_string(),
+            metrics: None,
         });
         log::info!("[LOG {:03}] {} — {}", seq, operation, description);
     }
 
+    /// Attach timing to the last entry added at or after index `since`.
+    ///
+    /// Operations may log several entries (or none, e.g. when they fail
+    /// early); the measurement belongs to the final one.
+    pub fn attach_metrics(&mut self, since: usize, metrics: OpMetrics) {
+        if self.entries.len() > since {
+            if let Some(entry) = self.entries.last_mut() {
+                entry.metrics = Some(metrics);
+                log::debug!("[LOG {:03}] {}", entry.sequence, metrics.summary());
+            }
+        }
+    }
+
+    /// Per-operation timing totals, slowest total first
+    pub fn performance_summary(&self) -> Vec<PerfSummary> {
+        let mut summary: Vec<PerfSummary> = Vec::new();
+        for entry in &self.entries {
+            let Some(m) = &entry.metrics else { continue };
+            match summary.iter_mut().find(|s| s.operation == entry.operation) {
+                Some(s) => {
+                    s.count += 1;
+                    s.total_ms += m.duration_ms;
+                    s.max_ms = s.max_ms.max(m.duration_ms);
+                    s.max_points = s.max_points.max(m.points_out);
+                }
+                None => summary.push(PerfSummary {
+                    operation: entry.operation.clone(),
+                    count: 1,
+                    total_ms: m.duration_ms,
+                    max_ms: m.duration_ms,
+                    max_points: m.points_out,
+                }),
+            }
+        }
+        summary.sort_by(|a, b| b.total_ms.partial_cmp(&a.total_ms).unwrap());
+        summary
+    }
+
     /// Remove the last entry (for undo)
     pub fn pop_entry(&mut self) -> Option<LogEntry> {
         self.entries.pop()
@@ -131,6 +214,65 @@ impl ReproLog {
         self.entries.is_empty()
     }
 
+    /// Compact summary of how the data was processed, as (label, value)
+    /// rows: window functions, zero-fill size, total phase correction and
+    /// baseline methods.  Steps that were not run are left out.
+    pub fn processing_summary(&self) -> Vec<(String, String)> {
+        let mut windows: Vec<&str> = Vec::new();
+        let mut zero_fill = None;
+        let mut phase: Option<(f64, f64)> = None;
+        let mut baselines: Vec<&str> = Vec::new();
+        for entry in &self.entries {
+            let (op, cmd) = (entry.operation.as_str(), entry.nmrpipe_command.as_str());
+            if let Some(window) = op.strip_prefix("Apodization: ") {
+                windows.push(window);
+            } else if op == "Zero Fill" {
+                zero_fill = flag_value(cmd, "-size").or(zero_fill);
+            } else if op == "Phase Correction" {
+                // Corrections add up
+                if let (Some(p0), Some(p1)) = (flag_value(cmd, "-p0"), flag_value(cmd, "-p1")) {
+                    let (t0, t1) = phase.unwrap_or_default();
+                    phase = Some((t0 + p0, t1 + p1));
+                }
+            } else if op.contains("Baseline Correction") {
+                let method = match op {
+                    "Baseline Correction" => "linear (auto)",
+                    "Polynomial Baseline Correction" => "polynomial (auto)",
+                    "ALS Baseline Correction" => "asymmetric least squares",
+                    "Manual Baseline Correction" => "piecewise-linear (manual)",
+                    other => other,
+                };
+                if !baselines.contains(&method) {
+                    baselines.push(method);
+                }
+            }
+        }
+        let mut rows = Vec::new();
+        if !windows.is_empty() {
+            rows.push(("Window".to_string(), windows.join(" + ")));
+        }
+        if let Some(size) = zero_fill {
+            rows.push(("Zero fill".to_string(), format!("{} points", size)));
+        }
+        if let Some((p0, p1)) = phase {
+            rows.push(("Phase".to_string(), format!("PH0 {:.1}°, PH1 {:.1}°", p0, p1)));
+        }
+        if !baselines.is_empty() {
+            rows.push(("Baseline".to_string(), baselines.join(", ")));
+        }
+        rows
+    }
+
+    /// [`processing_summary`](Self::processing_summary) on one line, as
+    /// embedded in exported figures (empty when nothing was processed)
+    pub fn processing_summary_text(&self) -> String {
+        self.processing_summary()
+            .iter()
+            .map(|(label, value)| format!("{}: {}", label, value))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
     /// Export as human-readable text
     pub fn to_text(&self) -> String {
         let mut out = String::new();
@@ -158,6 +300,18 @@ impl ReproLog {
             out.push_str("\n\n");
         }
 
+        let perf = self.performance_summary();
+        if !perf.is_empty() {
+            out.push_str("───────────────────────────────────────────────────────────────\n");
+            out.push_str("  Performance summary\n");
+            for p in &perf {
+                out.push_str(&format!(
+                    "  {:<28} {:>3}×  total {:>9.1} ms  max {:>9.1} ms  {:>9} pts\n",
+                    p.operation, p.count, p.total_ms, p.max_ms, p.max_points
+                ));
+            }
+        }
+
         out.push_str("═══════════════════════════════════════════════════════════════\n");
         out.push_str(&format!(
             "  Log exported: {}\n",
@@ -312,6 +466,31 @@ mod tests {
         assert_eq!(log.entries[1].sequence, 2);
     }
 
+    #[test]
+    fn test_metrics_attach_to_last_new_entry() {
+        let mut log = ReproLog::new();
+        log.add_entry("Load", "loaded", "");
+        let since = log.len();
+        let metrics = |ms| OpMetrics { duration_ms: ms, points_in: 1024, points_out: 2048 };
+
+        // Nothing logged since → nothing attached
+        log.attach_metrics(since, metrics(1.0));
+        assert!(log.entries[0].metrics.is_none());
+
+        log.add_entry("Zero Fill", "zf", "nmrPipe -fn ZF");
+        log.attach_metrics(since, metrics(2.0));
+        log.add_entry("Zero Fill", "zf", "nmrPipe -fn ZF");
+        log.attach_metrics(since + 1, metrics(5.0));
+        assert_eq!(log.entries[1].metrics.unwrap().duration_ms, 2.0);
+
+        let perf = log.performance_summary();
+        assert_eq!(perf.len(), 1);
+        assert_eq!(perf[0].count, 2);
+        assert_eq!(perf[0].total_ms, 7.0);
+        assert_eq!(perf[0].max_ms, 5.0);
+        assert!(log.to_text().contains("Performance summary"));
+    }
+
     #[test]
     fn test_undo_pops_last() {
         let mut log = ReproLog::new();
@@ -0,0 +1,348 @@
+//! Teaching explanations of processing steps
+//!
+//! Each logged operation can be explained in two short paragraphs: what the
+//! step does to the data mathematically (filled in with the parameters that
+//! were actually used, read back from the NMRPipe command) and why it is
+//! done. The app shows them as pop-up panels in teaching mode, and
+//! [`ReproLog::to_narrative`] strings them together into a lab-course
+//! write-up of a whole session.
+
+use super::reproducibility::{LogEntry, ReproLog};
+
+/// Explanation of one logged operation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    /// What the step does to the data, with the parameters used
+    pub what: String,
+    /// Why the step is part of processing
+    pub why: String,
+}
+
+/// Value following `flag` in an NMRPipe command (`-lb 0.300` → 0.3)
+pub(super) fn flag_value(command: &str, flag: &str) -> Option<f64> {
+    let mut words = command.split_whitespace();
+    words.find(|w| *w == flag)?;
+    words.next()?.parse().ok()
+}
+
+/// NMRPipe function name of a command (`nmrPipe -fn EM -lb 1` → `EM`)
+fn function_name(command: &str) -> Option<&str> {
+    let mut words = command.split_whitespace();
+    words.find(|w| *w == "-fn")?;
+    words.next()
+}
+
+/// Explain `entry`, or `None` for operations without a teaching text
+/// (loading, exports, clearing annotations, …)
+pub fn explain(entry: &LogEntry) -> Option<Explanation> {
+    let cmd = entry.nmrpipe_command.as_str();
+    let op = entry.operation.as_str();
+    let (what, why) = if op.starts_with("Apodization") {
+        match function_name(cmd)? {
+            "EM" => {
+                let lb = flag_value(cmd, "-lb")?;
+                (
+                    format!(
+                        "Every FID point s(t) was multiplied by exp(−π·LB·t) with LB = {:.2} Hz. \
+                         Multiplying in the time domain is a convolution in the frequency \
+                         domain, so each line becomes {:.2} Hz broader (Lorentzian).",
+                        lb, lb
+                    ),
+                    "Late points of the FID hold mostly noise because the signal has decayed. \
+                     Weighting them down raises the signal-to-noise ratio; an LB close to the \
+                     natural linewidth (the matched filter) gives the best S/N, at the cost of \
+                     resolution."
+                        .to_string(),
+                )
+            }
+            "GM" => {
+                let g1 = flag_value(cmd, "-g1")?;
+                let g2 = flag_value(cmd, "-g2")?;
+                (
+                    format!(
+                        "The FID was multiplied by exp(−π·LB·t)·exp(−(t / 2·GB·t_max)²) with \
+                         LB = {:.2} Hz and GB = {:.3}. A negative LB undoes part of the natural \
+                         exponential decay, and the Gaussian term then brings the signal smoothly \
+                         to zero (Lorentz-to-Gauss transformation).",
+                        g2, g1
+                    ),
+                    "Lorentzian lines have broad tails that hide close neighbours. Turning them \
+                     into narrower Gaussian lines resolves small couplings, but amplifies noise."
+                        .to_string(),
+                )
+            }
+            "SP" => {
+                let off = flag_value(cmd, "-off")?;
+                let end = flag_value(cmd, "-end")?;
+                let pow = flag_value(cmd, "-pow")?;
+                (
+                    format!(
+                        "The FID was multiplied by sin^{}(π·(off + (end − off)·t/t_max)) with \
+                         off = {:.2} and end = {:.2}. The window rises from sin(π·{:.2}) at the \
+                         first point and falls to sin(π·{:.2}) at the last.",
+                        pow, off, end, off, end
+                    ),
+                    "A sine bell weights the middle of the FID and suppresses the last points, \
+                     which removes truncation ripples ('sinc wiggles') when the acquisition \
+                     stopped before the signal decayed. Offsets near 0 also sharpen lines; 0.5 \
+                     (a cosine) only smooths."
+                        .to_string(),
+                )
+            }
+            _ => return None,
+        }
+    } else if op == "Zero Fill" {
+        let size = flag_value(cmd, "-size")?;
+        (
+            format!(
+                "Zeros were appended to the FID until it holds {} points ({}).",
+                size, entry.description
+            ),
+            "The Fourier transform gives one spectral point per time point. Padding with zeros \
+             adds no information but interpolates the spectrum: peaks are drawn with more \
+             points and their positions and heights are read more accurately. Doubling once \
+             also recovers the information held in the imaginary half of a complex FID."
+                .to_string(),
+        )
+    } else if op == "Fourier Transform" {
+        (
+            format!(
+                "The discrete Fourier transform S(ν) = Σ s(t)·exp(−2πi·ν·t) turned the FID into \
+                 a spectrum ({}). The FFT shift puts zero frequency in the middle of the axis.",
+                entry.description
+            ),
+            "The FID is the sum of decaying cosines, one per resonance. The Fourier transform \
+             separates them into peaks at their frequencies, which is the spectrum we read."
+                .to_string(),
+        )
+    } else if op == "Inverse Fourier Transform" {
+        (
+            format!(
+                "The inverse transform s(t) = (1/N)·Σ S(ν)·exp(+2πi·ν·t) turned the complex \
+                 spectrum back into a time signal ({}).",
+                entry.description
+            ),
+            "A spectrum that was processed elsewhere carries that software's window and zero \
+             fill. Going back to the time domain lets it be weighted differently and \
+             transformed again. Without the imaginary part it is rebuilt by a Hilbert \
+             transform, which holds because an FID is zero before t = 0."
+                .to_string(),
+        )
+    } else if op == "Phase Correction" {
+        let p0 = flag_value(cmd, "-p0")?;
+        let p1 = flag_value(cmd, "-p1")?;
+        (
+            format!(
+                "Each spectral point was rotated by φ(ν) = PH0 + PH1·(ν position) in the complex \
+                 plane, mixing real and imaginary parts, with PH0 = {:.1}° (the same for every \
+                 peak) and PH1 = {:.1}° (growing linearly across the spectrum).",
+                p0, p1
+            ),
+            "The receiver phase and the delay before acquisition start make the raw real part a \
+             mixture of absorption and dispersion line shapes. Phasing rotates it back so every \
+             peak is a pure, positive absorption line that can be integrated."
+                .to_string(),
+        )
+    } else if op == "2D Phase Correction" {
+        let p0 = flag_value(cmd, "-p0")?;
+        let p1 = flag_value(cmd, "-p1")?;
+        let along = if cmd.contains("# F1") { "column, along F1" } else { "row, along F2" };
+        (
+            format!(
+                "Every {} was rotated by PH0 = {:.1}° plus PH1 = {:.1}° growing across the \
+                 dimension, mixing the real part with the imaginary part of that dimension.",
+                along, p0, p1
+            ),
+            "Each dimension of a phase-sensitive 2D spectrum has its own phase error, so each is \
+             phased on its own: F2 like a 1D spectrum, row by row, then F1 column by column. \
+             Cross peaks then have pure absorption shapes and their signs mean something."
+                .to_string(),
+        )
+    } else if op.contains("Baseline Correction") {
+        (
+            format!(
+                "A baseline model was fitted to the spectrum and subtracted from every point \
+                 ({}).",
+                entry.description
+            ),
+            "Corrupted first FID points and filter effects leave a slow roll under the peaks. \
+             Integrals sum everything above zero, so the baseline must be flat first."
+                .to_string(),
+        )
+    } else if op == "Dead Time Correction" {
+        (
+            format!("The first points of the FID were repaired or removed ({}).", entry.description),
+            "The receiver needs a few microseconds to recover after the pulse, so the first \
+             points are distorted. Errors in the first points become a rolling baseline after \
+             the Fourier transform."
+                .to_string(),
+        )
+    } else if op == "Linear Prediction" {
+        (
+            format!("The FID was extended with predicted points ({}).", entry.description),
+            "A FID that is cut off before it decays gives sinc wiggles and broad lines. Linear \
+             prediction models the signal as decaying sinusoids and continues it, which is most \
+             useful in the short indirect dimension of 2D spectra."
+                .to_string(),
+        )
+    } else if op == "NUS Reconstruction" {
+        (
+            format!("The skipped t1 increments were filled in ({}).", entry.description),
+            "Non-uniform sampling records only some of the t1 increments to save time. A plain \
+             Fourier transform treats the gaps as zeros and spreads every peak into artefacts; \
+             iterative soft thresholding instead finds the spectrum with the fewest strong peaks \
+             that still matches every measured point."
+                .to_string(),
+        )
+    } else if op == "Solvent Suppression" {
+        (
+            format!(
+                "The spectrum was set to zero across the solvent region, with a sine-shaped \
+                 taper over the outer fifth on each side to avoid a sharp step ({}).",
+                entry.description
+            ),
+            "A huge solvent peak dominates the vertical scale and hides nearby signals. \
+             Blanking it makes the weak solute signals visible; anything under the solvent \
+             line is lost with it."
+                .to_string(),
+        )
+    } else if op == "Reference Deconvolution" {
+        (
+            format!(
+                "The reference line was compared with an ideal Lorentzian of the same area, and the \
+                 whole spectrum was corrected by the difference between the two ({}).",
+                entry.description
+            ),
+            "Poor shimming and field inhomogeneity give every line the same distorted shape. A \
+             singlet of known ideal shape measures that distortion, and dividing it out in the \
+             time domain sharpens all lines at once without fitting them one by one."
+                .to_string(),
+        )
+    } else if op == "Center Glitch Removal" {
+        (
+            format!(
+                "The few points of the spike at the centre of the spectrum were replaced by a \
+                 straight line between their neighbours ({}).",
+                entry.description
+            ),
+            "A constant offset in the FID, left by the receiver or by conversion, transforms \
+             into a single sharp spike at zero frequency. It is not a signal of the sample, and \
+             would otherwise be picked as a peak or upset phasing and integration."
+                .to_string(),
+        )
+    } else if op == "Non-finite Values" {
+        (
+            format!("Values that were not numbers (NaN) or infinite were repaired: {}.", entry.description),
+            "A corrupted file or conversion can leave such values in the data. A single one makes \
+             the whole spectrum impossible to scale or plot, and a Fourier transform spreads it \
+             to every point, so they are replaced before any further processing."
+                .to_string(),
+        )
+    } else if op == "Solvent Referencing" {
+        (
+            format!("The whole ppm axis was shifted by a constant: {}.", entry.description),
+            "Chemical shifts are only comparable between spectra when measured against the \
+             same reference. The residual solvent signal has a tabulated shift, so it is a \
+             convenient internal standard."
+                .to_string(),
+        )
+    } else if op == "2D Fourier Transform" {
+        (
+            format!(
+                "Every row (direct dimension, F2) and then every column (indirect dimension, F1) \
+                 was Fourier-transformed: {}.",
+                entry.description
+            ),
+            "A 2D experiment records a series of FIDs with an incremented delay t1. \
+             Transforming along both time axes gives a map whose cross peaks connect \
+             correlated nuclei. With States, Echo-Antiecho or TPPI sampling each t1 point \
+             has a cosine and a sine part, so the result has four quadrants and stays \
+             phase-sensitive in both dimensions."
+                .to_string(),
+        )
+    } else if op == "2D Magnitude" {
+        (
+            format!("Each point was replaced by its modulus √(re² + im²) ({}).", entry.description),
+            "Magnitude mode removes the need to phase a 2D spectrum at the price of broader \
+             line shapes; it is standard for COSY-type experiments."
+                .to_string(),
+        )
+    } else if op == "Peak Detection" {
+        (
+            format!(
+                "Local maxima above the threshold were listed as peaks ({}).",
+                entry.description
+            ),
+            "Peak positions give the chemical shifts that identify the chemical environments \
+             in the molecule."
+                .to_string(),
+        )
+    } else {
+        return None;
+    };
+    Some(Explanation { what, why })
+}
+
+impl ReproLog {
+    /// Educational narrative of the session as Markdown: each step with
+    /// its parameters, what it did and why
+    pub fn to_narrative(&self) -> String {
+        let mut out = String::from("# NMR processing, step by step\n\n");
+        if !self.source_file.is_empty() {
+            out.push_str(&format!("Data: `{}`", self.source_file));
+            if !self.nucleus_info.is_empty() {
+                out.push_str(&format!(" ({} {})", self.nucleus_info, self.experiment_info));
+            }
+            out.push_str("\n\n");
+        }
+        let mut step = 0;
+        for entry in &self.entries {
+            let Some(explanation) = explain(entry) else { continue };
+            step += 1;
+            out.push_str(&format!("## Step {}: {}\n\n", step, entry.operation));
+            out.push_str(&format!("**What was done.** {}\n\n", explanation.what));
+            out.push_str(&format!("**Why.** {}\n\n", explanation.why));
+            if !entry.nmrpipe_command.is_empty() && !entry.nmrpipe_command.starts_with('#') {
+                out.push_str(&format!("NMRPipe equivalent: `{}`\n\n", entry.nmrpipe_command));
+            }
+        }
+        if step == 0 {
+            out.push_str("No processing steps recorded yet.\n");
+        }
+        out
+    }
+
+    /// Save the narrative (Markdown)
+    pub fn save_narrative(&self, path: &std::path::Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_narrative())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explanations_use_logged_parameters() {
+        let mut log = ReproLog::new();
+        log.set_source("sample.fid");
+        log.add_entry("Format Detection", "Detected NMRPipe format", "");
+        log.add_entry("Apodization: EM", "Applied EM to 16384 points", "nmrPipe -fn EM -lb 0.300");
+        log.add_entry("Zero Fill", "Zero-filled from 16384 to 32768 points", "nmrPipe -fn ZF -size 32768");
+        log.add_entry("Phase Correction", "PH0=-30.00°, PH1=4.00°", "nmrPipe -fn PS -p0 -30.00 -p1 4.00 -di");
+
+        assert!(explain(&log.entries[0]).is_none());
+        let em = explain(&log.entries[1]).unwrap();
+        assert!(em.what.contains("LB = 0.30 Hz"));
+        assert!(explain(&log.entries[2]).unwrap().what.contains("32768 points"));
+        assert!(explain(&log.entries[3]).unwrap().what.contains("PH0 = -30.0°"));
+
+        // The narrative numbers only the explained steps
+        let narrative = log.to_narrative();
+        assert!(narrative.contains("Data: `sample.fid`"));
+        assert!(narrative.contains("## Step 1: Apodization: EM"));
+        assert!(narrative.contains("## Step 3: Phase Correction"));
+        assert!(!narrative.contains("Format Detection"));
+        assert!(narrative.contains("NMRPipe equivalent: `nmrPipe -fn ZF -size 32768`"));
+    }
+}
@@ -1,7 +1,9 @@
 #![allow(dead_code)]
 
 mod app;
+mod cli;
 mod data;
+mod error;
 mod gui;
 mod log;
 mod pipeline;
@@ -14,24 +16,56 @@ fn main() -> eframe::Result<()> {
         .format_timestamp_secs()
         .init();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+    // Anything else on the command line is a file, folder, archive,
+    // project or NMRPipe series pattern to open, and the command server
+    let gui = match cli::gui_args(&args) {
+        Ok(gui) => gui,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
     ::log::info!(
         "Starting NMR Spectral Processing GUI v{}",
         env!("CARGO_PKG_VERSION")
     );
 
+    // The GPU contour renderer needs eframe's wgpu backend; if that cannot
+    // start (no adapter), run on glow with CPU contours instead
+    #[cfg(feature = "gpu-contours")]
+    match run(eframe::Renderer::Wgpu, gui.open.clone(), gui.listen, gui.viewer) {
+        Ok(()) => return Ok(()),
+        Err(e) => ::log::warn!("wgpu renderer unavailable ({}), falling back to glow", e),
+    }
+    run(eframe::Renderer::Glow, gui.open, gui.listen, gui.viewer)
+}
+
+fn run(
+    renderer: eframe::Renderer,
+    open: Option<std::path::PathBuf>,
+    listen: Option<std::net::SocketAddr>,
+    viewer: bool,
+) -> eframe::Result<()> {
+    // The viewer opens in a window for a quick look rather than maximized
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([1400.0, 900.0])
+            .with_inner_size(if viewer { [1100.0, 700.0] } else { [1400.0, 900.0] })
             .with_min_inner_size([900.0, 600.0])
-            .with_maximized(true)
-            .with_title("NMR Spectral Processing")
+            .with_maximized(!viewer)
+            .with_title(if viewer { "NMR Viewer" } else { "NMR Spectral Processing" })
             .with_drag_and_drop(true),
+        renderer,
         ..Default::default()
     };
 
     eframe::run_native(
         "NMR Spectral Processing GUI",
         options,
-        Box::new(|cc| Ok(Box::new(NmrApp::new(cc)))),
+        Box::new(move |cc| Ok(Box::new(NmrApp::new(cc, open, listen, viewer)))),
     )
 }
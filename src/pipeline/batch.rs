@@ -0,0 +1,313 @@
+//! Batch conversion of a folder of vendor datasets to NMRPipe format.
+//!
+//! Discovers JEOL `.jdf` files and Bruker experiment directories, then runs
+//! the parallel `convert_many` APIs from the converter crates on a
+//! background thread so the GUI stays responsive while progress is shown.
+//! When processing profiles are passed, every converted dataset with a
+//! profile for its experiment type is also processed and written next to
+//! the `.fid` as `.ft1` / `.ft2`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use nmrpipe_io::batch::{bruker_output_name, check_unique_outputs, BatchJob, BatchProgress};
+
+use crate::data::bruker;
+use crate::data::native_converter;
+use crate::data::nmrpipe_format;
+use crate::data::spectrum::{ExperimentType, VendorFormat};
+use crate::gui::conversion_dialog::{ConversionSettings, DfMode};
+use crate::log::reproducibility::ReproLog;
+use super::conversion;
+use super::profiles::{self, ProcessingProfiles};
+
+/// Progress of a running batch, shared with the worker thread.
+#[derive(Debug, Clone, Default)]
+pub struct BatchStatus {
+    pub total: usize,
+    pub completed: usize,
+    /// Input of the most recently finished job
+    pub current: String,
+    /// One line per failed dataset (`name: error`)
+    pub failures: Vec<String>,
+    /// One line per processed dataset (`output (profile)`)
+    pub processed: Vec<String>,
+    /// Converted datasets whose processing failed (`name: error`)
+    pub processing_failures: Vec<String>,
+    pub finished: bool,
+}
+
+/// Handle to a batch conversion running in the background.
+pub struct BatchConversion {
+    pub source_dir: PathBuf,
+    pub out_dir: PathBuf,
+    status: Arc<Mutex<BatchStatus>>,
+    /// Set by the app once the finished batch has been logged
+    pub reported: bool,
+}
+
+impl BatchConversion {
+    /// Snapshot of the current progress.
+    pub fn status(&self) -> BatchStatus {
+        self.status.lock().unwrap().clone()
+    }
+}
+
+/// JEOL files and Bruker experiment directories found under a folder.
+#[derive(Debug, Default)]
+pub struct BatchInputs {
+    pub jeol_files: Vec<PathBuf>,
+    pub bruker_dirs: Vec<PathBuf>,
+}
+
+impl BatchInputs {
+    pub fn len(&self) -> usize {
+        self.jeol_files.len() + self.bruker_dirs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Find convertible datasets in `dir`: `.jdf` files directly inside it and
+/// Bruker experiments up to two levels deep (`dir/expno` or `dir/sample/expno`).
+pub fn find_batch_inputs(dir: &Path) -> BatchInputs {
+    let mut inputs = BatchInputs::default();
+
+    if is_bruker_experiment(dir) {
+        inputs.bruker_dirs.push(dir.to_path_buf());
+        return inputs;
+    }
+
+    inputs.jeol_files = conversion::list_nmr_files(dir)
+        .into_iter()
+        .filter(|p| conversion::detect_format(p) == VendorFormat::Jeol)
+        .collect();
+
+    for sub in sorted_subdirs(dir) {
+        if is_bruker_experiment(&sub) {
+            inputs.bruker_dirs.push(sub);
+        } else {
+            inputs
+                .bruker_dirs
+                .extend(sorted_subdirs(&sub).into_iter().filter(|d| is_bruker_experiment(d)));
+        }
+    }
+
+    inputs
+}
+
+fn sorted_subdirs(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.is_dir())
+                .collect()
+        })
+        .unwrap_or_default();
+    dirs.sort();
+    dirs
+}
+
+fn is_bruker_experiment(dir: &Path) -> bool {
+    conversion::detect_format(dir) == VendorFormat::Bruker
+        && (dir.join("fid").exists() || dir.join("ser").exists())
+}
+
+/// Start converting every dataset found in `dir` into `out_dir`.
+///
+/// Uses the built-in converters; JEOL digital-filter handling and the Bruker
+/// group-delay override follow the current conversion settings.  With
+/// `profiles`, converted datasets are also processed with the profile of
+/// their experiment type.  Returns an error if nothing convertible
+/// was found.
+pub fn start_batch_conversion(
+    dir: &Path,
+    out_dir: &Path,
+    settings: &ConversionSettings,
+    profiles: Option<ProcessingProfiles>,
+    ctx: egui::Context,
+) -> Result<BatchConversion, String> {
+    let inputs = find_batch_inputs(dir);
+    if inputs.is_empty() {
+        return Err(format!(
+            "No JEOL (.jdf) files or Bruker experiments found in {}",
+            dir.display()
+        ));
+    }
+
+    // Same-named inputs (x.jdf in two folders) would overwrite each other
+    let planned: Vec<BatchJob> = inputs
+        .jeol_files
+        .iter()
+        .map(|p| BatchJob::new(p, jeol_output(out_dir, p)))
+        .chain(inputs.bruker_dirs.iter().map(|d| BatchJob::new(d, bruker_output(out_dir, d))))
+        .collect();
+    check_unique_outputs(&planned)?;
+
+    let delta_opts = delta2pipe::DeltaOptions {
+        real_only: settings.df_mode == DfMode::RealOnly,
+        apply_df: settings.df_mode == DfMode::During,
+        ..Default::default()
+    };
+
+    let status = Arc::new(Mutex::new(BatchStatus {
+        total: inputs.len(),
+        ..Default::default()
+    }));
+
+    let dmx_override = settings.dmx_correction.forced();
+    let out = out_dir.to_path_buf();
+    let shared = Arc::clone(&status);
+    std::thread::spawn(move || {
+        run_batch(inputs, &out, &delta_opts, dmx_override, profiles.as_ref(), &shared, &ctx);
+        shared.lock().unwrap().finished = true;
+        ctx.request_repaint();
+    });
+
+    Ok(BatchConversion {
+        source_dir: dir.to_path_buf(),
+        out_dir: out_dir.to_path_buf(),
+        status,
+        reported: false,
+    })
+}
+
+fn run_batch(
+    inputs: BatchInputs,
+    out_dir: &Path,
+    delta_opts: &delta2pipe::DeltaOptions,
+    dmx_override: Option<bool>,
+    profiles: Option<&ProcessingProfiles>,
+    status: &Mutex<BatchStatus>,
+    ctx: &egui::Context,
+) {
+    let on_progress = |offset: usize| {
+        move |p: &BatchProgress| {
+            let mut s = status.lock().unwrap();
+            s.completed = offset + p.completed;
+            s.current = p.job.input.display().to_string();
+            if let Some(e) = &p.error {
+                s.failures.push(format!("{}: {}", p.job.input.display(), e));
+            }
+            ctx.request_repaint();
+        }
+    };
+
+    // ── JEOL ──
+    let jeol_jobs: Vec<BatchJob> = inputs
+        .jeol_files
+        .iter()
+        .map(|p| BatchJob::new(p, jeol_output(out_dir, p)))
+        .collect();
+    let report = delta2pipe::convert_many(&jeol_jobs, delta_opts, 0, on_progress(0));
+    log::info!("Batch JEOL conversion: {}/{} succeeded", report.succeeded(), jeol_jobs.len());
+    // JEOL experiment types come from the file name when the output is read
+    let mut converted: Vec<(PathBuf, Option<ExperimentType>)> = report
+        .outcomes
+        .iter()
+        .filter(|o| o.result.is_ok())
+        .map(|o| (o.job.output.clone(), None))
+        .collect();
+
+    // ── Bruker ──
+    // acqus parsing is cheap, so derive each experiment's options up front
+    let mut offset = jeol_jobs.len();
+    let mut bruker_jobs = Vec::new();
+    let mut bruker_opts = HashMap::new();
+    let mut bruker_types = HashMap::new();
+    for dir in &inputs.bruker_dirs {
+        match native_converter::bruker_native_setup(dir, dmx_override) {
+            Ok(setup) => {
+                let out = bruker_output(out_dir, dir);
+                bruker_opts.insert(setup.in_file.clone(), setup.options);
+                bruker_types.insert(out.clone(), bruker::detect_experiment_from_pulprog(&setup.params.pulprog));
+                bruker_jobs.push(BatchJob::new(setup.in_file, out));
+            }
+            Err(e) => {
+                let mut s = status.lock().unwrap();
+                offset += 1;
+                s.completed = offset;
+                s.failures.push(format!("{}: {}", dir.display(), e));
+            }
+        }
+    }
+    let report = bruk2pipe::convert_many_with(
+        &bruker_jobs,
+        |job| {
+            bruker_opts
+                .get(&job.input)
+                .cloned()
+                .ok_or_else(|| bruk2pipe::BrukerError::Config("missing acqus setup".into()))
+        },
+        0,
+        on_progress(offset),
+    );
+    log::info!("Batch Bruker conversion: {}/{} succeeded", report.succeeded(), bruker_jobs.len());
+    converted.extend(
+        report
+            .outcomes
+            .iter()
+            .filter(|o| o.result.is_ok())
+            .map(|o| (o.job.output.clone(), bruker_types.get(&o.job.output).cloned())),
+    );
+
+    // ── Processing profiles ──
+    let Some(profiles) = profiles else {
+        return;
+    };
+    for (fid, experiment) in converted {
+        status.lock().unwrap().current = format!("Processing {}", fid.display());
+        ctx.request_repaint();
+        let result = process_output(&fid, experiment, profiles);
+        let mut s = status.lock().unwrap();
+        match result {
+            Ok(Some(line)) => s.processed.push(line),
+            Ok(None) => {}
+            Err(e) => s.processing_failures.push(format!("{}: {}", fid.display(), e)),
+        }
+    }
+}
+
+/// Process one converted file with the profile of its experiment type.
+///
+/// `experiment` overrides the type guessed from the file name (Bruker
+/// pulse programs).  Returns the status line, or `None` without a profile.
+fn process_output(
+    fid: &Path,
+    experiment: Option<ExperimentType>,
+    profiles: &ProcessingProfiles,
+) -> std::io::Result<Option<String>> {
+    let mut spectrum = nmrpipe_format::read_nmrpipe_file(fid)?;
+    if let Some(experiment) = experiment {
+        spectrum.experiment_type = experiment;
+    }
+    spectrum.refine_experiment_type();
+    let Some(profile) = profiles.profile_for(&spectrum.experiment_type) else {
+        return Ok(None);
+    };
+    let mut log = ReproLog::new();
+    if !profiles::apply_profile(&mut spectrum, profile, &mut log) {
+        return Ok(None);
+    }
+    let out = fid.with_extension(if spectrum.is_2d() { "ft2" } else { "ft1" });
+    nmrpipe_format::write_nmrpipe_file(&spectrum, &out)?;
+    Ok(Some(format!("{} ({} profile)", out.display(), profile.experiment)))
+}
+
+fn jeol_output(out_dir: &Path, file: &Path) -> PathBuf {
+    let stem = file
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "data".to_string());
+    out_dir.join(format!("{}.fid", stem))
+}
+
+fn bruker_output(out_dir: &Path, dir: &Path) -> PathBuf {
+    out_dir.join(format!("{}.fid", bruker_output_name(dir)))
+}
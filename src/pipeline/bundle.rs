@@ -0,0 +1,319 @@
+//! Portable project bundles (`.nmrpkg`).
+//!
+//! A bundle is a directory holding the `.nmrproj` file plus a copy of the
+//! raw vendor data under `raw/`.  The project records where the raw data
+//! sits relative to itself, so the whole bundle can be moved to another
+//! machine and the data reconverted from the original files.
+//!
+//! Raw datasets can be large (2D `ser` files, whole sample folders), so the
+//! copy runs on a worker thread ([`start_copy_raw_data`]) and reports the
+//! bytes copied so far.
+//!
+//! A bundle can also be saved as a single `.zip` file: it is built in a
+//! temporary folder and then packed with the `.nmrpkg` folder at the top
+//! of the archive ([`start_zip_bundle`]), which the Open dialog extracts
+//! and opens like the folder itself.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::data::spectrum::VendorFormat;
+
+/// Extension of bundle directories
+pub const BUNDLE_EXTENSION: &str = "nmrpkg";
+/// Subdirectory of a bundle holding the raw data copy
+pub const RAW_DIR: &str = "raw";
+
+/// Path of the project file inside a bundle (`name.nmrpkg/name.nmrproj`).
+pub fn project_file_in(bundle: &Path) -> PathBuf {
+    let stem = bundle
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "project".to_string());
+    bundle.join(format!("{}.nmrproj", stem))
+}
+
+/// Raw data copy (or zip packing) of a bundle running in the background
+pub struct BundleCopy {
+    pub bundle: PathBuf,
+    /// Zip file the bundle goes into once it is complete
+    pub archive: Option<PathBuf>,
+    /// Packing the finished bundle into `archive` rather than copying raw data
+    pub zipping: bool,
+    /// Bytes copied so far and in total
+    pub copied: Arc<AtomicU64>,
+    pub total: u64,
+    result: Arc<Mutex<Option<io::Result<PathBuf>>>>,
+}
+
+impl BundleCopy {
+    /// Fraction done, 0–1
+    pub fn progress(&self) -> f32 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        (self.copied.load(Ordering::Relaxed) as f64 / self.total as f64).min(1.0) as f32
+    }
+
+    /// The copy's outcome (see [`copy_raw_data`]), or the zip file written,
+    /// once the worker is done.
+    pub fn take_result(&self) -> Option<io::Result<PathBuf>> {
+        self.result.lock().unwrap().take()
+    }
+}
+
+/// Run [`copy_raw_data`] on a worker thread.
+pub fn start_copy_raw_data(source: &Path, vendor: &VendorFormat, bundle: &Path, ctx: egui::Context) -> BundleCopy {
+    let total = tree_size(copy_source(source, vendor).0, bundle);
+    let copied = Arc::new(AtomicU64::new(0));
+    let result = Arc::new(Mutex::new(None));
+    let (counter, shared) = (Arc::clone(&copied), Arc::clone(&result));
+    let (source, vendor, target) = (source.to_path_buf(), vendor.clone(), bundle.to_path_buf());
+    std::thread::spawn(move || {
+        let on_bytes = |n: u64| {
+            counter.fetch_add(n, Ordering::Relaxed);
+            ctx.request_repaint();
+        };
+        let outcome = copy_raw_data_with_progress(&source, &vendor, &target, &on_bytes);
+        *shared.lock().unwrap() = Some(outcome);
+        ctx.request_repaint();
+    });
+    BundleCopy { bundle: bundle.to_path_buf(), archive: None, zipping: false, copied, total, result }
+}
+
+/// Run [`zip_bundle`] on a worker thread.
+pub fn start_zip_bundle(bundle: &Path, archive: &Path, ctx: egui::Context) -> BundleCopy {
+    let total = tree_size(bundle, archive);
+    let copied = Arc::new(AtomicU64::new(0));
+    let result = Arc::new(Mutex::new(None));
+    let (counter, shared) = (Arc::clone(&copied), Arc::clone(&result));
+    let (source, target) = (bundle.to_path_buf(), archive.to_path_buf());
+    std::thread::spawn(move || {
+        let on_bytes = |n: u64| {
+            counter.fetch_add(n, Ordering::Relaxed);
+            ctx.request_repaint();
+        };
+        let outcome = zip_bundle_with_progress(&source, &target, &on_bytes).map(|()| target);
+        *shared.lock().unwrap() = Some(outcome);
+        ctx.request_repaint();
+    });
+    BundleCopy {
+        bundle: bundle.to_path_buf(),
+        archive: Some(archive.to_path_buf()),
+        zipping: true,
+        copied,
+        total,
+        result,
+    }
+}
+
+/// Temporary folder to build a bundle in before it is zipped into
+/// `archive`, named like the archive (`sample.zip` → `sample.nmrpkg`)
+pub fn staging_dir_for(archive: &Path) -> PathBuf {
+    let stem = archive
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "project".to_string());
+    std::env::temp_dir()
+        .join(format!("nmr-bundle-{}", uuid::Uuid::new_v4()))
+        .join(format!("{}.{}", stem, BUNDLE_EXTENSION))
+}
+
+/// Pack the bundle folder into the zip file `archive`, with the folder
+/// itself as the archive's single top-level entry.
+pub fn zip_bundle(bundle: &Path, archive: &Path) -> io::Result<()> {
+    zip_bundle_with_progress(bundle, archive, &|_| {})
+}
+
+fn zip_bundle_with_progress(bundle: &Path, archive: &Path, on_bytes: &dyn Fn(u64)) -> io::Result<()> {
+    let name = bundle.file_name().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("Cannot zip {}", bundle.display()))
+    })?;
+    let mut zip = zip::ZipWriter::new(fs::File::create(archive)?);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .large_file(true);
+    zip_recursive(&mut zip, bundle, &name.to_string_lossy(), options, on_bytes)?;
+    zip.finish()?;
+    Ok(())
+}
+
+/// Add a file or directory tree to `zip` under the entry name `entry`
+fn zip_recursive(
+    zip: &mut zip::ZipWriter<fs::File>,
+    from: &Path,
+    entry: &str,
+    options: zip::write::SimpleFileOptions,
+    on_bytes: &dyn Fn(u64),
+) -> io::Result<()> {
+    if from.is_file() {
+        zip.start_file(entry, options)?;
+        let bytes = io::copy(&mut fs::File::open(from)?, zip)?;
+        on_bytes(bytes);
+        return Ok(());
+    }
+    zip.add_directory(entry, options)?;
+    let mut entries: Vec<PathBuf> = fs::read_dir(from)?.map(|e| e.map(|e| e.path())).collect::<io::Result<_>>()?;
+    entries.sort();
+    for path in entries {
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        zip_recursive(zip, &path, &format!("{}/{}", entry, name.to_string_lossy()), options, on_bytes)?;
+    }
+    Ok(())
+}
+
+/// Folder (or file) to copy for `source`, and the name of `source` inside it
+fn copy_source<'a>(source: &'a Path, vendor: &VendorFormat) -> (&'a Path, Option<&'a std::ffi::OsStr>) {
+    let whole_folder = source.is_file() && matches!(vendor, VendorFormat::Bruker | VendorFormat::Varian);
+    match source.parent() {
+        Some(parent) if whole_folder => (parent, source.file_name()),
+        _ => (source, None),
+    }
+}
+
+/// Copy the raw data behind `source` into `bundle/raw/`.
+///
+/// Directory datasets (Bruker experiment folders, Varian `.fid` folders) are
+/// copied whole.  When `source` is a file inside such a folder (e.g. a
+/// Bruker `fid`/`ser`), the enclosing folder is copied so the parameter
+/// files travel with it.  Returns the path of the copied `source` relative
+/// to the bundle.
+pub fn copy_raw_data(source: &Path, vendor: &VendorFormat, bundle: &Path) -> io::Result<PathBuf> {
+    copy_raw_data_with_progress(source, vendor, bundle, &|_| {})
+}
+
+fn copy_raw_data_with_progress(
+    source: &Path,
+    vendor: &VendorFormat,
+    bundle: &Path,
+    on_bytes: &dyn Fn(u64),
+) -> io::Result<PathBuf> {
+    if !source.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Raw data not found: {}", source.display()),
+        ));
+    }
+
+    let (copy_from, inner) = copy_source(source, vendor);
+    let name = copy_from.file_name().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("Cannot bundle {}", source.display()))
+    })?;
+
+    let raw_dir = bundle.join(RAW_DIR);
+    let target = raw_dir.join(name);
+    let mut relative = PathBuf::from(RAW_DIR).join(name);
+    if let Some(inner) = inner {
+        relative.push(inner);
+    }
+
+    // Re-saving a loaded bundle in place: the raw data is already there
+    if fs::canonicalize(&target).ok() == fs::canonicalize(copy_from).ok() {
+        return Ok(relative);
+    }
+    if target.exists() {
+        // Re-saving a bundle replaces the previous copy
+        if target.is_dir() {
+            fs::remove_dir_all(&target)?;
+        } else {
+            fs::remove_file(&target)?;
+        }
+    }
+    fs::create_dir_all(&raw_dir)?;
+    // The bundle may be saved inside the folder being copied; never copy
+    // it into itself
+    let skip = fs::canonicalize(bundle)?;
+    copy_recursive(copy_from, &target, &skip, on_bytes)?;
+    Ok(relative)
+}
+
+/// Copy a file or directory tree, leaving out `skip` (a canonical path).
+/// Returns the number of bytes copied.
+fn copy_recursive(from: &Path, to: &Path, skip: &Path, on_bytes: &dyn Fn(u64)) -> io::Result<u64> {
+    if from.is_file() {
+        let bytes = fs::copy(from, to)?;
+        on_bytes(bytes);
+        return Ok(bytes);
+    }
+    fs::create_dir_all(to)?;
+    let mut bytes = 0;
+    for entry in fs::read_dir(from)? {
+        let path = entry?.path();
+        if fs::canonicalize(&path).is_ok_and(|p| p == skip) {
+            continue;
+        }
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        bytes += copy_recursive(&path, &to.join(name), skip, on_bytes)?;
+    }
+    Ok(bytes)
+}
+
+/// Total size of the files under `path`, leaving out `skip`
+fn tree_size(path: &Path, skip: &Path) -> u64 {
+    if path.starts_with(skip) {
+        return 0;
+    }
+    if path.is_file() {
+        return fs::metadata(path).map_or(0, |m| m.len());
+    }
+    fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|e| tree_size(&e.path(), skip)).sum())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_copies_raw_dataset_folder() {
+        use crate::data::spectrum::VendorFormat;
+        use std::fs;
+
+        let root = std::env::temp_dir().join(format!("nmr_bundle_test_{}", uuid::Uuid::new_v4()));
+        let expno = root.join("data").join("10");
+        fs::create_dir_all(expno.join("pdata").join("1")).unwrap();
+        fs::write(expno.join("acqus"), "##$TD= 1024").unwrap();
+        fs::write(expno.join("fid"), [0u8; 64]).unwrap();
+        fs::write(expno.join("pdata").join("1").join("procs"), "##$SI= 1024").unwrap();
+
+        let pkg = root.join("sample.nmrpkg");
+        assert_eq!(project_file_in(&pkg), pkg.join("sample.nmrproj"));
+
+        // Opening the fid file itself still bundles the whole experiment folder
+        let rel = copy_raw_data(&expno.join("fid"), &VendorFormat::Bruker, &pkg).unwrap();
+        assert_eq!(rel, Path::new("raw").join("10").join("fid"));
+        assert!(pkg.join("raw/10/acqus").is_file());
+        assert!(pkg.join("raw/10/pdata/1/procs").is_file());
+
+        // Re-bundling from the bundled copy leaves it intact
+        let again = copy_raw_data(&pkg.join(&rel), &VendorFormat::Bruker, &pkg).unwrap();
+        assert_eq!(again, rel);
+        assert!(pkg.join(&rel).is_file());
+
+        // A bundle saved inside the dataset folder is not copied into itself
+        let inside = expno.join("inside.nmrpkg");
+        copy_raw_data(&expno.join("fid"), &VendorFormat::Bruker, &inside).unwrap();
+        assert!(inside.join("raw/10/fid").is_file());
+        assert!(!inside.join("raw/10/inside.nmrpkg").exists());
+
+        // A zipped bundle extracts back to the bundle folder
+        fs::write(project_file_in(&pkg), "{}").unwrap();
+        let archive = root.join("sample.zip");
+        zip_bundle(&pkg, &archive).unwrap();
+        let opened = crate::pipeline::open::extract_archive(&archive, &root.join("unzipped")).unwrap();
+        assert_eq!(opened, root.join("unzipped").join("sample.nmrpkg"));
+        assert!(project_file_in(&opened).is_file());
+        assert_eq!(fs::read(opened.join(&rel)).unwrap(), [0u8; 64]);
+        assert!(staging_dir_for(&archive).ends_with("sample.nmrpkg"));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,509 @@
+//! Conversion result cache.
+//!
+//! Converting a JEOL/Bruker/Varian dataset (natively or via the NMRPipe
+//! tools) is the slowest part of opening a file.  The converted
+//! `SpectrumData` is cached on disk keyed by the size and modification
+//! time of the data files, checksums of the parameter files beside them
+//! and the conversion settings, so reopening the same data is instant.  The cache is size-limited (least recently used
+//! entries are evicted first) and can be cleared from Preferences.
+//!
+//! Entries are binary: a small JSON header with the metadata followed by
+//! the data arrays as raw little-endian `f64`, so large 2D spectra stay
+//! compact and NaN samples survive the round trip.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+
+use crate::data::spectrum::SpectrumData;
+use crate::gui::conversion_dialog::ConversionSettings;
+use crate::log::reproducibility::LogEntry;
+use super::processing::NUSLIST_NAMES;
+
+/// User-facing cache settings (persisted in preferences)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheSettings {
+    pub enabled: bool,
+    /// Maximum total size of the cache directory in MB
+    pub max_size_mb: u64,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_size_mb: 1024,
+        }
+    }
+}
+
+/// Magic bytes at the start of every cache entry (bump on format changes)
+const ENTRY_MAGIC: &[u8; 8] = b"NMRCACH1";
+/// Extension of cache entry files
+const ENTRY_EXT: &str = "nmrc";
+/// Extension of entries written by older versions (JSON); still counted
+/// for eviction and clearing, never read
+const LEGACY_EXT: &str = "json";
+
+/// Metadata header of a cached conversion.  The spectrum is stored with
+/// its data arrays emptied; they follow the header in binary form.
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    key: String,
+    source: PathBuf,
+    /// Log entries produced by the original conversion, replayed on a hit
+    log_entries: Vec<LogEntry>,
+    spectrum: SpectrumData,
+}
+
+/// Cached result returned on a hit
+pub struct CachedConversion {
+    pub spectrum: SpectrumData,
+    pub log_entries: Vec<LogEntry>,
+}
+
+/// On-disk conversion cache
+#[derive(Debug, Clone)]
+pub struct ConversionCache {
+    pub dir: PathBuf,
+    pub max_bytes: u64,
+}
+
+impl ConversionCache {
+    pub fn new(dir: PathBuf, settings: &CacheSettings) -> Self {
+        Self {
+            dir,
+            max_bytes: settings.max_size_mb * 1024 * 1024,
+        }
+    }
+
+    /// Platform cache directory (`~/.cache/nmr_gui/conversions`, etc.)
+    pub fn default_dir() -> PathBuf {
+        let base = if cfg!(target_os = "windows") {
+            std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            std::env::var_os("HOME").map(|h| PathBuf::from(h).join("Library").join("Caches"))
+        } else {
+            std::env::var_os("XDG_CACHE_HOME")
+                .map(PathBuf::from)
+                .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))
+        };
+        base.unwrap_or_else(std::env::temp_dir)
+            .join("nmr_gui")
+            .join("conversions")
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", key, ENTRY_EXT))
+    }
+
+    /// Look up a cached conversion; refreshes its LRU timestamp on a hit.
+    pub fn get(&self, key: &str) -> Option<CachedConversion> {
+        let path = self.entry_path(key);
+        let file = File::open(&path).ok()?;
+        let entry = read_entry(&mut BufReader::new(file)).ok()?;
+        if entry.key != key {
+            return None;
+        }
+        if let Ok(f) = File::options().write(true).open(&path) {
+            let _ = f.set_modified(SystemTime::now());
+        }
+        Some(CachedConversion {
+            spectrum: entry.spectrum,
+            log_entries: entry.log_entries,
+        })
+    }
+
+    /// Store a conversion result, then evict old entries over the size limit.
+    pub fn put(
+        &self,
+        key: &str,
+        source: &Path,
+        spectrum: &SpectrumData,
+        log_entries: &[LogEntry],
+    ) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.entry_path(key);
+        // Write under a temporary name so a crash never leaves a truncated entry
+        let tmp = path.with_extension("tmp");
+        {
+            let mut w = BufWriter::new(File::create(&tmp)?);
+            write_entry(&mut w, key, source, spectrum, log_entries)?;
+            w.flush()?;
+        }
+        fs::rename(&tmp, &path)?;
+        self.evict()
+    }
+
+    /// All cache entries as (path, size, last used), oldest first
+    fn entries(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+        let mut entries: Vec<_> = fs::read_dir(&self.dir)
+            .map(|rd| {
+                rd.flatten()
+                    .filter(|e| {
+                        e.path()
+                            .extension()
+                            .map(|x| x == ENTRY_EXT || x == LEGACY_EXT)
+                            .unwrap_or(false)
+                    })
+                    .filter_map(|e| {
+                        let meta = e.metadata().ok()?;
+                        Some((e.path(), meta.len(), meta.modified().unwrap_or(SystemTime::UNIX_EPOCH)))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        entries.sort_by_key(|(_, _, t)| *t);
+        entries
+    }
+
+    /// Remove least recently used entries until the cache fits the limit
+    pub fn evict(&self) -> io::Result<()> {
+        let entries = self.entries();
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        for (path, size, _) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            fs::remove_file(&path)?;
+            total -= size;
+        }
+        Ok(())
+    }
+
+    /// (entry count, total bytes)
+    pub fn usage(&self) -> (usize, u64) {
+        let entries = self.entries();
+        (entries.len(), entries.iter().map(|(_, size, _)| size).sum())
+    }
+
+    /// Delete every cache entry
+    pub fn clear(&self) -> io::Result<()> {
+        for (path, _, _) in self.entries() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+// ────────────────────────────────────────────────────────────────
+//  Entry format
+// ────────────────────────────────────────────────────────────────
+
+fn write_f64s<W: Write>(w: &mut W, values: &[f64]) -> io::Result<()> {
+    w.write_u64::<LittleEndian>(values.len() as u64)?;
+    for &v in values {
+        w.write_f64::<LittleEndian>(v)?;
+    }
+    Ok(())
+}
+
+fn read_f64s<R: Read>(r: &mut R) -> io::Result<Vec<f64>> {
+    let n = r.read_u64::<LittleEndian>()? as usize;
+    let mut values = vec![0.0; n];
+    r.read_f64_into::<LittleEndian>(&mut values)?;
+    Ok(values)
+}
+
+fn write_rows<W: Write>(w: &mut W, rows: &[Vec<f64>]) -> io::Result<()> {
+    w.write_u64::<LittleEndian>(rows.len() as u64)?;
+    for row in rows {
+        write_f64s(w, row)?;
+    }
+    Ok(())
+}
+
+fn read_rows<R: Read>(r: &mut R) -> io::Result<Vec<Vec<f64>>> {
+    let n = r.read_u64::<LittleEndian>()? as usize;
+    (0..n).map(|_| read_f64s(r)).collect()
+}
+
+/// Layout: magic, header length (u64), JSON header, then real, imag,
+/// data_2d and data_2d_imag as length-prefixed little-endian f64 arrays.
+fn write_entry<W: Write>(
+    w: &mut W,
+    key: &str,
+    source: &Path,
+    spectrum: &SpectrumData,
+    log_entries: &[LogEntry],
+) -> io::Result<()> {
+    let header = CacheEntry {
+        key: key.to_string(),
+        source: source.to_path_buf(),
+        log_entries: log_entries.to_vec(),
+        spectrum: SpectrumData {
+            source_path: spectrum.source_path.clone(),
+            vendor_format: spectrum.vendor_format.clone(),
+            experiment_type: spectrum.experiment_type.clone(),
+            dimensionality: spectrum.dimensionality.clone(),
+            sample_name: spectrum.sample_name.clone(),
+            solvent: spectrum.solvent.clone(),
+            array: spectrum.array.clone(),
+            metadata: spectrum.metadata.clone(),
+            axes: spectrum.axes.clone(),
+            real: Vec::new(),
+            imag: Vec::new(),
+            data_2d: Vec::new(),
+            data_2d_imag: Vec::new(),
+            f1_quadrants: spectrum.f1_quadrants.clone(),
+            nus_schedule: spectrum.nus_schedule.clone(),
+            group_delay: spectrum.group_delay,
+            f1_acquisition: spectrum.f1_acquisition,
+            pulse_program: spectrum.pulse_program.clone(),
+            is_frequency_domain: spectrum.is_frequency_domain,
+            provenance: spectrum.provenance,
+            nmrpipe_path: spectrum.nmrpipe_path.clone(),
+            conversion_method_used: spectrum.conversion_method_used.clone(),
+        },
+    };
+    let json = serde_json::to_vec(&header)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    w.write_all(ENTRY_MAGIC)?;
+    w.write_u64::<LittleEndian>(json.len() as u64)?;
+    w.write_all(&json)?;
+    write_f64s(w, &spectrum.real)?;
+    write_f64s(w, &spectrum.imag)?;
+    write_rows(w, &spectrum.data_2d)?;
+    write_rows(w, &spectrum.data_2d_imag)
+}
+
+fn read_entry<R: Read>(r: &mut R) -> io::Result<CacheEntry> {
+    let mut magic = [0u8; 8];
+    r.read_exact(&mut magic)?;
+    if &magic != ENTRY_MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a cache entry"));
+    }
+    let len = r.read_u64::<LittleEndian>()? as usize;
+    let mut json = vec![0u8; len];
+    r.read_exact(&mut json)?;
+    let mut entry: CacheEntry = serde_json::from_slice(&json)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    entry.spectrum.real = read_f64s(r)?;
+    entry.spectrum.imag = read_f64s(r)?;
+    entry.spectrum.data_2d = read_rows(r)?;
+    entry.spectrum.data_2d_imag = read_rows(r)?;
+    Ok(entry)
+}
+
+// ────────────────────────────────────────────────────────────────
+//  Cache keys
+// ────────────────────────────────────────────────────────────────
+
+/// 64-bit FNV-1a hasher (stable across runs and platforms)
+struct Fnv64(u64);
+
+impl Fnv64 {
+    fn new() -> Self {
+        Fnv64(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn update_file(&mut self, path: &Path) -> io::Result<()> {
+        let mut file = File::open(path)?;
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            self.update(&buf[..n]);
+        }
+        Ok(())
+    }
+}
+
+/// Acquisition parameter files read with the data of a Bruker or Varian
+/// dataset (spectral width, frequencies, phases, sampling)
+const PARAMETER_FILES: [&str; 10] =
+    ["acqus", "acqu2s", "acqu3s", "acqu", "acqu2", "acqu3", "procs", "pulseprogram", "procpar", "text"];
+
+/// Whether `path` is a parameter or sampling schedule file, small enough
+/// to checksum
+fn is_parameter_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| PARAMETER_FILES.contains(&n) || NUSLIST_NAMES.contains(&n))
+}
+
+/// Input files whose contents determine the conversion result.
+///
+/// A file input (`fid`, `ser`, `.jdf`, …) is keyed with the parameter
+/// files next to it; a directory (Bruker / Varian) on all regular files
+/// directly inside it plus `pdata/1` (processed data).
+fn input_files(path: &Path) -> Vec<PathBuf> {
+    if path.is_file() {
+        let mut files = vec![path.to_path_buf()];
+        if let Some(dir) = path.parent() {
+            files.extend(
+                PARAMETER_FILES
+                    .iter()
+                    .chain(&NUSLIST_NAMES)
+                    .map(|name| dir.join(name))
+                    .filter(|p| p.is_file() && p != path),
+            );
+        }
+        return files;
+    }
+    let mut files = Vec::new();
+    for dir in [path.to_path_buf(), path.join("pdata").join("1")] {
+        if let Ok(rd) = fs::read_dir(&dir) {
+            files.extend(rd.flatten().map(|e| e.path()).filter(|p| p.is_file()));
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Cache key: size and modification time of the data files, contents of
+/// the parameter files + conversion settings + app version.
+///
+/// Data files are only read (and checksummed) when the filesystem cannot
+/// report a modification time.  The work directory (`output_dir`) and
+/// `verbose` are excluded since they do not change the converted data.
+pub fn cache_key(path: &Path, settings: &ConversionSettings) -> io::Result<String> {
+    let mut h = Fnv64::new();
+    h.update(env!("CARGO_PKG_VERSION").as_bytes());
+
+    let mut keyed = settings.clone();
+    keyed.output_dir = None;
+    keyed.verbose = false;
+    let settings_json = serde_json::to_string(&keyed)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    h.update(settings_json.as_bytes());
+
+    let files = input_files(path);
+    if files.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no input files to checksum"));
+    }
+    for file in files {
+        if let Some(name) = file.file_name() {
+            h.update(name.to_string_lossy().as_bytes());
+        }
+        let meta = fs::metadata(&file)?;
+        h.update(&meta.len().to_le_bytes());
+        if is_parameter_file(&file) {
+            h.update_file(&file)?;
+            continue;
+        }
+        match meta.modified().ok().and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok()) {
+            Some(mtime) => h.update(&mtime.as_nanos().to_le_bytes()),
+            None => h.update_file(&file)?,
+        }
+    }
+    Ok(format!("{:016x}", h.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nmr_cache_test_{}_{}", tag, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_cache_key_tracks_content_and_settings() {
+        let dir = temp_dir("key");
+        let file = dir.join("a.jdf");
+        fs::write(&file, b"JEOL.NMR one").unwrap();
+
+        let settings = ConversionSettings::default();
+        let k1 = cache_key(&file, &settings).unwrap();
+        assert_eq!(k1, cache_key(&file, &settings).unwrap());
+
+        let mut moved = settings.clone();
+        moved.output_dir = Some(dir.join("elsewhere"));
+        assert_eq!(k1, cache_key(&file, &moved).unwrap());
+
+        let mut quiet = settings.clone();
+        quiet.verbose = !quiet.verbose;
+        assert_eq!(k1, cache_key(&file, &quiet).unwrap());
+
+        let mut other = settings.clone();
+        other.ndim = 2;
+        assert_ne!(k1, cache_key(&file, &other).unwrap());
+
+        // Same size, newer modification time
+        fs::write(&file, b"JEOL.NMR two").unwrap();
+        File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(SystemTime::now() + std::time::Duration::from_secs(5))
+            .unwrap();
+        assert_ne!(k1, cache_key(&file, &settings).unwrap());
+
+        fs::write(&file, b"JEOL.NMR three").unwrap();
+        assert_ne!(k1, cache_key(&file, &settings).unwrap());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_key_tracks_parameter_files_next_to_the_data() {
+        let dir = temp_dir("params");
+        let fid = dir.join("fid");
+        let acqus = dir.join("acqus");
+        fs::write(&fid, [0u8; 64]).unwrap();
+        fs::write(&acqus, "##$SW_h= 8000\n").unwrap();
+        let settings = ConversionSettings::default();
+        let k1 = cache_key(&fid, &settings).unwrap();
+
+        // Same size and modification time, other spectral width
+        let mtime = fs::metadata(&acqus).unwrap().modified().unwrap();
+        fs::write(&acqus, "##$SW_h= 9000\n").unwrap();
+        File::options().write(true).open(&acqus).unwrap().set_modified(mtime).unwrap();
+        let k2 = cache_key(&fid, &settings).unwrap();
+        assert_ne!(k1, k2);
+
+        // A schedule appearing beside the data, but not an unrelated file
+        fs::write(dir.join("notes.txt"), "run 2").unwrap();
+        assert_eq!(k2, cache_key(&fid, &settings).unwrap());
+        fs::write(dir.join("nuslist"), "0\n3\n").unwrap();
+        assert_ne!(k2, cache_key(&fid, &settings).unwrap());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_roundtrip_and_eviction() {
+        let dir = temp_dir("store");
+        let mut cache = ConversionCache::new(dir.clone(), &CacheSettings::default());
+        let mut spectrum = SpectrumData {
+            real: vec![1.0; 100],
+            data_2d: vec![vec![2.0; 8]; 4],
+            sample_name: "sample".into(),
+            ..Default::default()
+        };
+        spectrum.real[3] = f64::NAN;
+
+        cache.put("aaaa", Path::new("x.jdf"), &spectrum, &[]).unwrap();
+        let hit = cache.get("aaaa").expect("cache hit");
+        assert_eq!(hit.spectrum.real.len(), 100);
+        assert!(hit.spectrum.real[3].is_nan());
+        assert_eq!(hit.spectrum.real[4], 1.0);
+        assert_eq!(hit.spectrum.data_2d, spectrum.data_2d);
+        assert_eq!(hit.spectrum.sample_name, "sample");
+        assert!(cache.get("bbbb").is_none());
+
+        // A limit smaller than any entry evicts everything
+        cache.max_bytes = 1;
+        cache.put("bbbb", Path::new("y.jdf"), &spectrum, &[]).unwrap();
+        assert_eq!(cache.usage().0, 0);
+
+        cache.clear().unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -4,9 +4,98 @@
 /// and integrates with the reproducibility log.
 
 use serde::{Deserialize, Serialize};
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Output};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::processing::{
+    self, BaselineMethod, DeadTimeMode, Ft2dMode, LpMode, PhaseDim, ProcessingOp, WindowFunction,
+};
+use super::tools::find_tool;
+use crate::data::spectrum::SpectrumData;
+use crate::error::{NmrError, NmrResult};
+use crate::log::reproducibility::ReproLog;
+
+/// Shared flag used to cancel a running external tool from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation; the running child process is killed
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Error returned when a command was killed via its [`CancelToken`]
+pub fn cancelled_error() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "Cancelled by user")
+}
+
+/// Run a command to completion like [`Command::output`], killing the child
+/// process as soon as `cancel` is triggered.
+pub fn run_cancellable(cmd: &mut Command, cancel: Option<&CancelToken>) -> io::Result<Output> {
+    let Some(cancel) = cancel else {
+        return cmd.output();
+    };
+    if cancel.is_cancelled() {
+        return Err(cancelled_error());
+    }
+
+    let child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    wait_cancellable(child, Some(cancel))
+}
+
+/// Collect a spawned child's output (stdout/stderr must be piped), killing
+/// it as soon as `cancel` is triggered.
+pub fn wait_cancellable(mut child: Child, cancel: Option<&CancelToken>) -> io::Result<Output> {
+    // Drain the pipes on threads so a chatty tool cannot block on a full pipe
+    let drain = |pipe: Option<Box<dyn Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            buf
+        })
+    };
+    let stdout = drain(child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    let stderr = drain(child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if cancel.is_some_and(CancelToken::is_cancelled) {
+            log::info!("Killing cancelled process {}", child.id());
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(cancelled_error());
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
 
 /// Result of executing an NMRPipe command
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +116,9 @@ pub struct NmrPipeCommand {
     pub input_file: Option<PathBuf>,
     pub output_file: Option<PathBuf>,
     pub description: String,
+    /// Kills the process when triggered (not part of the command itself)
+    #[serde(skip)]
+    pub cancel: Option<CancelToken>,
 }
 
 impl NmrPipeCommand {
@@ -38,6 +130,7 @@ impl NmrPipeCommand {
             input_file: None,
             output_file: None,
             description: String::new(),
+            cancel: None,
         }
     }
 
@@ -71,6 +164,11 @@ impl NmrPipeCommand {
         self
     }
 
+    pub fn cancel_token(mut self, cancel: Option<&CancelToken>) -> Self {
+        self.cancel = cancel.cloned();
+        self
+    }
+
     /// Build the command string for logging/display
     pub fn to_command_string(&self) -> String {
         let mut parts = vec![self.program.clone()];
@@ -93,10 +191,22 @@ impl NmrPipeCommand {
         }
     }
 
+    /// Resolve the program via [`find_tool`] (so NMRPipe installs outside
+    /// PATH and inside WSL work), falling back to running it by name.
+    fn build_command(&self) -> Command {
+        match find_tool(&self.program) {
+            Some(location) => location.command(&self.args),
+            None => {
+                let mut cmd = Command::new(&self.program);
+                cmd.args(&self.args);
+                cmd
+            }
+        }
+    }
+
     /// Execute the command
     pub fn execute(&self) -> io::Result<CommandResult> {
-        let mut cmd = Command::new(&self.program);
-        cmd.args(&self.args);
+        let mut cmd = self.build_command();
 
         if let Some(dir) = &self.working_dir {
             cmd.current_dir(dir);
@@ -104,7 +214,7 @@ impl NmrPipeCommand {
 
         log::info!("Executing: {}", self.to_command_string());
 
-        let output: Output = cmd.output()?;
+        let output: Output = run_cancellable(&mut cmd, self.cancel.as_ref())?;
 
         let result = CommandResult {
             success: output.status.success(),
@@ -130,8 +240,7 @@ impl NmrPipeCommand {
     pub fn execute_piped(&self, stdin_data: Option<&[u8]>) -> io::Result<Vec<u8>> {
         use std::process::Stdio;
 
-        let mut cmd = Command::new(&self.program);
-        cmd.args(&self.args);
+        let mut cmd = self.build_command();
 
         if let Some(dir) = &self.working_dir {
             cmd.current_dir(dir);
@@ -202,8 +311,11 @@ pub fn execute_pipeline(commands: &[NmrPipeCommand]) -> io::Result<CommandResult
 
 /// Check if NMRPipe is available on the system
 pub fn check_nmrpipe_available() -> bool {
-    Command::new("nmrPipe")
-        .arg("-help")
+    let Some(nmrpipe) = find_tool("nmrPipe") else {
+        return false;
+    };
+    nmrpipe
+        .command(&["-help".to_string()])
         .output()
         .map(|o| o.status.success() || !o.stdout.is_empty() || !o.stderr.is_empty())
         .unwrap_or(false)
@@ -211,9 +323,415 @@ pub fn check_nmrpipe_available() -> bool {
 
 /// Check if a specific NMRPipe tool is available
 pub fn check_tool_available(tool: &str) -> bool {
-    Command::new("which")
-        .arg(tool)
-        .output()
-        .map(|o| o.status.success())
-        .unwrap_or(false)
+    find_tool(tool).is_some()
+}
+
+// =========================================================================
+//  NMRPipe script import
+// =========================================================================
+
+/// A `-fn` block of an imported NMRPipe script that was not run
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedFunction {
+    /// The block as written, e.g. `EXT -left -sw`
+    pub function: String,
+    pub reason: String,
+}
+
+/// Processing steps read from an NMRPipe `fid.com` / `ft2.com` script
+#[derive(Debug, Clone, Default)]
+pub struct ImportedScript {
+    /// Operations in the order they run, each with the `-fn` block it
+    /// came from
+    pub steps: Vec<(ProcessingOp, String)>,
+    pub skipped: Vec<SkippedFunction>,
+}
+
+/// Arguments of one `-fn` block: flags with their numeric value, if any
+struct FnArgs<'a>(&'a [String]);
+
+impl FnArgs<'_> {
+    fn has(&self, flag: &str) -> bool {
+        self.0.iter().any(|a| a == flag)
+    }
+
+    fn value(&self, flag: &str) -> Option<f64> {
+        let i = self.0.iter().position(|a| a == flag)?;
+        self.0.get(i + 1)?.parse().ok()
+    }
+
+    fn text(&self, flag: &str) -> Option<&str> {
+        let i = self.0.iter().position(|a| a == flag)?;
+        self.0.get(i + 1).map(String::as_str)
+    }
+}
+
+/// Pipe stages of a shell script: comments dropped, `\` continuations
+/// joined, each stage split into words (quotes removed)
+fn script_stages(text: &str) -> Vec<Vec<String>> {
+    let mut joined = String::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("").trim_end();
+        match line.strip_suffix('\\') {
+            Some(continued) => {
+                joined.push_str(continued);
+                joined.push(' ');
+            }
+            None => {
+                joined.push_str(line);
+                joined.push('\n');
+            }
+        }
+    }
+    joined
+        .lines()
+        .flat_map(|line| line.split('|'))
+        .map(|stage| {
+            stage
+                .split_whitespace()
+                .map(|w| w.trim_matches(|c| c == '"' || c == '\'').to_string())
+                .collect::<Vec<_>>()
+        })
+        .filter(|words| !words.is_empty())
+        .collect()
+}
+
+/// Read the `nmrPipe -fn` blocks of an NMRPipe processing script and map
+/// them to the app's operations, for data of `points` complex points (1D)
+/// or a 2D matrix.
+///
+/// Conversion stages (`bruk2pipe`, `var2pipe`, …) and the input/output
+/// `nmrPipe` stages are ignored: the loaded spectrum is processed.  For 2D
+/// data the dimension is followed through `TP`; the F2 and F1 `FT`s become
+/// one 2D transform, phase corrections of either dimension are applied
+/// after it, and `MC` gives a magnitude spectrum.  Blocks without an
+/// equivalent are listed as skipped with the reason.
+pub fn parse_nmrpipe_script(text: &str, points: usize, is_2d: bool) -> ImportedScript {
+    let mut script = ImportedScript::default();
+    let mut points = points;
+    // 2D: current dimension, F2 transformed, both transformed
+    let mut dim = PhaseDim::F2;
+    let (mut f2_ft, mut ft_done) = (false, false);
+    let mut deferred: Vec<(ProcessingOp, String)> = Vec::new();
+
+    for words in script_stages(text) {
+        let program = words[0].rsplit(['/', '\\']).next().unwrap_or("");
+        let Some(fn_at) = words.iter().position(|w| w == "-fn") else {
+            continue;
+        };
+        if program != "nmrPipe" || fn_at + 1 >= words.len() {
+            continue;
+        }
+        let args: Vec<String> = words[fn_at + 2..]
+            .iter()
+            .take_while(|w| !matches!(w.as_str(), "-in" | "-out" | "-ov"))
+            .cloned()
+            .collect();
+        let block = std::iter::once(words[fn_at + 1].as_str())
+            .chain(args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let args = FnArgs(&args);
+        // APOD names its window with -qName
+        let name = match words[fn_at + 1].as_str() {
+            "APOD" => args.text("-qName").unwrap_or("APOD").to_string(),
+            other => other.to_string(),
+        };
+        let mut skip = |reason: &str| {
+            script.skipped.push(SkippedFunction { function: block.clone(), reason: reason.to_string() });
+        };
+
+        let op = match name.as_str() {
+            "SP" | "EM" | "GM" | "ZF" if is_2d => {
+                skip("window functions and zero filling are not applied to 2D data here");
+                continue;
+            }
+            "SP" => ProcessingOp::Apodization(WindowFunction::SineBell {
+                power: args.value("-pow").unwrap_or(1.0),
+                offset: args.value("-off").unwrap_or(0.0),
+                end: args.value("-end").unwrap_or(1.0),
+            }),
+            "EM" => ProcessingOp::Apodization(WindowFunction::Exponential { lb_hz: args.value("-lb").unwrap_or(0.0) }),
+            // The inverse of the app's own GM export
+            "GM" => ProcessingOp::Apodization(WindowFunction::Gaussian {
+                gb: args.value("-g1").unwrap_or(0.0),
+                lb_hz: args.value("-g2").unwrap_or(0.0),
+            }),
+            "ZF" => {
+                let mut size = match (args.value("-size"), args.value("-pad")) {
+                    (Some(size), _) => size as usize,
+                    (None, Some(pad)) => points + pad as usize,
+                    (None, None) => points << args.value("-zf").unwrap_or(1.0) as u32,
+                };
+                if args.has("-auto") {
+                    size = size.next_power_of_two();
+                }
+                points = points.max(size);
+                ProcessingOp::ZeroFill { target_size: size }
+            }
+            "SHIFT" => match args.value("-ls") {
+                Some(n) if n >= 1.0 && !ft_done && dim == PhaseDim::F2 => {
+                    ProcessingOp::DeadTimeCorrection { points: n as usize, mode: DeadTimeMode::Drop }
+                }
+                _ => {
+                    skip("only left shifts of the FID are supported");
+                    continue;
+                }
+            },
+            "LP" if args.has("-before") => match args.value("-pred") {
+                Some(n) if n >= 1.0 && dim == PhaseDim::F2 => {
+                    ProcessingOp::DeadTimeCorrection { points: n as usize, mode: DeadTimeMode::BackwardLp }
+                }
+                _ => {
+                    skip("backward prediction of the first points needs -pred, in the direct dimension");
+                    continue;
+                }
+            },
+            "LP" => {
+                // 2D data is extended along F1 only
+                if is_2d && dim != PhaseDim::F1 {
+                    skip("2D linear prediction runs along F1 only");
+                    continue;
+                }
+                let predicted = args.value("-pred").map_or(points, |n| n as usize);
+                let mode = if args.has("-fb") {
+                    LpMode::Mixed
+                } else if args.has("-b") {
+                    LpMode::Backward
+                } else {
+                    LpMode::Forward
+                };
+                points += predicted;
+                ProcessingOp::LinearPrediction { order: args.value("-ord").unwrap_or(8.0) as usize, predicted, mode }
+            }
+            "FT" if args.has("-inv") => {
+                if is_2d {
+                    skip("inverse transforms of 2D data are not supported");
+                    continue;
+                }
+                ProcessingOp::InverseFourierTransform
+            }
+            "FT" if is_2d => match dim {
+                PhaseDim::F2 => {
+                    f2_ft = true;
+                    continue;
+                }
+                PhaseDim::F1 if f2_ft && !ft_done => {
+                    ft_done = true;
+                    script
+                        .steps
+                        .push((ProcessingOp::FourierTransform2D { mode: Ft2dMode::PhaseSensitive }, block.clone()));
+                    script.steps.append(&mut deferred);
+                    continue;
+                }
+                PhaseDim::F1 => {
+                    skip("the F1 transform needs the F2 transform before it");
+                    continue;
+                }
+            },
+            "FT" => ProcessingOp::FourierTransform { use_imaginary: !args.has("-real") },
+            "PS" => {
+                let (ph0, ph1) = (args.value("-p0").unwrap_or(0.0), args.value("-p1").unwrap_or(0.0));
+                if is_2d {
+                    let op = ProcessingOp::PhaseCorrection2D { dim, ph0, ph1 };
+                    if !ft_done {
+                        // Phased once the whole matrix is transformed
+                        deferred.push((op, block.clone()));
+                        continue;
+                    }
+                    op
+                } else {
+                    ProcessingOp::PhaseCorrection { ph0, ph1 }
+                }
+            }
+            "MC" if is_2d => {
+                if !ft_done {
+                    deferred.push((ProcessingOp::Magnitude2D, block.clone()));
+                    continue;
+                }
+                ProcessingOp::Magnitude2D
+            }
+            "POLY" if is_2d => {
+                skip("baseline correction is 1D only");
+                continue;
+            }
+            "POLY" if args.has("-time") => {
+                skip("time-domain solvent filter; use solvent suppression on the spectrum");
+                continue;
+            }
+            "POLY" => ProcessingOp::BaselineCorrection {
+                method: match args.value("-ord") {
+                    Some(order) => BaselineMethod::Polynomial { order: order as usize },
+                    None => BaselineMethod::Linear,
+                },
+            },
+            "TP" | "YTP" | "XY2YX" if is_2d => {
+                dim = match dim {
+                    PhaseDim::F2 => PhaseDim::F1,
+                    PhaseDim::F1 => PhaseDim::F2,
+                };
+                continue;
+            }
+            "DI" => {
+                skip("imaginary data is kept for phasing");
+                continue;
+            }
+            "SOL" => {
+                skip("time-domain solvent filter; use solvent suppression on the spectrum");
+                continue;
+            }
+            _ => {
+                skip("no equivalent in this program");
+                continue;
+            }
+        };
+        script.steps.push((op, block));
+    }
+    for (_, block) in deferred {
+        script.skipped.push(SkippedFunction { function: block, reason: "the spectrum is never transformed".to_string() });
+    }
+    script
+}
+
+/// Run the steps of an imported script on `spectrum`, stopping at the
+/// first that cannot be applied
+pub fn run_imported_script(spectrum: &mut SpectrumData, script: &ImportedScript, log: &mut ReproLog) -> NmrResult<()> {
+    for (op, block) in &script.steps {
+        match op {
+            ProcessingOp::Apodization(window) => processing::apply_apodization(spectrum, window, log),
+            ProcessingOp::ZeroFill { target_size } => processing::zero_fill(spectrum, *target_size, log),
+            ProcessingOp::DeadTimeCorrection { points, mode } => {
+                processing::dead_time_correct(spectrum, *points, *mode, log)
+            }
+            ProcessingOp::LinearPrediction { order, predicted, mode } => {
+                processing::linear_predict(spectrum, *order, *predicted, *mode, log)?
+            }
+            ProcessingOp::FourierTransform { use_imaginary } => {
+                let use_imaginary = *use_imaginary && !spectrum.imag.is_empty();
+                processing::fourier_transform(spectrum, use_imaginary, log)
+            }
+            ProcessingOp::InverseFourierTransform => processing::inverse_fourier_transform(spectrum, log),
+            ProcessingOp::FourierTransform2D { mode } => {
+                if spectrum.nus_schedule.is_some() {
+                    processing::nus_reconstruct(spectrum, processing::NUS_DEFAULT_ITERATIONS, log)?;
+                }
+                processing::fourier_transform_2d(spectrum, *mode, log)
+            }
+            ProcessingOp::PhaseCorrection { ph0, ph1 } => processing::phase_correct(spectrum, *ph0, *ph1, log),
+            ProcessingOp::PhaseCorrection2D { dim, ph0, ph1 } => {
+                processing::phase_correct_2d(spectrum, *dim, *ph0, *ph1, log)?
+            }
+            ProcessingOp::Magnitude2D => processing::magnitude_2d(spectrum, log),
+            ProcessingOp::BaselineCorrection { method } => processing::baseline_correct(spectrum, method, log),
+            _ => return Err(NmrError::NotApplicable(format!("{} cannot be run from a script", block))),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cancel_kills_external_process() {
+        use std::time::Instant;
+
+        let token = CancelToken::new();
+        let trigger = token.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            trigger.cancel();
+        });
+
+        let start = Instant::now();
+        let err = run_cancellable(Command::new("sleep").arg("10"), Some(&token)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let out = run_cancellable(Command::new("echo").arg("ok"), Some(&CancelToken::new())).unwrap();
+        assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "ok");
+    }
+
+    #[test]
+    fn test_nmrpipe_script_import() {
+        use crate::pipeline::processing::{self, BaselineMethod, Ft2dMode, PhaseDim, ProcessingOp, WindowFunction};
+        use crate::data::spectrum::{AxisParams, SpectrumData};
+
+        let fid_com = r#"#!/bin/csh
+bruk2pipe -in ./fid -bad 0.0 -DMX -decim 1600 \
+  -xN 32768 -xT 16384 -xMODE DQD -ov -out ./test.fid
+
+nmrPipe -in test.fid \
+| nmrPipe -fn EM -lb 0.3 -c 0.5 \
+| nmrPipe -fn ZF -auto \
+| nmrPipe -fn FT -auto \
+| nmrPipe -fn PS -p0 43.0 -p1 -12.5 -di  # phased by hand
+| nmrPipe -fn EXT -left -sw \
+| nmrPipe -fn POLY -auto -ord 2 \
+  -ov -out test.ft1
+"#;
+        let script = parse_nmrpipe_script(fid_com, 12_000, false);
+        let ops: Vec<String> = script.steps.iter().map(|(op, _)| format!("{:?}", op)).collect();
+        assert_eq!(
+            ops,
+            [
+                format!("{:?}", ProcessingOp::Apodization(WindowFunction::Exponential { lb_hz: 0.3 })),
+                format!("{:?}", ProcessingOp::ZeroFill { target_size: 32_768 }),
+                format!("{:?}", ProcessingOp::FourierTransform { use_imaginary: true }),
+                format!("{:?}", ProcessingOp::PhaseCorrection { ph0: 43.0, ph1: -12.5 }),
+                format!("{:?}", ProcessingOp::BaselineCorrection { method: BaselineMethod::Polynomial { order: 2 } }),
+            ]
+        );
+        assert_eq!(script.steps[3].1, "PS -p0 43.0 -p1 -12.5 -di");
+        assert_eq!(script.skipped.len(), 1);
+        assert_eq!(script.skipped[0].function, "EXT -left -sw");
+
+        // The steps run as the same calls would
+        let n = 1024;
+        let fid = SpectrumData {
+            real: (0..n).map(|i| (i as f64 * 0.3).cos() * (-(i as f64) / 200.0).exp()).collect(),
+            imag: (0..n).map(|i| (i as f64 * 0.3).sin() * (-(i as f64) / 200.0).exp()).collect(),
+            axes: vec![AxisParams { num_points: n, spectral_width_hz: 4000.0, observe_freq_mhz: 400.0, ..Default::default() }],
+            ..Default::default()
+        };
+        let script = parse_nmrpipe_script(fid_com, n, false);
+        let mut imported = fid.clone();
+        let mut log = ReproLog::new();
+        run_imported_script(&mut imported, &script, &mut log).unwrap();
+        let mut direct = fid;
+        let mut direct_log = ReproLog::new();
+        processing::apply_apodization(&mut direct, &WindowFunction::Exponential { lb_hz: 0.3 }, &mut direct_log);
+        processing::zero_fill(&mut direct, 2048, &mut direct_log);
+        processing::fourier_transform(&mut direct, true, &mut direct_log);
+        processing::phase_correct(&mut direct, 43.0, -12.5, &mut direct_log);
+        processing::baseline_correct(&mut direct, &BaselineMethod::Polynomial { order: 2 }, &mut direct_log);
+        assert!(imported.is_frequency_domain);
+        assert_eq!(imported.real, direct.real);
+        assert_eq!(log.len(), direct_log.len());
+
+        // 2D: the dimension follows TP, the F2 phase waits for the 2D FT
+        let ft2_com = "nmrPipe -in test.fid \\
+| nmrPipe -fn SP -off 0.5 -end 0.98 -pow 2 \\
+| nmrPipe -fn FT \\
+| nmrPipe -fn PS -p0 -90 -p1 0 \\
+| nmrPipe -fn TP \\
+| nmrPipe -fn LP -fb -ord 16 -pred 128 \\
+| nmrPipe -fn FT \\
+| nmrPipe -fn PS -p0 0 -p1 180 \\
+| nmrPipe -fn TP \\
+| nmrPipe -fn MC \\
+  -out test.ft2 -ov
+";
+        let script = parse_nmrpipe_script(ft2_com, 0, true);
+        let ops: Vec<String> = script.steps.iter().map(|(op, _)| format!("{:?}", op)).collect();
+        assert_eq!(ops.len(), 5, "{:?}", ops);
+        assert!(matches!(script.steps[0].0, ProcessingOp::LinearPrediction { order: 16, predicted: 128, .. }));
+        assert!(matches!(script.steps[1].0, ProcessingOp::FourierTransform2D { mode: Ft2dMode::PhaseSensitive }));
+        assert!(matches!(script.steps[2].0, ProcessingOp::PhaseCorrection2D { dim: PhaseDim::F2, ph0, .. } if ph0 == -90.0));
+        assert!(matches!(script.steps[3].0, ProcessingOp::PhaseCorrection2D { dim: PhaseDim::F1, ph1, .. } if ph1 == 180.0));
+        assert!(matches!(script.steps[4].0, ProcessingOp::Magnitude2D));
+        assert_eq!(script.skipped.len(), 1, "{:?}", script.skipped);
+    }
 }
@@ -12,10 +12,16 @@ use crate::data::jdf;
 use crate::data::nmrpipe_format;
 use crate::data::bruker;
 use crate::data::jcamp;
+use crate::data::varian;
 use crate::data::native_converter;
+use crate::data::pulse_program;
+use crate::error::{NmrError, NmrResult};
 use crate::gui::conversion_dialog::{ConversionMethod, ConversionSettings};
 use crate::log::reproducibility::ReproLog;
+use super::cache::{self, ConversionCache};
+use super::processing;
 use super::command::NmrPipeCommand;
+use super::tools;
 
 /// Detect the vendor format from a path (file or directory)
 pub fn detect_format(path: &Path) -> VendorFormat {
@@ -72,8 +78,8 @@ pub fn detect_format(path: &Path) -> VendorFormat {
     VendorFormat::Unknown
 }
 
-/// Conversion output directory
-fn conversion_output_dir(source: &Path) -> PathBuf {
+/// Conversion output directory (`<parent>/<stem>_nmrpipe`)
+pub fn conversion_output_dir(source: &Path) -> PathBuf {
     let parent = source.parent().unwrap_or(Path::new("."));
     let stem = source
         .file_stem()
@@ -82,11 +88,20 @@ fn conversion_output_dir(source: &Path) -> PathBuf {
     parent.join(format!("{}_nmrpipe", stem))
 }
 
+/// Output directory for a conversion: the configured work directory,
+/// or next to the data when none is set.
+fn output_dir(source: &Path, settings: &ConversionSettings) -> PathBuf {
+    settings
+        .output_dir
+        .clone()
+        .unwrap_or_else(|| conversion_output_dir(source))
+}
+
 /// Convert a JEOL .jdf file to NMRPipe format.
 ///
 /// With BuiltIn method, uses the native `delta2pipe` library crate directly.
 /// With NMRPipe method, shells out to NMRPipe's external `delta2pipe` tool.
-fn convert_jeol(path: &Path, log: &mut ReproLog, settings: &ConversionSettings) -> io::Result<SpectrumData> {
+fn convert_jeol(path: &Path, log: &mut ReproLog, settings: &ConversionSettings) -> NmrResult<SpectrumData> {
     log.add_entry(
         "Format Detection",
         &format!("Detected JEOL Delta format: {}", path.display()),
@@ -102,7 +117,7 @@ fn convert_jeol(path: &Path, log: &mut ReproLog, settings: &ConversionSettings)
 }
 
 /// Convert JEOL .jdf using the native delta2pipe library (no external tools).
-fn convert_jeol_builtin(path: &Path, log: &mut ReproLog, settings: &ConversionSettings) -> io::Result<SpectrumData> {
+fn convert_jeol_builtin(path: &Path, log: &mut ReproLog, settings: &ConversionSettings) -> NmrResult<SpectrumData> {
     let stem = path
         .file_stem()
         .map(|s| s.to_string_lossy().to_string())
@@ -116,7 +131,8 @@ fn convert_jeol_builtin(path: &Path, log: &mut ReproLog, settings: &ConversionSe
         verbose: settings.verbose,
     };
 
-    let mut spectrum = native_converter::convert_jdf_native(path, &native_opts)?;
+    let mut spectrum =
+        native_converter::convert_jdf_native(path, &native_opts).map_err(|e| NmrError::reading(path, e))?;
 
     // Detect experiment type from filename
     let experiment_type = crate::data::spectrum::detect_experiment_type(&stem);
@@ -141,19 +157,41 @@ fn convert_jeol_builtin(path: &Path, log: &mut ReproLog, settings: &ConversionSe
     Ok(spectrum)
 }
 
+/// After a cancelled conversion, delete the partial `<stem>.fid` /
+/// `<stem>NNN.fid` outputs, and the output directory if that leaves it empty.
+fn remove_partial_outputs(err: &io::Error, out_dir: &Path, stem: &str) {
+    if err.kind() != io::ErrorKind::Interrupted {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(out_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let is_partial = name
+            .strip_prefix(stem)
+            .and_then(|rest| rest.strip_suffix(".fid"))
+            .map(|num| num.chars().all(|c| c.is_ascii_digit()))
+            .unwrap_or(false);
+        if is_partial {
+            if let Err(e) = fs::remove_file(entry.path()) {
+                log::warn!("Could not remove partial output {}: {}", name, e);
+            }
+        }
+    }
+    // Only succeeds if the directory is now empty
+    let _ = fs::remove_dir(out_dir);
+}
+
 /// Convert JEOL .jdf using the external NMRPipe delta2pipe tool.
-fn convert_jeol_nmrpipe(path: &Path, log: &mut ReproLog, settings: &ConversionSettings) -> io::Result<SpectrumData> {
+fn convert_jeol_nmrpipe(path: &Path, log: &mut ReproLog, settings: &ConversionSettings) -> NmrResult<SpectrumData> {
 
     // Check delta2pipe availability
     if jdf::find_delta2pipe().is_none() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            "delta2pipe not found. Ensure NMRPipe is installed and in PATH.\n\
-             delta2pipe is part of NMRPipe and converts JEOL Delta (.jdf) files.",
-        ));
+        return Err(NmrError::ToolMissing("delta2pipe".to_string()));
     }
 
-    let out_dir = conversion_output_dir(path);
+    let out_dir = output_dir(path, settings);
     let stem = path
         .file_stem()
         .map(|s| s.to_string_lossy().to_string())
@@ -175,7 +213,9 @@ fn convert_jeol_nmrpipe(path: &Path, log: &mut ReproLog, settings: &ConversionSe
     let extra_args = settings.to_args();
 
     // Run delta2pipe
-    let result = jdf::convert_jdf(path, &out_dir, &stem, dim_hint, &extra_args)?;
+    let result = jdf::convert_jdf(path, &out_dir, &stem, dim_hint, &extra_args, settings.cancel.as_ref())
+        .inspect_err(|e| remove_partial_outputs(e, &out_dir, &stem))
+        .map_err(|e| NmrError::tool("delta2pipe", e))?;
 
     log.add_entry(
         "Conversion (delta2pipe)",
@@ -192,10 +232,11 @@ fn convert_jeol_nmrpipe(path: &Path, log: &mut ReproLog, settings: &ConversionSe
     // read_nmrpipe_file handles both 1D and 2D (single-file) formats via the header.
     // Only use multi-plane reader if delta2pipe actually split across multiple files.
     let mut spectrum = if result.output_files.len() > 1 {
-        nmrpipe_format::read_nmrpipe_2d_planes(&result.output_files)?
+        nmrpipe_format::read_nmrpipe_2d_planes(&result.output_files)
     } else {
-        nmrpipe_format::read_nmrpipe_file(&result.primary_file)?
-    };
+        nmrpipe_format::read_nmrpipe_file(&result.primary_file)
+    }
+    .map_err(|e| NmrError::reading(&result.primary_file, e))?;
 
     // Restore original source metadata
     spectrum.source_path = path.to_path_buf();
@@ -220,7 +261,7 @@ fn convert_jeol_nmrpipe(path: &Path, log: &mut ReproLog, settings: &ConversionSe
 /// conversion doesn't use hard-coded garbage values.
 ///
 /// This mirrors how `convert_jeol()` uses `delta2pipe`.
-fn convert_bruker(path: &Path, log: &mut ReproLog, settings: &ConversionSettings) -> io::Result<SpectrumData> {
+fn convert_bruker(path: &Path, log: &mut ReproLog, settings: &ConversionSettings) -> NmrResult<SpectrumData> {
     log.add_entry(
         "Format Detection",
         &format!("Detected Bruker format: {}", path.display()),
@@ -241,15 +282,15 @@ fn convert_bruker(path: &Path, log: &mut ReproLog, settings: &ConversionSettings
     };
 
     if use_builtin {
-        return convert_bruker_builtin(path, log);
+        return convert_bruker_builtin(path, log, settings);
     }
 
-    convert_bruker_nmrpipe(path, log)
+    convert_bruker_nmrpipe(path, log, settings)
 }
 
 /// Convert Bruker data using NMRPipe's bruk2pipe
-fn convert_bruker_nmrpipe(path: &Path, log: &mut ReproLog) -> io::Result<SpectrumData> {
-    let out_dir = conversion_output_dir(path);
+fn convert_bruker_nmrpipe(path: &Path, log: &mut ReproLog, settings: &ConversionSettings) -> NmrResult<SpectrumData> {
+    let out_dir = output_dir(path, settings);
     let stem = path
         .file_name()
         .or_else(|| path.parent().and_then(|p| p.file_name()))
@@ -257,11 +298,14 @@ fn convert_bruker_nmrpipe(path: &Path, log: &mut ReproLog) -> io::Result<Spectru
         .unwrap_or_else(|| "data".to_string());
 
     // Read acqus to get experiment metadata for the spectrum
-    let (params, _is_2d) = bruker::read_bruker_params(path)?;
+    let (params, _is_2d) = bruker::read_bruker_params(path).map_err(|e| NmrError::reading(path, e))?;
     let experiment_type = bruker::detect_experiment_from_pulprog(&params.pulprog);
+    let dmx = log_dmx_decision(path, &params, settings, log);
 
     // Run bruk2pipe with args derived from acqus
-    let result = bruker::convert_bruker_data(path, &out_dir, &stem)?;
+    let result = bruker::convert_bruker_data(path, &out_dir, &stem, dmx.apply, settings.cancel.as_ref())
+        .inspect_err(|e| remove_partial_outputs(e, &out_dir, &stem))
+        .map_err(|e| NmrError::tool("bruk2pipe", e))?;
 
     log.add_entry(
         "Conversion (bruk2pipe)",
@@ -280,10 +324,11 @@ fn convert_bruker_nmrpipe(path: &Path, log: &mut ReproLog) -> io::Result<Spectru
 
     // Read back the converted NMRPipe data
     let mut spectrum = if result.output_files.len() > 1 {
-        nmrpipe_format::read_nmrpipe_2d_planes(&result.output_files)?
+        nmrpipe_format::read_nmrpipe_2d_planes(&result.output_files)
     } else {
-        nmrpipe_format::read_nmrpipe_file(&result.primary_file)?
-    };
+        nmrpipe_format::read_nmrpipe_file(&result.primary_file)
+    }
+    .map_err(|e| NmrError::reading(&result.primary_file, e))?;
 
     // Set metadata from acqus
     spectrum.source_path = path.to_path_buf();
@@ -291,7 +336,15 @@ fn convert_bruker_nmrpipe(path: &Path, log: &mut ReproLog) -> io::Result<Spectru
     spectrum.experiment_type = experiment_type;
     spectrum.nmrpipe_path = Some(result.primary_file);
     spectrum.sample_name = stem;
+    spectrum.metadata = params.metadata();
+    spectrum.group_delay = deferred_group_delay(&params, settings, &dmx);
+    spectrum.solvent = params.solvent;
     spectrum.conversion_method_used = "NMRPipe (bruk2pipe)".to_string();
+    // bruk2pipe recombines echo/antiecho pairs into States pairs
+    spectrum.f1_acquisition = match F1Acquisition::from_bruker_fnmode(params.fnmode) {
+        F1Acquisition::EchoAntiecho => F1Acquisition::States,
+        mode => mode,
+    };
 
     if !spectrum.data_2d.is_empty() {
         spectrum.dimensionality = crate::data::spectrum::Dimensionality::TwoD;
@@ -300,11 +353,47 @@ fn convert_bruker_nmrpipe(path: &Path, log: &mut ReproLog) -> io::Result<Spectru
     Ok(spectrum)
 }
 
+/// Decide on group-delay correction for raw Bruker data and log why.
+fn log_dmx_decision(
+    path: &Path,
+    params: &bruker::BrukerParams,
+    settings: &ConversionSettings,
+    log: &mut ReproLog,
+) -> bruker::DmxDecision {
+    let dmx = bruker::dmx_decision(path, params, settings.dmx_correction.forced());
+    log.add_entry(
+        "Digital Filter (DMX)",
+        &format!(
+            "{} group-delay correction: {}\n# DECIM={} DSPFVS={} GRPDLY={:.3}",
+            if dmx.apply { "Applying" } else { "Skipping" },
+            dmx.reason,
+            params.decim,
+            params.dspfvs,
+            params.grpdly,
+        ),
+        if dmx.apply { "# bruk2pipe -DMX" } else { "# bruk2pipe -AMX (no group-delay correction)" },
+    );
+    dmx
+}
+
+/// Group delay left in the data when the correction was skipped on a
+/// guess, so the pipeline can still offer to remove it.  Not when the user
+/// said the data is corrected or `DIGMOD = 0` says there is no filter.
+fn deferred_group_delay(
+    params: &bruker::BrukerParams,
+    settings: &ConversionSettings,
+    dmx: &bruker::DmxDecision,
+) -> Option<f64> {
+    let grpdly = bruker::resolve_grpdly(params);
+    let guessed = settings.dmx_correction.forced().is_none() && params.digmod != Some(0);
+    (!dmx.apply && guessed && grpdly > 0.0).then_some(grpdly)
+}
+
 /// Read Bruker data using built-in native converter/reader.
 ///
 /// First tries the native bruk2pipe library for raw FID/SER data,
 /// falls back to the simple Bruker reader for processed data.
-fn convert_bruker_builtin(path: &Path, log: &mut ReproLog) -> io::Result<SpectrumData> {
+fn convert_bruker_builtin(path: &Path, log: &mut ReproLog, settings: &ConversionSettings) -> NmrResult<SpectrumData> {
     // Try processed data first if raw files are missing or if processed exists
     let has_raw = path.join("fid").exists() || path.join("ser").exists();
     let has_processed = path.join("pdata/1/1r").exists()
@@ -319,8 +408,12 @@ fn convert_bruker_builtin(path: &Path, log: &mut ReproLog) -> io::Result<Spectru
             "# built-in native bruk2pipe — no external tools required",
         );
 
-        match native_converter::convert_bruker_native(path) {
-            Ok(spectrum) => {
+        let (params, _) = bruker::read_bruker_params(path).map_err(|e| NmrError::reading(path, e))?;
+        let dmx = log_dmx_decision(path, &params, settings, log);
+
+        match native_converter::convert_bruker_native(path, Some(dmx.apply)) {
+            Ok(mut spectrum) => {
+                spectrum.group_delay = deferred_group_delay(&params, settings, &dmx);
                 log.add_entry(
                     "Load (native bruk2pipe)",
                     &format!(
@@ -337,7 +430,8 @@ fn convert_bruker_builtin(path: &Path, log: &mut ReproLog) -> io::Result<Spectru
             Err(e) => {
                 log::warn!("Native bruk2pipe failed: {}, trying simple reader fallback", e);
                 if !has_processed {
-                    return Err(e);
+                    return read_bruker_fid_fallback(path, &params, settings, &dmx, log)
+                        .map_err(|_| NmrError::reading(path, e));
                 }
                 // Fall through to simple reader below
             }
@@ -351,7 +445,7 @@ fn convert_bruker_builtin(path: &Path, log: &mut ReproLog) -> io::Result<Spectru
                       # Method: Built-in\n# Source: {}", path.display()),
             "# built-in reader — no NMRPipe required",
         );
-        let spectrum = bruker::read_bruker_processed(path)?;
+        let spectrum = bruker::read_bruker_processed(path).map_err(|e| NmrError::reading(path, e))?;
 
         log.add_entry(
             "Load (built-in Bruker reader)",
@@ -367,29 +461,128 @@ fn convert_bruker_builtin(path: &Path, log: &mut ReproLog) -> io::Result<Spectru
     }
 
     // No raw or processed data found
-    Err(io::Error::new(
-        io::ErrorKind::NotFound,
-        format!("No convertible Bruker data (fid/ser/1r) in {}", path.display()),
-    ))
+    Err(NmrError::MissingFiles { folder: path.to_path_buf(), expected: vec!["fid", "ser", "pdata/1/1r"] })
 }
 
-/// Convert Varian/Agilent data to NMRPipe format using var2pipe
-fn convert_varian(path: &Path, log: &mut ReproLog) -> io::Result<SpectrumData> {
+/// Raw Bruker FID through the simple reader, for data the native
+/// bruk2pipe cannot convert; the group delay is then removed here
+fn read_bruker_fid_fallback(
+    path: &Path,
+    params: &bruker::BrukerParams,
+    settings: &ConversionSettings,
+    dmx: &bruker::DmxDecision,
+    log: &mut ReproLog,
+) -> NmrResult<SpectrumData> {
+    let mut spectrum = bruker::read_bruker_fid(path).map_err(|e| NmrError::reading(path, e))?;
+    log.add_entry(
+        "Load (built-in Bruker reader)",
+        &format!("Read raw Bruker FID without bruk2pipe: {} points", spectrum.real.len()),
+        "# built-in reader — no NMRPipe required",
+    );
+    let grpdly = bruker::resolve_grpdly(params);
+    if dmx.apply && grpdly > 0.0 {
+        processing::correct_group_delay(&mut spectrum, grpdly, log)?;
+    } else {
+        spectrum.group_delay = deferred_group_delay(params, settings, dmx);
+    }
+    Ok(spectrum)
+}
+
+/// Convert Varian/Agilent data: natively, or with var2pipe in NMRPipe mode
+/// (falling back to the built-in reader when var2pipe is not installed)
+fn convert_varian(path: &Path, log: &mut ReproLog, settings: &ConversionSettings) -> NmrResult<SpectrumData> {
     log.add_entry(
         "Format Detection",
         &format!("Detected Varian/Agilent format: {}", path.display()),
         "",
     );
 
-    let out_dir = conversion_output_dir(path);
-    fs::create_dir_all(&out_dir)?;
+    let use_builtin = match settings.conversion_method {
+        ConversionMethod::BuiltIn => true,
+        ConversionMethod::NMRPipe => {
+            if tools::find_tool("var2pipe").is_none() {
+                log::warn!("var2pipe not found, falling back to built-in reader");
+                true
+            } else {
+                false
+            }
+        }
+    };
+
+    let spectrum = if use_builtin {
+        convert_varian_builtin(path, log)?
+    } else {
+        convert_varian_nmrpipe(path, log, settings)?
+    };
+    if let Some(array) = &spectrum.array {
+        log.add_entry(
+            "Arrayed Acquisition",
+            &format!(
+                "{} FIDs, array = {}{}",
+                array.slices,
+                array.array,
+                array
+                    .parameters
+                    .iter()
+                    .map(|(name, values)| format!(
+                        "\n# {}: {}",
+                        name,
+                        values.iter().map(|v| format!("{}", v)).collect::<Vec<_>>().join(", ")
+                    ))
+                    .collect::<String>()
+            ),
+            "",
+        );
+    }
+    Ok(spectrum)
+}
+
+/// Read Varian/Agilent `fid` + `procpar` with the built-in reader
+fn convert_varian_builtin(path: &Path, log: &mut ReproLog) -> NmrResult<SpectrumData> {
+    log.add_entry(
+        "Load (built-in Varian reader)",
+        &format!("Reading Varian/Agilent fid natively\n\
+                  # Method: Built-in\n# Source: {}", path.display()),
+        "# built-in reader — no NMRPipe required",
+    );
+    let mut spectrum = varian::read_varian_fid(path).map_err(|e| NmrError::reading(path, e))?;
+    let quadrature = if spectrum.is_2d() {
+        varian::read_procpar(path)
+            .map(|params| {
+                let quadrature = varian::F1Quadrature::from_procpar(&params);
+                spectrum.f1_acquisition = quadrature.into();
+                format!(", F1 {}", quadrature)
+            })
+            .unwrap_or_default()
+    } else {
+        String::new()
+    };
+    log.add_entry(
+        "Load (built-in Varian reader)",
+        &format!(
+            "Loaded: {} complex pts × {} FID(s), {}{}",
+            spectrum.axes[0].num_points,
+            spectrum.data_2d.len().max(1),
+            spectrum.axes[0].nucleus,
+            quadrature,
+        ),
+        "",
+    );
+    Ok(spectrum)
+}
+
+/// Convert Varian/Agilent data to NMRPipe format using var2pipe
+fn convert_varian_nmrpipe(path: &Path, log: &mut ReproLog, settings: &ConversionSettings) -> NmrResult<SpectrumData> {
+    let out_dir = output_dir(path, settings);
+    fs::create_dir_all(&out_dir).map_err(|e| NmrError::writing(&out_dir, e))?;
     let out_file = out_dir.join("test.fid");
 
     let cmd = NmrPipeCommand::new("var2pipe")
         .arg("-in").arg(&path.to_string_lossy())
         .arg("-out").arg(&out_file.to_string_lossy())
         .arg("-noaswap")
-        .describe("Convert Varian/Agilent data to NMRPipe format");
+        .describe("Convert Varian/Agilent data to NMRPipe format")
+        .cancel_token(settings.cancel.as_ref());
 
     log.add_entry(
         "Conversion (var2pipe)",
@@ -397,33 +590,44 @@ fn convert_varian(path: &Path, log: &mut ReproLog) -> io::Result<SpectrumData> {
         &cmd.to_command_string(),
     );
 
-    let result = cmd.execute()?;
+    let result = cmd
+        .execute()
+        .inspect_err(|e| remove_partial_outputs(e, &out_dir, "test"))
+        .map_err(|e| NmrError::tool("var2pipe", e))?;
     if !result.success {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!(
-                "var2pipe conversion failed. Ensure NMRPipe is installed.\nstderr: {}",
-                result.stderr
-            ),
-        ));
+        return Err(NmrError::ToolFailed { tool: "var2pipe".to_string(), message: result.stderr });
     }
 
-    let mut spectrum = nmrpipe_format::read_nmrpipe_file(&out_file)?;
+    let mut spectrum = nmrpipe_format::read_nmrpipe_file(&out_file).map_err(|e| NmrError::reading(&out_file, e))?;
     spectrum.source_path = path.to_path_buf();
     spectrum.vendor_format = VendorFormat::Varian;
+    spectrum.nmrpipe_path = Some(out_file);
     spectrum.conversion_method_used = "NMRPipe (var2pipe)".to_string();
+
+    // var2pipe keeps arrayed FIDs as rows; procpar says what was stepped
+    match varian::read_procpar(path) {
+        Ok(params) => {
+            spectrum.solvent = params.get("solvent").map(|v| v.as_str().to_string()).unwrap_or_default();
+            spectrum.array = varian::arrayed_acquisition(&params);
+            spectrum.metadata = varian::metadata(&params);
+            if spectrum.is_2d() {
+                spectrum.f1_acquisition = varian::F1Quadrature::from_procpar(&params).into();
+            }
+        }
+        Err(e) => log::warn!("Could not read procpar for {}: {}", path.display(), e),
+    }
     Ok(spectrum)
 }
 
 /// Read JCAMP-DX spectral data file natively
-fn convert_jcamp(path: &Path, log: &mut ReproLog) -> io::Result<SpectrumData> {
+fn convert_jcamp(path: &Path, log: &mut ReproLog) -> NmrResult<SpectrumData> {
     log.add_entry(
         "Format Detection",
         &format!("Detected JCAMP-DX format: {}", path.display()),
         "",
     );
 
-    let spectrum = jcamp::read_jcamp_file(path)?;
+    let spectrum = jcamp::read_jcamp_file(path).map_err(|e| NmrError::reading(path, e))?;
 
     log.add_entry(
         "Load (native JCAMP-DX reader)",
@@ -446,7 +650,7 @@ fn convert_jcamp(path: &Path, log: &mut ReproLog) -> io::Result<SpectrumData> {
 /// Given e.g. `/path/to/data001.fid`, finds `data002.fid`, `data003.fid`, etc.
 /// Returns sorted list of all discovered plane files, or just the original file if
 /// no numbered pattern is detected.
-fn discover_nmrpipe_planes(path: &Path) -> Vec<PathBuf> {
+pub fn discover_nmrpipe_planes(path: &Path) -> Vec<PathBuf> {
     let stem = match path.file_stem().and_then(|s| s.to_str()) {
         Some(s) => s.to_string(),
         None => return vec![path.to_path_buf()],
@@ -517,17 +721,20 @@ pub fn load_spectrum(
     path: &Path,
     log: &mut ReproLog,
     settings: Option<&ConversionSettings>,
-) -> io::Result<SpectrumData> {
+) -> NmrResult<SpectrumData> {
+    if !path.exists() {
+        return Err(NmrError::NotFound(path.to_path_buf()));
+    }
     let format = detect_format(path);
     log::info!("Detected format: {:?} for {}", format, path.display());
 
     let default_settings = ConversionSettings::default();
     let settings = settings.unwrap_or(&default_settings);
 
-    match format {
+    let mut spectrum = match format {
         VendorFormat::Jeol => convert_jeol(path, log, settings),
         VendorFormat::Bruker => convert_bruker(path, log, settings),
-        VendorFormat::Varian => convert_varian(path, log),
+        VendorFormat::Varian => convert_varian(path, log, settings),
         VendorFormat::Jcamp => convert_jcamp(path, log),
         VendorFormat::NMRPipe => {
             log.add_entry(
@@ -545,24 +752,94 @@ pub fn load_spectrum(
                     &format!("Found {} plane files for 2D dataset", plane_files.len()),
                     "",
                 );
-                let mut spectrum = nmrpipe_format::read_nmrpipe_2d_planes(&plane_files)?;
+                let mut spectrum =
+                    nmrpipe_format::read_nmrpipe_2d_planes(&plane_files).map_err(|e| NmrError::reading(path, e))?;
                 spectrum.conversion_method_used = "Direct (NMRPipe 2D planes)".to_string();
                 Ok(spectrum)
             } else {
-                let mut spectrum = nmrpipe_format::read_nmrpipe_file(path)?;
+                let mut spectrum = nmrpipe_format::read_nmrpipe_file(path).map_err(|e| NmrError::reading(path, e))?;
                 spectrum.conversion_method_used = "Direct (NMRPipe format)".to_string();
                 Ok(spectrum)
             }
         }
-        VendorFormat::Unknown => Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!(
-                "Unknown NMR data format for: {}. \
-                 Supported: Bruker, Varian/Agilent, JEOL Delta (.jdf), JCAMP-DX (.jdx/.dx), NMRPipe",
-                path.display()
+        VendorFormat::Unknown => Err(NmrError::UnsupportedFormat(path.to_path_buf())),
+    }?;
+    spectrum.refine_experiment_type();
+    spectrum.pulse_program = pulse_program::read_pulse_program(path, &format);
+    if let Some(schedule) = processing::find_nus_schedule(&spectrum, path) {
+        log.add_entry(
+            "NUS Schedule",
+            &format!(
+                "Non-uniformly sampled: {} of {} t1 increments acquired (schedule from {}); reconstruct before the 2D FT",
+                schedule.sampled.len(),
+                schedule.grid,
+                schedule.source
             ),
-        )),
+            "",
+        );
+        spectrum.nus_schedule = Some(schedule);
     }
+    Ok(spectrum)
+}
+
+/// Load a spectrum, reusing a cached conversion when the input files and
+/// conversion settings are unchanged.
+///
+/// Only vendor formats that need conversion (JEOL, Bruker, Varian) are
+/// cached; NMRPipe and JCAMP-DX files are read directly.  On a hit the
+/// original conversion log entries are replayed so the log stays complete.
+pub fn load_spectrum_cached(
+    path: &Path,
+    log: &mut ReproLog,
+    settings: Option<&ConversionSettings>,
+    cache: &ConversionCache,
+) -> NmrResult<SpectrumData> {
+    let format = detect_format(path);
+    if !matches!(format, VendorFormat::Jeol | VendorFormat::Bruker | VendorFormat::Varian) {
+        return load_spectrum(path, log, settings);
+    }
+
+    let default_settings = ConversionSettings::default();
+    let settings = settings.unwrap_or(&default_settings);
+
+    let key = match cache::cache_key(path, settings) {
+        Ok(key) => key,
+        Err(e) => {
+            log::warn!("Cannot checksum {} for caching: {}", path.display(), e);
+            return load_spectrum(path, log, Some(settings));
+        }
+    };
+
+    if let Some(hit) = cache.get(&key) {
+        for entry in &hit.log_entries {
+            log.add_entry(&entry.operation, &entry.description, &entry.nmrpipe_command);
+        }
+        log.add_entry(
+            "Conversion Cache",
+            &format!(
+                "Reused cached conversion of {}\n# Cache key: {} (input size/mtime + settings)",
+                path.display(),
+                key
+            ),
+            "# cached result — conversion above was not re-run",
+        );
+        let mut spectrum = hit.spectrum;
+        spectrum.source_path = path.to_path_buf();
+        // Converter outputs may have been cleaned up since
+        if spectrum.nmrpipe_path.as_ref().map(|p| !p.exists()).unwrap_or(false) {
+            spectrum.nmrpipe_path = None;
+        }
+        // Entries cached before provenance was recorded
+        spectrum.provenance = Provenance::on_load(spectrum.is_frequency_domain);
+        return Ok(spectrum);
+    }
+
+    let first_entry = log.len();
+    let spectrum = load_spectrum(path, log, Some(settings))?;
+    if let Err(e) = cache.put(&key, path, &spectrum, &log.entries[first_entry..]) {
+        log::warn!("Failed to cache conversion of {}: {}", path.display(), e);
+    }
+    Ok(spectrum)
 }
 
 /// List all loadable NMR files in a directory
@@ -571,20 +848,65 @@ pub fn list_nmr_files(dir: &Path) -> Vec<PathBuf> {
     if let Ok(entries) = fs::read_dir(dir) {
         for entry in entries.flatten() {
             let p = entry.path();
-            if p.is_file() {
-                let ext = p
-                    .extension()
-                    .map(|e| e.to_string_lossy().to_lowercase())
-                    .unwrap_or_default();
-                match ext.as_str() {
-                    "jdf" | "fid" | "ft1" | "ft2" | "jdx" | "dx" | "jcamp" => {
-                        files.push(p);
-                    }
-                    _ => {}
-                }
+            if is_nmr_file(&p) {
+                files.push(p);
             }
         }
     }
     files.sort();
     files
 }
+
+/// Whether `path` is a file with a known NMR data extension
+pub fn is_nmr_file(path: &Path) -> bool {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    path.is_file() && matches!(ext.as_str(), "jdf" | "fid" | "ft1" | "ft2" | "jdx" | "dx" | "jcamp")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_errors_are_classified() {
+        let dir = std::env::temp_dir().join(format!("nmr_errors_test_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let load = |path: &Path, settings: Option<&ConversionSettings>| {
+            load_spectrum(path, &mut ReproLog::new(), settings).unwrap_err()
+        };
+
+        let missing = load(&dir.join("gone.jdf"), None);
+        assert!(matches!(missing, NmrError::NotFound(_)), "{:?}", missing);
+
+        std::fs::write(dir.join("notes.txt"), "not NMR data").unwrap();
+        assert!(matches!(load(&dir.join("notes.txt"), None), NmrError::UnsupportedFormat(_)));
+
+        // A Bruker folder with parameters but no data names what it lacks
+        let expt = dir.join("10");
+        std::fs::create_dir_all(&expt).unwrap();
+        std::fs::write(expt.join("acqus"), "##TITLE= Parameter file\n##$TD= 1024\n##$SW_h= 5000\n##$SFO1= 400.13\n##END=\n").unwrap();
+        let empty = load(&expt, None);
+        assert!(
+            matches!(&empty, NmrError::MissingFiles { folder, expected } if *folder == expt && expected.contains(&"fid")),
+            "{:?}",
+            empty
+        );
+
+        // An NMRPipe file cut short in its header
+        std::fs::write(dir.join("short.ft1"), [0u8; 100]).unwrap();
+        let short = load(&dir.join("short.ft1"), None);
+        assert!(matches!(&short, NmrError::Corrupted { path, .. } if path.ends_with("short.ft1")), "{:?}", short);
+
+        if jdf::find_delta2pipe().is_none() {
+            std::fs::write(dir.join("sample.jdf"), b"JEOL.NMR").unwrap();
+            let settings = ConversionSettings { conversion_method: ConversionMethod::NMRPipe, ..Default::default() };
+            let e = load(&dir.join("sample.jdf"), Some(&settings));
+            assert!(matches!(&e, NmrError::ToolMissing(tool) if tool == "delta2pipe"), "{:?}", e);
+        }
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
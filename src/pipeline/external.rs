@@ -0,0 +1,252 @@
+//! External prediction / structure tools.
+//!
+//! Users can hook any program (a Python shift predictor, a structure
+//! checker, …) into the GUI by configuring its command line in the
+//! preferences.  The protocol is one JSON document each way: the tool reads
+//! a [`ToolRequest`] on stdin and writes a [`ToolResponse`] on stdout.
+//! Predicted lines come back as stick spectra drawn over the data, and
+//! suggested assignments as ppm labels.
+//!
+//! A minimal tool:
+//!
+//! ```text
+//! import json, sys
+//! req = json.load(sys.stdin)
+//! json.dump({"sticks": [{"ppm": 2.17, "intensity": 1.0, "label": "CH3"}],
+//!            "assignments": [{"ppm": p["ppm"], "label": "?"} for p in req["peaks"]]},
+//!           sys.stdout)
+//! ```
+
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::spectrum::SpectrumData;
+use crate::error::{NmrError, NmrResult};
+use crate::pipeline::command::{self, CancelToken};
+use crate::pipeline::processing::{CrossPeak, Multiplet};
+
+/// Version sent in every request; bumped on incompatible changes
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// A configured external tool
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExternalTool {
+    /// Shown in the tool menu
+    pub name: String,
+    /// Executable (e.g. `python3`)
+    pub program: String,
+    /// Arguments, separated by whitespace (e.g. `/opt/respredict/predict.py --json`)
+    pub args: String,
+}
+
+impl ExternalTool {
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new(self.program.trim());
+        cmd.args(self.args.split_whitespace());
+        cmd
+    }
+
+    /// Command line as shown in the log
+    pub fn command_line(&self) -> String {
+        format!("{} {}", self.program.trim(), self.args.trim()).trim().to_string()
+    }
+}
+
+/// A 1D peak sent to the tool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolPeak {
+    pub ppm: f64,
+    pub intensity: f64,
+}
+
+/// A multiplet sent to the tool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolMultiplet {
+    pub ppm: f64,
+    pub j_hz: f64,
+    pub lines: usize,
+    pub label: String,
+}
+
+/// What the tool reads on stdin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolRequest {
+    pub version: u32,
+    pub sample_name: String,
+    /// Direct-dimension nucleus, e.g. "1H"
+    pub nucleus: String,
+    /// Indirect-dimension nucleus for 2D data
+    pub indirect_nucleus: Option<String>,
+    pub observe_freq_mhz: f64,
+    pub peaks: Vec<ToolPeak>,
+    pub multiplets: Vec<ToolMultiplet>,
+    pub cross_peaks: Vec<CrossPeak>,
+}
+
+impl ToolRequest {
+    pub fn new(spectrum: &SpectrumData, peaks: &[[f64; 2]], multiplets: &[Multiplet], cross_peaks: &[CrossPeak]) -> Self {
+        let axis = spectrum.axes.first();
+        Self {
+            version: PROTOCOL_VERSION,
+            sample_name: spectrum.sample_name.clone(),
+            nucleus: axis.map(|a| a.nucleus.to_string()).unwrap_or_default(),
+            indirect_nucleus: spectrum.is_2d().then(|| spectrum.axes.get(1)).flatten().map(|a| a.nucleus.to_string()),
+            observe_freq_mhz: axis.map(|a| a.observe_freq_mhz).unwrap_or_default(),
+            peaks: peaks.iter().map(|p| ToolPeak { ppm: p[0], intensity: p[1] }).collect(),
+            multiplets: multiplets
+                .iter()
+                .map(|m| ToolMultiplet { ppm: m.center_ppm, j_hz: m.j_hz, lines: m.num_lines, label: m.label.clone() })
+                .collect(),
+            cross_peaks: cross_peaks.to_vec(),
+        }
+    }
+}
+
+/// A predicted line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stick {
+    pub ppm: f64,
+    /// Relative height; the tallest stick is drawn at the spectrum's maximum
+    #[serde(default = "unit_intensity")]
+    pub intensity: f64,
+    #[serde(default)]
+    pub label: String,
+}
+
+fn unit_intensity() -> f64 {
+    1.0
+}
+
+/// A suggested assignment of a shift
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Assignment {
+    pub ppm: f64,
+    pub label: String,
+}
+
+/// What the tool writes on stdout; every field is optional
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolResponse {
+    pub sticks: Vec<Stick>,
+    pub assignments: Vec<Assignment>,
+    /// Free text shown to the user
+    pub message: String,
+}
+
+impl ToolResponse {
+    /// Parse the output of `tool`, dropping non-finite shifts
+    pub fn parse(tool: &str, stdout: &str) -> NmrResult<Self> {
+        let mut response: Self = serde_json::from_str(stdout.trim()).map_err(|e| NmrError::ExternalTool {
+            tool: tool.to_string(),
+            message: format!("output is not a valid response: {}", e),
+        })?;
+        response.sticks.retain(|s| s.ppm.is_finite() && s.intensity.is_finite());
+        response.assignments.retain(|a| a.ppm.is_finite());
+        Ok(response)
+    }
+}
+
+/// Run `tool` with `request` on stdin and parse its answer
+pub fn run_tool(tool: &ExternalTool, request: &ToolRequest, cancel: Option<&CancelToken>) -> NmrResult<ToolResponse> {
+    let failed = |message: String| NmrError::ExternalTool { tool: tool.name.clone(), message };
+    if tool.program.trim().is_empty() {
+        return Err(failed("no program configured".to_string()));
+    }
+    let input = serde_json::to_vec(request).map_err(io::Error::other)?;
+    let output = run_with_input(&mut tool.command(), input, cancel).map_err(|e| match e.kind() {
+        io::ErrorKind::Interrupted => NmrError::Cancelled,
+        _ => failed(format!("could not run {}: {}", tool.command_line(), e)),
+    })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(failed(format!("failed ({}): {}", output.status, stderr.trim())));
+    }
+    ToolResponse::parse(&tool.name, &String::from_utf8_lossy(&output.stdout))
+}
+
+/// [`command::run_cancellable`] with `input` fed to the child's stdin
+fn run_with_input(cmd: &mut Command, input: Vec<u8>, cancel: Option<&CancelToken>) -> io::Result<std::process::Output> {
+    let mut child = cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    // Write on a thread: a tool that answers before reading everything
+    // must not deadlock against a full stdin pipe
+    if let Some(mut stdin) = child.stdin.take() {
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(&input);
+        });
+    }
+    command::wait_cancellable(child, cancel)
+}
+
+/// A tool running on a worker thread
+pub struct PendingTool {
+    pub tool: ExternalTool,
+    pub cancel: CancelToken,
+    result: Arc<Mutex<Option<NmrResult<ToolResponse>>>>,
+}
+
+impl PendingTool {
+    /// The tool's outcome once it has finished
+    pub fn take_result(&self) -> Option<NmrResult<ToolResponse>> {
+        self.result.lock().unwrap().take()
+    }
+}
+
+/// Run [`run_tool`] on a worker thread
+pub fn start_tool(tool: &ExternalTool, request: ToolRequest, ctx: egui::Context) -> PendingTool {
+    let cancel = CancelToken::new();
+    let result = Arc::new(Mutex::new(None));
+    let (shared, token, worker_tool) = (Arc::clone(&result), cancel.clone(), tool.clone());
+    std::thread::spawn(move || {
+        let outcome = run_tool(&worker_tool, &request, Some(&token));
+        *shared.lock().unwrap() = Some(outcome);
+        ctx.request_repaint();
+    });
+    PendingTool { tool: tool.clone(), cancel, result }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_parsing_is_lenient() {
+        let response = ToolResponse::parse(
+            "tool",
+            r#"{"sticks": [{"ppm": 2.17}, {"ppm": 7.26, "intensity": 0.5, "label": "CHCl3"}],
+                "assignments": [{"ppm": 1.2, "label": "CH3"}], "extra": true}"#,
+        )
+        .unwrap();
+        assert_eq!(response.sticks[0], Stick { ppm: 2.17, intensity: 1.0, label: String::new() });
+        assert_eq!(response.sticks[1].label, "CHCl3");
+        assert_eq!(response.assignments, vec![Assignment { ppm: 1.2, label: "CH3".to_string() }]);
+        assert_eq!(ToolResponse::parse("tool", "{}").unwrap(), ToolResponse::default());
+        assert!(ToolResponse::parse("tool", "Traceback (most recent call last):").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_tool_roundtrip_over_stdin() {
+        // `cat` echoes the request; a request parses as a (mostly empty) response
+        let spectrum = SpectrumData { sample_name: "menthol".to_string(), ..Default::default() };
+        let request = ToolRequest::new(&spectrum, &[[3.41, 10.0]], &[], &[]);
+        let cat = ExternalTool { name: "echo".to_string(), program: "cat".to_string(), args: String::new() };
+        assert_eq!(run_tool(&cat, &request, None).unwrap(), ToolResponse::default());
+
+        let script = r#"read -r _; echo '{"sticks":[{"ppm":1.0,"intensity":2.0}],"message":"ok"}'"#;
+        let out = run_with_input(Command::new("sh").args(["-c", script]), b"{}\n".to_vec(), None).unwrap();
+        assert!(out.status.success());
+        let response = ToolResponse::parse("sh", &String::from_utf8_lossy(&out.stdout)).unwrap();
+        assert_eq!((response.sticks[0].intensity, response.message.as_str()), (2.0, "ok"));
+
+        let failing = ExternalTool { name: "false".to_string(), program: "false".to_string(), args: String::new() };
+        let e = run_tool(&failing, &request, None).unwrap_err();
+        assert!(matches!(&e, NmrError::ExternalTool { tool, .. } if tool == "false"), "{:?}", e);
+        assert!(e.to_string().starts_with("false: failed"), "{}", e);
+        assert!(run_tool(&ExternalTool::default(), &request, None).is_err());
+    }
+}
@@ -0,0 +1,371 @@
+//! Command server for acquisition software and scripts.
+//!
+//! `nmr_gui --listen [address]` starts the GUI with a JSON-RPC 2.0 server
+//! on a loopback TCP port, so the spectrometer console or a LIMS script can
+//! have the running GUI open each dataset as it finishes, process it and
+//! write the results.  Requests and responses are one JSON object per line:
+//!
+//! ```text
+//! → {"jsonrpc": "2.0", "id": 1, "token": "…", "method": "open", "params": {"path": "/data/exp/10"}}
+//! ← {"jsonrpc": "2.0", "id": 1, "result": {"file": "/data/exp/10", "experiment": "1H", …}}
+//! ```
+//!
+//! | Method    | Params                                            | Result                 |
+//! |-----------|---------------------------------------------------|------------------------|
+//! | `open`    | `path` of a dataset or project                    | status, once loaded    |
+//! | `process` | none (the experiment's processing profile), or `steps` as in a batch script's `[processing]` | status |
+//! | `export`  | `stem` of the output files, `targets` as in a batch script's `export` | files written |
+//! | `status`  | none                                              | status                 |
+//!
+//! Requests of one connection run in order, each after the previous one
+//! (including its load) has finished.  Only loopback addresses are accepted,
+//! as the server reads and writes files on the caller's behalf.  For the
+//! same reason every request carries the `token` of the session, printed at
+//! startup and written to [`TOKEN_FILE`] in the config directory, readable
+//! only by the user.  A line without it, or one that is not a JSON-RPC
+//! request at all, is answered once and the connection closed.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::script::{ScriptExport, ScriptProcessing};
+
+/// Port the server listens on when `--listen` names none
+pub const DEFAULT_PORT: u16 = 7373;
+
+/// File in the config directory holding the token of the running server
+pub const TOKEN_FILE: &str = "ipc_token";
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The GUI could not carry out a valid request
+const COMMAND_FAILED: i64 = -32000;
+/// The request did not carry the session token
+const UNAUTHORIZED: i64 = -32001;
+
+/// A command for the GUI
+#[derive(Debug, Clone, PartialEq)]
+pub enum IpcCommand {
+    /// Load a dataset or project
+    Open { path: PathBuf },
+    /// Run the experiment's processing profile, or the given steps
+    Process { steps: Option<ScriptProcessing> },
+    /// Write exports of the current data next to `stem`
+    Export { stem: PathBuf, targets: Vec<ScriptExport> },
+    /// Report what is loaded
+    Status,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OpenParams {
+    path: PathBuf,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ProcessParams {
+    steps: Option<ScriptProcessing>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExportParams {
+    stem: PathBuf,
+    targets: Vec<ScriptExport>,
+}
+
+/// A request waiting for the GUI, with the way back to its caller
+pub struct IpcRequest {
+    pub command: IpcCommand,
+    reply: Sender<Result<Value, String>>,
+}
+
+impl IpcRequest {
+    /// Answer the caller; a caller that hung up is ignored
+    pub fn reply(self, result: Result<Value, String>) {
+        let _ = self.reply.send(result);
+    }
+}
+
+/// The listening server; requests arrive through [`IpcServer::try_next`]
+pub struct IpcServer {
+    pub address: SocketAddr,
+    requests: Receiver<IpcRequest>,
+}
+
+impl IpcServer {
+    /// Next request, if one is waiting
+    pub fn try_next(&self) -> Option<IpcRequest> {
+        self.requests.try_recv().ok()
+    }
+}
+
+/// Address for `--listen`: `host:port`, a bare port, or the default port
+/// on 127.0.0.1.  Only loopback addresses are allowed.
+pub fn parse_listen_address(arg: Option<&str>) -> Result<SocketAddr, String> {
+    let address = match arg {
+        None => SocketAddr::from(([127, 0, 0, 1], DEFAULT_PORT)),
+        Some(text) => match text.parse::<u16>() {
+            Ok(port) => SocketAddr::from(([127, 0, 0, 1], port)),
+            Err(_) => text
+                .parse::<SocketAddr>()
+                .map_err(|_| format!("not an address or port: {}", text))?,
+        },
+    };
+    if !address.ip().is_loopback() {
+        return Err(format!("{} is not a loopback address; the command server only listens locally", address));
+    }
+    Ok(address)
+}
+
+/// A fresh random token for one run of the server
+pub fn new_token() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+/// Write `token` to [`TOKEN_FILE`] in `dir`, readable and writable only by
+/// the user, replacing the previous session's
+pub fn write_token_file(dir: &Path, token: &str) -> io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(TOKEN_FILE);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // An older file keeps its mode when opened; tighten it first
+        if path.exists() {
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    let mut file = options.open(&path)?;
+    writeln!(file, "{}", token)?;
+    Ok(path)
+}
+
+/// Bind `address` and serve connections on background threads, accepting
+/// requests that carry `token`.  `wake` is called whenever a request is
+/// queued, to repaint the GUI.
+pub fn start(
+    address: SocketAddr,
+    token: String,
+    wake: impl Fn() + Send + Sync + Clone + 'static,
+) -> io::Result<IpcServer> {
+    let listener = TcpListener::bind(address)?;
+    let address = listener.local_addr()?;
+    let (sender, requests) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let (sender, wake, token) = (sender.clone(), wake.clone(), token.clone());
+                    thread::spawn(move || {
+                        if let Err(e) = serve_connection(stream, &token, &sender, &wake) {
+                            log::warn!("Command connection closed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => log::warn!("Command server: {}", e),
+            }
+        }
+    });
+    log::info!("Command server listening on {}", address);
+    Ok(IpcServer { address, requests })
+}
+
+fn serve_connection(stream: TcpStream, token: &str, sender: &Sender<IpcRequest>, wake: &impl Fn()) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match parse_request(&line, token) {
+            Ok((id, command)) => {
+                let (reply, answer) = mpsc::channel();
+                if sender.send(IpcRequest { command, reply }).is_err() {
+                    // The GUI has closed
+                    return Ok(());
+                }
+                wake();
+                match answer.recv() {
+                    Ok(Ok(result)) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                    Ok(Err(message)) => error_response(id, COMMAND_FAILED, &message),
+                    Err(_) => error_response(id, COMMAND_FAILED, "the request was dropped"),
+                }
+            }
+            Err((id, code, message)) => {
+                // Not a request from a client holding the token: a
+                // misbehaving tool or another protocol (an HTTP POST from a
+                // web page) talking to the port; stop listening to it
+                if matches!(code, PARSE_ERROR | INVALID_REQUEST | UNAUTHORIZED) {
+                    log::warn!("Command connection rejected: {}", message);
+                    writeln!(writer, "{}", error_response(id, code, &message))?;
+                    return Ok(());
+                }
+                error_response(id, code, &message)
+            }
+        };
+        writeln!(writer, "{}", response)?;
+    }
+    Ok(())
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+/// Parse one request line, which must carry `token`, into its id and
+/// command, or the id, JSON-RPC error code and message to answer with
+pub fn parse_request(line: &str, token: &str) -> Result<(Value, IpcCommand), (Value, i64, String)> {
+    let request: Value = serde_json::from_str(line).map_err(|e| (Value::Null, PARSE_ERROR, e.to_string()))?;
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    if request.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+        return Err((id, INVALID_REQUEST, "not a JSON-RPC 2.0 request".to_string()));
+    }
+    if !request.get("token").and_then(Value::as_str).is_some_and(|given| same_token(given, token)) {
+        return Err((id, UNAUTHORIZED, "missing or wrong token".to_string()));
+    }
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        return Err((id, INVALID_REQUEST, "missing method".to_string()));
+    };
+    let params = request.get("params").cloned().unwrap_or_else(|| json!({}));
+    let invalid = |e: serde_json::Error| (id.clone(), INVALID_PARAMS, format!("{}: {}", method, e));
+    let command = match method {
+        "open" => {
+            let p: OpenParams = serde_json::from_value(params).map_err(invalid)?;
+            IpcCommand::Open { path: p.path }
+        }
+        "process" => {
+            let p: ProcessParams = serde_json::from_value(params).map_err(invalid)?;
+            IpcCommand::Process { steps: p.steps }
+        }
+        "export" => {
+            let p: ExportParams = serde_json::from_value(params).map_err(invalid)?;
+            IpcCommand::Export { stem: p.stem, targets: p.targets }
+        }
+        "status" => IpcCommand::Status,
+        other => return Err((id, METHOD_NOT_FOUND, format!("unknown method: {}", other))),
+    };
+    Ok((id, command))
+}
+
+/// Compare tokens in time independent of where they first differ
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_server_round_trip() {
+        // Loopback only, with the default port when none is given
+        assert_eq!(parse_listen_address(None).unwrap().port(), DEFAULT_PORT);
+        assert_eq!(parse_listen_address(Some("8000")).unwrap().to_string(), "127.0.0.1:8000");
+        assert!(parse_listen_address(Some("[::1]:8000")).is_ok());
+        assert!(parse_listen_address(Some("0.0.0.0:8000")).is_err());
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let gui = crate::cli::gui_args(&args(&["--listen", "7400", "data.jdf"])).unwrap();
+        assert_eq!((gui.listen.unwrap().port(), gui.open.unwrap()), (7400, "data.jdf".into()));
+        let gui = crate::cli::gui_args(&args(&["--listen", "data.jdf"])).unwrap();
+        assert_eq!((gui.listen.unwrap().port(), gui.open.unwrap()), (DEFAULT_PORT, "data.jdf".into()));
+
+        let (id, command) = parse_request(
+            r#"{"jsonrpc": "2.0", "id": 3, "token": "t0", "method": "export", "params": {"stem": "/out/a", "targets": ["png", "log"]}}"#,
+            "t0",
+        )
+        .unwrap();
+        assert_eq!(id, 3);
+        assert_eq!(command, IpcCommand::Export { stem: "/out/a".into(), targets: vec![ScriptExport::Png, ScriptExport::Log] });
+
+        // Valid requests reach the GUI side and its answers go back to the
+        // caller; malformed ones are answered by the server itself
+        let server = start("127.0.0.1:0".parse().unwrap(), "t0".to_string(), || {}).unwrap();
+        let stream = std::net::TcpStream::connect(server.address).unwrap();
+        let gui = std::thread::spawn(move || {
+            for _ in 0..2 {
+                let request = loop {
+                    match server.try_next() {
+                        Some(request) => break request,
+                        None => std::thread::sleep(std::time::Duration::from_millis(5)),
+                    }
+                };
+                let result = match &request.command {
+                    IpcCommand::Status => Ok(serde_json::json!({"file": null})),
+                    IpcCommand::Process { steps } => {
+                        assert!(steps.as_ref().unwrap().auto_phase);
+                        Err("no data loaded".to_string())
+                    }
+                    other => panic!("unexpected command {:?}", other),
+                };
+                request.reply(result);
+            }
+        });
+        let mut writer = stream.try_clone().unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        let mut call = |line: &str| -> serde_json::Value {
+            writeln!(writer, "{}", line).unwrap();
+            serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap()
+        };
+        assert_eq!(call(r#"{"jsonrpc": "2.0", "id": 1, "token": "t0", "method": "reboot"}"#)["error"]["code"], -32601);
+        assert_eq!(call(r#"{"jsonrpc": "2.0", "id": 2, "token": "t0", "method": "open", "params": {}}"#)["error"]["code"], -32602);
+        let status = call(r#"{"jsonrpc": "2.0", "id": 4, "token": "t0", "method": "status"}"#);
+        assert_eq!(status["id"], 4);
+        assert!(status["result"]["file"].is_null());
+        let failed = call(r#"{"jsonrpc": "2.0", "id": 5, "token": "t0", "method": "process", "params": {"steps": {"auto_phase": true}}}"#);
+        assert_eq!(failed["error"]["code"], -32000);
+        assert_eq!(failed["error"]["message"], "no data loaded");
+        gui.join().unwrap();
+    }
+
+    #[test]
+    fn test_command_server_rejects_unauthenticated_connections() {
+        let token = new_token();
+        assert_eq!(token.len(), 32);
+        assert_ne!(token, new_token());
+        let dir = std::env::temp_dir().join(format!("nmr_ipc_token_{}", std::process::id()));
+        let path = write_token_file(&dir, &token).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().trim(), token);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let server = start("127.0.0.1:0".parse().unwrap(), token.clone(), || {}).unwrap();
+        let status = format!(r#"{{"jsonrpc": "2.0", "id": 9, "token": "{}", "method": "status"}}"#, token);
+        // Each connection gets one error reply and is closed; the valid
+        // request sent after it is never read
+        for (first, code) in [
+            (r#"{"jsonrpc": "2.0", "id": 1, "method": "status"}"#.to_string(), -32001),
+            (r#"{"jsonrpc": "2.0", "id": 1, "token": "guess", "method": "status"}"#.to_string(), -32001),
+            ("POST / HTTP/1.1".to_string(), -32700),
+            (format!(r#"{{"id": 1, "token": "{}", "method": "status"}}"#, token), -32600),
+        ] {
+            let stream = std::net::TcpStream::connect(server.address).unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut lines = BufReader::new(stream).lines();
+            writeln!(writer, "{}", first).unwrap();
+            let reply: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+            assert_eq!(reply["error"]["code"], code, "{}", first);
+            let _ = writeln!(writer, "{}", status);
+            assert!(!matches!(lines.next(), Some(Ok(_))), "{}", first);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(server.try_next().is_none());
+    }
+}
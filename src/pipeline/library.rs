@@ -0,0 +1,172 @@
+//! Reference spectra library.
+//!
+//! Processed 1D spectra can be saved as named references (solvents,
+//! starting materials, known impurities) and overlaid on the current
+//! spectrum for quick identification.  Each reference is a small JSON file
+//! in the per-user config directory holding the ppm trace, decimated and
+//! normalised to a maximum of 1.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::spectrum::{Nucleus, SpectrumData};
+use crate::pipeline::processing;
+
+/// Points kept per reference trace
+const REFERENCE_POINTS: usize = 8000;
+
+/// A saved reference spectrum
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReferenceSpectrum {
+    pub name: String,
+    pub nucleus: Nucleus,
+    pub observe_freq_mhz: f64,
+    /// When it was saved (RFC 3339)
+    pub saved: String,
+    /// [ppm, intensity] with the tallest point at 1.0
+    pub points: Vec<[f64; 2]>,
+}
+
+impl ReferenceSpectrum {
+    /// Reference trace of a processed 1D spectrum; `None` for FIDs, 2D data
+    /// or an all-zero spectrum
+    pub fn from_spectrum(name: &str, spectrum: &SpectrumData) -> Option<Self> {
+        if !spectrum.is_frequency_domain || spectrum.is_2d() {
+            return None;
+        }
+        let axis = spectrum.axes.first()?;
+        let max = spectrum.real.iter().fold(0.0f64, |m, v| m.max(v.abs()));
+        if max <= 0.0 || !max.is_finite() {
+            return None;
+        }
+        let trace: Vec<[f64; 2]> = spectrum
+            .real
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| [axis.index_to_ppm(i), v / max])
+            .collect();
+        Some(Self {
+            name: name.trim().to_string(),
+            nucleus: axis.nucleus.clone(),
+            observe_freq_mhz: axis.observe_freq_mhz,
+            saved: chrono::Local::now().to_rfc3339(),
+            points: processing::lttb(&trace, REFERENCE_POINTS),
+        })
+    }
+}
+
+/// File name for a reference: the name with anything unusual replaced
+fn file_name(name: &str) -> String {
+    let stem: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    format!("{}.json", stem)
+}
+
+/// References stored in one directory
+#[derive(Debug, Clone)]
+pub struct ReferenceLibrary {
+    pub dir: PathBuf,
+    /// Sorted by name
+    pub entries: Vec<ReferenceSpectrum>,
+}
+
+impl ReferenceLibrary {
+    /// Per-user library directory (next to the preferences)
+    pub fn default_dir() -> PathBuf {
+        crate::gui::preferences::Preferences::config_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("references")
+    }
+
+    /// Read every reference in `dir`; unreadable files are skipped
+    pub fn open(dir: &Path) -> Self {
+        let mut entries = Vec::new();
+        if let Ok(read) = fs::read_dir(dir) {
+            for path in read.flatten().map(|e| e.path()) {
+                if path.extension().is_some_and(|e| e == "json") {
+                    match fs::read_to_string(&path).map(|t| serde_json::from_str::<ReferenceSpectrum>(&t)) {
+                        Ok(Ok(r)) => entries.push(r),
+                        _ => log::warn!("Skipping unreadable reference {}", path.display()),
+                    }
+                }
+            }
+        }
+        entries.sort_by_key(|r| r.name.to_lowercase());
+        Self { dir: dir.to_path_buf(), entries }
+    }
+
+    /// Save a reference, replacing one with the same name
+    pub fn add(&mut self, reference: ReferenceSpectrum) -> io::Result<()> {
+        if reference.name.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Reference name is empty"));
+        }
+        fs::create_dir_all(&self.dir)?;
+        let json = serde_json::to_string(&reference)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(self.dir.join(file_name(&reference.name)), json)?;
+        self.entries.retain(|r| r.name != reference.name);
+        self.entries.push(reference);
+        self.entries.sort_by_key(|r| r.name.to_lowercase());
+        Ok(())
+    }
+
+    /// Delete a reference by name
+    pub fn remove(&mut self, name: &str) -> io::Result<()> {
+        let path = self.dir.join(file_name(name));
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        self.entries.retain(|r| r.name != name);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::spectrum::AxisParams;
+
+    #[test]
+    fn test_reference_library_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("nmr_library_test_{}", uuid::Uuid::new_v4()));
+        let spectrum = SpectrumData {
+            is_frequency_domain: true,
+            axes: vec![AxisParams { num_points: 20000, spectral_width_hz: 4000.0, reference_ppm: 10.0, ..Default::default() }],
+            real: (0..20000).map(|i| if i == 13000 { 250.0 } else { (i % 7) as f64 }).collect(),
+            ..Default::default()
+        };
+
+        let mut library = ReferenceLibrary::open(&dir);
+        assert!(library.entries.is_empty());
+        let dmso = ReferenceSpectrum::from_spectrum(" DMSO-d6 / H2O ", &spectrum).unwrap();
+        assert_eq!(dmso.name, "DMSO-d6 / H2O");
+        assert!(dmso.points.len() <= REFERENCE_POINTS);
+        // The tallest line survives decimation at full height
+        let top = dmso.points.iter().cloned().fold([0.0, 0.0], |a, p| if p[1] > a[1] { p } else { a });
+        assert_eq!(top[1], 1.0);
+        assert!((top[0] - 3.5).abs() < 1e-9);
+        library.add(dmso.clone()).unwrap();
+        library.add(ReferenceSpectrum { name: "acetone".to_string(), ..dmso.clone() }).unwrap();
+
+        let reopened = ReferenceLibrary::open(&dir);
+        assert_eq!(reopened.entries.len(), 2);
+        assert_eq!(reopened.entries[0].name, "acetone");
+        // JSON text keeps the trace to within rounding
+        let back = &reopened.entries[1];
+        assert_eq!((&back.name, &back.nucleus, &back.saved), (&dmso.name, &dmso.nucleus, &dmso.saved));
+        assert_eq!(back.points.len(), dmso.points.len());
+        assert!(back.points.iter().zip(&dmso.points).all(|(a, b)| (a[0] - b[0]).abs() < 1e-12 && (a[1] - b[1]).abs() < 1e-12));
+
+        library.remove("acetone").unwrap();
+        assert_eq!(ReferenceLibrary::open(&dir).entries.len(), 1);
+        assert!(library.add(ReferenceSpectrum { name: String::new(), ..dmso }).is_err());
+        assert!(ReferenceSpectrum::from_spectrum("fid", &SpectrumData::default()).is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+//! Background loading of a dataset.
+//!
+//! Vendor conversions can take a long time (external delta2pipe/bruk2pipe/
+//! var2pipe on large 2D data), so loading runs on a worker thread while the
+//! GUI shows a progress overlay.  Cancelling kills the external converter,
+//! removes its partial outputs and discards the result, leaving the
+//! previously loaded data untouched.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::data::bruker;
+use crate::data::spectrum::{SpectrumData, VendorFormat};
+use crate::error::{NmrError, NmrResult};
+use crate::gui::conversion_dialog::ConversionSettings;
+use crate::log::reproducibility::{OpMetrics, ReproLog};
+use super::cache::ConversionCache;
+use super::command::CancelToken;
+use super::conversion;
+
+/// Outcome of a background load, with the log it produced.
+pub struct LoadResult {
+    pub spectrum: NmrResult<SpectrumData>,
+    pub log: ReproLog,
+}
+
+/// Handle to a load running in the background.
+pub struct PendingLoad {
+    pub path: PathBuf,
+    pub started: Instant,
+    /// The loaded spectrum joins the session instead of replacing the
+    /// active one; set by the caller
+    pub add_to_session: bool,
+    cancel: CancelToken,
+    result: Arc<Mutex<Option<LoadResult>>>,
+}
+
+impl PendingLoad {
+    /// Kill any running converter and discard the result.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// The result, once the worker thread has finished.
+    pub fn take_result(&self) -> Option<LoadResult> {
+        self.result.lock().unwrap().take()
+    }
+}
+
+/// Start loading `path` on a worker thread.
+///
+/// `log` is the fresh log for the new session; conversion entries are added
+/// to it and returned with the result.  `cache` enables the conversion cache.
+pub fn start_load(
+    path: &Path,
+    mut log: ReproLog,
+    mut settings: ConversionSettings,
+    cache: Option<ConversionCache>,
+    ctx: egui::Context,
+) -> PendingLoad {
+    let cancel = CancelToken::new();
+    settings.cancel = Some(cancel.clone());
+
+    let result = Arc::new(Mutex::new(None));
+    let shared = Arc::clone(&result);
+    let token = cancel.clone();
+    let source = path.to_path_buf();
+
+    std::thread::spawn(move || {
+        let log_start = log.len();
+        let start = Instant::now();
+        let mut spectrum = match &cache {
+            Some(cache) => conversion::load_spectrum_cached(&source, &mut log, Some(&settings), cache),
+            None => conversion::load_spectrum(&source, &mut log, Some(&settings)),
+        };
+
+        // Built-in readers cannot be interrupted; drop their result instead
+        if token.is_cancelled() && spectrum.is_ok() {
+            spectrum = Err(NmrError::Cancelled);
+        }
+        if let Ok(s) = &spectrum {
+            log.attach_metrics(
+                log_start,
+                OpMetrics {
+                    duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+                    points_in: raw_input_points(&source),
+                    points_out: s.total_points(),
+                },
+            );
+        }
+
+        *shared.lock().unwrap() = Some(LoadResult { spectrum, log });
+        ctx.request_repaint();
+    });
+
+    PendingLoad {
+        path: path.to_path_buf(),
+        started: Instant::now(),
+        add_to_session: false,
+        cancel,
+        result,
+    }
+}
+
+/// Size of the raw input in data points, for the load metrics: TD (times
+/// the F1 TD for a ser file) for Bruker, otherwise the data file size in
+/// 32-bit words.
+fn raw_input_points(path: &Path) -> usize {
+    if conversion::detect_format(path) == VendorFormat::Bruker {
+        let dir = if path.is_dir() { path } else { path.parent().unwrap_or(path) };
+        if let Ok((params, _)) = bruker::read_bruker_params(dir) {
+            return params.td * params.td_f1.max(1);
+        }
+    }
+    let data_file = if path.is_dir() { path.join("fid") } else { path.to_path_buf() };
+    std::fs::metadata(data_file).map_or(0, |m| m.len() as usize / 4)
+}
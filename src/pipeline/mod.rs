@@ -1,6 +1,21 @@
+pub mod batch;
+pub mod bundle;
+pub mod cache;
 pub mod command;
 pub mod conversion;
+pub mod external;
+pub mod ipc;
+pub mod library;
+pub mod loader;
+pub mod open;
 pub mod processing;
+pub mod profiles;
+pub mod relink;
+pub mod script;
+pub mod template;
+pub mod tools;
+pub mod validation;
+pub mod workdir;
 
 #[cfg(test)]
 mod tests {
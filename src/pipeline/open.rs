@@ -0,0 +1,267 @@
+//! One entry point for everything that can be opened.
+//!
+//! The Open button, drag-and-drop and a path on the command line all go
+//! through [`resolve`], which decides what a path is: a saved project, a
+//! single dataset (file or vendor folder), a Bruker sample folder holding
+//! several experiments, a zip archive to extract first, or a folder with
+//! several datasets for the user to choose from.  NMRPipe series patterns
+//! (`test%03d.ft2`) resolve to the first plane, from which the loader
+//! finds the rest.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::data::bruker::{self, BrukerExperiment};
+use crate::data::spectrum::VendorFormat;
+use crate::error::{NmrError, NmrResult};
+
+use super::bundle;
+use super::conversion;
+
+/// What opening a path should do
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpenTarget {
+    /// A saved `.nmrproj` project
+    Project(PathBuf),
+    /// A data file or vendor folder to convert and load
+    Dataset(PathBuf),
+    /// A Bruker sample folder: the user picks an experiment
+    BrukerSample(PathBuf, Vec<BrukerExperiment>),
+    /// A zip archive, to be extracted and resolved again
+    Archive(PathBuf),
+    /// A folder with several datasets: the user picks one
+    Choose(PathBuf, Vec<Candidate>),
+}
+
+/// A dataset or project offered when a folder holds several
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub path: PathBuf,
+    /// Format shown in the list ("Bruker", "Project", "NMRPipe series (3 planes)", …)
+    pub kind: String,
+}
+
+impl Candidate {
+    /// Describe `path` if it can be opened on its own
+    pub fn for_path(path: &Path) -> Option<Candidate> {
+        let kind = if is_project(path) {
+            "Project".to_string()
+        } else if is_archive(path) {
+            "Zip archive".to_string()
+        } else if path.is_dir() {
+            match conversion::detect_format(path) {
+                format @ (VendorFormat::Bruker | VendorFormat::Varian) => format.to_string(),
+                _ => match bruker::list_experiments(path).len() {
+                    0 => return None,
+                    n => format!("Bruker sample ({} experiments)", n),
+                },
+            }
+        } else if conversion::is_nmr_file(path) {
+            match conversion::discover_nmrpipe_planes(path).len() {
+                1 => conversion::detect_format(path).to_string(),
+                n => format!("NMRPipe series ({} planes)", n),
+            }
+        } else {
+            return None;
+        };
+        Some(Candidate { path: path.to_path_buf(), kind })
+    }
+}
+
+fn has_extension(path: &Path, ext: &str) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case(ext))
+}
+
+fn is_project(path: &Path) -> bool {
+    if has_extension(path, bundle::BUNDLE_EXTENSION) {
+        return bundle::project_file_in(path).is_file();
+    }
+    has_extension(path, "nmrproj") && path.is_file()
+}
+
+fn is_archive(path: &Path) -> bool {
+    has_extension(path, "zip") && path.is_file()
+}
+
+/// Decide what opening `path` should do
+pub fn resolve(path: &Path) -> NmrResult<OpenTarget> {
+    if let Some(first) = series_first_plane(path)? {
+        return Ok(OpenTarget::Dataset(first));
+    }
+    if !path.exists() {
+        return Err(NmrError::NotFound(path.to_path_buf()));
+    }
+    if is_project(path) {
+        return Ok(OpenTarget::Project(if path.is_dir() { bundle::project_file_in(path) } else { path.to_path_buf() }));
+    }
+    if is_archive(path) {
+        return Ok(OpenTarget::Archive(path.to_path_buf()));
+    }
+    if !path.is_dir() {
+        return Ok(OpenTarget::Dataset(path.to_path_buf()));
+    }
+    if matches!(conversion::detect_format(path), VendorFormat::Bruker | VendorFormat::Varian) {
+        return Ok(OpenTarget::Dataset(path.to_path_buf()));
+    }
+    let experiments = bruker::list_experiments(path);
+    if !experiments.is_empty() {
+        return Ok(OpenTarget::BrukerSample(path.to_path_buf(), experiments));
+    }
+    let mut candidates = folder_candidates(path);
+    match candidates.len() {
+        0 => Err(NmrError::UnsupportedFormat(path.to_path_buf())),
+        1 => resolve(&candidates.remove(0).path),
+        _ => Ok(OpenTarget::Choose(path.to_path_buf(), candidates)),
+    }
+}
+
+/// Datasets and projects directly inside `dir`: project files and
+/// bundles, data files (one entry per plane series), vendor and sample
+/// folders, and archives
+pub fn folder_candidates(dir: &Path) -> Vec<Candidate> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    paths.sort();
+    let mut candidates: Vec<Candidate> = Vec::new();
+    // Later planes of a series are covered by the first
+    let mut covered: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        if covered.contains(&path) {
+            continue;
+        }
+        if let Some(candidate) = Candidate::for_path(&path) {
+            if path.is_file() {
+                covered.extend(conversion::discover_nmrpipe_planes(&path));
+            }
+            candidates.push(candidate);
+        }
+    }
+    candidates
+}
+
+/// First existing file of an NMRPipe series pattern such as `test%03d.ft2`
+/// (`None` if the file name holds no pattern, or names an existing file)
+fn series_first_plane(path: &Path) -> NmrResult<Option<PathBuf>> {
+    if path.exists() {
+        return Ok(None);
+    }
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return Ok(None);
+    };
+    let Some(start) = name.find('%') else {
+        return Ok(None);
+    };
+    let Some(len) = name[start + 1..].find('d') else {
+        return Ok(None);
+    };
+    let spec = &name[start + 1..start + 1 + len];
+    if !spec.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(None);
+    }
+    // `%03d` pads to three digits; `%d` and `%3d` match any number
+    let width: Option<usize> = if spec.starts_with('0') { spec.parse().ok() } else { None };
+    let (prefix, suffix) = (&name[..start], &name[start + 2 + len..]);
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let mut planes: Vec<(u64, PathBuf)> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| {
+            let file = e.file_name().to_str()?.to_string();
+            let digits = file.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            if !digits.chars().all(|c| c.is_ascii_digit()) || width.is_some_and(|w| digits.len() != w) {
+                return None;
+            }
+            Some((digits.parse().ok()?, e.path()))
+        })
+        .collect();
+    planes.sort();
+    match planes.into_iter().next() {
+        Some((_, first)) => Ok(Some(first)),
+        None => Err(NmrError::NotFound(path.to_path_buf())),
+    }
+}
+
+/// Extract `archive` into `dest` and return the folder to open: the
+/// archive's single top-level folder, or `dest` itself
+pub fn extract_archive(archive: &Path, dest: &Path) -> io::Result<PathBuf> {
+    let mut zip = zip::ZipArchive::new(fs::File::open(archive)?)?;
+    fs::create_dir_all(dest)?;
+    // `extract` refuses entry names that would escape `dest`
+    zip.extract(dest)?;
+    let top: Vec<PathBuf> = fs::read_dir(dest)?
+        .flatten()
+        .filter(|e| e.file_name() != "__MACOSX")
+        .map(|e| e.path())
+        .collect();
+    match top.as_slice() {
+        [only] if only.is_dir() => Ok(only.clone()),
+        _ => Ok(dest.to_path_buf()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_resolve_dispatches_by_content() {
+        let dir = std::env::temp_dir().join(format!("nmr_open_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let touch = |name: &str| {
+            let p = dir.join(name);
+            fs::create_dir_all(p.parent().unwrap()).unwrap();
+            fs::write(&p, b"").unwrap();
+            p
+        };
+
+        // A lone data file in a folder opens directly
+        let jdf = touch("one/sample.jdf");
+        assert_eq!(resolve(&dir.join("one")).unwrap(), OpenTarget::Dataset(jdf.clone()));
+        assert_eq!(resolve(&jdf).unwrap(), OpenTarget::Dataset(jdf));
+
+        // Projects, plain and bundled
+        let project = touch("p/run.nmrproj");
+        assert_eq!(resolve(&project).unwrap(), OpenTarget::Project(project.clone()));
+        let bundled = touch("b/run.nmrpkg/run.nmrproj");
+        assert_eq!(resolve(&dir.join("b/run.nmrpkg")).unwrap(), OpenTarget::Project(bundled));
+
+        // A plane series counts once and resolves from its pattern
+        let planes: Vec<PathBuf> = (1..=3).map(|i| touch(&format!("many/test{:03}.ft2", i))).collect();
+        touch("many/other.jdx");
+        touch("many/expt/acqus");
+        touch("many/notes.txt");
+        match resolve(&dir.join("many")) {
+            Ok(OpenTarget::Choose(_, candidates)) => {
+                let kinds: Vec<&str> = candidates.iter().map(|c| c.kind.as_str()).collect();
+                assert_eq!(kinds, ["Bruker", "JCAMP-DX", "NMRPipe series (3 planes)"]);
+            }
+            other => panic!("expected a choice, got {:?}", other),
+        }
+        assert_eq!(resolve(&dir.join("many/test%03d.ft2")).unwrap(), OpenTarget::Dataset(planes[0].clone()));
+        assert!(matches!(resolve(&dir.join("many/none%03d.ft2")), Err(NmrError::NotFound(_))));
+        touch("empty/notes.txt");
+        assert!(matches!(resolve(&dir.join("empty")), Err(NmrError::UnsupportedFormat(_))));
+
+        // Archives extract to their top-level folder, which then resolves
+        let zip_path = dir.join("data.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        zip.start_file("data/sample.jdx", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"##TITLE=x").unwrap();
+        zip.finish().unwrap();
+        assert_eq!(resolve(&zip_path).unwrap(), OpenTarget::Archive(zip_path.clone()));
+        let root = extract_archive(&zip_path, &dir.join("unzipped")).unwrap();
+        assert_eq!(root, dir.join("unzipped/data"));
+        assert_eq!(resolve(&root).unwrap(), OpenTarget::Dataset(root.join("sample.jdx")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
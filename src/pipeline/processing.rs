@@ -13,6 +13,7 @@ use rustfft::FftPlanner;
 use serde::{Deserialize, Serialize};
 
 use crate::data::spectrum::*;
+use crate::error::{NmrError, NmrResult};
 use crate::log::reproducibility::ReproLog;
 use super::command::NmrPipeCommand;
 
@@ -51,12 +52,155 @@ pub enum ProcessingOp {
     Apodization(WindowFunction),
     ZeroFill { target_size: usize },
     FourierTransform { use_imaginary: bool },
-    FourierTransform2D,
+    FourierTransform2D { mode: Ft2dMode },
+    InverseFourierTransform,
     PhaseCorrection { ph0: f64, ph1: f64 },
+    PhaseCorrection2D { dim: PhaseDim, ph0: f64, ph1: f64 },
     AutoPhase,
-    BaselineCorrection,
+    BaselineCorrection { method: BaselineMethod },
     ManualBaselineCorrection { num_points: usize },
     SolventSuppression { center_ppm: f64, width_ppm: f64 },
+    /// Regions picked on the spectrum, as (low, high) ppm
+    SolventSuppressionRegions { regions: Vec<(f64, f64)> },
+    ReferenceDeconvolution { center_ppm: f64, width_ppm: f64, target_lw_hz: f64 },
+    CenterGlitchRemoval { ppm: f64 },
+    ReplaceNonFinite { fix: NonFiniteFix, count: usize },
+    DeadTimeCorrection { points: usize, mode: DeadTimeMode },
+    LinearPrediction { order: usize, predicted: usize, mode: LpMode },
+    NusReconstruction { iterations: usize },
+    GroupDelayCorrection { grpdly: f64 },
+    T1NoiseSuppression { method: T1NoiseMethod, strength: f64 },
+    Magnitude2D,
+    /// Processing profile of an experiment type, run start to finish
+    QuickProcess { experiment: ExperimentType },
+    /// Steps of a batch-script `[processing]` table, sent by a remote caller
+    ProcessingSteps,
+    /// `-fn` steps of an imported NMRPipe script
+    NmrPipeScript { steps: usize },
+    /// Indirect axis moved by `shift_ppm` onto a known shift
+    F1Calibration { shift_ppm: f64 },
+    /// Steps of a saved processing template, run in order
+    Template { name: String, steps: Vec<ProcessingOp> },
+}
+
+/// How the corrupted first points of a FID (probe ring-down, receiver
+/// dead time) are handled before the transform
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DeadTimeMode {
+    /// Left-shift the FID by N points, zero-filling the end
+    Drop,
+    /// Rebuild the first N points by backward linear prediction
+    BackwardLp,
+}
+
+impl std::fmt::Display for DeadTimeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeadTimeMode::Drop => write!(f, "drop"),
+            DeadTimeMode::BackwardLp => write!(f, "backward LP"),
+        }
+    }
+}
+
+/// Which linear-prediction coefficients extend a FID (NMRPipe `LP -f`,
+/// `-b`, `-fb`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LpMode {
+    /// Each point fitted to the points before it
+    Forward,
+    /// Each point fitted to the points after it; the roots are inverted
+    /// to predict forward
+    Backward,
+    /// Average of the forward and backward coefficients, the most stable
+    /// against noise
+    Mixed,
+}
+
+impl LpMode {
+    /// NMRPipe `LP` flag for the mode
+    pub fn nmrpipe_flag(self) -> &'static str {
+        match self {
+            LpMode::Forward => "-f",
+            LpMode::Backward => "-b",
+            LpMode::Mixed => "-fb",
+        }
+    }
+}
+
+impl std::fmt::Display for LpMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LpMode::Forward => write!(f, "forward"),
+            LpMode::Backward => write!(f, "backward"),
+            LpMode::Mixed => write!(f, "forward-backward"),
+        }
+    }
+}
+
+/// Dimension of a 2D spectrum that a phase correction runs along
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PhaseDim {
+    /// Direct dimension: along each row
+    #[default]
+    F2,
+    /// Indirect dimension: along each column
+    F1,
+}
+
+impl std::fmt::Display for PhaseDim {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PhaseDim::F2 => write!(f, "F2"),
+            PhaseDim::F1 => write!(f, "F1"),
+        }
+    }
+}
+
+/// What the 2D Fourier transform keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Ft2dMode {
+    /// √(re² + im²): no phasing needed, broader lines, phase lost
+    Magnitude,
+    /// Real and imaginary parts are kept, so the spectrum can be phased
+    PhaseSensitive,
+}
+
+impl Ft2dMode {
+    /// Usual mode for an experiment: COSY and HMBC are normally acquired
+    /// for magnitude display, everything else phase-sensitive
+    pub fn default_for(experiment: &ExperimentType) -> Self {
+        match experiment {
+            ExperimentType::Cosy | ExperimentType::Hmbc => Ft2dMode::Magnitude,
+            _ => Ft2dMode::PhaseSensitive,
+        }
+    }
+}
+
+impl std::fmt::Display for Ft2dMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ft2dMode::Magnitude => write!(f, "magnitude"),
+            Ft2dMode::PhaseSensitive => write!(f, "phase-sensitive"),
+        }
+    }
+}
+
+/// How the t1-noise ridge of a 2D spectrum is estimated
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum T1NoiseMethod {
+    /// Median of each F2 column over all F1 rows
+    ColumnMedian,
+    /// Mean of the quietest F1 rows (mostly ridge, little signal)
+    ReferenceRows,
+}
+
+impl std::fmt::Display for T1NoiseMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            T1NoiseMethod::ColumnMedian => write!(f, "column median"),
+            T1NoiseMethod::ReferenceRows => write!(f, "reference rows"),
+        }
+    }
 }
 
 impl std::fmt::Display for ProcessingOp {
@@ -71,18 +215,51 @@ impl std::fmt::Display for ProcessingOp {
                     write!(f, "Fourier Transform (Real-only)")
                 }
             }
-            ProcessingOp::FourierTransform2D => write!(f, "2D Fourier Transform (Magnitude)"),
+            ProcessingOp::FourierTransform2D { mode } => write!(f, "2D Fourier Transform ({})", mode),
+            ProcessingOp::InverseFourierTransform => write!(f, "Inverse Fourier Transform"),
             ProcessingOp::PhaseCorrection { ph0, ph1 } => {
                 write!(f, "Phase Correction (PH0={:.1}°, PH1={:.1}°)", ph0, ph1)
             }
+            ProcessingOp::PhaseCorrection2D { dim, ph0, ph1 } => {
+                write!(f, "2D Phase Correction ({}: PH0={:.1}°, PH1={:.1}°)", dim, ph0, ph1)
+            }
             ProcessingOp::AutoPhase => write!(f, "Automatic Phase Correction"),
-            ProcessingOp::BaselineCorrection => write!(f, "Baseline Correction"),
+            ProcessingOp::BaselineCorrection { method } => write!(f, "Baseline Correction ({})", method),
             ProcessingOp::ManualBaselineCorrection { num_points } => {
                 write!(f, "Manual Baseline Correction ({} points)", num_points)
             }
             ProcessingOp::SolventSuppression { center_ppm, width_ppm } => {
                 write!(f, "Solvent Suppression ({:.2} ± {:.2} ppm)", center_ppm, width_ppm)
             }
+            ProcessingOp::SolventSuppressionRegions { regions } => {
+                write!(f, "Solvent Suppression ({} regions)", regions.len())
+            }
+            ProcessingOp::ReferenceDeconvolution { center_ppm, width_ppm, target_lw_hz } => {
+                write!(f, "Reference Deconvolution ({:.3} ± {:.3} ppm, {:.2} Hz)", center_ppm, width_ppm / 2.0, target_lw_hz)
+            }
+            ProcessingOp::CenterGlitchRemoval { ppm } => write!(f, "Center Glitch Removal ({:.3} ppm)", ppm),
+            ProcessingOp::ReplaceNonFinite { fix, count } => write!(f, "Replace NaN/Inf ({} values, {})", count, fix),
+            ProcessingOp::DeadTimeCorrection { points, mode } => {
+                write!(f, "Dead Time Correction ({} points, {})", points, mode)
+            }
+            ProcessingOp::LinearPrediction { order, predicted, mode } => {
+                write!(f, "Linear Prediction (+{} points, {} order {})", predicted, mode, order)
+            }
+            ProcessingOp::NusReconstruction { iterations } => {
+                write!(f, "NUS Reconstruction (IST, {} iterations)", iterations)
+            }
+            ProcessingOp::GroupDelayCorrection { grpdly } => {
+                write!(f, "Digital Filter Correction (GRPDLY {:.3})", grpdly)
+            }
+            ProcessingOp::T1NoiseSuppression { method, strength } => {
+                write!(f, "t1 Noise Suppression ({}, {:.0}%)", method, strength * 100.0)
+            }
+            ProcessingOp::Magnitude2D => write!(f, "2D Magnitude"),
+            ProcessingOp::QuickProcess { experiment } => write!(f, "Quick Process ({} profile)", experiment),
+            ProcessingOp::ProcessingSteps => write!(f, "Processing steps"),
+            ProcessingOp::NmrPipeScript { steps } => write!(f, "NMRPipe script ({} steps)", steps),
+            ProcessingOp::F1Calibration { shift_ppm } => write!(f, "F1 Calibration ({:+.4} ppm)", shift_ppm),
+            ProcessingOp::Template { name, steps } => write!(f, "Template '{}' ({} steps)", name, steps.len()),
         }
     }
 }
@@ -209,856 +386,7158 @@ pub fn zero_fill(
     );
 }
 
-/// Next power of two >= n
-pub fn next_power_of_two(n: usize) -> usize {
-    let mut p = 1;
-    while p < n {
-        p <<= 1;
-    }
-    p
+// =========================================================================
+//  Memory Estimates
+// =========================================================================
+
+/// Rough size in bytes of the data arrays of `spectrum` after `op`, so a
+/// step that grows the data can be checked against the memory budget
+/// before it runs.  Steps that keep the size return the current size.
+pub fn estimated_heap_bytes(spectrum: &SpectrumData, op: &ProcessingOp) -> usize {
+    const F64: usize = std::mem::size_of::<f64>();
+    let current = spectrum.heap_bytes();
+    let rows = spectrum.data_2d.len();
+    let cols = spectrum.data_2d.first().map_or(0, Vec::len);
+    let estimate = match op {
+        ProcessingOp::ZeroFill { target_size } if !spectrum.is_2d() && !spectrum.real.is_empty() => {
+            current / spectrum.real.len() * target_size
+        }
+        // Real and imaginary (or four hypercomplex quadrants of half the
+        // rows), both dimensions padded to a power of two
+        ProcessingOp::FourierTransform2D { .. } if rows > 0 => {
+            2 * next_power_of_two(rows) * next_power_of_two(cols) * F64
+        }
+        ProcessingOp::LinearPrediction { predicted, .. } if spectrum.is_2d() && rows > 0 => {
+            let per = spectrum.f1_acquisition.rows_per_increment();
+            current + 2 * predicted.next_multiple_of(per) * cols * F64
+        }
+        ProcessingOp::LinearPrediction { predicted, .. } => current + 2 * predicted * F64,
+        ProcessingOp::NusReconstruction { .. } => match &spectrum.nus_schedule {
+            Some(schedule) => 2 * schedule.grid * schedule.rows_per_increment.max(1) * cols * F64,
+            None => current,
+        },
+        _ => current,
+    };
+    estimate.max(current)
 }
 
 // =========================================================================
-//  Fourier Transform
+//  Dead Time / First-Point Correction
 // =========================================================================
 
-/// Apply complex FFT to the FID data, converting to frequency domain
-pub fn fourier_transform(
+/// Largest LP order used for backward prediction
+const DEAD_TIME_LP_ORDER: usize = 16;
+
+/// Points of good data the backward-LP coefficients are fitted to
+const DEAD_TIME_LP_FIT: usize = 256;
+
+/// Remove or rebuild the first `points` complex points of the FID (every
+/// F2 row for 2D data).  Distorted leading points give a rolling baseline
+/// after FT, which matters most for broad 19F/31P and semi-solid signals.
+pub fn dead_time_correct(
     spectrum: &mut SpectrumData,
-    use_imaginary: bool,
+    points: usize,
+    mode: DeadTimeMode,
     log: &mut ReproLog,
 ) {
-    if spectrum.is_frequency_domain {
-        log::warn!("Data is already in frequency domain, skipping FT");
-        return;
-    }
-
-    let n = spectrum.real.len();
-    if n == 0 {
+    if points == 0 || spectrum.is_frequency_domain {
         return;
     }
 
-    // Ensure power of 2
-    let fft_size = next_power_of_two(n);
-    spectrum.real.resize(fft_size, 0.0);
-    spectrum.imag.resize(fft_size, 0.0);
-
-    // Build complex buffer
-    let mut buffer: Vec<Complex<f64>> = if use_imaginary && !spectrum.imag.is_empty() {
-        spectrum
-            .real
-            .iter()
-            .zip(spectrum.imag.iter())
-            .map(|(&r, &i)| Complex::new(r, i))
-            .collect()
-    } else {
-        spectrum
-            .real
-            .iter()
-            .map(|&r| Complex::new(r, 0.0))
-            .collect()
+    let fix = |re: &mut Vec<f64>, im: &mut Vec<f64>| match mode {
+        DeadTimeMode::Drop => {
+            for v in [re, im] {
+                if !v.is_empty() {
+                    let n = points.min(v.len());
+                    v.drain(..n);
+                    v.resize(v.len() + n, 0.0);
+                }
+            }
+        }
+        DeadTimeMode::BackwardLp => backward_lp(re, im, points),
     };
 
-    // First-point correction: multiply the first complex point by 0.5
-    // This removes the DC-offset artifact that appears at the edges of the
-    // spectrum (standard NMR convention, equivalent to NMRPipe FT -auto).
-    if !buffer.is_empty() {
-        buffer[0] *= 0.5;
-    }
-
-    // Execute FFT
-    let mut planner = FftPlanner::new();
-    let fft = planner.plan_fft_forward(fft_size);
-    fft.process(&mut buffer);
-
-    // FFT shift (swap halves so 0 Hz is in the center)
-    let half = fft_size / 2;
-    let mut shifted = vec![Complex::new(0.0, 0.0); fft_size];
-    for i in 0..fft_size {
-        shifted[i] = buffer[(i + half) % fft_size];
-    }
-
-    // Reverse so that index 0 = highest frequency (downfield / high ppm)
-    // This matches the ppm_scale convention: index_to_ppm(0) = reference_ppm
-    shifted.reverse();
-
-    // Extract real and imaginary
-    spectrum.real = shifted.iter().map(|c| c.re).collect();
-    spectrum.imag = shifted.iter().map(|c| c.im).collect();
-
-    // Auto-sign correction: if the spectrum is predominantly negative,
-    // apply a 180° phase flip so absorption peaks point upward.
-    let pos_sum: f64 = spectrum.real.iter().filter(|&&v| v > 0.0).sum();
-    let neg_sum: f64 = spectrum.real.iter().filter(|&&v| v < 0.0).map(|v| v.abs()).sum();
-    if neg_sum > pos_sum * 1.5 {
-        for v in spectrum.real.iter_mut() {
-            *v = -*v;
+    let rows = if spectrum.is_2d() && !spectrum.data_2d.is_empty() {
+        spectrum.data_2d_imag.resize(spectrum.data_2d.len(), Vec::new());
+        for (re, im) in spectrum.data_2d.iter_mut().zip(spectrum.data_2d_imag.iter_mut()) {
+            fix(re, im);
         }
-        for v in spectrum.imag.iter_mut() {
-            *v = -*v;
+        spectrum.data_2d.len()
+    } else {
+        if spectrum.real.len() <= points {
+            return;
         }
-    }
-    spectrum.is_frequency_domain = true;
-
-    if let Some(ax) = spectrum.axes.first_mut() {
-        ax.num_points = fft_size;
-    }
+        fix(&mut spectrum.real, &mut spectrum.imag);
+        1
+    };
 
-    let nmrpipe_cmd = if use_imaginary {
-        "nmrPipe -fn FT -auto".to_string()
-    } else {
-        "nmrPipe -fn FT -real".to_string()
+    let n = spectrum.data_2d.first().map_or(spectrum.real.len(), |r| r.len());
+    let (order, fit) = lp_order_and_fit(n, points);
+    let nmrpipe_cmd = match mode {
+        DeadTimeMode::Drop => format!("nmrPipe -fn SHIFT -ls {}", points),
+        DeadTimeMode::BackwardLp => format!(
+            "nmrPipe -fn LP -before -x1 {} -xn {} -pred {} -ord {}",
+            points + 1,
+            points + fit,
+            points,
+            order
+        ),
+    };
+    let how = match mode {
+        DeadTimeMode::Drop => "Left-shifted FID, zero-filling the end".to_string(),
+        DeadTimeMode::BackwardLp => format!(
+            "Replaced by backward linear prediction (order {}, fitted to points {}–{})",
+            order,
+            points + 1,
+            points + fit
+        ),
     };
     log.add_entry(
-        "Fourier Transform",
+        "Dead Time Correction",
         &format!(
-            "{} FFT ({} → {} points, with FFT shift)",
-            if use_imaginary { "Complex" } else { "Real-only" },
-            n,
-            fft_size
+            "First {} points of {} FID{}: {}",
+            points,
+            rows,
+            if rows == 1 { "" } else { "s" },
+            how
         ),
         &nmrpipe_cmd,
     );
 }
 
 // =========================================================================
-//  2D Fourier Transform
+//  Digital Filter (Group Delay)
 // =========================================================================
 
-/// Apply 2D FFT to a 2D time-domain spectrum (e.g. COSY, HSQC, HMBC).
-///
-/// Pipeline:
-///   1. FFT along F2 (direct / rows) — each row is a complex FID
-///   2. FFT along F1 (indirect / columns) — transpose, FFT each column
-///   3. Compute magnitude: sqrt(re² + im²) for display
+/// Remove the digital-filter group delay of `grpdly` points (Bruker
+/// GRPDLY) that leaves a "burp" at the start of the FID and a smile at the
+/// edges of the spectrum.
 ///
-/// After processing `data_2d` contains the magnitude spectrum and
-/// `data_2d_imag` is cleared.  `is_frequency_domain` is set to `true`.
-pub fn fourier_transform_2d(
-    spectrum: &mut SpectrumData,
-    log: &mut ReproLog,
-) {
+/// A FID (every F2 row for 2D data) is shifted left by the fractional
+/// delay through a phase ramp on its spectrum, following NMRPipe's `dmx()`
+/// as ported in `nmrpipe_io::dfcorrect`; the last ⌈grpdly⌉ points, which
+/// wrapped around, are zeroed.  A 1D spectrum transformed with the delay
+/// still in it gets the matching first-order phase instead.
+pub fn correct_group_delay(spectrum: &mut SpectrumData, grpdly: f64, log: &mut ReproLog) -> NmrResult<()> {
+    if grpdly.is_nan() || grpdly <= 0.0 {
+        return Err(NmrError::InvalidInput(format!("group delay of {} points", grpdly)));
+    }
     if spectrum.is_frequency_domain {
-        log::warn!("2D data is already in frequency domain, skipping FT");
-        return;
+        if spectrum.is_2d() {
+            return Err(NmrError::NotApplicable(
+                "The group delay of 2D data is removed before the 2D FT".to_string(),
+            ));
+        }
+        if spectrum.imag.len() != spectrum.real.len() {
+            return Err(NmrError::NotApplicable(
+                "the spectrum has no imaginary data to phase with (magnitude mode?)".to_string(),
+            ));
+        }
+        // The FT puts frequency bin N/2 - 1 - i at point i; with the -180°·g
+        // that dmx() leaves, the ramp is -2π·g·(i + 1)/N
+        let n = spectrum.real.len() as f64;
+        let ph1 = -2.0 * PI * grpdly;
+        let ph0 = ph1 / n;
+        for (i, (re, im)) in spectrum.real.iter_mut().zip(spectrum.imag.iter_mut()).enumerate() {
+            let (sin_p, cos_p) = (ph0 + ph1 * i as f64 / n).sin_cos();
+            let (r, m) = (*re, *im);
+            *re = r * cos_p - m * sin_p;
+            *im = r * sin_p + m * cos_p;
+        }
+        let (ph0_deg, ph1_deg) = (ph0.to_degrees().rem_euclid(360.0), ph1.to_degrees());
+        log.add_entry(
+            "Digital Filter Correction",
+            &format!(
+                "Removed group delay of {:.3} points as a phase ramp (PH0={:.2}°, PH1={:.2}°)",
+                grpdly, ph0_deg, ph1_deg
+            ),
+            &format!("nmrPipe -fn PS -p0 {:.2} -p1 {:.2} -di", ph0_deg, ph1_deg),
+        );
+    } else {
+        let rows = if spectrum.is_2d() && !spectrum.data_2d.is_empty() {
+            spectrum.data_2d_imag.resize(spectrum.data_2d.len(), Vec::new());
+            for (re, im) in spectrum.data_2d.iter_mut().zip(spectrum.data_2d_imag.iter_mut()) {
+                shift_out_group_delay(re, im, grpdly);
+            }
+            spectrum.data_2d.len()
+        } else {
+            shift_out_group_delay(&mut spectrum.real, &mut spectrum.imag, grpdly);
+            1
+        };
+        log.add_entry(
+            "Digital Filter Correction",
+            &format!(
+                "Removed group delay of {:.3} points from {} FID{} (FFT phase ramp, as bruk2pipe -DMX)",
+                grpdly,
+                rows,
+                if rows == 1 { "" } else { "s" }
+            ),
+            &format!("# bruk2pipe -DMX -grpdly {:.4}", grpdly),
+        );
     }
+    spectrum.group_delay = None;
+    Ok(())
+}
 
-    let n_rows = spectrum.data_2d.len();
-    if n_rows == 0 {
-        return;
-    }
-    let n_cols = spectrum.data_2d[0].len();
-    if n_cols == 0 {
+/// Shift one complex FID left by `grpdly` points: inverse FFT, phase ramp
+/// about the centre, forward FFT, as NMRPipe `dmx()`.  The first point is
+/// doubled and the ⌈grpdly⌉ wrapped points at the end are zeroed.
+fn shift_out_group_delay(re: &mut [f64], im: &mut Vec<f64>, grpdly: f64) {
+    let len = re.len();
+    if len == 0 {
         return;
     }
+    im.resize(len, 0.0);
+    let n = next_power_of_two(len);
+    let mut buf: Vec<Complex<f64>> = re.iter().zip(im.iter()).map(|(&r, &i)| Complex::new(r, i)).collect();
+    buf.resize(n, Complex::new(0.0, 0.0));
 
-    let has_imag = !spectrum.data_2d_imag.is_empty()
-        && spectrum.data_2d_imag.len() == n_rows;
-
-    // ── Step 1: FFT along F2 (rows) ──
-    let fft_cols = next_power_of_two(n_cols);
     let mut planner = FftPlanner::new();
-    let fft_f2 = planner.plan_fft_forward(fft_cols);
-
-    // Store complex result matrix (rows × fft_cols)
-    let mut re_2d = vec![vec![0.0f64; fft_cols]; n_rows];
-    let mut im_2d = vec![vec![0.0f64; fft_cols]; n_rows];
-
-    for row_idx in 0..n_rows {
-        let row_len = spectrum.data_2d[row_idx].len();
-        let mut buffer: Vec<Complex<f64>> = Vec::with_capacity(fft_cols);
+    planner.plan_fft_inverse(n).process(&mut buf);
+    buf.rotate_left(n / 2);
+    for (k, z) in buf.iter_mut().enumerate() {
+        *z *= Complex::from_polar(1.0 / n as f64, -2.0 * PI * grpdly * k as f64 / n as f64);
+    }
+    buf.rotate_left(n / 2);
+    planner.plan_fft_forward(n).process(&mut buf);
+    buf[0] *= 2.0;
 
-        for col in 0..fft_cols {
-            if col < row_len {
-                let re = spectrum.data_2d[row_idx][col];
-                let im = if has_imag && col < spectrum.data_2d_imag[row_idx].len() {
-                    spectrum.data_2d_imag[row_idx][col]
-                } else {
-                    0.0
-                };
-                buffer.push(Complex::new(re, im));
-            } else {
-                buffer.push(Complex::new(0.0, 0.0)); // zero-pad
-            }
-        }
+    let valid = len.saturating_sub(grpdly.ceil() as usize);
+    for (i, (r, m)) in re.iter_mut().zip(im.iter_mut()).enumerate() {
+        let z = if i < valid { buf[i] } else { Complex::new(0.0, 0.0) };
+        *r = z.re;
+        *m = z.im;
+    }
+}
 
-        // First-point correction (standard NMR convention)
-        if !buffer.is_empty() {
-            buffer[0] *= 0.5;
-        }
+/// LP order and fit length for a FID of `n` points with `points` bad ones
+fn lp_order_and_fit(n: usize, points: usize) -> (usize, usize) {
+    let fit = n.saturating_sub(points).min(DEAD_TIME_LP_FIT);
+    let order = DEAD_TIME_LP_ORDER.min(fit / 4).max(1);
+    (order, fit)
+}
 
-        fft_f2.process(&mut buffer);
+/// Replace the first `points` points of a complex FID with a backward
+/// linear prediction from the points that follow them.
+///
+/// The coefficients are a least-squares fit of each good point to the
+/// `order` points after it, so the prediction runs from the first good
+/// point back to the start.  Real-only data is predicted as is.
+fn backward_lp(re: &mut [f64], im: &mut [f64], points: usize) {
+    let n = re.len();
+    let (order, fit) = lp_order_and_fit(n, points);
+    if fit < 2 * order + 1 {
+        return;
+    }
+    let z: Vec<Complex<f64>> = (0..n)
+        .map(|i| Complex::new(re[i], im.get(i).copied().unwrap_or(0.0)))
+        .collect();
+    let Some(coeffs) = lp_fit(&z[points..points + fit], order, true) else {
+        return;
+    };
 
-        // FFT-shift (swap halves)
-        let half = fft_cols / 2;
-        for i in 0..fft_cols {
-            let si = (i + half) % fft_cols;
-            re_2d[row_idx][i] = buffer[si].re;
-            im_2d[row_idx][i] = buffer[si].im;
+    let mut out = z;
+    for i in (0..points).rev() {
+        let value: Complex<f64> = coeffs.iter().enumerate().map(|(j, a)| a * out[i + 1 + j]).sum();
+        out[i] = value;
+    }
+    for i in 0..points {
+        re[i] = out[i].re;
+        if i < im.len() {
+            im[i] = out[i].im;
         }
     }
+}
 
-    // ── Step 2: FFT along F1 (columns) ──
-    let fft_rows = next_power_of_two(n_rows);
-    let fft_f1 = planner.plan_fft_forward(fft_rows);
-
-    // Extend rows if needed (zero-pad in F1 dimension)
-    re_2d.resize(fft_rows, vec![0.0; fft_cols]);
-    im_2d.resize(fft_rows, vec![0.0; fft_cols]);
-
-    for col_idx in 0..fft_cols {
-        // Build column vector
-        let mut col_buf: Vec<Complex<f64>> = Vec::with_capacity(fft_rows);
-        for row_idx in 0..fft_rows {
-            col_buf.push(Complex::new(re_2d[row_idx][col_idx], im_2d[row_idx][col_idx]));
+/// Least-squares linear-prediction coefficients of `z`: forward
+/// `z[k] = Σ a[j]·z[k−1−j]`, or backward `z[k] = Σ a[j]·z[k+1+j]`.
+/// `None` if `z` is too short for the order or the fit is singular.
+fn lp_fit(z: &[Complex<f64>], order: usize, backward: bool) -> Option<Vec<Complex<f64>>> {
+    if order == 0 || z.len() < 2 * order + 1 {
+        return None;
+    }
+    // Normal equations  (AᴴA) a = Aᴴb  over every point with `order` neighbours
+    let mut ata = vec![vec![Complex::new(0.0, 0.0); order]; order];
+    let mut atb = vec![Complex::new(0.0, 0.0); order];
+    for k in 0..z.len() - order {
+        let (target, regressor) = if backward {
+            (z[k], &z[k + 1..k + 1 + order])
+        } else {
+            (z[k + order], &z[k..k + order])
+        };
+        // Forward regressors run backwards in time: a[0] weighs z[k−1]
+        let at = |j: usize| if backward { regressor[j] } else { regressor[order - 1 - j] };
+        for r in 0..order {
+            let ar = at(r).conj();
+            atb[r] += ar * target;
+            for (c, cell) in ata[r].iter_mut().enumerate() {
+                *cell += ar * at(c);
+            }
         }
+    }
+    // Light Tikhonov damping keeps noisy fits stable
+    let damping = (0..order).map(|i| ata[i][i].re).sum::<f64>() / order as f64 * 1e-8;
+    for (i, row) in ata.iter_mut().enumerate() {
+        row[i] += damping;
+    }
+    solve_linear(ata, atb)
+}
 
-        // First-point correction in F1
-        if !col_buf.is_empty() {
-            col_buf[0] *= 0.5;
+/// Roots of the monic polynomial `x^p + c[0]·x^(p−1) + … + c[p−1]`
+/// (Durand–Kerner iteration)
+fn polynomial_roots(c: &[Complex<f64>]) -> Vec<Complex<f64>> {
+    let p = c.len();
+    let radius = 1.0 + c.iter().map(|v| v.norm()).fold(0.0, f64::max);
+    let seed = Complex::new(0.4, 0.9);
+    let mut roots: Vec<Complex<f64>> = (0..p).map(|k| seed.powu(k as u32) * radius / (1.0 + k as f64).sqrt()).collect();
+    let eval = |x: Complex<f64>| c.iter().fold(Complex::new(1.0, 0.0), |acc, &v| acc * x + v);
+    for _ in 0..500 {
+        let mut change: f64 = 0.0;
+        for i in 0..p {
+            let denom: Complex<f64> = (0..p).filter(|&j| j != i).map(|j| roots[i] - roots[j]).product();
+            if denom.norm() < 1e-300 {
+                continue;
+            }
+            let step = eval(roots[i]) / denom;
+            roots[i] -= step;
+            change = change.max(step.norm());
         }
-
-        fft_f1.process(&mut col_buf);
-
-        // FFT-shift (swap halves)
-        let half = fft_rows / 2;
-        for row_idx in 0..fft_rows {
-            let si = (row_idx + half) % fft_rows;
-            re_2d[row_idx][col_idx] = col_buf[si].re;
-            im_2d[row_idx][col_idx] = col_buf[si].im;
+        if change < 1e-12 * radius {
+            break;
         }
     }
+    roots
+}
 
-    // ── Step 3: Compute magnitude and reverse axes ──
-    // Reverse each row so index 0 → highest ppm (matches 1D convention)
-    let mut magnitude = vec![vec![0.0f64; fft_cols]; fft_rows];
-    for row_idx in 0..fft_rows {
-        for col_idx in 0..fft_cols {
-            let re = re_2d[row_idx][col_idx];
-            let im = im_2d[row_idx][col_idx];
-            // Reverse column direction (so high ppm = left = index 0)
-            magnitude[row_idx][fft_cols - 1 - col_idx] = (re * re + im * im).sqrt();
+/// Forward coefficients `a` (as in [`lp_fit`]) of the predictor whose
+/// characteristic roots are `roots`
+fn lp_from_roots(roots: &[Complex<f64>]) -> Vec<Complex<f64>> {
+    // Expand Π (x − r) = x^p + c[0]·x^(p−1) + …; the predictor is a = −c
+    let mut poly = vec![Complex::new(1.0, 0.0)];
+    for &r in roots {
+        let mut next = poly.clone();
+        next.push(Complex::new(0.0, 0.0));
+        for (k, &v) in poly.iter().enumerate() {
+            next[k + 1] -= r * v;
         }
+        poly = next;
     }
+    poly[1..].iter().map(|v| -v).collect()
+}
 
-    // Reverse row order for F1 (so high ppm = top = index 0)
-    magnitude.reverse();
+/// Forward prediction coefficients of `z` for `mode`.  Roots outside the
+/// unit circle (growing signals) are reflected inside (NMRPipe `-fix`), so
+/// the prediction always decays.
+fn lp_coefficients(z: &[Complex<f64>], order: usize, mode: LpMode) -> Option<Vec<Complex<f64>>> {
+    let stable = |roots: Vec<Complex<f64>>| -> Vec<Complex<f64>> {
+        roots.into_iter().map(|r| if r.norm() > 1.0 { r / r.norm_sqr() } else { r }).collect()
+    };
+    let forward = || -> Option<Vec<Complex<f64>>> {
+        let a = lp_fit(z, order, false)?;
+        let c: Vec<Complex<f64>> = a.iter().map(|v| -v).collect();
+        Some(lp_from_roots(&stable(polynomial_roots(&c))))
+    };
+    // Backward roots s belong to 1/r of the forward signal roots r
+    let backward = || -> Option<Vec<Complex<f64>>> {
+        let b = lp_fit(z, order, true)?;
+        let c: Vec<Complex<f64>> = b.iter().map(|v| -v).collect();
+        let roots = polynomial_roots(&c)
+            .into_iter()
+            .filter(|s| s.norm() > 1e-12)
+            .map(|s| s.inv())
+            .collect();
+        Some(lp_from_roots(&stable(roots)))
+    };
+    match mode {
+        LpMode::Forward => forward(),
+        LpMode::Backward => backward(),
+        LpMode::Mixed => {
+            let (f, b) = (forward()?, backward()?);
+            (f.len() == b.len()).then(|| f.iter().zip(&b).map(|(x, y)| (x + y) * 0.5).collect())
+        }
+    }
+}
 
-    // Store result
-    spectrum.data_2d = magnitude;
-    spectrum.data_2d_imag.clear();
-    spectrum.is_frequency_domain = true;
+/// `z` followed by `predicted` linearly predicted points (`None` when
+/// `z` is too short for the order)
+fn lp_extend(z: &[Complex<f64>], order: usize, predicted: usize, mode: LpMode) -> Option<Vec<Complex<f64>>> {
+    let a = lp_coefficients(z, order, mode)?;
+    let mut out = z.to_vec();
+    for _ in 0..predicted {
+        let k = out.len();
+        let value: Complex<f64> = a.iter().enumerate().map(|(j, c)| c * out[k - 1 - j]).sum();
+        out.push(value);
+    }
+    Some(out)
+}
 
-    // Also set the 1D projection (first row) for the status bar
-    spectrum.real = spectrum.data_2d.first().cloned().unwrap_or_default();
-    spectrum.imag.clear();
+/// The t1 series of a 2D FID as NMRPipe's F1 linear prediction and hmsIST
+/// see them: taken after an F2 FT of every row, one complex interferogram
+/// per F2 point and increment.  The cosine and sine rows of a States-type
+/// pair become the real and imaginary part of one point, separately for
+/// the real and imaginary F2 part; TPPI rows are two real series; complex
+/// (QF) rows are one series of the F2 spectrum.  Changed increments are
+/// written back as time-domain rows in the original acquisition layout.
+struct F1Interferograms {
+    acquisition: F1Acquisition,
+    cols: usize,
+    /// Per F2 point: the series of the real F2 part, then (except for
+    /// complex rows) of the imaginary one
+    series: Vec<Vec<Complex<f64>>>,
+}
 
-    // Update axis sizes
-    if let Some(ax) = spectrum.axes.get_mut(0) {
-        ax.num_points = fft_cols;
+impl F1Interferograms {
+    /// The interferograms of the rows of `spectrum`, laid out as
+    /// `acquisition`
+    fn from_fid(spectrum: &SpectrumData, mut acquisition: F1Acquisition) -> NmrResult<Self> {
+        let rows = spectrum.data_2d.len();
+        let cols = spectrum.data_2d.first().map_or(0, Vec::len);
+        // As the 2D FT does with a single row
+        if acquisition.rows_per_increment() == 2 && rows < 2 {
+            acquisition = F1Acquisition::Complex;
+        }
+        if !rows.is_multiple_of(acquisition.rows_per_increment()) {
+            return Err(NmrError::InvalidInput(format!(
+                "{} rows do not make whole {} increment pairs",
+                rows, acquisition
+            )));
+        }
+        let has_imag = spectrum.data_2d_imag.len() == rows;
+        let fft = FftPlanner::new().plan_fft_forward(cols.max(1));
+        let f2: Vec<Vec<Complex<f64>>> = (0..rows)
+            .map(|r| {
+                let mut row: Vec<Complex<f64>> = (0..cols)
+                    .map(|c| {
+                        let im = if has_imag { spectrum.data_2d_imag[r].get(c).copied().unwrap_or(0.0) } else { 0.0 };
+                        Complex::new(spectrum.data_2d[r].get(c).copied().unwrap_or(0.0), im)
+                    })
+                    .collect();
+                fft.process(&mut row);
+                row
+            })
+            .collect();
+
+        let mut series = Vec::new();
+        for c in 0..cols {
+            match acquisition {
+                F1Acquisition::Complex => series.push(f2.iter().map(|row| row[c]).collect()),
+                F1Acquisition::Tppi => {
+                    series.push(f2.iter().map(|row| Complex::new(row[c].re, 0.0)).collect());
+                    series.push(f2.iter().map(|row| Complex::new(row[c].im, 0.0)).collect());
+                }
+                _ => {
+                    let points: Vec<(Complex<f64>, Complex<f64>)> = (0..rows / 2)
+                        .map(|k| decode_pair(f2[2 * k][c], f2[2 * k + 1][c], k, acquisition))
+                        .collect();
+                    series.push(points.iter().map(|(cos, sin)| Complex::new(cos.re, sin.re)).collect());
+                    series.push(points.iter().map(|(cos, sin)| Complex::new(cos.im, sin.im)).collect());
+                }
+            }
+        }
+        Ok(Self { acquisition, cols, series })
     }
-    if let Some(ax) = spectrum.axes.get_mut(1) {
-        ax.num_points = fft_rows;
+
+    /// t1 increments in each series
+    fn increments(&self) -> usize {
+        self.series.first().map_or(0, Vec::len)
+    }
+
+    /// Replace the rows of `increments` in `spectrum` by the series,
+    /// growing the matrix to the length of the series; other rows are
+    /// left as they are
+    fn write_increments(&self, spectrum: &mut SpectrumData, increments: impl Iterator<Item = usize>) {
+        let (per, cols) = (self.acquisition.rows_per_increment(), self.cols);
+        let rows = self.increments() * per;
+        spectrum.data_2d.resize(rows, vec![0.0; cols]);
+        spectrum.data_2d_imag.resize(rows, vec![0.0; cols]);
+        let ifft = FftPlanner::new().plan_fft_inverse(cols.max(1));
+        let mut write = |r: usize, mut row: Vec<Complex<f64>>| {
+            ifft.process(&mut row);
+            spectrum.data_2d[r] = row.iter().map(|v| v.re / cols as f64).collect();
+            spectrum.data_2d_imag[r] = row.iter().map(|v| v.im / cols as f64).collect();
+        };
+        for k in increments {
+            match self.acquisition {
+                F1Acquisition::Complex => write(k, (0..cols).map(|c| self.series[c][k]).collect()),
+                F1Acquisition::Tppi => write(
+                    k,
+                    (0..cols).map(|c| Complex::new(self.series[2 * c][k].re, self.series[2 * c + 1][k].re)).collect(),
+                ),
+                _ => {
+                    let points: Vec<(Complex<f64>, Complex<f64>)> = (0..cols)
+                        .map(|c| {
+                            let (re, im) = (self.series[2 * c][k], self.series[2 * c + 1][k]);
+                            let (cos, sin) = (Complex::new(re.re, im.re), Complex::new(re.im, im.im));
+                            encode_pair(cos, sin, k, self.acquisition)
+                        })
+                        .collect();
+                    write(2 * k, points.iter().map(|p| p.0).collect());
+                    write(2 * k + 1, points.iter().map(|p| p.1).collect());
+                }
+            }
+        }
+    }
+}
+
+/// Extend truncated FIDs by `predicted` linearly predicted points before
+/// the FT, for sharper lines without truncation wiggles.  A 1D FID is
+/// extended at its end; a 2D time-domain matrix along F1, where
+/// acquisition is cut shortest: each F1 interferogram after the F2 FT
+/// (see [`F1Interferograms`]) is continued by whole t1 increments, so
+/// `predicted` rows are rounded up to complete States pairs.
+pub fn linear_predict(
+    spectrum: &mut SpectrumData,
+    order: usize,
+    predicted: usize,
+    mode: LpMode,
+    log: &mut ReproLog,
+) -> NmrResult<()> {
+    if spectrum.is_frequency_domain {
+        return Err(NmrError::NotApplicable(
+            "Linear prediction extends FIDs; transform back to the time domain first".to_string(),
+        ));
+    }
+    if predicted == 0 {
+        return Ok(());
+    }
+    if spectrum.is_2d() && !spectrum.data_2d.is_empty() {
+        let rows = spectrum.data_2d.len();
+        let mut f1 = F1Interferograms::from_fid(spectrum, spectrum.f1_acquisition)?;
+        let per = f1.acquisition.rows_per_increment();
+        let increments = f1.increments();
+        let added = predicted.div_ceil(per);
+        for series in f1.series.iter_mut() {
+            *series = lp_extend(series, order, added, mode).ok_or_else(|| {
+                NmrError::InvalidInput(format!("{} t1 increments are too few for LP order {}", increments, order))
+            })?;
+        }
+        f1.write_increments(spectrum, increments..increments + added);
+        if let Some(ax) = spectrum.axes.get_mut(1) {
+            ax.num_points = spectrum.data_2d.len();
+        }
+        log.add_entry(
+            "Linear Prediction",
+            &format!(
+                "Extended the F1 interferograms of {} F2 points from {} to {} t1 increments ({} rows, {}) after an F2 FT ({} LP, order {}, roots reflected to decay)",
+                f1.cols,
+                increments,
+                increments + added,
+                rows + added * per,
+                f1.acquisition,
+                mode,
+                order
+            ),
+            &format!(
+                "nmrPipe -fn FT  # F2\nnmrPipe -fn TP\nnmrPipe -fn LP {} -ord {} -pred {} -fix  # F1\nnmrPipe -fn TP\nnmrPipe -fn FT -inv  # F2",
+                mode.nmrpipe_flag(),
+                order,
+                added
+            ),
+        );
+        return Ok(());
     }
 
+    let n = spectrum.real.len();
+    let has_imag = spectrum.imag.len() == n;
+    let fid: Vec<Complex<f64>> = (0..n)
+        .map(|i| Complex::new(spectrum.real[i], if has_imag { spectrum.imag[i] } else { 0.0 }))
+        .collect();
+    let extended = lp_extend(&fid, order, predicted, mode)
+        .ok_or_else(|| NmrError::InvalidInput(format!("{} points are too few for LP order {}", n, order)))?;
+    spectrum.real.extend(extended[n..].iter().map(|v| v.re));
+    if has_imag {
+        spectrum.imag.extend(extended[n..].iter().map(|v| v.im));
+    }
+    if let Some(ax) = spectrum.axes.first_mut() {
+        ax.num_points = spectrum.real.len();
+    }
     log.add_entry(
-        "2D Fourier Transform",
-        &format!(
-            "Complex 2D FFT: {}×{} → {}×{} (magnitude mode)",
-            n_rows, n_cols, fft_rows, fft_cols
-        ),
+        "Linear Prediction",
         &format!(
-            "nmrPipe -fn FT -auto  # F2\nnmrPipe -fn FT -auto  # F1"
+            "Extended 1 FID from {} to {} points ({} LP, order {}, roots reflected to decay)",
+            n,
+            n + predicted,
+            mode,
+            order
         ),
+        &format!("nmrPipe -fn LP {} -ord {} -pred {} -fix", mode.nmrpipe_flag(), order, predicted),
     );
+    Ok(())
 }
 
 // =========================================================================
-//  Phase Correction
+//  Non-Uniform Sampling (NUS) Reconstruction
 // =========================================================================
 
-/// Apply zero-order and first-order phase correction
-pub fn phase_correct(
-    spectrum: &mut SpectrumData,
-    ph0_degrees: f64,
-    ph1_degrees: f64,
-    log: &mut ReproLog,
-) {
-    let n = spectrum.real.len();
-    if n == 0 {
-        return;
-    }
+/// File names a sampling schedule is looked up under, next to the data
+pub const NUSLIST_NAMES: [&str; 3] = ["nuslist", "nuslist.txt", "vclist_nus"];
 
-    let ph0 = ph0_degrees * PI / 180.0;
-    let ph1 = ph1_degrees * PI / 180.0;
+/// IST iterations used unless chosen otherwise
+pub const NUS_DEFAULT_ITERATIONS: usize = 200;
 
-    for i in 0..n {
-        let frac = i as f64 / n as f64;
-        let phase = ph0 + ph1 * frac;
-        let cos_p = phase.cos();
-        let sin_p = phase.sin();
-        let re = spectrum.real[i];
-        let im = if i < spectrum.imag.len() {
-            spectrum.imag[i]
-        } else {
-            0.0
+/// Threshold of the last IST iteration, relative to the first
+const IST_FINAL_THRESHOLD: f64 = 1e-3;
+
+/// Parse a sampling schedule: the first integer of every line is a
+/// sampled t1 increment (0-based; further columns belong to higher
+/// dimensions and `#` starts a comment).  Returned sorted and unique.
+pub fn parse_nuslist(text: &str) -> Result<Vec<usize>, String> {
+    let mut sampled = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let Some(first) = line.split_whitespace().next() else {
+            continue;
         };
-        spectrum.real[i] = re * cos_p - im * sin_p;
-        if i < spectrum.imag.len() {
-            spectrum.imag[i] = re * sin_p + im * cos_p;
+        let value = first
+            .parse::<usize>()
+            .map_err(|_| format!("line {}: '{}' is not an increment number", n + 1, first))?;
+        sampled.push(value);
+    }
+    sampled.sort_unstable();
+    sampled.dedup();
+    if sampled.is_empty() {
+        return Err("the schedule lists no increments".to_string());
+    }
+    Ok(sampled)
+}
+
+/// Find the NUS schedule of a 2D FID: a `nuslist` beside the data
+/// (Bruker, Varian and NMRPipe scripts use that name), or else the empty
+/// increments of a matrix already expanded to the full grid, as JEOL
+/// Delta writes NUS data.  `None` for uniformly sampled data.
+pub fn find_nus_schedule(spectrum: &SpectrumData, path: &Path) -> Option<NusSchedule> {
+    // Arrayed acquisitions step a parameter, not t1
+    if !spectrum.is_2d() || spectrum.is_frequency_domain || spectrum.data_2d.is_empty() || spectrum.array.is_some() {
+        return None;
+    }
+    let dir = if path.is_dir() { path } else { path.parent()? };
+    for name in NUSLIST_NAMES {
+        let file = dir.join(name);
+        let Ok(text) = std::fs::read_to_string(&file) else {
+            continue;
+        };
+        match parse_nuslist(&text) {
+            Ok(sampled) => {
+                // A matrix already expanded to the grid may be longer than
+                // the last sampled increment
+                let per = nus_rows_per_increment(&spectrum.data_2d, &sampled);
+                let grid = (spectrum.data_2d.len() / per).max(sampled[sampled.len() - 1] + 1);
+                return Some(NusSchedule { sampled, grid, rows_per_increment: per, source: file.display().to_string() });
+            }
+            Err(e) => log::warn!("Ignoring sampling schedule {}: {}", file.display(), e),
         }
     }
 
-    let nmrpipe_cmd = format!("nmrPipe -fn PS -p0 {:.2} -p1 {:.2} -di", ph0_degrees, ph1_degrees);
-    log.add_entry(
-        "Phase Correction",
-        &format!("PH0={:.2}°, PH1={:.2}°", ph0_degrees, ph1_degrees),
-        &nmrpipe_cmd,
-    );
+    // Rows of all zeros inside the acquired grid are skipped increments;
+    // a zero tail is only zero filling.  Both hold for all the rows
+    let empty = |r: usize| {
+        spectrum.data_2d[r].iter().all(|&v| v == 0.0)
+            && spectrum.data_2d_imag.get(r).is_none_or(|row| row.iter().all(|&v| v == 0.0))
+    };
+    // of every increment, which is a row pair for States-type data
+    let rows = spectrum.data_2d.len();
+    let per = spectrum.f1_acquisition.rows_per_increment();
+    let per = if rows.is_multiple_of(per) { per } else { 1 };
+    let empty_increment = |k: usize| (k * per..(k + 1) * per).all(empty);
+    let last = (0..rows / per).rev().find(|&k| !empty_increment(k))?;
+    let sampled: Vec<usize> = (0..=last).filter(|&k| !empty_increment(k)).collect();
+    (sampled.len() <= last).then(|| NusSchedule {
+        sampled,
+        grid: rows / per,
+        rows_per_increment: per,
+        source: "empty increments of the data matrix".to_string(),
+    })
 }
 
-/// Automatic phase correction using entropy minimization
-pub fn auto_phase(
-    spectrum: &mut SpectrumData,
-    log: &mut ReproLog,
-) -> (f64, f64) {
-    let n = spectrum.real.len();
-    if n == 0 {
-        return (0.0, 0.0);
+/// Rows of the matrix per t1 increment: 2 when a States pair of FIDs was
+/// acquired for each listed increment, else 1.  A matrix expanded to the
+/// grid is a pair layout when its filled rows only fit that reading.
+fn nus_rows_per_increment(data: &[Vec<f64>], sampled: &[usize]) -> usize {
+    let rows = data.len();
+    if rows == sampled.len() {
+        return 1;
     }
+    if rows == 2 * sampled.len() {
+        return 2;
+    }
+    let fits = |per: usize| {
+        data.iter()
+            .enumerate()
+            .filter(|(_, row)| row.iter().any(|&v| v != 0.0))
+            .all(|(r, _)| sampled.binary_search(&(r / per)).is_ok())
+    };
+    if !fits(1) && fits(2) { 2 } else { 1 }
+}
 
-    // Simple automatic phasing:
-    // Search for ph0 that maximizes the integral of the real part
-    // Then search for ph1 that minimizes baseline distortion
-    let mut best_ph0 = 0.0f64;
-    let mut best_score = f64::NEG_INFINITY;
-
-    // Coarse search for ph0
-    let mut ph0 = -180.0;
-    while ph0 <= 180.0 {
-        let score = evaluate_phase(spectrum, ph0, 0.0);
-        if score > best_score {
-            best_score = score;
-            best_ph0 = ph0;
+/// Iterative soft thresholding of one complex t1 series: the measured
+/// points `y` at `mask`, the rest reconstructed from the spectrum that is
+/// sparsest (fewest strong peaks) while matching the measurements
+fn ist_reconstruct(
+    y: &[Complex<f64>],
+    mask: &[bool],
+    iterations: usize,
+    planner: &mut FftPlanner<f64>,
+) -> Vec<Complex<f64>> {
+    // Work on a doubled grid, as hmsIST does, so a decaying FID is not
+    // forced to wrap around onto its start
+    let size = y.len();
+    let n = 2 * next_power_of_two(size);
+    let fft = planner.plan_fft_forward(n);
+    let ifft = planner.plan_fft_inverse(n);
+    let mut y = y.to_vec();
+    y.resize(n, Complex::new(0.0, 0.0));
+    let mut mask = mask.to_vec();
+    mask.resize(n, false);
+    let (y, mask) = (&y[..], &mask[..]);
+    let mut residual = y.to_vec();
+    let mut spectrum = vec![Complex::new(0.0, 0.0); n];
+    let mut buffer = residual.clone();
+    fft.process(&mut buffer);
+    let max0 = buffer.iter().map(|v| v.norm()).fold(0.0, f64::max);
+    if max0 == 0.0 {
+        return y[..size].to_vec();
+    }
+    let step = IST_FINAL_THRESHOLD.powf(1.0 / iterations.max(1) as f64);
+    let mut threshold = max0;
+    let mut estimate = vec![Complex::new(0.0, 0.0); n];
+    for _ in 0..iterations {
+        threshold *= step;
+        buffer.copy_from_slice(&residual);
+        fft.process(&mut buffer);
+        // Move everything above the threshold into the spectrum
+        for (s, v) in spectrum.iter_mut().zip(&buffer) {
+            let norm = v.norm();
+            if norm > threshold {
+                *s += v * ((norm - threshold) / norm);
+            }
+        }
+        estimate.copy_from_slice(&spectrum);
+        ifft.process(&mut estimate);
+        for (((r, e), &m), &yv) in residual.iter_mut().zip(estimate.iter_mut()).zip(mask).zip(y) {
+            *e /= n as f64;
+            *r = if m { yv - *e } else { Complex::new(0.0, 0.0) };
         }
-        ph0 += 5.0;
     }
+    // Keep the measured points exactly
+    estimate.iter().zip(y).zip(mask).take(size).map(|((&e, &yv), &m)| if m { yv } else { e }).collect()
+}
 
-    // Fine search around best ph0
-    let mut fine_ph0 = best_ph0 - 5.0;
-    best_score = f64::NEG_INFINITY;
-    while fine_ph0 <= best_ph0 + 5.0 {
-        let score = evaluate_phase(spectrum, fine_ph0, 0.0);
-        if score > best_score {
-            best_score = score;
-            best_ph0 = fine_ph0;
-        }
-        fine_ph0 += 0.5;
+/// Reconstruct the skipped t1 increments of NUS data by iterative soft
+/// thresholding (IST) along F1, as hmsIST does: after an F2 FT, each F1
+/// interferogram (one complex point per increment, from the cosine and
+/// sine rows of a States pair; see [`F1Interferograms`]) is reconstructed
+/// on its own, so the 2D FT sees a uniformly sampled FID.  A matrix holding
+/// only the sampled increments is first expanded onto the full grid; the
+/// measured rows are kept as they are.  Clears `nus_schedule`.
+pub fn nus_reconstruct(spectrum: &mut SpectrumData, iterations: usize, log: &mut ReproLog) -> NmrResult<()> {
+    let Some(schedule) = spectrum.nus_schedule.clone() else {
+        return Err(NmrError::NotApplicable("No NUS sampling schedule for this data".to_string()));
+    };
+    if spectrum.is_frequency_domain || spectrum.data_2d.is_empty() {
+        return Err(NmrError::NotApplicable("NUS reconstruction needs the 2D time-domain data".to_string()));
     }
+    let rows = spectrum.data_2d.len();
+    let per = schedule.rows_per_increment.max(1);
+    let full = schedule.grid * per;
+    let cols = spectrum.data_2d[0].len();
+    spectrum.data_2d_imag.resize(rows, vec![0.0; cols]);
 
-    // Search for ph1
-    let mut best_ph1 = 0.0f64;
-    best_score = f64::NEG_INFINITY;
-    let mut ph1 = -180.0;
-    while ph1 <= 180.0 {
-        let score = evaluate_phase(spectrum, best_ph0, ph1);
-        if score > best_score {
-            best_score = score;
-            best_ph1 = ph1;
+    if rows == per * schedule.sampled.len() && rows < full {
+        // Only the sampled increments were stored: spread them onto the grid
+        let mut re = vec![vec![0.0; cols]; full];
+        let mut im = vec![vec![0.0; cols]; full];
+        for (k, &inc) in schedule.sampled.iter().enumerate() {
+            for j in 0..per {
+                re[inc * per + j] = std::mem::take(&mut spectrum.data_2d[k * per + j]);
+                im[inc * per + j] = std::mem::take(&mut spectrum.data_2d_imag[k * per + j]);
+            }
         }
-        ph1 += 5.0;
+        spectrum.data_2d = re;
+        spectrum.data_2d_imag = im;
+    } else if rows < full {
+        return Err(NmrError::InvalidInput(format!(
+            "{} rows match neither the {} sampled nor the {} grid increments of the schedule",
+            rows,
+            schedule.sampled.len(),
+            schedule.grid
+        )));
     }
 
-    // Fine search for ph1
-    let saved_ph1 = best_ph1;
-    best_score = f64::NEG_INFINITY;
-    let mut fine_ph1 = saved_ph1 - 5.0;
-    while fine_ph1 <= saved_ph1 + 5.0 {
-        let score = evaluate_phase(spectrum, best_ph0, fine_ph1);
-        if score > best_score {
-            best_score = score;
-            best_ph1 = fine_ph1;
+    // The schedule decides whether the rows come in pairs
+    let acquisition = match (per, spectrum.f1_acquisition.rows_per_increment()) {
+        (2, 2) | (1, 1) => spectrum.f1_acquisition,
+        (2, _) => F1Acquisition::States,
+        _ => F1Acquisition::Complex,
+    };
+    let mut f1 = F1Interferograms::from_fid(spectrum, acquisition)?;
+    let n = f1.increments();
+    let mut mask = vec![false; n];
+    for &inc in &schedule.sampled {
+        if let Some(m) = mask.get_mut(inc) {
+            *m = true;
         }
-        fine_ph1 += 0.5;
     }
+    let mut planner = FftPlanner::new();
+    for series in f1.series.iter_mut() {
+        for (v, &m) in series.iter_mut().zip(&mask) {
+            if !m {
+                *v = Complex::new(0.0, 0.0);
+            }
+        }
+        *series = ist_reconstruct(series, &mask, iterations, &mut planner);
+    }
+    f1.write_increments(spectrum, (0..n).filter(|&k| !mask[k]));
+    if let Some(ax) = spectrum.axes.get_mut(1) {
+        ax.num_points = spectrum.data_2d.len();
+    }
+    spectrum.nus_schedule = None;
 
-    // Apply the best phase
-    phase_correct(spectrum, best_ph0, best_ph1, log);
+    log.add_entry(
+        "NUS Reconstruction",
+        &format!(
+            "IST ({} iterations) along F1 after an F2 FT, one complex interferogram per {} increment: {} of {} t1 increments sampled ({:.0}%), schedule from {}",
+            iterations,
+            acquisition,
+            schedule.sampled.len(),
+            schedule.grid,
+            100.0 * schedule.sampled.len() as f64 / schedule.grid.max(1) as f64,
+            schedule.source
+        ),
+        &format!(
+            "nmrPipe -fn FT  # F2\nnmrPipe -fn TP\nhmsIST -dim 1 -itr {} -sched nuslist -xN {} -yN {}  # F1, after nusExpand.tcl\nnmrPipe -fn TP\nnmrPipe -fn FT -inv  # F2",
+            iterations,
+            cols,
+            spectrum.data_2d.len()
+        ),
+    );
+    Ok(())
+}
 
-    (best_ph0, best_ph1)
+/// Entries of the linear systems solved by [`solve_linear`]
+trait LinearScalar:
+    Copy
+    + Default
+    + std::ops::Sub<Output = Self>
+    + std::ops::Mul<Output = Self>
+    + std::ops::Div<Output = Self>
+    + std::ops::SubAssign
+    + std::iter::Sum
+{
+    /// Size compared when choosing the pivot
+    fn magnitude(self) -> f64;
 }
 
-/// Evaluate phase quality: sum of positive real values (higher = better phased)
-fn evaluate_phase(spectrum: &SpectrumData, ph0_deg: f64, ph1_deg: f64) -> f64 {
-    let n = spectrum.real.len();
-    let ph0 = ph0_deg * PI / 180.0;
-    let ph1 = ph1_deg * PI / 180.0;
+impl LinearScalar for f64 {
+    fn magnitude(self) -> f64 {
+        self.abs()
+    }
+}
 
-    let mut score = 0.0;
-    for i in 0..n {
-        let frac = i as f64 / n as f64;
-        let phase = ph0 + ph1 * frac;
-        let re = spectrum.real[i];
-        let im = if i < spectrum.imag.len() {
-            spectrum.imag[i]
-        } else {
-            0.0
-        };
-        let corrected_re = re * phase.cos() - im * phase.sin();
-        // Penalize negative values (absorption mode should be mostly positive)
-        if corrected_re > 0.0 {
-            score += corrected_re;
-        } else {
-            score += corrected_re * 2.0; // Stronger penalty for negative
+impl LinearScalar for Complex<f64> {
+    fn magnitude(self) -> f64 {
+        self.norm()
+    }
+}
+
+/// Gaussian elimination with partial pivoting; `None` if singular
+fn solve_linear<T: LinearScalar>(mut a: Vec<Vec<T>>, mut b: Vec<T>) -> Option<Vec<T>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].magnitude().total_cmp(&a[j][col].magnitude()))?;
+        if a[pivot][col].magnitude() < 1e-300 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col].clone();
+        for row in col + 1..n {
+            let f = a[row][col] / pivot_row[col];
+            for (dst, &v) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *dst -= f * v;
+            }
+            let v = b[col];
+            b[row] -= f * v;
         }
     }
-    score
+    let mut x = vec![T::default(); n];
+    for row in (0..n).rev() {
+        let sum: T = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Next power of two >= n
+pub fn next_power_of_two(n: usize) -> usize {
+    let mut p = 1;
+    while p < n {
+        p <<= 1;
+    }
+    p
 }
 
 // =========================================================================
-//  Baseline Correction
+//  Fourier Transform
 // =========================================================================
 
-/// Simple polynomial baseline correction
-pub fn baseline_correct(
+/// Apply complex FFT to the FID data, converting to frequency domain
+pub fn fourier_transform(
     spectrum: &mut SpectrumData,
+    use_imaginary: bool,
     log: &mut ReproLog,
 ) {
+    if spectrum.is_frequency_domain {
+        log::warn!("Data is already in frequency domain, skipping FT");
+        return;
+    }
+
     let n = spectrum.real.len();
     if n == 0 {
         return;
     }
 
-    // Use the edge regions (first/last 10%) to estimate baseline
-    let edge = (n as f64 * 0.1) as usize;
-    let edge = edge.max(1);
+    // Ensure power of 2
+    let fft_size = next_power_of_two(n);
+    spectrum.real.resize(fft_size, 0.0);
+    spectrum.imag.resize(fft_size, 0.0);
 
-    let left_mean: f64 = spectrum.real[..edge].iter().sum::<f64>() / edge as f64;
-    let right_mean: f64 = spectrum.real[n - edge..].iter().sum::<f64>() / edge as f64;
+    // Build complex buffer
+    let mut buffer: Vec<Complex<f64>> = if use_imaginary && !spectrum.imag.is_empty() {
+        spectrum
+            .real
+            .iter()
+            .zip(spectrum.imag.iter())
+            .map(|(&r, &i)| Complex::new(r, i))
+            .collect()
+    } else {
+        spectrum
+            .real
+            .iter()
+            .map(|&r| Complex::new(r, 0.0))
+            .collect()
+    };
 
-    // Linear baseline subtraction
-    for i in 0..n {
-        let frac = i as f64 / n as f64;
-        let baseline = left_mean + (right_mean - left_mean) * frac;
-        spectrum.real[i] -= baseline;
+    // First-point correction: multiply the first complex point by 0.5
+    // This removes the DC-offset artifact that appears at the edges of the
+    // spectrum (standard NMR convention, equivalent to NMRPipe FT -auto).
+    if !buffer.is_empty() {
+        buffer[0] *= 0.5;
     }
 
-    let nmrpipe_cmd = "nmrPipe -fn POLY -auto".to_string();
-    log.add_entry(
-        "Baseline Correction",
-        &format!(
-            "Linear baseline correction (left={:.2}, right={:.2})",
-            left_mean, right_mean
-        ),
+    // Execute FFT
+    let mut planner = FftPlanner::new();
+    let fft = planner.plan_fft_forward(fft_size);
+    fft.process(&mut buffer);
+
+    // FFT shift (swap halves so 0 Hz is in the center)
+    let half = fft_size / 2;
+    let mut shifted = vec![Complex::new(0.0, 0.0); fft_size];
+    for i in 0..fft_size {
+        shifted[i] = buffer[(i + half) % fft_size];
+    }
+
+    // Reverse so that index 0 = highest frequency (downfield / high ppm)
+    // This matches the ppm_scale convention: index_to_ppm(0) = reference_ppm
+    shifted.reverse();
+
+    // Extract real and imaginary
+    spectrum.real = shifted.iter().map(|c| c.re).collect();
+    spectrum.imag = shifted.iter().map(|c| c.im).collect();
+
+    // Auto-sign correction: if the spectrum is predominantly negative,
+    // apply a 180° phase flip so absorption peaks point upward.
+    let pos_sum: f64 = spectrum.real.iter().filter(|&&v| v > 0.0).sum();
+    let neg_sum: f64 = spectrum.real.iter().filter(|&&v| v < 0.0).map(|v| v.abs()).sum();
+    if neg_sum > pos_sum * 1.5 {
+        for v in spectrum.real.iter_mut() {
+            *v = -*v;
+        }
+        for v in spectrum.imag.iter_mut() {
+            *v = -*v;
+        }
+    }
+    spectrum.is_frequency_domain = true;
+
+    if let Some(ax) = spectrum.axes.first_mut() {
+        ax.num_points = fft_size;
+    }
+
+    let nmrpipe_cmd = if use_imaginary {
+        "nmrPipe -fn FT -auto".to_string()
+    } else {
+        "nmrPipe -fn FT -real".to_string()
+    };
+    log.add_entry(
+        "Fourier Transform",
+        &format!(
+            "{} FFT ({} → {} points, with FFT shift)",
+            if use_imaginary { "Complex" } else { "Real-only" },
+            n,
+            fft_size
+        ),
         &nmrpipe_cmd,
     );
 }
 
-/// Manual baseline correction using user-picked anchor points.
-/// Performs piecewise-linear interpolation between sorted anchor points
-/// and subtracts the resulting baseline from the spectrum.
-pub fn manual_baseline_correct(
-    spectrum: &mut SpectrumData,
-    anchor_points: &[[f64; 2]], // (ppm, intensity) pairs
-    log: &mut ReproLog,
-) {
-    let n = spectrum.real.len();
-    if n == 0 || anchor_points.len() < 2 {
+/// Transform a spectrum back to the time domain, undoing the ordering,
+/// FFT shift and first-point scaling of [`fourier_transform`] (1D) or
+/// [`fourier_transform_2d`] (2D), so that transforming again gives the
+/// same spectrum.
+///
+/// For a vendor-processed spectrum this recomputes a pseudo-FID that keeps
+/// the vendor's apodization and zero filling; it can be re-apodized and
+/// transformed again.  A real-only spectrum has its imaginary part rebuilt
+/// by a Hilbert transform, which assumes the signal starts at t = 0 and
+/// was zero-filled at least twofold: only the causal half of the FID is
+/// kept in each dimension.  The F1-imaginary quadrants of a hypercomplex
+/// 2D spectrum are dropped.
+pub fn inverse_fourier_transform(spectrum: &mut SpectrumData, log: &mut ReproLog) {
+    if !spectrum.is_frequency_domain {
+        log::warn!("Data is already in time domain, skipping inverse FT");
         return;
     }
-
-    // Sort anchors by ppm
-    let mut anchors = anchor_points.to_vec();
-    anchors.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap());
-
-    // Build the ppm scale
-    let ppm_scale = if spectrum.is_frequency_domain && !spectrum.axes.is_empty() {
-        spectrum.axes[0].ppm_scale()
+    let is_2d = spectrum.is_2d();
+    let hilbert = if is_2d {
+        spectrum.data_2d_imag.len() != spectrum.data_2d.len()
+            || spectrum.data_2d_imag.iter().flatten().all(|&v| v == 0.0)
     } else {
-        (0..n).map(|i| i as f64).collect::<Vec<_>>()
+        !spectrum.has_imaginary()
     };
+    // The FID of an extracted region has the region as its spectral width
+    for ax in &mut spectrum.axes {
+        *ax = ax.to_standalone();
+    }
+    let size = if is_2d { inverse_ft_2d(spectrum, hilbert) } else { inverse_ft_1d(spectrum, hilbert) };
+    let Some(size) = size else { return };
+    spectrum.is_frequency_domain = false;
 
-    // For each data point, interpolate baseline from anchors
-    for i in 0..n {
-        let ppm = ppm_scale[i];
-
-        // Find surrounding anchors
-        let baseline_val = if ppm <= anchors[0][0] {
-            // Extrapolate from first two points
-            let (x0, y0) = (anchors[0][0], anchors[0][1]);
-            let (x1, y1) = (anchors[1][0], anchors[1][1]);
-            if (x1 - x0).abs() > 1e-12 {
-                y0 + (ppm - x0) * (y1 - y0) / (x1 - x0)
-            } else {
-                y0
-            }
-        } else if ppm >= anchors[anchors.len() - 1][0] {
-            // Extrapolate from last two points
-            let len = anchors.len();
-            let (x0, y0) = (anchors[len - 2][0], anchors[len - 2][1]);
-            let (x1, y1) = (anchors[len - 1][0], anchors[len - 1][1]);
-            if (x1 - x0).abs() > 1e-12 {
-                y0 + (ppm - x0) * (y1 - y0) / (x1 - x0)
+    let ift = if hilbert { "nmrPipe -fn HT -auto\nnmrPipe -fn FT -inv" } else { "nmrPipe -fn FT -inv" };
+    let cmd = if is_2d {
+        let dim = |d: &str| ift.replace('\n', &format!("  # {}\n", d)) + "  # " + d;
+        format!("{}\n{}", dim("F2"), dim("F1"))
+    } else {
+        ift.to_string()
+    };
+    log.add_entry(
+        "Inverse Fourier Transform",
+        &format!(
+            "Complex inverse FFT back to the time domain ({}{}{})",
+            size,
+            if hilbert { ", imaginary part rebuilt by Hilbert transform" } else { "" },
+            if spectrum.provenance == Provenance::VendorProcessed {
+                ", pseudo-FID of a vendor-processed spectrum"
             } else {
-                y1
+                ""
             }
-        } else {
-            // Interpolate between surrounding anchors
-            let mut val = 0.0;
-            for j in 0..anchors.len() - 1 {
-                if ppm >= anchors[j][0] && ppm <= anchors[j + 1][0] {
-                    let (x0, y0) = (anchors[j][0], anchors[j][1]);
-                    let (x1, y1) = (anchors[j + 1][0], anchors[j + 1][1]);
-                    let frac = if (x1 - x0).abs() > 1e-12 {
-                        (ppm - x0) / (x1 - x0)
-                    } else {
-                        0.5
-                    };
-                    val = y0 + frac * (y1 - y0);
-                    break;
-                }
+        ),
+        &cmd,
+    );
+}
+
+/// 1D part of [`inverse_fourier_transform`]; returns the size change
+fn inverse_ft_1d(spectrum: &mut SpectrumData, hilbert: bool) -> Option<String> {
+    let n = spectrum.real.len();
+    if n < 2 {
+        return None;
+    }
+    spectrum.imag.resize(n, 0.0);
+    let mut fid = spectrum_to_fid(&spectrum.real, &spectrum.imag);
+    if hilbert {
+        // The real spectrum holds s(t)/2 + conj(s(−t))/2: drop negative
+        // times and double the rest (the first point is already whole)
+        fid.truncate(n / 2);
+        for c in fid.iter_mut().skip(1) {
+            *c *= 2.0;
+        }
+    }
+    spectrum.real = fid.iter().map(|c| c.re).collect();
+    spectrum.imag = fid.iter().map(|c| c.im).collect();
+    if let Some(ax) = spectrum.axes.first_mut() {
+        ax.num_points = fid.len();
+    }
+    Some(format!("{} → {} points", n, fid.len()))
+}
+
+/// 2D part of [`inverse_fourier_transform`]: rows (F2), then columns (F1)
+fn inverse_ft_2d(spectrum: &mut SpectrumData, hilbert: bool) -> Option<String> {
+    let rows = spectrum.data_2d.len();
+    let cols = spectrum.data_2d.first()?.len();
+    if rows < 2 || cols < 2 || spectrum.data_2d.iter().any(|r| r.len() != cols) {
+        return None;
+    }
+    let zeros = vec![0.0; cols];
+    let mut fid: Vec<Vec<Complex<f64>>> = spectrum
+        .data_2d
+        .iter()
+        .enumerate()
+        .map(|(r, re)| {
+            let im = if hilbert { &zeros } else { &spectrum.data_2d_imag[r] };
+            spectrum_to_fid(re, im)
+        })
+        .collect();
+    for c in 0..cols {
+        let (re, im): (Vec<f64>, Vec<f64>) = fid.iter().map(|row| (row[c].re, row[c].im)).unzip();
+        for (row, v) in fid.iter_mut().zip(spectrum_to_fid(&re, &im)) {
+            row[c] = v;
+        }
+    }
+    if hilbert {
+        // As in 1D, with the mirror image at (−t1, −t2): keep the first
+        // quadrant and double all but its origin
+        fid.truncate(rows / 2);
+        for (r, row) in fid.iter_mut().enumerate() {
+            row.truncate(cols / 2);
+            for v in row.iter_mut().skip(usize::from(r == 0)) {
+                *v *= 2.0;
             }
-            val
-        };
+        }
+    }
+    let (new_rows, new_cols) = (fid.len(), fid[0].len());
 
-        spectrum.real[i] -= baseline_val;
+    spectrum.data_2d = fid.iter().map(|row| row.iter().map(|c| c.re).collect()).collect();
+    spectrum.data_2d_imag = fid.iter().map(|row| row.iter().map(|c| c.im).collect()).collect();
+    spectrum.f1_quadrants = None;
+    spectrum.real = spectrum.data_2d[0].clone();
+    spectrum.imag = spectrum.data_2d_imag[0].clone();
+    if let Some(ax) = spectrum.axes.get_mut(0) {
+        ax.num_points = new_cols;
+    }
+    if let Some(ax) = spectrum.axes.get_mut(1) {
+        ax.num_points = new_rows;
     }
+    Some(format!("{}×{} → {}×{}", rows, cols, new_rows, new_cols))
+}
 
-    let ppm_list: Vec<String> = anchors.iter().map(|a| format!("{:.2}", a[0])).collect();
+// =========================================================================
+//  Resolution Enhancement Preview
+// =========================================================================
+
+/// Undo [`fourier_transform`]'s reversal and FFT shift and inverse-transform
+/// a complex spectrum back to its (zero-filled) pseudo-FID.
+fn spectrum_to_fid(real: &[f64], imag: &[f64]) -> Vec<Complex<f64>> {
+    let n = real.len();
+    let half = n / 2;
+    let mut buffer = vec![Complex::new(0.0, 0.0); n];
+    for (i, (&re, &im)) in real.iter().rev().zip(imag.iter().rev()).enumerate() {
+        buffer[(i + half) % n] = Complex::new(re, im);
+    }
+    let mut planner = FftPlanner::new();
+    planner.plan_fft_inverse(n).process(&mut buffer);
+    for c in buffer.iter_mut() {
+        *c /= n as f64;
+    }
+    // The forward transform halved the first point
+    buffer[0] *= 2.0;
+    buffer
+}
+
+/// Forward transform with the same first-point, shift and ordering
+/// conventions as [`fourier_transform`] (without the auto sign flip).
+fn fid_to_spectrum(mut buffer: Vec<Complex<f64>>) -> Vec<Complex<f64>> {
+    let n = buffer.len();
+    buffer[0] *= 0.5;
+    let mut planner = FftPlanner::new();
+    planner.plan_fft_forward(n).process(&mut buffer);
+    let half = n / 2;
+    let mut shifted: Vec<Complex<f64>> = (0..n).map(|i| buffer[(i + half) % n]).collect();
+    shifted.reverse();
+    shifted
+}
+
+/// Lorentz-to-Gauss resolution enhancement of a processed 1D spectrum,
+/// returned as `[ppm, intensity]` points within `lo_ppm..=hi_ppm` only.
+///
+/// The spectrum is transformed back to a pseudo-FID, multiplied by
+/// `exp(π·lb·t) · exp(−(π·gb·t)² / (4 ln 2))` — removing `lb_hz` of
+/// Lorentzian width and adding a Gaussian of `gb_hz` FWHM — and
+/// transformed again.  Nothing is modified; this is meant as a preview
+/// before committing to a GM apodization.  Needs the imaginary part.
+pub fn lorentz_gauss_preview(
+    spectrum: &SpectrumData,
+    lb_hz: f64,
+    gb_hz: f64,
+    lo_ppm: f64,
+    hi_ppm: f64,
+) -> Option<Vec<[f64; 2]>> {
+    let n = spectrum.real.len();
+    if !spectrum.is_frequency_domain || spectrum.is_2d() || n < 4 || spectrum.imag.len() != n {
+        return None;
+    }
+    let axis = spectrum.axes.first()?;
+    // The pseudo-FID spans the stored points, which may be an extracted region
+    let sw = axis.hz_per_point() * n as f64;
+    if sw <= 0.0 {
+        return None;
+    }
+    let dwell = 1.0 / sw;
+
+    let mut fid = spectrum_to_fid(&spectrum.real, &spectrum.imag);
+    let gauss = (PI * gb_hz.max(0.0)).powi(2) / (4.0 * 2f64.ln());
+    for (i, c) in fid.iter_mut().enumerate() {
+        let t = i as f64 * dwell;
+        *c *= (PI * lb_hz * t - gauss * t * t).exp();
+    }
+    let enhanced = fid_to_spectrum(fid);
+
+    let (lo, hi) = (lo_ppm.min(hi_ppm), lo_ppm.max(hi_ppm));
+    Some(
+        enhanced
+            .iter()
+            .enumerate()
+            .map(|(i, c)| [axis.index_to_ppm(i), c.re])
+            .filter(|p| p[0] >= lo && p[0] <= hi)
+            .collect(),
+    )
+}
+
+// =========================================================================
+//  Reference Deconvolution (FIDDLE)
+// =========================================================================
+
+/// Reference deconvolution (FIDDLE) of a phased 1D spectrum.
+///
+/// The reference line (TMS, a solvent line) within `width_ppm` around
+/// `center_ppm` is cut out and transformed back to its FID, and an ideal
+/// Lorentzian of `target_lw_hz` with the same position, integral and window
+/// likewise.  Their ratio, ideal / measured, is the correction that turns
+/// the measured reference into the ideal line; multiplying the pseudo-FID
+/// of the whole spectrum by it removes the same shimming and field
+/// inhomogeneity distortions from every other line.  Returns the ppm of
+/// the reference peak.
+pub fn reference_deconvolution(
+    spectrum: &mut SpectrumData,
+    center_ppm: f64,
+    width_ppm: f64,
+    target_lw_hz: f64,
+    log: &mut ReproLog,
+) -> NmrResult<f64> {
+    let n = spectrum.real.len();
+    if !spectrum.is_frequency_domain || spectrum.is_2d() {
+        return Err(NmrError::NotApplicable(
+            "Reference deconvolution works on a 1D frequency-domain spectrum".to_string(),
+        ));
+    }
+    if spectrum.imag.len() != n || n < 16 {
+        return Err(NmrError::NotApplicable(
+            "Reference deconvolution needs the imaginary part of the spectrum".to_string(),
+        ));
+    }
+    let axis = spectrum.axes.first().cloned().unwrap_or_default();
+    let hz_per_point = axis.hz_per_point();
+    if hz_per_point <= 0.0 || target_lw_hz <= 0.0 {
+        return Err(NmrError::InvalidInput(format!(
+            "a target linewidth of {:.2} Hz on an axis of {:.3} Hz per point",
+            target_lw_hz, hz_per_point
+        )));
+    }
+
+    let (lo, hi) = (center_ppm - width_ppm.abs() / 2.0, center_ppm + width_ppm.abs() / 2.0);
+    let window: Vec<usize> = (0..n).filter(|&i| (lo..=hi).contains(&axis.index_to_ppm(i))).collect();
+    let (Some(&first), Some(&last)) = (window.first(), window.last()) else {
+        return Err(NmrError::InvalidInput(format!("No points between {:.3} and {:.3} ppm", lo, hi)));
+    };
+    let top = (first..=last).max_by(|&a, &b| spectrum.real[a].total_cmp(&spectrum.real[b])).unwrap_or(first);
+    if top == first || top == last || spectrum.real[top] <= 0.0 {
+        return Err(NmrError::InvalidInput(format!(
+            "No reference peak inside {:.3}–{:.3} ppm: widen the window around the line",
+            lo, hi
+        )));
+    }
+    let (ym, y0, yp) = (spectrum.real[top - 1], spectrum.real[top], spectrum.real[top + 1]);
+    let curvature = ym - 2.0 * y0 + yp;
+    let pos = top as f64 + if curvature < 0.0 { 0.5 * (ym - yp) / curvature } else { 0.0 };
+
+    // Ideal complex Lorentzian over the same window; the dispersion sign is
+    // taken from the measured line, so either storage convention works
+    let gamma = target_lw_hz / 2.0 / hz_per_point;
+    let sign = if (first..=last).map(|i| spectrum.imag[i] * (i as f64 - pos)).sum::<f64>() < 0.0 { -1.0 } else { 1.0 };
+    let shape = |i: usize| {
+        let d = i as f64 - pos;
+        let denom = gamma * gamma + d * d;
+        (gamma * gamma / denom, sign * gamma * d / denom)
+    };
+    let area: f64 = (first..=last).map(|i| spectrum.real[i]).sum();
+    let unit_area: f64 = (first..=last).map(|i| shape(i).0).sum();
+    let scale = area / unit_area;
+
+    let (mut ref_re, mut ref_im) = (vec![0.0; n], vec![0.0; n]);
+    let (mut ideal_re, mut ideal_im) = (vec![0.0; n], vec![0.0; n]);
+    for i in first..=last {
+        ref_re[i] = spectrum.real[i];
+        ref_im[i] = spectrum.imag[i];
+        let (re, im) = shape(i);
+        ideal_re[i] = scale * re;
+        ideal_im[i] = scale * im;
+    }
+    let measured = spectrum_to_fid(&ref_re, &ref_im);
+    let ideal = spectrum_to_fid(&ideal_re, &ideal_im);
+
+    // Regularised ideal / measured, so the noise at the end of the
+    // reference FID is not blown up
+    let floor = (1e-3 * measured[0].norm()).powi(2);
+    let mut fid = spectrum_to_fid(&spectrum.real, &spectrum.imag);
+    for ((c, m), e) in fid.iter_mut().zip(&measured).zip(&ideal) {
+        *c *= e * m.conj() / (m.norm_sqr() + floor);
+    }
+    let corrected = fid_to_spectrum(fid);
+    spectrum.real = corrected.iter().map(|c| c.re).collect();
+    spectrum.imag = corrected.iter().map(|c| c.im).collect();
+
+    let peak_ppm = axis.index_to_ppm(top) - (pos - top as f64) * axis.ppm_per_point();
     log.add_entry(
-        "Manual Baseline Correction",
-        &format!(
-            "Piecewise-linear baseline from {} anchor points at ppm: [{}]",
-            anchors.len(),
-            ppm_list.join(", ")
-        ),
+        "Reference Deconvolution",
         &format!(
-            "# Manual baseline correction with {} user-defined anchor points",
-            anchors.len()
+            "Reference line at {:.3} ppm ({:.3}–{:.3} ppm, {} points) corrected to a {:.2} Hz Lorentzian; the FID was multiplied by ideal / measured",
+            peak_ppm,
+            lo,
+            hi,
+            last - first + 1,
+            target_lw_hz
         ),
+        "# no NMRPipe equivalent (FIDDLE reference deconvolution)",
     );
+    Ok(peak_ppm)
 }
 
 // =========================================================================
-//  Peak Detection
+//  2D Fourier Transform
 // =========================================================================
 
-/// Simple peak detection: find local maxima above a noise threshold.
-/// Returns peaks as `[ppm, intensity]` pairs sorted by ppm descending.
-pub fn detect_peaks(
-    spectrum: &SpectrumData,
-    threshold_fraction: f64, // 0.0–1.0, fraction of max intensity
-    min_distance: usize,     // minimum index distance between accepted peaks
-) -> Vec<[f64; 2]> {
-    let n = spectrum.real.len();
-    if n < 3 {
-        return vec![];
+/// Apply 2D FFT to a 2D time-domain spectrum (e.g. COSY, HSQC, HMBC).
+///
+/// Pipeline:
+///   1. FFT along F2 (direct / rows) — each row is a complex FID
+///   2. FFT along F1 (indirect / columns) by the t1 sampling in
+///      `spectrum.f1_acquisition`:
+///      - Complex: each column is one complex series
+///      - States / States-TPPI / Echo-Antiecho: the row pair of each
+///        increment (recombined first for echo/antiecho, every other
+///        increment negated for States-TPPI) gives the real and imaginary
+///        t1 parts, transformed separately for the real and imaginary F2
+///        parts into the four quadrants RR, RI, IR, II
+///      - TPPI: real FT of each column, keeping the positive half
+///   3. Magnitude mode: √ of the summed squares for display; phase-sensitive
+///      mode keeps every part
+///
+/// After processing `data_2d` holds the magnitude (or RR) spectrum,
+/// `data_2d_imag` is cleared (or holds RI) and `f1_quadrants` holds IR / II
+/// of a phase-sensitive hypercomplex spectrum, which can then be phased
+/// along both dimensions. `is_frequency_domain` is set to `true`.
+pub fn fourier_transform_2d(
+    spectrum: &mut SpectrumData,
+    mode: Ft2dMode,
+    log: &mut ReproLog,
+) {
+    if spectrum.is_frequency_domain {
+        log::warn!("2D data is already in frequency domain, skipping FT");
+        return;
     }
 
-    let max_val = spectrum
-        .real
-        .iter()
-        .cloned()
-        .fold(f64::NEG_INFINITY, f64::max);
-    if max_val <= 0.0 {
-        return vec![];
+    let n_rows = spectrum.data_2d.len();
+    if n_rows == 0 {
+        return;
+    }
+    let n_cols = spectrum.data_2d[0].len();
+    if n_cols == 0 {
+        return;
+    }
+
+    let has_imag = !spectrum.data_2d_imag.is_empty()
+        && spectrum.data_2d_imag.len() == n_rows;
+    let mut acquisition = spectrum.f1_acquisition;
+    let pairs = matches!(
+        acquisition,
+        F1Acquisition::States | F1Acquisition::StatesTppi | F1Acquisition::EchoAntiecho
+    );
+    if pairs && n_rows < 2 {
+        log::warn!("{} data needs a row pair per increment; transforming F1 as complex", acquisition);
+        acquisition = F1Acquisition::Complex;
     }
-    let threshold = max_val * threshold_fraction;
 
-    // Collect local-maxima candidates above threshold
-    let mut candidates: Vec<(usize, f64)> = Vec::new();
-    for i in 1..n - 1 {
-        let val = spectrum.real[i];
-        if val > threshold
-            && val >= spectrum.real[i - 1]
-            && val >= spectrum.real[i + 1]
-            && val > 0.0
-        {
-            candidates.push((i, val));
+    // ── Step 1: FFT along F2 (rows) ──
+    let fft_cols = next_power_of_two(n_cols);
+    let mut planner = FftPlanner::new();
+    let fft_f2 = planner.plan_fft_forward(fft_cols);
+
+    // Store complex result matrix (rows × fft_cols)
+    let mut re_2d = vec![vec![0.0f64; fft_cols]; n_rows];
+    let mut im_2d = vec![vec![0.0f64; fft_cols]; n_rows];
+
+    for row_idx in 0..n_rows {
+        let row_len = spectrum.data_2d[row_idx].len();
+        let mut buffer: Vec<Complex<f64>> = Vec::with_capacity(fft_cols);
+
+        for col in 0..fft_cols {
+            if col < row_len {
+                let re = spectrum.data_2d[row_idx][col];
+                let im = if has_imag && col < spectrum.data_2d_imag[row_idx].len() {
+                    spectrum.data_2d_imag[row_idx][col]
+                } else {
+                    0.0
+                };
+                buffer.push(Complex::new(re, im));
+            } else {
+                buffer.push(Complex::new(0.0, 0.0)); // zero-pad
+            }
+        }
+
+        // First-point correction (standard NMR convention)
+        if !buffer.is_empty() {
+            buffer[0] *= 0.5;
+        }
+
+        fft_f2.process(&mut buffer);
+
+        // FFT-shift (swap halves)
+        let half = fft_cols / 2;
+        for i in 0..fft_cols {
+            let si = (i + half) % fft_cols;
+            re_2d[row_idx][i] = buffer[si].re;
+            im_2d[row_idx][i] = buffer[si].im;
+        }
+    }
+
+    // ── Step 2: FFT along F1 (columns) ──
+    let mut parts = match acquisition {
+        F1Acquisition::Complex => {
+            let fft_rows = next_power_of_two(n_rows);
+            let (re, im) = ft_columns(&re_2d, &im_2d, fft_rows, &mut planner);
+            vec![re, im]
+        }
+        F1Acquisition::Tppi => {
+            // A real series: the spectrum is symmetric, keep the positive half
+            let fft_rows = next_power_of_two(n_rows);
+            let keep = |(re, im): (Vec<Vec<f64>>, Vec<Vec<f64>>)| {
+                (re[fft_rows / 2..].to_vec(), im[fft_rows / 2..].to_vec())
+            };
+            let (rr, ir) = keep(ft_columns(&re_2d, &[], fft_rows, &mut planner));
+            let (ri, ii) = keep(ft_columns(&im_2d, &[], fft_rows, &mut planner));
+            vec![rr, ri, ir, ii]
+        }
+        _ => {
+            let [cos_re, cos_im, sin_re, sin_im] = states_pairs(&re_2d, &im_2d, acquisition);
+            let fft_rows = next_power_of_two(cos_re.len());
+            let (rr, ir) = ft_columns(&cos_re, &sin_re, fft_rows, &mut planner);
+            let (ri, ii) = ft_columns(&cos_im, &sin_im, fft_rows, &mut planner);
+            vec![rr, ri, ir, ii]
+        }
+    };
+    let fft_rows = parts[0].len();
+
+    // ── Step 3: Compute magnitude (or keep every part) and reverse axes ──
+    if mode == Ft2dMode::Magnitude {
+        let (first, rest) = parts.split_at_mut(1);
+        for (r, row) in first[0].iter_mut().enumerate() {
+            for (c, v) in row.iter_mut().enumerate() {
+                let sum: f64 = rest.iter().map(|m| m[r][c] * m[r][c]).sum();
+                *v = (*v * *v + sum).sqrt();
+            }
+        }
+        parts.truncate(1);
+    }
+    // Reverse each row so index 0 → highest ppm (matches 1D convention),
+    // and the row order for F1 (so high ppm = top = index 0)
+    for matrix in parts.iter_mut() {
+        for row in matrix.iter_mut() {
+            row.reverse();
+        }
+        matrix.reverse();
+    }
+
+    // Store result
+    let mut parts = parts.into_iter();
+    spectrum.data_2d = parts.next().unwrap_or_default();
+    spectrum.data_2d_imag = parts.next().unwrap_or_default();
+    spectrum.f1_quadrants = match (parts.next(), parts.next()) {
+        (Some(ir), Some(ii)) => Some(F1Quadrants { ir, ii }),
+        _ => None,
+    };
+    spectrum.is_frequency_domain = true;
+
+    // Also set the 1D projection (first row) for the status bar
+    spectrum.real = spectrum.data_2d.first().cloned().unwrap_or_default();
+    spectrum.imag.clear();
+
+    // Update axis sizes
+    if let Some(ax) = spectrum.axes.get_mut(0) {
+        ax.num_points = fft_cols;
+    }
+    if let Some(ax) = spectrum.axes.get_mut(1) {
+        ax.num_points = fft_rows;
+    }
+
+    log.add_entry(
+        "2D Fourier Transform",
+        &format!(
+            "{} 2D FFT ({} in t1): {}×{} → {}×{} ({} mode)",
+            if acquisition.is_hypercomplex() { "Hypercomplex" } else { "Complex" },
+            acquisition,
+            n_rows, n_cols, fft_rows, fft_cols, mode
+        ),
+        match mode {
+            Ft2dMode::Magnitude => "nmrPipe -fn FT -auto  # F2\nnmrPipe -fn FT -auto  # F1\nnmrPipe -fn MC",
+            Ft2dMode::PhaseSensitive => "nmrPipe -fn FT -auto  # F2\nnmrPipe -fn FT -auto  # F1",
+        },
+    );
+}
+
+/// FFT every column of the complex matrix `re + i·im` (an empty `im` is
+/// zero), zero-filled to `size` rows, with first-point correction and the
+/// zero frequency moved to the centre
+fn ft_columns(
+    re: &[Vec<f64>],
+    im: &[Vec<f64>],
+    size: usize,
+    planner: &mut FftPlanner<f64>,
+) -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+    let cols = re.first().map_or(0, |r| r.len());
+    let fft = planner.plan_fft_forward(size);
+    let mut out_re = vec![vec![0.0; cols]; size];
+    let mut out_im = vec![vec![0.0; cols]; size];
+    let half = size / 2;
+    for c in 0..cols {
+        let mut buffer: Vec<Complex<f64>> = (0..size)
+            .map(|r| match re.get(r) {
+                Some(row) => Complex::new(row[c], im.get(r).map_or(0.0, |row| row[c])),
+                None => Complex::new(0.0, 0.0),
+            })
+            .collect();
+        buffer[0] *= 0.5;
+        fft.process(&mut buffer);
+        for r in 0..size {
+            let v = buffer[(r + half) % size];
+            out_re[r][c] = v.re;
+            out_im[r][c] = v.im;
+        }
+    }
+    (out_re, out_im)
+}
+
+/// Split F2-transformed rows into the cosine- and sine-modulated series of
+/// a States acquisition, each as (real, imaginary) F2 parts.  Echo/antiecho
+/// pairs (P-type first) become C = (P + N) / 2 and S = (P − N) / 2i;
+/// States-TPPI increments are negated on every other one.  Returns
+/// [cos real, cos imaginary, sin real, sin imaginary].
+fn states_pairs(re: &[Vec<f64>], im: &[Vec<f64>], acquisition: F1Acquisition) -> [Vec<Vec<f64>>; 4] {
+    let increments = re.len() / 2;
+    let (mut cos_re, mut cos_im, mut sin_re, mut sin_im) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for k in 0..increments {
+        let (a_re, a_im, b_re, b_im) = (&re[2 * k], &im[2 * k], &re[2 * k + 1], &im[2 * k + 1]);
+        let point = |i: usize| {
+            decode_pair(Complex::new(a_re[i], a_im[i]), Complex::new(b_re[i], b_im[i]), k, acquisition)
+        };
+        let points: Vec<(Complex<f64>, Complex<f64>)> = (0..a_re.len()).map(point).collect();
+        cos_re.push(points.iter().map(|p| p.0.re).collect());
+        cos_im.push(points.iter().map(|p| p.0.im).collect());
+        sin_re.push(points.iter().map(|p| p.1.re).collect());
+        sin_im.push(points.iter().map(|p| p.1.im).collect());
+    }
+    [cos_re, cos_im, sin_re, sin_im]
+}
+
+/// Cosine and sine point of increment `k` from its row pair `a`, `b`
+/// (see [`states_pairs`])
+fn decode_pair(a: Complex<f64>, b: Complex<f64>, k: usize, acquisition: F1Acquisition) -> (Complex<f64>, Complex<f64>) {
+    let sign = if acquisition == F1Acquisition::StatesTppi && k % 2 == 1 { -1.0 } else { 1.0 };
+    let (c, s) = if acquisition == F1Acquisition::EchoAntiecho {
+        ((a + b) * 0.5, (a - b) * Complex::new(0.0, -0.5))
+    } else {
+        (a, b)
+    };
+    (c * sign, s * sign)
+}
+
+/// Row pair of increment `k` from its cosine and sine point, undoing
+/// [`decode_pair`]
+fn encode_pair(c: Complex<f64>, s: Complex<f64>, k: usize, acquisition: F1Acquisition) -> (Complex<f64>, Complex<f64>) {
+    let sign = if acquisition == F1Acquisition::StatesTppi && k % 2 == 1 { -1.0 } else { 1.0 };
+    let (c, s) = (c * sign, s * sign);
+    if acquisition == F1Acquisition::EchoAntiecho {
+        (c + s * Complex::new(0.0, 1.0), c - s * Complex::new(0.0, 1.0))
+    } else {
+        (c, s)
+    }
+}
+
+/// Whether a 2D spectrum still has (non-zero) imaginary data that a
+/// magnitude calculation would discard
+pub fn has_2d_imaginary(spectrum: &SpectrumData) -> bool {
+    let nonzero = |m: &Vec<Vec<f64>>| m.iter().flatten().any(|&v| v != 0.0);
+    spectrum.is_2d()
+        && (nonzero(&spectrum.data_2d_imag)
+            || spectrum.f1_quadrants.as_ref().is_some_and(|q| nonzero(&q.ir) || nonzero(&q.ii)))
+}
+
+/// Replace a phase-sensitive 2D spectrum by its magnitude,
+/// √(RR² + RI² + IR² + II²) over whichever quadrants are present, and drop
+/// the imaginary data.
+pub fn magnitude_2d(spectrum: &mut SpectrumData, log: &mut ReproLog) {
+    if !has_2d_imaginary(spectrum) {
+        return;
+    }
+    let quadrants = spectrum.f1_quadrants.take();
+    let imaginary: Vec<&Vec<Vec<f64>>> = std::iter::once(&spectrum.data_2d_imag)
+        .chain(quadrants.iter().flat_map(|q| [&q.ir, &q.ii]))
+        .filter(|m| !m.is_empty())
+        .collect();
+    for (r, row) in spectrum.data_2d.iter_mut().enumerate() {
+        for (c, v) in row.iter_mut().enumerate() {
+            let sum: f64 = imaginary
+                .iter()
+                .filter_map(|m| m.get(r).and_then(|row| row.get(c)))
+                .map(|x| x * x)
+                .sum();
+            *v = (*v * *v + sum).sqrt();
+        }
+    }
+    let n = imaginary.len();
+    spectrum.data_2d_imag.clear();
+    spectrum.real = spectrum.data_2d.first().cloned().unwrap_or_default();
+    spectrum.imag.clear();
+
+    log.add_entry(
+        "2D Magnitude",
+        &format!("Magnitude of {} quadrants; imaginary data discarded", n + 1),
+        "nmrPipe -fn MC  # F2\nnmrPipe -fn TP | nmrPipe -fn MC | nmrPipe -fn TP  # F1",
+    );
+}
+
+// =========================================================================
+//  t1 Noise Suppression (2D, cosmetic)
+// =========================================================================
+
+/// Fraction of F1 rows (the quietest ones) averaged for `ReferenceRows`
+const T1_REFERENCE_FRACTION: f64 = 0.1;
+
+/// Subtract an estimate of the t1-noise ridges from a 2D spectrum.
+///
+/// t1 noise shows up as streaks along F1 at the F2 positions of strong
+/// signals.  The ridge profile (one value per F2 column) is estimated with
+/// `method` and `strength` (0–1) times it is subtracted from every row.
+/// This improves contour readability only — intensities are no longer
+/// quantitative, and the log entry says so.
+pub fn suppress_t1_noise(
+    spectrum: &mut SpectrumData,
+    method: T1NoiseMethod,
+    strength: f64,
+    log: &mut ReproLog,
+) {
+    if !spectrum.is_2d() || !spectrum.is_frequency_domain {
+        log::warn!("t1 noise suppression needs a transformed 2D spectrum, skipping");
+        return;
+    }
+    let n_rows = spectrum.data_2d.len();
+    let n_cols = spectrum.data_2d.iter().map(|r| r.len()).min().unwrap_or(0);
+    if n_rows < 2 || n_cols == 0 {
+        return;
+    }
+    let strength = strength.clamp(0.0, 1.0);
+
+    let profile: Vec<f64> = match method {
+        T1NoiseMethod::ColumnMedian => (0..n_cols)
+            .map(|j| {
+                let mut column: Vec<f64> = spectrum
+                    .data_2d
+                    .iter()
+                    .map(|row| row[j])
+                    .filter(|v| v.is_finite())
+                    .collect();
+                if column.is_empty() {
+                    return 0.0;
+                }
+                column.sort_by(|a, b| a.total_cmp(b));
+                let mid = column.len() / 2;
+                if column.len().is_multiple_of(2) {
+                    (column[mid - 1] + column[mid]) / 2.0
+                } else {
+                    column[mid]
+                }
+            })
+            .collect(),
+        T1NoiseMethod::ReferenceRows => {
+            let mut energy: Vec<(usize, f64)> = spectrum
+                .data_2d
+                .iter()
+                .enumerate()
+                .map(|(i, row)| (i, row.iter().filter(|v| v.is_finite()).map(|v| v.abs()).sum()))
+                .collect();
+            energy.sort_by(|a, b| a.1.total_cmp(&b.1));
+            let count = ((n_rows as f64 * T1_REFERENCE_FRACTION).round() as usize).max(1);
+            let reference = &energy[..count];
+            (0..n_cols)
+                .map(|j| {
+                    reference
+                        .iter()
+                        .map(|&(i, _)| spectrum.data_2d[i][j])
+                        .filter(|v| v.is_finite())
+                        .sum::<f64>()
+                        / count as f64
+                })
+                .collect()
+        }
+    };
+
+    for row in spectrum.data_2d.iter_mut() {
+        for (v, ridge) in row.iter_mut().zip(&profile) {
+            *v -= strength * ridge;
+        }
+    }
+
+    log.add_entry(
+        "t1 Noise Suppression (cosmetic)",
+        &format!(
+            "Subtracted {:.0}% of the {} t1-ridge estimate from {}×{} 2D spectrum. \
+             Cosmetic: improves contour readability, intensities are no longer quantitative",
+            strength * 100.0,
+            method,
+            n_rows,
+            n_cols
+        ),
+        "# cosmetic t1-noise suppression (no NMRPipe equivalent)",
+    );
+}
+
+// =========================================================================
+//  Phase Correction
+// =========================================================================
+
+/// Apply zero-order and first-order phase correction
+pub fn phase_correct(
+    spectrum: &mut SpectrumData,
+    ph0_degrees: f64,
+    ph1_degrees: f64,
+    log: &mut ReproLog,
+) {
+    let n = spectrum.real.len();
+    if n == 0 {
+        return;
+    }
+
+    let ph0 = ph0_degrees * PI / 180.0;
+    let ph1 = ph1_degrees * PI / 180.0;
+
+    for i in 0..n {
+        let frac = i as f64 / n as f64;
+        let phase = ph0 + ph1 * frac;
+        let cos_p = phase.cos();
+        let sin_p = phase.sin();
+        let re = spectrum.real[i];
+        let im = if i < spectrum.imag.len() {
+            spectrum.imag[i]
+        } else {
+            0.0
+        };
+        spectrum.real[i] = re * cos_p - im * sin_p;
+        if i < spectrum.imag.len() {
+            spectrum.imag[i] = re * sin_p + im * cos_p;
+        }
+    }
+
+    let nmrpipe_cmd = format!("nmrPipe -fn PS -p0 {:.2} -p1 {:.2} -di", ph0_degrees, ph1_degrees);
+    log.add_entry(
+        "Phase Correction",
+        &format!("PH0={:.2}°, PH1={:.2}°", ph0_degrees, ph1_degrees),
+        &nmrpipe_cmd,
+    );
+}
+
+/// Phase of the point at `index` of `n` for PH0/PH1 in radians, as in
+/// [`phase_correct`]
+fn phase_at(ph0: f64, ph1: f64, index: usize, n: usize) -> (f64, f64) {
+    let phase = ph0 + ph1 * index as f64 / n as f64;
+    (phase.cos(), phase.sin())
+}
+
+/// Rotate the complex pair (`re`, `im`) by the phase `(cos, sin)`
+fn rotate(re: &mut f64, im: &mut f64, (cos_p, sin_p): (f64, f64)) {
+    let (r, i) = (*re, *im);
+    *re = r * cos_p - i * sin_p;
+    *im = r * sin_p + i * cos_p;
+}
+
+/// Rotate every point of the pair of matrices that are real and imaginary
+/// along `dim` by the phase of its position along `dim`
+fn phase_matrices(re: &mut [Vec<f64>], im: &mut [Vec<f64>], dim: PhaseDim, ph0: f64, ph1: f64) {
+    let n_rows = re.len();
+    for (r, (re_row, im_row)) in re.iter_mut().zip(im.iter_mut()).enumerate() {
+        let n_cols = re_row.len();
+        for (c, (a, b)) in re_row.iter_mut().zip(im_row.iter_mut()).enumerate() {
+            let phase = match dim {
+                PhaseDim::F2 => phase_at(ph0, ph1, c, n_cols),
+                PhaseDim::F1 => phase_at(ph0, ph1, r, n_rows),
+            };
+            rotate(a, b, phase);
+        }
+    }
+}
+
+/// Zero- and first-order phase correction of a frequency-domain 2D
+/// spectrum along one dimension, keeping the imaginary data so the other
+/// dimension can still be phased.  A magnitude spectrum has nothing left
+/// to phase and is refused.
+pub fn phase_correct_2d(
+    spectrum: &mut SpectrumData,
+    dim: PhaseDim,
+    ph0_degrees: f64,
+    ph1_degrees: f64,
+    log: &mut ReproLog,
+) -> NmrResult<()> {
+    if !spectrum.is_2d() || !spectrum.is_frequency_domain {
+        return Err(NmrError::NotApplicable("2D phasing needs a Fourier-transformed 2D spectrum".to_string()));
+    }
+    let n_rows = spectrum.data_2d.len();
+    let n_cols = spectrum.data_2d.first().map_or(0, Vec::len);
+    let shaped = |m: &Vec<Vec<f64>>| m.len() == n_rows && m.iter().all(|r| r.len() == n_cols);
+    if n_rows == 0 || !shaped(&spectrum.data_2d_imag) {
+        return Err(NmrError::NotApplicable(
+            "the spectrum has no imaginary data to phase with (magnitude mode?)".to_string(),
+        ));
+    }
+    if spectrum.f1_quadrants.as_ref().is_some_and(|q| !shaped(&q.ir) || !shaped(&q.ii)) {
+        return Err(NmrError::InvalidInput("the F1 quadrants do not match the spectrum size".to_string()));
+    }
+
+    // Hypercomplex data pairs RR/RI and IR/II along F2, RR/IR and RI/II
+    // along F1; complex points are rotated as they are
+    let (ph0, ph1) = (ph0_degrees.to_radians(), ph1_degrees.to_radians());
+    let (rr, ri) = (&mut spectrum.data_2d, &mut spectrum.data_2d_imag);
+    match (spectrum.f1_quadrants.as_mut(), dim) {
+        (Some(q), PhaseDim::F2) => {
+            phase_matrices(rr, ri, dim, ph0, ph1);
+            phase_matrices(&mut q.ir, &mut q.ii, dim, ph0, ph1);
+        }
+        (Some(q), PhaseDim::F1) => {
+            phase_matrices(rr, &mut q.ir, dim, ph0, ph1);
+            phase_matrices(ri, &mut q.ii, dim, ph0, ph1);
+        }
+        (None, _) => phase_matrices(rr, ri, dim, ph0, ph1),
+    }
+    spectrum.real = spectrum.data_2d.first().cloned().unwrap_or_default();
+
+    let nmrpipe_cmd = match dim {
+        PhaseDim::F2 => format!("nmrPipe -fn PS -p0 {:.2} -p1 {:.2}  # F2", ph0_degrees, ph1_degrees),
+        PhaseDim::F1 => format!(
+            "nmrPipe -fn TP | nmrPipe -fn PS -p0 {:.2} -p1 {:.2} | nmrPipe -fn TP  # F1",
+            ph0_degrees, ph1_degrees
+        ),
+    };
+    log.add_entry(
+        "2D Phase Correction",
+        &format!("{}: PH0={:.2}°, PH1={:.2}°", dim, ph0_degrees, ph1_degrees),
+        &nmrpipe_cmd,
+    );
+    Ok(())
+}
+
+/// Real part of one trace of a 2D spectrum phased along it: row `index`
+/// for F2, column `index` for F1, for the live preview of
+/// [`phase_correct_2d`].  Empty when the trace or its imaginary part is
+/// missing.
+pub fn phased_trace_2d(spectrum: &SpectrumData, dim: PhaseDim, index: usize, ph0_degrees: f64, ph1_degrees: f64) -> Vec<f64> {
+    let imag = match (&spectrum.f1_quadrants, dim) {
+        (Some(q), PhaseDim::F1) => &q.ir,
+        _ => &spectrum.data_2d_imag,
+    };
+    let point = |r: usize, c: usize| -> Option<(f64, f64)> {
+        Some((*spectrum.data_2d.get(r)?.get(c)?, *imag.get(r)?.get(c)?))
+    };
+    let (ph0, ph1) = (ph0_degrees.to_radians(), ph1_degrees.to_radians());
+    let n = match dim {
+        PhaseDim::F2 => spectrum.data_2d.get(index).map_or(0, Vec::len),
+        PhaseDim::F1 => spectrum.data_2d.len(),
+    };
+    (0..n)
+        .map(|i| {
+            let (mut re, mut im) = match dim {
+                PhaseDim::F2 => point(index, i)?,
+                PhaseDim::F1 => point(i, index)?,
+            };
+            rotate(&mut re, &mut im, phase_at(ph0, ph1, i, n));
+            Some(re)
+        })
+        .collect::<Option<Vec<f64>>>()
+        .unwrap_or_default()
+}
+
+/// Automatic phase correction using entropy minimization
+pub fn auto_phase(
+    spectrum: &mut SpectrumData,
+    log: &mut ReproLog,
+) -> (f64, f64) {
+    let n = spectrum.real.len();
+    if n == 0 {
+        return (0.0, 0.0);
+    }
+
+    // Simple automatic phasing:
+    // Search for ph0 that maximizes the integral of the real part
+    // Then search for ph1 that minimizes baseline distortion
+    let mut best_ph0 = 0.0f64;
+    let mut best_score = f64::NEG_INFINITY;
+
+    // Coarse search for ph0
+    let mut ph0 = -180.0;
+    while ph0 <= 180.0 {
+        let score = evaluate_phase(spectrum, ph0, 0.0);
+        if score > best_score {
+            best_score = score;
+            best_ph0 = ph0;
+        }
+        ph0 += 5.0;
+    }
+
+    // Fine search around best ph0
+    let mut fine_ph0 = best_ph0 - 5.0;
+    best_score = f64::NEG_INFINITY;
+    while fine_ph0 <= best_ph0 + 5.0 {
+        let score = evaluate_phase(spectrum, fine_ph0, 0.0);
+        if score > best_score {
+            best_score = score;
+            best_ph0 = fine_ph0;
+        }
+        fine_ph0 += 0.5;
+    }
+
+    // Search for ph1
+    let mut best_ph1 = 0.0f64;
+    best_score = f64::NEG_INFINITY;
+    let mut ph1 = -180.0;
+    while ph1 <= 180.0 {
+        let score = evaluate_phase(spectrum, best_ph0, ph1);
+        if score > best_score {
+            best_score = score;
+            best_ph1 = ph1;
+        }
+        ph1 += 5.0;
+    }
+
+    // Fine search for ph1
+    let saved_ph1 = best_ph1;
+    best_score = f64::NEG_INFINITY;
+    let mut fine_ph1 = saved_ph1 - 5.0;
+    while fine_ph1 <= saved_ph1 + 5.0 {
+        let score = evaluate_phase(spectrum, best_ph0, fine_ph1);
+        if score > best_score {
+            best_score = score;
+            best_ph1 = fine_ph1;
+        }
+        fine_ph1 += 0.5;
+    }
+
+    // Apply the best phase
+    phase_correct(spectrum, best_ph0, best_ph1, log);
+
+    (best_ph0, best_ph1)
+}
+
+/// An isolated peak to phase on: where it sits on the PH1 ramp and the
+/// phase that turns it into a pure absorption line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhaseAnchor {
+    /// ppm of the peak maximum
+    pub ppm: f64,
+    /// Position as a fraction of the spectrum (`i / n`, as in [`phase_correct`])
+    pub frac: f64,
+    /// Total phase (degrees) that rotates the peak to positive absorption
+    pub phase: f64,
+}
+
+/// Phase anchor of the peak nearest `ppm`: the point of largest magnitude
+/// within `max(3, n / 500)` points of the click, so a click beside the
+/// line still finds its top.  `None` without an imaginary part or outside
+/// the spectrum.
+pub fn phase_anchor(spectrum: &SpectrumData, ppm: f64) -> Option<PhaseAnchor> {
+    let n = spectrum.real.len();
+    let axis = spectrum.axes.first()?;
+    if n == 0 || spectrum.imag.len() != n || !spectrum.is_frequency_domain {
+        return None;
+    }
+    let center = axis.ppm_to_position(ppm)?.round();
+    if center < 0.0 || center >= n as f64 {
+        return None;
+    }
+    let center = center as usize;
+    let half = (n / 500).max(3);
+    let magnitude = |i: usize| spectrum.real[i].hypot(spectrum.imag[i]);
+    let i = (center.saturating_sub(half)..(center + half + 1).min(n))
+        .max_by(|&a, &b| magnitude(a).total_cmp(&magnitude(b)))?;
+    // Re(z·e^{iφ}) = |z| when φ = −arg z
+    let phase = -spectrum.imag[i].atan2(spectrum.real[i]).to_degrees();
+    Some(PhaseAnchor { ppm: axis.index_to_ppm(i), frac: i as f64 / n as f64, phase })
+}
+
+/// Wrap an angle in degrees into (−180, 180]
+fn wrap_degrees(deg: f64) -> f64 {
+    let wrapped = deg.rem_euclid(360.0);
+    if wrapped > 180.0 { wrapped - 360.0 } else { wrapped }
+}
+
+/// PH0 and PH1 that make the anchor peaks absorptive.  One peak sets PH0
+/// and keeps `ph1`; a second peak also sets PH1 from the phase difference
+/// between them (the smallest one, so the peaks should not be too close).
+pub fn phase_from_anchors(first: &PhaseAnchor, second: Option<&PhaseAnchor>, ph1: f64) -> (f64, f64) {
+    let ph1 = match second {
+        Some(second) if (second.frac - first.frac).abs() > 1e-9 => {
+            wrap_degrees(second.phase - first.phase) / (second.frac - first.frac)
+        }
+        _ => ph1,
+    };
+    (wrap_degrees(first.phase - ph1 * first.frac), ph1)
+}
+
+/// Evaluate phase quality: sum of positive real values (higher = better phased)
+fn evaluate_phase(spectrum: &SpectrumData, ph0_deg: f64, ph1_deg: f64) -> f64 {
+    let n = spectrum.real.len();
+    let ph0 = ph0_deg * PI / 180.0;
+    let ph1 = ph1_deg * PI / 180.0;
+
+    let mut score = 0.0;
+    for i in 0..n {
+        let frac = i as f64 / n as f64;
+        let phase = ph0 + ph1 * frac;
+        let re = spectrum.real[i];
+        let im = if i < spectrum.imag.len() {
+            spectrum.imag[i]
+        } else {
+            0.0
+        };
+        let corrected_re = re * phase.cos() - im * phase.sin();
+        // Penalize negative values (absorption mode should be mostly positive)
+        if corrected_re > 0.0 {
+            score += corrected_re;
+        } else {
+            score += corrected_re * 2.0; // Stronger penalty for negative
+        }
+    }
+    score
+}
+
+// =========================================================================
+//  Center Glitch
+// =========================================================================
+
+/// Default height, in noise standard deviations, above which a spike at
+/// the carrier frequency counts as a glitch
+pub const CENTER_GLITCH_SIGMA: f64 = 8.0;
+
+/// Points either side of the centre that a glitch may cover
+const CENTER_GLITCH_HALF_WIDTH: usize = 2;
+
+/// Last point of the flanks the local baseline and noise are fitted to
+const CENTER_GLITCH_FLANK: usize = 20;
+
+/// Height, in noise standard deviations, that the points just outside a
+/// glitch must stay below; a real peak at the carrier is broader
+const CENTER_GLITCH_GUARD_SIGMA: f64 = 4.0;
+
+/// A zero-frequency spike found at the centre of a 1D spectrum
+#[derive(Debug, Clone, PartialEq)]
+pub struct CenterGlitch {
+    /// First point of the spike
+    pub start: usize,
+    /// Last point of the spike (inclusive)
+    pub end: usize,
+    /// Chemical shift of the centre point
+    pub ppm: f64,
+    /// Height of the spike above the local baseline, in noise standard
+    /// deviations
+    pub sigma: f64,
+}
+
+/// Straight line through the flanks of the centre, evaluated at each
+/// point of the window, and the robust noise level of the flanks
+fn center_flank_fit(values: &[f64], center: usize) -> (impl Fn(usize) -> f64, f64) {
+    let flank: Vec<(f64, f64)> = (CENTER_GLITCH_HALF_WIDTH + 2..=CENTER_GLITCH_FLANK)
+        .flat_map(|d| [center - d, center + d])
+        .map(|i| (i as f64 - center as f64, values[i]))
+        .collect();
+    let m = flank.len() as f64;
+    let mean_x = flank.iter().map(|p| p.0).sum::<f64>() / m;
+    let mean_y = flank.iter().map(|p| p.1).sum::<f64>() / m;
+    let sxx: f64 = flank.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    let sxy: f64 = flank.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    let slope = if sxx > 0.0 { sxy / sxx } else { 0.0 };
+    let intercept = mean_y - slope * mean_x;
+
+    let mut residuals: Vec<f64> = flank.iter().map(|p| (p.1 - intercept - slope * p.0).abs()).collect();
+    residuals.sort_by(|a, b| a.total_cmp(b));
+    let sigma = 1.4826 * residuals[residuals.len() / 2];
+    let c = center as f64;
+    (move |i: usize| intercept + slope * (i as f64 - c), sigma)
+}
+
+/// Look for the spike at zero frequency that a DC offset of the FID
+/// leaves at the centre of a 1D spectrum after the Fourier transform.
+///
+/// The real and imaginary parts are compared with a line fitted to the
+/// points either side; a glitch is at most a few points wide, stands more
+/// than `threshold_sigma` noise standard deviations off that line, and
+/// the points just outside it are back at the baseline, which keeps a
+/// real signal sitting on the carrier from being flagged.
+pub fn detect_center_glitch(spectrum: &SpectrumData, threshold_sigma: f64) -> Option<CenterGlitch> {
+    let n = spectrum.real.len();
+    if !spectrum.is_frequency_domain || spectrum.is_2d() || n < 4 * CENTER_GLITCH_FLANK {
+        return None;
+    }
+    // Zero frequency after the FFT shift and reversal of `fourier_transform`
+    let center = n - 1 - n / 2;
+
+    let mut channels = vec![&spectrum.real];
+    if spectrum.imag.len() == n {
+        channels.push(&spectrum.imag);
+    }
+    let fits: Vec<_> = channels.iter().map(|values| center_flank_fit(values, center)).collect();
+    let deviation = |i: usize| -> f64 {
+        channels
+            .iter()
+            .zip(&fits)
+            .map(|(values, (line, sigma))| {
+                let scale = sigma.max(f64::MIN_POSITIVE);
+                (values[i] - line(i)).abs() / scale
+            })
+            .fold(0.0, f64::max)
+    };
+
+    let height = deviation(center);
+    if height <= threshold_sigma {
+        return None;
+    }
+    let mut start = center;
+    while center - start < CENTER_GLITCH_HALF_WIDTH && deviation(start - 1) > threshold_sigma {
+        start -= 1;
+    }
+    let mut end = center;
+    while end - center < CENTER_GLITCH_HALF_WIDTH && deviation(end + 1) > threshold_sigma {
+        end += 1;
+    }
+    if deviation(start - 1) > CENTER_GLITCH_GUARD_SIGMA || deviation(end + 1) > CENTER_GLITCH_GUARD_SIGMA {
+        return None;
+    }
+
+    let ppm = spectrum
+        .axes
+        .first()
+        .map(|ax| ax.index_to_ppm(center))
+        .unwrap_or(0.0);
+    Some(CenterGlitch { start, end, ppm, sigma: height })
+}
+
+/// Replace a centre glitch found by [`detect_center_glitch`] with a
+/// straight line between the points either side of it, in both the real
+/// and imaginary parts
+pub fn remove_center_glitch(spectrum: &mut SpectrumData, glitch: &CenterGlitch, log: &mut ReproLog) {
+    let n = spectrum.real.len();
+    if glitch.start == 0 || glitch.end + 1 >= n {
+        return;
+    }
+    let (left, right) = (glitch.start - 1, glitch.end + 1);
+    let span = (right - left) as f64;
+    let imag_len = spectrum.imag.len();
+    for values in [&mut spectrum.real, &mut spectrum.imag] {
+        if values.len() != n {
+            continue;
+        }
+        let (a, b) = (values[left], values[right]);
+        for (offset, v) in values[glitch.start..=glitch.end].iter_mut().enumerate() {
+            let frac = (offset + 1) as f64 / span;
+            *v = a + (b - a) * frac;
+        }
+    }
+
+    log.add_entry(
+        "Center Glitch Removal",
+        &format!(
+            "Interpolated {} point(s) of the zero-frequency spike at {:.3} ppm ({:.0}σ above the baseline{})",
+            glitch.end - glitch.start + 1,
+            glitch.ppm,
+            glitch.sigma,
+            if imag_len == n { ", real and imaginary" } else { "" }
+        ),
+        "# after FT (no NMRPipe equivalent); before FT, nmrPipe -fn POLY -time removes the DC offset behind it",
+    );
+}
+
+// =========================================================================
+//  Non-finite Values
+// =========================================================================
+
+/// Positions listed in a [`NonFiniteReport`]; the counts cover the rest
+const NON_FINITE_LOCATIONS: usize = 6;
+
+/// NaN and ±Inf values in the data of a spectrum.  A corrupted conversion
+/// can leave them behind; they blank the plot and spread through any FFT
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NonFiniteReport {
+    pub nan: usize,
+    pub infinite: usize,
+    /// First few positions, e.g. "real[1024]" or "2D imaginary[3, 17]"
+    pub locations: Vec<String>,
+}
+
+impl NonFiniteReport {
+    pub fn total(&self) -> usize {
+        self.nan + self.infinite
+    }
+}
+
+impl std::fmt::Display for NonFiniteReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} NaN, {} infinite at {}", self.nan, self.infinite, self.locations.join(", "))?;
+        if self.total() > self.locations.len() {
+            write!(f, ", …")?;
+        }
+        Ok(())
+    }
+}
+
+/// What non-finite values are replaced with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NonFiniteFix {
+    Zero,
+    /// Straight line between the nearest finite points of the trace
+    Interpolate,
+}
+
+impl std::fmt::Display for NonFiniteFix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NonFiniteFix::Zero => write!(f, "zero"),
+            NonFiniteFix::Interpolate => write!(f, "interpolated"),
+        }
+    }
+}
+
+/// Count and locate the non-finite values of a spectrum; `None` when all
+/// are finite.  The 1D arrays of 2D data mirror its first row and are not
+/// counted twice
+pub fn find_non_finite(spectrum: &SpectrumData) -> Option<NonFiniteReport> {
+    let mut report = NonFiniteReport::default();
+    let mut scan = |name: &str, row: Option<usize>, values: &[f64]| {
+        for (i, v) in values.iter().enumerate().filter(|(_, v)| !v.is_finite()) {
+            if v.is_nan() {
+                report.nan += 1;
+            } else {
+                report.infinite += 1;
+            }
+            if report.locations.len() < NON_FINITE_LOCATIONS {
+                report.locations.push(match row {
+                    Some(r) => format!("{}[{}, {}]", name, r, i),
+                    None => format!("{}[{}]", name, i),
+                });
+            }
+        }
+    };
+
+    if spectrum.is_2d() {
+        let quadrants = spectrum.f1_quadrants.as_ref();
+        let matrices = [
+            ("2D real", Some(&spectrum.data_2d)),
+            ("2D imaginary", Some(&spectrum.data_2d_imag)),
+            ("2D F1-imaginary", quadrants.map(|q| &q.ir)),
+            ("2D F1/F2-imaginary", quadrants.map(|q| &q.ii)),
+        ];
+        for (name, matrix) in matrices {
+            for (r, row) in matrix.into_iter().flatten().enumerate() {
+                scan(name, Some(r), row);
+            }
+        }
+    } else {
+        scan("real", None, &spectrum.real);
+        scan("imaginary", None, &spectrum.imag);
+    }
+    (report.total() > 0).then_some(report)
+}
+
+/// Replace the non-finite values of one trace; returns how many
+fn replace_non_finite_trace(values: &mut [f64], fix: NonFiniteFix) -> usize {
+    let n = values.len();
+    let mut replaced = 0;
+    let mut i = 0;
+    while i < n {
+        if values[i].is_finite() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < n && !values[i].is_finite() {
+            i += 1;
+        }
+        // values[start..i] is a run of non-finite points
+        let left = start.checked_sub(1).map(|k| values[k]);
+        let right = (i < n).then(|| values[i]);
+        let span = (i - start + 1) as f64;
+        for (offset, v) in values[start..i].iter_mut().enumerate() {
+            *v = match (fix, left, right) {
+                (NonFiniteFix::Zero, _, _) => 0.0,
+                (_, Some(a), Some(b)) => a + (b - a) * (offset + 1) as f64 / span,
+                (_, Some(a), None) => a,
+                (_, None, Some(b)) => b,
+                (_, None, None) => 0.0,
+            };
+        }
+        replaced += i - start;
+    }
+    replaced
+}
+
+/// Replace every NaN/Inf of a spectrum with zero or by interpolation along
+/// its trace (F2 rows for 2D data); returns how many were replaced
+pub fn replace_non_finite(spectrum: &mut SpectrumData, fix: NonFiniteFix, log: &mut ReproLog) -> usize {
+    let mut replaced = 0;
+    if spectrum.is_2d() {
+        let mut matrices = vec![&mut spectrum.data_2d, &mut spectrum.data_2d_imag];
+        if let Some(q) = spectrum.f1_quadrants.as_mut() {
+            matrices.extend([&mut q.ir, &mut q.ii]);
+        }
+        for row in matrices.into_iter().flatten() {
+            replaced += replace_non_finite_trace(row, fix);
+        }
+        if let Some(first) = spectrum.data_2d.first() {
+            spectrum.real = first.clone();
+        }
+    } else {
+        replaced += replace_non_finite_trace(&mut spectrum.real, fix);
+        replaced += replace_non_finite_trace(&mut spectrum.imag, fix);
+    }
+
+    if replaced > 0 {
+        log.add_entry(
+            "Non-finite Values",
+            &format!("Replaced {} NaN/Inf value(s) with {} values", replaced, fix),
+            "# NaN/Inf repair (no NMRPipe equivalent)",
+        );
+    }
+    replaced
+}
+
+// =========================================================================
+//  Baseline Correction
+// =========================================================================
+
+/// Baseline model fitted by automatic baseline correction
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum BaselineMethod {
+    /// Straight line between the means of the outer 10% of the spectrum
+    #[default]
+    Linear,
+    /// Polynomial of `order` fitted to the points detected as signal-free
+    Polynomial { order: usize },
+    /// Asymmetric least squares (Whittaker smoother): `lambda` sets the
+    /// smoothness, `p` the weight of points above the baseline
+    Als { lambda: f64, p: f64 },
+}
+
+impl std::fmt::Display for BaselineMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BaselineMethod::Linear => write!(f, "linear"),
+            BaselineMethod::Polynomial { order } => write!(f, "polynomial, order {}", order),
+            BaselineMethod::Als { lambda, p } => write!(f, "ALS, λ={:.0e}, p={}", lambda, p),
+        }
+    }
+}
+
+/// Highest polynomial order offered for baseline fits
+pub const BASELINE_MAX_ORDER: usize = 12;
+/// Reweighting passes of the asymmetric least squares fit
+const ALS_ITERATIONS: usize = 10;
+/// A point is signal when the first derivative within its window exceeds
+/// this many noise standard deviations
+const BASELINE_DERIVATIVE_THRESHOLD: f64 = 4.0;
+
+/// Points free of signal, for fitting a baseline model.  The first
+/// derivative removes slow baseline roll, so only peaks rise above its
+/// noise; every point within a window of such a rise (1/256 of the
+/// spectrum either side) belongs to the peak, which also covers the flat
+/// tops and the tails.
+pub fn detect_baseline_points(real: &[f64]) -> Vec<bool> {
+    let n = real.len();
+    if n < 8 {
+        return vec![true; n];
+    }
+    // The difference of two points has √2 times the noise of one
+    let threshold = BASELINE_DERIVATIVE_THRESHOLD * noise_rms(real) * std::f64::consts::SQRT_2;
+    let window = (n / 256).max(2);
+    let mut is_baseline = vec![true; n];
+    for (i, pair) in real.windows(2).enumerate() {
+        if (pair[1] - pair[0]).abs() > threshold {
+            let lo = i.saturating_sub(window);
+            let hi = (i + 1 + window).min(n - 1);
+            is_baseline[lo..=hi].iter_mut().for_each(|b| *b = false);
+        }
+    }
+    is_baseline
+}
+
+/// Baseline of a spectrum under `method`, one value per point of `real`
+pub fn fit_baseline(real: &[f64], method: &BaselineMethod) -> Vec<f64> {
+    let n = real.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    match *method {
+        BaselineMethod::Linear => {
+            let edge = ((n as f64 * 0.1) as usize).max(1);
+            let left_mean = real[..edge].iter().sum::<f64>() / edge as f64;
+            let right_mean = real[n - edge..].iter().sum::<f64>() / edge as f64;
+            (0..n)
+                .map(|i| left_mean + (right_mean - left_mean) * (i as f64 / n as f64))
+                .collect()
+        }
+        BaselineMethod::Polynomial { order } => {
+            let mut mask = detect_baseline_points(real);
+            if mask.iter().filter(|&&b| b).count() <= order {
+                // Signal everywhere: fit all points rather than nothing
+                mask = vec![true; n];
+            }
+            polynomial_baseline(real, &mask, order)
+        }
+        BaselineMethod::Als { lambda, p } => als_baseline(real, lambda, p),
+    }
+}
+
+/// Least-squares polynomial through the points where `mask` is set, in
+/// Chebyshev polynomials over [-1, 1] so high orders stay well conditioned
+fn polynomial_baseline(real: &[f64], mask: &[bool], order: usize) -> Vec<f64> {
+    let n = real.len();
+    let terms = order.min(BASELINE_MAX_ORDER) + 1;
+    let basis = |i: usize| {
+        let x = if n > 1 { 2.0 * i as f64 / (n - 1) as f64 - 1.0 } else { 0.0 };
+        let mut t = vec![1.0; terms];
+        if terms > 1 {
+            t[1] = x;
+        }
+        for k in 2..terms {
+            t[k] = 2.0 * x * t[k - 1] - t[k - 2];
+        }
+        t
+    };
+    // Normal equations
+    let mut ata = vec![vec![0.0; terms]; terms];
+    let mut atb = vec![0.0; terms];
+    for i in (0..n).filter(|&i| mask[i]) {
+        let t = basis(i);
+        for (j, tj) in t.iter().enumerate() {
+            atb[j] += tj * real[i];
+            for (k, tk) in t.iter().enumerate() {
+                ata[j][k] += tj * tk;
+            }
+        }
+    }
+    let Some(coeffs) = solve_linear(ata, atb) else {
+        return vec![0.0; n];
+    };
+    (0..n)
+        .map(|i| basis(i).iter().zip(&coeffs).map(|(t, c)| t * c).sum())
+        .collect()
+}
+
+/// Asymmetric least squares baseline (Eilers & Boelens, 2005): the
+/// Whittaker smoother minimises Σ wᵢ(yᵢ − zᵢ)² + λ Σ (Δ²zᵢ)², and points
+/// above the smooth curve get the small weight `p` (those below 1 − p), so
+/// the curve settles under the peaks after a few reweighting passes.
+fn als_baseline(real: &[f64], lambda: f64, p: f64) -> Vec<f64> {
+    let n = real.len();
+    if n < 3 {
+        return real.to_vec();
+    }
+    // λ DᵀD for the second-difference matrix D, as its three bands
+    let mut diag = vec![0.0; n];
+    let mut off1 = vec![0.0; n - 1];
+    let mut off2 = vec![0.0; n - 2];
+    let c = [1.0, -2.0, 1.0];
+    for r in 0..n - 2 {
+        for j in 0..3 {
+            diag[r + j] += lambda * c[j] * c[j];
+        }
+        for j in 0..2 {
+            off1[r + j] += lambda * c[j] * c[j + 1];
+        }
+        off2[r] += lambda * c[0] * c[2];
+    }
+    let mut weights = vec![1.0; n];
+    let mut z = real.to_vec();
+    for _ in 0..ALS_ITERATIONS {
+        let d: Vec<f64> = diag.iter().zip(&weights).map(|(d, w)| d + w).collect();
+        let rhs: Vec<f64> = real.iter().zip(&weights).map(|(y, w)| y * w).collect();
+        z = solve_pentadiagonal(&d, &off1, &off2, &rhs);
+        let next: Vec<f64> = real.iter().zip(&z).map(|(y, b)| if y > b { p } else { 1.0 - p }).collect();
+        if next == weights {
+            break;
+        }
+        weights = next;
+    }
+    z
+}
+
+/// Solve a symmetric positive definite system with bands `diag`, `off1`
+/// and `off2` by banded Cholesky factorisation
+fn solve_pentadiagonal(diag: &[f64], off1: &[f64], off2: &[f64], rhs: &[f64]) -> Vec<f64> {
+    let n = diag.len();
+    // L has the diagonal l0 and the two sub-diagonals l1 (i, i−1), l2 (i, i−2)
+    let (mut l0, mut l1, mut l2) = (vec![0.0; n], vec![0.0; n], vec![0.0; n]);
+    for i in 0..n {
+        if i >= 2 {
+            l2[i] = off2[i - 2] / l0[i - 2];
+        }
+        if i >= 1 {
+            let above = if i >= 2 { l2[i] * l1[i - 1] } else { 0.0 };
+            l1[i] = (off1[i - 1] - above) / l0[i - 1];
+        }
+        let sq = diag[i] - l1[i] * l1[i] - l2[i] * l2[i];
+        l0[i] = sq.max(1e-300).sqrt();
+    }
+    let mut y = vec![0.0; n];
+    for i in 0..n {
+        let mut v = rhs[i];
+        if i >= 1 {
+            v -= l1[i] * y[i - 1];
+        }
+        if i >= 2 {
+            v -= l2[i] * y[i - 2];
+        }
+        y[i] = v / l0[i];
+    }
+    let mut x = vec![0.0; n];
+    for i in (0..n).rev() {
+        let mut v = y[i];
+        if i + 1 < n {
+            v -= l1[i + 1] * x[i + 1];
+        }
+        if i + 2 < n {
+            v -= l2[i + 2] * x[i + 2];
+        }
+        x[i] = v / l0[i];
+    }
+    x
+}
+
+/// Automatic baseline correction: fit `method` to the real part and
+/// subtract it from every point
+pub fn baseline_correct(
+    spectrum: &mut SpectrumData,
+    method: &BaselineMethod,
+    log: &mut ReproLog,
+) {
+    let n = spectrum.real.len();
+    if n == 0 {
+        return;
+    }
+    let free = detect_baseline_points(&spectrum.real).iter().filter(|&&b| b).count();
+    let baseline = fit_baseline(&spectrum.real, method);
+    for (v, b) in spectrum.real.iter_mut().zip(&baseline) {
+        *v -= b;
+    }
+
+    match *method {
+        BaselineMethod::Linear => log.add_entry(
+            "Baseline Correction",
+            &format!(
+                "Linear baseline correction (left={:.2}, right={:.2})",
+                baseline[0],
+                baseline[n - 1]
+            ),
+            "nmrPipe -fn POLY -auto",
+        ),
+        BaselineMethod::Polynomial { order } => log.add_entry(
+            "Polynomial Baseline Correction",
+            &format!("Order-{} polynomial fitted to {} of {} points detected as baseline", order, free, n),
+            &format!("nmrPipe -fn POLY -auto -ord {}", order),
+        ),
+        BaselineMethod::Als { lambda, p } => log.add_entry(
+            "ALS Baseline Correction",
+            &format!(
+                "Asymmetric least squares baseline (λ={:.0e}, p={}, {} passes at most)",
+                lambda, p, ALS_ITERATIONS
+            ),
+            &format!("# ALS baseline, lambda={:e} p={} (no NMRPipe equivalent)", lambda, p),
+        ),
+    }
+}
+
+/// Manual baseline correction using user-picked anchor points.
+/// Performs piecewise-linear interpolation between sorted anchor points
+/// and subtracts the resulting baseline from the spectrum.
+pub fn manual_baseline_correct(
+    spectrum: &mut SpectrumData,
+    anchor_points: &[[f64; 2]], // (ppm, intensity) pairs
+    log: &mut ReproLog,
+) {
+    let n = spectrum.real.len();
+    if n == 0 || anchor_points.len() < 2 {
+        return;
+    }
+
+    // Sort anchors by ppm
+    let mut anchors = anchor_points.to_vec();
+    anchors.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap());
+
+    // Build the ppm scale
+    let ppm_scale = spectrum.x_scale();
+
+    // For each data point, interpolate baseline from anchors
+    for i in 0..n {
+        let ppm = ppm_scale[i];
+
+        // Find surrounding anchors
+        let baseline_val = if ppm <= anchors[0][0] {
+            // Extrapolate from first two points
+            let (x0, y0) = (anchors[0][0], anchors[0][1]);
+            let (x1, y1) = (anchors[1][0], anchors[1][1]);
+            if (x1 - x0).abs() > 1e-12 {
+                y0 + (ppm - x0) * (y1 - y0) / (x1 - x0)
+            } else {
+                y0
+            }
+        } else if ppm >= anchors[anchors.len() - 1][0] {
+            // Extrapolate from last two points
+            let len = anchors.len();
+            let (x0, y0) = (anchors[len - 2][0], anchors[len - 2][1]);
+            let (x1, y1) = (anchors[len - 1][0], anchors[len - 1][1]);
+            if (x1 - x0).abs() > 1e-12 {
+                y0 + (ppm - x0) * (y1 - y0) / (x1 - x0)
+            } else {
+                y1
+            }
+        } else {
+            // Interpolate between surrounding anchors
+            let mut val = 0.0;
+            for j in 0..anchors.len() - 1 {
+                if ppm >= anchors[j][0] && ppm <= anchors[j + 1][0] {
+                    let (x0, y0) = (anchors[j][0], anchors[j][1]);
+                    let (x1, y1) = (anchors[j + 1][0], anchors[j + 1][1]);
+                    let frac = if (x1 - x0).abs() > 1e-12 {
+                        (ppm - x0) / (x1 - x0)
+                    } else {
+                        0.5
+                    };
+                    val = y0 + frac * (y1 - y0);
+                    break;
+                }
+            }
+            val
+        };
+
+        spectrum.real[i] -= baseline_val;
+    }
+
+    let ppm_list: Vec<String> = anchors.iter().map(|a| format!("{:.2}", a[0])).collect();
+    log.add_entry(
+        "Manual Baseline Correction",
+        &format!(
+            "Piecewise-linear baseline from {} anchor points at ppm: [{}]",
+            anchors.len(),
+            ppm_list.join(", ")
+        ),
+        &format!(
+            "# Manual baseline correction with {} user-defined anchor points",
+            anchors.len()
+        ),
+    );
+}
+
+// =========================================================================
+//  Peak Detection
+// =========================================================================
+
+/// Which peaks peak picking looks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PeakSign {
+    /// Maxima above the baseline
+    #[default]
+    Positive,
+    /// Minima below the baseline (e.g. CH2 in DEPT-135)
+    Negative,
+    /// Both, for DEPT-135, APT and phase-sensitive 2D rows
+    Both,
+}
+
+impl std::fmt::Display for PeakSign {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PeakSign::Positive => write!(f, "positive"),
+            PeakSign::Negative => write!(f, "negative"),
+            PeakSign::Both => write!(f, "positive & negative"),
+        }
+    }
+}
+
+impl PeakSign {
+    /// Usual choice for an experiment: both signs for edited carbon spectra
+    /// (DEPT-135, APT), positive otherwise
+    pub fn default_for(experiment: &ExperimentType) -> Self {
+        match experiment {
+            ExperimentType::Dept135 => PeakSign::Both,
+            ExperimentType::Other(name) if name.to_uppercase().contains("APT") => PeakSign::Both,
+            _ => PeakSign::Positive,
+        }
+    }
+}
+
+/// Simple peak detection: find local extrema beyond a noise threshold.
+///
+/// `sign` selects maxima, minima or both; the threshold is a fraction of
+/// the largest |intensity| of the selected sign(s).  Negative peaks keep
+/// their negative intensity.  Returns peaks as `[ppm, intensity]` pairs
+/// sorted by ppm descending.
+pub fn detect_peaks(
+    spectrum: &SpectrumData,
+    threshold_fraction: f64, // 0.0–1.0, fraction of max intensity
+    min_distance: usize,     // minimum index distance between accepted peaks
+    sign: PeakSign,
+) -> Vec<[f64; 2]> {
+    let n = spectrum.real.len();
+    if n < 3 {
+        return vec![];
+    }
+
+    let want_pos = sign != PeakSign::Negative;
+    let want_neg = sign != PeakSign::Positive;
+    let max_abs = spectrum
+        .real
+        .iter()
+        .map(|&v| if (v > 0.0 && want_pos) || (v < 0.0 && want_neg) { v.abs() } else { 0.0 })
+        .fold(0.0, f64::max);
+    if max_abs <= 0.0 {
+        return vec![];
+    }
+    let threshold = max_abs * threshold_fraction;
+
+    // Collect local-extremum candidates beyond threshold, by |intensity|
+    let mut candidates: Vec<(usize, f64)> = Vec::new();
+    for i in 1..n - 1 {
+        let (prev, val, next) = (spectrum.real[i - 1], spectrum.real[i], spectrum.real[i + 1]);
+        let is_max = want_pos && val > threshold && val >= prev && val >= next;
+        let is_min = want_neg && -val > threshold && val <= prev && val <= next;
+        if is_max || is_min {
+            candidates.push((i, val.abs()));
+        }
+    }
+
+    // Keep strongest first, enforce minimum distance
+    candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    let mut selected: Vec<usize> = Vec::new();
+    for &(idx, _) in &candidates {
+        let too_close = selected
+            .iter()
+            .any(|&s| (idx as i64 - s as i64).unsigned_abs() as usize <= min_distance);
+        if !too_close {
+            selected.push(idx);
+        }
+    }
+
+    // Build ppm scale
+    let ppm_scale = spectrum.x_scale();
+
+    let mut peaks: Vec<[f64; 2]> = selected
+        .iter()
+        .filter_map(|&i| {
+            if i < ppm_scale.len() {
+                Some([refined_position(&spectrum.real, &ppm_scale, i), spectrum.real[i]])
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    // Sort by ppm descending (NMR convention: high ppm first)
+    peaks.sort_by(|a, b| b[0].partial_cmp(&a[0]).unwrap());
+    peaks
+}
+
+/// Offset (−0.5…0.5 points) of the vertex of the parabola through the
+/// extremum at `i` and its two neighbours; 0 at the ends of the data or
+/// when the three points are collinear
+pub fn parabolic_offset(values: &[f64], i: usize) -> f64 {
+    if i == 0 || i + 1 >= values.len() {
+        return 0.0;
+    }
+    let (a, b, c) = (values[i - 1], values[i], values[i + 1]);
+    let curvature = a - 2.0 * b + c;
+    if curvature == 0.0 {
+        return 0.0;
+    }
+    (0.5 * (a - c) / curvature).clamp(-0.5, 0.5)
+}
+
+/// Position of the extremum at point `i` on `scale`, refined between data
+/// points by parabolic interpolation, so picked shifts (and the J values
+/// measured between them) are not quantised to the digital resolution.
+/// Intensities stay the sampled values, as everywhere else.
+pub fn refined_position(values: &[f64], scale: &[f64], i: usize) -> f64 {
+    let step = match (scale.get(i + 1), i.checked_sub(1).and_then(|p| scale.get(p))) {
+        (Some(next), _) => next - scale[i],
+        (None, Some(prev)) => scale[i] - prev,
+        (None, None) => 0.0,
+    };
+    scale[i] + parabolic_offset(values, i) * step
+}
+
+/// How peak intensities are reported in labels and exports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum IntensityMode {
+    /// Raw spectrum value at the peak
+    #[default]
+    Absolute,
+    /// Percent of the tallest peak
+    RelativeToTallest,
+    /// Intensity per proton of the reference (first) integral
+    RelativeToIntegral,
+}
+
+impl IntensityMode {
+    pub const ALL: [IntensityMode; 3] = [
+        IntensityMode::Absolute,
+        IntensityMode::RelativeToTallest,
+        IntensityMode::RelativeToIntegral,
+    ];
+
+    pub fn label(&self) -> &str {
+        match self {
+            IntensityMode::Absolute => "Absolute",
+            IntensityMode::RelativeToTallest => "% of tallest peak",
+            IntensityMode::RelativeToIntegral => "Per H of reference integral",
+        }
+    }
+
+    /// Column header used in data exports
+    pub fn column_name(&self) -> &str {
+        match self {
+            IntensityMode::Absolute => "Intensity",
+            IntensityMode::RelativeToTallest => "Relative_Intensity",
+            IntensityMode::RelativeToIntegral => "Intensity_per_H",
+        }
+    }
+}
+
+/// Re-read peak intensities from the current spectrum at each peak's ppm.
+///
+/// Peaks store the intensity at the time they were picked; after scaling,
+/// phasing or baseline operations (or undo) that value is stale.  Only
+/// meaningful for 1D frequency-domain data — other spectra are left alone.
+pub fn resample_peak_intensities(spectrum: &SpectrumData, peaks: &mut [[f64; 2]]) {
+    if !spectrum.is_frequency_domain || spectrum.is_2d() || spectrum.axes.is_empty() {
+        return;
+    }
+    let axis = &spectrum.axes[0];
+    let n = spectrum.real.len().min(axis.num_points);
+    if n == 0 {
+        return;
+    }
+    for peak in peaks.iter_mut() {
+        // Nearest point; positions off the axis clamp to its ends
+        let Some(pos) = axis.ppm_to_position(peak[0]) else {
+            continue;
+        };
+        let idx = (pos.round().max(0.0) as usize).min(n - 1);
+        peak[1] = spectrum.real[idx];
+    }
+}
+
+/// Peak intensities expressed in the given mode.
+///
+/// `reference_integral` is the raw integral of the reference region and
+/// `reference_h` the number of protons it represents; without a reference
+/// integral, `RelativeToIntegral` falls back to absolute values.
+pub fn normalized_intensities(
+    peaks: &[[f64; 2]],
+    mode: IntensityMode,
+    reference_integral: Option<f64>,
+    reference_h: f64,
+) -> Vec<f64> {
+    match mode {
+        IntensityMode::Absolute => peaks.iter().map(|p| p[1]).collect(),
+        IntensityMode::RelativeToTallest => {
+            let max_intensity = peaks
+                .iter()
+                .map(|p| p[1].abs())
+                .fold(0.0f64, f64::max)
+                .max(1e-20);
+            peaks.iter().map(|p| p[1] / max_intensity * 100.0).collect()
+        }
+        IntensityMode::RelativeToIntegral => match reference_integral {
+            Some(raw) if raw.abs() > 1e-20 && reference_h > 0.0 => {
+                let per_h = raw / reference_h;
+                peaks.iter().map(|p| p[1] / per_h).collect()
+            }
+            _ => peaks.iter().map(|p| p[1]).collect(),
+        },
+    }
+}
+
+// =========================================================================
+//  Linewidth (FWHM)
+// =========================================================================
+
+/// Full width at half maximum of a 1D peak
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Linewidth {
+    /// Peak top: [ppm, intensity]
+    pub peak: [f64; 2],
+    /// Interpolated half-height crossings on either side of the top
+    pub hi_ppm: f64,
+    pub lo_ppm: f64,
+    pub fwhm_hz: f64,
+}
+
+impl Linewidth {
+    pub fn fwhm_ppm(&self) -> f64 {
+        self.hi_ppm - self.lo_ppm
+    }
+
+    /// Intensity at half height (negative for a negative peak)
+    pub fn half_height(&self) -> f64 {
+        self.peak[1] / 2.0
+    }
+}
+
+/// Measure the FWHM of the 1D peak at `ppm`.
+///
+/// Climbs from the nearest point to the top of the peak (downwards for a
+/// negative peak), walks out on both sides until the trace drops below
+/// half height and interpolates each crossing linearly between the two
+/// points straddling it.  `None` for non-spectra and when either side never
+/// reaches half height (a peak at the edge of the window, or a shoulder on
+/// a taller line).
+pub fn measure_linewidth(spectrum: &SpectrumData, ppm: f64) -> Option<Linewidth> {
+    if !spectrum.is_frequency_domain || spectrum.is_2d() {
+        return None;
+    }
+    let axis = spectrum.axes.first()?;
+    let data = &spectrum.real;
+    let n = data.len().min(axis.num_points);
+    if n < 3 || axis.observe_freq_mhz <= 0.0 {
+        return None;
+    }
+    let start = (axis.ppm_to_position(ppm)?.round().max(0.0) as usize).min(n - 1);
+    let sign = if data[start] < 0.0 { -1.0 } else { 1.0 };
+    let value = |i: usize| sign * data[i];
+
+    let mut top = start;
+    while top > 0 && value(top - 1) > value(top) {
+        top -= 1;
+    }
+    while top + 1 < n && value(top + 1) > value(top) {
+        top += 1;
+    }
+    // Parabolic interpolation of the top: the sampled maximum of a narrow
+    // line sits below the true one and would overstate the width
+    let (mut offset, mut height) = (0.0, value(top));
+    if top > 0 && top + 1 < n {
+        let (ym, y0, yp) = (value(top - 1), value(top), value(top + 1));
+        let curvature = ym - 2.0 * y0 + yp;
+        if curvature < 0.0 {
+            offset = 0.5 * (ym - yp) / curvature;
+            height = y0 - 0.25 * (ym - yp) * offset;
+        }
+    }
+    let half = height / 2.0;
+    if !(half > 0.0 && half.is_finite()) {
+        return None;
+    }
+
+    // Fractional index of the half-height crossing walking away from the top
+    let crossing = |step: isize| -> Option<f64> {
+        let mut i = top;
+        loop {
+            let j = i.checked_add_signed(step).filter(|&j| j < n)?;
+            if value(j) < half {
+                let t = (value(i) - half) / (value(i) - value(j));
+                return Some(i as f64 + step as f64 * t);
+            }
+            i = j;
+        }
+    };
+    let (left, right) = (crossing(-1)?, crossing(1)?);
+    let ppm_per_point = axis.ppm_per_point();
+    let origin = axis.index_to_ppm(0);
+    Some(Linewidth {
+        peak: [origin - (top as f64 + offset) * ppm_per_point, sign * height],
+        hi_ppm: origin - left * ppm_per_point,
+        lo_ppm: origin - right * ppm_per_point,
+        fwhm_hz: (right - left) * axis.hz_per_point(),
+    })
+}
+
+// =========================================================================
+//  Peak Fitting
+// =========================================================================
+
+/// Line shape of a peak fit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LineShape {
+    #[default]
+    Lorentzian,
+    Gaussian,
+    /// Sum of a Lorentzian and a Gaussian of the same width, with the
+    /// Lorentzian fraction fitted per peak
+    PseudoVoigt,
+}
+
+impl LineShape {
+    pub const ALL: [LineShape; 3] = [LineShape::Lorentzian, LineShape::Gaussian, LineShape::PseudoVoigt];
+
+    pub fn label(&self) -> &str {
+        match self {
+            LineShape::Lorentzian => "Lorentzian",
+            LineShape::Gaussian => "Gaussian",
+            LineShape::PseudoVoigt => "Pseudo-Voigt",
+        }
+    }
+
+    /// Parameters fitted per peak: centre, width, amplitude (and the
+    /// Lorentzian fraction of a pseudo-Voigt)
+    fn stride(self) -> usize {
+        if self == LineShape::PseudoVoigt { 4 } else { 3 }
+    }
+}
+
+impl std::fmt::Display for LineShape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// One fitted line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FittedPeak {
+    pub center_ppm: f64,
+    pub fwhm_hz: f64,
+    /// Height of the line above the fitted offset
+    pub amplitude: f64,
+    /// Area of the line in the units of [`integrate_region`] (intensity
+    /// summed over points), so it compares directly with integrals
+    pub area: f64,
+    /// Lorentzian share of the line: 1 for a Lorentzian, 0 for a Gaussian
+    pub lorentz_fraction: f64,
+}
+
+/// Result of fitting a region with a sum of lines
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeakFit {
+    pub lo_ppm: f64,
+    pub hi_ppm: f64,
+    pub shape: LineShape,
+    /// Lines in ppm order, highest first
+    pub peaks: Vec<FittedPeak>,
+    /// Constant baseline under the region
+    pub offset: f64,
+    /// RMS of the residual relative to the largest point in the region
+    pub relative_residual: f64,
+    /// Sum of the lines and offset over the region: [ppm, intensity]
+    pub trace: Vec<[f64; 2]>,
+    /// Each line on the offset, in the order of `peaks`
+    pub components: Vec<Vec<[f64; 2]>>,
+}
+
+/// Iteration limit of the Levenberg–Marquardt fit
+const FIT_MAX_ITERATIONS: usize = 200;
+/// Narrowest line a fit may shrink to, in points
+const FIT_MIN_WIDTH_POINTS: f64 = 0.3;
+
+/// Area of a line of unit height and unit FWHM
+fn unit_line_area(lorentz_fraction: f64) -> f64 {
+    let lorentzian = PI / 2.0;
+    let gaussian = (PI / std::f64::consts::LN_2).sqrt() / 2.0;
+    lorentz_fraction * lorentzian + (1.0 - lorentz_fraction) * gaussian
+}
+
+/// Model of a peak fit at point `x` (fractional index), with its partial
+/// derivatives in `grad`.  `p` holds the offset, then for each line its
+/// centre and FWHM in points, amplitude and, for a pseudo-Voigt, its
+/// Lorentzian fraction.
+fn fit_model(p: &[f64], shape: LineShape, x: f64, grad: &mut [f64]) -> f64 {
+    let stride = shape.stride();
+    let mut value = p[0];
+    grad[0] = 1.0;
+    for (k, line) in p[1..].chunks(stride).enumerate() {
+        let (x0, w, a) = (line[0], line[1], line[2]);
+        let eta = match shape {
+            LineShape::Lorentzian => 1.0,
+            LineShape::Gaussian => 0.0,
+            LineShape::PseudoVoigt => line[3],
+        };
+        let u = 2.0 * (x - x0) / w;
+        let l = 1.0 / (1.0 + u * u);
+        let g = (-std::f64::consts::LN_2 * u * u).exp();
+        let s = eta * l + (1.0 - eta) * g;
+        let ds_du = eta * (-2.0 * u * l * l) + (1.0 - eta) * (-2.0 * std::f64::consts::LN_2 * u * g);
+        let j = 1 + k * stride;
+        grad[j] = a * ds_du * (-2.0 / w);
+        grad[j + 1] = a * ds_du * (-u / w);
+        grad[j + 2] = s;
+        if shape == LineShape::PseudoVoigt {
+            grad[j + 3] = a * (l - g);
+        }
+        value += a * s;
+    }
+    value
+}
+
+/// Fit the 1D spectrum between `lo_ppm` and `hi_ppm` with a sum of lines of
+/// `shape` on a constant offset, by Levenberg–Marquardt least squares.
+///
+/// One line is fitted per ppm in `starts` inside the region (normally the
+/// picked peaks), starting from its height and half-height width; with
+/// none, a single line starts at the largest point.  `None` for
+/// non-spectra, regions with fewer points than parameters, or when the
+/// normal equations are singular from the start.
+pub fn fit_peaks(spectrum: &SpectrumData, lo_ppm: f64, hi_ppm: f64, starts: &[f64], shape: LineShape) -> Option<PeakFit> {
+    if !spectrum.is_frequency_domain || spectrum.is_2d() {
+        return None;
+    }
+    let axis = spectrum.axes.first()?;
+    let n = spectrum.real.len().min(axis.num_points);
+    if n < 3 || axis.observe_freq_mhz <= 0.0 {
+        return None;
+    }
+    let (lo, hi) = (lo_ppm.min(hi_ppm), lo_ppm.max(hi_ppm));
+    let first = axis.ppm_to_position(hi)?.ceil().max(0.0);
+    let last = axis.ppm_to_position(lo)?.floor().min((n - 1) as f64);
+    if last < first {
+        return None;
+    }
+    let (first, last) = (first as usize, last as usize);
+    let ys = &spectrum.real[first..=last];
+    let hz_per_point = axis.hz_per_point();
+
+    // Starting values
+    let offset = (ys[0] + ys[ys.len() - 1]) / 2.0;
+    let mut centres: Vec<f64> = starts
+        .iter()
+        .filter(|&&ppm| ppm >= lo && ppm <= hi)
+        .filter_map(|&ppm| axis.ppm_to_position(ppm))
+        .collect();
+    if centres.is_empty() {
+        let top = (0..ys.len()).max_by(|&i, &j| (ys[i] - offset).abs().total_cmp(&(ys[j] - offset).abs()))?;
+        centres.push((first + top) as f64);
+    }
+    let stride = shape.stride();
+    let mut p = vec![offset];
+    for &x0 in &centres {
+        let i = (x0.round() as usize).clamp(first, last);
+        let width = measure_linewidth(spectrum, axis.index_to_ppm(i))
+            .map(|lw| lw.fwhm_hz / hz_per_point)
+            .filter(|w| w.is_finite())
+            .unwrap_or(3.0)
+            .clamp(1.0, ys.len() as f64);
+        p.extend([x0, width, spectrum.real[i] - offset]);
+        if shape == LineShape::PseudoVoigt {
+            p.push(0.5);
+        }
+    }
+    let m = p.len();
+    if ys.len() <= m {
+        return None;
+    }
+    let constrain = |p: &mut Vec<f64>| {
+        for line in p[1..].chunks_mut(stride) {
+            line[0] = line[0].clamp(first as f64, last as f64);
+            line[1] = line[1].abs().max(FIT_MIN_WIDTH_POINTS);
+            if shape == LineShape::PseudoVoigt {
+                line[3] = line[3].clamp(0.0, 1.0);
+            }
+        }
+    };
+    let cost_of = |p: &[f64]| -> f64 {
+        let mut grad = vec![0.0; m];
+        ys.iter()
+            .enumerate()
+            .map(|(i, &y)| (y - fit_model(p, shape, (first + i) as f64, &mut grad)).powi(2))
+            .sum()
+    };
+
+    // Levenberg–Marquardt with Marquardt's diagonal scaling
+    let mut cost = cost_of(&p);
+    let mut lambda = 1e-3;
+    let mut grad = vec![0.0; m];
+    'outer: for _ in 0..FIT_MAX_ITERATIONS {
+        let mut jtj = vec![vec![0.0; m]; m];
+        let mut jtr = vec![0.0; m];
+        for (i, &y) in ys.iter().enumerate() {
+            let r = y - fit_model(&p, shape, (first + i) as f64, &mut grad);
+            for ((row, v), gj) in jtj.iter_mut().zip(jtr.iter_mut()).zip(&grad) {
+                *v += gj * r;
+                for (cell, gk) in row.iter_mut().zip(&grad) {
+                    *cell += gj * gk;
+                }
+            }
+        }
+        loop {
+            let mut a = jtj.clone();
+            for (j, row) in a.iter_mut().enumerate() {
+                row[j] += lambda * jtj[j][j].max(1e-30);
+            }
+            let Some(step) = solve_linear(a, jtr.clone()) else {
+                break 'outer;
+            };
+            let mut trial: Vec<f64> = p.iter().zip(&step).map(|(v, d)| v + d).collect();
+            constrain(&mut trial);
+            let trial_cost = cost_of(&trial);
+            if trial_cost.is_finite() && trial_cost < cost {
+                let converged = (cost - trial_cost) <= 1e-10 * cost;
+                p = trial;
+                cost = trial_cost;
+                lambda = (lambda / 10.0).max(1e-12);
+                if converged {
+                    break 'outer;
+                }
+                break;
+            }
+            lambda *= 10.0;
+            if lambda > 1e12 {
+                break 'outer;
+            }
+        }
+    }
+
+    let ppm_at = |x: f64| axis.index_to_ppm(0) - x * hz_per_point / axis.observe_freq_mhz;
+    let mut lines: Vec<(FittedPeak, usize)> = p[1..]
+        .chunks(stride)
+        .enumerate()
+        .map(|(k, line)| {
+            let eta = match shape {
+                LineShape::Lorentzian => 1.0,
+                LineShape::Gaussian => 0.0,
+                LineShape::PseudoVoigt => line[3],
+            };
+            let peak = FittedPeak {
+                center_ppm: ppm_at(line[0]),
+                fwhm_hz: line[1] * hz_per_point,
+                amplitude: line[2],
+                area: line[2] * line[1] * unit_line_area(eta),
+                lorentz_fraction: eta,
+            };
+            (peak, k)
+        })
+        .collect();
+    lines.sort_by(|a, b| b.0.center_ppm.total_cmp(&a.0.center_ppm));
+
+    let peak = ys.iter().fold(0.0f64, |m, v| m.max(v.abs())).max(1e-30);
+    let trace: Vec<[f64; 2]> =
+        (first..=last).map(|i| [axis.index_to_ppm(i), fit_model(&p, shape, i as f64, &mut grad)]).collect();
+    let components = lines
+        .iter()
+        .map(|&(_, k)| {
+            // The model with only line k
+            let mut single = vec![p[0]];
+            single.extend_from_slice(&p[1 + k * stride..1 + (k + 1) * stride]);
+            let mut g = vec![0.0; single.len()];
+            (first..=last).map(|i| [axis.index_to_ppm(i), fit_model(&single, shape, i as f64, &mut g)]).collect()
+        })
+        .collect();
+    Some(PeakFit {
+        lo_ppm: lo,
+        hi_ppm: hi,
+        shape,
+        peaks: lines.into_iter().map(|(peak, _)| peak).collect(),
+        offset: p[0],
+        relative_residual: (cost / ys.len() as f64).sqrt() / peak,
+        trace,
+        components,
+    })
+}
+
+// =========================================================================
+//  Multiplet Detection
+// =========================================================================
+
+/// A detected multiplet group
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Multiplet {
+    /// Center ppm of the multiplet
+    pub center_ppm: f64,
+    /// Coupling constant J in Hz (average spacing between lines)
+    pub j_hz: f64,
+    /// Number of lines in the multiplet
+    pub num_lines: usize,
+    /// Classification label
+    pub label: String,
+    /// The peaks that form this multiplet: [ppm, intensity]
+    pub peaks: Vec<[f64; 2]>,
+    /// Coupling constants from [`multiplet_couplings`], largest first;
+    /// empty until measured
+    #[serde(default)]
+    pub couplings: Vec<Coupling>,
+}
+
+/// A coupling constant averaged over the consistent line spacings of a
+/// multiplet
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Coupling {
+    pub j_hz: f64,
+    /// Sample standard deviation of the averaged spacings (0 for one)
+    pub sd_hz: f64,
+    /// Number of spacings averaged
+    pub count: usize,
+}
+
+impl std::fmt::Display for Coupling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.2} ± {:.2} Hz (n={})", self.j_hz, self.sd_hz, self.count)
+    }
+}
+
+impl Multiplet {
+    /// J value(s) for labels: the measured couplings when present
+    /// ("10.2, 4.1"), else the average spacing; `None` for singlets
+    pub fn j_text(&self) -> Option<String> {
+        if !self.couplings.is_empty() {
+            let js: Vec<String> = self.couplings.iter().map(|c| format!("{:.1}", c.j_hz)).collect();
+            Some(js.join(", "))
+        } else if self.j_hz > 0.5 {
+            Some(format!("{:.1}", self.j_hz))
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for Multiplet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.j_text() {
+            Some(j) => write!(f, "{:.2} ppm ({}, J={} Hz)", self.center_ppm, self.label, j),
+            None => write!(f, "{:.2} ppm ({})", self.center_ppm, self.label),
+        }
+    }
+}
+
+fn multiplet_label(n: usize) -> &'static str {
+    match n {
+        1 => "s",
+        2 => "d",
+        3 => "t",
+        4 => "q",
+        5 => "quint",
+        6 => "sext",
+        7 => "sept",
+        _ => "m",
+    }
+}
+
+/// Settings for [`detect_multiplets`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MultipletParams {
+    /// Largest line spacing grouped into one multiplet (~20 Hz for ¹H)
+    pub max_j_hz: f64,
+    /// Neighbouring lines whose intensities differ by more than this
+    /// factor are not grouped (a small peak beside a large one is usually
+    /// another signal; a septet's outer lines are ~1:6)
+    pub max_intensity_ratio: f64,
+    /// Groups with fewer lines are dropped (1 keeps singlets)
+    pub min_lines: usize,
+}
+
+impl Default for MultipletParams {
+    fn default() -> Self {
+        Self { max_j_hz: 20.0, max_intensity_ratio: 10.0, min_lines: 1 }
+    }
+}
+
+/// Group detected peaks into multiplets based on coupling patterns.
+///
+/// `obs_mhz`: observe frequency in MHz (needed to convert ppm spacing → Hz).
+pub fn detect_multiplets(
+    peaks: &[[f64; 2]],
+    params: &MultipletParams,
+    obs_mhz: f64,
+) -> Vec<Multiplet> {
+    if peaks.is_empty() || obs_mhz <= 0.0 {
+        return vec![];
+    }
+
+    // Sort peaks by ppm ascending for grouping
+    let mut sorted = peaks.to_vec();
+    sorted.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap());
+
+    // Convert max J from Hz to ppm
+    let max_j_ppm = params.max_j_hz / obs_mhz;
+    let similar = |a: f64, b: f64| {
+        let (a, b) = (a.abs(), b.abs());
+        a.max(b) <= a.min(b) * params.max_intensity_ratio
+    };
+
+    // Greedy grouping: walk through sorted peaks, group if gap ≤ max_j_ppm
+    // and the neighbouring intensities are comparable
+    let mut groups: Vec<Vec<[f64; 2]>> = Vec::new();
+    let mut current_group: Vec<[f64; 2]> = vec![sorted[0]];
+
+    for i in 1..sorted.len() {
+        let gap = (sorted[i][0] - sorted[i - 1][0]).abs();
+        if gap <= max_j_ppm && similar(sorted[i][1], sorted[i - 1][1]) {
+            current_group.push(sorted[i]);
+        } else {
+            groups.push(std::mem::take(&mut current_group));
+            current_group = vec![sorted[i]];
+        }
+    }
+    if !current_group.is_empty() {
+        groups.push(current_group);
+    }
+
+    // Build multiplets from groups
+    let mut multiplets: Vec<Multiplet> = Vec::new();
+    for group in groups.iter().filter(|g| g.len() >= params.min_lines) {
+        let n = group.len();
+        // Center ppm: intensity-weighted average
+        let total_int: f64 = group.iter().map(|p| p[1].abs()).sum();
+        let center = if total_int > 0.0 {
+            group.iter().map(|p| p[0] * p[1].abs()).sum::<f64>() / total_int
+        } else {
+            group.iter().map(|p| p[0]).sum::<f64>() / n as f64
+        };
+
+        // Average J: mean spacing between consecutive lines (in Hz)
+        let j_hz = if n >= 2 {
+            let mut spacings = Vec::new();
+            for i in 1..n {
+                spacings.push((group[i][0] - group[i - 1][0]).abs() * obs_mhz);
+            }
+            spacings.iter().sum::<f64>() / spacings.len() as f64
+        } else {
+            0.0
+        };
+
+        multiplets.push(Multiplet {
+            center_ppm: center,
+            j_hz,
+            num_lines: n,
+            label: multiplet_label(n).to_string(),
+            peaks: group.clone(),
+            couplings: Vec::new(),
+        });
+    }
+
+    // Sort by ppm descending (NMR convention)
+    multiplets.sort_by(|a, b| b.center_ppm.partial_cmp(&a.center_ppm).unwrap());
+    multiplets
+}
+
+/// Spacings within this many Hz of each other count as the same J
+pub const J_CONSISTENCY_HZ: f64 = 0.5;
+
+/// Coupling constants of a multiplet from all adjacent line spacings:
+/// spacings that agree within `tolerance_hz` are averaged into one J with
+/// its standard deviation.  Returned largest J first.
+pub fn multiplet_couplings(peaks: &[[f64; 2]], obs_mhz: f64, tolerance_hz: f64) -> Vec<Coupling> {
+    let mut lines: Vec<f64> = peaks.iter().map(|p| p[0]).collect();
+    lines.sort_by(f64::total_cmp);
+    let mut spacings: Vec<f64> = lines.windows(2).map(|w| (w[1] - w[0]) * obs_mhz).collect();
+    spacings.sort_by(f64::total_cmp);
+
+    // Greedy clusters over the sorted spacings, compared with the running mean
+    let mut clusters: Vec<Vec<f64>> = Vec::new();
+    for s in spacings {
+        match clusters.last_mut() {
+            Some(c) if s - c.iter().sum::<f64>() / c.len() as f64 <= tolerance_hz => c.push(s),
+            _ => clusters.push(vec![s]),
+        }
+    }
+
+    let mut couplings: Vec<Coupling> = clusters
+        .into_iter()
+        .map(|c| {
+            let n = c.len() as f64;
+            let mean = c.iter().sum::<f64>() / n;
+            let sd = if c.len() > 1 {
+                (c.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+            } else {
+                0.0
+            };
+            Coupling { j_hz: mean, sd_hz: sd, count: c.len() }
+        })
+        .collect();
+    couplings.sort_by(|a, b| b.j_hz.total_cmp(&a.j_hz));
+    couplings
+}
+
+// =========================================================================
+//  2D Cross Peaks & Assignment Transfer
+// =========================================================================
+
+/// A picked 2D cross peak; `label` is the assignment ("" if unassigned)
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CrossPeak {
+    pub f2_ppm: f64,
+    pub f1_ppm: f64,
+    pub intensity: f64,
+    #[serde(default)]
+    pub label: String,
+    /// Baseline-corrected volume from [`cross_peak_volume`], once integrated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f64>,
+}
+
+/// Most cross peaks kept by [`pick_cross_peaks`] (strongest first)
+const MAX_CROSS_PEAKS: usize = 500;
+
+/// Local extrema of |intensity| over their 3×3 neighbourhood above
+/// `threshold_fraction` of the largest |intensity| of a transformed 2D
+/// spectrum.  Negative (edited HSQC) peaks are kept with their sign.
+pub fn pick_cross_peaks(spectrum: &SpectrumData, threshold_fraction: f64) -> Vec<CrossPeak> {
+    let data = &spectrum.data_2d;
+    if !spectrum.is_frequency_domain || spectrum.axes.len() < 2 || data.len() < 3 {
+        return vec![];
+    }
+    let n_cols = data.iter().map(|r| r.len()).min().unwrap_or(0);
+    if n_cols < 3 {
+        return vec![];
+    }
+    let max_abs = data.iter().flatten().map(|v| v.abs()).fold(0.0f64, f64::max);
+    if max_abs <= 0.0 {
+        return vec![];
+    }
+    let threshold = max_abs * threshold_fraction;
+
+    let mut found: Vec<(usize, usize, f64)> = Vec::new();
+    for r in 1..data.len() - 1 {
+        for c in 1..n_cols - 1 {
+            let v = data[r][c];
+            if v.abs() <= threshold {
+                continue;
+            }
+            let is_max = (r - 1..=r + 1)
+                .all(|rr| (c - 1..=c + 1).all(|cc| data[rr][cc].abs() <= v.abs()));
+            if is_max {
+                found.push((r, c, v));
+            }
+        }
+    }
+    found.sort_by(|a, b| b.2.abs().partial_cmp(&a.2.abs()).unwrap());
+    found.truncate(MAX_CROSS_PEAKS);
+
+    let mut peaks: Vec<CrossPeak> = found
+        .into_iter()
+        .map(|(r, c, v)| CrossPeak {
+            f2_ppm: spectrum.axes[0].index_to_ppm(c),
+            f1_ppm: spectrum.axes[1].index_to_ppm(r),
+            intensity: v,
+            label: String::new(),
+            volume: None,
+        })
+        .collect();
+    peaks.sort_by(|a, b| b.f2_ppm.partial_cmp(&a.f2_ppm).unwrap());
+    peaks
+}
+
+/// Assignments of a 1D spectrum as `(ppm, label)`: one per multiplet
+/// ("7.26 d"), or one per picked peak if no multiplets were analysed.
+pub fn assignments_from_1d(peaks: &[[f64; 2]], multiplets: &[Multiplet]) -> Vec<(f64, String)> {
+    if multiplets.is_empty() {
+        peaks.iter().map(|p| (p[0], format!("{:.2}", p[0]))).collect()
+    } else {
+        multiplets
+            .iter()
+            .map(|m| (m.center_ppm, format!("{:.2} {}", m.center_ppm, m.label)))
+            .collect()
+    }
+}
+
+/// Label each unassigned cross peak with the nearest 1D assignment whose
+/// shift matches its F2 shift within `tolerance_ppm`.  Existing labels are
+/// kept.  Returns the number of cross peaks labelled.
+pub fn transfer_assignments(
+    cross_peaks: &mut [CrossPeak],
+    assignments: &[(f64, String)],
+    tolerance_ppm: f64,
+) -> usize {
+    let mut labelled = 0;
+    for peak in cross_peaks.iter_mut().filter(|p| p.label.is_empty()) {
+        let nearest = assignments
+            .iter()
+            .map(|(ppm, label)| ((ppm - peak.f2_ppm).abs(), label))
+            .filter(|(d, _)| *d <= tolerance_ppm)
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        if let Some((_, label)) = nearest {
+            peak.label = label.clone();
+            labelled += 1;
+        }
+    }
+    labelled
+}
+
+/// Sparky assignment of a cross peak: `?-?` when unassigned.  Labels come
+/// from the F2 (1D) assignments, so a label without a dash names the w2
+/// atom; whitespace would split the column and becomes `_`.
+fn sparky_assignment(label: &str) -> String {
+    let label: String = label.trim().chars().map(|c| if c.is_whitespace() { '_' } else { c }).collect();
+    if label.is_empty() {
+        "?-?".to_string()
+    } else if label.contains('-') {
+        label
+    } else {
+        format!("?-{}", label)
+    }
+}
+
+/// Cross peaks as a Sparky `.list` peak list (w1 = F1, w2 = F2, as Sparky
+/// numbers the direct dimension last), with a Volume column once the peaks
+/// are integrated
+pub fn sparky_peak_list(peaks: &[CrossPeak]) -> String {
+    let with_volume = peaks.iter().any(|p| p.volume.is_some());
+    let mut out = format!("{:>17}{:>11}{:>11}{:>15}", "Assignment", "w1", "w2", "Data Height");
+    if with_volume {
+        out.push_str(&format!("{:>15}", "Volume"));
+    }
+    out.push_str("\n\n");
+    for p in peaks {
+        out.push_str(&format!(
+            "{:>17}{:>11.3}{:>11.3}{:>15.4e}",
+            sparky_assignment(&p.label),
+            p.f1_ppm,
+            p.f2_ppm,
+            p.intensity
+        ));
+        if with_volume {
+            out.push_str(&format!("{:>15.4e}", p.volume.unwrap_or(0.0)));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Cross peaks as an XEASY/CYANA peak list, dimension 1 = F2.  The volume
+/// column holds the integrated volume, or the height of peaks not
+/// integrated.  XEASY assignments are atom numbers of a `.prot` file,
+/// which a 1D label does not give, so they are written as 0 and the label
+/// follows as a comment.
+pub fn xeasy_peak_list(peaks: &[CrossPeak], f2: &Nucleus, f1: &Nucleus) -> String {
+    // XEASY names dimensions by element: 1H → H, 13C → C
+    let element = |n: &Nucleus| n.to_string().trim_start_matches(|c: char| c.is_ascii_digit()).to_string();
+    let mut out = format!(
+        "# Number of dimensions 2\n# INAME 1 {}\n# INAME 2 {}\n",
+        element(f2),
+        element(f1)
+    );
+    for (i, p) in peaks.iter().enumerate() {
+        out.push_str(&format!(
+            "{:>5} {:>8.3} {:>8.3} 1 U {:>12.4e} {:>10.2e} e 0 {:>5} {:>5}",
+            i + 1,
+            p.f2_ppm,
+            p.f1_ppm,
+            p.volume.unwrap_or(p.intensity),
+            0.0,
+            0,
+            0
+        ));
+        if !p.label.trim().is_empty() {
+            out.push_str(&format!(" # {}", p.label.trim()));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Shape of the region summed around each cross peak
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum VolumeShape {
+    #[default]
+    Ellipse,
+    Box,
+}
+
+impl std::fmt::Display for VolumeShape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VolumeShape::Ellipse => write!(f, "ellipse"),
+            VolumeShape::Box => write!(f, "box"),
+        }
+    }
+}
+
+/// Region integrated around each cross peak, centred on the peak, with
+/// half-widths in ppm along F2 and F1
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VolumeRegion {
+    pub shape: VolumeShape,
+    pub f2_half_width: f64,
+    pub f1_half_width: f64,
+}
+
+impl Default for VolumeRegion {
+    /// Suits an HSQC: about a multiplet wide in 1H, a line wide in 13C
+    fn default() -> Self {
+        Self { shape: VolumeShape::Ellipse, f2_half_width: 0.03, f1_half_width: 0.3 }
+    }
+}
+
+impl VolumeRegion {
+    /// Whether a point `d_f2`, `d_f1` ppm away from the peak lies inside
+    pub fn contains(&self, d_f2: f64, d_f1: f64) -> bool {
+        let (u, v) = (d_f2 / self.f2_half_width, d_f1 / self.f1_half_width);
+        match self.shape {
+            VolumeShape::Ellipse => u * u + v * v <= 1.0,
+            VolumeShape::Box => u.abs() <= 1.0 && v.abs() <= 1.0,
+        }
+    }
+
+    /// Closed outline around the peak at (`f2_ppm`, `f1_ppm`) as
+    /// [f2, f1] ppm points
+    pub fn outline(&self, f2_ppm: f64, f1_ppm: f64) -> Vec<[f64; 2]> {
+        let (a, b) = (self.f2_half_width, self.f1_half_width);
+        match self.shape {
+            VolumeShape::Ellipse => (0..=48)
+                .map(|k| {
+                    let t = k as f64 / 48.0 * 2.0 * PI;
+                    [f2_ppm + a * t.cos(), f1_ppm + b * t.sin()]
+                })
+                .collect(),
+            VolumeShape::Box => vec![
+                [f2_ppm - a, f1_ppm - b],
+                [f2_ppm + a, f1_ppm - b],
+                [f2_ppm + a, f1_ppm + b],
+                [f2_ppm - a, f1_ppm + b],
+                [f2_ppm - a, f1_ppm - b],
+            ],
+        }
+    }
+}
+
+/// Volume of the cross peak at (`f2_ppm`, `f1_ppm`): the sum of the points
+/// inside `region`, less a plane fitted by least squares to the ring of
+/// points just outside the region's bounding box, so a local offset or
+/// tilt (a t1 ridge, a broad background) does not count.  `None` if the
+/// region holds no points.
+pub fn cross_peak_volume(spectrum: &SpectrumData, f2_ppm: f64, f1_ppm: f64, region: &VolumeRegion) -> Option<f64> {
+    let data = &spectrum.data_2d;
+    let (f2_axis, f1_axis) = match &spectrum.axes[..] {
+        [f2, f1, ..] if spectrum.is_frequency_domain => (f2, f1),
+        _ => return None,
+    };
+    let n_rows = data.len() as isize;
+    let n_cols = data.iter().map(|r| r.len()).min().unwrap_or(0) as isize;
+    // Index bounds of the region, one point wider on each side for the ring
+    let bounds = |axis: &AxisParams, ppm: f64, half: f64, n: isize| -> Option<(isize, isize)> {
+        let a = axis.ppm_to_position(ppm - half)?;
+        let b = axis.ppm_to_position(ppm + half)?;
+        let lo = (a.min(b).ceil() as isize - 1).max(0);
+        let hi = (a.max(b).floor() as isize + 1).min(n - 1);
+        (lo <= hi).then_some((lo, hi))
+    };
+    let (c0, c1) = bounds(f2_axis, f2_ppm, region.f2_half_width, n_cols)?;
+    let (r0, r1) = bounds(f1_axis, f1_ppm, region.f1_half_width, n_rows)?;
+
+    let mut inside = Vec::new();
+    let mut ring = Vec::new();
+    for r in r0..=r1 {
+        for c in c0..=c1 {
+            let (x, y, z) = (c as f64, r as f64, data[r as usize][c as usize]);
+            let d_f2 = f2_axis.index_to_ppm(c as usize) - f2_ppm;
+            let d_f1 = f1_axis.index_to_ppm(r as usize) - f1_ppm;
+            if region.contains(d_f2, d_f1) {
+                inside.push((x, y, z));
+            } else if r == r0 || r == r1 || c == c0 || c == c1 {
+                ring.push((x, y, z));
+            }
+        }
+    }
+    if inside.is_empty() {
+        return None;
+    }
+    let plane = fit_plane(&ring);
+    Some(inside.iter().map(|&(x, y, z)| z - plane(x, y)).sum())
+}
+
+/// Least-squares plane z = a + b·x + c·y through `points`; their mean (or
+/// zero) when there are too few or they are collinear
+fn fit_plane(points: &[(f64, f64, f64)]) -> impl Fn(f64, f64) -> f64 {
+    let n = points.len() as f64;
+    let mean = |f: fn(&(f64, f64, f64)) -> f64| if n > 0.0 { points.iter().map(f).sum::<f64>() / n } else { 0.0 };
+    let (mx, my, mz) = (mean(|p| p.0), mean(|p| p.1), mean(|p| p.2));
+    // Centred normal equations for the slopes
+    let (mut sxx, mut sxy, mut syy, mut sxz, mut syz) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for &(x, y, z) in points {
+        let (x, y, z) = (x - mx, y - my, z - mz);
+        sxx += x * x;
+        sxy += x * y;
+        syy += y * y;
+        sxz += x * z;
+        syz += y * z;
+    }
+    let det = sxx * syy - sxy * sxy;
+    let (b, c) = if points.len() >= 3 && det.abs() > 1e-12 {
+        ((sxz * syy - syz * sxy) / det, (syz * sxx - sxz * sxy) / det)
+    } else {
+        (0.0, 0.0)
+    };
+    move |x, y| mz + b * (x - mx) + c * (y - my)
+}
+
+// =========================================================================
+//  Integration
+// =========================================================================
+
+/// Integrate the spectrum between two ppm values (trapezoidal sum).
+/// Returns the raw integral value — ratios between regions are what matter.
+pub fn integrate_region(spectrum: &SpectrumData, start_ppm: f64, end_ppm: f64) -> f64 {
+    if spectrum.axes.is_empty() || spectrum.real.is_empty() {
+        return 0.0;
+    }
+
+    let ppm_scale = spectrum.x_scale();
+
+    let lo = start_ppm.min(end_ppm);
+    let hi = start_ppm.max(end_ppm);
+
+    let mut integral = 0.0;
+    for i in 0..spectrum.real.len().min(ppm_scale.len()) {
+        if ppm_scale[i] >= lo && ppm_scale[i] <= hi {
+            integral += spectrum.real[i];
+        }
+    }
+
+    integral
+}
+
+/// Points averaged at each end of a region for its two-point baseline
+const INTEGRAL_EDGE_POINTS: usize = 3;
+
+/// Baseline subtracted from each integration region
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum IntegralBaseline {
+    /// Raw sum of the data, as in [`integrate_region`]
+    None,
+    /// Straight line through the region endpoints, so a baseline offset or
+    /// tilt under the region does not bias the integral
+    #[default]
+    TwoPoint,
+}
+
+impl std::fmt::Display for IntegralBaseline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntegralBaseline::None => write!(f, "none (raw sum)"),
+            IntegralBaseline::TwoPoint => write!(
+                f,
+                "two-point (straight line through the region endpoints, {}-point averages)",
+                INTEGRAL_EDGE_POINTS
+            ),
+        }
+    }
+}
+
+/// Integrate a region like [`integrate_region`], after subtracting the
+/// chosen local baseline.  The two-point line runs between the mean of the
+/// first and of the last few points inside the region.
+pub fn integrate_region_corrected(
+    spectrum: &SpectrumData,
+    start_ppm: f64,
+    end_ppm: f64,
+    baseline: IntegralBaseline,
+) -> f64 {
+    let raw = integrate_region(spectrum, start_ppm, end_ppm);
+    if baseline == IntegralBaseline::None || !spectrum.is_frequency_domain || spectrum.axes.is_empty() {
+        return raw;
+    }
+
+    let axis = &spectrum.axes[0];
+    let n = spectrum.real.len().min(axis.num_points);
+    let lo = start_ppm.min(end_ppm);
+    let hi = start_ppm.max(end_ppm);
+    // ppm falls with index, so the region's points are contiguous
+    let region: Vec<f64> = (0..n)
+        .filter(|&i| (lo..=hi).contains(&axis.index_to_ppm(i)))
+        .map(|i| spectrum.real[i])
+        .collect();
+    if region.len() < 2 {
+        return raw;
+    }
+    let k = (region.len() / 2).clamp(1, INTEGRAL_EDGE_POINTS);
+    let first = region[..k].iter().sum::<f64>() / k as f64;
+    let last = region[region.len() - k..].iter().sum::<f64>() / k as f64;
+    // Sum of a straight line over evenly spaced points: count × mean of the ends
+    raw - region.len() as f64 * (first + last) / 2.0
+}
+
+/// Running integral across a region, from its high-ppm edge, as (ppm,
+/// integral so far) per point; the last value is the region's
+/// [`integrate_region_corrected`].  The two-point baseline is taken off
+/// point by point, so the trace levels out between signals.
+pub fn integral_trace(
+    spectrum: &SpectrumData,
+    start_ppm: f64,
+    end_ppm: f64,
+    baseline: IntegralBaseline,
+) -> Vec<[f64; 2]> {
+    let ppm_scale = spectrum.x_scale();
+    let lo = start_ppm.min(end_ppm);
+    let hi = start_ppm.max(end_ppm);
+    let mut region: Vec<(f64, f64)> = ppm_scale
+        .iter()
+        .zip(&spectrum.real)
+        .filter(|(&ppm, _)| ppm >= lo && ppm <= hi)
+        .map(|(&ppm, &v)| (ppm, v))
+        .collect();
+    region.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let n = region.len();
+    let (first, last) = if baseline == IntegralBaseline::TwoPoint && spectrum.is_frequency_domain && n >= 2 {
+        let k = (n / 2).clamp(1, INTEGRAL_EDGE_POINTS);
+        let mean = |points: &[(f64, f64)]| points.iter().map(|p| p.1).sum::<f64>() / k as f64;
+        (mean(&region[..k]), mean(&region[n - k..]))
+    } else {
+        (0.0, 0.0)
+    };
+    let mut sum = 0.0;
+    region
+        .iter()
+        .enumerate()
+        .map(|(i, &(ppm, v))| {
+            sum += v - (first + (last - first) * i as f64 / (n - 1).max(1) as f64);
+            [ppm, sum]
+        })
+        .collect()
+}
+
+/// Noise RMS of a spectrum, estimated from the median absolute difference
+/// of neighbouring points.  Differencing removes the baseline and the
+/// median ignores the few points on peak flanks, so the whole spectrum can
+/// be used without picking an empty region.
+pub fn noise_rms(values: &[f64]) -> f64 {
+    let mut diffs: Vec<f64> = values.windows(2).map(|w| (w[1] - w[0]).abs()).collect();
+    if diffs.is_empty() {
+        return 0.0;
+    }
+    let mid = diffs.len() / 2;
+    let (_, median, _) = diffs.select_nth_unstable_by(mid, |a, b| a.total_cmp(b));
+    // MAD → σ for a normal distribution, and the difference of two
+    // independent points has √2 times the noise of one
+    1.4826 * *median / std::f64::consts::SQRT_2
+}
+
+/// Standard deviation of an integral over `n_points` points with white
+/// noise `noise`.  With the two-point baseline, the edge points also set
+/// the subtracted line, which weights them by 1 − n/2k.
+pub fn integral_sigma(noise: f64, n_points: usize, baseline: IntegralBaseline) -> f64 {
+    let n = n_points as f64;
+    let k = (n_points / 2).clamp(1, INTEGRAL_EDGE_POINTS);
+    let variance = match baseline {
+        IntegralBaseline::TwoPoint if n_points >= 2 => {
+            let edge = 1.0 - n / (2.0 * k as f64);
+            (n - 2.0 * k as f64) + 2.0 * k as f64 * edge * edge
+        }
+        _ => n,
+    };
+    noise * variance.sqrt()
+}
+
+/// Noise-propagated uncertainty of one integration region
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntegralUncertainty {
+    /// σ of the integral, in the units of [`integrate_region`]
+    pub sigma: f64,
+    /// σ of the derived H-count.  Zero for the first region, whose count
+    /// is fixed by the reference; its σ enters all the others.
+    pub h_sigma: f64,
+}
+
+/// Uncertainties of `integrations` (lo, hi, raw integral) from the noise
+/// RMS of the spectrum and the number of points in each region, with the
+/// H-counts relative to the first region set to `reference_h`
+pub fn integral_uncertainties(
+    spectrum: &SpectrumData,
+    integrations: &[(f64, f64, f64)],
+    baseline: IntegralBaseline,
+    reference_h: f64,
+) -> Vec<IntegralUncertainty> {
+    let zero = IntegralUncertainty { sigma: 0.0, h_sigma: 0.0 };
+    let Some(axis) = spectrum.axes.first().filter(|_| spectrum.is_frequency_domain) else {
+        return vec![zero; integrations.len()];
+    };
+    let noise = noise_rms(&spectrum.real);
+    let n = spectrum.real.len().min(axis.num_points);
+    let sigmas: Vec<f64> = integrations
+        .iter()
+        .map(|&(start, end, _)| {
+            let (lo, hi) = (start.min(end), start.max(end));
+            let count = (0..n).filter(|&i| (lo..=hi).contains(&axis.index_to_ppm(i))).count();
+            integral_sigma(noise, count, baseline)
+        })
+        .collect();
+    let Some(&(_, _, ref_raw)) = integrations.first() else {
+        return Vec::new();
+    };
+    let ref_rel = sigmas[0] / ref_raw.abs().max(1e-20);
+    integrations
+        .iter()
+        .zip(&sigmas)
+        .enumerate()
+        .map(|(i, (&(_, _, raw), &sigma))| {
+            let h = raw / ref_raw.abs().max(1e-20) * reference_h;
+            let h_sigma = if i == 0 {
+                0.0
+            } else {
+                h.abs() * ((sigma / raw.abs().max(1e-20)).powi(2) + ref_rel.powi(2)).sqrt()
+            };
+            IntegralUncertainty { sigma, h_sigma }
+        })
+        .collect()
+}
+
+/// H-count label, with its uncertainty when there is one: `2.03 ± 0.04H`
+pub fn format_h_count(h: f64, h_sigma: f64) -> String {
+    if h_sigma > 0.0 {
+        format!("{:.2} ± {:.2}H", h, h_sigma)
+    } else {
+        format!("{:.2}H", h)
+    }
+}
+
+// =========================================================================
+//  Impurity Quantification
+// =========================================================================
+
+/// A library reference fitted to an impurity signal of the sample
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImpurityFit {
+    /// Reference trace multiplier and constant baseline offset
+    pub scale: f64,
+    pub offset: f64,
+    /// Integral of the scaled reference over the region (same units as
+    /// [`integrate_region`]), i.e. the impurity's share of the signal
+    pub impurity_integral: f64,
+    /// Impurity / (impurity + main compound), per proton, in percent
+    pub mol_percent: f64,
+    /// RMS of the fit residual relative to the largest sample point in
+    /// the region; large values mean the reference does not match
+    pub relative_residual: f64,
+    /// Fitted trace over the region: [ppm, intensity]
+    pub trace: Vec<[f64; 2]>,
+}
+
+/// Linear interpolation of a [ppm, intensity] trace (either ppm order);
+/// `None` outside it
+fn interpolate_trace(points: &[[f64; 2]], ppm: f64) -> Option<f64> {
+    if points.len() < 2 {
+        return None;
+    }
+    let descending = points[0][0] > points[points.len() - 1][0];
+    let idx = points.partition_point(|p| if descending { p[0] > ppm } else { p[0] < ppm });
+    if idx == points.len() {
+        return None;
+    }
+    if idx == 0 {
+        return (points[0][0] == ppm).then_some(points[0][1]);
+    }
+    let (a, b) = (points[idx - 1], points[idx]);
+    let t = (ppm - a[0]) / (b[0] - a[0]);
+    Some(a[1] + t * (b[1] - a[1]))
+}
+
+/// Least-squares fit of `sample ≈ scale · reference + offset` over
+/// `lo_ppm`–`hi_ppm`, then the impurity mol% from the fitted integral:
+/// (I_imp / H_imp) / (I_imp / H_imp + I_main / H_main), with `main_integral`
+/// the raw integral of the main compound's reference region.
+pub fn quantify_impurity(
+    spectrum: &SpectrumData,
+    reference: &[[f64; 2]],
+    lo_ppm: f64,
+    hi_ppm: f64,
+    impurity_h: f64,
+    main_integral: f64,
+    main_h: f64,
+) -> Option<ImpurityFit> {
+    if !spectrum.is_frequency_domain || spectrum.is_2d() || impurity_h <= 0.0 || main_h <= 0.0 {
+        return None;
+    }
+    let axis = spectrum.axes.first()?;
+    let (lo, hi) = (lo_ppm.min(hi_ppm), lo_ppm.max(hi_ppm));
+    let pairs: Vec<(f64, f64, f64)> = spectrum
+        .real
+        .iter()
+        .enumerate()
+        .map(|(i, &s)| (axis.index_to_ppm(i), s))
+        .filter(|&(ppm, _)| ppm >= lo && ppm <= hi)
+        .filter_map(|(ppm, s)| Some((ppm, s, interpolate_trace(reference, ppm)?)))
+        .collect();
+    let n = pairs.len() as f64;
+    if pairs.len() < 3 {
+        return None;
+    }
+
+    // Normal equations for [scale, offset]
+    let (sr, ss, srr, ssr) = pairs.iter().fold((0.0, 0.0, 0.0, 0.0), |(sr, ss, srr, ssr), &(_, s, r)| {
+        (sr + r, ss + s, srr + r * r, ssr + s * r)
+    });
+    let det = n * srr - sr * sr;
+    if det.abs() < 1e-30 {
+        return None;
+    }
+    let scale = (n * ssr - sr * ss) / det;
+    let offset = (ss - scale * sr) / n;
+
+    let impurity_integral: f64 = pairs.iter().map(|&(_, _, r)| scale * r).sum();
+    let peak = pairs.iter().fold(0.0f64, |m, &(_, s, _)| m.max(s.abs())).max(1e-30);
+    let rms = (pairs.iter().map(|&(_, s, r)| (s - scale * r - offset).powi(2)).sum::<f64>() / n).sqrt();
+    let per_h_imp = impurity_integral / impurity_h;
+    let per_h_main = main_integral / main_h;
+    let total = per_h_imp + per_h_main;
+    if total.abs() < 1e-30 {
+        return None;
+    }
+    Some(ImpurityFit {
+        scale,
+        offset,
+        impurity_integral,
+        mol_percent: per_h_imp / total * 100.0,
+        relative_residual: rms / peak,
+        trace: pairs.iter().map(|&(ppm, _, r)| [ppm, scale * r + offset]).collect(),
+    })
+}
+
+// =========================================================================
+//  Comparison
+// =========================================================================
+
+/// Point-by-point difference `after − before`, aligned to `after`'s points.
+///
+/// Frequency-domain spectra are compared by ppm (the before spectrum is
+/// linearly interpolated, so zero-filling in between is fine); points
+/// outside the before spectrum's range are NaN.  Time-domain data must have
+/// the same length.  Returns `None` if the two are not comparable.
+pub fn difference_spectrum(after: &SpectrumData, before: &SpectrumData) -> Option<Vec<f64>> {
+    if after.is_2d() || before.is_2d() || after.is_frequency_domain != before.is_frequency_domain {
+        return None;
+    }
+
+    if !after.is_frequency_domain {
+        if after.real.len() != before.real.len() {
+            return None;
+        }
+        return Some(after.real.iter().zip(&before.real).map(|(a, b)| a - b).collect());
+    }
+
+    let (a_ax, b_ax) = (after.axes.first()?, before.axes.first()?);
+    if b_ax.num_points < 2 || b_ax.spectral_width_hz <= 0.0 || b_ax.observe_freq_mhz <= 0.0 {
+        return None;
+    }
+    let b_len = before.real.len().min(b_ax.num_points);
+    if b_len < 2 {
+        return None;
+    }
+
+    let diff = after
+        .real
+        .iter()
+        .enumerate()
+        .map(|(i, &a)| {
+            let pos = match b_ax.ppm_to_position(a_ax.index_to_ppm(i)) {
+                Some(pos) if pos >= 0.0 && pos <= (b_len - 1) as f64 => pos,
+                _ => return f64::NAN,
+            };
+            let lo = pos.floor() as usize;
+            let hi = (lo + 1).min(b_len - 1);
+            let t = pos - lo as f64;
+            a - (before.real[lo] * (1.0 - t) + before.real[hi] * t)
+        })
+        .collect();
+    Some(diff)
+}
+
+// =========================================================================
+//  Decimated Web Export
+// =========================================================================
+
+/// Largest-triangle-three-buckets downsampling of `[x, y]` points to at
+/// most `budget` points.
+///
+/// Keeps the first and last point and, from each bucket in between, the
+/// point spanning the largest triangle with its neighbours, so peaks and
+/// the overall line shape survive far better than with plain striding.
+pub fn lttb(points: &[[f64; 2]], budget: usize) -> Vec<[f64; 2]> {
+    let budget = budget.max(3);
+    if points.len() <= budget {
+        return points.to_vec();
+    }
+    let every = (points.len() - 2) as f64 / (budget - 2) as f64;
+    let mut out = Vec::with_capacity(budget);
+    let mut a = 0;
+    out.push(points[0]);
+    for bucket in 0..budget - 2 {
+        let start = (bucket as f64 * every) as usize + 1;
+        let end = ((bucket + 1) as f64 * every) as usize + 1;
+        // Average of the next bucket (the last point for the final bucket)
+        let next_end = (((bucket + 2) as f64 * every) as usize + 1).min(points.len());
+        let next = &points[end..next_end.max(end + 1).min(points.len())];
+        let avg_x = next.iter().map(|p| p[0]).sum::<f64>() / next.len() as f64;
+        let avg_y = next.iter().map(|p| p[1]).sum::<f64>() / next.len() as f64;
+
+        let [ax, ay] = points[a];
+        let mut best = start;
+        let mut best_area = -1.0;
+        for (i, p) in points.iter().enumerate().take(end).skip(start) {
+            let area = ((ax - avg_x) * (p[1] - ay) - (ax - p[0]) * (avg_y - ay)).abs();
+            if area > best_area {
+                best_area = area;
+                best = i;
+            }
+        }
+        out.push(points[best]);
+        a = best;
+    }
+    out.push(points[points.len() - 1]);
+    out
+}
+
+/// Decimated 1D spectrum for embedding interactive plots in web pages /
+/// electronic lab notebooks
+#[derive(Debug, Clone, Serialize)]
+pub struct WebSpectrumExport {
+    pub sample: String,
+    pub experiment: String,
+    pub nucleus: String,
+    pub observe_freq_mhz: f64,
+    /// "ppm" for spectra, "s" for FIDs ("points" without a spectral width)
+    pub x_unit: String,
+    pub source_points: usize,
+    pub decimation: String,
+    /// `[x, intensity]`, in acquisition order (high ppm first)
+    pub points: Vec<[f64; 2]>,
+    /// Picked peaks as `[ppm, intensity]`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub peaks: Vec<[f64; 2]>,
+}
+
+/// Build the decimated web export of a 1D spectrum: NaN samples are
+/// dropped, the trace is reduced to `budget` points with LTTB, and x values
+/// are rounded to `x_decimals`.
+pub fn web_export(
+    spectrum: &SpectrumData,
+    peaks: &[[f64; 2]],
+    budget: usize,
+    x_decimals: usize,
+) -> NmrResult<WebSpectrumExport> {
+    if spectrum.is_2d() {
+        return Err(NmrError::NotApplicable("Web export supports 1D spectra only".to_string()));
+    }
+    let axis = spectrum.axes.first();
+    let x_unit = if spectrum.is_frequency_domain && axis.is_some() {
+        "ppm"
+    } else if spectrum.has_time_axis() {
+        "s"
+    } else {
+        "points"
+    };
+    let points: Vec<[f64; 2]> = spectrum
+        .x_scale()
+        .into_iter()
+        .zip(spectrum.real.iter())
+        .map(|(x, &y)| [x, y])
+        .filter(|p| p[0].is_finite() && p[1].is_finite())
+        .collect();
+    if points.is_empty() {
+        return Err(NmrError::InvalidInput("Spectrum has no finite data points".to_string()));
+    }
+
+    // Dwell times are microseconds: keep 0.1 µs whatever the ppm precision
+    let x_decimals = if x_unit == "s" { x_decimals.max(7) } else { x_decimals };
+    let scale = 10f64.powi(x_decimals as i32);
+    let decimated = lttb(&points, budget)
+        .into_iter()
+        .map(|[x, y]| [(x * scale).round() / scale, y])
+        .collect();
+    Ok(WebSpectrumExport {
+        sample: spectrum.sample_name.clone(),
+        experiment: spectrum.experiment_type.to_string(),
+        nucleus: axis.map(|a| a.nucleus.to_string()).unwrap_or_default(),
+        observe_freq_mhz: axis.map(|a| a.observe_freq_mhz).unwrap_or(0.0),
+        x_unit: x_unit.to_string(),
+        source_points: spectrum.real.len(),
+        decimation: format!("LTTB ({} points)", budget.max(3)),
+        points: decimated,
+        peaks: peaks.to_vec(),
+    })
+}
+
+// =========================================================================
+//  1H / 2H Isotope Shifts
+// =========================================================================
+
+/// A 1H peak and its 2H counterpart (if any) in an exchange study
+#[derive(Debug, Clone, PartialEq)]
+pub struct IsotopeShiftPair {
+    pub h_ppm: f64,
+    /// Matched 2H peak; `None` when the site shows no deuterium
+    pub d_ppm: Option<f64>,
+    /// δ(2H) − δ(1H) in ppb
+    pub shift_ppb: Option<f64>,
+    /// 2H intensity relative to the strongest matched 2H peak
+    pub d_relative: Option<f64>,
+}
+
+/// Pair 1H peaks with 2H peaks (`[ppm, intensity]`) of the same sample.
+///
+/// 2H and 1H shifts agree to within the isotope effect, so each 2H peak is
+/// matched to the nearest free 1H peak within `tolerance_ppm`, closest
+/// pairs first.  Every 1H peak is returned, highest ppm first; unmatched
+/// 2H peaks are ignored.
+pub fn pair_isotope_shifts(
+    h_peaks: &[[f64; 2]],
+    d_peaks: &[[f64; 2]],
+    tolerance_ppm: f64,
+) -> Vec<IsotopeShiftPair> {
+    let mut candidates: Vec<(f64, usize, usize)> = Vec::new();
+    for (hi, h) in h_peaks.iter().enumerate() {
+        for (di, d) in d_peaks.iter().enumerate() {
+            let dist = (d[0] - h[0]).abs();
+            if dist <= tolerance_ppm {
+                candidates.push((dist, hi, di));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut match_of: Vec<Option<usize>> = vec![None; h_peaks.len()];
+    let mut d_used = vec![false; d_peaks.len()];
+    for (_, hi, di) in candidates {
+        if match_of[hi].is_none() && !d_used[di] {
+            match_of[hi] = Some(di);
+            d_used[di] = true;
+        }
+    }
+
+    let d_max = match_of
+        .iter()
+        .flatten()
+        .map(|&di| d_peaks[di][1].abs())
+        .fold(0.0, f64::max);
+    let mut pairs: Vec<IsotopeShiftPair> = h_peaks
+        .iter()
+        .zip(&match_of)
+        .map(|(h, m)| {
+            let d = m.map(|di| d_peaks[di]);
+            IsotopeShiftPair {
+                h_ppm: h[0],
+                d_ppm: d.map(|d| d[0]),
+                shift_ppb: d.map(|d| (d[0] - h[0]) * 1000.0),
+                d_relative: d.filter(|_| d_max > 0.0).map(|d| d[1].abs() / d_max),
+            }
+        })
+        .collect();
+    pairs.sort_by(|a, b| b.h_ppm.total_cmp(&a.h_ppm));
+    pairs
+}
+
+// =========================================================================
+//  Peak List Comparison
+// =========================================================================
+
+/// How a peak changed between a reference spectrum and a compared one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeakChange {
+    /// In both spectra
+    Common,
+    /// Only in the compared spectrum
+    New,
+    /// Only in the reference spectrum
+    Missing,
+}
+
+impl std::fmt::Display for PeakChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PeakChange::Common => write!(f, "common"),
+            PeakChange::New => write!(f, "new"),
+            PeakChange::Missing => write!(f, "missing"),
+        }
+    }
+}
+
+/// One row of a peak list comparison
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeakDiff {
+    pub change: PeakChange,
+    /// Shift on the reference scale (compared peaks after alignment)
+    pub ppm: f64,
+    pub reference: Option<[f64; 2]>,
+    /// Peak of the compared spectrum at its own (unaligned) shift
+    pub compared: Option<[f64; 2]>,
+    /// Compared / reference intensity of a common peak, each normalised to
+    /// the summed intensity of the common peaks of its spectrum
+    pub intensity_ratio: Option<f64>,
+}
+
+/// Greedy one-to-one matching of `a` against `b` shifted by `offset_ppm`,
+/// closest pairs first: the index into `b` for each peak of `a`
+fn match_peaks(a: &[[f64; 2]], b: &[[f64; 2]], offset_ppm: f64, tolerance_ppm: f64) -> Vec<Option<usize>> {
+    let mut candidates: Vec<(f64, usize, usize)> = Vec::new();
+    for (ai, p) in a.iter().enumerate() {
+        for (bi, q) in b.iter().enumerate() {
+            let dist = (q[0] + offset_ppm - p[0]).abs();
+            if dist <= tolerance_ppm {
+                candidates.push((dist, ai, bi));
+            }
+        }
+    }
+    candidates.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+    let mut match_of = vec![None; a.len()];
+    let mut b_used = vec![false; b.len()];
+    for (_, ai, bi) in candidates {
+        if match_of[ai].is_none() && !b_used[bi] {
+            match_of[ai] = Some(bi);
+            b_used[bi] = true;
+        }
+    }
+    match_of
+}
+
+/// Offset (ppm) to add to the `compared` shifts to line them up with the
+/// `reference` ones, e.g. for a referencing difference between two runs.
+///
+/// Every peak-to-peak distance up to `max_offset_ppm` is tried as an
+/// offset; the one matching most peaks within `tolerance_ppm` wins (the
+/// smaller shift on a tie) and is refined to the median distance of its
+/// matched pairs.  0 when nothing matches.
+pub fn align_peak_lists(
+    reference: &[[f64; 2]],
+    compared: &[[f64; 2]],
+    tolerance_ppm: f64,
+    max_offset_ppm: f64,
+) -> f64 {
+    let mut offsets = vec![0.0];
+    for r in reference {
+        for c in compared {
+            let d = r[0] - c[0];
+            if d.abs() <= max_offset_ppm {
+                offsets.push(d);
+            }
+        }
+    }
+    let matched = |offset: f64| match_peaks(reference, compared, offset, tolerance_ppm).iter().flatten().count();
+    let mut best = (0, 0.0f64);
+    for offset in offsets {
+        let count = matched(offset);
+        if count > best.0 || (count == best.0 && offset.abs() < best.1.abs()) {
+            best = (count, offset);
+        }
+    }
+    if best.0 == 0 {
+        return 0.0;
+    }
+
+    let mut diffs: Vec<f64> = match_peaks(reference, compared, best.1, tolerance_ppm)
+        .iter()
+        .enumerate()
+        .filter_map(|(ri, m)| m.map(|ci| reference[ri][0] - compared[ci][0]))
+        .collect();
+    diffs.sort_by(|a, b| a.total_cmp(b));
+    let mid = diffs.len() / 2;
+    if diffs.len() % 2 == 1 {
+        diffs[mid]
+    } else {
+        (diffs[mid - 1] + diffs[mid]) / 2.0
+    }
+}
+
+/// Classify the peaks (`[ppm, intensity]`) of two spectra as common, new
+/// or missing.
+///
+/// `offset_ppm` (see [`align_peak_lists`]) is added to the compared shifts
+/// before matching within `tolerance_ppm`.  Intensity ratios use each
+/// spectrum's common peaks as the internal standard, so concentration and
+/// receiver gain differences cancel; without common peaks there is no
+/// ratio.  Rows are sorted by shift, highest first.
+pub fn compare_peak_lists(
+    reference: &[[f64; 2]],
+    compared: &[[f64; 2]],
+    offset_ppm: f64,
+    tolerance_ppm: f64,
+) -> Vec<PeakDiff> {
+    let match_of = match_peaks(reference, compared, offset_ppm, tolerance_ppm);
+    let (mut ref_sum, mut cmp_sum) = (0.0, 0.0);
+    for (ri, m) in match_of.iter().enumerate() {
+        if let Some(ci) = *m {
+            ref_sum += reference[ri][1].abs();
+            cmp_sum += compared[ci][1].abs();
+        }
+    }
+    let normalise = ref_sum > 0.0 && cmp_sum > 0.0;
+
+    let mut rows: Vec<PeakDiff> = reference
+        .iter()
+        .zip(&match_of)
+        .map(|(r, m)| match *m {
+            Some(ci) => {
+                let c = compared[ci];
+                PeakDiff {
+                    change: PeakChange::Common,
+                    ppm: r[0],
+                    reference: Some(*r),
+                    compared: Some(c),
+                    intensity_ratio: (normalise && r[1] != 0.0)
+                        .then(|| (c[1].abs() / cmp_sum) / (r[1].abs() / ref_sum)),
+                }
+            }
+            None => PeakDiff {
+                change: PeakChange::Missing,
+                ppm: r[0],
+                reference: Some(*r),
+                compared: None,
+                intensity_ratio: None,
+            },
+        })
+        .collect();
+    let used: Vec<usize> = match_of.iter().flatten().copied().collect();
+    rows.extend(compared.iter().enumerate().filter(|(ci, _)| !used.contains(ci)).map(|(_, c)| PeakDiff {
+        change: PeakChange::New,
+        ppm: c[0] + offset_ppm,
+        reference: None,
+        compared: Some(*c),
+        intensity_ratio: None,
+    }));
+    rows.sort_by(|a, b| b.ppm.total_cmp(&a.ppm));
+    rows
+}
+
+/// One peak followed through a series of spectra, e.g. a ligand titration
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeakTrack {
+    /// Shift of the peak in each spectrum of the series, `None` where it
+    /// was not found
+    pub shifts: Vec<Option<f64>>,
+}
+
+impl PeakTrack {
+    /// Shift the peak was first seen at
+    pub fn start_ppm(&self) -> Option<f64> {
+        self.shifts.iter().flatten().next().copied()
+    }
+
+    /// Chemical-shift perturbation Δδ at each point of the series, relative
+    /// to the first spectrum the peak was seen in
+    pub fn perturbations(&self) -> Vec<Option<f64>> {
+        let start = self.start_ppm();
+        self.shifts.iter().map(|s| Some(s.as_ref()? - start?)).collect()
+    }
+
+    /// Largest |Δδ| over the series
+    pub fn max_perturbation(&self) -> f64 {
+        self.perturbations().into_iter().flatten().fold(0.0, |m, d| m.max(d.abs()))
+    }
+}
+
+/// Follow the peaks (`[ppm, intensity]`) of a series of spectra from one
+/// spectrum to the next.
+///
+/// Each track is matched one-to-one against the peaks of the next spectrum
+/// from its last known shift, closest pairs first, within `tolerance_ppm`;
+/// a track keeps going through a spectrum where its peak was not found.
+/// Peaks left over start new tracks.  Tracks are sorted by the shift they
+/// start at, highest first.
+pub fn track_peaks(series: &[Vec<[f64; 2]>], tolerance_ppm: f64) -> Vec<PeakTrack> {
+    let mut tracks: Vec<PeakTrack> = Vec::new();
+    for (k, peaks) in series.iter().enumerate() {
+        let last: Vec<[f64; 2]> = tracks
+            .iter()
+            .map(|t| [t.shifts.iter().rev().flatten().next().copied().unwrap_or(f64::NAN), 0.0])
+            .collect();
+        let match_of = match_peaks(&last, peaks, 0.0, tolerance_ppm);
+        for (track, m) in tracks.iter_mut().zip(&match_of) {
+            track.shifts.push(m.map(|pi| peaks[pi][0]));
+        }
+        let used: Vec<usize> = match_of.iter().flatten().copied().collect();
+        for (_, p) in peaks.iter().enumerate().filter(|(pi, _)| !used.contains(pi)) {
+            let mut shifts = vec![None; k];
+            shifts.push(Some(p[0]));
+            tracks.push(PeakTrack { shifts });
+        }
+    }
+    let start = |t: &PeakTrack| t.start_ppm().unwrap_or(f64::NEG_INFINITY);
+    tracks.sort_by(|a, b| start(b).total_cmp(&start(a)));
+    tracks
+}
+
+/// Before/after D₂O shake comparison of a 1H spectrum
+#[derive(Debug, Clone, PartialEq)]
+pub struct D2oExchange {
+    /// Peaks of the before spectrum that exchange (OH/NH): gone after the
+    /// shake, or down to less than the ratio limit of their intensity
+    pub exchanged: Vec<[f64; 2]>,
+    /// Before minus the aligned, scaled after spectrum, as [ppm, intensity]
+    /// on the before axis: exchanged signals stand out, the rest cancels
+    pub difference: Vec<[f64; 2]>,
+    /// Factor applied to the after spectrum, matching the summed intensity
+    /// of the peaks that do not exchange
+    pub scale: f64,
+}
+
+/// Flag exchangeable protons from a peak comparison (`rows`, see
+/// [`compare_peak_lists`]) of a spectrum `before` a D₂O shake with the
+/// spectrum `after` it, aligned by `offset_ppm`.
+///
+/// A peak exchanges when it is missing afterwards or keeps less than
+/// `max_ratio` of its normalised intensity; new peaks (the growing HDO
+/// line) are not flagged.
+pub fn d2o_exchange(
+    before: &SpectrumData,
+    after: &SpectrumData,
+    rows: &[PeakDiff],
+    offset_ppm: f64,
+    max_ratio: f64,
+) -> D2oExchange {
+    let exchanges = |row: &PeakDiff| match row.change {
+        PeakChange::Missing => true,
+        PeakChange::Common => row.intensity_ratio.is_some_and(|r| r < max_ratio),
+        PeakChange::New => false,
+    };
+    let exchanged: Vec<[f64; 2]> = rows.iter().filter(|r| exchanges(r)).filter_map(|r| r.reference).collect();
+
+    let (mut ref_sum, mut cmp_sum) = (0.0, 0.0);
+    for row in rows.iter().filter(|r| r.change == PeakChange::Common && !exchanges(r)) {
+        if let (Some(r), Some(c)) = (row.reference, row.compared) {
+            ref_sum += r[1].abs();
+            cmp_sum += c[1].abs();
+        }
+    }
+    let scale = if ref_sum > 0.0 && cmp_sum > 0.0 { ref_sum / cmp_sum } else { 1.0 };
+
+    // Shift and scale a copy of the after spectrum onto the before one
+    let mut aligned = after.clone();
+    aligned.real.iter_mut().for_each(|v| *v *= scale);
+    if let Some(axis) = aligned.axes.first_mut() {
+        axis.reference_ppm += offset_ppm;
+    }
+    let difference = match (difference_spectrum(before, &aligned), before.axes.first()) {
+        (Some(diff), Some(axis)) => diff
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| v.is_finite())
+            .map(|(i, &v)| [axis.index_to_ppm(i), v])
+            .collect(),
+        _ => Vec::new(),
+    };
+    D2oExchange { exchanged, difference, scale }
+}
+
+/// How far (ppm) a peak may sit from a tabulated impurity shift; the
+/// tables vary about this much with concentration
+pub const IMPURITY_TOLERANCE_PPM: f64 = 0.03;
+
+/// Label the peaks of a 1H spectrum in `solvent_column` of the
+/// [`impurities`](crate::data::impurities) table with the common
+/// impurities they match, as (peak ppm, names).  An impurity with several
+/// signals matches only when every one of them has a peak.
+pub fn identify_common_impurities(
+    peaks: &[[f64; 2]],
+    solvent_column: usize,
+    tolerance_ppm: f64,
+) -> Vec<(f64, String)> {
+    match_impurities(peaks, crate::data::impurities::IMPURITIES, solvent_column, tolerance_ppm)
+}
+
+/// Match `peaks` against the impurity `table` in `solvent_column`
+fn match_impurities(
+    peaks: &[[f64; 2]],
+    table: &[crate::data::impurities::Impurity],
+    solvent_column: usize,
+    tolerance_ppm: f64,
+) -> Vec<(f64, String)> {
+    let nearest = |ppm: f64| {
+        peaks
+            .iter()
+            .map(|p| p[0])
+            .filter(|p| (p - ppm).abs() <= tolerance_ppm)
+            .min_by(|a, b| (a - ppm).abs().total_cmp(&(b - ppm).abs()))
+    };
+    let mut labels: Vec<(f64, String)> = Vec::new();
+    for impurity in table {
+        let shifts: Vec<f64> = impurity.signals.iter().filter_map(|s| s.ppm(solvent_column)).collect();
+        let matched: Option<Vec<f64>> = shifts.iter().map(|&ppm| nearest(ppm)).collect();
+        let Some(matched) = matched.filter(|m| !m.is_empty()) else {
+            continue;
+        };
+        for ppm in matched {
+            match labels.iter_mut().find(|(p, _)| *p == ppm) {
+                Some((_, names)) if !names.split(" / ").any(|n| n == impurity.name) => {
+                    names.push_str(" / ");
+                    names.push_str(impurity.name);
+                }
+                Some(_) => {}
+                None => labels.push((ppm, impurity.name.to_string())),
+            }
+        }
+    }
+    labels.sort_by(|a, b| b.0.total_cmp(&a.0));
+    labels
+}
+
+/// How far (ppm) from the residual solvent shift a peak is taken for the
+/// solvent, and from a tabulated impurity shift, per nucleus
+fn solvent_match_widths(nucleus: &Nucleus) -> Option<(f64, f64)> {
+    match nucleus {
+        Nucleus::H1 | Nucleus::H2 => Some((0.05, IMPURITY_TOLERANCE_PPM)),
+        Nucleus::C13 => Some((1.0, 0.3)),
+        _ => None,
+    }
+}
+
+/// Flag the peaks of the residual solvent signal and of the common
+/// impurities, for a spectrum recorded in `solvent` (as written in the
+/// acquisition parameters).  Peaks of the solvent multiplet are labelled
+/// "<solvent> (solvent)"; the others are matched against the impurity table
+/// of the nucleus when it lists the solvent.  Empty for an unknown solvent
+/// or a nucleus without tables.
+pub fn annotate_solvent_and_impurities(peaks: &[[f64; 2]], solvent: &str, nucleus: &Nucleus) -> Vec<(f64, String)> {
+    use crate::data::{impurities, solvent as solvents};
+
+    let (Some(known), Some((solvent_width, tolerance))) = (solvents::identify(solvent), solvent_match_widths(nucleus))
+    else {
+        return Vec::new();
+    };
+    let mut labels: Vec<(f64, String)> = match known.residual_ppm(nucleus) {
+        Some(residual) => peaks
+            .iter()
+            .filter(|p| (p[0] - residual).abs() <= solvent_width)
+            .map(|p| (p[0], format!("{} (solvent)", known.name)))
+            .collect(),
+        None => Vec::new(),
+    };
+    if let (Some(table), Some(column)) = (impurities::table(nucleus), impurities::column(known.name)) {
+        for (ppm, names) in match_impurities(peaks, table, column, tolerance) {
+            if !labels.iter().any(|(p, _)| *p == ppm) {
+                labels.push((ppm, names));
+            }
+        }
+    }
+    labels.sort_by(|a, b| b.0.total_cmp(&a.0));
+    labels
+}
+
+// =========================================================================
+//  Carbon Multiplicity (DEPT / APT)
+// =========================================================================
+
+/// Spectral-editing experiment whose peak signs tell carbon types apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpectralEditing {
+    /// CH and CH3 up, CH2 down; quaternary carbons do not appear
+    Dept135,
+    /// Attached proton test: C and CH2 up, CH and CH3 down (Bruker `jmod`
+    /// phasing)
+    Apt,
+}
+
+impl std::fmt::Display for SpectralEditing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpectralEditing::Dept135 => write!(f, "DEPT-135"),
+            SpectralEditing::Apt => write!(f, "APT"),
+        }
+    }
+}
+
+impl SpectralEditing {
+    /// Editing experiment of `spectrum`, from its experiment type or pulse
+    /// program; `None` for other spectra, a DEPT-90 included
+    pub fn detect(spectrum: &SpectrumData) -> Option<Self> {
+        if is_dept90(spectrum) {
+            return None;
+        }
+        let pulse_program = spectrum.metadata.get(META_PULSE_PROGRAM).map(|p| p.to_uppercase()).unwrap_or_default();
+        match &spectrum.experiment_type {
+            ExperimentType::Dept135 => Some(SpectralEditing::Dept135),
+            ExperimentType::Other(name) if name.to_uppercase().contains("APT") => Some(SpectralEditing::Apt),
+            _ if pulse_program.contains("APT") || pulse_program.contains("JMOD") => Some(SpectralEditing::Apt),
+            _ => None,
+        }
+    }
+
+    /// Whether a peak of sign `positive` is a CH or CH3, for a spectrum
+    /// phased the usual way (`inverted` false) or the opposite way
+    fn odd_protons(self, positive: bool, inverted: bool) -> bool {
+        let up = match self {
+            SpectralEditing::Dept135 => true,
+            SpectralEditing::Apt => false,
+        };
+        positive == (up != inverted)
+    }
+}
+
+/// Whether `spectrum` is a DEPT-90 (only CH carbons), from its pulse
+/// program, sample or file name
+pub fn is_dept90(spectrum: &SpectrumData) -> bool {
+    let names_dept90 = |text: &str| {
+        let compact: String = text.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+        compact.to_uppercase().contains("DEPT90")
+    };
+    let file = spectrum.source_path.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+    spectrum.metadata.get(META_PULSE_PROGRAM).is_some_and(|p| names_dept90(p))
+        || names_dept90(&spectrum.sample_name)
+        || names_dept90(&file)
+}
+
+/// Number of attached protons of a carbon, as far as the spectra tell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CarbonType {
+    Ch,
+    Ch2,
+    Ch3,
+    /// CH or CH3, without a DEPT-90 to tell them apart
+    ChOrCh3,
+    /// Quaternary C or CH2 (APT)
+    COrCh2,
+}
+
+impl std::fmt::Display for CarbonType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CarbonType::Ch => write!(f, "CH"),
+            CarbonType::Ch2 => write!(f, "CH2"),
+            CarbonType::Ch3 => write!(f, "CH3"),
+            CarbonType::ChOrCh3 => write!(f, "CH/CH3"),
+            CarbonType::COrCh2 => write!(f, "C/CH2"),
+        }
+    }
+}
+
+/// A peak of an edited carbon spectrum with its carbon type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CarbonPeak {
+    pub ppm: f64,
+    pub intensity: f64,
+    pub carbon: CarbonType,
+    /// Height of the matching DEPT-90 peak relative to the tallest one,
+    /// when a DEPT-90 was compared
+    pub dept90: Option<f64>,
+}
+
+/// Smallest DEPT-90 peak, relative to the tallest, that marks a CH; weaker
+/// ones are CH3 (and CH2) leaking through an imperfect 90° transfer
+pub const DEPT90_MIN_RATIO: f64 = 0.2;
+
+/// Carbon type of each of `peaks` of an edited spectrum from its sign.
+/// With the peaks of a DEPT-90 of the same sample, the CH/CH3 peaks that
+/// have a DEPT-90 peak within `tolerance_ppm` (at least
+/// [`DEPT90_MIN_RATIO`] of its tallest) are CH and the others CH3.
+pub fn classify_carbons(
+    peaks: &[[f64; 2]],
+    editing: SpectralEditing,
+    inverted: bool,
+    dept90: Option<&[[f64; 2]]>,
+    tolerance_ppm: f64,
+) -> Vec<CarbonPeak> {
+    let tallest_90 = dept90.map_or(0.0, |d| d.iter().fold(0.0f64, |m, p| m.max(p[1])));
+    peaks
+        .iter()
+        .filter(|p| p[1] != 0.0)
+        .map(|p| {
+            let odd = editing.odd_protons(p[1] > 0.0, inverted);
+            let ratio = dept90.filter(|_| odd && tallest_90 > 0.0).map(|d| {
+                d.iter()
+                    .filter(|q| (q[0] - p[0]).abs() <= tolerance_ppm)
+                    .fold(0.0f64, |m, q| m.max(q[1] / tallest_90))
+            });
+            let carbon = match (odd, editing, ratio) {
+                (true, _, None) => CarbonType::ChOrCh3,
+                (true, _, Some(r)) if r >= DEPT90_MIN_RATIO => CarbonType::Ch,
+                (true, _, Some(_)) => CarbonType::Ch3,
+                (false, SpectralEditing::Dept135, _) => CarbonType::Ch2,
+                (false, SpectralEditing::Apt, _) => CarbonType::COrCh2,
+            };
+            CarbonPeak { ppm: p[0], intensity: p[1], carbon, dept90: ratio }
+        })
+        .collect()
+}
+
+/// Count of each carbon type, e.g. "3 CH, 2 CH2, 1 CH3"
+pub fn carbon_type_summary(carbons: &[CarbonPeak]) -> String {
+    let types =
+        [CarbonType::Ch, CarbonType::Ch2, CarbonType::Ch3, CarbonType::ChOrCh3, CarbonType::COrCh2];
+    let counts: Vec<String> = types
+        .iter()
+        .map(|&t| (t, carbons.iter().filter(|c| c.carbon == t).count()))
+        .filter(|&(_, n)| n > 0)
+        .map(|(t, n)| format!("{} {}", n, t))
+        .collect();
+    if counts.is_empty() { "no peaks".to_string() } else { counts.join(", ") }
+}
+
+/// Quick default processing of a 1D FID for side-by-side comparisons:
+/// EM, zero-fill to twice the next power of two, FT, auto-phase and
+/// baseline correction.  Frequency-domain data is left untouched.
+pub fn quick_process_1d(spectrum: &mut SpectrumData, lb_hz: f64, log: &mut ReproLog) {
+    if spectrum.is_frequency_domain || spectrum.is_2d() {
+        return;
+    }
+    apply_apodization(spectrum, &WindowFunction::Exponential { lb_hz }, log);
+    let size = next_power_of_two(spectrum.real.len()) * 2;
+    zero_fill(spectrum, size, log);
+    let use_imaginary = !spectrum.imag.is_empty();
+    fourier_transform(spectrum, use_imaginary, log);
+    auto_phase(spectrum, log);
+    baseline_correct(spectrum, &BaselineMethod::Linear, log);
+}
+
+// =========================================================================
+//  Solvent Suppression
+// =========================================================================
+
+/// Suppress solvent signal by zeroing a region around the specified ppm
+pub fn solvent_suppress(
+    spectrum: &mut SpectrumData,
+    center_ppm: f64,
+    width_ppm: f64,
+    log: &mut ReproLog,
+) {
+    if !spectrum.is_frequency_domain {
+        log::warn!("Solvent suppression should be applied in frequency domain");
+        return;
+    }
+
+    let n = spectrum.real.len();
+    if n == 0 {
+        return;
+    }
+
+    if let Some(ax) = spectrum.axes.first() {
+        let low_ppm = center_ppm - width_ppm / 2.0;
+        let high_ppm = center_ppm + width_ppm / 2.0;
+
+        for i in 0..n {
+            let ppm = ax.index_to_ppm(i);
+            if ppm >= low_ppm && ppm <= high_ppm {
+                let factor = solvent_suppression_factor(ppm, center_ppm, width_ppm);
+                spectrum.real[i] *= factor;
+                if i < spectrum.imag.len() {
+                    spectrum.imag[i] *= factor;
+                }
+            }
+        }
+    }
+
+    let nmrpipe_cmd = format!(
+        "nmrPipe -fn SOL -fl {} -fs {}",
+        (width_ppm * 100.0) as i32,
+        16
+    );
+    log.add_entry(
+        "Solvent Suppression",
+        &format!("Suppressed region: {:.2} ± {:.2} ppm", center_ppm, width_ppm / 2.0),
+        &nmrpipe_cmd,
+    );
+}
+
+/// Fraction of the signal at `ppm` kept by solvent suppression of the
+/// given region: 0 across the inner 80 %, rising along a sine edge to 1 at
+/// the borders and beyond.
+pub fn solvent_suppression_factor(ppm: f64, center_ppm: f64, width_ppm: f64) -> f64 {
+    let half_width = width_ppm / 2.0;
+    let dist_from_center = (ppm - center_ppm).abs();
+    if dist_from_center > half_width {
+        1.0
+    } else if dist_from_center > half_width * 0.8 {
+        // Smooth transition using cosine window at edges
+        let edge_frac = (dist_from_center - half_width * 0.8) / (half_width * 0.2);
+        (edge_frac * PI / 2.0).sin()
+    } else {
+        0.0
+    }
+}
+
+/// Suppress several regions, each given as (low, high) ppm, e.g. picked on
+/// the spectrum: one `solvent_suppress` per region.
+pub fn solvent_suppress_regions(spectrum: &mut SpectrumData, regions: &[(f64, f64)], log: &mut ReproLog) {
+    for &(lo, hi) in regions {
+        let (lo, hi) = (lo.min(hi), lo.max(hi));
+        solvent_suppress(spectrum, (lo + hi) / 2.0, hi - lo, log);
+    }
+}
+
+// =========================================================================
+//  Solvent Referencing
+// =========================================================================
+
+/// Result of referencing a spectrum to its residual solvent signal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolventReference {
+    pub solvent: &'static str,
+    /// Tabulated residual shift
+    pub expected_ppm: f64,
+    /// Where the signal was found before referencing
+    pub found_ppm: f64,
+    /// Shift added to the ppm axis (`expected - found`)
+    pub shift_ppm: f64,
+}
+
+/// Half-width of the window searched for the residual signal around its
+/// tabulated shift
+fn solvent_search_window(nucleus: &Nucleus) -> f64 {
+    match nucleus {
+        Nucleus::H1 | Nucleus::H2 => 0.3,
+        _ => 2.0,
+    }
+}
+
+/// Minimum height of the residual signal over the median absolute
+/// intensity (a robust noise level for sparse NMR spectra)
+const SOLVENT_MIN_SNR: f64 = 10.0;
+
+/// Locate the residual solvent signal of a 1D spectrum without changing it.
+///
+/// Searches the magnitude (so an unphased spectrum straight after the FT
+/// works too) around the tabulated shift of the spectrum's solvent and
+/// takes the middle one of the lines reaching half the window's tallest
+/// point: the centre line of the CDCl3 13C triplet or the DMSO-d6 quintet
+/// rather than whichever line happens to be tallest.
+/// `None` if the solvent or nucleus is not in the table, or no signal
+/// stands out of the noise.
+pub fn find_solvent_reference(spectrum: &SpectrumData) -> Option<SolventReference> {
+    if !spectrum.is_frequency_domain || spectrum.is_2d() {
+        return None;
+    }
+    let solvent = crate::data::solvent::identify(&spectrum.solvent)?;
+    let axis = spectrum.axes.first()?;
+    let expected_ppm = solvent.residual_ppm(&axis.nucleus)?;
+    let n = spectrum.real.len().min(axis.num_points);
+    if n < 3 {
+        return None;
+    }
+    let magnitude: Vec<f64> = if spectrum.imag.len() >= n {
+        (0..n).map(|i| spectrum.real[i].hypot(spectrum.imag[i])).collect()
+    } else {
+        spectrum.real[..n].iter().map(|v| v.abs()).collect()
+    };
+
+    let window = solvent_search_window(&axis.nucleus);
+    let a = axis.ppm_to_position(expected_ppm + window)?;
+    let b = axis.ppm_to_position(expected_ppm - window)?;
+    let lo = (a.min(b).floor().max(1.0) as usize).min(n - 1);
+    let hi = (a.max(b).ceil().max(0.0) as usize).min(n - 2);
+    if lo > hi {
+        return None;
+    }
+    let tallest = magnitude[lo..=hi].iter().cloned().fold(0.0, f64::max);
+    let mut sorted = magnitude.clone();
+    let mid = sorted.len() / 2;
+    let noise = *sorted.select_nth_unstable_by(mid, |x, y| x.total_cmp(y)).1;
+    if tallest <= SOLVENT_MIN_SNR * noise {
+        return None;
+    }
+
+    let lines: Vec<usize> = (lo..=hi)
+        .filter(|&i| {
+            magnitude[i] >= tallest / 2.0
+                && magnitude[i] >= magnitude[i - 1]
+                && magnitude[i] > magnitude[i + 1]
+        })
+        .collect();
+    let top = match lines.len() {
+        0 => return None,
+        k if k % 2 == 1 => lines[k / 2],
+        k => {
+            let (i, j) = (lines[k / 2 - 1], lines[k / 2]);
+            if magnitude[i] >= magnitude[j] { i } else { j }
+        }
+    };
+
+    // Parabolic interpolation between the points around the top
+    let (ym, y0, yp) = (magnitude[top - 1], magnitude[top], magnitude[top + 1]);
+    let curvature = ym - 2.0 * y0 + yp;
+    let offset = if curvature < 0.0 { 0.5 * (ym - yp) / curvature } else { 0.0 };
+    let ppm_per_point = axis.ppm_per_point();
+    let found_ppm = axis.index_to_ppm(top) - offset * ppm_per_point;
+    Some(SolventReference {
+        solvent: solvent.name,
+        expected_ppm,
+        found_ppm,
+        shift_ppm: expected_ppm - found_ppm,
+    })
+}
+
+/// Reference a 1D spectrum to its residual solvent signal (see
+/// [`find_solvent_reference`]) by shifting the ppm axis, and log the shift
+/// applied.
+pub fn reference_to_solvent(spectrum: &mut SpectrumData, log: &mut ReproLog) -> Option<SolventReference> {
+    let found = find_solvent_reference(spectrum)?;
+    let axis = spectrum.axes.first_mut()?;
+    axis.reference_ppm += found.shift_ppm;
+    let carrier = axis.index_to_ppm(axis.num_points / 2);
+    log.add_entry(
+        "Solvent Referencing",
+        &format!(
+            "Referenced to residual {} at {:.2} ppm (found at {:.4} ppm, shift {:+.4} ppm)",
+            found.solvent, found.expected_ppm, found.found_ppm, found.shift_ppm
+        ),
+        &format!("# header only: sethdr <file> -xCAR {:.4}", carrier),
+    );
+    Some(found)
+}
+
+/// Calibrate the F1 axis of a 2D spectrum so that a signal seen at
+/// `observed_ppm` reads `true_ppm`, e.g. when the converter had no
+/// indirect carrier to go on.  Returns the shift applied, `None` for 1D data.
+pub fn calibrate_f1(spectrum: &mut SpectrumData, observed_ppm: f64, true_ppm: f64, log: &mut ReproLog) -> Option<f64> {
+    if !spectrum.is_2d() {
+        return None;
+    }
+    let axis = spectrum.axes.get_mut(1)?;
+    let shift = true_ppm - observed_ppm;
+    axis.reference_ppm += shift;
+    let carrier = axis.index_to_ppm(axis.num_points / 2);
+    log.add_entry(
+        "F1 Calibration",
+        &format!(
+            "F1 ({}) axis shifted {:+.4} ppm: {:.4} ppm now reads {:.4} ppm, carrier {:.4} ppm",
+            axis.label, shift, observed_ppm, true_ppm, carrier
+        ),
+        &format!("# header only: sethdr <file> -yCAR {:.4}", carrier),
+    );
+    Some(shift)
+}
+
+// =========================================================================
+//  NMRPipe Subprocess Execution
+// =========================================================================
+
+/// Execute a processing operation via NMRPipe subprocess
+/// This is used when NMRPipe is available and the user prefers it
+pub fn execute_via_nmrpipe(
+    input_path: &Path,
+    output_path: &Path,
+    function_name: &str,
+    params: &[(&str, &str)],
+    log: &mut ReproLog,
+) -> io::Result<()> {
+    let mut cmd = NmrPipeCommand::new("nmrPipe")
+        .arg("-in")
+        .arg(&input_path.to_string_lossy())
+        .arg("-fn")
+        .arg(function_name);
+
+    for (key, val) in params {
+        cmd = cmd.arg(key).arg(val);
+    }
+
+    cmd = cmd
+        .arg("-out")
+        .arg(&output_path.to_string_lossy())
+        .arg("-ov");
+
+    log.add_entry(
+        &format!("NMRPipe: {}", function_name),
+        &format!("Executing via NMRPipe subprocess"),
+        &cmd.to_command_string(),
+    );
+
+    let result = cmd.execute()?;
+    if !result.success {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("NMRPipe execution failed: {}", result.stderr),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peak_fitting_deconvolves_overlapping_lines() {
+        // Two 2 Hz lines 3 Hz apart (heights 100 and 40) on an offset of 5,
+        // 4096 points over 2000 Hz at 400 MHz
+        let axis = AxisParams {
+            num_points: 4096,
+            spectral_width_hz: 2000.0,
+            observe_freq_mhz: 400.0,
+            reference_ppm: 5.0,
+            ..Default::default()
+        };
+        let hz_per_point = 2000.0 / 4096.0;
+        let lines = [(3.0, 100.0), (3.0075, 40.0)];
+        let position = |ppm: f64| axis.ppm_to_position(ppm).unwrap();
+        // Line shapes take the distance from the centre in half widths (Hz)
+        let spectrum = |shape: &dyn Fn(f64) -> f64| SpectrumData {
+            is_frequency_domain: true,
+            real: (0..4096)
+                .map(|i| 5.0 + lines.iter().map(|&(ppm, h)| h * shape((i as f64 - position(ppm)) * hz_per_point)).sum::<f64>())
+                .collect(),
+            axes: vec![axis.clone()],
+            ..Default::default()
+        };
+        let lorentz = spectrum(&|hz| 1.0 / (1.0 + hz * hz));
+        let gauss = spectrum(&|hz| (-std::f64::consts::LN_2 * hz * hz).exp());
+
+        // Started from rough guesses, the fit recovers both lines
+        let fit = fit_peaks(&lorentz, 2.96, 3.05, &[3.0005, 3.007, 4.0], LineShape::Lorentzian).unwrap();
+        assert_eq!(fit.peaks.len(), 2);
+        assert_eq!(fit.components.len(), 2);
+        assert!((fit.offset - 5.0).abs() < 0.5, "offset {}", fit.offset);
+        assert!(fit.relative_residual < 1e-3, "residual {}", fit.relative_residual);
+        let (a, b) = (&fit.peaks[0], &fit.peaks[1]);
+        assert!((a.center_ppm - 3.0075).abs() < 1e-4 && (b.center_ppm - 3.0).abs() < 1e-4);
+        assert!((a.fwhm_hz - 2.0).abs() < 0.05 && (b.fwhm_hz - 2.0).abs() < 0.05);
+        assert!((a.amplitude - 40.0).abs() < 1.0 && (b.amplitude - 100.0).abs() < 1.0);
+        // Areas in integral units keep the 100:40 ratio
+        assert!((b.area / a.area - 2.5).abs() < 0.05);
+        let expected = 100.0 * std::f64::consts::PI / 2.0 * 2.0 / hz_per_point;
+        assert!((b.area / expected - 1.0).abs() < 0.02, "area {} vs {}", b.area, expected);
+
+        // A Gaussian model fits Gaussian lines, a pseudo-Voigt finds them
+        // mostly Gaussian, and a Lorentzian misfits them
+        let g = fit_peaks(&gauss, 2.96, 3.05, &[3.0, 3.0075], LineShape::Gaussian).unwrap();
+        assert!(g.relative_residual < 1e-3);
+        assert!(g.peaks.iter().all(|p| (p.fwhm_hz - 2.0).abs() < 0.05));
+        let v = fit_peaks(&gauss, 2.96, 3.05, &[3.0, 3.0075], LineShape::PseudoVoigt).unwrap();
+        assert!(v.peaks.iter().all(|p| p.lorentz_fraction < 0.1), "{:?}", v.peaks);
+        let l = fit_peaks(&gauss, 2.96, 3.05, &[3.0, 3.0075], LineShape::Lorentzian).unwrap();
+        assert!(l.relative_residual > 10.0 * g.relative_residual);
+
+        // Without picked peaks one line starts at the tallest point
+        let single = fit_peaks(&lorentz, 2.96, 3.05, &[], LineShape::Lorentzian).unwrap();
+        assert_eq!(single.peaks.len(), 1);
+        assert!((single.peaks[0].center_ppm - 3.0).abs() < 2e-3);
+        assert!(fit_peaks(&lorentz, 3.0, 3.0, &[3.0], LineShape::Lorentzian).is_none());
+    }
+
+    #[test]
+    fn test_integral_trace_ends_at_the_region_integral() {
+        // A line on an offset baseline: the trace rises through the line,
+        // is flat either side of it and ends at the region's integral
+        let n = 1000;
+        let axis = AxisParams {
+            num_points: n,
+            spectral_width_hz: 4000.0,
+            observe_freq_mhz: 400.0,
+            reference_ppm: 10.0,
+            ..Default::default()
+        };
+        let spectrum = SpectrumData {
+            real: (0..n).map(|i| 0.05 + 1.0 / (1.0 + ((i as f64 - 500.0) / 3.0).powi(2))).collect(),
+            is_frequency_domain: true,
+            axes: vec![axis],
+            ..Default::default()
+        };
+        let (lo, hi) = (spectrum.axes[0].index_to_ppm(650), spectrum.axes[0].index_to_ppm(350));
+        for baseline in [IntegralBaseline::TwoPoint, IntegralBaseline::None] {
+            let trace = integral_trace(&spectrum, lo, hi, baseline);
+            assert_eq!(trace.len(), 301);
+            assert!(trace.windows(2).all(|w| w[0][0] > w[1][0]), "starts at the high-ppm edge");
+            let total = integrate_region_corrected(&spectrum, lo, hi, baseline);
+            assert!((trace[300][1] - total).abs() < 1e-9 * total.abs());
+        }
+        let trace = integral_trace(&spectrum, lo, hi, IntegralBaseline::TwoPoint);
+        assert!(trace[100][1].abs() < 0.1 * trace[300][1]);
+        assert!((trace[200][1] - trace[300][1]).abs() < 0.1 * trace[300][1]);
+    }
+
+    #[test]
+    fn test_steps_that_do_not_apply_are_reported() {
+        let mut spectrum = SpectrumData::default();
+        let e = nus_reconstruct(&mut spectrum, 10, &mut ReproLog::new()).unwrap_err();
+        assert!(matches!(e, NmrError::NotApplicable(_)), "{:?}", e);
+    }
+
+    #[test]
+    fn test_ft2d_hypercomplex_acquisition_modes() {
+        use num_complex::Complex;
+
+        // One cross peak: F2 frequency w2 per point, F1 frequency w1 per
+        // increment, decaying in both
+        let (increments, cols, w1, w2) = (16, 32, 0.9, 1.3);
+        let f2 = |c: usize| Complex::from_polar((-0.08 * c as f64).exp(), w2 * c as f64);
+        let t1 = |k: usize| Complex::from_polar((-0.1 * k as f64).exp(), w1 * k as f64);
+        let fid = |acquisition: F1Acquisition, rows: Vec<Vec<Complex<f64>>>| SpectrumData {
+            dimensionality: Dimensionality::TwoD,
+            data_2d: rows.iter().map(|r| r.iter().map(|z| z.re).collect()).collect(),
+            data_2d_imag: rows.iter().map(|r| r.iter().map(|z| z.im).collect()).collect(),
+            f1_acquisition: acquisition,
+            axes: vec![AxisParams::default(); 2],
+            ..Default::default()
+        };
+        let rows = |f: &dyn Fn(usize) -> Complex<f64>, n: usize| -> Vec<Vec<Complex<f64>>> {
+            (0..n).map(|k| (0..cols).map(|c| f(k) * f2(c)).collect()).collect()
+        };
+        // Row pairs per increment: States cosine/sine, States-TPPI with every
+        // other increment negated, echo/antiecho as P- and N-type
+        let pairs = |first: &dyn Fn(usize) -> Complex<f64>, second: &dyn Fn(usize) -> Complex<f64>| {
+            rows(&|r| if r % 2 == 0 { first(r / 2) } else { second(r / 2) }, 2 * increments)
+        };
+        let sign = |k: usize| if k % 2 == 1 { -1.0 } else { 1.0 };
+        let complex = fid(F1Acquisition::Complex, rows(&t1, increments));
+        let states = fid(F1Acquisition::States, pairs(&|k| t1(k).re.into(), &|k| t1(k).im.into()));
+        let states_tppi =
+            fid(F1Acquisition::StatesTppi, pairs(&|k| (sign(k) * t1(k).re).into(), &|k| (sign(k) * t1(k).im).into()));
+        let echo = fid(F1Acquisition::EchoAntiecho, pairs(&t1, &|k| t1(k).conj()));
+
+        let mut log = ReproLog::new();
+        let transform = |mut s: SpectrumData, mode: Ft2dMode, log: &mut ReproLog| {
+            fourier_transform_2d(&mut s, mode, log);
+            s
+        };
+        let reference = transform(complex, Ft2dMode::Magnitude, &mut log);
+        let phased = transform(states.clone(), Ft2dMode::PhaseSensitive, &mut log);
+        assert_eq!((phased.data_2d.len(), phased.axes[1].num_points), (increments, increments));
+        assert!(phased.f1_quadrants.is_some() && has_2d_imaginary(&phased));
+        assert!(log.entries.last().unwrap().description.starts_with("Hypercomplex 2D FFT (States in t1)"));
+
+        // |RR, RI, IR, II| is the magnitude of the complex transform
+        let magnitude = transform(states, Ft2dMode::Magnitude, &mut log);
+        assert!(magnitude.f1_quadrants.is_none() && magnitude.data_2d_imag.is_empty());
+        for (a, b) in magnitude.data_2d.iter().flatten().zip(reference.data_2d.iter().flatten()) {
+            assert!((a - b).abs() < 1e-9, "{} vs {}", a, b);
+        }
+        // The other pair layouts give the very same quadrants
+        for other in [states_tppi, echo] {
+            let other = transform(other, Ft2dMode::PhaseSensitive, &mut log);
+            let (q, p) = (other.f1_quadrants.as_ref().unwrap(), phased.f1_quadrants.as_ref().unwrap());
+            let close = |a: &Vec<Vec<f64>>, b: &Vec<Vec<f64>>| {
+                a.iter().flatten().zip(b.iter().flatten()).all(|(x, y)| (x - y).abs() < 1e-9)
+            };
+            assert!(close(&other.data_2d, &phased.data_2d) && close(&other.data_2d_imag, &phased.data_2d_imag));
+            assert!(close(&q.ir, &p.ir) && close(&q.ii, &p.ii));
+        }
+
+        // TPPI: one real row per t1 point; the real FT keeps the positive
+        // half, so a cosine at 5/32 of the sampling rate is a single peak
+        let theta = 2.0 * std::f64::consts::PI * 5.0 / 32.0;
+        let tppi = fid(F1Acquisition::Tppi, rows(&|j| ((-0.05 * j as f64).exp() * (theta * j as f64).cos()).into(), 32));
+        let tppi = transform(tppi, Ft2dMode::Magnitude, &mut log);
+        assert_eq!(tppi.data_2d.len(), 16);
+        let column = |r: usize| tppi.data_2d[r].iter().cloned().fold(0.0, f64::max);
+        let tallest = (0..16).max_by(|&a, &b| column(a).total_cmp(&column(b))).unwrap();
+        assert_eq!(tallest, 16 - 1 - 5);
+    }
+
+    #[test]
+    fn test_peak_intensities_follow_scaling() {
+        let n = 64;
+        let mut spectrum = SpectrumData {
+            real: (0..n).map(|i| if i == 20 { 10.0 } else if i == 40 { 5.0 } else { 0.0 }).collect(),
+            is_frequency_domain: true,
+            axes: vec![AxisParams {
+                num_points: n,
+                spectral_width_hz: 6400.0,
+                observe_freq_mhz: 400.0,
+                reference_ppm: 12.0,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut peaks = detect_peaks(&spectrum, 0.1, 1, PeakSign::Positive);
+        assert_eq!(peaks.len(), 2);
+
+        // Scale the data: stored intensities go stale until resampled
+        for v in spectrum.real.iter_mut() {
+            *v *= 3.0;
+        }
+        resample_peak_intensities(&spectrum, &mut peaks);
+        assert!((peaks[0][1] - 30.0).abs() < 1e-9);
+        assert!((peaks[1][1] - 15.0).abs() < 1e-9);
+        // A NaN position is left alone instead of panicking
+        let mut odd = [[f64::NAN, 1.0], [100.0, 0.0]];
+        resample_peak_intensities(&spectrum, &mut odd);
+        assert_eq!(odd[0][1], 1.0);
+        assert_eq!(odd[1][1], spectrum.real[0]);
+
+        let rel = normalized_intensities(&peaks, IntensityMode::RelativeToTallest, None, 1.0);
+        assert_eq!(rel, vec![100.0, 50.0]);
+
+        // Integral normalization is invariant under scaling as well
+        let raw = integrate_region(&spectrum, 12.0, 0.0);
+        let per_h = normalized_intensities(&peaks, IntensityMode::RelativeToIntegral, Some(raw), 3.0);
+        assert!((per_h[0] - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_difference_spectrum_interpolates_by_ppm() {
+        let spectrum = |n: usize, f: &dyn Fn(f64) -> f64| {
+            let axis = AxisParams {
+                num_points: n,
+                spectral_width_hz: 4000.0,
+                observe_freq_mhz: 400.0,
+                reference_ppm: 10.0,
+                ..Default::default()
+            };
+            SpectrumData {
+                real: (0..n).map(|i| f(axis.index_to_ppm(i))).collect(),
+                is_frequency_domain: true,
+                axes: vec![axis],
+                ..Default::default()
+            }
+        };
+
+        // Linear baseline removed; "after" was zero-filled to twice the points
+        let before = spectrum(64, &|ppm| 2.0 * ppm + 1.0);
+        let after = spectrum(128, &|_| 0.0);
+        let diff = difference_spectrum(&after, &before).unwrap();
+        assert_eq!(diff.len(), 128);
+        for (i, d) in diff.iter().enumerate().filter(|(_, d)| d.is_finite()) {
+            let ppm = after.axes[0].index_to_ppm(i);
+            assert!((d + 2.0 * ppm + 1.0).abs() < 1e-9);
+        }
+
+        let mut fid = before.clone();
+        fid.is_frequency_domain = false;
+        assert!(difference_spectrum(&after, &fid).is_none());
+
+        // Axis claims points the data does not have
+        let mut empty = before.clone();
+        empty.real.clear();
+        assert!(difference_spectrum(&after, &empty).is_none());
+    }
+
+    #[test]
+    fn test_isotope_shift_pairing() {
+        // Three 1H sites; two exchanged, one 2H signal from residual solvent
+        let h = [[1.200, 3.0], [4.512, 1.0], [7.260, 2.0]];
+        let d = [[4.500, 0.5], [1.196, 1.0], [2.500, 4.0]];
+        let pairs = pair_isotope_shifts(&h, &d, 0.05);
+
+        assert_eq!(pairs.len(), 3);
+        assert_eq!(pairs[0].h_ppm, 7.260);
+        assert_eq!(pairs[0].d_ppm, None);
+        assert!((pairs[1].shift_ppb.unwrap() + 12.0).abs() < 1e-6);
+        assert!((pairs[2].shift_ppb.unwrap() + 4.0).abs() < 1e-6);
+        assert_eq!(pairs[2].d_relative, Some(1.0));
+        assert_eq!(pairs[1].d_relative, Some(0.5));
+
+        // A 2H acquisition named like a proton one is still a 2H spectrum
+        let mut s = SpectrumData {
+            axes: vec![AxisParams { nucleus: Nucleus::H2, ..Default::default() }],
+            experiment_type: ExperimentType::Proton,
+            ..Default::default()
+        };
+        s.refine_experiment_type();
+        assert_eq!(s.experiment_type, ExperimentType::Deuterium);
+    }
+
+    #[test]
+    fn test_dead_time_backward_lp_restores_fid() {
+        // Two damped complex sinusoids with the first 6 points wiped out
+        let fid = |i: usize| {
+            let t = i as f64;
+            let a = (-t / 200.0).exp();
+            let b = 0.5 * (-t / 80.0).exp();
+            (a * (0.31 * t).cos() + b * (1.7 * t).cos(), a * (0.31 * t).sin() + b * (1.7 * t).sin())
+        };
+        let clean: Vec<(f64, f64)> = (0..1024).map(fid).collect();
+        let mut s = SpectrumData {
+            real: clean.iter().map(|p| p.0).collect(),
+            imag: clean.iter().map(|p| p.1).collect(),
+            ..Default::default()
+        };
+        for i in 0..6 {
+            s.real[i] = 5.0;
+            s.imag[i] = -5.0;
+        }
+
+        let mut log = ReproLog::new();
+        dead_time_correct(&mut s, 6, DeadTimeMode::BackwardLp, &mut log);
+        for (i, (re, im)) in clean.iter().take(6).enumerate() {
+            assert!((s.real[i] - re).abs() < 1e-6 && (s.imag[i] - im).abs() < 1e-6);
+        }
+        assert_eq!(s.real[6], clean[6].0);
+        assert!(log.entries[0].nmrpipe_command.contains("LP -before"));
+
+        let fifth = s.real[4];
+        dead_time_correct(&mut s, 4, DeadTimeMode::Drop, &mut log);
+        assert_eq!(s.real.len(), 1024);
+        assert_eq!(s.real[0], fifth);
+        assert_eq!(s.imag[1023], 0.0);
+    }
+
+    #[test]
+    fn test_lttb_web_export_keeps_peaks() {
+        let n = 50_000;
+        let mut real: Vec<f64> = (0..n).map(|i| ((i as f64) * 0.01).sin() * 0.01).collect();
+        real[12_345] = 100.0;
+        real[40_000] = -50.0;
+        real[7] = f64::NAN;
+        let spectrum = SpectrumData {
+            real,
+            is_frequency_domain: true,
+            axes: vec![AxisParams {
+                num_points: n,
+                spectral_width_hz: 6000.0,
+                observe_freq_mhz: 600.0,
+                reference_ppm: 10.0,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let export = web_export(&spectrum, &[], 1000, 4).unwrap();
+        assert_eq!(export.points.len(), 1000);
+        assert_eq!(export.source_points, n);
+        assert_eq!(export.x_unit, "ppm");
+        // Ends are kept, extremes survive, order is preserved
+        assert_eq!(export.points[0][0], 10.0);
+        assert!(export.points.iter().any(|p| p[1] == 100.0));
+        assert!(export.points.iter().any(|p| p[1] == -50.0));
+        assert!(export.points.windows(2).all(|w| w[0][0] >= w[1][0]));
+        assert!(export.points.iter().all(|p| p[1].is_finite()));
+        assert!(serde_json::to_string(&export).unwrap().contains("\"points\":[[10.0,"));
+
+        // Small inputs pass through unchanged
+        let few = [[0.0, 1.0], [1.0, 2.0]];
+        assert_eq!(lttb(&few, 1000), few.to_vec());
+    }
+
+    #[test]
+    fn test_lorentz_gauss_preview_resolves_shoulder() {
+        // Two 2 Hz wide lines 1 Hz apart: a single maximum with a shoulder
+        let (n, sw) = (8192, 1000.0);
+        let fid = |i: usize| {
+            let t = i as f64 / sw;
+            let decay = (-std::f64::consts::PI * 2.0 * t).exp();
+            [100.0, 101.0].iter().fold((0.0, 0.0), |(re, im), f| {
+                let w = 2.0 * std::f64::consts::PI * f * t;
+                (re + decay * w.cos(), im + decay * w.sin())
+            })
+        };
+        let points: Vec<(f64, f64)> = (0..n).map(fid).collect();
+        let mut s = SpectrumData {
+            real: points.iter().map(|p| p.0).collect(),
+            imag: points.iter().map(|p| p.1).collect(),
+            axes: vec![AxisParams {
+                num_points: n,
+                spectral_width_hz: sw,
+                observe_freq_mhz: 400.0,
+                reference_ppm: 5.0,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        fourier_transform(&mut s, true, &mut ReproLog::new());
+        let original = s.real.clone();
+
+        let top = (0..n).max_by(|&a, &b| s.real[a].total_cmp(&s.real[b])).unwrap();
+        let (lo, hi) = (s.axes[0].index_to_ppm(top + 60), s.axes[0].index_to_ppm(top - 60));
+        let maxima = |ys: Vec<f64>| {
+            let max = ys.iter().cloned().fold(f64::MIN, f64::max);
+            ys.windows(3).filter(|w| w[1] > w[0] && w[1] > w[2] && w[1] > 0.3 * max).count()
+        };
+        let region: Vec<f64> = (top - 60..=top + 60).map(|i| original[i]).collect();
+        assert_eq!(maxima(region), 1);
+
+        let preview = lorentz_gauss_preview(&s, 2.0, 0.5, lo, hi).unwrap();
+        assert!(preview.len() > 100 && preview.len() < 130);
+        assert_eq!(maxima(preview.iter().map(|p| p[1]).collect()), 2);
+        // Preview only: the spectrum itself is untouched
+        assert_eq!(s.real, original);
+        // Without enhancement the round trip reproduces the spectrum
+        let same = lorentz_gauss_preview(&s, 0.0, 0.0, lo, hi).unwrap();
+        assert!((same[60][1] - original[top]).abs() < 1e-6 * original[top].abs());
+    }
+
+    #[test]
+    fn test_t1_noise_suppression_removes_ridge() {
+        // 64×32 magnitude spectrum: noise floor 1, a t1 ridge of +5 in
+        // column 10 and a real cross peak of 100 at (20, 10)
+        let base: Vec<Vec<f64>> = (0..64)
+            .map(|i| (0..32).map(|j| 1.0 + ((i * 7 + j * 3) % 5) as f64 * 0.1 + if j == 10 { 5.0 } else { 0.0 }).collect())
+            .collect();
+        let mut s = SpectrumData {
+            data_2d: base,
+            dimensionality: Dimensionality::TwoD,
+            is_frequency_domain: true,
+            ..Default::default()
+        };
+        s.data_2d[20][10] = 100.0;
+
+        for method in [T1NoiseMethod::ColumnMedian, T1NoiseMethod::ReferenceRows] {
+            let mut t = s.clone();
+            let mut log = ReproLog::new();
+            suppress_t1_noise(&mut t, method, 1.0, &mut log);
+            let ridge: f64 = (0..64).filter(|&i| i != 20).map(|i| t.data_2d[i][10]).sum::<f64>() / 63.0;
+            let floor: f64 = (0..64).map(|i| t.data_2d[i][5]).sum::<f64>() / 64.0;
+            assert!((ridge - floor).abs() < 0.2, "{:?}: ridge {} floor {}", method, ridge, floor);
+            assert!(t.data_2d[20][10] > 90.0);
+            assert!(log.entries[0].operation.contains("cosmetic"));
+        }
+
+        // Strength scales the subtraction; 1D data is left alone
+        let mut half = s.clone();
+        suppress_t1_noise(&mut half, T1NoiseMethod::ColumnMedian, 0.5, &mut ReproLog::new());
+        assert!((s.data_2d[0][10] - half.data_2d[0][10] - 3.0).abs() < 0.2);
+        let mut one_d = SpectrumData { real: vec![1.0; 8], is_frequency_domain: true, ..Default::default() };
+        let mut log = ReproLog::new();
+        suppress_t1_noise(&mut one_d, T1NoiseMethod::ColumnMedian, 1.0, &mut log);
+        assert!(log.entries.is_empty());
+    }
+
+    #[test]
+    fn test_cross_peak_assignment_transfer() {
+        // HSQC-like: F2 1H 10→0 ppm (0.1 ppm/pt), F1 13C 160→0 ppm (2.5 ppm/pt)
+        let mut data = vec![vec![0.0; 100]; 64];
+        data[20][27] = 100.0; // 7.3 / 110
+        data[40][70] = -80.0; // 3.0 / 60, negative (edited HSQC)
+        data[10][50] = 30.0; // 5.0 / 135
+        data[10][51] = 20.0; // shoulder, not a maximum
+        let s = SpectrumData {
+            data_2d: data,
+            dimensionality: Dimensionality::TwoD,
+            is_frequency_domain: true,
+            axes: vec![
+                AxisParams { num_points: 100, spectral_width_hz: 4000.0, reference_ppm: 10.0, ..Default::default() },
+                AxisParams {
+                    nucleus: Nucleus::C13,
+                    num_points: 64,
+                    spectral_width_hz: 16000.0,
+                    observe_freq_mhz: 100.0,
+                    reference_ppm: 160.0,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let mut cross = pick_cross_peaks(&s, 0.1);
+        assert_eq!(cross.len(), 3);
+        assert!((cross[0].f2_ppm - 7.3).abs() < 1e-9 && (cross[0].f1_ppm - 110.0).abs() < 1e-9);
+        assert_eq!(cross[2].intensity, -80.0);
+        assert_eq!(pick_cross_peaks(&s, 0.5).len(), 2);
+
+        let multiplet = |center_ppm: f64, label: &str| Multiplet {
+            center_ppm,
+            j_hz: 0.0,
+            num_lines: 1,
+            label: label.to_string(),
+            peaks: vec![],
+            couplings: vec![],
+        };
+        let assigned = assignments_from_1d(&[], &[multiplet(7.28, "d"), multiplet(3.01, "s"), multiplet(1.0, "t")]);
+        cross[1].label = "H-5".to_string();
+        assert_eq!(transfer_assignments(&mut cross, &assigned, 0.05), 2);
+        assert_eq!(cross[0].label, "7.28 d");
+        assert_eq!(cross[1].label, "H-5"); // user label kept
+        assert_eq!(cross[2].label, "3.01 s");
+
+        // Without multiplets the picked peaks are the assignments
+        let from_peaks = assignments_from_1d(&[[5.02, 1.0]], &[]);
+        assert_eq!(from_peaks, vec![(5.02, "5.02".to_string())]);
+    }
+
+    #[test]
+    fn test_impurity_quantification_against_reference() {
+        use crate::pipeline::library::ReferenceSpectrum;
+
+        // 10→0 ppm at 0.005 ppm/pt
+        let axis = AxisParams { num_points: 2000, spectral_width_hz: 4000.0, reference_ppm: 10.0, ..Default::default() };
+        let lorentz = |ppm: f64, centre: f64| 1.0 / (1.0 + ((ppm - centre) / 0.01).powi(2));
+        let spectrum = |f: &dyn Fn(f64) -> f64| SpectrumData {
+            is_frequency_domain: true,
+            axes: vec![axis.clone()],
+            real: (0..2000).map(|i| f(axis.index_to_ppm(i))).collect(),
+            ..Default::default()
+        };
+        // Acetone-like singlet as the library reference
+        let reference = ReferenceSpectrum::from_spectrum("acetone", &spectrum(&|p| 40.0 * lorentz(p, 2.1))).unwrap();
+        // Main compound: 2H at 7.0; impurity: 6 H-units at 2.1 on a small offset
+        let sample = spectrum(&|p| 100.0 * lorentz(p, 7.0) + 3.0 * lorentz(p, 2.1) + if p < 4.0 { 0.5 } else { 0.0 });
+        let main = integrate_region(&sample, 6.5, 7.5);
+
+        let fit = quantify_impurity(&sample, &reference.points, 2.6, 1.6, 6.0, main, 2.0).unwrap();
+        // The sampled top sits just off the line centre, so the scale is ~3
+        assert!((fit.scale - 3.0).abs() < 1e-4, "scale {}", fit.scale);
+        // The offset also soaks up the tail of the 7.0 ppm line
+        assert!((fit.offset - 0.5).abs() < 1e-3, "offset {}", fit.offset);
+        assert!(fit.relative_residual < 1e-4);
+        // (3/6) / (3/6 + 100/2) per proton
+        assert!((fit.mol_percent - 0.5 / 50.5 * 100.0).abs() < 1e-3, "mol% {}", fit.mol_percent);
+        assert!(!fit.trace.is_empty());
+
+        // No reference data in the region, or a nonsensical H count
+        assert!(quantify_impurity(&sample, &reference.points, 20.0, 21.0, 6.0, main, 2.0).is_none());
+        assert!(quantify_impurity(&sample, &reference.points, 2.6, 1.6, 0.0, main, 2.0).is_none());
+    }
+
+    #[test]
+    fn test_magnitude_2d_combines_quadrants() {
+        let mut s = SpectrumData {
+            dimensionality: Dimensionality::TwoD,
+            is_frequency_domain: true,
+            data_2d: vec![vec![1.0, 0.0], vec![3.0, -2.0]],
+            data_2d_imag: vec![vec![1.0, 0.0], vec![4.0, 0.0]],
+            f1_quadrants: Some(F1Quadrants { ir: vec![vec![1.0, 0.0]; 2], ii: vec![vec![1.0, 0.0]; 2] }),
+            ..Default::default()
+        };
+        assert!(has_2d_imaginary(&s));
+        let mut log = ReproLog::new();
+        magnitude_2d(&mut s, &mut log);
+        assert_eq!(s.data_2d, vec![vec![2.0, 0.0], vec![(9.0f64 + 16.0 + 1.0 + 1.0).sqrt(), 2.0]]);
+        assert!(!has_2d_imaginary(&s));
+        assert_eq!(log.len(), 1);
+        // Nothing left to combine: no-op
+        magnitude_2d(&mut s, &mut log);
+        assert_eq!(log.len(), 1);
+    }
+
+    #[test]
+    fn test_ft2d_modes_keep_or_combine_quadrature() {
+        // One decaying complex exponential along both dimensions
+        let (rows, cols) = (8, 16);
+        let signal = |r: usize, c: usize| {
+            let (decay, phase) = ((-0.1 * (r + c) as f64).exp(), 0.7 * c as f64 + 0.4 * r as f64);
+            (decay * phase.cos(), decay * phase.sin())
+        };
+        let fid = SpectrumData {
+            dimensionality: Dimensionality::TwoD,
+            data_2d: (0..rows).map(|r| (0..cols).map(|c| signal(r, c).0).collect()).collect(),
+            data_2d_imag: (0..rows).map(|r| (0..cols).map(|c| signal(r, c).1).collect()).collect(),
+            ..Default::default()
+        };
+        assert!(has_2d_imaginary(&fid));
+
+        let mut log = ReproLog::new();
+        let mut phased = fid.clone();
+        fourier_transform_2d(&mut phased, Ft2dMode::PhaseSensitive, &mut log);
+        let mut magnitude = fid.clone();
+        fourier_transform_2d(&mut magnitude, Ft2dMode::Magnitude, &mut log);
+        assert!(phased.is_frequency_domain && magnitude.is_frequency_domain);
+        assert_eq!((phased.data_2d_imag.len(), magnitude.data_2d_imag.len()), (rows, 0));
+        for r in 0..rows {
+            for c in 0..cols {
+                let (re, im) = (phased.data_2d[r][c], phased.data_2d_imag[r][c]);
+                assert!((magnitude.data_2d[r][c] - re.hypot(im)).abs() < 1e-9);
+            }
+        }
+        assert!(!has_2d_imaginary(&magnitude));
+        // All-zero imaginary rows (real data from a converter) do not count
+        let real_only = SpectrumData { data_2d_imag: vec![vec![0.0; cols]; rows], ..fid };
+        assert!(!has_2d_imaginary(&real_only));
+
+        assert_eq!(Ft2dMode::default_for(&ExperimentType::Cosy), Ft2dMode::Magnitude);
+        assert_eq!(Ft2dMode::default_for(&ExperimentType::Hsqc), Ft2dMode::PhaseSensitive);
+    }
+
+    #[test]
+    fn test_linewidth_of_lorentzian() {
+        // 16384 points over 4000 Hz at 400 MHz; a 1.5 Hz wide line at 6.0 ppm
+        let axis = AxisParams {
+            num_points: 16384,
+            spectral_width_hz: 4000.0,
+            observe_freq_mhz: 400.0,
+            reference_ppm: 10.0,
+            ..Default::default()
+        };
+        let hz_per_point = 4000.0 / 16384.0;
+        let center = axis.ppm_to_position(6.0).unwrap();
+        let gamma = 0.75 / hz_per_point;
+        let mut s = SpectrumData {
+            is_frequency_domain: true,
+            real: (0..16384).map(|i| 50.0 / (1.0 + ((i as f64 - center) / gamma).powi(2))).collect(),
+            axes: vec![axis],
+            ..Default::default()
+        };
+
+        // Clicking on the flank still measures the whole line; with only six
+        // points across it, linear interpolation is good to ~2%
+        let lw = measure_linewidth(&s, 6.002).unwrap();
+        assert!((lw.peak[0] - 6.0).abs() < 1e-4);
+        assert!((lw.peak[1] - 50.0).abs() < 2.0);
+        assert!((lw.fwhm_hz - 1.5).abs() < 0.03, "FWHM {} Hz", lw.fwhm_hz);
+        assert!((lw.fwhm_ppm() - 1.5 / 400.0).abs() < 1e-4);
+        assert!(lw.hi_ppm > 6.0 && lw.lo_ppm < 6.0);
+
+        // Negative peaks are measured at their (negative) half height
+        s.real.iter_mut().for_each(|v| *v = -*v);
+        let neg = measure_linewidth(&s, 6.0).unwrap();
+        assert!((neg.fwhm_hz - lw.fwhm_hz).abs() < 1e-9);
+        assert!(neg.half_height() < 0.0);
+
+        // A line cut off by the edge of the window has no FWHM
+        s.real = (0..16384).map(|i| 1.0 / (1.0 + (i as f64 / gamma).powi(2))).collect();
+        assert!(measure_linewidth(&s, 10.0).is_none());
+    }
+
+    #[test]
+    fn test_reference_to_residual_solvent() {
+        // 400 MHz 1H, 12 ppm window from 11 ppm; a small CHCl3 line that the
+        // spectrometer put at 7.30 ppm next to a tall sample line at 1.00 ppm
+        let axis = AxisParams {
+            num_points: 8192,
+            spectral_width_hz: 4800.0,
+            observe_freq_mhz: 400.0,
+            reference_ppm: 11.0,
+            ..Default::default()
+        };
+        let line = |axis: &AxisParams, ppm: f64, height: f64, width_pts: f64| {
+            let center = axis.ppm_to_position(ppm).unwrap();
+            (0..axis.num_points)
+                .map(|i| height / (1.0 + ((i as f64 - center) / width_pts).powi(2)))
+                .collect::<Vec<f64>>()
+        };
+        let solvent = line(&axis, 7.30, 10.0, 2.0);
+        let sample = line(&axis, 1.00, 100.0, 2.0);
+        let mut s = SpectrumData {
+            is_frequency_domain: true,
+            solvent: "CDCl3".to_string(),
+            real: solvent.iter().zip(&sample).map(|(a, b)| a + b + 0.01).collect(),
+            axes: vec![axis],
+            ..Default::default()
+        };
+
+        let mut log = ReproLog::new();
+        let r = reference_to_solvent(&mut s, &mut log).unwrap();
+        assert_eq!(r.solvent, "CDCl3");
+        assert!((r.found_ppm - 7.30).abs() < 1e-3, "found {}", r.found_ppm);
+        assert!((r.shift_ppm + 0.04).abs() < 1e-3);
+        assert!((s.axes[0].reference_ppm - (11.0 + r.shift_ppm)).abs() < 1e-12);
+        assert_eq!(log.len(), 1);
+        // Referencing again finds the line where it belongs
+        assert!(find_solvent_reference(&s).unwrap().shift_ppm.abs() < 1e-3);
+
+        // 13C: the centre of the CDCl3 triplet, not whichever line is tallest
+        let c13 = AxisParams {
+            nucleus: Nucleus::C13,
+            num_points: 32768,
+            spectral_width_hz: 24000.0,
+            observe_freq_mhz: 100.0,
+            reference_ppm: 230.0,
+            ..Default::default()
+        };
+        let triplet: Vec<f64> = [(77.16, 9.0), (77.48, 10.0), (76.84, 9.5)]
+            .iter()
+            .map(|&(ppm, h)| line(&c13, ppm + 0.5, h, 1.5))
+            .fold(vec![0.01; 32768], |acc, l| acc.iter().zip(&l).map(|(a, b)| a + b).collect());
+        let carbon = SpectrumData {
+            is_frequency_domain: true,
+            solvent: "CHLOROFORM-D".to_string(),
+            real: triplet,
+            axes: vec![c13],
+            ..Default::default()
+        };
+        let r = find_solvent_reference(&carbon).unwrap();
+        assert!((r.shift_ppm + 0.5).abs() < 1e-3, "shift {}", r.shift_ppm);
+
+        // Unknown solvent, unsupported nucleus or no signal: left alone
+        s.solvent = "unknown".to_string();
+        assert!(find_solvent_reference(&s).is_none());
+        s.solvent = "D2O".to_string();
+        assert!(find_solvent_reference(&s).is_none());
+        let mut flat = SpectrumData { real: vec![1.0; 8192], ..carbon };
+        flat.axes[0].nucleus = Nucleus::C13;
+        assert!(find_solvent_reference(&flat).is_none());
+    }
+
+    #[test]
+    fn test_compare_peak_lists() {
+        // A reaction: the 4.10 ppm reactant signal is gone, a product
+        // appears at 3.60 ppm and the second run is referenced 0.03 ppm low
+        let before = [[7.26, 10.0], [4.10, 20.0], [2.00, 30.0], [1.20, 30.0]];
+        let after = [[7.23, 5.0], [3.57, 40.0], [1.97, 15.0], [1.17, 30.0]];
+
+        assert!((align_peak_lists(&before, &after, 0.01, 0.2) - 0.03).abs() < 1e-12);
+        assert_eq!(align_peak_lists(&before, &[], 0.01, 0.2), 0.0);
+
+        let rows = compare_peak_lists(&before, &after, 0.03, 0.01);
+        let changes: Vec<_> = rows.iter().map(|r| (r.change, (r.ppm * 100.0).round() / 100.0)).collect();
+        assert_eq!(
+            changes,
+            [
+                (PeakChange::Common, 7.26),
+                (PeakChange::Missing, 4.10),
+                (PeakChange::New, 3.60),
+                (PeakChange::Common, 2.00),
+                (PeakChange::Common, 1.20),
+            ]
+        );
+        // Normalised to the common peaks (70 before, 50 after)
+        let ratio = rows[4].intensity_ratio.unwrap();
+        assert!((ratio - (30.0 / 50.0) / (30.0 / 70.0)).abs() < 1e-12);
+        assert_eq!(rows[2].compared, Some([3.57, 40.0]));
+        assert!(rows[1].intensity_ratio.is_none());
+
+        // Without alignment nothing lines up
+        let unaligned = compare_peak_lists(&before, &after, 0.0, 0.01);
+        assert!(unaligned.iter().all(|r| r.change != PeakChange::Common));
+    }
+
+    #[test]
+    fn test_sign_aware_peak_picking() {
+        // DEPT-135: CH/CH3 up, a taller CH2 down, small noise wiggles
+        let n = 128;
+        let spectrum = SpectrumData {
+            real: (0..n)
+                .map(|i| match i {
+                    30 => 8.0,
+                    60 => -12.0,
+                    90 => 4.0,
+                    _ => if i % 2 == 0 { 0.05 } else { -0.05 },
+                })
+                .collect(),
+            is_frequency_domain: true,
+            axes: vec![AxisParams {
+                num_points: n,
+                spectral_width_hz: 12800.0,
+                observe_freq_mhz: 100.0,
+                reference_ppm: 128.0,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let ppm = |i: usize| spectrum.axes[0].index_to_ppm(i);
+
+        let positive = detect_peaks(&spectrum, 0.1, 1, PeakSign::Positive);
+        assert_eq!(positive, vec![[ppm(30), 8.0], [ppm(90), 4.0]]);
+        let negative = detect_peaks(&spectrum, 0.1, 1, PeakSign::Negative);
+        assert_eq!(negative, vec![[ppm(60), -12.0]]);
+        // Both: the threshold follows the largest |intensity|, so the
+        // 4.0 line (33% of 12) survives at 30% but not at 40%
+        let both = detect_peaks(&spectrum, 0.3, 1, PeakSign::Both);
+        assert_eq!(both.iter().map(|p| p[1]).collect::<Vec<_>>(), vec![8.0, -12.0, 4.0]);
+        assert_eq!(detect_peaks(&spectrum, 0.4, 1, PeakSign::Both).len(), 2);
+
+        assert_eq!(PeakSign::default_for(&ExperimentType::Dept135), PeakSign::Both);
+        assert_eq!(PeakSign::default_for(&ExperimentType::Other("APT".into())), PeakSign::Both);
+        assert_eq!(PeakSign::default_for(&ExperimentType::Carbon), PeakSign::Positive);
+    }
+
+    #[test]
+    fn test_integral_subtracts_local_baseline() {
+        // Two lines of area 2:1 on a tilted, offset baseline
+        let n = 1000;
+        let line = |i: usize, c: f64| 1.0 / (1.0 + ((i as f64 - c) / 3.0).powi(2));
+        let axis = AxisParams {
+            num_points: n,
+            spectral_width_hz: 4000.0,
+            observe_freq_mhz: 400.0,
+            reference_ppm: 10.0,
+            ..Default::default()
+        };
+        let mut spectrum = SpectrumData {
+            real: (0..n).map(|i| 2.0 * line(i, 300.0) + line(i, 700.0)).collect(),
+            is_frequency_domain: true,
+            axes: vec![axis],
+            ..Default::default()
+        };
+        let region = |c: usize| (spectrum.axes[0].index_to_ppm(c - 150), spectrum.axes[0].index_to_ppm(c + 150));
+        let (a, b) = (region(300), region(700));
+        let clean = [a, b].map(|(lo, hi)| integrate_region(&spectrum, lo, hi));
+
+        for (i, v) in spectrum.real.iter_mut().enumerate() {
+            *v += 0.05 + 1e-4 * i as f64;
+        }
+        let raw = [a, b].map(|(lo, hi)| integrate_region(&spectrum, lo, hi));
+        let fixed = [a, b].map(|(lo, hi)| {
+            integrate_region_corrected(&spectrum, lo, hi, IntegralBaseline::TwoPoint)
+        });
+        // The offset swamps the raw ratio; the local line restores it
+        assert!((raw[0] / raw[1] - 2.0).abs() > 0.3);
+        assert!((fixed[0] / fixed[1] - 2.0).abs() < 0.05, "ratio {}", fixed[0] / fixed[1]);
+        assert!((fixed[0] - clean[0]).abs() / clean[0] < 0.1);
+        let none = integrate_region_corrected(&spectrum, a.0, a.1, IntegralBaseline::None);
+        assert_eq!(none, raw[0]);
+    }
+
+    #[test]
+    fn test_peak_positions_refined_between_points() {
+        // Doublet with J = 7.3 Hz at 0.5 Hz/point: the lines fall between points
+        let axis = AxisParams {
+            num_points: 2048,
+            spectral_width_hz: 1024.0,
+            observe_freq_mhz: 400.0,
+            reference_ppm: 3.0,
+            ..Default::default()
+        };
+        let hz_per_pt = 0.5;
+        let (c1, c2) = (1000.3, 1000.3 + 7.3 / hz_per_pt);
+        let lorentz = |i: f64, c: f64| 1.0 / (1.0 + ((i - c) / 2.0).powi(2));
+        let spectrum = SpectrumData {
+            real: (0..2048).map(|i| lorentz(i as f64, c1) + lorentz(i as f64, c2)).collect(),
+            is_frequency_domain: true,
+            axes: vec![axis.clone()],
+            ..Default::default()
+        };
+        let ppm_at = |pos: f64| axis.reference_ppm - pos / 2048.0 * (1024.0 / 400.0);
+
+        let peaks = detect_peaks(&spectrum, 0.5, 2, PeakSign::Positive);
+        assert_eq!(peaks.len(), 2);
+        let point_ppm = hz_per_pt / 400.0;
+        for (peak, c) in peaks.iter().zip([c1, c2]) {
+            assert!((peak[0] - ppm_at(c)).abs() < 0.1 * point_ppm, "{} vs {}", peak[0], ppm_at(c));
+        }
+        let j_hz = (peaks[0][0] - peaks[1][0]) * 400.0;
+        assert!((j_hz - 7.3).abs() < 0.05, "J = {}", j_hz);
+
+        assert_eq!(parabolic_offset(&[0.0, 1.0, 0.0], 1), 0.0);
+        assert_eq!(parabolic_offset(&[1.0, 1.0, 1.0], 1), 0.0);
+        assert_eq!(parabolic_offset(&[1.0, 2.0], 1), 0.0);
+        assert!(parabolic_offset(&[0.5, 1.0, 0.9], 1) > 0.0);
+    }
+
+    #[test]
+    fn test_multiplet_couplings_from_line_spacings() {
+        // Three pairs of lines 2.0 Hz apart, the pairs 5.1 Hz apart (400 MHz)
+        let hz = [0.0, 2.02, 7.08, 9.1, 14.2, 16.18];
+        let peaks: Vec<[f64; 2]> = hz.iter().map(|h| [7.0 + h / 400.0, 1.0]).collect();
+        let couplings = multiplet_couplings(&peaks, 400.0, J_CONSISTENCY_HZ);
+        assert_eq!(couplings.len(), 2);
+        assert_eq!(couplings[0].count, 2);
+        assert!((couplings[0].j_hz - 5.08).abs() < 1e-6, "{}", couplings[0]);
+        assert_eq!(couplings[1].count, 3);
+        assert!((couplings[1].j_hz - 2.0).abs() < 0.01 && couplings[1].sd_hz > 0.0);
+
+        let mut multiplet = Multiplet {
+            center_ppm: 7.02,
+            j_hz: 3.2,
+            num_lines: 6,
+            label: "m".to_string(),
+            peaks,
+            couplings: vec![],
+        };
+        assert_eq!(multiplet.j_text().as_deref(), Some("3.2"));
+        multiplet.couplings = couplings;
+        assert_eq!(multiplet.j_text().as_deref(), Some("5.1, 2.0"));
+
+        // A singlet has no spacings
+        assert!(multiplet_couplings(&[[1.0, 1.0]], 400.0, 0.5).is_empty());
+    }
+
+    #[test]
+    fn test_multiplet_detection_parameters() {
+        // Doublet (7 Hz) with a small impurity line 5 Hz beside it, and a
+        // triplet with 14 Hz spacings (400 MHz)
+        let at = |hz: f64, intensity: f64| [2.0 + hz / 400.0, intensity];
+        let peaks = [at(0.0, 100.0), at(7.0, 100.0), at(12.0, 5.0), at(400.0, 50.0), at(414.0, 100.0), at(428.0, 50.0)];
+
+        let defaults = detect_multiplets(&peaks, &MultipletParams::default(), 400.0);
+        let labels: Vec<&str> = defaults.iter().map(|m| m.label.as_str()).collect();
+        assert_eq!(labels, ["t", "s", "d"]);
+
+        let no_ratio = MultipletParams { max_intensity_ratio: f64::INFINITY, ..Default::default() };
+        let labels: Vec<String> =
+            detect_multiplets(&peaks, &no_ratio, 400.0).into_iter().map(|m| m.label).collect();
+        assert_eq!(labels, ["t", "t"]);
+
+        let narrow = MultipletParams { max_j_hz: 10.0, min_lines: 2, ..Default::default() };
+        let found = detect_multiplets(&peaks, &narrow, 400.0);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].label, "d");
+    }
+
+    #[test]
+    fn test_cross_peak_list_export() {
+        let peak = |f2_ppm: f64, f1_ppm: f64, label: &str| CrossPeak { f2_ppm, f1_ppm, intensity: 1.5e5, label: label.to_string(), volume: None };
+        let peaks = [peak(7.3, 110.0, "7.28 d"), peak(3.0, 60.25, "H5-C5"), peak(5.0, 135.0, "")];
+
+        // Sparky: w1 = F1, w2 = F2, whitespace-free assignments
+        let sparky = sparky_peak_list(&peaks);
+        let lines: Vec<&str> = sparky.lines().collect();
+        assert_eq!(lines[0].split_whitespace().collect::<Vec<_>>(), ["Assignment", "w1", "w2", "Data", "Height"]);
+        assert!(lines[1].is_empty());
+        assert_eq!(lines[2].split_whitespace().collect::<Vec<_>>(), ["?-7.28_d", "110.000", "7.300", "1.5000e5"]);
+        assert_eq!(lines[3].split_whitespace().next(), Some("H5-C5"));
+        assert_eq!(lines[4].split_whitespace().next(), Some("?-?"));
+
+        // XEASY: dimension 1 = F2, atom numbers 0, label as a comment
+        let xeasy = xeasy_peak_list(&peaks, &Nucleus::H1, &Nucleus::C13);
+        let lines: Vec<&str> = xeasy.lines().collect();
+        assert_eq!(&lines[..3], ["# Number of dimensions 2", "# INAME 1 H", "# INAME 2 C"]);
+        let row: Vec<&str> = lines[4].split_whitespace().collect();
+        assert_eq!(&row[..5], ["2", "3.000", "60.250", "1", "U"]);
+        assert_eq!(&row[7..], ["e", "0", "0", "0", "#", "H5-C5"]);
+        assert!(!lines[5].contains('#'));
+
+        // Integrated peaks add a Sparky Volume column and fill the XEASY one
+        let mut integrated = peaks.clone();
+        integrated[0].volume = Some(2.0e6);
+        let sparky = sparky_peak_list(&integrated);
+        assert!(sparky.lines().next().unwrap().ends_with("Volume"));
+        assert!(sparky.lines().nth(2).unwrap().ends_with("2.0000e6"));
+        let xeasy = xeasy_peak_list(&integrated, &Nucleus::H1, &Nucleus::C13);
+        assert_eq!(xeasy.lines().nth(3).unwrap().split_whitespace().nth(5), Some("2.0000e6"));
+    }
+
+    #[test]
+    fn test_integral_uncertainty_from_noise() {
+        // Gaussian noise (σ = 0.01, Box–Muller over an LCG) under lines of area 2:1
+        let mut seed = 12345u64;
+        let mut uniform = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((seed >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+        };
+        let n = 8000;
+        let line = |i: usize, c: f64| 1.0 / (1.0 + ((i as f64 - c) / 3.0).powi(2));
+        let spectrum = SpectrumData {
+            real: (0..n)
+                .map(|i| {
+                    let noise = 0.01 * (-2.0 * uniform().ln()).sqrt() * (2.0 * std::f64::consts::PI * uniform()).cos();
+                    2.0 * line(i, 2000.0) + line(i, 6000.0) + noise
+                })
+                .collect(),
+            is_frequency_domain: true,
+            axes: vec![AxisParams {
+                num_points: n,
+                spectral_width_hz: 8000.0,
+                observe_freq_mhz: 400.0,
+                reference_ppm: 12.0,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let noise = noise_rms(&spectrum.real);
+        assert!((noise - 0.01).abs() < 0.001, "noise {}", noise);
+
+        // A raw sum over N points has σ√N; the two-point line adds the edge noise
+        assert!((integral_sigma(0.01, 100, IntegralBaseline::None) - 0.1).abs() < 1e-12);
+        assert!(
+            integral_sigma(0.01, 100, IntegralBaseline::TwoPoint)
+                > integral_sigma(0.01, 100, IntegralBaseline::None)
+        );
+
+        let axis = &spectrum.axes[0];
+        let region = |c: usize| {
+            let (lo, hi) = (axis.index_to_ppm(c + 100), axis.index_to_ppm(c - 100));
+            (lo, hi, integrate_region_corrected(&spectrum, lo, hi, IntegralBaseline::TwoPoint))
+        };
+        let integrations = [region(2000), region(6000)];
+        let errors = integral_uncertainties(&spectrum, &integrations, IntegralBaseline::TwoPoint, 2.0);
+        let expected = integral_sigma(noise, 201, IntegralBaseline::TwoPoint);
+        assert!((errors[0].sigma - expected).abs() / expected < 0.02);
+        // The reference region defines the scale; the other carries both errors
+        assert_eq!(errors[0].h_sigma, 0.0);
+        let h = integrations[1].2 / integrations[0].2 * 2.0;
+        let rel = ((errors[1].sigma / integrations[1].2).powi(2) + (errors[0].sigma / integrations[0].2).powi(2)).sqrt();
+        assert!((errors[1].h_sigma - h * rel).abs() < 1e-12);
+        assert!(errors[1].h_sigma > 0.0 && errors[1].h_sigma < 0.1);
+        assert_eq!(format_h_count(1.0, 0.034), "1.00 ± 0.03H");
+        assert_eq!(format_h_count(2.0, 0.0), "2.00H");
+    }
+
+    #[test]
+    fn test_cross_peak_volume_subtracts_plane() {
+        // F2 0.1 ppm/pt, F1 2.5 ppm/pt; a 3×3 peak of volume 180 at
+        // (7.3, 110) on a tilted baseline
+        let mut data: Vec<Vec<f64>> =
+            (0..64).map(|r| (0..100).map(|c| 5.0 + 0.5 * c as f64 + 0.2 * r as f64).collect()).collect();
+        for (r, row) in data.iter_mut().enumerate().take(22).skip(19) {
+            for (c, v) in row.iter_mut().enumerate().take(29).skip(26) {
+                *v += if (r, c) == (20, 27) { 100.0 } else { 10.0 };
+            }
+        }
+        let s = SpectrumData {
+            data_2d: data,
+            dimensionality: Dimensionality::TwoD,
+            is_frequency_domain: true,
+            axes: vec![
+                AxisParams { num_points: 100, spectral_width_hz: 4000.0, reference_ppm: 10.0, ..Default::default() },
+                AxisParams {
+                    nucleus: Nucleus::C13,
+                    num_points: 64,
+                    spectral_width_hz: 16000.0,
+                    observe_freq_mhz: 100.0,
+                    reference_ppm: 160.0,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Both shapes recover the peak alone; the ellipse leaves out the
+        // corners of its 5×5 bounding box
+        for shape in [VolumeShape::Box, VolumeShape::Ellipse] {
+            let region = VolumeRegion { shape, f2_half_width: 0.25, f1_half_width: 6.25 };
+            let volume = cross_peak_volume(&s, 7.3, 110.0, &region).unwrap();
+            assert!((volume - 180.0).abs() < 1e-6, "{}: {}", shape, volume);
+        }
+        let ellipse = VolumeRegion::default();
+        assert!(ellipse.contains(0.02, 0.0) && !ellipse.contains(0.025, 0.25));
+        assert_eq!(ellipse.outline(7.3, 110.0).first(), ellipse.outline(7.3, 110.0).last());
+
+        // A region that misses every point has no volume
+        let tiny = VolumeRegion { shape: VolumeShape::Box, f2_half_width: 0.01, f1_half_width: 0.1 };
+        assert_eq!(cross_peak_volume(&s, 7.35, 110.0, &tiny), None);
+    }
+
+    #[test]
+    fn test_inverse_ft_returns_to_fid() {
+        let fid: Vec<(f64, f64)> = (0..512)
+            .map(|i| {
+                let t = i as f64;
+                let a = (-t / 100.0).exp();
+                (a * (0.4 * t).cos(), a * (0.4 * t).sin())
+            })
+            .collect();
+        let mut s = SpectrumData {
+            real: fid.iter().map(|p| p.0).collect(),
+            imag: fid.iter().map(|p| p.1).collect(),
+            ..Default::default()
+        };
+        let mut log = ReproLog::new();
+        fourier_transform(&mut s, true, &mut log);
+        assert!(s.has_imaginary());
+        inverse_fourier_transform(&mut s, &mut log);
+        assert!(!s.is_frequency_domain);
+        for (i, (re, im)) in fid.iter().enumerate() {
+            assert!((s.real[i] - re).abs() < 1e-9 && (s.imag[i] - im).abs() < 1e-9);
+        }
+        assert_eq!(log.entries[1].nmrpipe_command, "nmrPipe -fn FT -inv");
+
+        // A real-only processed spectrum (Bruker 1r alone): the Hilbert
+        // transform rebuilds the causal half of a twofold zero-filled FID
+        s.real.resize(1024, 0.0);
+        s.imag.resize(1024, 0.0);
+        fourier_transform(&mut s, true, &mut log);
+        s.imag.clear();
+        s.provenance = Provenance::on_load(true);
+        assert!(!s.has_imaginary());
+        inverse_fourier_transform(&mut s, &mut log);
+        assert_eq!((s.real.len(), s.axes[0].num_points), (512, 512));
+        for (i, (re, im)) in fid.iter().enumerate() {
+            assert!((s.real[i] - re).abs() < 1e-9 && (s.imag[i] - im).abs() < 1e-9);
+        }
+        assert!(log.entries[3].description.contains("Hilbert"));
+        assert!(log.entries[3].description.contains("vendor-processed"));
+        assert!(log.entries[3].nmrpipe_command.starts_with("nmrPipe -fn HT"));
+
+        // 2D: exact round trip with the imaginary part, first quadrant without
+        let (rows, cols) = (32, 64);
+        let point = |r: usize, c: usize| {
+            let (t1, t2) = (r as f64, c as f64);
+            let a = (-t1 / 20.0 - t2 / 40.0).exp();
+            (a * (0.7 * t1 + 0.3 * t2).cos(), a * (0.7 * t1 + 0.3 * t2).sin())
+        };
+        let grid = |f: &dyn Fn(usize, usize) -> f64| -> Vec<Vec<f64>> {
+            (0..rows).map(|r| (0..cols).map(|c| if r < rows / 2 && c < cols / 2 { f(r, c) } else { 0.0 }).collect()).collect()
+        };
+        let (re, im) = (grid(&|r, c| point(r, c).0), grid(&|r, c| point(r, c).1));
+        let mut s2 = SpectrumData {
+            dimensionality: crate::data::spectrum::Dimensionality::TwoD,
+            axes: vec![Default::default(), Default::default()],
+            data_2d: re.clone(),
+            data_2d_imag: im.clone(),
+            ..Default::default()
+        };
+        fourier_transform_2d(&mut s2, Ft2dMode::PhaseSensitive, &mut log);
+        inverse_fourier_transform(&mut s2, &mut log);
+        assert!(!s2.is_frequency_domain);
+        for (got, want) in s2.data_2d.iter().flatten().zip(re.iter().flatten()) {
+            assert!((got - want).abs() < 1e-9);
+        }
+        fourier_transform_2d(&mut s2, Ft2dMode::PhaseSensitive, &mut log);
+        s2.data_2d_imag.clear();
+        inverse_fourier_transform(&mut s2, &mut log);
+        assert_eq!((s2.data_2d.len(), s2.data_2d[0].len()), (rows / 2, cols / 2));
+        assert_eq!((s2.axes[0].num_points, s2.axes[1].num_points), (cols / 2, rows / 2));
+        for (r, (re_row, im_row)) in s2.data_2d.iter().zip(&s2.data_2d_imag).enumerate() {
+            for (c, (got_re, got_im)) in re_row.iter().zip(im_row).enumerate() {
+                let (want_re, want_im) = point(r, c);
+                assert!((got_re - want_re).abs() < 1e-9 && (got_im - want_im).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_d2o_shake_flags_exchangeable_peaks() {
+        let spectrum = |lines: &[[f64; 2]]| {
+            let axis = AxisParams {
+                num_points: 4096,
+                spectral_width_hz: 4000.0,
+                observe_freq_mhz: 400.0,
+                reference_ppm: 10.0,
+                ..Default::default()
+            };
+            let lorentz = |ppm: f64| lines.iter().map(|l| l[1] / (1.0 + ((ppm - l[0]) / 0.01).powi(2))).sum();
+            SpectrumData {
+                real: (0..4096).map(|i| lorentz(axis.index_to_ppm(i))).collect(),
+                is_frequency_domain: true,
+                axes: vec![axis],
+                ..Default::default()
+            }
+        };
+
+        // After the shake: half the concentration, referenced 0.02 ppm low,
+        // the OH at 4.80 gone, the NH at 1.50 mostly exchanged, HDO at 4.70
+        let before_peaks = [[7.20, 10.0], [4.80, 5.0], [2.00, 10.0], [1.50, 4.0]];
+        let after_peaks = [[7.18, 5.0], [4.68, 8.0], [1.98, 5.0], [1.48, 0.4]];
+        let (before, after) = (spectrum(&before_peaks), spectrum(&after_peaks));
+        let rows = compare_peak_lists(&before_peaks, &after_peaks, 0.02, 0.01);
+
+        let exchange = d2o_exchange(&before, &after, &rows, 0.02, 0.3);
+        assert_eq!(exchange.exchanged, [[4.80, 5.0], [1.50, 4.0]]);
+        assert!((exchange.scale - 2.0).abs() < 1e-12);
+
+        // The difference keeps the exchanged signals and cancels the rest
+        let at = |ppm: f64| {
+            exchange
+                .difference
+                .iter()
+                .min_by(|a, b| (a[0] - ppm).abs().total_cmp(&(b[0] - ppm).abs()))
+                .unwrap()[1]
+        };
+        assert!((at(4.80) - 5.0).abs() < 0.5);
+        assert!((at(1.50) - 3.2).abs() < 0.3);
+        assert!(at(7.20).abs() < 0.3);
+        assert!(at(4.70) < -10.0);
+
+        // A stricter limit keeps the partly exchanged NH
+        let strict = d2o_exchange(&before, &after, &rows, 0.02, 0.1);
+        assert_eq!(strict.exchanged, [[4.80, 5.0]]);
+    }
+
+    #[test]
+    fn test_fid_time_axis_from_spectral_width() {
+        let n = 1024;
+        let mut spectrum = SpectrumData {
+            real: (0..n).map(|i| (-(i as f64) / 200.0).exp()).collect(),
+            is_frequency_domain: false,
+            axes: vec![AxisParams { num_points: n, spectral_width_hz: 4000.0, ..Default::default() }],
+            ..Default::default()
+        };
+        let axis = &spectrum.axes[0];
+        assert_eq!(axis.dwell_time(), Some(250e-6));
+        assert!((axis.acquisition_time().unwrap() - 0.256).abs() < 1e-12);
+
+        // The FID is drawn and exported in seconds from t = 0
+        let xs = spectrum.x_scale();
+        assert_eq!(xs[0], 0.0);
+        assert!((xs[n - 1] - 1023.0 * 250e-6).abs() < 1e-12);
+        assert_eq!(spectrum.x_axis_label(), "Time (s)");
+        let export = web_export(&spectrum, &[], 4096, 4).unwrap();
+        assert_eq!(export.x_unit, "s");
+        assert_eq!(export.points[1][0], 0.00025);
+
+        // Without a spectral width the axis falls back to point numbers
+        spectrum.axes[0].spectral_width_hz = 0.0;
+        assert_eq!(spectrum.x_scale()[3], 3.0);
+        assert_eq!(spectrum.x_axis_label(), "Point");
+        assert_eq!(web_export(&spectrum, &[], 4096, 4).unwrap().x_unit, "points");
+    }
+
+    #[test]
+    fn test_phase_on_peaks_recovers_ph0_and_ph1() {
+        // Two absorptive Lorentzians, then a known phase error
+        let n = 4096;
+        let line = |i: usize, c: usize| {
+            let (w, d) = (3.0, i as f64 - c as f64);
+            (w * w / (w * w + d * d), -w * d / (w * w + d * d))
+        };
+        let (mut real, mut imag) = (vec![0.0; n], vec![0.0; n]);
+        for i in 0..n {
+            let (a, b) = line(i, 1000);
+            let (c, d) = line(i, 3000);
+            real[i] = a + 0.5 * c;
+            imag[i] = b + 0.5 * d;
+        }
+        let mut spectrum = SpectrumData {
+            real,
+            imag,
+            is_frequency_domain: true,
+            axes: vec![AxisParams {
+                num_points: n,
+                spectral_width_hz: 4000.0,
+                observe_freq_mhz: 400.0,
+                reference_ppm: 10.0,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        phase_correct(&mut spectrum, 40.0, -60.0, &mut ReproLog::new());
+
+        // Clicking beside a line still anchors on its top
+        let axis = spectrum.axes[0].clone();
+        let first = phase_anchor(&spectrum, axis.index_to_ppm(1002)).unwrap();
+        assert_eq!(first.ppm, axis.index_to_ppm(1000));
+        let second = phase_anchor(&spectrum, axis.index_to_ppm(3000)).unwrap();
+
+        let (ph0, ph1) = phase_from_anchors(&first, Some(&second), 0.0);
+        assert!((ph0 + 40.0).abs() < 0.5 && (ph1 - 60.0).abs() < 0.5, "PH0={} PH1={}", ph0, ph1);
+        // One peak sets PH0 and keeps the given PH1
+        let (ph0, ph1) = phase_from_anchors(&first, None, 60.0);
+        assert!((ph0 + 40.0).abs() < 0.5 && ph1 == 60.0);
+
+        // Applying the result leaves both lines positive and absorptive
+        phase_correct(&mut spectrum, ph0, ph1, &mut ReproLog::new());
+        assert!((spectrum.real[1000] - 1.0).abs() < 0.01 && (spectrum.real[3000] - 0.5).abs() < 0.01);
+        assert!(phase_anchor(&SpectrumData { imag: Vec::new(), ..spectrum }, first.ppm).is_none());
+    }
+
+    #[test]
+    fn test_linear_prediction_continues_truncated_fid() {
+        // Two damped complex sinusoids, acquired for 48 points only
+        let fid = |i: usize| {
+            let t = i as f64;
+            let a = (-t / 40.0).exp();
+            let b = 0.6 * (-t / 25.0).exp();
+            (a * (0.45 * t).cos() + b * (-1.2 * t).cos(), a * (0.45 * t).sin() + b * (-1.2 * t).sin())
+        };
+        for mode in [LpMode::Forward, LpMode::Backward, LpMode::Mixed] {
+            let mut s = SpectrumData {
+                real: (0..48).map(|i| fid(i).0).collect(),
+                imag: (0..48).map(|i| fid(i).1).collect(),
+                axes: vec![AxisParams { num_points: 48, ..Default::default() }],
+                ..Default::default()
+            };
+            let mut log = ReproLog::new();
+            linear_predict(&mut s, 4, 80, mode, &mut log).unwrap();
+            assert_eq!(s.real.len(), 128);
+            assert_eq!(s.axes[0].num_points, 128);
+            for i in 48..128 {
+                let (re, im) = fid(i);
+                assert!((s.real[i] - re).abs() < 1e-5 && (s.imag[i] - im).abs() < 1e-5, "{} at {}", mode, i);
+            }
+            assert!(log.entries[0].nmrpipe_command.starts_with("nmrPipe -fn LP"));
+            assert!(log.entries[0].nmrpipe_command.contains(mode.nmrpipe_flag()));
+        }
+
+        // 2D: every F2 column is continued along the t1 increments
+        let rows = 24;
+        let mut s = SpectrumData {
+            dimensionality: Dimensionality::TwoD,
+            data_2d: (0..rows).map(|r| (0..4).map(|c| fid(r).0 * (c + 1) as f64).collect()).collect(),
+            data_2d_imag: (0..rows).map(|r| (0..4).map(|c| fid(r).1 * (c + 1) as f64).collect()).collect(),
+            axes: vec![AxisParams::default(), AxisParams { num_points: rows, ..Default::default() }],
+            ..Default::default()
+        };
+        let mut log = ReproLog::new();
+        linear_predict(&mut s, 4, 8, LpMode::Mixed, &mut log).unwrap();
+        assert_eq!(s.data_2d.len(), 32);
+        assert_eq!(s.data_2d_imag.len(), 32);
+        assert_eq!(s.axes[1].num_points, 32);
+        assert!((s.data_2d[30][2] - 3.0 * fid(30).0).abs() < 1e-5);
+        assert!(log.entries[0].nmrpipe_command.contains("-pred 8 -fix  # F1"));
+
+        // Too few points for the order, or already transformed
+        let mut short = SpectrumData { real: vec![1.0; 6], ..Default::default() };
+        assert!(linear_predict(&mut short, 4, 8, LpMode::Forward, &mut log).is_err());
+        short.is_frequency_domain = true;
+        assert!(linear_predict(&mut short, 1, 8, LpMode::Forward, &mut log).is_err());
+    }
+
+    #[test]
+    fn test_linear_prediction_keeps_states_pairs_in_phase() {
+        // Two F1 frequencies on one decaying F2 line; each increment holds
+        // its cosine- and sine-modulated row in the layout of `acquisition`
+        let cos_sin = |k: usize, c: usize| {
+            let (t1, t2) = (k as f64, c as f64);
+            let f2 = Complex::from_polar((-t2 / 20.0).exp(), 0.7 * t2);
+            let f1 = |phase: f64| {
+                (-t1 / 30.0).exp() * (0.5 * t1 + phase).cos() + 0.4 * (-t1 / 15.0).exp() * (-1.3 * t1 + phase).cos()
+            };
+            (f2 * f1(0.0), f2 * f1(-std::f64::consts::FRAC_PI_2))
+        };
+        for acquisition in [F1Acquisition::States, F1Acquisition::StatesTppi, F1Acquisition::EchoAntiecho] {
+            let pair = |k: usize, c: usize| {
+                let (cos, sin) = cos_sin(k, c);
+                encode_pair(cos, sin, k, acquisition)
+            };
+            let row = |r: usize, c: usize| if r.is_multiple_of(2) { pair(r / 2, c).0 } else { pair(r / 2, c).1 };
+            let (acquired, cols) = (32, 16);
+            let mut s = SpectrumData {
+                dimensionality: Dimensionality::TwoD,
+                f1_acquisition: acquisition,
+                data_2d: (0..acquired).map(|r| (0..cols).map(|c| row(r, c).re).collect()).collect(),
+                data_2d_imag: (0..acquired).map(|r| (0..cols).map(|c| row(r, c).im).collect()).collect(),
+                axes: vec![AxisParams::default(), AxisParams { num_points: acquired, ..Default::default() }],
+                ..Default::default()
+            };
+            // An odd count is rounded up to whole increments
+            let mut log = ReproLog::new();
+            linear_predict(&mut s, 4, 31, LpMode::Forward, &mut log).unwrap();
+            assert_eq!((s.data_2d.len(), s.data_2d_imag.len(), s.axes[1].num_points), (64, 64, 64));
+            for r in 0..64 {
+                for c in 0..cols {
+                    let value = Complex::new(s.data_2d[r][c], s.data_2d_imag[r][c]);
+                    assert!((value - row(r, c)).norm() < 1e-6, "{} row {} col {}", acquisition, r, c);
+                }
+            }
+            assert!(log.entries[0].nmrpipe_command.contains("-pred 16 -fix  # F1"));
+        }
+
+        // Half a pair cannot be split into cosine and sine rows
+        let mut odd = SpectrumData {
+            dimensionality: Dimensionality::TwoD,
+            f1_acquisition: F1Acquisition::States,
+            data_2d: vec![vec![1.0; 4]; 15],
+            ..Default::default()
+        };
+        assert!(linear_predict(&mut odd, 2, 4, LpMode::Forward, &mut ReproLog::new()).is_err());
+    }
+
+    #[test]
+    fn test_ist_reconstructs_states_pairs_to_the_known_peaks() {
+        // Two F1 lines (9/64 and −21/64 of the F1 width) on one F2 line,
+        // acquired as States pairs on 40% of a 64-increment grid
+        let cos_sin = |k: usize, c: usize| {
+            let (t1, t2) = (k as f64, c as f64);
+            let f2 = Complex::from_polar((-t2 / 20.0).exp(), 2.0 * PI * 3.0 * t2 / 16.0);
+            let f1 = |phase: f64| {
+                (-t1 / 150.0).exp()
+                    * ((2.0 * PI * 9.0 * t1 / 64.0 + phase).cos() + 0.5 * (-2.0 * PI * 21.0 * t1 / 64.0 + phase).cos())
+            };
+            (f2 * f1(0.0), f2 * f1(-PI / 2.0))
+        };
+        let row = |r: usize, c: usize| if r.is_multiple_of(2) { cos_sin(r / 2, c).0 } else { cos_sin(r / 2, c).1 };
+        let matrix = |rows: &[usize], part: fn(Complex<f64>) -> f64| -> Vec<Vec<f64>> {
+            rows.iter().map(|&r| (0..16).map(|c| part(row(r, c))).collect()).collect()
+        };
+        let sampled: Vec<usize> = (0..64).filter(|&k| k < 8 || k == 63 || (k * 37 + 11) % 100 < 33).collect();
+        let stored: Vec<usize> = sampled.iter().flat_map(|&k| [2 * k, 2 * k + 1]).collect();
+        let all: Vec<usize> = (0..128).collect();
+        let truth = SpectrumData {
+            dimensionality: Dimensionality::TwoD,
+            f1_acquisition: F1Acquisition::States,
+            data_2d: matrix(&all, |v| v.re),
+            data_2d_imag: matrix(&all, |v| v.im),
+            axes: vec![AxisParams::default(), AxisParams { num_points: 128, ..Default::default() }],
+            ..Default::default()
+        };
+        let mut s = SpectrumData {
+            data_2d: matrix(&stored, |v| v.re),
+            data_2d_imag: matrix(&stored, |v| v.im),
+            nus_schedule: Some(NusSchedule {
+                sampled: sampled.clone(),
+                grid: 64,
+                rows_per_increment: 2,
+                source: "nuslist".to_string(),
+            }),
+            ..truth.clone()
+        };
+        let mut log = ReproLog::new();
+        nus_reconstruct(&mut s, 300, &mut log).unwrap();
+        assert_eq!((s.data_2d.len(), s.axes[1].num_points), (128, 128));
+        assert_eq!(s.data_2d[2 * sampled[5] + 1][3], row(2 * sampled[5] + 1, 3).re);
+        assert!(log.entries[0].description.contains("States"));
+
+        // Both quadrature rows of the skipped increments come back
+        let (mut err, mut power) = (0.0, 0.0);
+        for r in (0..128).filter(|r| !sampled.contains(&(r / 2))) {
+            for c in 0..16 {
+                err += (Complex::new(s.data_2d[r][c], s.data_2d_imag[r][c]) - row(r, c)).norm_sqr();
+                power += row(r, c).norm_sqr();
+            }
+        }
+        assert!((err / power).sqrt() < 0.05, "relative error {}", (err / power).sqrt());
+
+        // and the phase-sensitive spectrum has its peaks where the fully
+        // sampled one has them
+        let peaks = |mut spectrum: SpectrumData| {
+            fourier_transform_2d(&mut spectrum, Ft2dMode::PhaseSensitive, &mut ReproLog::new());
+            let mut cells: Vec<(f64, usize, usize)> = spectrum
+                .data_2d
+                .iter()
+                .enumerate()
+                .flat_map(|(r, row)| row.iter().enumerate().map(move |(c, &v)| (v, r, c)))
+                .collect();
+            cells.sort_by(|a, b| b.0.total_cmp(&a.0));
+            let top = cells[0];
+            let second = *cells.iter().find(|(_, r, _)| r.abs_diff(top.1) > 4).unwrap();
+            [(top.1, top.2), (second.1, second.2)]
+        };
+        assert_eq!(peaks(s), peaks(truth.clone()));
+
+        // A grid of States pairs with empty skipped increments (JEOL layout)
+        let mut grid = truth;
+        for r in (0..128).filter(|r| !sampled.contains(&(r / 2))) {
+            grid.data_2d[r].fill(0.0);
+            grid.data_2d_imag[r].fill(0.0);
+        }
+        let inferred = find_nus_schedule(&grid, std::path::Path::new("/nonexistent/data.jdf")).unwrap();
+        assert_eq!((inferred.sampled, inferred.grid, inferred.rows_per_increment), (sampled, 64, 2));
+    }
+
+    #[test]
+    fn test_nus_schedule_and_ist_reconstruction() {
+        // Two slowly decaying F1 frequencies in every F2 column
+        let signal = |r: usize, c: usize| {
+            let t = r as f64;
+            let a = (-t / 150.0).exp();
+            let b = 0.5 * (-t / 150.0).exp();
+            let z = num_complex::Complex::from_polar(a, 2.0 * std::f64::consts::PI * 9.0 * t / 64.0)
+                + num_complex::Complex::from_polar(b, -2.0 * std::f64::consts::PI * 21.0 * t / 64.0);
+            z * (c + 1) as f64
+        };
+        // 40% of the 64 increments, denser early as NUS schedules are
+        let sampled: Vec<usize> = (0..64).filter(|&r| r < 8 || r == 63 || (r * 37 + 11) % 100 < 33).collect();
+        let text: String = sampled.iter().map(|s| format!("{}\n", s)).collect();
+        assert_eq!(parse_nuslist(&format!("# schedule\n{}", text)).unwrap(), sampled);
+        assert!(parse_nuslist("3\nx\n").is_err());
+
+        // Only the sampled increments stored, schedule in a nuslist beside them
+        let dir = std::env::temp_dir().join(format!("nmr_nus_test_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("nuslist"), &text).unwrap();
+        let mut s = SpectrumData {
+            dimensionality: Dimensionality::TwoD,
+            data_2d: sampled.iter().map(|&r| (0..4).map(|c| signal(r, c).re).collect()).collect(),
+            data_2d_imag: sampled.iter().map(|&r| (0..4).map(|c| signal(r, c).im).collect()).collect(),
+            axes: vec![AxisParams::default(), AxisParams { num_points: sampled.len(), ..Default::default() }],
+            ..Default::default()
+        };
+        let schedule = find_nus_schedule(&s, &dir.join("fid")).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!((schedule.sampled.len(), schedule.grid, schedule.rows_per_increment), (sampled.len(), 64, 1));
+        s.nus_schedule = Some(schedule);
+
+        let mut log = ReproLog::new();
+        nus_reconstruct(&mut s, 300, &mut log).unwrap();
+        assert_eq!(s.data_2d.len(), 64);
+        assert_eq!(s.axes[1].num_points, 64);
+        assert!(s.nus_schedule.is_none());
+        assert_eq!(s.data_2d[sampled[5]][2], signal(sampled[5], 2).re);
+        let (mut err, mut power) = (0.0, 0.0);
+        for r in (0..64).filter(|r| !sampled.contains(r)) {
+            for c in 0..4 {
+                let truth = signal(r, c);
+                err += (num_complex::Complex::new(s.data_2d[r][c], s.data_2d_imag[r][c]) - truth).norm_sqr();
+                power += truth.norm_sqr();
+            }
+        }
+        // Leaving the gaps empty would be a relative error of 1
+        assert!((err / power).sqrt() < 0.02, "relative error {}", (err / power).sqrt());
+        assert!(log.entries[0].nmrpipe_command.contains("hmsIST"));
+
+        // A grid with zero rows for the skipped increments (JEOL layout)
+        let mut grid = s.clone();
+        for r in (0..48).filter(|r| !sampled.contains(r)) {
+            grid.data_2d[r].fill(0.0);
+            grid.data_2d_imag[r].fill(0.0);
         }
+        let inferred = find_nus_schedule(&grid, std::path::Path::new("/nonexistent/data.jdf")).unwrap();
+        assert_eq!(inferred.grid, 64);
+        assert!(inferred.sampled.len() < 64 && inferred.sampled.contains(&50));
+        assert!(find_nus_schedule(&s, std::path::Path::new("/nonexistent/data.jdf")).is_none());
     }
 
-    // Keep strongest first, enforce minimum distance
-    candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-    let mut selected: Vec<usize> = Vec::new();
-    for &(idx, _) in &candidates {
-        let too_close = selected
-            .iter()
-            .any(|&s| (idx as i64 - s as i64).unsigned_abs() as usize <= min_distance);
-        if !too_close {
-            selected.push(idx);
+    #[test]
+    fn test_common_impurities_need_every_signal() {
+        use crate::data::{impurities, solvent};
+
+        // Every table column is a solvent the loader recognises
+        for name in impurities::SOLVENT_COLUMNS {
+            assert!(solvent::SOLVENTS.iter().any(|s| s.name == name), "{}", name);
         }
-    }
+        let cdcl3 = impurities::column("CDCl3").unwrap();
+        assert_eq!(impurities::column("DMSO-d6"), Some(2));
+        assert_eq!(impurities::column("THF-d8"), None);
 
-    // Build ppm scale
-    let ppm_scale = if spectrum.is_frequency_domain && !spectrum.axes.is_empty() {
-        spectrum.axes[0].ppm_scale()
-    } else {
-        (0..n).map(|i| i as f64).collect()
-    };
+        // Water, the three ethyl acetate signals, and only the CH2 of Et3N
+        let peaks = [[7.10, 9.0], [4.13, 2.0], [2.53, 1.0], [2.04, 3.0], [1.57, 1.0], [1.25, 3.0]];
+        let found = identify_common_impurities(&peaks, cdcl3, IMPURITY_TOLERANCE_PPM);
+        let at = |ppm: f64| found.iter().find(|(p, _)| *p == ppm).map(|(_, n)| n.as_str());
+        assert_eq!(at(1.57), Some("H2O"));
+        assert_eq!(at(4.13), Some("ethyl acetate"));
+        assert_eq!(at(2.04), Some("ethyl acetate"));
+        assert!(at(1.25).unwrap().contains("ethyl acetate"));
+        assert_eq!(at(2.53), None);
+        assert_eq!(at(7.10), None);
+        assert!(found.windows(2).all(|w| w[0].0 > w[1].0));
 
-    let mut peaks: Vec<[f64; 2]> = selected
-        .iter()
-        .filter_map(|&i| {
-            if i < ppm_scale.len() {
-                Some([ppm_scale[i], spectrum.real[i]])
-            } else {
-                None
+        // The same water peak is not water in DMSO-d6
+        let dmso = identify_common_impurities(&peaks, 2, IMPURITY_TOLERANCE_PPM);
+        assert!(!dmso.iter().any(|(_, n)| n == "H2O"));
+    }
+
+    #[test]
+    fn test_center_glitch_detected_and_interpolated() {
+        // Two decaying lines off resonance in a little noise, plus a DC
+        // offset that the FT turns into a spike at zero frequency
+        let mut seed = 777u64;
+        let mut noise = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            0.02 * (((seed >> 11) as f64 / (1u64 << 53) as f64) - 0.5)
+        };
+        let n = 4096;
+        let fid = |offset: f64, noise: &mut dyn FnMut() -> f64| -> SpectrumData {
+            let (mut real, mut imag) = (Vec::with_capacity(n), Vec::with_capacity(n));
+            for i in 0..n {
+                let t = i as f64;
+                let (a, b) = ((-t / 300.0).exp(), 0.5 * (-t / 200.0).exp());
+                real.push(a * (0.9 * t).cos() + b * (-1.7 * t).cos() + offset + noise());
+                imag.push(a * (0.9 * t).sin() + b * (-1.7 * t).sin() + offset + noise());
             }
-        })
-        .collect();
+            SpectrumData { real, imag, ..Default::default() }
+        };
+        let mut log = ReproLog::new();
+        let mut s = fid(0.005, &mut noise);
+        fourier_transform(&mut s, true, &mut log);
 
-    // Sort by ppm descending (NMR convention: high ppm first)
-    peaks.sort_by(|a, b| b[0].partial_cmp(&a[0]).unwrap());
-    peaks
-}
+        let center = n - 1 - n / 2;
+        let glitch = detect_center_glitch(&s, CENTER_GLITCH_SIGMA).unwrap();
+        assert!(glitch.start <= center && center <= glitch.end);
+        assert!(glitch.end - glitch.start <= 4);
+        let (left, right) = (s.real[glitch.start - 1], s.real[glitch.end + 1]);
+        remove_center_glitch(&mut s, &glitch, &mut log);
+        assert!((s.real[center] - (left + right) / 2.0).abs() <= (left - right).abs() / 2.0 + 1e-12);
+        assert!(detect_center_glitch(&s, CENTER_GLITCH_SIGMA).is_none());
+        assert_eq!(log.entries.last().unwrap().operation, "Center Glitch Removal");
 
-// =========================================================================
-//  Multiplet Detection
-// =========================================================================
+        // No offset, no glitch
+        let mut clean = fid(0.0, &mut noise);
+        fourier_transform(&mut clean, true, &mut log);
+        assert!(detect_center_glitch(&clean, CENTER_GLITCH_SIGMA).is_none());
 
-/// A detected multiplet group
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct Multiplet {
-    /// Center ppm of the multiplet
-    pub center_ppm: f64,
-    /// Coupling constant J in Hz (average spacing between lines)
-    pub j_hz: f64,
-    /// Number of lines in the multiplet
-    pub num_lines: usize,
-    /// Classification label
-    pub label: String,
-    /// The peaks that form this multiplet: [ppm, intensity]
-    pub peaks: Vec<[f64; 2]>,
-}
+        // A real line on the carrier is wider than a glitch and is kept
+        let line = SpectrumData {
+            real: (0..n).map(|i| 1.0 / (1.0 + ((i as f64 - center as f64) / 3.0).powi(2)) + noise()).collect(),
+            is_frequency_domain: true,
+            ..Default::default()
+        };
+        assert!(detect_center_glitch(&line, CENTER_GLITCH_SIGMA).is_none());
+    }
 
-impl std::fmt::Display for Multiplet {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.j_hz > 0.0 {
-            write!(f, "{:.2} ppm ({}, J={:.1} Hz)", self.center_ppm, self.label, self.j_hz)
-        } else {
-            write!(f, "{:.2} ppm ({})", self.center_ppm, self.label)
+    #[test]
+    fn test_phase_2d_along_f2_and_f1() {
+        // Absorptive cross peak with PH0/PH1 errors of (30°, 60°) along F2
+        // and (-45°, 20°) along F1
+        let (rows, cols) = (32, 64);
+        let peak = |r: usize, c: usize| {
+            1.0 / (1.0 + ((r as f64 - 12.0) / 2.0).powi(2)) / (1.0 + ((c as f64 - 40.0) / 3.0).powi(2))
+        };
+        let error = |ph0: f64, ph1: f64, i: usize, n: usize| (ph0 + ph1 * i as f64 / n as f64).to_radians();
+        let f2 = |c: usize| error(30.0, 60.0, c, cols);
+        let f1 = |r: usize| error(-45.0, 20.0, r, rows);
+        let matrix = |f: &dyn Fn(usize, usize) -> f64| -> Vec<Vec<f64>> {
+            (0..rows).map(|r| (0..cols).map(|c| f(r, c)).collect()).collect()
+        };
+        let hypercomplex = SpectrumData {
+            dimensionality: Dimensionality::TwoD,
+            is_frequency_domain: true,
+            data_2d: matrix(&|r, c| peak(r, c) * f2(c).cos() * f1(r).cos()),
+            data_2d_imag: matrix(&|r, c| -peak(r, c) * f2(c).sin() * f1(r).cos()),
+            f1_quadrants: Some(F1Quadrants {
+                ir: matrix(&|r, c| -peak(r, c) * f2(c).cos() * f1(r).sin()),
+                ii: matrix(&|r, c| peak(r, c) * f2(c).sin() * f1(r).sin()),
+            }),
+            ..Default::default()
+        };
+        // Complex points: both phase errors in one rotation
+        let complex = SpectrumData {
+            data_2d: matrix(&|r, c| peak(r, c) * (f2(c) + f1(r)).cos()),
+            data_2d_imag: matrix(&|r, c| -peak(r, c) * (f2(c) + f1(r)).sin()),
+            f1_quadrants: None,
+            ..hypercomplex.clone()
+        };
+
+        for mut s in [hypercomplex, complex] {
+            let mut log = ReproLog::new();
+            // The preview trace is the row the correction produces
+            let preview = phased_trace_2d(&s, PhaseDim::F2, 12, 30.0, 60.0);
+            phase_correct_2d(&mut s, PhaseDim::F2, 30.0, 60.0, &mut log).unwrap();
+            assert!(preview.iter().zip(&s.data_2d[12]).all(|(a, b)| (a - b).abs() < 1e-12));
+            phase_correct_2d(&mut s, PhaseDim::F1, -45.0, 20.0, &mut log).unwrap();
+            for r in 0..rows {
+                for c in 0..cols {
+                    assert!((s.data_2d[r][c] - peak(r, c)).abs() < 1e-12, "({}, {})", r, c);
+                    assert!(s.data_2d_imag[r][c].abs() < 1e-12);
+                }
+            }
+            assert_eq!(log.entries[1].operation, "2D Phase Correction");
+            assert!(log.entries[1].nmrpipe_command.contains("TP"));
         }
-    }
-}
 
-fn multiplet_label(n: usize) -> &'static str {
-    match n {
-        1 => "s",
-        2 => "d",
-        3 => "t",
-        4 => "q",
-        5 => "quint",
-        6 => "sext",
-        7 => "sept",
-        _ => "m",
+        // A magnitude spectrum has nothing to phase with
+        let mut magnitude = SpectrumData {
+            dimensionality: Dimensionality::TwoD,
+            is_frequency_domain: true,
+            data_2d: vec![vec![1.0; 4]; 4],
+            ..Default::default()
+        };
+        assert!(phase_correct_2d(&mut magnitude, PhaseDim::F2, 10.0, 0.0, &mut ReproLog::new()).is_err());
+        assert!(phased_trace_2d(&magnitude, PhaseDim::F1, 0, 0.0, 0.0).is_empty());
     }
-}
 
-/// Group detected peaks into multiplets based on coupling patterns.
-///
-/// `max_j_hz`: maximum coupling constant to consider (typically ~20 Hz for ¹H).
-/// `obs_mhz`: observe frequency in MHz (needed to convert ppm spacing → Hz).
-pub fn detect_multiplets(
-    peaks: &[[f64; 2]],
-    max_j_hz: f64,
-    obs_mhz: f64,
-) -> Vec<Multiplet> {
-    if peaks.is_empty() || obs_mhz <= 0.0 {
-        return vec![];
+    #[test]
+    fn test_non_finite_values_reported_and_replaced() {
+        let mut log = ReproLog::new();
+        let line = |v: &[f64]| SpectrumData { real: v.to_vec(), imag: vec![0.0; v.len()], ..Default::default() };
+        assert!(find_non_finite(&line(&[1.0, 2.0, 3.0])).is_none());
+
+        let mut s = line(&[1.0, f64::NAN, f64::INFINITY, 4.0, f64::NEG_INFINITY]);
+        s.imag[0] = f64::NAN;
+        let report = find_non_finite(&s).unwrap();
+        assert_eq!((report.nan, report.infinite), (2, 2));
+        assert_eq!(report.locations, ["real[1]", "real[2]", "real[4]", "imaginary[0]"]);
+
+        // Interior runs are bridged by a line, ends copy their neighbour
+        let mut fixed = s.clone();
+        assert_eq!(replace_non_finite(&mut fixed, NonFiniteFix::Interpolate, &mut log), 4);
+        assert_eq!(fixed.real, [1.0, 2.0, 3.0, 4.0, 4.0]);
+        assert_eq!(fixed.imag[0], 0.0);
+        assert_eq!(log.entries.last().unwrap().operation, "Non-finite Values");
+        replace_non_finite(&mut s, NonFiniteFix::Zero, &mut log);
+        assert_eq!(s.real, [1.0, 0.0, 0.0, 4.0, 0.0]);
+        assert!(find_non_finite(&s).is_none());
+
+        // 2D: counted once per matrix point, and the 1D mirror is refreshed
+        let mut s2 = SpectrumData {
+            dimensionality: Dimensionality::TwoD,
+            data_2d: vec![vec![0.0, f64::NAN, 2.0], vec![1.0; 3]],
+            data_2d_imag: vec![vec![0.0; 3], vec![f64::INFINITY, 1.0, 1.0]],
+            ..Default::default()
+        };
+        s2.real = s2.data_2d[0].clone();
+        let report = find_non_finite(&s2).unwrap();
+        assert_eq!(report.locations, ["2D real[0, 1]", "2D imaginary[1, 0]"]);
+        replace_non_finite(&mut s2, NonFiniteFix::Interpolate, &mut log);
+        assert_eq!(s2.real, [0.0, 1.0, 2.0]);
+        assert_eq!(s2.data_2d_imag[1], [1.0, 1.0, 1.0]);
     }
 
-    // Sort peaks by ppm ascending for grouping
-    let mut sorted = peaks.to_vec();
-    sorted.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap());
+    #[test]
+    fn test_baseline_models_remove_roll_under_peaks() {
+        // Three Lorentzians on a cubic roll, in a little noise
+        let mut seed = 4759u64;
+        let mut noise = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            0.002 * (((seed >> 11) as f64 / (1u64 << 53) as f64) - 0.5)
+        };
+        let n = 4096;
+        let roll = |i: usize| {
+            let x = 2.0 * i as f64 / (n - 1) as f64 - 1.0;
+            0.3 + 0.2 * x - 0.4 * x * x + 0.25 * x * x * x
+        };
+        let peaks = |i: usize| {
+            [(800.0, 1.0), (2000.0, 0.6), (3100.0, 0.8)]
+                .iter()
+                .map(|&(c, h)| h / (1.0 + ((i as f64 - c) / 6.0).powi(2)))
+                .sum::<f64>()
+        };
+        let spectrum = SpectrumData {
+            real: (0..n).map(|i| peaks(i) + roll(i) + noise()).collect(),
+            is_frequency_domain: true,
+            ..Default::default()
+        };
 
-    // Convert max J from Hz to ppm
-    let max_j_ppm = max_j_hz / obs_mhz;
+        // Signal-free points avoid the peaks but cover most of the spectrum
+        let free = detect_baseline_points(&spectrum.real);
+        assert!(!free[800] && !free[2000] && !free[3100]);
+        assert!(free.iter().filter(|&&b| b).count() > n * 3 / 4);
 
-    // Greedy grouping: walk through sorted peaks, group if gap ≤ max_j_ppm
-    let mut groups: Vec<Vec<[f64; 2]>> = Vec::new();
-    let mut current_group: Vec<[f64; 2]> = vec![sorted[0]];
+        // Far from the peaks, the corrected spectrum is flat; the linear
+        // model cannot follow the curvature
+        let residual = |method: BaselineMethod| {
+            let mut s = spectrum.clone();
+            let mut log = ReproLog::new();
+            baseline_correct(&mut s, &method, &mut log);
+            let worst = (0..n)
+                .filter(|&i| [800.0, 2000.0, 3100.0].iter().all(|c: &f64| (i as f64 - c).abs() > 200.0))
+                .map(|i| s.real[i].abs())
+                .fold(0.0, f64::max);
+            (worst, log.entries.last().unwrap().operation.clone(), s.real[800])
+        };
+        let (linear, op, _) = residual(BaselineMethod::Linear);
+        assert!(linear > 0.05, "linear residual {}", linear);
+        assert_eq!(op, "Baseline Correction");
+        let (poly, op, top) = residual(BaselineMethod::Polynomial { order: 3 });
+        assert!(poly < 0.01, "polynomial residual {}", poly);
+        assert!((top - 1.0).abs() < 0.02, "peak height {}", top);
+        assert_eq!(op, "Polynomial Baseline Correction");
+        let (als, op, top) = residual(BaselineMethod::Als { lambda: 1e7, p: 0.01 });
+        assert!(als < 0.02, "ALS residual {}", als);
+        assert!((top - 1.0).abs() < 0.05, "peak height {}", top);
+        assert_eq!(op, "ALS Baseline Correction");
 
-    for i in 1..sorted.len() {
-        let gap = (sorted[i][0] - sorted[i - 1][0]).abs();
-        if gap <= max_j_ppm {
-            current_group.push(sorted[i]);
-        } else {
-            groups.push(std::mem::take(&mut current_group));
-            current_group = vec![sorted[i]];
-        }
-    }
-    if !current_group.is_empty() {
-        groups.push(current_group);
+        // The preview is the curve that gets subtracted
+        let baseline = fit_baseline(&spectrum.real, &BaselineMethod::Polynomial { order: 3 });
+        assert_eq!(baseline.len(), n);
+        assert!((baseline[100] - roll(100)).abs() < 0.01);
     }
 
-    // Build multiplets from groups
-    let mut multiplets: Vec<Multiplet> = Vec::new();
-    for group in &groups {
-        let n = group.len();
-        // Center ppm: intensity-weighted average
-        let total_int: f64 = group.iter().map(|p| p[1].abs()).sum();
-        let center = if total_int > 0.0 {
-            group.iter().map(|p| p[0] * p[1].abs()).sum::<f64>() / total_int
-        } else {
-            group.iter().map(|p| p[0]).sum::<f64>() / n as f64
+    #[test]
+    fn test_group_delay_removed_from_fid_or_spectrum() {
+        // Damped complex sinusoids whose t = 0 is `delay` points into the FID
+        let delay = 20.37;
+        let fid = |t: f64| {
+            let a = (-t / 60.0).exp();
+            let b = 0.5 * (-t / 40.0).exp();
+            (a * (0.31 * t).cos() + b * (1.7 * t).cos(), a * (0.31 * t).sin() + b * (1.7 * t).sin())
         };
-
-        // Average J: mean spacing between consecutive lines (in Hz)
-        let j_hz = if n >= 2 {
-            let mut spacings = Vec::new();
-            for i in 1..n {
-                spacings.push((group[i][0] - group[i - 1][0]).abs() * obs_mhz);
+        let delayed = || {
+            let points: Vec<(f64, f64)> = (0..1000).map(|i| fid(i as f64 - delay)).collect();
+            SpectrumData {
+                real: points.iter().map(|p| p.0).collect(),
+                imag: points.iter().map(|p| p.1).collect(),
+                group_delay: Some(delay),
+                ..Default::default()
             }
-            spacings.iter().sum::<f64>() / spacings.len() as f64
-        } else {
-            0.0
         };
 
-        multiplets.push(Multiplet {
-            center_ppm: center,
-            j_hz,
-            num_lines: n,
-            label: multiplet_label(n).to_string(),
-            peaks: group.clone(),
-        });
+        let mut log = ReproLog::new();
+        let mut s = delayed();
+        correct_group_delay(&mut s, delay, &mut log).unwrap();
+        assert_eq!(s.group_delay, None);
+        assert_eq!(s.real.len(), 1000);
+        // Shifted back to the start, with the -180°·delay phase of bruk2pipe -DMX
+        let rot = num_complex::Complex::from_polar(1.0, -std::f64::consts::PI * delay);
+        for i in 1..900 {
+            let (re, im) = fid(i as f64);
+            let expected = num_complex::Complex::new(re, im) * rot;
+            assert!((num_complex::Complex::new(s.real[i], s.imag[i]) - expected).norm() < 1e-2, "point {}", i);
+        }
+        assert_eq!((s.real[979], s.imag[999]), (0.0, 0.0));
+        assert!(log.entries[0].nmrpipe_command.contains("-grpdly 20.37"));
+
+        // Transformed with the delay and phased instead: the same spectrum.
+        // The burp rises from zero, so it does not wrap around as a step.
+        fourier_transform(&mut s, true, &mut log);
+        let mut t = delayed();
+        t.real[..21].fill(0.0);
+        t.imag[..21].fill(0.0);
+        fourier_transform(&mut t, true, &mut log);
+        correct_group_delay(&mut t, delay, &mut log).unwrap();
+        let dot: f64 = s.real.iter().zip(&t.real).map(|(a, b)| a * b).sum();
+        let norm = |v: &[f64]| v.iter().map(|a| a * a).sum::<f64>().sqrt();
+        assert!(dot / (norm(&s.real) * norm(&t.real)) > 0.99);
+        assert!(log.entries.last().unwrap().nmrpipe_command.starts_with("nmrPipe -fn PS"));
+
+        assert!(correct_group_delay(&mut t, 0.0, &mut log).is_err());
     }
 
-    // Sort by ppm descending (NMR convention)
-    multiplets.sort_by(|a, b| b.center_ppm.partial_cmp(&a.center_ppm).unwrap());
-    multiplets
-}
+    #[test]
+    fn test_reference_deconvolution_removes_shared_distortion() {
+        // Two lines sharing a lineshape with a shoulder (poor shimming),
+        // and the same two lines as ideal Lorentzians
+        let n = 8192;
+        let fid = |distorted: bool| -> SpectrumData {
+            let (mut real, mut imag) = (Vec::with_capacity(n), Vec::with_capacity(n));
+            for i in 0..n {
+                let t = i as f64;
+                let shape = if distorted {
+                    num_complex::Complex::new(0.7, 0.0) + 0.3 * num_complex::Complex::from_polar(1.0, 0.02 * t)
+                } else {
+                    num_complex::Complex::new(1.0, 0.0)
+                };
+                let z = (num_complex::Complex::from_polar(1.0, 0.9 * t) + 0.5 * num_complex::Complex::from_polar(1.0, -1.7 * t))
+                    * shape
+                    * (-t / 400.0).exp();
+                real.push(z.re);
+                imag.push(z.im);
+            }
+            SpectrumData {
+                real,
+                imag,
+                axes: vec![AxisParams { num_points: n, spectral_width_hz: 4000.0, reference_ppm: 10.0, ..Default::default() }],
+                ..Default::default()
+            }
+        };
+        let mut log = ReproLog::new();
+        let (mut s, mut ideal) = (fid(true), fid(false));
+        fourier_transform(&mut s, true, &mut log);
+        fourier_transform(&mut ideal, true, &mut log);
 
-// =========================================================================
-//  Integration
-// =========================================================================
+        let ax = s.axes[0].clone();
+        let top = |s: &SpectrumData, range: std::ops::Range<usize>| range.max_by(|&a, &b| s.real[a].total_cmp(&s.real[b])).unwrap();
+        let (reference, analyte) = (top(&ideal, 0..n / 2), top(&ideal, n / 2..n));
+        let misfit = |s: &SpectrumData| {
+            let window = analyte - 100..analyte + 100;
+            window.map(|i| (s.real[i] - ideal.real[i]).abs()).fold(0.0, f64::max) / ideal.real[analyte]
+        };
+        assert!(misfit(&s) > 0.2);
 
-/// Integrate the spectrum between two ppm values (trapezoidal sum).
-/// Returns the raw integral value — ratios between regions are what matter.
-pub fn integrate_region(spectrum: &SpectrumData, start_ppm: f64, end_ppm: f64) -> f64 {
-    if spectrum.axes.is_empty() || spectrum.real.is_empty() {
-        return 0.0;
-    }
+        // Target width of the undistorted decay: 1 / (π · 400 dwell times)
+        let lw = 4000.0 / (400.0 * std::f64::consts::PI);
+        let found = reference_deconvolution(&mut s, ax.index_to_ppm(reference), 0.2, lw, &mut log).unwrap();
+        assert!((found - ax.index_to_ppm(reference)).abs() < ax.ppm_per_point());
+        assert!(misfit(&s) < 0.05, "misfit {}", misfit(&s));
+        assert_eq!(log.entries.last().unwrap().operation, "Reference Deconvolution");
 
-    let ppm_scale = if spectrum.is_frequency_domain && !spectrum.axes.is_empty() {
-        spectrum.axes[0].ppm_scale()
-    } else {
-        (0..spectrum.real.len()).map(|i| i as f64).collect()
-    };
+        // An empty window or a window without a line top is refused
+        assert!(reference_deconvolution(&mut s, 50.0, 0.2, lw, &mut log).is_err());
+        let edge = ax.index_to_ppm(reference) + 0.15;
+        assert!(reference_deconvolution(&mut ideal, edge, 0.2, lw, &mut log).is_err());
+    }
 
-    let lo = start_ppm.min(end_ppm);
-    let hi = start_ppm.max(end_ppm);
+    #[test]
+    fn test_solvent_suppression_of_picked_regions() {
+        // 10 → 0 ppm over 1001 points: index i sits at 10 − i/100 ppm
+        let n = 1001;
+        let mut s = SpectrumData {
+            real: vec![1.0; n],
+            imag: vec![1.0; n],
+            axes: vec![AxisParams {
+                num_points: n,
+                spectral_width_hz: 10010.0,
+                observe_freq_mhz: 1001.0,
+                reference_ppm: 10.0,
+                ..Default::default()
+            }],
+            is_frequency_domain: true,
+            ..Default::default()
+        };
+        assert_eq!(solvent_suppression_factor(4.7, 4.7, 0.2), 0.0);
+        assert!((solvent_suppression_factor(4.8, 4.7, 0.2) - 1.0).abs() < 1e-12);
+        assert_eq!(solvent_suppression_factor(5.0, 4.7, 0.2), 1.0);
 
-    let mut integral = 0.0;
-    for i in 0..spectrum.real.len().min(ppm_scale.len()) {
-        if ppm_scale[i] >= lo && ppm_scale[i] <= hi {
-            integral += spectrum.real[i];
+        // Regions as dragged, in either direction
+        let mut log = ReproLog::new();
+        solvent_suppress_regions(&mut s, &[(4.6, 4.8), (2.6, 2.4)], &mut log);
+        let at = |ppm: f64| ((10.0 - ppm) * 100.0).round() as usize;
+        for ppm in [4.7, 2.5] {
+            assert_eq!(s.real[at(ppm)], 0.0);
+            assert_eq!(s.imag[at(ppm)], 0.0);
+        }
+        let edge = s.real[at(4.79)];
+        assert!(edge > 0.0 && edge < 1.0, "edge {}", edge);
+        for ppm in [7.26, 4.5, 3.3, 1.0] {
+            assert_eq!(s.real[at(ppm)], 1.0);
         }
+        assert_eq!(log.entries.len(), 2);
+        assert!(log.entries.iter().all(|e| e.operation == "Solvent Suppression"));
     }
 
-    integral
-}
-
-// =========================================================================
-//  Solvent Suppression
-// =========================================================================
+    #[test]
+    fn test_peak_tracking_through_a_titration() {
+        // Two amide peaks walk towards each other, the 8.10 ppm one
+        // broadening out at the third point, and a new peak appears
+        let series = vec![
+            vec![[8.30, 1.0], [8.10, 1.0], [7.50, 1.0]],
+            vec![[8.26, 1.0], [8.13, 1.0], [7.50, 1.0]],
+            vec![[8.22, 1.0], [7.50, 1.0], [6.80, 1.0]],
+            vec![[8.18, 1.0], [8.16, 1.0], [7.51, 1.0], [6.80, 1.0]],
+        ];
+        let tracks = track_peaks(&series, 0.05);
+        let shifts: Vec<_> = tracks.iter().map(|t| t.shifts.clone()).collect();
+        assert_eq!(
+            shifts,
+            [
+                vec![Some(8.30), Some(8.26), Some(8.22), Some(8.18)],
+                // Lost at the third point, found again from its last shift
+                vec![Some(8.10), Some(8.13), None, Some(8.16)],
+                vec![Some(7.50), Some(7.50), Some(7.50), Some(7.51)],
+                vec![None, None, Some(6.80), Some(6.80)],
+            ]
+        );
 
-/// Suppress solvent signal by zeroing a region around the specified ppm
-pub fn solvent_suppress(
-    spectrum: &mut SpectrumData,
-    center_ppm: f64,
-    width_ppm: f64,
-    log: &mut ReproLog,
-) {
-    if !spectrum.is_frequency_domain {
-        log::warn!("Solvent suppression should be applied in frequency domain");
-        return;
+        let deltas = tracks[1].perturbations();
+        assert!((deltas[3].unwrap() - 0.06).abs() < 1e-12);
+        assert_eq!(deltas[2], None);
+        assert!((tracks[0].max_perturbation() - 0.12).abs() < 1e-12);
+        assert_eq!(tracks[3].perturbations(), [None, None, Some(0.0), Some(0.0)]);
+        assert!(track_peaks(&[], 0.05).is_empty());
     }
 
-    let n = spectrum.real.len();
-    if n == 0 {
-        return;
+    #[test]
+    fn test_calibrate_f1_moves_only_the_indirect_axis() {
+        let axis = |sw: f64, obs: f64, reference_ppm: f64| AxisParams {
+            num_points: 64,
+            spectral_width_hz: sw,
+            observe_freq_mhz: obs,
+            reference_ppm,
+            ..Default::default()
+        };
+        let mut s = SpectrumData {
+            dimensionality: Dimensionality::TwoD,
+            is_frequency_domain: true,
+            data_2d: vec![vec![0.0; 64]; 64],
+            axes: vec![axis(4000.0, 400.0, 10.0), axis(20000.0, 100.0, 165.0)],
+            ..Default::default()
+        };
+        let mut log = ReproLog::new();
+        // CDCl3 seen at 72.1 ppm in F1
+        assert_eq!(calibrate_f1(&mut s, 72.1, 77.16, &mut log), Some(77.16 - 72.1));
+        assert!((s.axes[1].reference_ppm - (165.0 + 5.06)).abs() < 1e-9);
+        assert_eq!(s.axes[0].reference_ppm, 10.0);
+        assert_eq!(log.len(), 1);
+
+        s.dimensionality = Dimensionality::OneD;
+        assert_eq!(calibrate_f1(&mut s, 1.0, 2.0, &mut log), None);
     }
 
-    if let Some(ax) = spectrum.axes.first() {
-        let low_ppm = center_ppm - width_ppm / 2.0;
-        let high_ppm = center_ppm + width_ppm / 2.0;
+    #[test]
+    fn test_solvent_and_impurity_peaks_are_flagged() {
+        // CDCl3 residual, water and silicone grease; the product peak is left alone
+        let peaks = [[7.27, 5.0], [7.05, 9.0], [1.56, 2.0], [0.07, 1.0]];
+        let found = annotate_solvent_and_impurities(&peaks, "CHLOROFORM-D", &Nucleus::H1);
+        let at = |found: &[(f64, String)], ppm: f64| found.iter().find(|(p, _)| *p == ppm).map(|(_, n)| n.clone());
+        assert_eq!(at(&found, 7.27).as_deref(), Some("CDCl3 (solvent)"));
+        assert_eq!(at(&found, 1.56).as_deref(), Some("H2O"));
+        assert_eq!(at(&found, 0.07).as_deref(), Some("silicone grease"));
+        assert_eq!(at(&found, 7.05), None);
+        assert!(found.windows(2).all(|w| w[0].0 > w[1].0));
 
-        for i in 0..n {
-            let ppm = ax.index_to_ppm(i);
-            if ppm >= low_ppm && ppm <= high_ppm {
-                // Smooth transition using cosine window at edges
-                let dist_from_center = (ppm - center_ppm).abs();
-                let half_width = width_ppm / 2.0;
-                if dist_from_center > half_width * 0.8 {
-                    let edge_frac = (dist_from_center - half_width * 0.8) / (half_width * 0.2);
-                    let factor = (edge_frac * PI / 2.0).sin();
-                    spectrum.real[i] *= factor;
-                    if i < spectrum.imag.len() {
-                        spectrum.imag[i] *= factor;
-                    }
-                } else {
-                    spectrum.real[i] = 0.0;
-                    if i < spectrum.imag.len() {
-                        spectrum.imag[i] = 0.0;
-                    }
-                }
-            }
-        }
+        // 13C: the solvent triplet and grease
+        let carbon = [[77.48, 1.0], [77.16, 1.0], [76.84, 1.0], [29.75, 2.0], [45.0, 3.0]];
+        let found = annotate_solvent_and_impurities(&carbon, "CDCl3", &Nucleus::C13);
+        assert_eq!(found.iter().filter(|(_, n)| n == "CDCl3 (solvent)").count(), 3);
+        assert_eq!(at(&found, 29.75).as_deref(), Some("grease"));
+        assert_eq!(at(&found, 45.0), None);
+
+        // HDO in DMSO-d6; nothing for an unknown solvent or nucleus
+        let dmso = annotate_solvent_and_impurities(&[[3.33, 1.0], [2.50, 1.0]], "DMSO", &Nucleus::H1);
+        assert_eq!(at(&dmso, 3.33).as_deref(), Some("H2O"));
+        assert_eq!(at(&dmso, 2.50).as_deref(), Some("DMSO-d6 (solvent)"));
+        assert!(annotate_solvent_and_impurities(&peaks, "", &Nucleus::H1).is_empty());
+        assert!(annotate_solvent_and_impurities(&peaks, "CDCl3", &Nucleus::P31).is_empty());
     }
 
-    let nmrpipe_cmd = format!(
-        "nmrPipe -fn SOL -fl {} -fs {}",
-        (width_ppm * 100.0) as i32,
-        16
-    );
-    log.add_entry(
-        "Solvent Suppression",
-        &format!("Suppressed region: {:.2} ± {:.2} ppm", center_ppm, width_ppm / 2.0),
-        &nmrpipe_cmd,
-    );
-}
+    #[test]
+    fn test_memory_estimate_of_steps_that_grow_the_data() {
+        use crate::data::storage::MemoryBudget;
 
-// =========================================================================
-//  NMRPipe Subprocess Execution
-// =========================================================================
+        let fid = SpectrumData { real: vec![1.0; 1000], imag: vec![0.0; 1000], ..Default::default() };
+        assert_eq!(fid.heap_bytes(), 16_000);
+        assert_eq!(estimated_heap_bytes(&fid, &ProcessingOp::ZeroFill { target_size: 4000 }), 64_000);
+        assert_eq!(estimated_heap_bytes(&fid, &ProcessingOp::AutoPhase), 16_000);
 
-/// Execute a processing operation via NMRPipe subprocess
-/// This is used when NMRPipe is available and the user prefers it
-pub fn execute_via_nmrpipe(
-    input_path: &Path,
-    output_path: &Path,
-    function_name: &str,
-    params: &[(&str, &str)],
-    log: &mut ReproLog,
-) -> io::Result<()> {
-    let mut cmd = NmrPipeCommand::new("nmrPipe")
-        .arg("-in")
-        .arg(&input_path.to_string_lossy())
-        .arg("-fn")
-        .arg(function_name);
+        // 100 × 1000 complex rows: the FT pads both dimensions to 128 × 1024
+        let ser = SpectrumData {
+            dimensionality: Dimensionality::TwoD,
+            data_2d: vec![vec![0.0; 1000]; 100],
+            data_2d_imag: vec![vec![0.0; 1000]; 100],
+            ..Default::default()
+        };
+        let ft = ProcessingOp::FourierTransform2D { mode: Ft2dMode::PhaseSensitive };
+        assert_eq!(estimated_heap_bytes(&ser, &ft), 2 * 128 * 1024 * 8);
+        let lp = ProcessingOp::LinearPrediction { order: 8, predicted: 50, mode: LpMode::Forward };
+        assert_eq!(estimated_heap_bytes(&ser, &lp), ser.heap_bytes() + 2 * 50 * 1000 * 8);
 
-    for (key, val) in params {
-        cmd = cmd.arg(key).arg(val);
+        // 25 % NUS on a 200-increment grid of States pairs
+        let nus = SpectrumData {
+            nus_schedule: Some(NusSchedule {
+                sampled: (0..50).map(|i| i * 4).collect(),
+                grid: 200,
+                rows_per_increment: 2,
+                source: String::new(),
+            }),
+            ..ser.clone()
+        };
+        let ist = ProcessingOp::NusReconstruction { iterations: 10 };
+        assert_eq!(estimated_heap_bytes(&nus, &ist), 2 * 400 * 1000 * 8);
+
+        let budget = MemoryBudget { warn: true, budget_mb: 1 };
+        assert!(!budget.is_exceeded_by(1024 * 1024));
+        assert!(budget.is_exceeded_by(1024 * 1024 + 1));
+        assert!(!MemoryBudget { warn: false, ..budget }.is_exceeded_by(usize::MAX));
     }
 
-    cmd = cmd
-        .arg("-out")
-        .arg(&output_path.to_string_lossy())
-        .arg("-ov");
+    #[test]
+    fn test_dept_and_apt_carbon_types() {
+        // DEPT-135 of ethyl acetate's CH3 (14.2, 21.0) and CH2 (60.4), plus
+        // an isopropyl CH (25.0)
+        let dept135 = [[60.4, -0.8], [25.0, 0.9], [21.0, 1.0], [14.2, 0.7]];
+        let types = |carbons: &[CarbonPeak]| carbons.iter().map(|c| c.carbon).collect::<Vec<_>>();
+        let carbons = classify_carbons(&dept135, SpectralEditing::Dept135, false, None, 0.2);
+        assert_eq!(
+            types(&carbons),
+            vec![CarbonType::Ch2, CarbonType::ChOrCh3, CarbonType::ChOrCh3, CarbonType::ChOrCh3]
+        );
+        assert_eq!(carbon_type_summary(&carbons), "1 CH2, 3 CH/CH3");
 
-    log.add_entry(
-        &format!("NMRPipe: {}", function_name),
-        &format!("Executing via NMRPipe subprocess"),
-        &cmd.to_command_string(),
-    );
+        // The DEPT-90 keeps the CH; a weak CH3 leak stays CH3
+        let dept90 = [[25.05, 1.0], [21.0, 0.05]];
+        let carbons = classify_carbons(&dept135, SpectralEditing::Dept135, false, Some(&dept90), 0.2);
+        assert_eq!(types(&carbons), vec![CarbonType::Ch2, CarbonType::Ch, CarbonType::Ch3, CarbonType::Ch3]);
+        assert_eq!(carbons[1].dept90, Some(1.0));
+        assert_eq!(carbons[0].dept90, None);
+        assert_eq!(carbon_type_summary(&carbons), "1 CH, 1 CH2, 2 CH3");
 
-    let result = cmd.execute()?;
-    if !result.success {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!("NMRPipe execution failed: {}", result.stderr),
-        ));
+        // APT: CH/CH3 down, C/CH2 up; an inverted phase swaps the groups
+        let apt = [[170.9, 0.5], [60.4, 0.8], [21.0, -1.0]];
+        let carbons = classify_carbons(&apt, SpectralEditing::Apt, false, None, 0.2);
+        assert_eq!(types(&carbons), vec![CarbonType::COrCh2, CarbonType::COrCh2, CarbonType::ChOrCh3]);
+        let carbons = classify_carbons(&apt, SpectralEditing::Apt, true, None, 0.2);
+        assert_eq!(types(&carbons), vec![CarbonType::ChOrCh3, CarbonType::ChOrCh3, CarbonType::COrCh2]);
+
+        // The experiment comes from the type or the pulse program; a
+        // DEPT-90 (detected as DEPT by its name) is not an editing spectrum
+        let spectrum = |experiment: ExperimentType, pulse_program: &str| {
+            let mut s = SpectrumData { experiment_type: experiment, ..Default::default() };
+            s.metadata.insert(META_PULSE_PROGRAM.to_string(), pulse_program.to_string());
+            s
+        };
+        let dept = spectrum(ExperimentType::Dept135, "dept135");
+        assert_eq!(SpectralEditing::detect(&dept), Some(SpectralEditing::Dept135));
+        assert_eq!(SpectralEditing::detect(&spectrum(ExperimentType::Carbon, "jmod")), Some(SpectralEditing::Apt));
+        assert_eq!(SpectralEditing::detect(&spectrum(ExperimentType::Carbon, "zgpg30")), None);
+        let dept90 = spectrum(ExperimentType::Dept135, "dept90");
+        assert!(is_dept90(&dept90) && !is_dept90(&dept));
+        assert_eq!(SpectralEditing::detect(&dept90), None);
+        let named = SpectrumData { sample_name: "EtOAc DEPT-90".to_string(), ..Default::default() };
+        assert!(is_dept90(&named));
     }
-    Ok(())
 }
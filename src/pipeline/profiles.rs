@@ -0,0 +1,187 @@
+//! Processing profiles: the default recipe for each experiment type.
+//!
+//! A profile binds an [`ExperimentType`] to window function, zero filling,
+//! transform and correction settings.  "Quick Process" in the pipeline panel
+//! and batch conversion look up the profile of each dataset's experiment
+//! type, so a folder of mixed 1H, 13C and 2D data gets the right recipe
+//! without picking one by hand.  Profiles are edited in the Preferences
+//! window and stored with the other preferences.
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::spectrum::{experiment_dimensionality, Dimensionality, ExperimentType, SpectrumData};
+use crate::log::reproducibility::ReproLog;
+use super::processing::{self, BaselineMethod, Ft2dMode, WindowFunction};
+
+/// Processing recipe for one experiment type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessingProfile {
+    pub experiment: ExperimentType,
+    /// Apodization of 1D FIDs (2D data is transformed without a window)
+    pub window: WindowFunction,
+    /// Zero-fill doublings beyond the next power of two (1D)
+    pub zero_fill_doublings: u32,
+    /// Automatic phase correction after the transform (1D)
+    pub auto_phase: bool,
+    /// Automatic baseline correction after phasing (1D)
+    pub baseline: bool,
+    /// What the 2D transform keeps (2D)
+    pub ft2d_mode: Ft2dMode,
+}
+
+impl ProcessingProfile {
+    /// Built-in recipe for an experiment type
+    pub fn default_for(experiment: ExperimentType) -> Self {
+        let window = match experiment {
+            ExperimentType::Proton => WindowFunction::Exponential { lb_hz: 0.3 },
+            ExperimentType::Cosy | ExperimentType::Hmbc => {
+                WindowFunction::SineBell { power: 1.0, offset: 0.0, end: 1.0 }
+            }
+            ExperimentType::Hsqc => WindowFunction::SineBell { power: 2.0, offset: 0.5, end: 1.0 },
+            _ => WindowFunction::Exponential { lb_hz: 1.0 },
+        };
+        Self {
+            ft2d_mode: Ft2dMode::default_for(&experiment),
+            experiment,
+            window,
+            zero_fill_doublings: 1,
+            auto_phase: true,
+            baseline: true,
+        }
+    }
+
+    /// Whether the profile is for a 2D experiment
+    pub fn is_2d(&self) -> bool {
+        experiment_dimensionality(&self.experiment) == Dimensionality::TwoD
+    }
+}
+
+/// Processing profiles as stored in the preferences
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessingProfiles {
+    pub profiles: Vec<ProcessingProfile>,
+    /// Batch conversion also writes a processed spectrum for every dataset
+    /// with a profile
+    pub batch_process: bool,
+}
+
+impl Default for ProcessingProfiles {
+    fn default() -> Self {
+        Self {
+            profiles: [
+                ExperimentType::Proton,
+                ExperimentType::Carbon,
+                ExperimentType::Dept135,
+                ExperimentType::Cosy,
+                ExperimentType::Hsqc,
+                ExperimentType::Hmbc,
+            ]
+            .into_iter()
+            .map(ProcessingProfile::default_for)
+            .collect(),
+            batch_process: true,
+        }
+    }
+}
+
+impl ProcessingProfiles {
+    /// Profile bound to `experiment`, if any
+    pub fn profile_for(&self, experiment: &ExperimentType) -> Option<&ProcessingProfile> {
+        self.profiles.iter().find(|p| &p.experiment == experiment)
+    }
+}
+
+/// Experiment types a profile can be bound to
+pub const PROFILE_EXPERIMENTS: [ExperimentType; 7] = [
+    ExperimentType::Proton,
+    ExperimentType::Deuterium,
+    ExperimentType::Carbon,
+    ExperimentType::Dept135,
+    ExperimentType::Cosy,
+    ExperimentType::Hsqc,
+    ExperimentType::Hmbc,
+];
+
+/// Run a profile on a time-domain spectrum: window, zero fill and FT, then
+/// auto phase and baseline for 1D data.  Each step is logged as usual.
+///
+/// Returns `false` (and leaves the data alone) for frequency-domain data.
+pub fn apply_profile(spectrum: &mut SpectrumData, profile: &ProcessingProfile, log: &mut ReproLog) -> bool {
+    if spectrum.is_frequency_domain {
+        return false;
+    }
+    log.add_entry(
+        "Processing Profile",
+        &format!("Processing with the {} profile", profile.experiment),
+        "",
+    );
+    if spectrum.is_2d() {
+        // NUS data only transforms once the skipped increments are rebuilt
+        if spectrum.nus_schedule.is_some() {
+            if let Err(e) = processing::nus_reconstruct(spectrum, processing::NUS_DEFAULT_ITERATIONS, log) {
+                log::warn!("NUS reconstruction skipped: {}", e);
+            }
+        }
+        processing::fourier_transform_2d(spectrum, profile.ft2d_mode, log);
+        return true;
+    }
+    processing::apply_apodization(spectrum, &profile.window, log);
+    let size = processing::next_power_of_two(spectrum.real.len()) << profile.zero_fill_doublings;
+    processing::zero_fill(spectrum, size, log);
+    let use_imaginary = !spectrum.imag.is_empty();
+    processing::fourier_transform(spectrum, use_imaginary, log);
+    if profile.auto_phase {
+        processing::auto_phase(spectrum, log);
+    }
+    if profile.baseline {
+        processing::baseline_correct(spectrum, &BaselineMethod::Linear, log);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_processing_profile_per_experiment() {
+        use crate::data::spectrum::AxisParams;
+
+        let mut prefs = ProcessingProfiles::default();
+        assert_eq!(prefs.profile_for(&ExperimentType::Cosy).unwrap().ft2d_mode, Ft2dMode::Magnitude);
+        assert!(prefs.profile_for(&ExperimentType::Other("NOESY".into())).is_none());
+        prefs.profiles[0].window = WindowFunction::Exponential { lb_hz: 2.0 };
+        prefs.profiles[0].zero_fill_doublings = 2;
+        prefs.profiles[0].baseline = false;
+        let json = serde_json::to_string(&prefs).unwrap();
+        let prefs: ProcessingProfiles = serde_json::from_str(&json).unwrap();
+        let proton = prefs.profile_for(&ExperimentType::Proton).unwrap();
+        assert_eq!(proton.window, WindowFunction::Exponential { lb_hz: 2.0 });
+
+        // One damped complex line: 1000 points → 1024, two doublings → 4096
+        let fid: Vec<(f64, f64)> = (0..1000)
+            .map(|i| {
+                let t = i as f64;
+                let a = (-t / 150.0).exp();
+                (a * (0.4 * t).cos(), a * (0.4 * t).sin())
+            })
+            .collect();
+        let mut s = SpectrumData {
+            experiment_type: ExperimentType::Proton,
+            real: fid.iter().map(|p| p.0).collect(),
+            imag: fid.iter().map(|p| p.1).collect(),
+            axes: vec![AxisParams { num_points: 1000, spectral_width_hz: 5000.0, ..Default::default() }],
+            ..Default::default()
+        };
+        let mut log = ReproLog::new();
+        assert!(apply_profile(&mut s, proton, &mut log));
+        assert!(s.is_frequency_domain);
+        assert_eq!(s.real.len(), 4096);
+        let ops: Vec<&str> = log.entries.iter().map(|e| e.operation.as_str()).collect();
+        assert!(ops.iter().any(|o| o.starts_with("Apodization: EM")));
+        assert!(!ops.iter().any(|o| o.contains("Baseline")));
+        // Already transformed: nothing to do
+        assert!(!apply_profile(&mut s, proton, &mut log));
+    }
+}
@@ -0,0 +1,110 @@
+//! Project-relative source paths and relinking of moved data.
+//!
+//! Projects store the source path relative to the project file alongside
+//! the absolute one, so a project folder can be moved together with its
+//! data.  When neither resolves, the user picks the folder the data now
+//! lives in and every path under the old location is rebased onto it.
+
+use std::path::{Component, Path, PathBuf};
+
+/// `path` relative to the directory `base`, e.g. `../data/10`.
+///
+/// Returns `None` when either path is relative or they share no root
+/// (different drives on Windows).
+pub fn relative_to(path: &Path, base: &Path) -> Option<PathBuf> {
+    if !path.is_absolute() || !base.is_absolute() {
+        return None;
+    }
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    if path.first() != base.first() {
+        return None;
+    }
+
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    for c in &path[common..] {
+        relative.push(c.as_os_str());
+    }
+    Some(relative)
+}
+
+/// Where a saved source path points now: the project-relative path if it
+/// resolves, otherwise the absolute path if that still exists.
+pub fn resolve_source(project_file: &Path, absolute: &Path, relative: Option<&Path>) -> Option<PathBuf> {
+    let project_dir = project_file.parent().unwrap_or(Path::new("."));
+    relative
+        .map(|r| project_dir.join(r))
+        .filter(|p| p.exists())
+        .or_else(|| absolute.exists().then(|| absolute.to_path_buf()))
+}
+
+/// Find the missing `source` inside the folder the user picked.
+///
+/// Accepts either the dataset itself (same name as the missing source) or
+/// the folder containing it.
+pub fn relink_candidate(missing: &Path, picked: &Path) -> Option<PathBuf> {
+    let name = missing.file_name()?;
+    if picked.file_name() == Some(name) && picked.exists() {
+        return Some(picked.to_path_buf());
+    }
+    let inside = picked.join(name);
+    inside.exists().then_some(inside)
+}
+
+/// Move `path` from under `old_root` to under `new_root`.  Paths outside
+/// `old_root` are returned unchanged.
+pub fn rebase(path: &Path, old_root: &Path, new_root: &Path) -> PathBuf {
+    match path.strip_prefix(old_root) {
+        Ok(rest) if rest.as_os_str().is_empty() => new_root.to_path_buf(),
+        Ok(rest) => new_root.join(rest),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_project_relative_source_and_relink() {
+        use std::fs;
+        use std::path::PathBuf;
+
+        assert_eq!(
+            relative_to(Path::new("/lab/data/run1/10"), Path::new("/lab/projects")),
+            Some(PathBuf::from("../data/run1/10"))
+        );
+        assert_eq!(relative_to(Path::new("data/10"), Path::new("/lab")), None);
+        assert_eq!(
+            rebase(Path::new("/old/run1_nmrpipe/run1.fid"), Path::new("/old"), Path::new("/new")),
+            PathBuf::from("/new/run1_nmrpipe/run1.fid")
+        );
+        assert_eq!(rebase(Path::new("/elsewhere/x"), Path::new("/old"), Path::new("/new")), PathBuf::from("/elsewhere/x"));
+
+        // Project folder moved together with its data: the relative path wins
+        let root = std::env::temp_dir().join(format!("nmr_relink_test_{}", uuid::Uuid::new_v4()));
+        let data = root.join("moved").join("data").join("sample.jdf");
+        fs::create_dir_all(data.parent().unwrap()).unwrap();
+        fs::write(&data, b"JEOL").unwrap();
+        let project = root.join("moved").join("proj").join("sample.nmrproj");
+        fs::create_dir_all(project.parent().unwrap()).unwrap();
+        let stale = Path::new("/nonexistent/data/sample.jdf");
+        let relative = Path::new("../data/sample.jdf");
+        let found = resolve_source(&project, stale, Some(relative)).unwrap();
+        assert!(found.ends_with("proj/../data/sample.jdf"));
+        assert_eq!(resolve_source(&project, stale, None), None);
+
+        // Either the containing folder or the dataset itself can be picked
+        let folder = data.parent().unwrap();
+        assert_eq!(relink_candidate(stale, folder), Some(data.clone()));
+        assert_eq!(relink_candidate(stale, &data), Some(data.clone()));
+        assert_eq!(relink_candidate(stale, &root), None);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,387 @@
+//! Headless batch processing from a declarative TOML script.
+//!
+//! `nmr_gui --batch script.toml` loads every dataset listed under `inputs`
+//! (folders are searched for JEOL files and Bruker experiments, as for
+//! batch conversion), runs the steps of `[processing]` in pipeline order
+//! and writes the `export` targets to `output_dir`.  Steps that are not
+//! listed are not run, so a script without `[processing]` only converts.
+//!
+//! ```toml
+//! inputs = ["/data/2024-06", "extra/sample.jdf"]
+//! output_dir = "processed"
+//! export = ["nmrpipe", "png", "csv", "log"]
+//!
+//! [processing]
+//! window = { Exponential = { lb_hz = 0.3 } }
+//! zero_fill = 1
+//! ft = true
+//! auto_phase = true
+//! baseline = true
+//! baseline_method = { Polynomial = { order = 3 } }
+//! ```
+//!
+//! Relative paths are taken from the script's folder.  Window, zero fill,
+//! phasing and baseline apply to 1D data; 2D data is reconstructed (NUS)
+//! and transformed only.  Every dataset keeps its own reproducibility log.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use nmrpipe_io::batch::{bruker_output_name, check_unique_outputs, BatchJob};
+use serde::Deserialize;
+
+use crate::data::nmrpipe_format;
+use crate::data::spectrum::{F1Acquisition, SpectrumData, VendorFormat};
+use crate::error::{NmrError, NmrResult};
+use crate::gui::conversion_dialog::ConversionSettings;
+use crate::gui::export_dialog::ExportSettings;
+use crate::gui::plot_scene;
+use crate::gui::spectrum_view::SpectrumViewState;
+use crate::log::reproducibility::ReproLog;
+use super::batch;
+use super::conversion;
+use super::processing::{self, BaselineMethod, Ft2dMode, WindowFunction};
+
+/// A batch script as read from TOML
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchScript {
+    /// Datasets, or folders to search for them
+    pub inputs: Vec<PathBuf>,
+    /// Folder the exports are written to (created if missing)
+    #[serde(default = "default_output_dir")]
+    pub output_dir: PathBuf,
+    #[serde(default)]
+    pub processing: ScriptProcessing,
+    /// What is written for each dataset
+    #[serde(default = "default_exports")]
+    pub export: Vec<ScriptExport>,
+}
+
+fn default_output_dir() -> PathBuf {
+    PathBuf::from("processed")
+}
+
+fn default_exports() -> Vec<ScriptExport> {
+    vec![ScriptExport::Nmrpipe, ScriptExport::Log]
+}
+
+/// Processing steps of a script, run in this order when present
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScriptProcessing {
+    /// Apodization of 1D FIDs
+    pub window: Option<WindowFunction>,
+    /// Zero-fill doublings beyond the next power of two (1D)
+    pub zero_fill: Option<u32>,
+    /// Fourier transform of time-domain data
+    pub ft: bool,
+    /// What the 2D transform keeps; by default that of the experiment type
+    pub ft2d_mode: Option<Ft2dMode>,
+    /// t1 sampling of 2D data; by default that read from the dataset
+    pub f1_acquisition: Option<F1Acquisition>,
+    /// Automatic phase correction (1D)
+    pub auto_phase: bool,
+    /// Fixed [PH0, PH1] in degrees, applied after any automatic phasing (1D)
+    pub phase: Option<[f64; 2]>,
+    /// Automatic baseline correction (1D)
+    pub baseline: bool,
+    /// Model of the baseline correction; linear by default
+    pub baseline_method: Option<BaselineMethod>,
+}
+
+/// Files a script can write per dataset, named after it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptExport {
+    /// NMRPipe data: `.fid`, or `.ft1` / `.ft2` once transformed
+    Nmrpipe,
+    /// Figure with the Export tab's default settings (1D)
+    Png,
+    Svg,
+    Pdf,
+    /// `ppm,intensity` of the real part (1D)
+    Csv,
+    /// Reproducibility log as text (`_log.txt`)
+    Log,
+    /// Reproducibility log as JSON (`_log.json`)
+    Json,
+    /// Equivalent NMRPipe shell script (`_process.sh`)
+    Script,
+}
+
+impl BatchScript {
+    /// Parse a script, resolving relative paths against `base`
+    pub fn parse(text: &str, base: &Path) -> NmrResult<Self> {
+        let mut script: BatchScript = toml::from_str(text).map_err(|e| NmrError::InvalidInput(e.to_string()))?;
+        if script.inputs.is_empty() {
+            return Err(NmrError::InvalidInput("the script lists no inputs".to_string()));
+        }
+        for path in script.inputs.iter_mut().chain(std::iter::once(&mut script.output_dir)) {
+            if path.is_relative() {
+                *path = base.join(&*path);
+            }
+        }
+        Ok(script)
+    }
+
+    /// Read and parse a script file
+    pub fn load(path: &Path) -> NmrResult<Self> {
+        let text = fs::read_to_string(path).map_err(|e| NmrError::reading(path, e))?;
+        let base = path.parent().unwrap_or(Path::new("."));
+        Self::parse(&text, base).map_err(|e| NmrError::InvalidInput(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Datasets the inputs name, with the output stem of each: folders that
+    /// are not a Bruker or Varian experiment themselves are searched like
+    /// a batch conversion folder
+    pub fn datasets(&self) -> NmrResult<Vec<BatchJob>> {
+        let mut jobs = Vec::new();
+        for input in &self.inputs {
+            if !input.exists() {
+                return Err(NmrError::NotFound(input.clone()));
+            }
+            let format = conversion::detect_format(input);
+            if input.is_dir() && !matches!(format, VendorFormat::Bruker | VendorFormat::Varian) {
+                let found = batch::find_batch_inputs(input);
+                if found.is_empty() {
+                    return Err(NmrError::InvalidInput(format!("no JEOL files or Bruker experiments in {}", input.display())));
+                }
+                jobs.extend(found.jeol_files.into_iter().chain(found.bruker_dirs).map(|p| self.job(p)));
+            } else {
+                jobs.push(self.job(input.clone()));
+            }
+        }
+        check_unique_outputs(&jobs).map_err(NmrError::InvalidInput)?;
+        Ok(jobs)
+    }
+
+    fn job(&self, input: PathBuf) -> BatchJob {
+        let stem = if input.is_dir() {
+            bruker_output_name(&input).trim_end_matches(".fid").to_string()
+        } else {
+            input
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "data".to_string())
+        };
+        BatchJob::new(input, self.output_dir.join(stem))
+    }
+}
+
+/// Run the processing steps of `steps` on a loaded dataset
+pub fn process(spectrum: &mut SpectrumData, steps: &ScriptProcessing, log: &mut ReproLog) {
+    if spectrum.is_2d() {
+        if steps.ft && !spectrum.is_frequency_domain {
+            if spectrum.nus_schedule.is_some() {
+                if let Err(e) = processing::nus_reconstruct(spectrum, processing::NUS_DEFAULT_ITERATIONS, log) {
+                    log::warn!("NUS reconstruction skipped: {}", e);
+                }
+            }
+            let mode = steps.ft2d_mode.unwrap_or_else(|| Ft2dMode::default_for(&spectrum.experiment_type));
+            if let Some(acquisition) = steps.f1_acquisition {
+                spectrum.f1_acquisition = acquisition;
+            }
+            processing::fourier_transform_2d(spectrum, mode, log);
+        }
+        return;
+    }
+    if !spectrum.is_frequency_domain {
+        if let Some(window) = &steps.window {
+            processing::apply_apodization(spectrum, window, log);
+        }
+        if let Some(doublings) = steps.zero_fill {
+            let size = processing::next_power_of_two(spectrum.real.len()) << doublings;
+            processing::zero_fill(spectrum, size, log);
+        }
+        if steps.ft {
+            let use_imaginary = !spectrum.imag.is_empty();
+            processing::fourier_transform(spectrum, use_imaginary, log);
+        }
+    }
+    if !spectrum.is_frequency_domain {
+        return;
+    }
+    if steps.auto_phase {
+        processing::auto_phase(spectrum, log);
+    }
+    if let Some([ph0, ph1]) = steps.phase {
+        processing::phase_correct(spectrum, ph0, ph1, log);
+    }
+    if steps.baseline {
+        processing::baseline_correct(spectrum, &steps.baseline_method.unwrap_or_default(), log);
+    }
+}
+
+/// Write the exports of one processed dataset next to `stem`; returns the
+/// files written
+pub fn export(
+    spectrum: &SpectrumData,
+    log: &ReproLog,
+    targets: &[ScriptExport],
+    stem: &Path,
+) -> NmrResult<Vec<PathBuf>> {
+    let with_suffix = |suffix: &str| {
+        let mut name = stem.as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    };
+    let is_1d_spectrum = spectrum.is_frequency_domain && !spectrum.is_2d();
+    let mut written = Vec::new();
+    for &target in targets {
+        let path = match target {
+            ScriptExport::Nmrpipe => {
+                let ext = match (spectrum.is_frequency_domain, spectrum.is_2d()) {
+                    (false, _) => ".fid",
+                    (true, false) => ".ft1",
+                    (true, true) => ".ft2",
+                };
+                let path = with_suffix(ext);
+                nmrpipe_format::write_nmrpipe_file(spectrum, &path).map_err(|e| NmrError::writing(&path, e))?;
+                path
+            }
+            ScriptExport::Png | ScriptExport::Svg | ScriptExport::Pdf => {
+                if !is_1d_spectrum {
+                    log::info!("Skipping the {:?} figure of {}: 1D spectra only", target, stem.display());
+                    continue;
+                }
+                let path = with_suffix(match target {
+                    ScriptExport::Svg => ".svg",
+                    ScriptExport::Pdf => ".pdf",
+                    _ => ".png",
+                });
+                let scene = plot_scene::build_scene(spectrum, &SpectrumViewState::default(), &ExportSettings::default(), log)?;
+                let bytes = match target {
+                    ScriptExport::Svg => plot_scene::to_svg(&scene).into_bytes(),
+                    ScriptExport::Pdf => plot_scene::to_pdf(&scene),
+                    _ => plot_scene::to_png_bytes(&scene)?,
+                };
+                fs::write(&path, bytes).map_err(|e| NmrError::writing(&path, e))?;
+                path
+            }
+            ScriptExport::Csv => {
+                if !is_1d_spectrum {
+                    log::info!("Skipping the CSV of {}: 1D spectra only", stem.display());
+                    continue;
+                }
+                let mut csv = String::from("ppm,intensity\n");
+                for (ppm, value) in spectrum.x_scale().iter().zip(&spectrum.real) {
+                    csv.push_str(&format!("{:.6},{}\n", ppm, value));
+                }
+                let path = with_suffix(".csv");
+                fs::write(&path, csv).map_err(|e| NmrError::writing(&path, e))?;
+                path
+            }
+            ScriptExport::Log | ScriptExport::Json | ScriptExport::Script => {
+                let (path, result) = match target {
+                    ScriptExport::Log => {
+                        let path = with_suffix("_log.txt");
+                        let result = log.save_text(&path);
+                        (path, result)
+                    }
+                    ScriptExport::Json => {
+                        let path = with_suffix("_log.json");
+                        let result = log.save_json(&path);
+                        (path, result)
+                    }
+                    _ => {
+                        let path = with_suffix("_process.sh");
+                        let result = log.save_script(&path);
+                        (path, result)
+                    }
+                };
+                result.map_err(|e| NmrError::writing(&path, e))?;
+                path
+            }
+        };
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Load, process and export one dataset of a script
+pub fn run_job(script: &BatchScript, job: &BatchJob, settings: &ConversionSettings) -> NmrResult<Vec<PathBuf>> {
+    let mut log = ReproLog::new();
+    log.set_source(&job.input.display().to_string());
+    let mut spectrum = conversion::load_spectrum(&job.input, &mut log, Some(settings))?;
+    let nucleus = spectrum.axes.first().map(|a| a.nucleus.to_string()).unwrap_or_default();
+    log.set_spectrum_info(&nucleus, &spectrum.experiment_type.to_string());
+    process(&mut spectrum, &script.processing, &mut log);
+    export(&spectrum, &log, &script.export, &job.output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_script_processes_and_exports() {
+        use crate::data::spectrum::{AxisParams, SpectrumData};
+
+        let dir = std::env::temp_dir().join(format!("nmr_batch_script_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fid: Vec<(f64, f64)> = (0..1000)
+            .map(|i| {
+                let t = i as f64;
+                let a = (-t / 150.0).exp();
+                (a * (0.3 * t).cos(), a * (0.3 * t).sin())
+            })
+            .collect();
+        let spectrum = SpectrumData {
+            axes: vec![AxisParams {
+                num_points: 1000,
+                spectral_width_hz: 4800.0,
+                observe_freq_mhz: 400.0,
+                reference_ppm: 12.0,
+                ..Default::default()
+            }],
+            real: fid.iter().map(|p| p.0).collect(),
+            imag: fid.iter().map(|p| p.1).collect(),
+            ..Default::default()
+        };
+        nmrpipe_format::write_nmrpipe_file(&spectrum, &dir.join("sample.fid")).unwrap();
+
+        let text = r#"
+            inputs = ["sample.fid"]
+            output_dir = "out"
+            export = ["nmrpipe", "csv", "log", "script"]
+
+            [processing]
+            window = { Exponential = { lb_hz = 1.0 } }
+            zero_fill = 1
+            ft = true
+            auto_phase = true
+            baseline = true
+        "#;
+        let script = BatchScript::parse(text, &dir).unwrap();
+        assert_eq!(script.output_dir, dir.join("out"));
+        assert_eq!(script.export, [ScriptExport::Nmrpipe, ScriptExport::Csv, ScriptExport::Log, ScriptExport::Script]);
+        let jobs = script.datasets().unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].output, dir.join("out").join("sample"));
+
+        std::fs::create_dir_all(&script.output_dir).unwrap();
+        let written = run_job(&script, &jobs[0], &ConversionSettings::default()).unwrap();
+        let names: Vec<String> = written.iter().map(|p| p.file_name().unwrap().to_string_lossy().to_string()).collect();
+        assert_eq!(names, ["sample.ft1", "sample.csv", "sample_log.txt", "sample_process.sh"]);
+        let processed = nmrpipe_format::read_nmrpipe_file(&written[0]).unwrap();
+        assert!(processed.is_frequency_domain);
+        assert_eq!(processed.real.len(), 2048);
+        let log = std::fs::read_to_string(&written[2]).unwrap();
+        for step in ["Apodization", "Zero Fill", "Fourier Transform", "Baseline Correction"] {
+            assert!(log.contains(step), "log lacks {}", step);
+        }
+        let csv = std::fs::read_to_string(&written[1]).unwrap();
+        assert_eq!(csv.lines().count(), 2049);
+
+        // Nothing listed under [processing] only converts; typos are errors
+        let convert_only = BatchScript::parse("inputs = [\"sample.fid\"]", &dir).unwrap();
+        assert_eq!(convert_only.processing, Default::default());
+        assert_eq!(convert_only.export, [ScriptExport::Nmrpipe, ScriptExport::Log]);
+        assert!(BatchScript::parse("inputs = [\"sample.fid\"]\n[processing]\nbaseline_correct = true", &dir).is_err());
+        assert!(BatchScript::parse("inputs = []", &dir).is_err());
+        assert!(BatchScript::parse("inputs = [\"missing.jdf\"]", &dir).unwrap().datasets().is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
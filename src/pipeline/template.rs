@@ -0,0 +1,265 @@
+//! Processing templates: a pipeline saved to be run again.
+//!
+//! A template is the ordered list of [`ProcessingOp`]s applied to a
+//! spectrum, with their parameters, as recorded in the undo history.  It is
+//! saved as JSON and applied to another spectrum, or to every dataset of a
+//! folder, by replaying the steps in order.  Steps that only make sense for
+//! the data they were made on (a hand-placed baseline, a removed glitch)
+//! are left out when the template is made.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::spectrum::SpectrumData;
+use crate::error::{NmrError, NmrResult};
+use crate::gui::conversion_dialog::ConversionSettings;
+use crate::log::reproducibility::ReproLog;
+use super::conversion;
+use super::processing::{self, ProcessingOp};
+use super::profiles::{self, ProcessingProfiles};
+use super::script::{self, BatchScript, ScriptExport, ScriptProcessing};
+
+/// File extension of saved templates
+pub const TEMPLATE_EXTENSION: &str = "nmrtemplate";
+
+/// A saved processing pipeline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineTemplate {
+    pub name: String,
+    /// When it was saved (RFC 3339)
+    pub saved: String,
+    pub steps: Vec<ProcessingOp>,
+}
+
+impl PipelineTemplate {
+    /// Template of the steps in `history`, oldest first.  Returns the
+    /// template and the steps left out because they cannot be replayed.
+    pub fn from_history<'a>(name: &str, history: impl IntoIterator<Item = &'a ProcessingOp>) -> (Self, Vec<String>) {
+        let (steps, skipped): (Vec<&ProcessingOp>, Vec<&ProcessingOp>) =
+            history.into_iter().partition(|op| is_replayable(op));
+        let template = Self {
+            name: name.trim().to_string(),
+            saved: chrono::Local::now().to_rfc3339(),
+            steps: steps.into_iter().cloned().collect(),
+        };
+        (template, skipped.iter().map(|op| op.to_string()).collect())
+    }
+
+    pub fn save(&self, path: &Path) -> NmrResult<()> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| NmrError::writing(path, e))?;
+        fs::write(path, json).map_err(|e| NmrError::writing(path, e))
+    }
+
+    pub fn load(path: &Path) -> NmrResult<Self> {
+        let text = fs::read_to_string(path).map_err(|e| NmrError::reading(path, e))?;
+        serde_json::from_str(&text)
+            .map_err(|e| NmrError::Corrupted { path: path.to_path_buf(), reason: e.to_string() })
+    }
+
+    /// Run every step on `spectrum`, stopping at the first that fails.
+    /// A Quick Process step runs the profile `profiles` holds for the
+    /// experiment type it was recorded with.
+    pub fn apply(&self, spectrum: &mut SpectrumData, profiles: &ProcessingProfiles, log: &mut ReproLog) -> NmrResult<()> {
+        log.add_entry(
+            "Processing Template",
+            &format!("Processing with the template '{}' ({} steps)", self.name, self.steps.len()),
+            "",
+        );
+        for op in &self.steps {
+            replay_step(spectrum, op, profiles, log)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `op` carries everything needed to run it again on other data
+pub fn is_replayable(op: &ProcessingOp) -> bool {
+    match op {
+        ProcessingOp::Template { steps, .. } => steps.iter().all(is_replayable),
+        ProcessingOp::ManualBaselineCorrection { .. }
+        | ProcessingOp::CenterGlitchRemoval { .. }
+        | ProcessingOp::ProcessingSteps
+        | ProcessingOp::NmrPipeScript { .. } => false,
+        _ => true,
+    }
+}
+
+/// Run one recorded step on `spectrum`
+pub fn replay_step(
+    spectrum: &mut SpectrumData,
+    op: &ProcessingOp,
+    profiles: &ProcessingProfiles,
+    log: &mut ReproLog,
+) -> NmrResult<()> {
+    match op {
+        ProcessingOp::Apodization(window) => processing::apply_apodization(spectrum, window, log),
+        ProcessingOp::ZeroFill { target_size } => processing::zero_fill(spectrum, *target_size, log),
+        ProcessingOp::FourierTransform { use_imaginary } => {
+            let use_imaginary = *use_imaginary && !spectrum.imag.is_empty();
+            processing::fourier_transform(spectrum, use_imaginary, log)
+        }
+        ProcessingOp::FourierTransform2D { mode } => {
+            if spectrum.nus_schedule.is_some() {
+                processing::nus_reconstruct(spectrum, processing::NUS_DEFAULT_ITERATIONS, log)?;
+            }
+            processing::fourier_transform_2d(spectrum, *mode, log)
+        }
+        ProcessingOp::InverseFourierTransform => processing::inverse_fourier_transform(spectrum, log),
+        ProcessingOp::PhaseCorrection { ph0, ph1 } => processing::phase_correct(spectrum, *ph0, *ph1, log),
+        ProcessingOp::PhaseCorrection2D { dim, ph0, ph1 } => {
+            processing::phase_correct_2d(spectrum, *dim, *ph0, *ph1, log)?
+        }
+        ProcessingOp::AutoPhase => {
+            processing::auto_phase(spectrum, log);
+        }
+        ProcessingOp::BaselineCorrection { method } => processing::baseline_correct(spectrum, method, log),
+        ProcessingOp::SolventSuppression { center_ppm, width_ppm } => {
+            processing::solvent_suppress(spectrum, *center_ppm, *width_ppm, log)
+        }
+        ProcessingOp::SolventSuppressionRegions { regions } => {
+            processing::solvent_suppress_regions(spectrum, regions, log)
+        }
+        ProcessingOp::ReferenceDeconvolution { center_ppm, width_ppm, target_lw_hz } => {
+            processing::reference_deconvolution(spectrum, *center_ppm, *width_ppm, *target_lw_hz, log)?;
+        }
+        ProcessingOp::ReplaceNonFinite { fix, .. } => {
+            processing::replace_non_finite(spectrum, *fix, log);
+        }
+        ProcessingOp::DeadTimeCorrection { points, mode } => processing::dead_time_correct(spectrum, *points, *mode, log),
+        ProcessingOp::LinearPrediction { order, predicted, mode } => {
+            processing::linear_predict(spectrum, *order, *predicted, *mode, log)?
+        }
+        ProcessingOp::NusReconstruction { iterations } => processing::nus_reconstruct(spectrum, *iterations, log)?,
+        ProcessingOp::GroupDelayCorrection { grpdly } => processing::correct_group_delay(spectrum, *grpdly, log)?,
+        ProcessingOp::T1NoiseSuppression { method, strength } => {
+            processing::suppress_t1_noise(spectrum, *method, *strength, log)
+        }
+        ProcessingOp::Magnitude2D => processing::magnitude_2d(spectrum, log),
+        ProcessingOp::F1Calibration { shift_ppm } => {
+            if processing::calibrate_f1(spectrum, 0.0, *shift_ppm, log).is_none() {
+                return Err(NmrError::NotApplicable("F1 calibration needs 2D data".to_string()));
+            }
+        }
+        ProcessingOp::QuickProcess { experiment } => {
+            let Some(profile) = profiles.profile_for(experiment) else {
+                return Err(NmrError::NotApplicable(format!("no processing profile for {} data", experiment)));
+            };
+            if !profiles::apply_profile(spectrum, profile, log) {
+                return Err(NmrError::NotApplicable("Quick Process needs time-domain data".to_string()));
+            }
+        }
+        ProcessingOp::Template { steps, .. } => {
+            for step in steps {
+                replay_step(spectrum, step, profiles, log)?;
+            }
+        }
+        ProcessingOp::ManualBaselineCorrection { .. }
+        | ProcessingOp::CenterGlitchRemoval { .. }
+        | ProcessingOp::ProcessingSteps
+        | ProcessingOp::NmrPipeScript { .. } => {
+            return Err(NmrError::NotApplicable(format!("{} cannot be replayed", op)));
+        }
+    }
+    Ok(())
+}
+
+/// Outcome of a template run on one dataset of a folder
+#[derive(Debug)]
+pub struct TemplateRun {
+    pub input: PathBuf,
+    /// Files written, or why the dataset was not processed
+    pub result: NmrResult<Vec<PathBuf>>,
+}
+
+/// Apply `template` to every JEOL file and Bruker experiment in `folder`,
+/// writing each processed spectrum and its log to `folder/processed`
+pub fn apply_to_folder(
+    template: &PipelineTemplate,
+    folder: &Path,
+    profiles: &ProcessingProfiles,
+) -> NmrResult<Vec<TemplateRun>> {
+    let batch = BatchScript {
+        inputs: vec![folder.to_path_buf()],
+        output_dir: folder.join("processed"),
+        processing: ScriptProcessing::default(),
+        export: vec![ScriptExport::Nmrpipe, ScriptExport::Log],
+    };
+    let jobs = batch.datasets()?;
+    fs::create_dir_all(&batch.output_dir).map_err(|e| NmrError::writing(&batch.output_dir, e))?;
+    let settings = ConversionSettings::default();
+    Ok(jobs
+        .iter()
+        .map(|job| {
+            let mut log = ReproLog::new();
+            log.set_source(&job.input.display().to_string());
+            let result = conversion::load_spectrum(&job.input, &mut log, Some(&settings))
+                .and_then(|mut spectrum| {
+                    template.apply(&mut spectrum, profiles, &mut log)?;
+                    script::export(&spectrum, &log, &batch.export, &job.output)
+                });
+            TemplateRun { input: job.input.clone(), result }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline_template_replays_the_history() {
+        use crate::data::spectrum::{AxisParams, ExperimentType};
+        use crate::pipeline::processing::{BaselineMethod, WindowFunction};
+
+        let n = 512;
+        let fid = SpectrumData {
+            real: (0..n).map(|i| (i as f64 * 0.3).cos() * (-(i as f64) / 100.0).exp()).collect(),
+            imag: (0..n).map(|i| (i as f64 * 0.3).sin() * (-(i as f64) / 100.0).exp()).collect(),
+            experiment_type: ExperimentType::Proton,
+            axes: vec![AxisParams { num_points: n, spectral_width_hz: 4000.0, observe_freq_mhz: 400.0, ..Default::default() }],
+            ..Default::default()
+        };
+        let history = [
+            ProcessingOp::QuickProcess { experiment: ExperimentType::Proton },
+            ProcessingOp::ManualBaselineCorrection { num_points: 6 },
+            ProcessingOp::PhaseCorrection { ph0: 10.0, ph1: -5.0 },
+            ProcessingOp::BaselineCorrection { method: BaselineMethod::Polynomial { order: 2 } },
+        ];
+        let (saved, skipped) = PipelineTemplate::from_history("1H routine", &history);
+        assert_eq!(saved.steps.len(), 3);
+        assert_eq!(skipped.len(), 1);
+        assert!(!is_replayable(&ProcessingOp::Template { name: String::new(), steps: history.to_vec() }));
+
+        let path = std::env::temp_dir().join(format!("nmr_template_test_{}.nmrtemplate", uuid::Uuid::new_v4()));
+        saved.save(&path).unwrap();
+        let loaded = PipelineTemplate::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.name, "1H routine");
+
+        // Replaying gives what the same calls give
+        let profiles = ProcessingProfiles::default();
+        let mut replayed = fid.clone();
+        let mut log = ReproLog::new();
+        loaded.apply(&mut replayed, &profiles, &mut log).unwrap();
+        let mut direct = fid.clone();
+        let profile = profiles.profile_for(&ExperimentType::Proton).unwrap();
+        profiles::apply_profile(&mut direct, profile, &mut log);
+        processing::phase_correct(&mut direct, 10.0, -5.0, &mut log);
+        processing::baseline_correct(&mut direct, &BaselineMethod::Polynomial { order: 2 }, &mut log);
+        assert!(replayed.is_frequency_domain);
+        assert_eq!(replayed.real, direct.real);
+
+        // Quick Process stops the template on data already transformed
+        let again = PipelineTemplate {
+            name: "again".into(),
+            saved: String::new(),
+            steps: vec![
+                ProcessingOp::Apodization(WindowFunction::Exponential { lb_hz: 1.0 }),
+                ProcessingOp::QuickProcess { experiment: ExperimentType::Proton },
+            ],
+        };
+        assert!(again.apply(&mut replayed, &profiles, &mut log).is_err());
+    }
+}
@@ -0,0 +1,248 @@
+//! Locating NMRPipe tools (nmrPipe, delta2pipe, bruk2pipe, var2pipe).
+//!
+//! NMRPipe ships per-platform binary folders (`nmrbin.linux239_64`,
+//! `nmrbin.mac11_64`, …) that are often not on `PATH` for GUI apps.  Tools
+//! are searched on `PATH`, in `$NMRBIN` / `$NMR_BASE`, and in the usual
+//! install locations for Linux and macOS.  On Windows NMRPipe only runs
+//! inside WSL, so tools found there are invoked via `wsl.exe` with Windows
+//! paths in the arguments translated to `/mnt/<drive>/…`.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+
+/// How to invoke an NMRPipe tool
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolLocation {
+    /// Executable on this machine
+    Native(PathBuf),
+    /// Executable inside WSL (Windows only), given as a Linux path
+    Wsl(String),
+}
+
+impl ToolLocation {
+    /// Build a command for this tool.  For WSL tools, arguments that are
+    /// Windows paths are translated to their WSL equivalents.
+    pub fn command(&self, args: &[String]) -> Command {
+        match self {
+            ToolLocation::Native(path) => {
+                let mut cmd = Command::new(path);
+                cmd.args(args);
+                cmd
+            }
+            ToolLocation::Wsl(linux_path) => {
+                let mut cmd = Command::new("wsl.exe");
+                cmd.arg("-e").arg(linux_path);
+                cmd.args(args.iter().map(|a| windows_to_wsl_path(a).unwrap_or_else(|| a.clone())));
+                cmd
+            }
+        }
+    }
+
+    /// Program as shown in logs and reproducibility scripts
+    pub fn display(&self) -> String {
+        match self {
+            ToolLocation::Native(path) => path.to_string_lossy().to_string(),
+            ToolLocation::Wsl(linux_path) => format!("wsl.exe -e {}", linux_path),
+        }
+    }
+}
+
+/// Find an NMRPipe tool by name.  Results are cached for the session.
+pub fn find_tool(name: &str) -> Option<ToolLocation> {
+    static FOUND: OnceLock<Mutex<HashMap<String, Option<ToolLocation>>>> = OnceLock::new();
+    let found = FOUND.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(hit) = found.lock().unwrap().get(name) {
+        return hit.clone();
+    }
+
+    let location = find_native(name).map(ToolLocation::Native).or_else(|| {
+        if cfg!(target_os = "windows") {
+            find_in_wsl(name).map(ToolLocation::Wsl)
+        } else {
+            None
+        }
+    });
+    match &location {
+        Some(loc) => log::info!("Found {} at {}", name, loc.display()),
+        None => log::info!("{} not found", name),
+    }
+    found.lock().unwrap().insert(name.to_string(), location.clone());
+    location
+}
+
+fn executable_name(name: &str) -> String {
+    if cfg!(target_os = "windows") {
+        format!("{}.exe", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Search PATH, NMRPipe environment variables and standard install folders.
+fn find_native(name: &str) -> Option<PathBuf> {
+    let exe = executable_name(name);
+
+    if let Some(path) = std::env::var_os("PATH") {
+        if let Some(hit) = std::env::split_paths(&path).map(|d| d.join(&exe)).find(|p| p.is_file()) {
+            return Some(hit);
+        }
+    }
+
+    // NMRPipe's init scripts set NMRBIN to the platform binary folder
+    if let Some(bin) = std::env::var_os("NMRBIN") {
+        let p = PathBuf::from(bin).join(&exe);
+        if p.is_file() {
+            return Some(p);
+        }
+    }
+    if let Some(base) = std::env::var_os("NMR_BASE") {
+        let base = PathBuf::from(base);
+        if let Some(hit) = search_install_root(&base, &exe) {
+            return Some(hit);
+        }
+    }
+
+    install_roots()
+        .iter()
+        .find_map(|root| search_install_root(root, &exe))
+}
+
+/// The user's home folder from the environment looked up by `var`:
+/// `HOME`, or on Windows, where that is normally unset, `USERPROFILE`
+fn home_dir(var: impl Fn(&str) -> Option<OsString>, windows: bool) -> Option<PathBuf> {
+    var("HOME")
+        .filter(|home| !home.is_empty())
+        .or_else(|| if windows { var("USERPROFILE") } else { None })
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Folders NMRPipe is commonly installed into on this platform
+fn install_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if let Some(home) = home_dir(|name| std::env::var_os(name), cfg!(windows)) {
+        roots.push(home.join("Documents").join("NMRpipe"));
+        roots.push(home.join("NMRPipe"));
+        roots.push(home.join("nmrpipe"));
+        if cfg!(target_os = "macos") {
+            roots.push(home.join("Applications").join("NMRPipe"));
+        }
+    }
+    if cfg!(target_os = "macos") {
+        roots.push(PathBuf::from("/Applications/NMRPipe"));
+    }
+    roots.push(PathBuf::from("/usr/local/nmrpipe"));
+    roots.push(PathBuf::from("/opt/nmrpipe"));
+    roots
+}
+
+/// Look for `exe` in `root/bin` and the platform's `root/nmrbin.*` folders.
+fn search_install_root(root: &Path, exe: &str) -> Option<PathBuf> {
+    let bin = root.join("bin").join(exe);
+    if bin.is_file() {
+        return Some(bin);
+    }
+    let mut nmrbins: Vec<PathBuf> = fs::read_dir(root)
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .map(is_platform_nmrbin)
+                .unwrap_or(false)
+        })
+        .collect();
+    // Newest OS build first (e.g. nmrbin.mac11_64 before nmrbin.mac)
+    nmrbins.sort();
+    nmrbins.reverse();
+    nmrbins.into_iter().map(|d| d.join(exe)).find(|p| p.is_file())
+}
+
+/// Whether an `nmrbin.*` folder holds binaries for this platform
+fn is_platform_nmrbin(name: &str) -> bool {
+    let Some(platform) = name.strip_prefix("nmrbin.") else {
+        return false;
+    };
+    if cfg!(target_os = "macos") {
+        platform.starts_with("mac")
+    } else {
+        platform.starts_with("linux")
+    }
+}
+
+/// Ask WSL for the tool: on the login shell's PATH, else in a home install.
+fn find_in_wsl(name: &str) -> Option<String> {
+    let script = format!(
+        "command -v {0} || ls -1 $HOME/NMRPipe/nmrbin.linux*/{0} $HOME/Documents/NMRpipe/nmrbin.linux*/{0} \
+         $HOME/nmrpipe/bin/{0} 2>/dev/null | head -n 1",
+        name
+    );
+    let output = Command::new("wsl.exe").args(["-e", "sh", "-lc", &script]).output().ok()?;
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    path.starts_with('/').then_some(path)
+}
+
+/// Translate a Windows path to the path WSL sees.
+///
+/// `C:\Users\me\data.jdf` → `/mnt/c/Users/me/data.jdf`, and
+/// `\\wsl$\Ubuntu\home\me` / `\\wsl.localhost\Ubuntu\home\me` → `/home/me`.
+/// Returns `None` for anything that is not an absolute Windows path.
+pub fn windows_to_wsl_path(arg: &str) -> Option<String> {
+    for prefix in [r"\\wsl$\", r"\\wsl.localhost\"] {
+        if let Some(rest) = arg.strip_prefix(prefix) {
+            // Drop the distribution name
+            let inner = rest.split_once('\\').map(|(_, p)| p).unwrap_or("");
+            return Some(format!("/{}", inner.replace('\\', "/")));
+        }
+    }
+
+    let bytes = arg.as_bytes();
+    if bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && (bytes[2] == b'\\' || bytes[2] == b'/') {
+        let drive = (bytes[0] as char).to_ascii_lowercase();
+        return Some(format!("/mnt/{}/{}", drive, arg[3..].replace('\\', "/")));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_windows_paths_translate_for_wsl() {
+        assert_eq!(
+            windows_to_wsl_path(r"C:\Users\me\data\sample.jdf").as_deref(),
+            Some("/mnt/c/Users/me/data/sample.jdf")
+        );
+        assert_eq!(windows_to_wsl_path("D:/nmr/out%03d.fid").as_deref(), Some("/mnt/d/nmr/out%03d.fid"));
+        assert_eq!(
+            windows_to_wsl_path(r"\\wsl$\Ubuntu\home\me\run1").as_deref(),
+            Some("/home/me/run1")
+        );
+        assert_eq!(
+            windows_to_wsl_path(r"\\wsl.localhost\Ubuntu\tmp\x.fid").as_deref(),
+            Some("/tmp/x.fid")
+        );
+        // Flags, values and Unix paths pass through untouched
+        for arg in ["-xN", "26214", "Complex", "/home/me/a.fid", "C:"] {
+            assert_eq!(windows_to_wsl_path(arg), None);
+        }
+
+        // User installs are found through USERPROFILE when HOME is unset,
+        // as it normally is on Windows
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(n, _)| *n == name).map(|(_, v)| OsString::from(v))
+        };
+        let profile = env(&[("USERPROFILE", r"C:\Users\me")]);
+        assert_eq!(home_dir(profile, true), Some(PathBuf::from(r"C:\Users\me")));
+        assert_eq!(home_dir(profile, false), None);
+        let both = env(&[("HOME", "/home/me"), ("USERPROFILE", r"C:\Users\me")]);
+        assert_eq!(home_dir(both, true), Some(PathBuf::from("/home/me")));
+        assert_eq!(home_dir(env(&[("HOME", "")]), false), None);
+    }
+}
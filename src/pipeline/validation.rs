@@ -0,0 +1,427 @@
+//! Validation of the built-in converters against NMRPipe.
+//!
+//! Converts one dataset twice — with the native Rust reader and with
+//! `bruk2pipe`/`delta2pipe` — then aligns the two results (integer point
+//! shift and least-squares scale, since the readers may drop a different
+//! number of group-delay points or scale the data differently) and reports
+//! the remaining RMS difference per dimension.  Used by the `--validate`
+//! command line mode and Help → Developer → Validate Conversion.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::data::bruker;
+use crate::data::jdf;
+use crate::data::spectrum::{AxisParams, SpectrumData, VendorFormat};
+use crate::error::{NmrError, NmrResult};
+use crate::gui::conversion_dialog::{ConversionMethod, ConversionSettings};
+use crate::log::reproducibility::ReproLog;
+use super::conversion;
+
+/// Relative RMS difference above which a dimension is reported as failing
+pub const DEFAULT_TOLERANCE: f64 = 1e-3;
+
+/// Largest point shift tried when aligning the two conversions
+const MAX_SHIFT: usize = 128;
+
+/// Comparison of one dimension of the two conversions
+#[derive(Debug, Clone)]
+pub struct DimensionReport {
+    /// "F2 (direct)", "F1 (indirect)" or "1D"
+    pub name: String,
+    pub builtin_axis: AxisParams,
+    pub nmrpipe_axis: AxisParams,
+    /// Points compared after alignment
+    pub compared: usize,
+    /// Points the built-in trace is shifted by to line up with NMRPipe's
+    pub shift: isize,
+    /// Least-squares factor mapping built-in onto NMRPipe intensities
+    pub scale: f64,
+    /// RMS of the aligned difference over RMS of the NMRPipe trace
+    pub relative_rms: f64,
+}
+
+impl DimensionReport {
+    /// Axis parameters that disagree by more than rounding
+    pub fn axis_mismatches(&self) -> Vec<String> {
+        let (a, b) = (&self.builtin_axis, &self.nmrpipe_axis);
+        let mut out = Vec::new();
+        let differs = |x: f64, y: f64| (x - y).abs() > 1e-3 * x.abs().max(y.abs()).max(1e-9);
+        if a.nucleus != b.nucleus {
+            out.push(format!("nucleus {} vs {}", a.nucleus, b.nucleus));
+        }
+        if differs(a.spectral_width_hz, b.spectral_width_hz) {
+            out.push(format!("SW {:.3} vs {:.3} Hz", a.spectral_width_hz, b.spectral_width_hz));
+        }
+        if differs(a.observe_freq_mhz, b.observe_freq_mhz) {
+            out.push(format!("OBS {:.4} vs {:.4} MHz", a.observe_freq_mhz, b.observe_freq_mhz));
+        }
+        out
+    }
+}
+
+/// Result of validating one dataset
+#[derive(Debug, Clone)]
+pub struct ValidationReport {
+    pub source: PathBuf,
+    pub vendor: VendorFormat,
+    pub builtin_method: String,
+    pub nmrpipe_method: String,
+    pub dimensions: Vec<DimensionReport>,
+}
+
+impl ValidationReport {
+    /// Every dimension within `tolerance` and with matching axis parameters
+    pub fn passed(&self, tolerance: f64) -> bool {
+        !self.dimensions.is_empty()
+            && self
+                .dimensions
+                .iter()
+                .all(|d| d.relative_rms <= tolerance && d.axis_mismatches().is_empty())
+    }
+
+    /// Human-readable summary, one line per dimension
+    pub fn to_text(&self, tolerance: f64) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "Validation of {} ({})", self.source.display(), self.vendor);
+        let _ = writeln!(out, "  built-in: {}", self.builtin_method);
+        let _ = writeln!(out, "  NMRPipe:  {}", self.nmrpipe_method);
+        for d in &self.dimensions {
+            let status = if d.relative_rms <= tolerance && d.axis_mismatches().is_empty() {
+                "OK  "
+            } else {
+                "FAIL"
+            };
+            let _ = writeln!(
+                out,
+                "  {} {:<14} {} pts  shift {:+}  scale {:.6}  RMS diff {:.3e}",
+                status, d.name, d.compared, d.shift, d.scale, d.relative_rms
+            );
+            for m in d.axis_mismatches() {
+                let _ = writeln!(out, "       {}", m);
+            }
+        }
+        let _ = writeln!(
+            out,
+            "Result: {} (tolerance {:.1e})",
+            if self.passed(tolerance) { "PASS" } else { "FAIL" },
+            tolerance
+        );
+        out
+    }
+}
+
+/// Text diff of the FDATA headers of two NMRPipe files, e.g. a built-in
+/// conversion against a reference one, with the number of differences
+pub fn header_diff(a: &Path, b: &Path) -> io::Result<(String, usize)> {
+    let read = |path: &Path| {
+        let mut file = fs::File::open(path)?;
+        nmrpipe_io::reader::read_fdata_header(&mut file)
+            .map(|(fdata, _)| fdata)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+    };
+    let (fa, fb) = (read(a)?, read(b)?);
+    let name = |path: &Path| path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let (mut name_a, name_b) = (name(a), name(b));
+    if name_a == name_b {
+        name_a = a.display().to_string();
+    }
+    let diffs = nmrpipe_core::diff::diff_headers(&fa, &fb);
+    Ok((nmrpipe_core::diff::format_header_diff(&diffs, &name_a, &name_b), diffs.len()))
+}
+
+/// Convert `path` with both backends and compare the results.
+///
+/// Only Bruker and JEOL have a built-in reader to validate; the matching
+/// NMRPipe tool must be installed.  NMRPipe output goes to a temporary
+/// folder that is removed afterwards.
+pub fn validate_conversion(path: &Path, base: &ConversionSettings) -> NmrResult<ValidationReport> {
+    let vendor = conversion::detect_format(path);
+    let tool_found = match vendor {
+        VendorFormat::Bruker => bruker::find_bruk2pipe().is_some(),
+        VendorFormat::Jeol => jdf::find_delta2pipe().is_some(),
+        _ => return Err(NmrError::NotApplicable(format!("No built-in reader to validate for {} data", vendor))),
+    };
+    if !tool_found {
+        return Err(NmrError::ToolMissing(converter_name(&vendor).to_string()));
+    }
+
+    let out_dir = std::env::temp_dir().join(format!("nmr-validate-{}", std::process::id()));
+    let convert = |method: ConversionMethod| {
+        let settings = ConversionSettings {
+            conversion_method: method,
+            output_dir: Some(out_dir.clone()),
+            ..base.clone()
+        };
+        conversion::load_spectrum(path, &mut ReproLog::new(), Some(&settings))
+    };
+    let builtin = convert(ConversionMethod::BuiltIn);
+    let nmrpipe = convert(ConversionMethod::NMRPipe);
+    let _ = fs::remove_dir_all(&out_dir);
+    let (builtin, nmrpipe) = (builtin?, nmrpipe?);
+    // The Bruker path falls back to the processed 1r reader when the native
+    // converter fails; comparing that with an FID says nothing useful
+    if !builtin.conversion_method_used.contains("native") {
+        return Err(NmrError::NotApplicable(format!(
+            "Native converter did not run (built-in result came from {}); nothing to validate",
+            builtin.conversion_method_used
+        )));
+    }
+    if builtin.is_frequency_domain != nmrpipe.is_frequency_domain {
+        let domain = |s: &SpectrumData| if s.is_frequency_domain { "frequency" } else { "time" };
+        return Err(NmrError::InvalidInput(format!(
+            "Domain differs: built-in {} domain, NMRPipe {} domain",
+            domain(&builtin),
+            domain(&nmrpipe)
+        )));
+    }
+    if builtin.is_2d() != nmrpipe.is_2d() {
+        return Err(NmrError::InvalidInput(format!(
+            "Dimensionality differs: built-in {:?}, NMRPipe {:?}",
+            builtin.dimensionality, nmrpipe.dimensionality
+        )));
+    }
+
+    Ok(ValidationReport {
+        source: path.to_path_buf(),
+        vendor,
+        builtin_method: builtin.conversion_method_used.clone(),
+        nmrpipe_method: nmrpipe.conversion_method_used.clone(),
+        dimensions: compare_spectra(&builtin, &nmrpipe)?,
+    })
+}
+
+fn converter_name(vendor: &VendorFormat) -> &'static str {
+    match vendor {
+        VendorFormat::Jeol => "delta2pipe",
+        _ => "bruk2pipe",
+    }
+}
+
+/// Per-dimension comparison of two conversions of the same data.
+///
+/// 1D data is compared point by point (real and imaginary).  For 2D data
+/// F2 compares the rows, aligned with one shift and scale for the whole
+/// matrix, and F1 compares the columns (after the F2 shift), with their own
+/// shift, which shows errors in the indirect dimension (missing, duplicated
+/// or reordered rows).
+///
+/// Fails when the spectra cover no common ppm range or no points line up.
+pub fn compare_spectra(builtin: &SpectrumData, nmrpipe: &SpectrumData) -> NmrResult<Vec<DimensionReport>> {
+    let axis = |s: &SpectrumData, i: usize| s.axes.get(i).cloned().unwrap_or_default();
+    if builtin.is_frequency_domain && nmrpipe.is_frequency_domain {
+        let ppm_range = |a: &AxisParams| {
+            let (first, last) = (a.index_to_ppm(0), a.index_to_ppm(a.num_points.saturating_sub(1)));
+            (first.min(last), first.max(last))
+        };
+        let disjoint = builtin.axes.iter().zip(&nmrpipe.axes).any(|(a, b)| {
+            let ((a_lo, a_hi), (b_lo, b_hi)) = (ppm_range(a), ppm_range(b));
+            a.num_points > 0 && b.num_points > 0 && (a_hi < b_lo || b_hi < a_lo)
+        });
+        if disjoint {
+            return Err(no_overlap());
+        }
+    }
+
+    if !builtin.is_2d() || !nmrpipe.is_2d() {
+        // Align on the real part only; the imaginary half follows it
+        let shift = best_shift(&builtin.real, &nmrpipe.real);
+        let (compared, scale, relative_rms) = aligned_difference(
+            &[(&builtin.real, &nmrpipe.real), (&builtin.imag, &nmrpipe.imag)],
+            shift,
+        )?;
+        return Ok(vec![DimensionReport {
+            name: "1D".to_string(),
+            builtin_axis: axis(builtin, 0),
+            nmrpipe_axis: axis(nmrpipe, 0),
+            compared,
+            shift,
+            scale,
+            relative_rms,
+        }]);
+    }
+
+    let rows = builtin.data_2d.len().min(nmrpipe.data_2d.len());
+    let pairs: Vec<(&Vec<f64>, &Vec<f64>)> =
+        builtin.data_2d.iter().zip(&nmrpipe.data_2d).take(rows).collect();
+    // Align on the strongest row
+    let strongest = (0..rows)
+        .max_by(|&i, &j| energy(pairs[i].1).total_cmp(&energy(pairs[j].1)))
+        .unwrap_or(0);
+    let shift = pairs.get(strongest).map_or(0, |(a, b)| best_shift(a, b));
+    let (compared, scale, relative_rms) = aligned_difference(&pairs, shift)?;
+
+    // F1 traces: NMRPipe column j against built-in column j + shift
+    let column = |m: &[Vec<f64>], j: usize| -> Option<Vec<f64>> { m.iter().map(|r| r.get(j).copied()).collect() };
+    let columns: Vec<(Vec<f64>, Vec<f64>)> = (0..nmrpipe.data_2d.first().map_or(0, |r| r.len()))
+        .filter_map(|j| {
+            let i = usize::try_from(j as isize + shift).ok()?;
+            Some((column(&builtin.data_2d, i)?, column(&nmrpipe.data_2d, j)?))
+        })
+        .collect();
+    let strongest = (0..columns.len())
+        .max_by(|&i, &j| energy(&columns[i].1).total_cmp(&energy(&columns[j].1)))
+        .unwrap_or(0);
+    let f1_shift = columns.get(strongest).map_or(0, |(a, b)| best_shift(a, b));
+    let (f1_compared, f1_scale, f1_rms) = aligned_difference(&columns, f1_shift)?;
+
+    Ok(vec![
+        DimensionReport {
+            name: "F2 (direct)".to_string(),
+            builtin_axis: axis(builtin, 0),
+            nmrpipe_axis: axis(nmrpipe, 0),
+            compared,
+            shift,
+            scale,
+            relative_rms,
+        },
+        DimensionReport {
+            name: "F1 (indirect)".to_string(),
+            builtin_axis: axis(builtin, 1),
+            nmrpipe_axis: axis(nmrpipe, 1),
+            compared: f1_compared,
+            shift: f1_shift,
+            scale: f1_scale,
+            relative_rms: f1_rms,
+        },
+    ])
+}
+
+fn energy(v: &[f64]) -> f64 {
+    v.iter().map(|x| x * x).sum()
+}
+
+/// Shift `s` (|s| ≤ MAX_SHIFT) maximising the normalised correlation of
+/// `a[i + s]` with `b[i]`.
+fn best_shift(a: &[f64], b: &[f64]) -> isize {
+    let max = MAX_SHIFT.min(a.len().min(b.len()) / 4) as isize;
+    let mut best = (0, f64::MIN);
+    for s in -max..=max {
+        let mut dot = 0.0;
+        let (mut ea, mut eb) = (0.0, 0.0);
+        for (i, &y) in b.iter().enumerate() {
+            let Some(&x) = usize::try_from(i as isize + s).ok().and_then(|j| a.get(j)) else {
+                continue;
+            };
+            dot += x * y;
+            ea += x * x;
+            eb += y * y;
+        }
+        if ea > 0.0 && eb > 0.0 {
+            // Sign is absorbed by the scale factor
+            let corr = dot.abs() / (ea * eb).sqrt();
+            if corr > best.1 + 1e-12 {
+                best = (s, corr);
+            }
+        }
+    }
+    best.0
+}
+
+fn no_overlap() -> NmrError {
+    NmrError::InvalidInput("spectra do not overlap".to_string())
+}
+
+/// Points compared, least-squares scale of `a` onto `b`, and relative RMS
+/// of `b - scale·a` over all trace pairs, with `a` shifted by `shift`.
+fn aligned_difference<A: AsRef<[f64]>, B: AsRef<[f64]>>(pairs: &[(A, B)], shift: isize) -> NmrResult<(usize, f64, f64)> {
+    let overlap = |a: &[f64], b: &[f64]| -> Vec<(f64, f64)> {
+        b.iter()
+            .enumerate()
+            .filter_map(|(i, &y)| {
+                let j = usize::try_from(i as isize + shift).ok()?;
+                a.get(j).map(|&x| (x, y))
+            })
+            .collect()
+    };
+    let points: Vec<(f64, f64)> = pairs
+        .iter()
+        .flat_map(|(a, b)| overlap(a.as_ref(), b.as_ref()))
+        .collect();
+
+    let (ab, aa, bb) = points
+        .iter()
+        .fold((0.0, 0.0, 0.0), |(ab, aa, bb), (x, y)| (ab + x * y, aa + x * x, bb + y * y));
+    if points.is_empty() {
+        return Err(no_overlap());
+    }
+    if bb == 0.0 {
+        let identical = points.iter().all(|(x, y)| x == y);
+        return Ok((points.len(), 1.0, if identical { 0.0 } else { f64::INFINITY }));
+    }
+    let scale = if aa > 0.0 { ab / aa } else { 0.0 };
+    let residual: f64 = points.iter().map(|(x, y)| (y - scale * x).powi(2)).sum();
+    Ok((points.len(), scale, (residual / bb).sqrt()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation_aligns_shift_and_scale() {
+        use crate::data::spectrum::SpectrumData;
+
+        let fid: Vec<f64> = (0..512)
+            .map(|i| (i as f64 * 0.3).cos() * (-(i as f64) / 150.0).exp())
+            .collect();
+        let builtin = SpectrumData { real: fid.clone(), ..Default::default() };
+        // NMRPipe dropped three more group-delay points and scaled by -2
+        let mut nmrpipe = SpectrumData {
+            real: fid[3..].iter().map(|v| -2.0 * v).collect(),
+            ..Default::default()
+        };
+
+        let dims = compare_spectra(&builtin, &nmrpipe).unwrap();
+        assert_eq!(dims.len(), 1);
+        assert_eq!(dims[0].shift, 3);
+        assert!((dims[0].scale + 2.0).abs() < 1e-9);
+        assert!(dims[0].relative_rms < 1e-9);
+
+        nmrpipe.real[100] += 1.0;
+        let dims = compare_spectra(&builtin, &nmrpipe).unwrap();
+        assert!(dims[0].relative_rms > DEFAULT_TOLERANCE);
+
+        // Spectra over different ppm ranges, or with nothing to line up, fail
+        let spectrum = |reference_ppm: f64| SpectrumData {
+            real: fid.clone(),
+            is_frequency_domain: true,
+            axes: vec![AxisParams {
+                num_points: fid.len(),
+                spectral_width_hz: 4000.0,
+                observe_freq_mhz: 400.0,
+                reference_ppm,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert!(compare_spectra(&spectrum(10.0), &spectrum(12.0)).is_ok());
+        let e = compare_spectra(&spectrum(10.0), &spectrum(30.0)).unwrap_err();
+        assert_eq!(e.to_string(), "spectra do not overlap");
+        let empty = SpectrumData::default();
+        assert!(matches!(compare_spectra(&empty, &empty), Err(NmrError::InvalidInput(_))));
+
+        // 2D: two rows of equal energy swapped only show up down the columns
+        let rows: Vec<Vec<f64>> = (0..32)
+            .map(|r| (0..128).map(|i| ((i as f64) * 0.2 + r as f64 * 0.7).cos()).collect())
+            .collect();
+        let two_d = |data_2d: Vec<Vec<f64>>| SpectrumData {
+            dimensionality: crate::data::spectrum::Dimensionality::TwoD,
+            data_2d,
+            ..Default::default()
+        };
+        let mut swapped = rows.clone();
+        swapped.swap(10, 11);
+        let dims = compare_spectra(&two_d(rows.clone()), &two_d(rows.clone())).unwrap();
+        assert!(dims[1].relative_rms < 1e-9);
+        let dims = compare_spectra(&two_d(swapped), &two_d(rows)).unwrap();
+        assert_eq!(dims[1].name, "F1 (indirect)");
+        assert!(dims[1].relative_rms > DEFAULT_TOLERANCE);
+
+        let jcamp = Path::new("test-files/does-not-matter.jdx");
+        let settings = crate::gui::conversion_dialog::ConversionSettings::default();
+        assert!(validate_conversion(jcamp, &settings).is_err());
+    }
+}
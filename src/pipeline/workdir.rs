@@ -0,0 +1,176 @@
+//! Scratch/work directory for conversion outputs.
+//!
+//! External converters (delta2pipe, bruk2pipe, var2pipe) and the batch
+//! converter write NMRPipe files somewhere on disk.  `WorkDir` decides
+//! where: next to the source data (the historical behaviour) or in a
+//! unique per-session subfolder of a scratch directory, and removes what
+//! this session created on exit unless the user chose to keep it.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Where conversion outputs are written.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum WorkDirLocation {
+    /// `<data parent>/<stem>_nmrpipe`, alongside the source data
+    NextToData,
+    /// A per-session folder inside the system temp directory
+    SystemTemp,
+    /// A per-session folder inside a user-chosen directory
+    Custom(PathBuf),
+}
+
+impl WorkDirLocation {
+    pub fn label(&self) -> &str {
+        match self {
+            WorkDirLocation::NextToData => "Next to the data",
+            WorkDirLocation::SystemTemp => "System temp directory",
+            WorkDirLocation::Custom(_) => "Custom directory",
+        }
+    }
+}
+
+/// User-configurable work directory policy (persisted in preferences).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkDirSettings {
+    pub location: WorkDirLocation,
+    /// Keep converted intermediates when the application exits
+    pub keep_intermediates: bool,
+}
+
+impl Default for WorkDirSettings {
+    fn default() -> Self {
+        Self {
+            location: WorkDirLocation::NextToData,
+            keep_intermediates: true,
+        }
+    }
+}
+
+impl WorkDirSettings {
+    /// Settings of the read-only viewer: nothing is written next to the
+    /// data, and the converted files go with the session
+    pub fn transient() -> Self {
+        Self { location: WorkDirLocation::SystemTemp, keep_intermediates: false }
+    }
+}
+
+/// Per-session work directory manager.
+#[derive(Debug)]
+pub struct WorkDir {
+    pub settings: WorkDirSettings,
+    /// Unique name of this session's subfolder (`session-<time>-<id>`)
+    session_name: String,
+    /// Output directories this session created (and may clean up)
+    created: Vec<PathBuf>,
+}
+
+impl WorkDir {
+    pub fn new(settings: WorkDirSettings) -> Self {
+        let id = uuid::Uuid::new_v4().to_string();
+        let session_name = format!(
+            "session-{}-{}",
+            chrono::Local::now().format("%Y%m%d-%H%M%S"),
+            &id[..8]
+        );
+        Self {
+            settings,
+            session_name,
+            created: Vec::new(),
+        }
+    }
+
+    /// This session's scratch folder, or `None` when writing next to the data.
+    pub fn session_dir(&self) -> Option<PathBuf> {
+        let root = match &self.settings.location {
+            WorkDirLocation::NextToData => return None,
+            WorkDirLocation::SystemTemp => std::env::temp_dir().join("nmr_gui"),
+            WorkDirLocation::Custom(dir) => dir.clone(),
+        };
+        Some(root.join(&self.session_name))
+    }
+
+    /// Output directory for converting `source`.
+    ///
+    /// Records the directory if it does not exist yet, so that cleanup only
+    /// ever removes folders this session created.
+    pub fn output_dir_for(&mut self, source: &Path) -> PathBuf {
+        let dir = match self.session_dir() {
+            Some(session) => session.join(output_dir_name(source)),
+            None => super::conversion::conversion_output_dir(source),
+        };
+        if !dir.exists() && !self.created.contains(&dir) {
+            self.created.push(dir.clone());
+        }
+        dir
+    }
+
+    /// Folder a zip archive is extracted into: `<stem>_unzipped` in the
+    /// session folder, or next to the archive.  Recorded for cleanup like
+    /// conversion outputs.
+    pub fn extract_dir_for(&mut self, archive: &Path) -> PathBuf {
+        let stem = archive
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "archive".to_string());
+        let name = format!("{}_unzipped", stem);
+        let dir = match self.session_dir() {
+            Some(session) => session.join(name),
+            None => archive.parent().unwrap_or(Path::new(".")).join(name),
+        };
+        if !dir.exists() && !self.created.contains(&dir) {
+            self.created.push(dir.clone());
+        }
+        dir
+    }
+
+    /// Remove this session's outputs unless intermediates are kept.
+    pub fn cleanup(&mut self) -> io::Result<()> {
+        if self.settings.keep_intermediates {
+            return Ok(());
+        }
+        for dir in self.created.drain(..) {
+            if dir.exists() {
+                fs::remove_dir_all(&dir)?;
+            }
+        }
+        if let Some(session) = self.session_dir() {
+            if session.exists() {
+                fs::remove_dir_all(&session)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `<stem>_nmrpipe`, matching the next-to-data naming.
+fn output_dir_name(source: &Path) -> String {
+    let stem = source
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "output".to_string());
+    format!("{}_nmrpipe", stem)
+}
+
+/// Open the platform file manager at `path` (a file is revealed in its folder).
+pub fn reveal_in_file_manager(path: &Path) -> io::Result<()> {
+    let target = if path.is_file() {
+        path.parent().unwrap_or(path)
+    } else {
+        path
+    };
+
+    let program = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+
+    std::process::Command::new(program).arg(target).spawn()?;
+    Ok(())
+}